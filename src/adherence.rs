@@ -0,0 +1,112 @@
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use crate::config::{Config, Goals};
+use crate::db::{Rollup, TargetAdjustment};
+use crate::program;
+
+/// How far a day can miss a goal and still count as on target.
+pub const TOLERANCE: f64 = 0.1;
+
+/// One logged day, judged against the goals in effect that day.
+#[derive(Debug, Serialize)]
+pub struct Day {
+    pub date: String,
+    pub calories: f64,
+    pub protein: f64,
+    pub goals: Goals,
+    /// The refeed or diet break scheduled for the day, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scheduled: Option<String>,
+    pub on_target: bool,
+    /// Logged under the incomplete-day threshold
+    pub incomplete: bool,
+}
+
+/// Calories within `TOLERANCE` of the goal either way, and protein no more
+/// than `TOLERANCE` short of it. Goals that aren't set always pass.
+pub fn judge(calories: f64, protein: f64, goals: &Goals) -> bool {
+    goals
+        .calories
+        .is_none_or(|t| (calories - t).abs() <= t * TOLERANCE)
+        && goals
+            .protein
+            .is_none_or(|t| protein >= t * (1.0 - TOLERANCE))
+}
+
+/// Judge each day of `days` (daily rollups) against its own goals, so a
+/// scheduled refeed is held to the refeed's targets.
+pub fn evaluate(config: &Config, adjustments: &[TargetAdjustment], days: &[Rollup]) -> Vec<Day> {
+    days.iter()
+        .filter_map(|day| {
+            let date = NaiveDate::parse_from_str(&day.period, "%Y-%m-%d").ok()?;
+            let (goals, scheduled) = program::goals_with(config, adjustments, date);
+            Some(Day {
+                date: day.period.clone(),
+                calories: day.calories,
+                protein: day.protein,
+                on_target: judge(day.calories, day.protein, &goals),
+                incomplete: day.incomplete > 0,
+                goals,
+                scheduled,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(date: &str, calories: f64) -> Rollup {
+        Rollup {
+            period: date.to_string(),
+            days: 1,
+            incomplete: 0,
+            protein: 150.0,
+            fat: 0.0,
+            carbs: 0.0,
+            calories,
+            micros: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_scheduled_days_use_their_own_goals() {
+        let config = Config::parse(
+            "[goals]\ncalories = 2000.0\nprotein = 140.0\n\n\
+             [[schedule]]\nname = \"refeed\"\nweekdays = [\"sat\"]\ncalories = 2800.0\n\n\
+             [[schedule]]\nname = \"diet break\"\nfrom = \"2026-10-12\"\nto = \"2026-10-13\"\ncalories = 2400.0\n",
+        )
+        .unwrap();
+        let days = evaluate(
+            &config,
+            &[],
+            &[
+                day("2026-10-09", 2050.0),
+                day("2026-10-10", 2750.0),
+                day("2026-10-11", 2750.0),
+                day("2026-10-12", 2450.0),
+                day("2026-10-14", 2450.0),
+            ],
+        );
+        let judged: Vec<(Option<&str>, bool)> = days
+            .iter()
+            .map(|d| (d.scheduled.as_deref(), d.on_target))
+            .collect();
+        assert_eq!(
+            judged,
+            [
+                (None, true),
+                (Some("refeed"), true),
+                (None, false),
+                (Some("diet break"), true),
+                (None, false),
+            ]
+        );
+        assert_eq!(days[1].goals.protein, Some(140.0));
+
+        assert!(Config::parse("[[schedule]]\nname = \"x\"\nweekdays = [\"funday\"]\n").is_err());
+        assert!(Config::parse("[[schedule]]\nname = \"x\"\ncalories = 1.0\n").is_err());
+    }
+}
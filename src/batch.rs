@@ -0,0 +1,325 @@
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::db::{Database, DeleteMode, LogOptions};
+use crate::food;
+use crate::logging;
+
+/// One change in a batch. Scripts give them one per line in CLI syntax
+/// (`log chicken 200g --date 2024-03-01`, `unlog 12`), or as a JSON array of
+/// objects tagged with `op` (`{"op": "unlog", "id": 12}`).
+#[derive(Debug, Subcommand, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Op {
+    /// Log food, e.g. "chicken breast 200g"
+    Log {
+        #[arg(required = true)]
+        #[serde(deserialize_with = "words")]
+        food: Vec<String>,
+        #[arg(long)]
+        date: Option<String>,
+    },
+    /// Delete a log entry by ID
+    Unlog { id: i64 },
+    /// Change a log entry's amount or macros
+    #[serde(alias = "edit-log")]
+    EditLog {
+        id: i64,
+        #[arg(long)]
+        amount: Option<String>,
+        #[arg(long)]
+        protein: Option<f64>,
+        #[arg(long)]
+        fat: Option<f64>,
+        #[arg(long)]
+        carbs: Option<f64>,
+    },
+    /// Delete a food (it stays in the trash)
+    Delete {
+        name: String,
+        #[arg(long, conflicts_with = "keep_history")]
+        #[serde(default)]
+        cascade: bool,
+        #[arg(long)]
+        #[serde(default)]
+        keep_history: bool,
+    },
+    /// Log water, e.g. "500ml"
+    Water {
+        #[arg(required = true)]
+        #[serde(deserialize_with = "words")]
+        amount: Vec<String>,
+        #[arg(long)]
+        date: Option<String>,
+    },
+    /// Record a weigh-in, e.g. "82.4kg"
+    Weight {
+        #[arg(required = true)]
+        #[serde(deserialize_with = "words")]
+        amount: Vec<String>,
+        #[arg(long)]
+        date: Option<String>,
+    },
+}
+
+/// Free text in JSON ("chicken 200g"), split like the command line would be.
+fn words<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    Ok(text.split_whitespace().map(str::to_string).collect())
+}
+
+#[derive(Parser)]
+#[command(no_binary_name = true, disable_help_flag = true)]
+struct Line {
+    #[command(subcommand)]
+    op: Op,
+}
+
+/// An operation and where it came from, for error messages.
+#[derive(Debug)]
+pub struct Step {
+    pub label: String,
+    pub op: Op,
+}
+
+/// What one step did.
+#[derive(Debug, Serialize)]
+pub struct Applied {
+    pub step: String,
+    pub summary: String,
+    pub result: serde_json::Value,
+}
+
+/// Read a script: a JSON array of operations, or one command per line with
+/// blank lines and `#` comments ignored.
+pub fn parse(input: &str) -> Result<Vec<Step>> {
+    if input.trim_start().starts_with('[') {
+        let ops: Vec<Op> = serde_json::from_str(input)
+            .map_err(|e| anyhow::anyhow!("Invalid JSON operations: {}", e))?;
+        return Ok(ops
+            .into_iter()
+            .enumerate()
+            .map(|(i, op)| Step {
+                label: format!("operation {}", i + 1),
+                op,
+            })
+            .collect());
+    }
+
+    let mut steps = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let label = format!("line {}", i + 1);
+        let words =
+            shlex::split(line).ok_or_else(|| anyhow::anyhow!("{}: unbalanced quotes", label))?;
+        let op = Line::try_parse_from(words)
+            .map_err(|e| {
+                let message = e.to_string();
+                let first = message.lines().next().unwrap_or_default();
+                anyhow::anyhow!("{}: {}", label, first.trim_start_matches("error: "))
+            })?
+            .op;
+        steps.push(Step { label, op });
+    }
+    Ok(steps)
+}
+
+/// Apply every step in one transaction. The first failure rolls all of them
+/// back and is reported with its step.
+pub fn run(db: &Database, steps: Vec<Step>, strict: bool) -> Result<Vec<Applied>> {
+    // A food deleted with its history kept is still pointed at by its log
+    // entries; the check can't be turned off once the transaction is open
+    let keeps_history = steps.iter().any(|step| {
+        matches!(
+            step.op,
+            Op::Delete {
+                keep_history: true,
+                ..
+            }
+        )
+    });
+    if keeps_history {
+        db.without_foreign_keys(|| run_atomically(db, steps, strict))
+    } else {
+        run_atomically(db, steps, strict)
+    }
+}
+
+fn run_atomically(db: &Database, steps: Vec<Step>, strict: bool) -> Result<Vec<Applied>> {
+    db.atomically(|db| {
+        steps
+            .into_iter()
+            .map(|step| {
+                let (summary, result) = apply(db, step.op, strict)
+                    .map_err(|e| anyhow::anyhow!("{}: {}; nothing was changed", step.label, e))?;
+                Ok(Applied {
+                    step: step.label,
+                    summary,
+                    result,
+                })
+            })
+            .collect()
+    })
+}
+
+fn apply(db: &Database, op: Op, strict: bool) -> Result<(String, serde_json::Value)> {
+    Ok(match op {
+        Op::Log { food, date } => {
+            let opts = LogOptions {
+                date: date.as_deref(),
+                strict,
+                ..Default::default()
+            };
+            let entry = logging::parse_and_log_with(db, &food.join(" "), &opts)?;
+            (
+                format!(
+                    "logged {} {} ({:.0} kcal) as #{}",
+                    entry.amount,
+                    entry.food_name,
+                    entry.calories,
+                    entry.id.unwrap_or_default()
+                ),
+                serde_json::to_value(entry)?,
+            )
+        }
+        Op::Unlog { id } => {
+            let entry = db.delete_log_entry(id)?;
+            (
+                format!("unlogged #{} ({} {})", id, entry.amount, entry.food_name),
+                serde_json::to_value(entry)?,
+            )
+        }
+        Op::EditLog {
+            id,
+            amount,
+            protein,
+            fat,
+            carbs,
+        } => {
+            let entry = db.edit_log_entry(id, amount, protein, fat, carbs)?;
+            (
+                format!(
+                    "updated #{} to {} {} ({:.0} kcal)",
+                    id, entry.amount, entry.food_name, entry.calories
+                ),
+                serde_json::to_value(entry)?,
+            )
+        }
+        Op::Delete {
+            name,
+            cascade,
+            keep_history,
+        } => {
+            let mode = if cascade {
+                DeleteMode::Cascade
+            } else if keep_history {
+                DeleteMode::KeepHistory
+            } else {
+                DeleteMode::Refuse
+            };
+            db.delete_food(&name, mode)?;
+            (
+                format!("deleted {}", name),
+                serde_json::json!({ "deleted": name }),
+            )
+        }
+        Op::Water { amount, date } => {
+            let input = amount.join(" ");
+            let ml = food::parse_water_ml(&input)
+                .ok_or_else(|| anyhow::anyhow!("Could not parse water amount: '{}'", input))?;
+            let entry = db.log_water(ml, date.as_deref())?;
+            (
+                format!("logged {:.0}ml water on {}", entry.amount_ml, entry.date),
+                serde_json::to_value(entry)?,
+            )
+        }
+        Op::Weight { amount, date } => {
+            let input = amount.join(" ");
+            let kg = food::parse_weight_kg(&input)
+                .ok_or_else(|| anyhow::anyhow!("Could not parse weight: '{}'", input))?;
+            let entry = db.log_weight(kg, date.as_deref())?;
+            (
+                format!("weighed in {:.1}kg on {}", entry.kg, entry.date),
+                serde_json::to_value(entry)?,
+            )
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::food::Food;
+
+    fn test_db() -> Database {
+        let db = Database::open_in_memory().unwrap();
+        db.add_food(&Food::new("Ribeye", 26.0, 15.0, 0.0, 250.0, "100g", vec![]))
+            .unwrap();
+        db
+    }
+
+    #[test]
+    fn test_parse_lines_and_json() {
+        let steps = parse(
+            "# fix up Tuesday\n\
+             log ribeye 200g --date 2024-03-05\n\
+             \n\
+             edit-log 4 --amount 150g\n\
+             delete \"old shake\" --cascade\n",
+        )
+        .unwrap();
+        let labels: Vec<&str> = steps.iter().map(|s| s.label.as_str()).collect();
+        assert_eq!(labels, ["line 2", "line 4", "line 5"]);
+        assert!(matches!(
+            &steps[0].op,
+            Op::Log { food, date } if food == &["ribeye", "200g"] && date.as_deref() == Some("2024-03-05")
+        ));
+        assert!(
+            matches!(&steps[2].op, Op::Delete { name, cascade: true, .. } if name == "old shake")
+        );
+
+        let steps = parse(
+            r#"[{"op": "log", "food": "ribeye 200g"}, {"op": "edit_log", "id": 4, "protein": 30}]"#,
+        )
+        .unwrap();
+        assert_eq!(steps[1].label, "operation 2");
+        assert!(matches!(&steps[0].op, Op::Log { food, .. } if food.len() == 2));
+
+        let err = parse("log ribeye\nunlog twelve").unwrap_err().to_string();
+        assert!(err.starts_with("line 2: "), "{}", err);
+        assert!(parse(r#"[{"op": "log", "food": "x", "extra": 1}]"#).is_err());
+        assert!(parse("log \"ribeye").is_err());
+    }
+
+    #[test]
+    fn test_run_is_all_or_nothing() {
+        let db = test_db();
+        let steps = parse("log ribeye 200g\nwater 500ml\nunlog 999").unwrap();
+        let err = run(&db, steps, false).unwrap_err().to_string();
+        assert_eq!(err, "line 3: Log entry #999 not found; nothing was changed");
+        assert!(db.get_history(1).unwrap().is_empty());
+        assert_eq!(db.get_today_water().unwrap().total_ml, 0.0);
+
+        let steps = parse("log ribeye 200g\nlog ribeye 200g\nweight 82.4kg").unwrap();
+        let applied = run(&db, steps, false).unwrap();
+        assert_eq!(applied.len(), 3);
+        assert_eq!(applied[0].result["calories"], 500.0);
+        assert_eq!(db.get_history(1).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_run_delete_keep_history() {
+        let db = test_db();
+        let steps = parse("log ribeye 200g\ndelete ribeye --keep-history").unwrap();
+        let applied = run(&db, steps, false).unwrap();
+        assert_eq!(applied[1].summary, "deleted ribeye");
+        assert!(db.get_food_by_name("ribeye").unwrap().is_none());
+        let history = db.get_history(1).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].calories, 500.0);
+    }
+}
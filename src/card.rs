@@ -0,0 +1,264 @@
+use crate::db::{FoodCard, FoodStats};
+use crate::food::{parse_grams, Macros};
+
+/// A label-style card for `chomp show`: the food's values per serving and
+/// per each other useful amount, then what it's linked to and how it's used.
+pub fn render(card: &FoodCard) -> String {
+    let food = &card.food;
+    let mut lines = vec![
+        food.name.clone(),
+        "─".repeat(food.name.chars().count().max(30)),
+    ];
+
+    // Serving variants: the serving itself, the default amount, 100g, and
+    // one of each of the food's own units
+    let mut columns: Vec<(String, Macros)> = Vec::new();
+    let mut variant = |amount: &str| {
+        if columns.iter().any(|(label, _)| label == amount) {
+            return;
+        }
+        if let Some(macros) = food.calculate(amount) {
+            columns.push((amount.to_string(), macros));
+        }
+    };
+    variant(&food.serving);
+    if let Some(amount) = &food.default_amount {
+        variant(amount);
+    }
+    if parse_grams(&food.serving).is_some() {
+        variant("100g");
+    }
+    for unit in food.units.keys() {
+        variant(&format!("1 {}", unit));
+    }
+
+    let width = columns
+        .iter()
+        .map(|(label, _)| label.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(8);
+    let row = |name: &str, cells: Vec<String>| {
+        let cells: Vec<String> = cells
+            .iter()
+            .map(|c| format!("{:>width$}", c, width = width))
+            .collect();
+        format!("{:<10}{}", name, cells.join("  "))
+    };
+    lines.push(row(
+        "per",
+        columns.iter().map(|(label, _)| label.clone()).collect(),
+    ));
+    let grams = |f: fn(&Macros) -> f64| -> Vec<String> {
+        columns
+            .iter()
+            .map(|(_, m)| format!("{:.1}g", f(m)))
+            .collect()
+    };
+    lines.push(row(
+        "Calories",
+        columns
+            .iter()
+            .map(|(_, m)| format!("{:.0}", m.calories))
+            .collect(),
+    ));
+    lines.push(row("Protein", grams(|m| m.protein)));
+    lines.push(row("Fat", grams(|m| m.fat)));
+    lines.push(row("Carbs", grams(|m| m.carbs)));
+    if food.fiber > 0.0 {
+        lines.push(row("Fiber", grams(|m| m.fiber)));
+    }
+    // Shown only when known, as labels and imports often leave them out
+    if food.micros.sugar.is_some() {
+        lines.push(row("Sugar", grams(|m| m.micros.sugar.unwrap_or_default())));
+    }
+    if food.micros.saturated_fat.is_some() {
+        lines.push(row(
+            "Sat. fat",
+            grams(|m| m.micros.saturated_fat.unwrap_or_default()),
+        ));
+    }
+    if food.micros.sodium.is_some() {
+        lines.push(row(
+            "Sodium",
+            columns
+                .iter()
+                .map(|(_, m)| format!("{:.0}mg", m.micros.sodium.unwrap_or_default()))
+                .collect(),
+        ));
+    }
+    lines.push(String::new());
+
+    if !food.aliases.is_empty() {
+        lines.push(format!("Aliases: {}", food.aliases.join(", ")));
+    }
+    if !card.tags.is_empty() {
+        lines.push(format!("Tags: {}", card.tags.join(", ")));
+    }
+    if !card.components.is_empty() {
+        let parts: Vec<String> = card
+            .components
+            .iter()
+            .map(|(name, amount)| format!("{} {}", amount, name))
+            .collect();
+        lines.push(format!("Made of: {}", parts.join(" + ")));
+    }
+    if !card.used_in.is_empty() {
+        lines.push(format!("Used in: {}", card.used_in.join(", ")));
+    }
+    if let Some(source) = &card.source {
+        lines.push(format!("Source: {}", source));
+    }
+    lines.push(match (&card.last_logged, &card.first_logged) {
+        (Some(last), Some(first)) => format!(
+            "Logged {} time{} since {}, last on {}{}",
+            card.times_logged,
+            if card.times_logged == 1 { "" } else { "s" },
+            first,
+            last,
+            card.last_amount
+                .as_deref()
+                .map(|a| format!(" ({})", a))
+                .unwrap_or_default()
+        ),
+        _ => "Never logged".to_string(),
+    });
+    lines.join("\n")
+}
+
+/// `chomp food stats`: how often, how much, and when a food is logged.
+pub fn render_stats(stats: &FoodStats) -> String {
+    let mut lines = vec![
+        stats.name.clone(),
+        "─".repeat(stats.name.chars().count().max(30)),
+    ];
+    let (Some(first), Some(last)) = (&stats.first_logged, &stats.last_logged) else {
+        lines.push("Never logged".to_string());
+        return lines.join("\n");
+    };
+    let plural = |n: i64| if n == 1 { "" } else { "s" };
+    lines.push(format!(
+        "Logged {} time{} on {} day{}, {} to {}",
+        stats.times_logged,
+        plural(stats.times_logged),
+        stats.days_logged,
+        plural(stats.days_logged),
+        first,
+        last
+    ));
+    lines.push(format!("About {:.1} a week", stats.per_week));
+    if let Some(amount) = &stats.usual_amount {
+        lines.push(format!(
+            "Usual amount: {}{}",
+            amount,
+            stats
+                .average_grams
+                .map(|g| format!(" (average {:.0}g)", g))
+                .unwrap_or_default()
+        ));
+    }
+    let m = &stats.average;
+    lines.push(format!(
+        "Per entry: {:.0} kcal, {:.0}p/{:.0}f/{:.0}c",
+        m.calories, m.protein, m.fat, m.carbs
+    ));
+    if let Some(hour) = stats.usual_hour {
+        lines.push(format!(
+            "Usually logged: {:02}:00–{:02}:00",
+            hour,
+            (hour + 1) % 24
+        ));
+    }
+    let (recent, before) = (stats.last_30_days, stats.previous_30_days);
+    lines.push(format!(
+        "Last 30 days: {} ({})",
+        recent,
+        match recent.cmp(&before) {
+            std::cmp::Ordering::Greater => format!("up from {} the 30 days before", before),
+            std::cmp::Ordering::Less => format!("down from {} the 30 days before", before),
+            std::cmp::Ordering::Equal => "same as the 30 days before".to_string(),
+        }
+    ));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::food::Food;
+
+    #[test]
+    fn test_render_card() {
+        let mut food = Food::new(
+            "chicken breast",
+            31.0,
+            3.6,
+            0.0,
+            165.0,
+            "4oz",
+            vec!["cb".to_string()],
+        );
+        food.default_amount = Some("150g".to_string());
+        food.micros.sodium = Some(74.0);
+        let card = FoodCard {
+            food,
+            tags: vec!["estimated".to_string()],
+            components: vec![],
+            used_in: vec!["stir fry".to_string()],
+            times_logged: 2,
+            first_logged: Some("2026-10-01".to_string()),
+            last_logged: Some("2026-10-15".to_string()),
+            last_amount: Some("200g".to_string()),
+            source: Some("fdc:171477".to_string()),
+        };
+        let text = render(&card);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "chicken breast");
+        assert_eq!(lines[2], "per            4oz      150g      100g");
+        assert!(lines[4].starts_with("Protein      31.0g"));
+        assert!(lines[7].starts_with("Sodium        74mg"));
+        assert!(!text.contains("Sugar"));
+        assert!(
+            text.contains("Aliases: cb\nTags: estimated\nUsed in: stir fry\nSource: fdc:171477")
+        );
+        assert!(text.ends_with("Logged 2 times since 2026-10-01, last on 2026-10-15 (200g)"));
+    }
+
+    #[test]
+    fn test_render_stats() {
+        let stats = FoodStats {
+            name: "oats".to_string(),
+            times_logged: 9,
+            days_logged: 8,
+            first_logged: Some("2026-08-20".to_string()),
+            last_logged: Some("2026-10-15".to_string()),
+            per_week: 1.125,
+            usual_amount: Some("1 cup".to_string()),
+            average_grams: Some(82.4),
+            average: Macros {
+                protein: 10.4,
+                fat: 5.6,
+                carbs: 54.4,
+                calories: 311.2,
+                fiber: 8.0,
+                ..Default::default()
+            },
+            usual_hour: Some(7),
+            last_30_days: 6,
+            previous_30_days: 3,
+        };
+        let text = render_stats(&stats);
+        let lines: Vec<&str> = text.lines().skip(2).collect();
+        assert_eq!(
+            lines,
+            [
+                "Logged 9 times on 8 days, 2026-08-20 to 2026-10-15",
+                "About 1.1 a week",
+                "Usual amount: 1 cup (average 82g)",
+                "Per entry: 311 kcal, 10p/6f/54c",
+                "Usually logged: 07:00–08:00",
+                "Last 30 days: 6 (up from 3 the 30 days before)",
+            ]
+        );
+    }
+}
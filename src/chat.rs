@@ -0,0 +1,137 @@
+//! Plain-text replies shared by the chat-style frontends: `POST /quicklog`
+//! and the Discord and Matrix bots.
+
+use crate::db::{Database, DayClosed, DuplicateLog, EntryLocked, LogOptions};
+
+/// Minutes back an undo looks, as for `chomp undo`.
+pub const UNDO_WINDOW: u32 = 10;
+
+/// How `log_text` went, for `POST /quicklog` to pick a status from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    /// At least one food was logged
+    Logged,
+    /// Nothing was logged only because it already was, or the day is closed
+    Conflict,
+    /// Nothing was logged
+    Failed,
+    /// The database couldn't be read
+    Error,
+}
+
+pub struct Reply {
+    #[cfg(feature = "sse")]
+    pub outcome: Outcome,
+    pub text: String,
+}
+
+impl Reply {
+    fn new(outcome: Outcome, text: String) -> Self {
+        #[cfg(not(feature = "sse"))]
+        let _ = outcome;
+        Reply {
+            #[cfg(feature = "sse")]
+            outcome,
+            text,
+        }
+    }
+}
+
+/// Log each food in `text`, one confirmation (or complaint) per line and
+/// today's totals at the end.
+pub fn log_text(db: &Database, text: &str) -> Reply {
+    let items = match crate::logging::split_items(db, text) {
+        Ok(items) if items.is_empty() => {
+            return Reply::new(Outcome::Failed, "Nothing to log".to_string())
+        }
+        Ok(items) => items,
+        Err(e) => return Reply::new(Outcome::Error, e.to_string()),
+    };
+
+    let opts = LogOptions {
+        dedupe_window: Some(crate::logging::default_dedupe_window()),
+        ..Default::default()
+    };
+    let before = crate::hooks::before_log(db);
+    let mut lines = Vec::new();
+    let mut logged = 0;
+    let mut conflict = true;
+    for item in &items {
+        match crate::logging::parse_and_log_with(db, item, &opts) {
+            Ok(entry) => {
+                logged += 1;
+                // So `chomp redo` can repeat it; best effort, as it's logged
+                let args = std::iter::once("log").chain(item.split_whitespace());
+                let _ = db.record_command(&args.map(String::from).collect::<Vec<_>>());
+                lines.push(format!(
+                    "Logged {} {}: {:.0} kcal, {:.0}g protein",
+                    entry.amount, entry.food_name, entry.calories, entry.protein
+                ));
+                lines.extend(entry.warning.map(|w| format!("Note: {}", w)));
+            }
+            Err(e) => {
+                conflict &= e.is::<DuplicateLog>() || e.is::<DayClosed>() || e.is::<EntryLocked>();
+                lines.push(format!("Couldn't log {}: {}", item, e));
+            }
+        }
+    }
+    if logged == 0 {
+        let outcome = if conflict {
+            Outcome::Conflict
+        } else {
+            Outcome::Failed
+        };
+        return Reply::new(outcome, lines.join("\n"));
+    }
+    crate::hooks::after_log(db, &before);
+    if let Ok(today) = db.get_today_totals() {
+        lines.push(format!(
+            "Today: {:.0} kcal, {:.0}g protein",
+            today.calories, today.protein
+        ));
+    }
+    Reply::new(Outcome::Logged, lines.join("\n"))
+}
+
+/// Today's totals in one line.
+pub fn today(db: &Database) -> String {
+    match (db.get_today_totals(), db.get_today_entries()) {
+        (Ok(totals), Ok(entries)) => format!(
+            "Today: {:.0} kcal, {:.0}g protein, {:.0}g fat, {:.0}g carbs ({} entries)",
+            totals.calories,
+            totals.protein,
+            totals.fat,
+            totals.carbs,
+            entries.len()
+        ),
+        (Err(e), _) | (_, Err(e)) => format!("Couldn't read today: {}", e),
+    }
+}
+
+/// Undo the latest change within `UNDO_WINDOW`, saying what was undone.
+pub fn undo(db: &Database) -> String {
+    match db.undo_last(UNDO_WINDOW) {
+        Ok(undone) => undone
+            .iter()
+            .map(|change| change.description.as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Err(e) => e.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_text_records_commands() {
+        let db = Database::open_in_memory().unwrap();
+        let eggs = crate::food::Food::new("eggs", 6.0, 5.0, 0.5, 70.0, "1", vec![]);
+        db.add_food(&eggs).unwrap();
+        log_text(&db, "eggs 2, kale");
+        let commands = db.recent_commands(5).unwrap();
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].args, ["log", "eggs", "2"]);
+    }
+}
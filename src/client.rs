@@ -2,7 +2,10 @@ use anyhow::{anyhow, Result};
 use reqwest::blocking::Client;
 use reqwest::StatusCode;
 
-use crate::db::{CaffeineEntry, CaffeineTotals, LogEntry, Stats, WaterEntry, WaterTotals};
+use crate::db::{
+    CaffeineEntry, CaffeineTotals, DeleteMode, HistoryPage, LogEntry, NameClash, Stats, TimeWindow,
+    UndoneChange, WaterEntry, WaterTotals,
+};
 
 fn encode_path(s: &str) -> String {
     s.replace('%', "%25")
@@ -12,7 +15,7 @@ fn encode_path(s: &str) -> String {
         .replace('#', "%23")
         .replace('&', "%26")
 }
-use crate::food::{Food, Macros};
+use crate::food::{Food, Macros, Micros};
 
 pub struct RemoteClient {
     base_url: String,
@@ -65,11 +68,30 @@ impl RemoteClient {
         Ok(resp)
     }
 
-    pub fn log_food(&self, input: &str, date: Option<&str>) -> Result<LogEntry> {
-        let mut body = serde_json::json!({"food": input});
+    pub fn log_food(
+        &self,
+        input: &str,
+        date: Option<&str>,
+        time: Option<chrono::NaiveTime>,
+        meal: Option<&str>,
+        force: bool,
+        strict: bool,
+    ) -> Result<LogEntry> {
+        let mut body = serde_json::json!({
+            "food": input,
+            "force": force,
+            "warn_duplicates": true,
+            "strict": strict,
+        });
         if let Some(d) = date {
             body["date"] = serde_json::Value::String(d.to_string());
         }
+        if let Some(t) = time {
+            body["time"] = serde_json::Value::String(t.format("%H:%M").to_string());
+        }
+        if let Some(m) = meal {
+            body["meal"] = serde_json::Value::String(m.to_string());
+        }
         let resp = self.post("/api/log").json(&body).send()?;
         let resp = Self::check_response(resp)?;
         Ok(resp.json()?)
@@ -88,36 +110,52 @@ impl RemoteClient {
         protein: f64,
         fat: f64,
         carbs: f64,
-        per: &str,
+        per: Option<&str>,
         calories: Option<f64>,
+        fiber: f64,
+        micros: &Micros,
         aliases: Vec<String>,
-    ) -> Result<Food> {
+    ) -> Result<(Food, Vec<NameClash>)> {
         let mut body = serde_json::json!({
             "name": name,
             "protein": protein,
             "fat": fat,
             "carbs": carbs,
-            "per": per,
+            "fiber": fiber,
             "aliases": aliases,
         });
+        if let serde_json::Value::Object(micros) = serde_json::to_value(micros)? {
+            body.as_object_mut().unwrap().extend(micros);
+        }
+        if let Some(per) = per {
+            body["per"] = serde_json::json!(per);
+        }
         if let Some(c) = calories {
             body["calories"] = serde_json::json!(c);
         }
         let resp = self.post("/api/foods").json(&body).send()?;
         let resp = Self::check_response(resp)?;
-        Ok(resp.json()?)
+        let mut value: serde_json::Value = resp.json()?;
+        let clashes = serde_json::from_value(value["clashes"].take()).unwrap_or_default();
+        Ok((serde_json::from_value(value)?, clashes))
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn edit_food(
         &self,
         name: &str,
         protein: Option<f64>,
         fat: Option<f64>,
         carbs: Option<f64>,
+        fiber: Option<f64>,
         per: Option<&str>,
         calories: Option<f64>,
+        micros: &Micros,
     ) -> Result<Option<Food>> {
-        let mut body = serde_json::Map::new();
+        let mut body = match serde_json::to_value(micros)? {
+            serde_json::Value::Object(micros) => micros,
+            _ => serde_json::Map::new(),
+        };
         if let Some(p) = protein {
             body.insert("protein".into(), serde_json::json!(p));
         }
@@ -127,6 +165,9 @@ impl RemoteClient {
         if let Some(c) = carbs {
             body.insert("carbs".into(), serde_json::json!(c));
         }
+        if let Some(f) = fiber {
+            body.insert("fiber".into(), serde_json::json!(f));
+        }
         if let Some(s) = per {
             body.insert("per".into(), serde_json::json!(s));
         }
@@ -141,10 +182,12 @@ impl RemoteClient {
         Ok(resp.json()?)
     }
 
-    pub fn delete_food(&self, name: &str) -> Result<()> {
-        let resp = self
-            .delete(&format!("/api/foods/{}", encode_path(name)))
-            .send()?;
+    pub fn delete_food(&self, name: &str, mode: DeleteMode) -> Result<()> {
+        let mut req = self.delete(&format!("/api/foods/{}", encode_path(name)));
+        if mode != DeleteMode::Refuse {
+            req = req.query(&[("mode", mode)]);
+        }
+        let resp = req.send()?;
         Self::check_response(resp)?;
         Ok(())
     }
@@ -156,13 +199,36 @@ impl RemoteClient {
         Ok(serde_json::from_value(data["totals"].clone())?)
     }
 
-    pub fn get_history(&self, days: u32) -> Result<Vec<LogEntry>> {
-        let resp = self
-            .get("/api/history")
-            .query(&[("days", days.to_string())])
-            .send()?;
+    /// One page of history; the server reports the next offset in the
+    /// `X-Next-Offset` header.
+    pub fn get_history_page(
+        &self,
+        days: u32,
+        window: TimeWindow,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> Result<HistoryPage> {
+        let mut query = vec![("days", days.to_string()), ("offset", offset.to_string())];
+        if let Some(limit) = limit {
+            query.push(("limit", limit.to_string()));
+        }
+        if let Some(after) = window.after {
+            query.push(("after", after.format("%H:%M").to_string()));
+        }
+        if let Some(before) = window.before {
+            query.push(("before", before.format("%H:%M").to_string()));
+        }
+        let resp = self.get("/api/history").query(&query).send()?;
         let resp = Self::check_response(resp)?;
-        Ok(resp.json()?)
+        let next_offset = resp
+            .headers()
+            .get("x-next-offset")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse().ok());
+        Ok(HistoryPage {
+            entries: resp.json()?,
+            next_offset,
+        })
     }
 
     pub fn delete_log_entry(&self, id: i64) -> Result<LogEntry> {
@@ -206,6 +272,15 @@ impl RemoteClient {
         Ok(resp.json()?)
     }
 
+    pub fn undo_last(&self, window_minutes: u32) -> Result<Vec<UndoneChange>> {
+        let resp = self
+            .post("/api/undo")
+            .query(&[("window", window_minutes.to_string())])
+            .send()?;
+        let resp = Self::check_response(resp)?;
+        Ok(resp.json()?)
+    }
+
     pub fn get_stats(&self) -> Result<Stats> {
         let resp = self.get("/api/stats").send()?;
         let resp = Self::check_response(resp)?;
@@ -0,0 +1,487 @@
+use anyhow::Result;
+use chrono::Datelike;
+use serde::{Deserialize, Serialize};
+
+use crate::db::Database;
+use crate::food::{CalorieFactors, CarbMode, IncompleteDays, Macros};
+
+/// User preferences from `~/.chomp/config.toml` (or CHOMP_CONFIG_PATH).
+/// Every key is optional; a missing file means all defaults.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Color theme: default, high-contrast, or mono
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub theme: Option<String>,
+    /// Set to false to disable colored output entirely
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<bool>,
+    /// Output language (en, es, de, fr); defaults to CHOMP_LOCALE or LANG
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+    /// Daily targets, used when CHOMP_CALORIE_TARGET / CHOMP_PROTEIN_TARGET are unset
+    pub goals: Goals,
+    /// Points along the way to a goal that fire the hooks, e.g. 90% of calories
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub thresholds: Vec<Threshold>,
+    /// Where threshold events are sent
+    pub hooks: Hooks,
+    /// Lock log entries dated more than this many days ago; `--amend` overrides
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lock_after_days: Option<u32>,
+    /// Calories per gram of protein, fat, carbs, and fiber
+    #[serde(skip_serializing_if = "CalorieFactors::is_default")]
+    pub calorie_factors: CalorieFactors,
+    /// Make amounts whose weight would be assumed an error: units missing
+    /// from units.toml, and pieces converted to a weight
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub strict_units: bool,
+    /// Count carbs as total or net of fiber in totals, goals, and reports
+    #[serde(skip_serializing_if = "CarbMode::is_total")]
+    pub carb_mode: CarbMode,
+    /// Which days count as incompletely logged, and whether averages skip them
+    #[serde(skip_serializing_if = "IncompleteDays::is_default")]
+    pub incomplete_days: IncompleteDays,
+    /// A cut, maintain, or bulk program that adjusts the calorie goal weekly
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub program: Option<Program>,
+    /// Refeed days and diet breaks with their own goals
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub schedule: Vec<Scheduled>,
+    /// SSE server tuning, used when the `chomp serve` flags are unset
+    #[serde(skip_serializing_if = "Server::is_default")]
+    pub server: Server,
+    /// Meals entries can be logged under with `--meal`, in the order
+    /// `chomp today` lists them; defaults to `DEFAULT_MEALS`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub meals: Vec<String>,
+}
+
+pub const DEFAULT_MEALS: [&str; 4] = ["breakfast", "lunch", "dinner", "snack"];
+
+#[derive(Debug, Default, Clone, Copy, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Goals {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub calories: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protein: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fat: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub carbs: Option<f64>,
+    /// Millilitres of water a day
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub water: Option<f64>,
+}
+
+impl Goals {
+    pub fn get(&self, metric: Metric) -> Option<f64> {
+        match metric {
+            Metric::Calories => self.calories,
+            Metric::Protein => self.protein,
+            Metric::Fat => self.fat,
+            Metric::Carbs => self.carbs,
+        }
+    }
+
+    pub fn set(&mut self, metric: Metric, value: Option<f64>) {
+        match metric {
+            Metric::Calories => self.calories = value,
+            Metric::Protein => self.protein = value,
+            Metric::Fat => self.fat = value,
+            Metric::Carbs => self.carbs = value,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Metric {
+    Calories,
+    Protein,
+    Fat,
+    Carbs,
+}
+
+impl Metric {
+    pub fn name(self) -> &'static str {
+        match self {
+            Metric::Calories => "calories",
+            Metric::Protein => "protein",
+            Metric::Fat => "fat",
+            Metric::Carbs => "carbs",
+        }
+    }
+
+    /// This metric's share of `macros`.
+    pub fn value(self, macros: &Macros) -> f64 {
+        match self {
+            Metric::Calories => macros.calories,
+            Metric::Protein => macros.protein,
+            Metric::Fat => macros.fat,
+            Metric::Carbs => macros.carbs,
+        }
+    }
+
+    /// This metric's share of `macros` as it counts toward totals, with
+    /// carbs net of fiber under `mode`.
+    pub fn counted(self, macros: &Macros, mode: CarbMode) -> f64 {
+        match self {
+            Metric::Carbs => macros.counted_carbs(mode),
+            _ => self.value(macros),
+        }
+    }
+}
+
+/// `[[thresholds]]`: crossed once the day's total reaches `percent` of the
+/// target, which defaults to the matching goal.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Threshold {
+    /// Defaults to "<metric>-<percent>", e.g. "calories-90"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub metric: Metric,
+    #[serde(default = "full_percent")]
+    pub percent: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<f64>,
+}
+
+fn full_percent() -> f64 {
+    100.0
+}
+
+impl Threshold {
+    pub fn name(&self) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| format!("{}-{}", self.metric.name(), self.percent))
+    }
+}
+
+/// `[program]`: starting from `goals.calories`, the calorie target moves
+/// each week by how far the weigh-ins strayed from the planned rate.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Program {
+    pub phase: Phase,
+    /// First day of the program (YYYY-MM-DD)
+    pub start_date: String,
+    /// Weight on the first day, in kg
+    pub start_weight: f64,
+    /// kg per week to lose on a cut or gain on a bulk
+    #[serde(default = "default_rate")]
+    pub rate: f64,
+}
+
+fn default_rate() -> f64 {
+    0.5
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Phase {
+    Cut,
+    Maintain,
+    Bulk,
+}
+
+impl Program {
+    /// Planned change in kg per week: negative on a cut.
+    pub fn planned_rate(&self) -> f64 {
+        match self.phase {
+            Phase::Cut => -self.rate.abs(),
+            Phase::Maintain => 0.0,
+            Phase::Bulk => self.rate.abs(),
+        }
+    }
+}
+
+/// `[[schedule]]`: goals for refeed days or diet-break weeks. A day is
+/// covered when it falls between `from` and `to` (inclusive; `to` defaults
+/// to `from`, and either end may be left open) and, when `weekdays` are
+/// given, on one of them. The first matching entry wins; goals it leaves
+/// out stay as usual.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scheduled {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    /// e.g. ["sat"] for a weekly refeed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub weekdays: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub calories: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protein: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fat: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub carbs: Option<f64>,
+}
+
+impl Scheduled {
+    pub fn covers(&self, date: chrono::NaiveDate) -> bool {
+        let day = date.format("%Y-%m-%d").to_string();
+        let to = self.to.as_ref().or(self.from.as_ref());
+        self.from.as_ref().is_none_or(|from| day >= *from)
+            && to.is_none_or(|to| day <= *to)
+            && (self.weekdays.is_empty()
+                || self
+                    .weekdays
+                    .iter()
+                    .any(|w| w.parse::<chrono::Weekday>().ok() == Some(date.weekday())))
+    }
+
+    /// `goals` with this entry's goals in place.
+    pub fn apply(&self, goals: Goals) -> Goals {
+        Goals {
+            calories: self.calories.or(goals.calories),
+            protein: self.protein.or(goals.protein),
+            fat: self.fat.or(goals.fat),
+            carbs: self.carbs.or(goals.carbs),
+            ..goals
+        }
+    }
+
+    fn check(&self) -> Result<()> {
+        for date in self.from.iter().chain(&self.to) {
+            if chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").is_err() {
+                anyhow::bail!(
+                    "schedule '{}': '{}' is not a YYYY-MM-DD date",
+                    self.name,
+                    date
+                );
+            }
+        }
+        if self.from.is_none() && self.weekdays.is_empty() {
+            anyhow::bail!("schedule '{}' needs `from` or `weekdays`", self.name);
+        }
+        for day in &self.weekdays {
+            if day.parse::<chrono::Weekday>().is_err() {
+                anyhow::bail!("schedule '{}': '{}' is not a weekday", self.name, day);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Hooks {
+    /// Shell command run with the event JSON on stdin
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// URL the event JSON is POSTed to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub webhook: Option<String>,
+}
+
+/// `[server]`: SSE stream settings for `chomp serve`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Server {
+    /// Seconds between keep-alive comments on an idle stream (default 15)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_alive_secs: Option<u64>,
+    /// Events queued per session before sends wait on a slow client (default 96)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel_capacity: Option<usize>,
+    /// Concurrent sessions allowed (default unlimited)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_sessions: Option<usize>,
+    /// Seconds an MCP message may run before it is cancelled (default 300)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_timeout_secs: Option<u64>,
+}
+
+impl Server {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Fill any setting not given explicitly from the config.
+    pub fn or_config(self, config: &Config) -> Self {
+        Self {
+            keep_alive_secs: self.keep_alive_secs.or(config.server.keep_alive_secs),
+            channel_capacity: self.channel_capacity.or(config.server.channel_capacity),
+            max_sessions: self.max_sessions.or(config.server.max_sessions),
+            request_timeout_secs: self
+                .request_timeout_secs
+                .or(config.server.request_timeout_secs),
+        }
+    }
+}
+
+impl Config {
+    pub fn path() -> Result<std::path::PathBuf> {
+        if let Ok(path) = std::env::var("CHOMP_CONFIG_PATH") {
+            return Ok(std::path::PathBuf::from(path));
+        }
+        let home =
+            dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?;
+        Ok(home.join(".chomp").join("config.toml"))
+    }
+
+    pub fn load() -> Result<Self> {
+        let path = Self::path()?;
+        match std::fs::read_to_string(&path) {
+            Ok(text) => Self::parse(&text)
+                .map_err(|e| anyhow::anyhow!("Invalid config {}: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Entry lock age from CHOMP_LOCK_AFTER_DAYS, falling back to the config.
+    pub fn lock_after_days(&self) -> Option<u32> {
+        std::env::var("CHOMP_LOCK_AFTER_DAYS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .or(self.lock_after_days)
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let config: Self = toml::from_str(text)?;
+        for scheduled in &config.schedule {
+            scheduled.check()?;
+        }
+        Ok(config)
+    }
+
+    /// Meal names, from the config or the defaults, in order.
+    pub fn meals(&self) -> Vec<String> {
+        if self.meals.is_empty() {
+            DEFAULT_MEALS.iter().map(|m| m.to_string()).collect()
+        } else {
+            self.meals.iter().map(|m| m.trim().to_lowercase()).collect()
+        }
+    }
+
+    /// `name` as one of the configured meals, ignoring case.
+    pub fn meal(&self, name: &str) -> Result<String> {
+        let meals = self.meals();
+        let name = name.trim().to_lowercase();
+        if meals.contains(&name) {
+            return Ok(name);
+        }
+        anyhow::bail!(
+            "Unknown meal '{}' (use {}, or add it to meals in the config)",
+            name,
+            meals.join(", ")
+        )
+    }
+
+    /// The schedule entry covering `date`, if any.
+    pub fn scheduled_on(&self, date: chrono::NaiveDate) -> Option<&Scheduled> {
+        self.schedule.iter().find(|s| s.covers(date))
+    }
+
+    /// Write the config back to its path, creating `~/.chomp` if needed.
+    pub fn save(&self) -> Result<std::path::PathBuf> {
+        let path = Self::path()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, toml::to_string(self)?)?;
+        Ok(path)
+    }
+}
+
+/// Apply the config's entry lock, calorie factors, carb mode, and
+/// incomplete-day threshold to `db`. Today's totals are cached until the
+/// next write, for the servers and bots that keep answering from it.
+pub fn configure(db: Database) -> Database {
+    let config = Config::load().unwrap_or_default();
+    db.with_lock_after_days(config.lock_after_days())
+        .with_calorie_factors(config.calorie_factors)
+        .with_carb_mode(config.carb_mode)
+        .with_incomplete_days(config.incomplete_days)
+        .with_totals_cache(true)
+}
+
+/// Open a profile's database (the default one for None), configured and
+/// ready to use.
+pub fn open_database(profile: Option<&str>) -> Result<Database> {
+    let db = configure(Database::open_profile(profile)?);
+    db.init()?;
+    Ok(db)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config = Config::parse("theme = \"mono\"\ncolor = false\n").unwrap();
+        assert_eq!(config.theme.as_deref(), Some("mono"));
+        assert_eq!(config.color, Some(false));
+        assert!(Config::parse("").unwrap().theme.is_none());
+        assert!(Config::parse("colour = true").is_err());
+
+        let config = Config::parse("[goals]\ncalories = 2200.0\n").unwrap();
+        assert_eq!(config.goals.calories, Some(2200.0));
+        let round_trip = Config::parse(&toml::to_string(&config).unwrap()).unwrap();
+        assert_eq!(round_trip.goals.calories, Some(2200.0));
+        assert!(round_trip.theme.is_none());
+        assert_eq!(
+            Config::parse("lock_after_days = 30")
+                .unwrap()
+                .lock_after_days,
+            Some(30)
+        );
+
+        let factors = Config::parse("[calorie_factors]\ncarbs = 3.75\n")
+            .unwrap()
+            .calorie_factors;
+        assert_eq!(factors.carbs, 3.75);
+        assert_eq!(
+            factors.calories_from_macros(10.0, 10.0, 20.0, 4.0, CarbMode::Total),
+            205.0
+        );
+        assert_eq!(
+            factors.calories_from_macros(10.0, 10.0, 20.0, 4.0, CarbMode::Net),
+            198.0
+        );
+        assert!(Config::parse("[calorie_factors]\nsugar = 4.0\n").is_err());
+        assert_eq!(
+            Config::parse("carb_mode = \"net\"").unwrap().carb_mode,
+            CarbMode::Net
+        );
+        assert!(Config::parse("carb_mode = \"gross\"").is_err());
+    }
+
+    #[test]
+    fn test_meals() {
+        let config = Config::default();
+        assert_eq!(config.meal("Lunch").unwrap(), "lunch");
+        assert!(config.meal("brunch").is_err());
+
+        let config = Config::parse("meals = [\"Breakfast\", \"pre-workout\"]\n").unwrap();
+        assert_eq!(config.meals(), ["breakfast", "pre-workout"]);
+        assert_eq!(config.meal("PRE-WORKOUT").unwrap(), "pre-workout");
+        assert!(config.meal("lunch").is_err());
+    }
+
+    #[test]
+    fn test_parse_thresholds_and_hooks() {
+        let config = Config::parse(
+            "[[thresholds]]\nmetric = \"calories\"\npercent = 90\n\n\
+             [[thresholds]]\nname = \"protein-met\"\nmetric = \"protein\"\n\n\
+             [hooks]\ncommand = \"notify-send chomp\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.thresholds.len(), 2);
+        assert_eq!(config.thresholds[0].name(), "calories-90");
+        assert_eq!(config.thresholds[1].percent, 100.0);
+        assert_eq!(config.thresholds[1].name(), "protein-met");
+        assert_eq!(config.hooks.command.as_deref(), Some("notify-send chomp"));
+        assert!(Config::parse("[[thresholds]]\nmetric = \"fiber\"\n").is_err());
+    }
+}
@@ -1,14 +1,118 @@
 use anyhow::Result;
-use chrono::Local;
+use chrono::{Local, TimeZone};
 use fuzzy_matcher::skim::SkimMatcherV2;
 use fuzzy_matcher::FuzzyMatcher;
-use rusqlite::{params, Connection};
+use rusqlite::{functions::FunctionFlags, params, types::ValueRef, Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
 
-use crate::food::{Food, Macros};
+use crate::food::{
+    CalorieFactors, CarbMode, Food, IncompleteDays, MacroFilter, Macros, Micros, RecipeYield,
+};
+use crate::logging::FoodNotFound;
 
 pub struct Database {
     conn: Connection,
+    /// Entries dated more than this many days ago can't be changed
+    lock_after_days: Option<u32>,
+    /// Factors used when calories are recomputed from macros
+    calorie_factors: CalorieFactors,
+    /// Whether fiber counts toward carb totals
+    carb_mode: CarbMode,
+    /// Which days rollups flag as incomplete, and whether they skip them
+    incomplete_days: IncompleteDays,
+    /// Whether today's totals come from `TODAY_TOTALS` between writes
+    cache_totals: bool,
+}
+
+/// Today's totals per database file, for long-running processes whose
+/// clients poll them. Each entry watches the file on a connection of its
+/// own, whose `data_version` moves whenever any other connection (in this
+/// process or another) commits, which is what invalidates it.
+static TODAY_TOTALS: std::sync::OnceLock<
+    std::sync::Mutex<std::collections::HashMap<String, CachedTotals>>,
+> = std::sync::OnceLock::new();
+
+struct CachedTotals {
+    watcher: Connection,
+    data_version: i64,
+    date: String,
+    /// Before the carb mode is applied
+    totals: Macros,
+}
+
+/// Schema version this build writes, kept in SQLite's `user_version`. Bump
+/// it whenever `migrate` changes the schema in a way older builds would
+/// misread; they then refuse the database instead of failing on the SQL.
+pub const SCHEMA_VERSION: i64 = 2;
+
+/// Common whole foods with everyday serving sizes, for `chomp import starter`.
+/// Columns: name, protein, fat, carbs, calories, serving, default_amount,
+/// aliases (`;`-separated). Macros are per serving.
+pub(crate) const STARTER_FOODS: &str = include_str!("../starter_foods.csv");
+
+/// SQL helpers defined in Rust. `serving_grams(serving)` is the weight of a
+/// serving such as "100g" or "4oz", or NULL when it isn't a weight.
+fn register_functions(conn: &Connection) -> rusqlite::Result<()> {
+    conn.create_scalar_function(
+        "serving_grams",
+        1,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| Ok(crate::food::parse_grams(&ctx.get::<String>(0)?)),
+    )
+}
+
+/// Days a deleted food stays in the trash before it's purged for good.
+pub const TRASH_DAYS: u32 = 30;
+
+/// Maximum number of retries for a write that hits SQLITE_BUSY/SQLITE_LOCKED.
+const BUSY_RETRIES: u32 = 8;
+
+/// Run a write, retrying with exponential backoff (10ms, 20ms, ... ~1.3s) while
+/// another connection holds the database lock. SQLite's busy handler does not
+/// cover every case (e.g. lock upgrades inside a transaction or COMMIT while
+/// readers are active), so a concurrent CLI and server can otherwise error out.
+pub(crate) fn retry_busy<T>(mut op: impl FnMut() -> rusqlite::Result<T>) -> rusqlite::Result<T> {
+    let mut attempt = 0;
+    loop {
+        match op() {
+            Err(rusqlite::Error::SqliteFailure(e, msg))
+                if matches!(
+                    e.code,
+                    rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked
+                ) =>
+            {
+                if attempt >= BUSY_RETRIES {
+                    return Err(rusqlite::Error::SqliteFailure(e, msg));
+                }
+                std::thread::sleep(std::time::Duration::from_millis(10 << attempt));
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// A transaction that nests. Outside any transaction it starts one; inside
+/// `Database::atomically` it commits into the enclosing transaction, so the
+/// whole batch still rolls back together. Dropping it uncommitted rolls back.
+struct Savepoint<'a> {
+    conn: &'a Connection,
+    done: bool,
+}
+
+impl Savepoint<'_> {
+    fn commit(mut self) -> rusqlite::Result<()> {
+        self.done = true;
+        retry_busy(|| self.conn.execute_batch("RELEASE chomp"))
+    }
+}
+
+impl Drop for Savepoint<'_> {
+    fn drop(&mut self) {
+        if !self.done {
+            let _ = self.conn.execute_batch("ROLLBACK TO chomp; RELEASE chomp");
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -16,12 +120,293 @@ pub struct LogEntry {
     pub id: Option<i64>,
     pub date: String,
     pub food_name: String,
-    pub food_id: i64,
+    /// None once the food is deleted for good
+    pub food_id: Option<i64>,
     pub amount: String,
     pub protein: f64,
     pub fat: f64,
     pub carbs: f64,
     pub calories: f64,
+    /// The recurring entry that logged this, when it wasn't logged by hand
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recurring: Option<i64>,
+    /// Breakfast, lunch, or another meal from the config
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meal: Option<String>,
+    /// Something about how the input was read that the logger should check;
+    /// only set on the entry logging returns
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+}
+
+/// Optional attributes for a new log, water, or caffeine entry.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogOptions<'a> {
+    /// Date to log for (YYYY-MM-DD); defaults to today.
+    pub date: Option<&'a str>,
+    /// Client-supplied key; logging again with the same key returns the
+    /// original entry instead of inserting a duplicate.
+    pub idempotency_key: Option<&'a str>,
+    /// Refuse to log when the same food and amount was logged for the same
+    /// date within this many minutes. Skipped when an idempotency key is set.
+    pub dedupe_window: Option<u32>,
+    /// Log a duplicate anyway, noting it in the entry's warning, instead of
+    /// refusing.
+    pub warn_duplicates: bool,
+    /// Only log exact name or alias matches; never guess the most likely food.
+    pub strict: bool,
+    /// The recurring entry doing the logging, to mark the entry auto-logged.
+    pub recurring: Option<i64>,
+    /// Local time of day it was eaten, for food entries; defaults to now.
+    pub time: Option<chrono::NaiveTime>,
+    /// Meal the food entry belongs to, already checked against the config.
+    pub meal: Option<&'a str>,
+}
+
+impl<'a> LogOptions<'a> {
+    pub fn on_date(date: Option<&'a str>) -> Self {
+        Self {
+            date,
+            ..Self::default()
+        }
+    }
+
+    fn date_or_today(&self) -> String {
+        self.date
+            .map(|d| d.to_string())
+            .unwrap_or_else(|| Local::now().format("%Y-%m-%d").to_string())
+    }
+
+    /// When the entry was eaten as a UTC timestamp, when a time was given.
+    fn logged_at(&self, day: chrono::NaiveDate) -> Result<Option<String>> {
+        let Some(time) = self.time else {
+            return Ok(None);
+        };
+        let local = Local
+            .from_local_datetime(&day.and_time(time))
+            .earliest()
+            .ok_or_else(|| anyhow::anyhow!("{} doesn't exist on {}", time.format("%H:%M"), day))?;
+        Ok(Some(
+            local.naive_utc().format("%Y-%m-%d %H:%M:%S").to_string(),
+        ))
+    }
+}
+
+/// Nutrients a food or entry may or may not have; NULL when unknown.
+const MICRO_COLUMNS: [&str; 3] = ["sugar", "sodium", "saturated_fat"];
+
+/// Columns `export_csv` writes by default, in order.
+pub const CSV_COLUMNS: [&str; 8] = [
+    "date", "food", "amount", "grams", "protein", "fat", "carbs", "calories",
+];
+
+/// Further columns `export_csv` can write when asked for.
+pub const EXTRA_CSV_COLUMNS: [&str; 4] = ["id", "fiber", "meal", "created_at"];
+
+/// What `export_csv` writes and how.
+#[derive(Debug, Clone)]
+pub struct CsvExport {
+    /// Columns in order, from `CSV_COLUMNS` and `EXTRA_CSV_COLUMNS`
+    pub columns: Vec<String>,
+    pub delimiter: u8,
+    /// Only entries dated on or after this (YYYY-MM-DD)
+    pub since: Option<String>,
+    /// Replace personal food names with stable hashes
+    pub anonymize: bool,
+}
+
+impl Default for CsvExport {
+    fn default() -> Self {
+        Self {
+            columns: CSV_COLUMNS.iter().map(|c| c.to_string()).collect(),
+            delimiter: b',',
+            since: None,
+            anonymize: false,
+        }
+    }
+}
+
+/// What `import_food` did with one food.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportOutcome {
+    Added,
+    /// Nutrition changed at the source since the last import
+    Updated,
+    Unchanged,
+    /// Name already taken by an unrelated food
+    Skipped,
+}
+
+/// What `import_log_csv` did with each row.
+#[derive(Debug, Default, PartialEq)]
+pub struct LogImport {
+    pub entries: usize,
+    /// Rows whose entry was already logged
+    pub skipped: usize,
+    /// Foods that didn't exist and were created from the row's macros
+    pub foods_created: usize,
+}
+
+/// What `import_aliases_csv` did with each row.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct AliasImport {
+    pub added: usize,
+    /// Rows whose alias already pointed at the same food
+    pub existing: usize,
+    /// Aliases skipped because they already name a different food
+    pub conflicts: Vec<String>,
+    /// Foods named in the file that don't exist here
+    pub missing_foods: Vec<String>,
+}
+
+/// Guess a CSV file's delimiter from its header line, preferring commas.
+fn sniff_delimiter(data: &[u8]) -> u8 {
+    let header = data.split(|&b| b == b'\n').next().unwrap_or_default();
+    [b'|', b'\t', b';', b',']
+        .into_iter()
+        .max_by_key(|d| header.iter().filter(|b| *b == d).count())
+        .unwrap_or(b',')
+}
+
+/// Returned (via anyhow) when a log looks like an accidental double submission.
+#[derive(Debug)]
+pub struct DuplicateLog {
+    pub existing: LogEntry,
+    pub window_minutes: u32,
+}
+
+impl std::fmt::Display for DuplicateLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}; use --force to log it again", self.summary())
+    }
+}
+
+impl DuplicateLog {
+    /// The message without the CLI's `--force` hint, for the APIs to word their own.
+    pub fn summary(&self) -> String {
+        format!(
+            "{} {} was already logged in the last {} minute(s) (entry #{})",
+            self.existing.amount,
+            self.existing.food_name,
+            self.window_minutes,
+            self.existing.id.unwrap_or(0)
+        )
+    }
+}
+
+impl std::error::Error for DuplicateLog {}
+
+/// Returned (via anyhow) when changing entries on a day closed with `chomp close`.
+#[derive(Debug)]
+pub struct DayClosed {
+    pub date: String,
+}
+
+impl std::fmt::Display for DayClosed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} is closed; reopen it with: chomp close --reopen --date {}",
+            self.date, self.date
+        )
+    }
+}
+
+impl std::error::Error for DayClosed {}
+
+/// Returned (via anyhow) when changing entries older than the configured lock age.
+#[derive(Debug)]
+pub struct EntryLocked {
+    pub date: String,
+    pub lock_after_days: u32,
+}
+
+impl std::fmt::Display for EntryLocked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}; pass --amend to change them", self.summary())
+    }
+}
+
+impl EntryLocked {
+    /// The message without the CLI's `--amend` hint.
+    pub fn summary(&self) -> String {
+        format!(
+            "Entries for {} are locked (older than {} days)",
+            self.date, self.lock_after_days
+        )
+    }
+}
+
+impl std::error::Error for EntryLocked {}
+
+/// Returned (via anyhow) when deleting a food that log entries or compound
+/// foods still use, and no `DeleteMode` says what to do with them.
+#[derive(Debug)]
+pub struct FoodInUse {
+    pub name: String,
+    pub log_entries: i64,
+    pub compounds: Vec<String>,
+}
+
+impl std::fmt::Display for FoodInUse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}; pass --keep-history to keep the log entries, or --cascade to trash them along with the food",
+            self.summary()
+        )
+    }
+}
+
+impl FoodInUse {
+    /// The message without the CLI's `--keep-history`/`--cascade` hint.
+    pub fn summary(&self) -> String {
+        let mut uses = Vec::new();
+        if self.log_entries > 0 {
+            uses.push(format!(
+                "{} log entr{}",
+                self.log_entries,
+                if self.log_entries == 1 { "y" } else { "ies" }
+            ));
+        }
+        if !self.compounds.is_empty() {
+            uses.push(format!(
+                "{} compound food{} ({})",
+                self.compounds.len(),
+                if self.compounds.len() == 1 { "" } else { "s" },
+                self.compounds.join(", ")
+            ));
+        }
+        format!("'{}' is used by {}", self.name, uses.join(" and "))
+    }
+}
+
+impl std::error::Error for FoodInUse {}
+
+/// What `delete_food` does with the log entries and compound foods that use
+/// the food. Either way the food is taken out of the compound foods.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DeleteMode {
+    /// Refuse while anything uses the food
+    #[default]
+    Refuse,
+    /// Keep the log entries; history shows them as 'deleted'
+    KeepHistory,
+    /// Move the log entries to the trash with the food
+    Cascade,
+}
+
+/// Ratings stored when a day is closed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DayReview {
+    pub date: String,
+    /// How full the day left you, 1-5
+    pub satiety: Option<u8>,
+    /// Energy through the day, 1-5
+    pub energy: Option<u8>,
+    /// When the day was closed; None once reopened
+    pub closed_at: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -31,11 +416,89 @@ pub struct WaterEntry {
     pub amount_ml: f64,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WeightEntry {
+    pub id: Option<i64>,
+    pub date: String,
+    pub kg: f64,
+}
+
+/// A chomp invocation, as recorded for `chomp redo`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CommandRecord {
+    pub id: i64,
+    /// Arguments after the program name
+    pub args: Vec<String>,
+    pub created_at: String,
+}
+
+/// A program's weekly change to the calorie target.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TargetAdjustment {
+    /// Monday of the week the target applies from
+    pub week_start: String,
+    /// Daily calorie target from that week on
+    pub calories: f64,
+    /// Change from the previous target
+    pub change: f64,
+    /// Weight change over the week before, in kg; None without weigh-ins
+    pub actual_rate: Option<f64>,
+    pub planned_rate: f64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct WaterTotals {
     pub total_ml: f64,
 }
 
+/// A failed lookup that was followed by logging a food, suggesting the
+/// query should become an alias for it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AliasSuggestion {
+    pub query: String,
+    pub food_id: i64,
+    pub food_name: String,
+    /// How many times the query missed before this food was logged
+    pub misses: usize,
+}
+
+/// A cooked batch of a compound food, split into containers. Logging the
+/// food by weight afterwards uses the batch's macros per cooked gram.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrepBatch {
+    pub id: i64,
+    pub food_id: i64,
+    pub food_name: String,
+    pub portions: f64,
+    pub container_grams: f64,
+    /// Macros of the whole batch
+    pub protein: f64,
+    pub fat: f64,
+    pub carbs: f64,
+    pub calories: f64,
+    pub fiber: f64,
+    pub created_at: String,
+}
+
+impl PrepBatch {
+    pub fn total_grams(&self) -> f64 {
+        self.portions * self.container_grams
+    }
+
+    /// Macros for `grams` of the cooked batch.
+    pub fn macros_for(&self, grams: f64) -> Macros {
+        let factor = grams / self.total_grams();
+        Macros {
+            protein: self.protein * factor,
+            fat: self.fat * factor,
+            carbs: self.carbs * factor,
+            calories: self.calories * factor,
+            fiber: self.fiber * factor,
+            micros: Micros::default(),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CaffeineEntry {
     pub id: Option<i64>,
@@ -49,6 +512,15 @@ pub struct CaffeineTotals {
     pub total_mg: f64,
 }
 
+/// A mutation that was reverted by `undo_last`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UndoneChange {
+    pub action: String,
+    pub table: String,
+    pub row_id: i64,
+    pub description: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Stats {
     pub food_count: i64,
@@ -57,26 +529,498 @@ pub struct Stats {
     pub last_entry: Option<String>,
 }
 
+/// Part of the day, by the local time entries were logged. Either end may
+/// be open; `after` later than `before` wraps past midnight.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TimeWindow {
+    pub after: Option<chrono::NaiveTime>,
+    pub before: Option<chrono::NaiveTime>,
+}
+
+impl TimeWindow {
+    pub fn parse(after: Option<&str>, before: Option<&str>) -> Result<Self> {
+        Ok(Self {
+            after: after.map(parse_time_of_day).transpose()?,
+            before: before.map(parse_time_of_day).transpose()?,
+        })
+    }
+
+    /// SQL condition on `column` (a UTC timestamp) for this window.
+    fn condition(&self, column: &str) -> String {
+        let time = format!("time({}, 'localtime')", column);
+        let bound =
+            |op: &str, t: chrono::NaiveTime| format!("{} {} '{}'", time, op, t.format("%H:%M:%S"));
+        match (self.after, self.before) {
+            (None, None) => "1".to_string(),
+            (Some(a), None) => bound(">=", a),
+            (None, Some(b)) => bound("<", b),
+            (Some(a), Some(b)) if a > b => format!("({} OR {})", bound(">=", a), bound("<", b)),
+            (Some(a), Some(b)) => format!("({} AND {})", bound(">=", a), bound("<", b)),
+        }
+    }
+}
+
+/// "18:00", "18", "6pm", or "6:30pm".
+pub fn parse_time_of_day(input: &str) -> Result<chrono::NaiveTime> {
+    let s = input.trim().to_lowercase();
+    let (clock, offset) = if let Some(t) = s.strip_suffix("am") {
+        (t.trim(), Some(0))
+    } else if let Some(t) = s.strip_suffix("pm") {
+        (t.trim(), Some(12))
+    } else {
+        (s.as_str(), None)
+    };
+    let (hour, minute) = clock.split_once(':').unwrap_or((clock, "0"));
+    let parsed = hour.parse::<u32>().ok().zip(minute.parse::<u32>().ok());
+    let time = parsed.and_then(|(hour, minute)| {
+        let hour = match offset {
+            Some(_) if !(1..=12).contains(&hour) => return None,
+            Some(offset) => hour % 12 + offset,
+            None => hour,
+        };
+        chrono::NaiveTime::from_hms_opt(hour, minute, 0)
+    });
+    time.ok_or_else(|| anyhow::anyhow!("Invalid time '{}', expected e.g. 18:00 or 6pm", input))
+}
+
+/// Rows from an ad-hoc `Database::query_read_only`.
+#[derive(Debug, Serialize)]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<serde_json::Value>>,
+}
+
+/// One page of history, newest first.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HistoryPage {
+    pub entries: Vec<LogEntry>,
+    /// Offset of the next page, when there are more entries
+    pub next_offset: Option<u32>,
+}
+
+/// A name or alias being added that another food already answers to,
+/// ignoring case. Lookups try names before aliases, so only one of the two
+/// can be found.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NameClash {
+    /// The name or alias being added
+    pub name: String,
+    /// Whether `name` is being added as an alias
+    pub is_alias: bool,
+    /// The food that already answers to it
+    pub existing: String,
+    /// Whether `existing` answers to it by alias rather than by name
+    pub existing_is_alias: bool,
+}
+
+impl NameClash {
+    /// Whether lookups will find the new entry rather than the existing food.
+    pub fn new_wins(&self) -> bool {
+        !self.is_alias && self.existing_is_alias
+    }
+}
+
+impl std::fmt::Display for NameClash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}'{}' is already {} {}; ",
+            if self.is_alias { "alias " } else { "" },
+            self.name,
+            if self.existing_is_alias {
+                "an alias of"
+            } else {
+                "the name of"
+            },
+            self.existing
+        )?;
+        if self.new_wins() {
+            write!(f, "lookups will find the new food instead")
+        } else {
+            write!(f, "lookups will keep finding {}", self.existing)
+        }
+    }
+}
+
+/// What a share link shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ShareScope {
+    /// Individual log entries
+    Log,
+    /// Daily totals
+    Reports,
+}
+
+impl ShareScope {
+    pub fn name(self) -> &'static str {
+        match self {
+            ShareScope::Log => "log",
+            ShareScope::Reports => "reports",
+        }
+    }
+}
+
+/// A token-protected, read-only view of recent data for someone else.
+#[derive(Debug, Serialize)]
+pub struct Share {
+    pub token: String,
+    pub scopes: Vec<ShareScope>,
+    /// How many days back the view reaches
+    pub days: u32,
+    pub created_at: String,
+    pub revoked_at: Option<String>,
+    /// When the link stops working; None if it never does
+    pub expires_at: Option<String>,
+}
+
+impl Share {
+    /// Neither revoked nor past its expiry.
+    pub fn is_active(&self) -> bool {
+        let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        self.revoked_at.is_none() && self.expires_at.as_ref().is_none_or(|at| *at > now)
+    }
+}
+
+/// Something logged often enough to have a short name, logged with
+/// `POST /quicklog/fav/<slug>`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Favorite {
+    pub slug: String,
+    /// What gets logged, as typed to `chomp log`
+    pub input: String,
+    pub created_at: String,
+}
+
+/// Something logged every day at a set time by `chomp recurring run`, or
+/// as soon as chomp first runs that day when `auto`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Recurring {
+    pub id: i64,
+    /// What gets logged, as typed to `chomp log`
+    pub input: String,
+    /// Local time of day, HH:MM
+    pub time: String,
+    /// First day it's due (YYYY-MM-DD)
+    pub since: String,
+    /// Last day it was logged for
+    pub last_run: Option<String>,
+    /// Logged the first time chomp runs each day, not only by `chomp recurring run`
+    pub auto: bool,
+}
+
+/// A deleted food waiting in the trash.
+#[derive(Debug, Serialize)]
+pub struct TrashedFood {
+    pub id: i64,
+    pub name: String,
+    pub deleted_at: String,
+    /// Log entries that point at the food and come back with it
+    pub log_entries: i64,
+}
+
+/// Everything known about one food, for `chomp show`.
+#[derive(Debug, Serialize)]
+pub struct FoodCard {
+    pub food: Food,
+    pub tags: Vec<String>,
+    /// (food, amount) per serving, when the food is a compound
+    pub components: Vec<(String, String)>,
+    /// Compound foods made with this one
+    pub used_in: Vec<String>,
+    pub times_logged: i64,
+    pub first_logged: Option<String>,
+    pub last_logged: Option<String>,
+    pub last_amount: Option<String>,
+    /// Id in the database the food was imported from, e.g. "fdc:171077"
+    pub source: Option<String>,
+}
+
+/// How one food shows up in the log, for `chomp food stats`.
+#[derive(Debug, Serialize)]
+pub struct FoodStats {
+    pub name: String,
+    pub times_logged: i64,
+    pub days_logged: i64,
+    pub first_logged: Option<String>,
+    pub last_logged: Option<String>,
+    /// Entries per week from the first entry to today
+    pub per_week: f64,
+    /// The amount logged most often, as typed
+    pub usual_amount: Option<String>,
+    /// Mean weight per entry, over entries whose amount converts to grams
+    pub average_grams: Option<f64>,
+    /// Mean macros per entry
+    pub average: Macros,
+    /// Local hour (0-23) most entries were logged in
+    pub usual_hour: Option<u32>,
+    /// Entries dated in the last 30 days, and in the 30 before that
+    pub last_30_days: i64,
+    pub previous_30_days: i64,
+}
+
+/// One day's totals, freshly written to `daily_totals`.
+#[derive(Debug, Clone, Serialize)]
+pub struct DaySnapshot {
+    pub date: String,
+    pub entries: i64,
+    pub totals: Macros,
+}
+
+/// A past day with nothing logged, or too little to be the whole day.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MissingDay {
+    pub date: String,
+    pub entries: i64,
+    pub calories: f64,
+}
+
+/// What one food contributed over a stretch of days.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FoodTally {
+    pub name: String,
+    pub times: i64,
+    pub calories: f64,
+}
+
+/// Everything one food contributed over a stretch of days.
+#[derive(Debug, Clone, Serialize)]
+pub struct MacroSource {
+    pub name: String,
+    pub times: i64,
+    pub totals: Macros,
+}
+
+/// Average daily intake over one week or month (or a single day).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rollup {
+    /// "2024-03" for months, the Monday ("2024-03-04") for weeks, or the date
+    pub period: String,
+    /// Days with at least one entry, less any incomplete days left out
+    pub days: i64,
+    /// Days logged under the incomplete threshold, whether or not left out
+    #[serde(default)]
+    pub incomplete: i64,
+    pub protein: f64,
+    pub fat: f64,
+    pub carbs: f64,
+    pub calories: f64,
+    /// Averaged over the days that have them
+    #[serde(flatten)]
+    pub micros: Micros,
+}
+
+/// Which chomp made a database and which schema it's on, for `chomp db info`.
+#[derive(Debug, Serialize)]
+pub struct DbInfo {
+    pub schema_version: i64,
+    /// Newest schema this build can open
+    pub supported_schema_version: i64,
+    /// chomp version that created the database; None if it predates stamping
+    pub created_by: Option<String>,
+    pub created_at: Option<String>,
+    /// chomp version that last moved the database to a newer schema
+    pub migrated_by: Option<String>,
+    pub sqlite_version: String,
+}
+
 impl Database {
     /// Open an in-memory database (for testing)
     #[allow(dead_code)]
     pub fn open_in_memory() -> Result<Self> {
         let conn = Connection::open_in_memory()?;
-        let db = Self { conn };
+        register_functions(&conn)?;
+        let db = Self {
+            conn,
+            lock_after_days: None,
+            calorie_factors: CalorieFactors::default(),
+            carb_mode: CarbMode::Total,
+            incomplete_days: IncompleteDays::default(),
+            cache_totals: false,
+        };
         db.init()?;
         Ok(db)
     }
 
     pub fn open() -> Result<Self> {
-        let db_path = Self::db_path()?;
+        Self::open_at(&Self::db_path()?)
+    }
 
+    /// Open the database file at an explicit path.
+    pub fn open_at(db_path: &std::path::Path) -> Result<Self> {
         // Create parent directory if needed
         if let Some(parent) = db_path.parent() {
             std::fs::create_dir_all(parent)?;
         }
 
-        let conn = Connection::open(&db_path)?;
-        Ok(Self { conn })
+        let conn = Connection::open(db_path)?;
+        register_functions(&conn)?;
+        Ok(Self {
+            conn,
+            lock_after_days: None,
+            calorie_factors: CalorieFactors::default(),
+            carb_mode: CarbMode::Total,
+            incomplete_days: IncompleteDays::default(),
+            cache_totals: false,
+        })
+    }
+
+    /// Run one ad-hoc statement on a read-only connection to the database
+    /// file, so it can't change anything whatever it says.
+    pub fn query_read_only(db_path: &std::path::Path, sql: &str) -> Result<QueryResult> {
+        let conn = Connection::open_with_flags(
+            db_path,
+            rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY | rusqlite::OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .map_err(|e| anyhow::anyhow!("Could not open {}: {}", db_path.display(), e))?;
+        register_functions(&conn)?;
+        let mut batch = rusqlite::Batch::new(&conn, sql);
+        let Some(mut stmt) = batch.next()? else {
+            anyhow::bail!("No SQL statement given");
+        };
+        if batch.next()?.is_some() {
+            anyhow::bail!("Only one statement can be run at a time");
+        }
+        let columns: Vec<String> = stmt.column_names().iter().map(|n| n.to_string()).collect();
+        let mut rows = stmt.query([])?;
+        let mut result = Vec::new();
+        while let Some(row) = rows.next()? {
+            result.push(
+                (0..columns.len())
+                    .map(|i| row.get_ref(i).map(json_value))
+                    .collect::<rusqlite::Result<_>>()?,
+            );
+        }
+        Ok(QueryResult {
+            columns,
+            rows: result,
+        })
+    }
+
+    fn savepoint(&self) -> rusqlite::Result<Savepoint<'_>> {
+        self.conn.execute_batch("SAVEPOINT chomp")?;
+        Ok(Savepoint {
+            conn: &self.conn,
+            done: false,
+        })
+    }
+
+    /// Run `f` as a single transaction: if it fails, none of its changes are
+    /// kept, and if it succeeds they undo together as one change.
+    pub fn atomically<T>(&self, f: impl FnOnce(&Self) -> Result<T>) -> Result<T> {
+        let tx = self.savepoint()?;
+        let last_audit: i64 =
+            self.conn
+                .query_row("SELECT COALESCE(MAX(id), 0) FROM audit_log", [], |row| {
+                    row.get(0)
+                })?;
+        let value = f(self)?;
+        retry_busy(|| {
+            self.conn.execute(
+                "UPDATE audit_log SET group_id = (SELECT MIN(id) FROM audit_log WHERE id > ?1)
+                 WHERE id > ?1",
+                params![last_audit],
+            )
+        })?;
+        tx.commit()?;
+        Ok(value)
+    }
+
+    /// Lock entries dated more than `days` days ago against logging, edits,
+    /// and deletes. None (the default) leaves history editable.
+    pub fn with_lock_after_days(mut self, days: Option<u32>) -> Self {
+        self.lock_after_days = days;
+        self
+    }
+
+    /// Use these factors wherever calories are recomputed from macros.
+    pub fn with_calorie_factors(mut self, factors: CalorieFactors) -> Self {
+        self.calorie_factors = factors;
+        self
+    }
+
+    /// Count carbs net of fiber in totals and rollups.
+    pub fn with_carb_mode(mut self, mode: CarbMode) -> Self {
+        self.carb_mode = mode;
+        self
+    }
+
+    /// Flag days under `incomplete.below` calories in rollups, leaving them
+    /// out of averages if `incomplete.exclude`.
+    pub fn with_incomplete_days(mut self, incomplete: IncompleteDays) -> Self {
+        self.incomplete_days = incomplete;
+        self
+    }
+
+    /// Keep today's totals in memory until the next write, for servers
+    /// answering frequent polls.
+    pub fn with_totals_cache(mut self, cache: bool) -> Self {
+        self.cache_totals = cache;
+        self
+    }
+
+    /// Calories from macros under the configured factors and carb mode.
+    pub fn calories_from_macros(&self, protein: f64, fat: f64, carbs: f64, fiber: f64) -> f64 {
+        self.calorie_factors
+            .calories_from_macros(protein, fat, carbs, fiber, self.carb_mode)
+    }
+
+    /// A handle that aborts whatever statement this connection is running,
+    /// from another thread.
+    #[cfg(feature = "sse")]
+    pub fn interrupt_handle(&self) -> rusqlite::InterruptHandle {
+        self.conn.get_interrupt_handle()
+    }
+
+    /// Open the database for a named profile, or the default database for None.
+    pub fn open_profile(profile: Option<&str>) -> Result<Self> {
+        match profile {
+            Some(name) => Self::open_at(&Self::profile_path(name)?),
+            None => Self::open(),
+        }
+    }
+
+    /// Path of a named profile database, stored next to the default one
+    /// under `profiles/<name>.db`.
+    pub fn profile_path(name: &str) -> Result<std::path::PathBuf> {
+        let valid = !name.is_empty()
+            && name.len() <= 64
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            anyhow::bail!(
+                "Invalid profile name: '{}'. Use letters, digits, '-' or '_'.",
+                name
+            );
+        }
+        let db_path = Self::db_path()?;
+        let dir = db_path
+            .parent()
+            .map(|p| p.to_path_buf())
+            .unwrap_or_default();
+        Ok(dir.join("profiles").join(format!("{}.db", name)))
+    }
+
+    /// Names of the profile databases next to the default one.
+    pub fn profile_names() -> Result<Vec<String>> {
+        let dir = Self::profile_path("default")?
+            .parent()
+            .map(|p| p.to_path_buf())
+            .unwrap_or_default();
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut names: Vec<String> = entries
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                (path.extension()? == "db").then(|| path.file_stem()?.to_str().map(String::from))?
+            })
+            .collect();
+        names.sort();
+        Ok(names)
     }
 
     pub fn db_path() -> Result<std::path::PathBuf> {
@@ -90,8 +1034,15 @@ impl Database {
     }
 
     pub fn init(&self) -> Result<()> {
-        self.conn.execute_batch(
-            "
+        self.check_schema_version()?;
+        let fresh: bool = self.conn.query_row(
+            "SELECT NOT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table')",
+            [],
+            |row| row.get(0),
+        )?;
+        retry_busy(|| {
+            self.conn.execute_batch(
+                "
             CREATE TABLE IF NOT EXISTS foods (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
                 name TEXT NOT NULL UNIQUE,
@@ -114,13 +1065,14 @@ impl Database {
             CREATE TABLE IF NOT EXISTS log (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
                 date TEXT NOT NULL,
-                food_id INTEGER NOT NULL,
+                food_id INTEGER,
                 amount TEXT NOT NULL,
                 protein REAL NOT NULL,
                 fat REAL NOT NULL,
                 carbs REAL NOT NULL,
                 calories REAL NOT NULL,
                 created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+                idempotency_key TEXT,
                 FOREIGN KEY (food_id) REFERENCES foods(id)
             );
 
@@ -143,7 +1095,8 @@ impl Database {
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
                 date TEXT NOT NULL,
                 amount_ml REAL NOT NULL,
-                created_at TEXT DEFAULT CURRENT_TIMESTAMP
+                created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+                idempotency_key TEXT
             );
 
             CREATE TABLE IF NOT EXISTS caffeine_log (
@@ -151,106 +1104,738 @@ impl Database {
                 date TEXT NOT NULL,
                 amount_mg REAL NOT NULL,
                 source TEXT NOT NULL DEFAULT '',
-                created_at TEXT DEFAULT CURRENT_TIMESTAMP
+                created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+                idempotency_key TEXT
             );
 
-            CREATE INDEX IF NOT EXISTS idx_log_date ON log(date);
+            CREATE TABLE IF NOT EXISTS audit_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                group_id INTEGER,
+                action TEXT NOT NULL,
+                table_name TEXT NOT NULL,
+                row_id INTEGER NOT NULL,
+                snapshot TEXT,
+                created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+                undone_at TEXT
+            );
+
+            CREATE TABLE IF NOT EXISTS food_tags (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                food_id INTEGER NOT NULL,
+                tag TEXT NOT NULL,
+                UNIQUE (food_id, tag),
+                FOREIGN KEY (food_id) REFERENCES foods(id) ON DELETE CASCADE
+            );
+
+            CREATE TABLE IF NOT EXISTS prep_batches (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                food_id INTEGER NOT NULL,
+                portions REAL NOT NULL,
+                container_grams REAL NOT NULL,
+                protein REAL NOT NULL,
+                fat REAL NOT NULL,
+                carbs REAL NOT NULL,
+                calories REAL NOT NULL,
+                created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (food_id) REFERENCES foods(id) ON DELETE CASCADE
+            );
+
+            CREATE TABLE IF NOT EXISTS day_reviews (
+                date TEXT PRIMARY KEY,
+                satiety INTEGER,
+                energy INTEGER,
+                closed_at TEXT
+            );
+
+            CREATE TABLE IF NOT EXISTS lookup_misses (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                query TEXT NOT NULL,
+                after_log_id INTEGER,
+                created_at TEXT DEFAULT CURRENT_TIMESTAMP
+            );
+
+            CREATE TABLE IF NOT EXISTS weights (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                date TEXT NOT NULL,
+                kg REAL NOT NULL,
+                created_at TEXT DEFAULT CURRENT_TIMESTAMP
+            );
+
+            CREATE TABLE IF NOT EXISTS target_adjustments (
+                week_start TEXT PRIMARY KEY,
+                calories REAL NOT NULL,
+                change REAL NOT NULL,
+                actual_rate REAL,
+                planned_rate REAL NOT NULL,
+                created_at TEXT DEFAULT CURRENT_TIMESTAMP
+            );
+
+            CREATE TABLE IF NOT EXISTS shares (
+                token TEXT PRIMARY KEY,
+                scopes TEXT NOT NULL,
+                days INTEGER NOT NULL,
+                created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+                revoked_at TEXT,
+                expires_at TEXT
+            );
+
+            CREATE TABLE IF NOT EXISTS favorites (
+                slug TEXT PRIMARY KEY,
+                input TEXT NOT NULL,
+                created_at TEXT DEFAULT CURRENT_TIMESTAMP
+            );
+
+            CREATE TABLE IF NOT EXISTS recurring (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                input TEXT NOT NULL,
+                time TEXT NOT NULL,
+                since TEXT NOT NULL,
+                last_run TEXT,
+                created_at TEXT DEFAULT CURRENT_TIMESTAMP
+            );
+
+            CREATE TABLE IF NOT EXISTS trash (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                food_id INTEGER NOT NULL,
+                name TEXT NOT NULL,
+                rows TEXT NOT NULL,
+                deleted_at TEXT DEFAULT CURRENT_TIMESTAMP
+            );
+
+            CREATE TABLE IF NOT EXISTS command_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                args TEXT NOT NULL,
+                created_at TEXT DEFAULT CURRENT_TIMESTAMP
+            );
+
+            CREATE TABLE IF NOT EXISTS daily_totals (
+                date TEXT PRIMARY KEY,
+                protein REAL NOT NULL,
+                fat REAL NOT NULL,
+                carbs REAL NOT NULL,
+                calories REAL NOT NULL,
+                entries INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS food_units (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                food_id INTEGER NOT NULL,
+                unit TEXT NOT NULL,
+                grams REAL NOT NULL,
+                UNIQUE(food_id, unit),
+                FOREIGN KEY (food_id) REFERENCES foods(id) ON DELETE CASCADE
+            );
+
+            CREATE TABLE IF NOT EXISTS pantry (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                food_id INTEGER NOT NULL UNIQUE,
+                added_at TEXT DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (food_id) REFERENCES foods(id) ON DELETE CASCADE
+            );
+
+            CREATE TABLE IF NOT EXISTS meta (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_log_date ON log(date);
             CREATE INDEX IF NOT EXISTS idx_foods_name ON foods(name);
             CREATE INDEX IF NOT EXISTS idx_aliases_alias ON aliases(alias);
             CREATE INDEX IF NOT EXISTS idx_water_log_date ON water_log(date);
             CREATE INDEX IF NOT EXISTS idx_caffeine_log_date ON caffeine_log(date);
             ",
+            )
+        })?;
+        self.migrate()?;
+        self.stamp_version(fresh)?;
+        Ok(())
+    }
+
+    /// Refuse a database on a newer schema than this build knows, naming
+    /// the version that wrote it, before any statement trips over it.
+    fn check_schema_version(&self) -> Result<()> {
+        let version = self.schema_version()?;
+        if version <= SCHEMA_VERSION {
+            return Ok(());
+        }
+        let writer = self
+            .meta("migrated_by")
+            .ok()
+            .flatten()
+            .map(|v| format!("chomp {}", v))
+            .unwrap_or_else(|| "a newer chomp".to_string());
+        anyhow::bail!(
+            "This database was upgraded by {} (schema {}), and this chomp {} only reads up to schema {}. Upgrade chomp to open it",
+            writer,
+            version,
+            env!("CARGO_PKG_VERSION"),
+            SCHEMA_VERSION
+        )
+    }
+
+    /// Record which version created the database, and move it to
+    /// `SCHEMA_VERSION` once `migrate` has brought it there.
+    fn stamp_version(&self, fresh: bool) -> Result<()> {
+        let version = env!("CARGO_PKG_VERSION");
+        if fresh {
+            retry_busy(|| {
+                self.conn.execute(
+                    "INSERT OR IGNORE INTO meta (key, value) VALUES
+                        ('created_by', ?1), ('created_at', CURRENT_TIMESTAMP)",
+                    params![version],
+                )
+            })?;
+        }
+        if self.schema_version()? < SCHEMA_VERSION {
+            retry_busy(|| {
+                if !fresh {
+                    self.conn.execute(
+                        "INSERT OR REPLACE INTO meta (key, value) VALUES ('migrated_by', ?1)",
+                        params![version],
+                    )?;
+                }
+                self.conn
+                    .execute_batch(&format!("PRAGMA user_version = {}", SCHEMA_VERSION))
+            })?;
+        }
+        Ok(())
+    }
+
+    fn schema_version(&self) -> Result<i64> {
+        Ok(self
+            .conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))?)
+    }
+
+    /// A value from the `meta` table, which databases from before it
+    /// existed don't have.
+    fn meta(&self, key: &str) -> Result<Option<String>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT value FROM meta WHERE key = ?1",
+                params![key],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    /// Keep a copy of the settings (`chomp settings export`) in the
+    /// database, so a backup carries the setup along with the data.
+    pub fn store_settings(&self, settings: &str) -> Result<()> {
+        retry_busy(|| {
+            self.conn.execute(
+                "INSERT OR REPLACE INTO meta (key, value) VALUES ('settings', ?1)",
+                params![settings],
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Settings stored by `store_settings`, if any.
+    pub fn stored_settings(&self) -> Result<Option<String>> {
+        Ok(self.meta("settings").ok().flatten())
+    }
+
+    /// Versions the database was created and migrated by. Works without
+    /// `init`, so it can describe a database too new to open.
+    pub fn info(&self) -> Result<DbInfo> {
+        let meta = |key| self.meta(key).ok().flatten();
+        Ok(DbInfo {
+            schema_version: self.schema_version()?,
+            supported_schema_version: SCHEMA_VERSION,
+            created_by: meta("created_by"),
+            created_at: meta("created_at"),
+            migrated_by: meta("migrated_by"),
+            sqlite_version: rusqlite::version().to_string(),
+        })
+    }
+
+    /// Bring databases created by older versions up to the current schema.
+    fn migrate(&self) -> Result<()> {
+        for table in ["log", "water_log", "caffeine_log"] {
+            self.add_column_if_missing(table, "idempotency_key", "TEXT")?;
+        }
+        self.add_column_if_missing("lookup_misses", "after_log_id", "INTEGER")?;
+        for table in ["foods", "log", "prep_batches"] {
+            self.add_column_if_missing(table, "fiber", "REAL NOT NULL DEFAULT 0")?;
+        }
+        for table in ["foods", "log"] {
+            for column in MICRO_COLUMNS {
+                self.add_column_if_missing(table, column, "REAL")?;
+            }
+        }
+        self.add_column_if_missing("foods", "external_id", "TEXT")?;
+        self.add_column_if_missing("log", "recurring_id", "INTEGER")?;
+        self.add_column_if_missing("log", "meal", "TEXT")?;
+        self.add_column_if_missing("recurring", "auto", "INTEGER NOT NULL DEFAULT 0")?;
+        self.add_column_if_missing("shares", "expires_at", "TEXT")?;
+        // Recipes: compound items are the whole batch, divided into servings
+        self.add_column_if_missing("compound_foods", "servings", "REAL NOT NULL DEFAULT 1")?;
+        self.add_column_if_missing("compound_foods", "yield_grams", "REAL")?;
+        self.allow_null_log_food()?;
+        let mut stale_cache =
+            self.add_column_if_missing("daily_totals", "fiber", "REAL NOT NULL DEFAULT 0")?;
+        for column in MICRO_COLUMNS {
+            stale_cache |= self.add_column_if_missing("daily_totals", column, "REAL")?;
+        }
+        if stale_cache {
+            // Triggers from before a column was tracked don't fill it
+            retry_busy(|| {
+                self.conn.execute_batch(
+                    "DROP TRIGGER IF EXISTS daily_totals_insert;
+                     DROP TRIGGER IF EXISTS daily_totals_delete;
+                     DROP TRIGGER IF EXISTS daily_totals_update;",
+                )
+            })?;
+        }
+        retry_busy(|| {
+            self.conn.execute_batch(
+                "
+            CREATE UNIQUE INDEX IF NOT EXISTS idx_log_idempotency
+                ON log(idempotency_key) WHERE idempotency_key IS NOT NULL;
+            CREATE UNIQUE INDEX IF NOT EXISTS idx_water_log_idempotency
+                ON water_log(idempotency_key) WHERE idempotency_key IS NOT NULL;
+            CREATE UNIQUE INDEX IF NOT EXISTS idx_caffeine_log_idempotency
+                ON caffeine_log(idempotency_key) WHERE idempotency_key IS NOT NULL;
+            CREATE UNIQUE INDEX IF NOT EXISTS idx_foods_external_id
+                ON foods(external_id) WHERE external_id IS NOT NULL;
+            ",
+            )
+        })?;
+        self.create_daily_totals_triggers()?;
+        let (cached, logged): (i64, i64) = self.conn.query_row(
+            "SELECT (SELECT COUNT(*) FROM daily_totals), (SELECT COUNT(*) FROM log)",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
         )?;
+        if (cached == 0 || stale_cache) && logged > 0 {
+            self.rebuild_daily_totals()?;
+        }
         Ok(())
     }
 
-    pub fn add_food(&self, food: &Food) -> Result<i64> {
-        self.conn.execute(
-            "INSERT INTO foods (name, protein, fat, carbs, calories, serving, default_amount)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            params![
-                food.name,
-                food.protein,
-                food.fat,
-                food.carbs,
-                food.calories,
-                food.serving,
-                food.default_amount,
-            ],
+    /// Keep `daily_totals` in step with every write to `log`, whichever code
+    /// path makes it. Each trigger re-sums only the day it touched.
+    fn create_daily_totals_triggers(&self) -> Result<()> {
+        let resum = |date: &str| {
+            format!(
+                "DELETE FROM daily_totals WHERE date = {date};
+                 INSERT INTO daily_totals (date, protein, fat, carbs, calories, entries, fiber,
+                                           sugar, sodium, saturated_fat)
+                     SELECT date, SUM(protein), SUM(fat), SUM(carbs), SUM(calories), COUNT(*),
+                            SUM(fiber), SUM(sugar), SUM(sodium), SUM(saturated_fat)
+                     FROM log WHERE date = {date} GROUP BY date;"
+            )
+        };
+        let sql = format!(
+            "CREATE TRIGGER IF NOT EXISTS daily_totals_insert AFTER INSERT ON log BEGIN {new} END;
+             CREATE TRIGGER IF NOT EXISTS daily_totals_delete AFTER DELETE ON log BEGIN {old} END;
+             CREATE TRIGGER IF NOT EXISTS daily_totals_update AFTER UPDATE ON log BEGIN {old} {new} END;",
+            new = resum("NEW.date"),
+            old = resum("OLD.date"),
+        );
+        retry_busy(|| self.conn.execute_batch(&sql))?;
+        Ok(())
+    }
+
+    /// Recompute `daily_totals` from the raw log. Returns the number of days.
+    pub fn rebuild_daily_totals(&self) -> Result<usize> {
+        retry_busy(|| {
+            self.conn.execute_batch(
+                "BEGIN;
+                 DELETE FROM daily_totals;
+                 INSERT INTO daily_totals (date, protein, fat, carbs, calories, entries, fiber,
+                                           sugar, sodium, saturated_fat)
+                     SELECT date, SUM(protein), SUM(fat), SUM(carbs), SUM(calories), COUNT(*),
+                            SUM(fiber), SUM(sugar), SUM(sodium), SUM(saturated_fat)
+                     FROM log GROUP BY date;
+                 COMMIT;",
+            )
+        })?;
+        let days: i64 = self
+            .conn
+            .query_row("SELECT COUNT(*) FROM daily_totals", [], |row| row.get(0))?;
+        Ok(days as usize)
+    }
+
+    /// Re-sum one day's row in `daily_totals` from the raw log and return it.
+    pub fn snapshot_day(&self, date: &str) -> Result<DaySnapshot> {
+        retry_busy(|| {
+            let tx = self.conn.unchecked_transaction()?;
+            tx.execute("DELETE FROM daily_totals WHERE date = ?1", params![date])?;
+            tx.execute(
+                "INSERT INTO daily_totals (date, protein, fat, carbs, calories, entries, fiber,
+                                           sugar, sodium, saturated_fat)
+                     SELECT date, SUM(protein), SUM(fat), SUM(carbs), SUM(calories), COUNT(*),
+                            SUM(fiber), SUM(sugar), SUM(sodium), SUM(saturated_fat)
+                     FROM log WHERE date = ?1 GROUP BY date",
+                params![date],
+            )?;
+            tx.commit()
+        })?;
+        let entries: i64 = self.conn.query_row(
+            "SELECT COALESCE(SUM(entries), 0) FROM daily_totals WHERE date = ?1",
+            params![date],
+            |row| row.get(0),
+        )?;
+        Ok(DaySnapshot {
+            date: date.to_string(),
+            entries,
+            totals: self.get_totals_for_date(date)?,
+        })
+    }
+
+    /// Write a consistent copy of the whole database to `path`, which must
+    /// not exist yet.
+    pub fn backup_to(&self, path: &std::path::Path) -> Result<()> {
+        let path = path
+            .to_str()
+            .ok_or_else(|| anyhow::anyhow!("Backup path is not valid UTF-8"))?;
+        retry_busy(|| self.conn.execute("VACUUM INTO ?1", params![path]))?;
+        Ok(())
+    }
+
+    /// Let log entries outlive their food once it's purged from the trash,
+    /// by rebuilding `log` without the NOT NULL on `food_id`. The triggers
+    /// go with the old table and are made again by `migrate`.
+    fn allow_null_log_food(&self) -> Result<()> {
+        let not_null: bool = self.conn.query_row(
+            "SELECT \"notnull\" FROM pragma_table_info('log') WHERE name = 'food_id'",
+            [],
+            |row| row.get(0),
+        )?;
+        if !not_null {
+            return Ok(());
+        }
+        let table: String = self.conn.query_row(
+            "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = 'log'",
+            [],
+            |row| row.get(0),
         )?;
+        let indexes: Vec<String> = self
+            .conn
+            .prepare("SELECT sql FROM sqlite_master WHERE type = 'index' AND tbl_name = 'log' AND sql IS NOT NULL")?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        let table = table
+            .replacen("CREATE TABLE log", "CREATE TABLE log_rebuilt", 1)
+            .replacen("food_id INTEGER NOT NULL", "food_id INTEGER", 1);
+        // Entries of foods in the trash already point at nothing
+        self.without_foreign_keys(|| {
+            let tx = self.savepoint()?;
+            retry_busy(|| {
+                self.conn.execute_batch(&format!(
+                    "{table};
+                     INSERT INTO log_rebuilt SELECT * FROM log;
+                     DROP TABLE log;
+                     ALTER TABLE log_rebuilt RENAME TO log;
+                     {};",
+                    indexes.join(";\n")
+                ))
+            })?;
+            tx.commit()?;
+            Ok(())
+        })
+    }
+
+    /// Returns whether the column had to be added.
+    fn add_column_if_missing(&self, table: &str, column: &str, definition: &str) -> Result<bool> {
+        let exists = self
+            .conn
+            .prepare(&format!("PRAGMA table_info({})", table))?
+            .query_map([], |row| row.get::<_, String>(1))?
+            .filter_map(|r| r.ok())
+            .any(|c| c == column);
+        if !exists {
+            retry_busy(|| {
+                self.conn.execute_batch(&format!(
+                    "ALTER TABLE {} ADD COLUMN {} {}",
+                    table, column, definition
+                ))
+            })?;
+        }
+        Ok(!exists)
+    }
+
+    pub fn add_food(&self, food: &Food) -> Result<i64> {
+        // A clashing alias leaves no half-added food behind
+        let tx = self.savepoint()?;
+        retry_busy(|| {
+            self.conn.execute(
+                "INSERT INTO foods (name, protein, fat, carbs, calories, serving, default_amount, fiber,
+                                    sugar, sodium, saturated_fat)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                params![
+                    food.name,
+                    food.protein,
+                    food.fat,
+                    food.carbs,
+                    food.calories,
+                    food.serving,
+                    food.default_amount,
+                    food.fiber,
+                    food.micros.sugar,
+                    food.micros.sodium,
+                    food.micros.saturated_fat,
+                ],
+            )
+        })?;
 
         let food_id = self.conn.last_insert_rowid();
+        let group = self.record_change(None, "insert", "foods", food_id, None)?;
 
         // Add aliases
         for alias in &food.aliases {
-            self.conn.execute(
-                "INSERT INTO aliases (food_id, alias) VALUES (?1, ?2)",
-                params![food_id, alias],
-            )?;
+            retry_busy(|| {
+                self.conn.execute(
+                    "INSERT INTO aliases (food_id, alias) VALUES (?1, ?2)",
+                    params![food_id, alias],
+                )
+            })?;
+            let alias_id = self.conn.last_insert_rowid();
+            self.record_change(Some(group), "insert", "aliases", alias_id, None)?;
+        }
+        for (unit, grams) in &food.units {
+            self.set_food_unit_in(Some(group), food_id, unit, *grams)?;
         }
 
+        tx.commit()?;
         Ok(food_id)
     }
 
+    /// Where `name` and `aliases` collide with what other foods (any but
+    /// `food_id`) already answer to.
+    pub fn name_clashes(
+        &self,
+        name: &str,
+        aliases: &[String],
+        food_id: Option<i64>,
+    ) -> Result<Vec<NameClash>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT name, 0 FROM foods
+             WHERE LOWER(name) = LOWER(?1) AND id IS NOT ?2
+             UNION ALL
+             SELECT f.name, 1 FROM aliases a JOIN foods f ON f.id = a.food_id
+             WHERE LOWER(a.alias) = LOWER(?1) AND f.id IS NOT ?2",
+        )?;
+        let mut clashes = Vec::new();
+        let wanted = std::iter::once((name, false)).chain(
+            aliases
+                .iter()
+                .filter(|a| !a.eq_ignore_ascii_case(name))
+                .map(|a| (a.as_str(), true)),
+        );
+        for (wanted, is_alias) in wanted {
+            let found = stmt
+                .query_map(params![wanted, food_id], |row| {
+                    Ok(NameClash {
+                        name: wanted.to_string(),
+                        is_alias,
+                        existing: row.get(0)?,
+                        existing_is_alias: row.get(1)?,
+                    })
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            clashes.extend(found);
+        }
+        Ok(clashes)
+    }
+
     pub fn get_food_by_name(&self, name: &str) -> Result<Option<Food>> {
         let name_lower = name.to_lowercase();
 
         // Try exact match first
-        let mut stmt = self.conn.prepare(
-            "SELECT id, name, protein, fat, carbs, calories, serving, default_amount 
-             FROM foods WHERE LOWER(name) = ?1",
-        )?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {FOOD_COLUMNS}, {ALIASES_COLUMN} FROM foods f WHERE LOWER(f.name) = ?1"
+        ))?;
 
-        if let Ok(food) = stmt.query_row(params![&name_lower], |row| {
-            Ok(Food {
-                id: Some(row.get(0)?),
-                name: row.get(1)?,
-                protein: row.get(2)?,
-                fat: row.get(3)?,
-                carbs: row.get(4)?,
-                calories: row.get(5)?,
-                serving: row.get(6)?,
-                default_amount: row.get(7)?,
-                aliases: vec![],
-            })
-        }) {
+        if let Ok(food) = stmt.query_row(params![&name_lower], food_from_row) {
             return Ok(Some(food));
         }
 
         // Try alias match
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {FOOD_COLUMNS}, {ALIASES_COLUMN} FROM foods f
+             JOIN aliases m ON f.id = m.food_id
+             WHERE LOWER(m.alias) = ?1"
+        ))?;
+
+        if let Ok(food) = stmt.query_row(params![&name_lower], food_from_row) {
+            return Ok(Some(food));
+        }
+
+        Ok(None)
+    }
+
+    /// Names of the variants of `base`: foods named like "Oats (dry)" and
+    /// "Oats (cooked)" for "oats", by name.
+    pub fn food_variants(&self, base: &str) -> Result<Vec<String>> {
+        let base = base.trim();
+        if base.is_empty() {
+            return Ok(Vec::new());
+        }
+        let prefix = format!("{} (", base.to_lowercase());
+        let names = self
+            .conn
+            .prepare(
+                "SELECT name FROM foods WHERE substr(LOWER(name), 1, ?2) = ?1 ORDER BY name COLLATE NOCASE",
+            )?
+            .query_map(params![prefix, prefix.chars().count() as i64], |row| {
+                row.get::<_, String>(0)
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(names
+            .into_iter()
+            .filter(|name| crate::food::base_name(name).eq_ignore_ascii_case(base))
+            .collect())
+    }
+
+    /// Best guess for a name that has no exact or alias match, ranked by
+    /// edit distance to the food's name and aliases, how often it's been
+    /// logged, and how recently. None when nothing is close enough.
+    pub fn most_likely_food(&self, name: &str) -> Result<Option<Food>> {
+        let query = name.trim().to_lowercase();
+        if query.is_empty() {
+            return Ok(None);
+        }
+
+        // (food id, times logged, days since last logged)
         let mut stmt = self.conn.prepare(
-            "SELECT f.id, f.name, f.protein, f.fat, f.carbs, f.calories, f.serving, f.default_amount 
-             FROM foods f
-             JOIN aliases a ON f.id = a.food_id
-             WHERE LOWER(a.alias) = ?1"
+            "SELECT food_id, COUNT(*), julianday('now', 'localtime') - julianday(MAX(date))
+             FROM log WHERE food_id IS NOT NULL GROUP BY food_id",
         )?;
+        let history: std::collections::HashMap<i64, (f64, f64)> = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    (row.get::<_, f64>(1)?, row.get::<_, f64>(2)?.max(0.0)),
+                ))
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        let max_count = history.values().map(|h| h.0).fold(1.0, f64::max);
 
-        if let Ok(food) = stmt.query_row(params![&name_lower], |row| {
-            Ok(Food {
-                id: Some(row.get(0)?),
-                name: row.get(1)?,
-                protein: row.get(2)?,
-                fat: row.get(3)?,
-                carbs: row.get(4)?,
-                calories: row.get(5)?,
-                serving: row.get(6)?,
-                default_amount: row.get(7)?,
-                aliases: vec![],
+        let best = self
+            .name_similarities(&query)?
+            .into_iter()
+            .filter_map(|(id, _, similarity)| {
+                if similarity < MIN_GUESS_SIMILARITY {
+                    return None;
+                }
+                let (frequency, recency) = history
+                    .get(&id)
+                    .map(|(count, days)| (count / max_count, 1.0 / (1.0 + days / 7.0)))
+                    .unwrap_or((0.0, 0.0));
+                Some((0.7 * similarity + 0.2 * frequency + 0.1 * recency, id))
             })
-        }) {
-            return Ok(Some(food));
+            .max_by(|a, b| a.0.total_cmp(&b.0).then(b.1.cmp(&a.1)));
+
+        match best {
+            Some((_, id)) => self.get_food_by_id(id),
+            None => Ok(None),
         }
+    }
 
-        Ok(None)
+    /// Names of up to `limit` foods that look like `name`, closest first, to
+    /// offer when a lookup fails. Looser than `most_likely_food`, which has
+    /// to be sure enough to log.
+    pub fn food_suggestions(&self, name: &str, limit: usize) -> Result<Vec<String>> {
+        let query = name.trim().to_lowercase();
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+        let scored = self
+            .name_similarities(&query)?
+            .into_iter()
+            .map(|(_, name, similarity)| (similarity, name));
+        Ok(crate::suggest::top(scored, MIN_SUGGEST_SIMILARITY, limit))
     }
 
-    pub fn search_foods(&self, query: &str) -> Result<Vec<Food>> {
+    /// Every food as (id, name, similarity of its closest name or alias to
+    /// the lowercased `query`), in id order.
+    fn name_similarities(&self, query: &str) -> Result<Vec<(i64, String, f64)>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, name, protein, fat, carbs, calories, serving, default_amount FROM foods",
+            "SELECT f.id, f.name, LOWER(f.name) FROM foods f
+             UNION ALL SELECT f.id, f.name, LOWER(a.alias)
+             FROM aliases a JOIN foods f ON f.id = a.food_id
+             ORDER BY 1",
         )?;
+        let mut foods: Vec<(i64, String, f64)> = Vec::new();
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?;
+        for row in rows {
+            let (id, name, candidate) = row?;
+            let similarity = crate::suggest::name_similarity(query, &candidate);
+            match foods.last_mut() {
+                Some(last) if last.0 == id => last.2 = last.2.max(similarity),
+                _ => foods.push((id, name, similarity)),
+            }
+        }
+        Ok(foods)
+    }
 
-        let foods: Vec<Food> = stmt
-            .query_map([], |row| {
+    fn get_food_by_id(&self, id: i64) -> Result<Option<Food>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {FOOD_COLUMNS}, {ALIASES_COLUMN} FROM foods f WHERE f.id = ?1"
+        ))?;
+        let food = stmt
+            .query_map(params![id], food_from_row)?
+            .next()
+            .transpose()?;
+        Ok(food)
+    }
+
+    /// Foods matching every filter, by name. With `per` (a weight such as
+    /// "100g"), values are compared and returned per that weight, and foods
+    /// whose serving isn't a weight are left out.
+    pub fn find_foods(&self, filters: &[MacroFilter], per: Option<&str>) -> Result<Vec<Food>> {
+        let per_grams = per
+            .map(|p| {
+                crate::food::parse_grams(p)
+                    .ok_or_else(|| anyhow::anyhow!("'{}' is not a weight, e.g. 100g", p))
+            })
+            .transpose()?;
+        let mut params: Vec<f64> = Vec::new();
+        let value = |column: &str| match per_grams {
+            Some(_) => format!("({} * ?1 / serving_grams(serving))", column),
+            None => column.to_string(),
+        };
+        let mut conditions = Vec::new();
+        if let Some(grams) = per_grams {
+            params.push(grams);
+            conditions.push("serving_grams(serving) > 0".to_string());
+        }
+        for filter in filters {
+            let (sql, values) = filter.to_sql(&value(filter.column));
+            conditions.extend(sql);
+            params.extend(values);
+        }
+        let where_clause = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT id, name, {}, {}, {}, {}, serving, default_amount, {}, {}, {}, {},
+                    {ALIASES_COLUMN}
+             FROM foods f {} ORDER BY name",
+            value("protein"),
+            value("fat"),
+            value("carbs"),
+            value("calories"),
+            value("fiber"),
+            value("sugar"),
+            value("sodium"),
+            value("saturated_fat"),
+            where_clause
+        ))?;
+        let foods = stmt
+            .query_map(rusqlite::params_from_iter(params), |row| {
                 Ok(Food {
                     id: Some(row.get(0)?),
                     name: row.get(1)?,
@@ -258,11 +1843,28 @@ impl Database {
                     fat: row.get(3)?,
                     carbs: row.get(4)?,
                     calories: row.get(5)?,
-                    serving: row.get(6)?,
+                    serving: match per {
+                        Some(per) => per.to_string(),
+                        None => row.get(6)?,
+                    },
                     default_amount: row.get(7)?,
-                    aliases: vec![],
+                    aliases: parse_aliases(row, 12)?,
+                    fiber: row.get(8)?,
+                    micros: micros_from_row(row, 9)?,
+                    units: parse_units(row, 13)?,
                 })
             })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(foods)
+    }
+
+    pub fn search_foods(&self, query: &str) -> Result<Vec<Food>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {FOOD_COLUMNS}, {ALIASES_COLUMN} FROM foods f"
+        ))?;
+
+        let foods: Vec<Food> = stmt
+            .query_map([], food_from_row)?
             .filter_map(|r| r.ok())
             .collect();
 
@@ -278,37 +1880,76 @@ impl Database {
             })
             .collect();
 
-        scored.sort_by(|a, b| b.0.cmp(&a.0));
+        scored.sort_by_key(|s| std::cmp::Reverse(s.0));
 
         Ok(scored.into_iter().map(|(_, f)| f).take(10).collect())
     }
 
-    pub fn log_food(
+    /// Log `amount` of a food with its already-calculated macros.
+    pub fn log_food_with(
         &self,
         food_id: i64,
         amount: &str,
         macros: &Macros,
-        date: Option<&str>,
+        opts: &LogOptions,
     ) -> Result<LogEntry> {
-        let date = date
-            .map(|d| d.to_string())
-            .unwrap_or_else(|| Local::now().format("%Y-%m-%d").to_string());
+        let date = opts.date_or_today();
+        let day = chrono::NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+            .map_err(|_| anyhow::anyhow!("Invalid date '{}', expected YYYY-MM-DD", date))?;
+        let logged_at = opts.logged_at(day)?;
+        self.ensure_open(&date)?;
+
+        let mut warning = None;
+        if let (Some(window), None) = (opts.dedupe_window, opts.idempotency_key) {
+            if let Some(existing) = self.find_recent_duplicate(food_id, amount, &date, window)? {
+                let duplicate = DuplicateLog {
+                    existing,
+                    window_minutes: window,
+                };
+                if !opts.warn_duplicates {
+                    return Err(duplicate.into());
+                }
+                warning = Some(format!("{}; logged it again", duplicate.summary()));
+            }
+        }
 
-        self.conn.execute(
-            "INSERT INTO log (date, food_id, amount, protein, fat, carbs, calories)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            params![
-                date,
-                food_id,
-                amount,
-                macros.protein,
-                macros.fat,
-                macros.carbs,
-                macros.calories,
-            ],
-        )?;
+        let inserted = retry_busy(|| {
+            self.conn.execute(
+                "INSERT INTO log (date, food_id, amount, protein, fat, carbs, calories, idempotency_key, fiber,
+                                  sugar, sodium, saturated_fat, recurring_id, created_at, meal)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13,
+                         COALESCE(?14, CURRENT_TIMESTAMP), ?15)
+                 ON CONFLICT(idempotency_key) WHERE idempotency_key IS NOT NULL DO NOTHING",
+                params![
+                    date,
+                    food_id,
+                    amount,
+                    macros.protein,
+                    macros.fat,
+                    macros.carbs,
+                    macros.calories,
+                    opts.idempotency_key,
+                    macros.fiber,
+                    macros.micros.sugar,
+                    macros.micros.sodium,
+                    macros.micros.saturated_fat,
+                    opts.recurring,
+                    logged_at,
+                    opts.meal,
+                ],
+            )
+        })?;
+
+        if inserted == 0 {
+            // Replayed idempotency key: hand back the entry that was logged first
+            let key = opts.idempotency_key.unwrap_or_default();
+            return self.find_log_by_idempotency_key(key)?.ok_or_else(|| {
+                anyhow::anyhow!("Log entry for idempotency key '{}' not found", key)
+            });
+        }
 
         let id = self.conn.last_insert_rowid();
+        self.record_change(None, "insert", "log", id, None)?;
 
         // Get food name
         let food_name: String = self.conn.query_row(
@@ -321,61 +1962,152 @@ impl Database {
             id: Some(id),
             date,
             food_name,
-            food_id,
+            food_id: Some(food_id),
             amount: amount.to_string(),
             protein: macros.protein,
             fat: macros.fat,
             carbs: macros.carbs,
             calories: macros.calories,
+            recurring: opts.recurring,
+            meal: opts.meal.map(str::to_string),
+            warning,
         })
     }
 
-    pub fn get_today_totals(&self) -> Result<Macros> {
-        let date = Local::now().format("%Y-%m-%d").to_string();
-
-        let mut stmt = self.conn.prepare(
-            "SELECT COALESCE(SUM(protein), 0), COALESCE(SUM(fat), 0), 
-                    COALESCE(SUM(carbs), 0), COALESCE(SUM(calories), 0)
-             FROM log WHERE date = ?1",
-        )?;
+    /// Most recent entry for the same food, amount and date created within
+    /// the last `window_minutes`. A window of 0 never matches.
+    pub fn find_recent_duplicate(
+        &self,
+        food_id: i64,
+        amount: &str,
+        date: &str,
+        window_minutes: u32,
+    ) -> Result<Option<LogEntry>> {
+        if window_minutes == 0 {
+            return Ok(None);
+        }
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {ENTRY_COLUMNS}
+             FROM log l
+             LEFT JOIN foods f ON l.food_id = f.id
+             WHERE l.food_id = ?1 AND l.amount = ?2 AND l.date = ?3
+               AND l.created_at >= datetime('now', ?4)
+             ORDER BY l.id DESC LIMIT 1"
+        ))?;
+        let entry = stmt
+            .query_map(
+                params![
+                    food_id,
+                    amount,
+                    date,
+                    format!("-{} minutes", window_minutes)
+                ],
+                entry_from_row,
+            )?
+            .next()
+            .transpose()?;
+        Ok(entry)
+    }
+
+    pub fn find_log_by_idempotency_key(&self, key: &str) -> Result<Option<LogEntry>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {ENTRY_COLUMNS}
+             FROM log l
+             LEFT JOIN foods f ON l.food_id = f.id
+             WHERE l.idempotency_key = ?1"
+        ))?;
+        let entry = stmt
+            .query_map(params![key], entry_from_row)?
+            .next()
+            .transpose()?;
+        Ok(entry)
+    }
+
+    pub fn get_today_totals(&self) -> Result<Macros> {
+        let date = Local::now().format("%Y-%m-%d").to_string();
+        let mut totals = match self.conn.path().filter(|path| !path.is_empty()) {
+            // Inside a transaction, its own writes aren't committed for the
+            // watcher to notice
+            Some(path) if self.cache_totals && self.conn.is_autocommit() => {
+                self.cached_totals(path, &date)?
+            }
+            _ => self.summed_totals(&date)?,
+        };
+        totals.carbs = totals.counted_carbs(self.carb_mode);
+        Ok(totals)
+    }
+
+    /// Totals for one day, from the `daily_totals` cache.
+    pub fn get_totals_for_date(&self, date: &str) -> Result<Macros> {
+        let mut totals = self.summed_totals(date)?;
+        totals.carbs = totals.counted_carbs(self.carb_mode);
+        Ok(totals)
+    }
+
+    /// `date`'s totals from `TODAY_TOTALS`, summed again if anything has
+    /// been committed since they were.
+    fn cached_totals(&self, path: &str, date: &str) -> Result<Macros> {
+        let cache = TODAY_TOTALS.get_or_init(Default::default);
+        let mut cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = match cache.entry(path.to_string()) {
+            std::collections::hash_map::Entry::Occupied(e) => e.into_mut(),
+            std::collections::hash_map::Entry::Vacant(e) => e.insert(CachedTotals {
+                watcher: Connection::open_with_flags(
+                    path,
+                    rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY
+                        | rusqlite::OpenFlags::SQLITE_OPEN_NO_MUTEX,
+                )?,
+                data_version: -1,
+                date: String::new(),
+                totals: Macros::default(),
+            }),
+        };
+        // Read before summing, so a commit in between shows next time
+        let data_version: i64 = entry
+            .watcher
+            .query_row("PRAGMA data_version", [], |row| row.get(0))?;
+        if entry.data_version != data_version || entry.date != date {
+            entry.totals = self.summed_totals(date)?;
+            entry.data_version = data_version;
+            entry.date = date.to_string();
+        }
+        Ok(entry.totals.clone())
+    }
 
-        let macros = stmt.query_row(params![date], |row| {
+    /// `date`'s totals with carbs as stored, fiber included.
+    fn summed_totals(&self, date: &str) -> Result<Macros> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT COALESCE(SUM(protein), 0), COALESCE(SUM(fat), 0),
+                    COALESCE(SUM(carbs), 0), COALESCE(SUM(calories), 0),
+                    COALESCE(SUM(fiber), 0), SUM(sugar), SUM(sodium), SUM(saturated_fat)
+             FROM daily_totals WHERE date = ?1",
+        )?;
+        let totals = stmt.query_row(params![date], |row| {
             Ok(Macros {
                 protein: row.get(0)?,
                 fat: row.get(1)?,
                 carbs: row.get(2)?,
                 calories: row.get(3)?,
+                fiber: row.get(4)?,
+                micros: micros_from_row(row, 5)?,
             })
         })?;
-
-        Ok(macros)
+        Ok(totals)
     }
 
     pub fn get_today_entries(&self) -> Result<Vec<LogEntry>> {
         let date = Local::now().format("%Y-%m-%d").to_string();
 
-        let mut stmt = self.conn.prepare(
-            "SELECT l.id, l.date, COALESCE(f.name, 'deleted'), l.food_id, l.amount, l.protein, l.fat, l.carbs, l.calories
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {ENTRY_COLUMNS}
              FROM log l
              LEFT JOIN foods f ON l.food_id = f.id
              WHERE l.date = ?1
-             ORDER BY l.id DESC",
-        )?;
+             ORDER BY l.id DESC"
+        ))?;
 
         let entries = stmt
-            .query_map(params![date], |row| {
-                Ok(LogEntry {
-                    id: Some(row.get(0)?),
-                    date: row.get(1)?,
-                    food_name: row.get(2)?,
-                    food_id: row.get(3)?,
-                    amount: row.get(4)?,
-                    protein: row.get(5)?,
-                    fat: row.get(6)?,
-                    carbs: row.get(7)?,
-                    calories: row.get(8)?,
-                })
-            })?
+            .query_map(params![date], entry_from_row)?
             .filter_map(|r| r.ok())
             .collect();
 
@@ -383,53 +2115,76 @@ impl Database {
     }
 
     pub fn get_history(&self, days: u32) -> Result<Vec<LogEntry>> {
+        Ok(self
+            .get_history_page(days, TimeWindow::default(), None, 0)?
+            .entries)
+    }
+
+    /// Up to `limit` entries from the last `days` days logged within
+    /// `window`, skipping the newest `offset`. Only the requested page is
+    /// read from the database. A `limit` of 0 is taken as 1 so that paging
+    /// always moves forward.
+    pub fn get_history_page(
+        &self,
+        days: u32,
+        window: TimeWindow,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> Result<HistoryPage> {
         let start_date = Local::now()
             .checked_sub_signed(chrono::Duration::days(days as i64))
             .unwrap()
             .format("%Y-%m-%d")
             .to_string();
 
-        let mut stmt = self.conn.prepare(
-            "SELECT l.id, l.date, f.name, l.food_id, l.amount, l.protein, l.fat, l.carbs, l.calories
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {ENTRY_COLUMNS}
              FROM log l
-             JOIN foods f ON l.food_id = f.id
-             WHERE l.date >= ?1
-             ORDER BY l.date DESC, l.id DESC"
-        )?;
-
-        let entries = stmt
-            .query_map(params![start_date], |row| {
-                Ok(LogEntry {
-                    id: Some(row.get(0)?),
-                    date: row.get(1)?,
-                    food_name: row.get(2)?,
-                    food_id: row.get(3)?,
-                    amount: row.get(4)?,
-                    protein: row.get(5)?,
-                    fat: row.get(6)?,
-                    carbs: row.get(7)?,
-                    calories: row.get(8)?,
-                })
-            })?
+             LEFT JOIN foods f ON l.food_id = f.id
+             WHERE l.date >= ?1 AND {}
+             ORDER BY l.date DESC, l.id DESC
+             LIMIT ?2 OFFSET ?3",
+            window.condition("l.created_at")
+        ))?;
+
+        let limit = limit.map(|l| l.max(1));
+        // One extra row tells whether another page follows
+        let fetch = limit.map_or(-1, |l| i64::from(l) + 1);
+        let mut entries: Vec<LogEntry> = stmt
+            .query_map(params![start_date, fetch, offset], entry_from_row)?
             .filter_map(|r| r.ok())
             .collect();
 
-        Ok(entries)
+        let next_offset = match limit {
+            Some(limit) if entries.len() > limit as usize => {
+                entries.truncate(limit as usize);
+                Some(offset + limit)
+            }
+            _ => None,
+        };
+        Ok(HistoryPage {
+            entries,
+            next_offset,
+        })
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn edit_food(
         &self,
         name: &str,
         protein: Option<f64>,
         fat: Option<f64>,
         carbs: Option<f64>,
+        fiber: Option<f64>,
         serving: Option<&str>,
         calories: Option<f64>,
-    ) -> Result<()> {
+        micros: &Micros,
+    ) -> Result<Vec<String>> {
         // Get the current food
-        let food = self
-            .get_food_by_name(name)?
-            .ok_or_else(|| anyhow::anyhow!("Food not found: '{}'", name))?;
+        let food = match self.get_food_by_name(name)? {
+            Some(food) => food,
+            None => return Err(FoodNotFound::lookup(self, name)?.into()),
+        };
 
         // Build update query based on which fields are provided
         let mut updates = Vec::new();
@@ -447,10 +2202,26 @@ impl Database {
             updates.push("carbs = ?");
             params_vec.push(Box::new(c));
         }
+        if let Some(f) = fiber {
+            updates.push("fiber = ?");
+            params_vec.push(Box::new(f));
+        }
         if let Some(s) = serving {
             updates.push("serving = ?");
             params_vec.push(Box::new(s.to_string()));
         }
+        if let Some(s) = micros.sugar {
+            updates.push("sugar = ?");
+            params_vec.push(Box::new(s));
+        }
+        if let Some(s) = micros.sodium {
+            updates.push("sodium = ?");
+            params_vec.push(Box::new(s));
+        }
+        if let Some(s) = micros.saturated_fat {
+            updates.push("saturated_fat = ?");
+            params_vec.push(Box::new(s));
+        }
 
         // Calculate new calories if macros changed or calories provided
         let new_protein = protein.unwrap_or(food.protein);
@@ -459,43 +2230,453 @@ impl Database {
         let new_calories = if let Some(c) = calories {
             c
         } else {
-            (new_protein * 4.0) + (new_fat * 9.0) + (new_carbs * 4.0)
+            self.calories_from_macros(new_protein, new_fat, new_carbs, fiber.unwrap_or(food.fiber))
         };
 
         updates.push("calories = ?");
         params_vec.push(Box::new(new_calories));
 
         if updates.is_empty() {
-            return Ok(());
+            return Ok(Vec::new());
         }
 
-        // Add the name parameter for WHERE clause
-        params_vec.push(Box::new(name.to_string()));
+        // Add the id parameter for WHERE clause (the name may have been an alias)
+        let food_id = food.id.unwrap();
+        params_vec.push(Box::new(food_id));
 
-        let query = format!(
-            "UPDATE foods SET {} WHERE LOWER(name) = LOWER(?)",
-            updates.join(", ")
-        );
+        let query = format!("UPDATE foods SET {} WHERE id = ?", updates.join(", "));
 
         let params_refs: Vec<&dyn rusqlite::ToSql> =
             params_vec.iter().map(|p| p.as_ref()).collect();
 
-        self.conn.execute(&query, params_refs.as_slice())?;
-        Ok(())
+        // A compound that can no longer be worked out keeps the edit out too
+        self.atomically(|db| {
+            let before = db.snapshot_row("foods", food_id)?;
+            retry_busy(|| db.conn.execute(&query, params_refs.as_slice()))?;
+            let group = db.record_change(None, "update", "foods", food_id, before)?;
+            db.refresh_compounds_using(food_id, group)
+        })
+    }
+
+    /// Bring the food entry of every compound food made with `food_id`,
+    /// directly or through another compound, up to date with its
+    /// components. The updates join undo group `group`. Returns the names
+    /// of the compounds refreshed.
+    fn refresh_compounds_using(&self, food_id: i64, group: i64) -> Result<Vec<String>> {
+        let mut refreshed: Vec<String> = Vec::new();
+        let mut changed = vec![food_id];
+        while let Some(food_id) = changed.pop() {
+            let mut stmt = self.conn.prepare(
+                "SELECT DISTINCT c.name FROM compound_food_items ci
+                 JOIN compound_foods c ON c.id = ci.compound_food_id
+                 WHERE ci.food_id = ?1 ORDER BY c.name",
+            )?;
+            let names: Vec<String> = stmt
+                .query_map(params![food_id], |row| row.get(0))?
+                .collect::<rusqlite::Result<_>>()?;
+            for name in names {
+                if refreshed.contains(&name) {
+                    continue;
+                }
+                let refresh = self.refresh_compound(&name).map_err(|e| {
+                    anyhow::anyhow!(
+                        "{} couldn't be recalculated ({}); nothing was changed",
+                        name,
+                        e
+                    )
+                })?;
+                if let Some((id, before)) = refresh {
+                    self.record_change(Some(group), "update", "foods", id, before)?;
+                    changed.push(id);
+                }
+                refreshed.push(name);
+            }
+        }
+        Ok(refreshed)
     }
 
     pub fn search_food(&self, name: &str) -> Result<Option<Food>> {
         self.get_food_by_name(name)
     }
 
-    pub fn delete_food(&self, name: &str) -> Result<()> {
-        self.conn.execute(
-            "DELETE FROM foods WHERE LOWER(name) = LOWER(?1)",
-            params![name],
+    /// Move a food to the trash along with its aliases, tags, compound
+    /// recipe, and prep batches. Log entries keep pointing at its id, so a
+    /// restore brings the history back too.
+    pub fn delete_food(&self, name: &str, mode: DeleteMode) -> Result<()> {
+        self.purge_trash()?;
+        let foods: Vec<(i64, String)> = self
+            .conn
+            .prepare("SELECT id, name FROM foods WHERE LOWER(name) = LOWER(?1)")?
+            .query_map(params![name], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        if mode == DeleteMode::Refuse {
+            for (id, name) in &foods {
+                let (log_entries, compounds) = self.food_uses(*id)?;
+                if log_entries > 0 || !compounds.is_empty() {
+                    return Err(FoodInUse {
+                        name: name.clone(),
+                        log_entries,
+                        compounds,
+                    }
+                    .into());
+                }
+            }
+        }
+
+        // Kept log entries hold on to the food's id while it's in the trash
+        self.without_foreign_keys(|| self.trash_foods(foods, mode))
+    }
+
+    /// How many log entries use a food, and which compound foods contain it.
+    fn food_uses(&self, food_id: i64) -> Result<(i64, Vec<String>)> {
+        let log_entries = self.conn.query_row(
+            "SELECT COUNT(*) FROM log WHERE food_id = ?1",
+            params![food_id],
+            |row| row.get(0),
         )?;
+        let compounds = self
+            .conn
+            .prepare(
+                "SELECT DISTINCT c.name FROM compound_food_items ci
+                 JOIN compound_foods c ON c.id = ci.compound_food_id
+                 WHERE ci.food_id = ?1
+                 ORDER BY c.name",
+            )?
+            .query_map(params![food_id], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok((log_entries, compounds))
+    }
+
+    fn trash_foods(&self, foods: Vec<(i64, String)>, mode: DeleteMode) -> Result<()> {
+        let tx = self.savepoint()?;
+        for (id, name) in foods {
+            let ids = |sql: &str, param: &dyn rusqlite::ToSql| -> Result<Vec<i64>> {
+                let mut stmt = self.conn.prepare(sql)?;
+                let ids = stmt
+                    .query_map([param], |row| row.get(0))?
+                    .collect::<rusqlite::Result<_>>()?;
+                Ok(ids)
+            };
+            let compounds = ids(
+                "SELECT id FROM compound_foods WHERE LOWER(name) = LOWER(?1)",
+                &name,
+            )?;
+            // Dependent rows first and the food last, so that restoring in
+            // reverse (as undo does) never inserts a row before its parent
+            let mut related: Vec<(&str, i64)> = Vec::new();
+            for (table, sql) in [
+                ("aliases", "SELECT id FROM aliases WHERE food_id = ?1"),
+                ("food_tags", "SELECT id FROM food_tags WHERE food_id = ?1"),
+                ("food_units", "SELECT id FROM food_units WHERE food_id = ?1"),
+                ("pantry", "SELECT id FROM pantry WHERE food_id = ?1"),
+                (
+                    "prep_batches",
+                    "SELECT id FROM prep_batches WHERE food_id = ?1",
+                ),
+                (
+                    "compound_food_items",
+                    "SELECT id FROM compound_food_items WHERE food_id = ?1",
+                ),
+            ] {
+                related.extend(ids(sql, &id)?.into_iter().map(|row| (table, row)));
+            }
+            if mode == DeleteMode::Cascade {
+                related.extend(
+                    ids("SELECT id FROM log WHERE food_id = ?1", &id)?
+                        .into_iter()
+                        .map(|row| ("log", row)),
+                );
+            }
+            for compound in compounds {
+                related.extend(
+                    ids(
+                        "SELECT id FROM compound_food_items WHERE compound_food_id = ?1",
+                        &compound,
+                    )?
+                    .into_iter()
+                    .map(|row| ("compound_food_items", row)),
+                );
+                related.push(("compound_foods", compound));
+            }
+            related.push(("foods", id));
+
+            let mut rows = Vec::new();
+            let mut group = None;
+            for (table, row_id) in related {
+                let before = self.snapshot_row(table, row_id)?;
+                rows.push(serde_json::json!({"table": table, "row": before}));
+                retry_busy(|| {
+                    self.conn.execute(
+                        &format!("DELETE FROM {} WHERE id = ?1", table),
+                        params![row_id],
+                    )
+                })?;
+                group = Some(self.record_change(group, "delete", table, row_id, before)?);
+            }
+            let rows = serde_json::Value::Array(rows).to_string();
+            retry_busy(|| {
+                self.conn.execute(
+                    "INSERT INTO trash (food_id, name, rows) VALUES (?1, ?2, ?3)",
+                    params![id, name, rows],
+                )
+            })?;
+            let trash_id = self.conn.last_insert_rowid();
+            self.record_change(group, "insert", "trash", trash_id, None)?;
+        }
+        tx.commit()?;
         Ok(())
     }
 
+    // ── Trash ────────────────────────────────────────────────────
+
+    /// Foods in the trash, most recently deleted first.
+    pub fn list_trash(&self) -> Result<Vec<TrashedFood>> {
+        self.purge_trash()?;
+        let mut stmt = self.conn.prepare(
+            "SELECT t.id, t.name, t.deleted_at,
+                    (SELECT COUNT(*) FROM log l WHERE l.food_id = t.food_id)
+                    + (SELECT COUNT(*) FROM json_each(t.rows)
+                       WHERE json_extract(value, '$.table') = 'log')
+             FROM trash t ORDER BY t.id DESC",
+        )?;
+        let foods = stmt
+            .query_map([], |row| {
+                Ok(TrashedFood {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    deleted_at: row.get(2)?,
+                    log_entries: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(foods)
+    }
+
+    /// Put the most recently trashed food called `name` back, with everything
+    /// that was moved aside with it.
+    pub fn restore_food(&self, name: &str) -> Result<TrashedFood> {
+        let trashed = self
+            .list_trash()?
+            .into_iter()
+            .find(|t| t.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| anyhow::anyhow!("'{}' is not in the trash", name))?;
+        if self.get_food_by_name(&trashed.name)?.is_some() {
+            anyhow::bail!(
+                "A food named '{}' already exists; rename or delete it first",
+                trashed.name
+            );
+        }
+        let snapshot = self.snapshot_row("trash", trashed.id)?;
+        let rows: Vec<serde_json::Value> = serde_json::from_str(
+            snapshot
+                .as_ref()
+                .and_then(|s| s["rows"].as_str())
+                .unwrap_or("[]"),
+        )?;
+
+        let tx = self.savepoint()?;
+        let group = self.record_change(None, "delete", "trash", trashed.id, snapshot)?;
+        retry_busy(|| {
+            self.conn
+                .execute("DELETE FROM trash WHERE id = ?1", params![trashed.id])
+        })?;
+        for entry in rows.iter().rev() {
+            let table = entry["table"]
+                .as_str()
+                .ok_or_else(|| anyhow::anyhow!("Corrupt trash entry for {}", trashed.name))?;
+            self.restore_row(table, &entry["row"])?;
+            let row_id = entry["row"]["id"].as_i64().unwrap_or_default();
+            self.record_change(Some(group), "insert", table, row_id, None)?;
+        }
+        tx.commit()?;
+        Ok(trashed)
+    }
+
+    /// Run `f` with foreign key checks off, so rows can be moved aside while
+    /// others still point at them. SQLite ignores the switch inside a
+    /// transaction, so wrap `atomically` in this, not the other way round.
+    pub(crate) fn without_foreign_keys<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        self.conn.pragma_update(None, "foreign_keys", false)?;
+        let result = f();
+        self.conn.pragma_update(None, "foreign_keys", true)?;
+        result
+    }
+
+    /// Drop trash entries older than `TRASH_DAYS`. Log entries kept from
+    /// those foods lose their food for good, and show as 'deleted'.
+    fn purge_trash(&self) -> Result<usize> {
+        let age = format!("-{} days", TRASH_DAYS);
+        let tx = self.savepoint()?;
+        retry_busy(|| {
+            self.conn.execute(
+                "UPDATE log SET food_id = NULL
+                 WHERE food_id IN (SELECT food_id FROM trash WHERE deleted_at < datetime('now', ?1))",
+                params![age],
+            )
+        })?;
+        let purged = retry_busy(|| {
+            self.conn.execute(
+                "DELETE FROM trash WHERE deleted_at < datetime('now', ?1)",
+                params![age],
+            )
+        })?;
+        tx.commit()?;
+        Ok(purged)
+    }
+
+    /// Per-period averages of logged days since `since` (YYYY-MM-DD), read
+    /// from `daily_totals` so the raw log is never scanned. `resolution` is
+    /// day, week, or month. When incomplete days are excluded, periods made
+    /// up only of them are dropped.
+    pub fn rollups(&self, since: &str, resolution: &str) -> Result<Vec<Rollup>> {
+        let period = match resolution {
+            "day" => "date",
+            "week" => "date(date, 'weekday 0', '-6 days')",
+            "month" => "substr(date, 1, 7)",
+            other => anyhow::bail!("Unknown resolution '{}' (use day, week, or month)", other),
+        };
+        let carbs = match self.carb_mode {
+            CarbMode::Total => "carbs",
+            CarbMode::Net => "MAX(carbs - fiber, 0)",
+        };
+        let counted = if self.incomplete_days.exclude {
+            "calories >= ?2"
+        } else {
+            "1"
+        };
+        let avg = |column: &str| format!("AVG(CASE WHEN {counted} THEN {column} END)");
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {period} AS period, SUM({counted}), {}, {}, {}, {}, SUM(calories < ?2),
+                    {}, {}, {}
+             FROM daily_totals
+             WHERE date >= ?1
+             GROUP BY period
+             HAVING SUM({counted}) > 0
+             ORDER BY period",
+            avg("protein"),
+            avg("fat"),
+            avg(carbs),
+            avg("calories"),
+            avg("sugar"),
+            avg("sodium"),
+            avg("saturated_fat"),
+        ))?;
+        let rollups = stmt
+            .query_map(params![since, self.incomplete_days.below], |row| {
+                Ok(Rollup {
+                    period: row.get(0)?,
+                    days: row.get(1)?,
+                    incomplete: row.get(6)?,
+                    protein: row.get(2)?,
+                    fat: row.get(3)?,
+                    carbs: row.get(4)?,
+                    calories: row.get(5)?,
+                    micros: micros_from_row(row, 7)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(rollups)
+    }
+
+    /// Days in the `days` before today with no entries or fewer calories
+    /// than the incomplete-day threshold, newest first. Days before the
+    /// first entry ever logged aren't counted.
+    pub fn missing_days(&self, days: u32) -> Result<Vec<MissingDay>> {
+        let today = Local::now().date_naive();
+        let Some(first_logged) = self
+            .conn
+            .query_row("SELECT MIN(date) FROM log", [], |row| {
+                row.get::<_, Option<String>>(0)
+            })?
+            .and_then(|d| chrono::NaiveDate::parse_from_str(&d, "%Y-%m-%d").ok())
+        else {
+            return Ok(Vec::new());
+        };
+        let since = (today - chrono::Duration::days(days.into())).max(first_logged);
+
+        let mut stmt = self.conn.prepare(
+            "SELECT date, entries, calories FROM daily_totals WHERE date >= ?1 AND date < ?2",
+        )?;
+        let logged: std::collections::HashMap<String, (i64, f64)> = stmt
+            .query_map(params![since.to_string(), today.to_string()], |row| {
+                Ok((row.get(0)?, (row.get(1)?, row.get(2)?)))
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let mut missing: Vec<MissingDay> = since
+            .iter_days()
+            .take_while(|d| *d < today)
+            .map(|d| d.to_string())
+            .filter_map(|date| {
+                let (entries, calories) = logged.get(&date).copied().unwrap_or((0, 0.0));
+                (calories < self.incomplete_days.below).then_some(MissingDay {
+                    date,
+                    entries,
+                    calories,
+                })
+            })
+            .collect();
+        missing.reverse();
+        Ok(missing)
+    }
+
+    /// Foods logged from `from` to `to` (inclusive), by calories contributed.
+    pub fn top_foods(&self, from: &str, to: &str, limit: usize) -> Result<Vec<FoodTally>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT COALESCE(f.name, 'deleted'), COUNT(*), SUM(l.calories) AS total
+             FROM log l
+             LEFT JOIN foods f ON l.food_id = f.id
+             WHERE l.date BETWEEN ?1 AND ?2
+             GROUP BY l.food_id
+             ORDER BY total DESC, COUNT(*) DESC
+             LIMIT ?3",
+        )?;
+        let tallies = stmt
+            .query_map(params![from, to, limit as i64], |row| {
+                Ok(FoodTally {
+                    name: row.get(0)?,
+                    times: row.get(1)?,
+                    calories: row.get(2)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(tallies)
+    }
+
+    /// Every food logged from `from` to `to` (inclusive) with its summed
+    /// macros, most calories first.
+    pub fn macro_sources(&self, from: &str, to: &str) -> Result<Vec<MacroSource>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT COALESCE(f.name, 'deleted'), COUNT(*),
+                    SUM(l.protein), SUM(l.fat), SUM(l.carbs), SUM(l.calories), SUM(l.fiber),
+                    SUM(l.sugar), SUM(l.sodium), SUM(l.saturated_fat)
+             FROM log l
+             LEFT JOIN foods f ON l.food_id = f.id
+             WHERE l.date BETWEEN ?1 AND ?2
+             GROUP BY l.food_id
+             ORDER BY SUM(l.calories) DESC, COUNT(*) DESC",
+        )?;
+        let sources = stmt
+            .query_map(params![from, to], |row| {
+                Ok(MacroSource {
+                    name: row.get(0)?,
+                    times: row.get(1)?,
+                    totals: Macros {
+                        protein: row.get(2)?,
+                        fat: row.get(3)?,
+                        carbs: row.get(4)?,
+                        calories: row.get(5)?,
+                        fiber: row.get(6)?,
+                        micros: micros_from_row(row, 7)?,
+                    },
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(sources)
+    }
+
     pub fn get_stats(&self) -> Result<Stats> {
         let food_count: i64 = self
             .conn
@@ -523,39 +2704,295 @@ impl Database {
         })
     }
 
-    pub fn export_csv(&self) -> Result<()> {
-        let mut stmt = self.conn.prepare(
-            "SELECT l.date, f.name, l.amount, l.protein, l.fat, l.carbs, l.calories
+    /// Timestamp (UTC) of the most recently created log entry, if any.
+    #[cfg(any(feature = "sse", test))]
+    pub fn get_last_logged_at(&self) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
+        let ts: Option<String> =
+            self.conn
+                .query_row("SELECT MAX(created_at) FROM log", [], |row| row.get(0))?;
+        Ok(ts.and_then(|t| {
+            chrono::NaiveDateTime::parse_from_str(&t, "%Y-%m-%d %H:%M:%S")
+                .ok()
+                .map(|dt| dt.and_utc())
+        }))
+    }
+
+    /// With `anonymize`, food names other than the built-in starter foods are
+    /// replaced by a stable hash (see `Anonymizer`).
+    pub fn export_csv<W: std::io::Write>(&self, out: W, export: &CsvExport) -> Result<()> {
+        if let Some(unknown) = export.columns.iter().find(|c| {
+            !CSV_COLUMNS.contains(&c.as_str()) && !EXTRA_CSV_COLUMNS.contains(&c.as_str())
+        }) {
+            anyhow::bail!(
+                "Unknown column '{}' (use {}, {})",
+                unknown,
+                CSV_COLUMNS.join(", "),
+                EXTRA_CSV_COLUMNS.join(", ")
+            );
+        }
+        let anon = export
+            .anonymize
+            .then(|| Anonymizer::new(self))
+            .transpose()?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT l.id, l.date, COALESCE(f.name, 'deleted'), l.amount, l.protein, l.fat,
+                    l.carbs, l.calories, l.fiber, l.created_at, f.serving, l.meal, {UNITS_COLUMN}
              FROM log l
-             JOIN foods f ON l.food_id = f.id
-             ORDER BY l.date, l.id",
-        )?;
+             LEFT JOIN foods f ON l.food_id = f.id
+             WHERE l.date >= ?1
+             ORDER BY l.date, l.id"
+        ))?;
+
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(export.delimiter)
+            .from_writer(out);
+        writer.write_record(&export.columns)?;
+
+        let mut rows = stmt.query(params![export.since.as_deref().unwrap_or("")])?;
+        while let Some(row) = rows.next()? {
+            // Full precision, so an import gets back exactly what was logged
+            let number =
+                |i: usize| -> rusqlite::Result<String> { Ok(row.get::<_, f64>(i)?.to_string()) };
+            let mut record = Vec::with_capacity(export.columns.len());
+            for column in &export.columns {
+                record.push(match column.as_str() {
+                    "id" => row.get::<_, i64>(0)?.to_string(),
+                    "date" => row.get(1)?,
+                    "food" => export_name(anon.as_ref(), row.get(2)?),
+                    "amount" => row.get(3)?,
+                    "protein" => number(4)?,
+                    "fat" => number(5)?,
+                    "carbs" => number(6)?,
+                    "calories" => number(7)?,
+                    "fiber" => number(8)?,
+                    "meal" => row.get::<_, Option<String>>(11)?.unwrap_or_default(),
+                    "grams" => export_grams(
+                        &row.get::<_, String>(3)?,
+                        row.get(10)?,
+                        &parse_units(row, 12)?,
+                    )
+                    .map(|g| g.to_string())
+                    .unwrap_or_default(),
+                    _ => row.get::<_, Option<String>>(9)?.unwrap_or_default(),
+                });
+            }
+            writer.write_record(&record)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
 
-        println!("date,food,amount,protein,fat,carbs,calories");
+    pub fn export_json(&self, anonymize: bool) -> Result<()> {
+        let anon = anonymize.then(|| Anonymizer::new(self)).transpose()?;
+        let mut foods = std::collections::HashMap::new();
+        let mut values = Vec::new();
+        for mut entry in self.get_history(365)? {
+            let food = match foods.entry(entry.food_id) {
+                std::collections::hash_map::Entry::Occupied(e) => e.into_mut(),
+                std::collections::hash_map::Entry::Vacant(e) => {
+                    let food = match entry.food_id {
+                        Some(id) => self.get_food_by_id(id)?,
+                        None => None,
+                    };
+                    e.insert(food)
+                }
+            };
+            let grams = match food {
+                Some(food) => export_grams(&entry.amount, Some(food.serving.clone()), &food.units),
+                None => None,
+            };
+            entry.food_name = export_name(anon.as_ref(), std::mem::take(&mut entry.food_name));
+            let mut value = serde_json::to_value(&entry)?;
+            value["grams"] = grams.into();
+            values.push(value);
+        }
+        println!("{}", serde_json::to_string_pretty(&values)?);
+        Ok(())
+    }
 
+    /// Stream every food and log entry as newline-delimited JSON, one object
+    /// per line tagged with `"type": "food"` or `"type": "log"`. Rows are
+    /// written as they are read, so memory use stays flat on large databases.
+    pub fn export_ndjson<W: std::io::Write>(&self, mut out: W, anonymize: bool) -> Result<()> {
+        let anon = anonymize.then(|| Anonymizer::new(self)).transpose()?;
+        let mut stmt = self.conn.prepare(
+            "SELECT f.id, f.name, f.protein, f.fat, f.carbs, f.calories, f.serving, f.default_amount,
+                    (SELECT json_group_array(a.alias) FROM aliases a WHERE a.food_id = f.id), f.fiber,
+                    f.sugar, f.sodium, f.saturated_fat,
+                    (SELECT json_group_object(u.unit, u.grams) FROM food_units u WHERE u.food_id = f.id)
+             FROM foods f
+             ORDER BY f.id",
+        )?;
         let mut rows = stmt.query([])?;
         while let Some(row) = rows.next()? {
-            let date: String = row.get(0)?;
-            let name: String = row.get(1)?;
-            let amount: String = row.get(2)?;
-            let protein: f64 = row.get(3)?;
-            let fat: f64 = row.get(4)?;
-            let carbs: f64 = row.get(5)?;
-            let calories: f64 = row.get(6)?;
+            let aliases: String = row.get(8)?;
+            let mut food = Food {
+                id: Some(row.get(0)?),
+                name: row.get(1)?,
+                protein: row.get(2)?,
+                fat: row.get(3)?,
+                carbs: row.get(4)?,
+                calories: row.get(5)?,
+                serving: row.get(6)?,
+                default_amount: row.get(7)?,
+                aliases: serde_json::from_str(&aliases)?,
+                fiber: row.get(9)?,
+                micros: micros_from_row(row, 10)?,
+                units: parse_units(row, 13)?,
+            };
+            if let Some(anon) = &anon {
+                let name = anon.name(&food.name);
+                if name != food.name {
+                    // Aliases would give a hashed name away
+                    food.aliases.clear();
+                }
+                food.name = name;
+            }
+            let mut value = serde_json::to_value(&food)?;
+            value["type"] = "food".into();
+            serde_json::to_writer(&mut out, &value)?;
+            out.write_all(b"\n")?;
+        }
 
-            println!(
-                "{},{},{},{:.1},{:.1},{:.1},{:.0}",
-                date, name, amount, protein, fat, carbs, calories
-            );
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT l.id, l.date, COALESCE(f.name, 'deleted'), l.food_id, l.amount, l.protein, l.fat, l.carbs, l.calories,
+                    f.serving, l.recurring_id, l.meal, {UNITS_COLUMN}
+             FROM log l
+             LEFT JOIN foods f ON l.food_id = f.id
+             ORDER BY l.date, l.id"
+        ))?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let entry = LogEntry {
+                id: Some(row.get(0)?),
+                date: row.get(1)?,
+                food_name: export_name(anon.as_ref(), row.get(2)?),
+                food_id: row.get(3)?,
+                amount: row.get(4)?,
+                protein: row.get(5)?,
+                fat: row.get(6)?,
+                carbs: row.get(7)?,
+                calories: row.get(8)?,
+                recurring: row.get(10)?,
+                meal: row.get(11)?,
+                warning: None,
+            };
+            let mut value = serde_json::to_value(&entry)?;
+            value["grams"] =
+                export_grams(&entry.amount, row.get(9)?, &parse_units(row, 12)?).into();
+            value["type"] = "log".into();
+            serde_json::to_writer(&mut out, &value)?;
+            out.write_all(b"\n")?;
         }
 
+        out.flush()?;
         Ok(())
     }
 
-    pub fn export_json(&self) -> Result<()> {
-        let entries = self.get_history(365)?;
-        println!("{}", serde_json::to_string_pretty(&entries)?);
-        Ok(())
+    /// Write the full log, joined with each entry's food attributes, as a
+    /// Parquet file. Returns the number of rows written.
+    #[cfg(feature = "parquet")]
+    pub fn export_parquet(&self, path: &std::path::Path, anonymize: bool) -> Result<usize> {
+        use parquet::data_type::{ByteArray, ByteArrayType, DoubleType, Int64Type};
+        use parquet::file::properties::WriterProperties;
+        use parquet::file::writer::SerializedFileWriter;
+        use parquet::schema::parser::parse_message_type;
+        use std::sync::Arc;
+
+        const SCHEMA: &str = "message log {
+            required int64 id;
+            required binary date (UTF8);
+            optional binary created_at (UTF8);
+            optional int64 food_id;
+            optional binary food_name (UTF8);
+            required binary amount (UTF8);
+            optional double grams;
+            required double protein;
+            required double fat;
+            required double carbs;
+            required double calories;
+            optional binary food_serving (UTF8);
+            optional binary food_default_amount (UTF8);
+            optional double food_protein;
+            optional double food_fat;
+            optional double food_carbs;
+            optional double food_calories;
+        }";
+
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT l.id, l.date, l.created_at, l.food_id, f.name, l.amount,
+                    l.protein, l.fat, l.carbs, l.calories,
+                    f.serving, f.default_amount, f.protein, f.fat, f.carbs, f.calories,
+                    {UNITS_COLUMN}
+             FROM log l
+             LEFT JOIN foods f ON l.food_id = f.id
+             ORDER BY l.date, l.id"
+        ))?;
+
+        let anon = anonymize.then(|| Anonymizer::new(self)).transpose()?;
+
+        // Column-major buffers, in schema order within each type
+        let mut ints: [Vec<Option<i64>>; 2] = Default::default();
+        let mut texts: [Vec<Option<String>>; 6] = Default::default();
+        let mut reals: [Vec<Option<f64>>; 8] = Default::default();
+        let mut grams: Vec<Option<f64>> = Vec::new();
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            ints[0].push(row.get(0)?);
+            texts[0].push(row.get(1)?);
+            texts[1].push(row.get(2)?);
+            ints[1].push(row.get(3)?);
+            let name: Option<String> = row.get(4)?;
+            texts[2].push(name.map(|n| export_name(anon.as_ref(), n)));
+            let amount: String = row.get(5)?;
+            grams.push(export_grams(&amount, row.get(10)?, &parse_units(row, 16)?));
+            texts[3].push(Some(amount));
+            for (i, col) in reals.iter_mut().take(4).enumerate() {
+                col.push(row.get(6 + i)?);
+            }
+            texts[4].push(row.get(10)?);
+            texts[5].push(row.get(11)?);
+            for (i, col) in reals.iter_mut().skip(4).enumerate() {
+                col.push(row.get(12 + i)?);
+            }
+        }
+        let count = ints[0].len();
+
+        let file = std::fs::File::create(path)
+            .map_err(|e| anyhow::anyhow!("Failed to create {}: {}", path.display(), e))?;
+        let props = Arc::new(WriterProperties::builder().build());
+        let mut writer =
+            SerializedFileWriter::new(file, Arc::new(parse_message_type(SCHEMA)?), props)?;
+        let mut row_group = writer.next_row_group()?;
+
+        let [id, food_id] = ints;
+        let [date, created_at, food_name, amount, serving, default_amount] = texts;
+        let text = |col: Vec<Option<String>>| -> Vec<Option<ByteArray>> {
+            col.into_iter()
+                .map(|v| v.map(|s| ByteArray::from(s.into_bytes())))
+                .collect()
+        };
+        write_parquet_column::<Int64Type>(&mut row_group, id)?;
+        write_parquet_column::<ByteArrayType>(&mut row_group, text(date))?;
+        write_parquet_column::<ByteArrayType>(&mut row_group, text(created_at))?;
+        write_parquet_column::<Int64Type>(&mut row_group, food_id)?;
+        write_parquet_column::<ByteArrayType>(&mut row_group, text(food_name))?;
+        write_parquet_column::<ByteArrayType>(&mut row_group, text(amount))?;
+        write_parquet_column::<DoubleType>(&mut row_group, grams)?;
+        let [protein, fat, carbs, calories, food_protein, food_fat, food_carbs, food_calories] =
+            reals;
+        for col in [protein, fat, carbs, calories] {
+            write_parquet_column::<DoubleType>(&mut row_group, col)?;
+        }
+        write_parquet_column::<ByteArrayType>(&mut row_group, text(serving))?;
+        write_parquet_column::<ByteArrayType>(&mut row_group, text(default_amount))?;
+        for col in [food_protein, food_fat, food_carbs, food_calories] {
+            write_parquet_column::<DoubleType>(&mut row_group, col)?;
+        }
+
+        row_group.close()?;
+        writer.close()?;
+        Ok(count)
     }
 
     pub fn import_usda(&self) -> Result<()> {
@@ -597,9 +3034,10 @@ impl Database {
             }
         }
 
-        // Nutrient IDs: 1003=protein, 1004=fat, 1005=carbs, 1008=calories
-        // Parse nutrients: fdc_id -> (protein, fat, carbs, calories)
-        let mut nutrients: std::collections::HashMap<String, (f64, f64, f64, f64)> =
+        // Nutrient IDs: 1003=protein, 1004=fat, 1005=carbs, 1008=calories,
+        // 1079=fiber, 2000=sugars, 1093=sodium (mg), 1258=saturated fat
+        // Parse nutrients: fdc_id -> macros per 100g
+        let mut nutrients: std::collections::HashMap<String, Macros> =
             std::collections::HashMap::new();
         let mut nut_reader = csv::Reader::from_reader(nutrient_csv.as_bytes());
         for record in nut_reader.records() {
@@ -608,12 +3046,16 @@ impl Database {
             let nutrient_id = record.get(2).unwrap_or("");
             let amount: f64 = record.get(3).unwrap_or("0").parse().unwrap_or(0.0);
 
-            let entry = nutrients.entry(fdc_id).or_insert((0.0, 0.0, 0.0, 0.0));
+            let entry = nutrients.entry(fdc_id).or_default();
             match nutrient_id {
-                "1003" => entry.0 = amount,
-                "1004" => entry.1 = amount,
-                "1005" => entry.2 = amount,
-                "1008" => entry.3 = amount,
+                "1003" => entry.protein = amount,
+                "1004" => entry.fat = amount,
+                "1005" => entry.carbs = amount,
+                "1008" => entry.calories = amount,
+                "1079" => entry.fiber = amount,
+                "2000" => entry.micros.sugar = Some(amount),
+                "1093" => entry.micros.sodium = Some(amount),
+                "1258" => entry.micros.saturated_fat = Some(amount),
                 _ => {}
             }
         }
@@ -621,11 +3063,13 @@ impl Database {
         // Filter to foods that have all macros and reasonable names
         println!("Importing foods...");
         let mut count = 0;
+        let mut updated = 0;
 
-        self.conn.execute("BEGIN", [])?;
+        retry_busy(|| self.conn.execute("BEGIN", []))?;
 
         for (fdc_id, name) in &foods {
-            if let Some(&(protein, fat, carbs, calories)) = nutrients.get(fdc_id) {
+            if let Some(n) = nutrients.get(fdc_id) {
+                let (protein, fat, carbs, calories) = (n.protein, n.fat, n.carbs, n.calories);
                 // Skip foods with no nutritional data
                 if protein == 0.0 && fat == 0.0 && carbs == 0.0 && calories == 0.0 {
                     continue;
@@ -649,31 +3093,51 @@ impl Database {
                     .collect::<Vec<_>>()
                     .join(" ");
 
-                let result = self.conn.execute(
-                    "INSERT OR IGNORE INTO foods (name, protein, fat, carbs, calories, serving)
-                     VALUES (?1, ?2, ?3, ?4, ?5, '100g')",
-                    params![title_name, protein, fat, carbs, calories],
-                );
-
-                if let Ok(changes) = result {
-                    if changes > 0 {
-                        count += 1;
-                    }
+                let mut food =
+                    Food::new(&title_name, protein, fat, carbs, calories, "100g", vec![]);
+                food.fiber = n.fiber;
+                food.micros = n.micros;
+                food.assume_serving();
+                match self.import_food(&food, Some(&format!("fdc:{}", fdc_id))) {
+                    Ok(ImportOutcome::Added) => count += 1,
+                    Ok(ImportOutcome::Updated) => updated += 1,
+                    _ => {}
                 }
             }
         }
 
-        self.conn.execute("COMMIT", [])?;
+        retry_busy(|| self.conn.execute("COMMIT", []))?;
 
-        println!("Imported {} foods from USDA SR Legacy", count);
+        println!(
+            "Imported {} foods from USDA SR Legacy ({} updated)",
+            count, updated
+        );
         Ok(())
     }
 
+    /// Import foods from a CSV file (name, protein, fat, carbs, calories,
+    /// serving). A file with `date` and `food` columns is one of our own log
+    /// exports and is re-ingested as log entries instead.
     pub fn import_csv(&self, path: &str) -> Result<()> {
-        let mut reader = csv::Reader::from_path(path)
-            .map_err(|e| anyhow::anyhow!("Failed to open CSV file: {}", e))?;
+        let data =
+            std::fs::read(path).map_err(|e| anyhow::anyhow!("Failed to open CSV file: {}", e))?;
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(sniff_delimiter(&data))
+            .from_reader(data.as_slice());
+        let headers = reader.headers()?;
+        if headers.iter().any(|h| h.trim() == "date") && headers.iter().any(|h| h.trim() == "food")
+        {
+            let imported = self.import_log_csv(&data)?;
+            println!(
+                "Imported {} log entries ({} already present, {} foods created)",
+                imported.entries, imported.skipped, imported.foods_created
+            );
+            return Ok(());
+        }
 
+        let external_id_col = headers.iter().position(|h| h.trim() == "external_id");
         let mut count = 0;
+        let mut updated = 0;
         let mut skipped = 0;
 
         for record in reader.records() {
@@ -684,60 +3148,394 @@ impl Database {
             let fat: f64 = record.get(2).unwrap_or("0").parse().unwrap_or(0.0);
             let carbs: f64 = record.get(3).unwrap_or("0").parse().unwrap_or(0.0);
             let calories: f64 = record.get(4).unwrap_or("0").parse().unwrap_or(0.0);
-            let serving = record.get(5).unwrap_or("100g").trim().to_string();
+            let serving = record.get(5).map(str::trim).unwrap_or_default();
 
             if name.is_empty() {
                 continue;
             }
 
             let calories = if calories == 0.0 {
-                protein * 4.0 + fat * 9.0 + carbs * 4.0
+                self.calories_from_macros(protein, fat, carbs, 0.0)
             } else {
                 calories
             };
 
-            let result = self.conn.execute(
-                "INSERT OR IGNORE INTO foods (name, protein, fat, carbs, calories, serving)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-                params![name, protein, fat, carbs, calories, serving],
-            );
-
-            match result {
-                Ok(changes) if changes > 0 => count += 1,
-                Ok(_) => skipped += 1,
-                Err(_) => skipped += 1,
+            let external_id = external_id_col
+                .and_then(|i| record.get(i))
+                .map(str::trim)
+                .filter(|id| !id.is_empty());
+            let mut food = Food::new(&name, protein, fat, carbs, calories, "100g", vec![]);
+            if serving.is_empty() {
+                food.assume_serving();
+            } else {
+                food.serving = serving.to_string();
+            }
+            match self.import_food(&food, external_id) {
+                Ok(ImportOutcome::Added) => count += 1,
+                Ok(ImportOutcome::Updated) => updated += 1,
+                Ok(ImportOutcome::Unchanged) => {}
+                Ok(ImportOutcome::Skipped) | Err(_) => skipped += 1,
             }
         }
 
-        println!("Imported {} foods ({} skipped/duplicates)", count, skipped);
+        println!(
+            "Imported {} foods ({} updated, {} skipped/duplicates)",
+            count, updated, skipped
+        );
         Ok(())
     }
 
+    /// Add or refresh one food from an import source. With an `external_id`
+    /// (e.g. "fdc:171077", "off:737628064502", "mfp:1234") a re-import
+    /// updates the nutrition of the food it created last time, keeping any
+    /// rename; a same-named food from before ids were tracked is adopted when
+    /// its values match. Without one, foods whose name is taken are skipped.
+    pub fn import_food(&self, food: &Food, external_id: Option<&str>) -> Result<ImportOutcome> {
+        let insert = |external_id: Option<&str>| {
+            retry_busy(|| {
+                self.conn.execute(
+                    "INSERT OR IGNORE INTO foods (name, protein, fat, carbs, calories, serving, fiber, external_id, default_amount,
+                                                  sugar, sodium, saturated_fat)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+                    params![
+                        food.name,
+                        food.protein,
+                        food.fat,
+                        food.carbs,
+                        food.calories,
+                        food.serving,
+                        food.fiber,
+                        external_id,
+                        food.default_amount,
+                        food.micros.sugar,
+                        food.micros.sodium,
+                        food.micros.saturated_fat,
+                    ],
+                )
+            })
+            .map(|changes| {
+                if changes > 0 {
+                    ImportOutcome::Added
+                } else {
+                    ImportOutcome::Skipped
+                }
+            })
+        };
+        let Some(external_id) = external_id else {
+            return Ok(insert(None)?);
+        };
+
+        // Whether the macros match, and whether the micronutrients do too
+        let same = |row: &rusqlite::Row| -> rusqlite::Result<(i64, bool, bool)> {
+            Ok((
+                row.get(0)?,
+                row.get::<_, f64>(1)? == food.protein
+                    && row.get::<_, f64>(2)? == food.fat
+                    && row.get::<_, f64>(3)? == food.carbs
+                    && row.get::<_, f64>(4)? == food.calories
+                    && row.get::<_, f64>(5)? == food.fiber
+                    && row.get::<_, String>(6)? == food.serving,
+                micros_from_row(row, 7)? == food.micros,
+            ))
+        };
+        let columns =
+            "id, protein, fat, carbs, calories, fiber, serving, sugar, sodium, saturated_fat";
+        let set_micros = |id: i64| {
+            retry_busy(|| {
+                self.conn.execute(
+                    "UPDATE foods SET sugar = ?2, sodium = ?3, saturated_fat = ?4 WHERE id = ?1",
+                    params![
+                        id,
+                        food.micros.sugar,
+                        food.micros.sodium,
+                        food.micros.saturated_fat
+                    ],
+                )
+            })
+        };
+
+        let linked = self
+            .conn
+            .query_row(
+                &format!("SELECT {} FROM foods WHERE external_id = ?1", columns),
+                params![external_id],
+                same,
+            )
+            .optional()?;
+        if let Some((id, same_macros, same_micros)) = linked {
+            if same_macros && same_micros {
+                return Ok(ImportOutcome::Unchanged);
+            }
+            retry_busy(|| {
+                self.conn.execute(
+                    "UPDATE foods SET protein = ?2, fat = ?3, carbs = ?4, calories = ?5,
+                        fiber = ?6, serving = ?7
+                     WHERE id = ?1",
+                    params![
+                        id,
+                        food.protein,
+                        food.fat,
+                        food.carbs,
+                        food.calories,
+                        food.fiber,
+                        food.serving,
+                    ],
+                )
+            })?;
+            set_micros(id)?;
+            return Ok(ImportOutcome::Updated);
+        }
+
+        let legacy = self
+            .conn
+            .query_row(
+                &format!(
+                    "SELECT {} FROM foods WHERE name = ?1 AND external_id IS NULL",
+                    columns
+                ),
+                params![food.name],
+                same,
+            )
+            .optional()?;
+        match legacy {
+            Some((id, true, same_micros)) => {
+                retry_busy(|| {
+                    self.conn.execute(
+                        "UPDATE foods SET external_id = ?2 WHERE id = ?1",
+                        params![id, external_id],
+                    )
+                })?;
+                if same_micros {
+                    return Ok(ImportOutcome::Unchanged);
+                }
+                set_micros(id)?;
+                Ok(ImportOutcome::Updated)
+            }
+            Some((_, false, _)) => Ok(ImportOutcome::Skipped),
+            None => Ok(insert(Some(external_id))?),
+        }
+    }
+
+    /// Re-ingest a CSV written by `export_csv`, in any column order or
+    /// delimiter. Only `date` and `food` are required; missing macros are 0
+    /// and a missing calories column is derived from the macros. Foods are
+    /// matched by exact name and created from the row when absent. Rows
+    /// whose `created_at` matches an existing entry are skipped, so an export
+    /// that includes `created_at` can be imported again safely. All or
+    /// nothing: any bad row aborts the import.
+    pub fn import_log_csv(&self, data: &[u8]) -> Result<LogImport> {
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(sniff_delimiter(data))
+            .from_reader(data);
+        let headers = reader.headers()?.clone();
+        let column = |name: &str| headers.iter().position(|h| h.trim() == name);
+        let (Some(date_col), Some(food_col)) = (column("date"), column("food")) else {
+            anyhow::bail!("Log CSV needs 'date' and 'food' columns");
+        };
+        let [amount_col, protein_col, fat_col, carbs_col, calories_col, fiber_col, meal_col, created_col] =
+            [
+                "amount",
+                "protein",
+                "fat",
+                "carbs",
+                "calories",
+                "fiber",
+                "meal",
+                "created_at",
+            ]
+            .map(column);
+
+        let tx = self.conn.unchecked_transaction()?;
+        let mut group = None;
+        let mut imported = LogImport::default();
+        let mut occurrences: std::collections::HashMap<
+            (String, i64, String, String),
+            (usize, usize),
+        > = std::collections::HashMap::new();
+        for record in reader.records() {
+            let record = record?;
+            let line = record.position().map_or(0, |p| p.line());
+            let field =
+                |col: Option<usize>| col.and_then(|i| record.get(i)).map_or("", |v| v.trim());
+            let number = |col: Option<usize>| -> Result<f64> {
+                match field(col) {
+                    "" => Ok(0.0),
+                    v => v
+                        .parse()
+                        .map_err(|_| anyhow::anyhow!("Line {}: invalid number '{}'", line, v)),
+                }
+            };
+
+            let date = field(Some(date_col));
+            if chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").is_err() {
+                anyhow::bail!("Line {}: invalid date '{}'", line, date);
+            }
+            let name = field(Some(food_col));
+            if name.is_empty() {
+                anyhow::bail!("Line {}: missing food name", line);
+            }
+            let amount = field(amount_col);
+            let (protein, fat, carbs) =
+                (number(protein_col)?, number(fat_col)?, number(carbs_col)?);
+            let fiber = number(fiber_col)?;
+            let calories = match calories_col {
+                Some(_) => number(calories_col)?,
+                None => self.calories_from_macros(protein, fat, carbs, fiber),
+            };
+            let meal = Some(field(meal_col).to_lowercase()).filter(|m| !m.is_empty());
+            let created_at = Some(field(created_col)).filter(|c| !c.is_empty());
+            self.ensure_open(date)?;
+
+            let existing: Option<i64> = self
+                .conn
+                .query_row(
+                    "SELECT id FROM foods WHERE name = ?1",
+                    params![name],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            let food_id = match existing {
+                Some(id) => id,
+                None => {
+                    let serving = if amount.is_empty() {
+                        "1 serving"
+                    } else {
+                        amount
+                    };
+                    retry_busy(|| {
+                        self.conn.execute(
+                            "INSERT INTO foods (name, protein, fat, carbs, calories, serving, fiber)
+                             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                            params![name, protein, fat, carbs, calories, serving, fiber],
+                        )
+                    })?;
+                    let id = self.conn.last_insert_rowid();
+                    group = Some(self.record_change(group, "insert", "foods", id, None)?);
+                    imported.foods_created += 1;
+                    id
+                }
+            };
+
+            if let Some(created_at) = created_at {
+                // Identical entries logged in the same second are legitimate,
+                // so only skip as many rows as were logged before this import
+                let (seen, inserted) = occurrences
+                    .entry((
+                        date.to_string(),
+                        food_id,
+                        amount.to_string(),
+                        created_at.to_string(),
+                    ))
+                    .or_insert((0, 0));
+                *seen += 1;
+                let logged: usize = self.conn.query_row(
+                    "SELECT COUNT(*) FROM log
+                     WHERE date = ?1 AND food_id = ?2 AND amount = ?3 AND created_at = ?4",
+                    params![date, food_id, amount, created_at],
+                    |row| row.get(0),
+                )?;
+                if *seen <= logged - *inserted {
+                    imported.skipped += 1;
+                    continue;
+                }
+                *inserted += 1;
+            }
+
+            retry_busy(|| {
+                self.conn.execute(
+                    "INSERT INTO log (date, food_id, amount, protein, fat, carbs, calories, fiber, created_at, meal)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, COALESCE(?9, CURRENT_TIMESTAMP), ?10)",
+                    params![date, food_id, amount, protein, fat, carbs, calories, fiber, created_at, meal],
+                )
+            })?;
+            let id = self.conn.last_insert_rowid();
+            group = Some(self.record_change(group, "insert", "log", id, None)?);
+            imported.entries += 1;
+        }
+        tx.commit()?;
+        Ok(imported)
+    }
+
+    /// Import the starter pack of common foods bundled with the binary.
+    /// Foods whose name or alias already resolves are left alone, so this
+    /// is safe to re-run. Returns the number of foods added.
+    pub fn import_starter(&self) -> Result<usize> {
+        let mut reader = csv::Reader::from_reader(STARTER_FOODS.as_bytes());
+        let mut count = 0;
+
+        let tx = self.conn.unchecked_transaction()?;
+        for record in reader.records() {
+            let record = record?;
+            let field = |i: usize| record.get(i).unwrap_or("").trim();
+            let number = |i: usize| field(i).parse::<f64>().unwrap_or(0.0);
+
+            let name = field(0);
+            let aliases: Vec<&str> = field(7).split(';').filter(|a| !a.is_empty()).collect();
+            if self.get_food_by_name(name)?.is_some() {
+                continue;
+            }
+
+            let default_amount = Some(field(6)).filter(|d| !d.is_empty());
+            retry_busy(|| {
+                self.conn.execute(
+                    "INSERT INTO foods (name, protein, fat, carbs, calories, serving, default_amount)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![
+                        name,
+                        number(1),
+                        number(2),
+                        number(3),
+                        number(4),
+                        field(5),
+                        default_amount
+                    ],
+                )
+            })?;
+            let food_id = self.conn.last_insert_rowid();
+
+            // An alias the user already has for something else stays theirs
+            for alias in aliases {
+                if self.get_food_by_name(alias)?.is_none() {
+                    retry_busy(|| {
+                        self.conn.execute(
+                            "INSERT INTO aliases (food_id, alias) VALUES (?1, ?2)",
+                            params![food_id, alias],
+                        )
+                    })?;
+                }
+            }
+            count += 1;
+        }
+        tx.commit()?;
+        Ok(count)
+    }
+
+    fn get_log_entry(&self, id: i64) -> Result<LogEntry> {
+        self.conn
+            .query_row(
+                &format!(
+                    "SELECT {ENTRY_COLUMNS} FROM log l
+                     LEFT JOIN foods f ON l.food_id = f.id
+                     WHERE l.id = ?1"
+                ),
+                params![id],
+                entry_from_row,
+            )
+            .optional()?
+            .ok_or_else(|| anyhow::anyhow!("Log entry #{} not found", id))
+    }
+
     pub fn delete_log_entry(&self, id: i64) -> Result<LogEntry> {
         // Get the entry before deleting for confirmation
-        let entry: LogEntry = self.conn.query_row(
-            "SELECT l.id, l.date, f.name, l.food_id, l.amount, l.protein, l.fat, l.carbs, l.calories
-             FROM log l
-             JOIN foods f ON l.food_id = f.id
-             WHERE l.id = ?1",
-            params![id],
-            |row| {
-                Ok(LogEntry {
-                    id: Some(row.get(0)?),
-                    date: row.get(1)?,
-                    food_name: row.get(2)?,
-                    food_id: row.get(3)?,
-                    amount: row.get(4)?,
-                    protein: row.get(5)?,
-                    fat: row.get(6)?,
-                    carbs: row.get(7)?,
-                    calories: row.get(8)?,
-                })
-            },
-        )?;
+        let entry: LogEntry = self.get_log_entry(id)?;
+        self.ensure_open(&entry.date)?;
 
-        self.conn
-            .execute("DELETE FROM log WHERE id = ?1", params![id])?;
+        let before = self.snapshot_row("log", id)?;
+        retry_busy(|| {
+            self.conn
+                .execute("DELETE FROM log WHERE id = ?1", params![id])
+        })?;
+        self.record_change(None, "delete", "log", id, before)?;
         Ok(entry)
     }
 
@@ -761,26 +3559,9 @@ impl Database {
         carbs: Option<f64>,
     ) -> Result<LogEntry> {
         // Get the current entry
-        let entry: LogEntry = self.conn.query_row(
-            "SELECT l.id, l.date, f.name, l.food_id, l.amount, l.protein, l.fat, l.carbs, l.calories
-             FROM log l
-             JOIN foods f ON l.food_id = f.id
-             WHERE l.id = ?1",
-            params![id],
-            |row| {
-                Ok(LogEntry {
-                    id: Some(row.get(0)?),
-                    date: row.get(1)?,
-                    food_name: row.get(2)?,
-                    food_id: row.get(3)?,
-                    amount: row.get(4)?,
-                    protein: row.get(5)?,
-                    fat: row.get(6)?,
-                    carbs: row.get(7)?,
-                    calories: row.get(8)?,
-                })
-            },
-        )?;
+        let entry: LogEntry = self.get_log_entry(id)?;
+
+        self.ensure_open(&entry.date)?;
 
         // Build update query based on which fields are provided
         let mut updates = Vec::new();
@@ -790,7 +3571,12 @@ impl Database {
         let new_protein = protein.unwrap_or(entry.protein);
         let new_fat = fat.unwrap_or(entry.fat);
         let new_carbs = carbs.unwrap_or(entry.carbs);
-        let new_calories = (new_protein * 4.0) + (new_fat * 9.0) + (new_carbs * 4.0);
+        let fiber: f64 =
+            self.conn
+                .query_row("SELECT fiber FROM log WHERE id = ?1", [id], |row| {
+                    row.get(0)
+                })?;
+        let new_calories = self.calories_from_macros(new_protein, new_fat, new_carbs, fiber);
 
         if amount.is_some() {
             updates.push("amount = ?");
@@ -827,7 +3613,9 @@ impl Database {
         let params_refs: Vec<&dyn rusqlite::ToSql> =
             params_vec.iter().map(|p| p.as_ref()).collect();
 
-        self.conn.execute(&query, params_refs.as_slice())?;
+        let before = self.snapshot_row("log", id)?;
+        retry_busy(|| self.conn.execute(&query, params_refs.as_slice()))?;
+        self.record_change(None, "update", "log", id, before)?;
 
         // Return updated entry
         Ok(LogEntry {
@@ -840,22 +3628,58 @@ impl Database {
             fat: new_fat,
             carbs: new_carbs,
             calories: new_calories,
+            recurring: entry.recurring,
+            meal: entry.meal,
+            warning: None,
         })
     }
 
+    /// Set when an entry was logged (UTC), for generated history.
+    pub fn set_logged_at(&self, id: i64, at: chrono::NaiveDateTime) -> Result<()> {
+        retry_busy(|| {
+            self.conn.execute(
+                "UPDATE log SET created_at = ?1 WHERE id = ?2",
+                params![at.format("%Y-%m-%d %H:%M:%S").to_string(), id],
+            )
+        })?;
+        Ok(())
+    }
+
     // ── Water tracking ───────────────────────────────────────────
 
     pub fn log_water(&self, amount_ml: f64, date: Option<&str>) -> Result<WaterEntry> {
-        let date = date
-            .map(|d| d.to_string())
-            .unwrap_or_else(|| Local::now().format("%Y-%m-%d").to_string());
+        self.log_water_with(amount_ml, &LogOptions::on_date(date))
+    }
 
-        self.conn.execute(
-            "INSERT INTO water_log (date, amount_ml) VALUES (?1, ?2)",
-            params![date, amount_ml],
-        )?;
+    pub fn log_water_with(&self, amount_ml: f64, opts: &LogOptions) -> Result<WaterEntry> {
+        let date = opts.date_or_today();
+        self.ensure_open(&date)?;
+
+        let inserted = retry_busy(|| {
+            self.conn.execute(
+                "INSERT INTO water_log (date, amount_ml, idempotency_key) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(idempotency_key) WHERE idempotency_key IS NOT NULL DO NOTHING",
+                params![date, amount_ml, opts.idempotency_key],
+            )
+        })?;
+
+        if inserted == 0 {
+            let entry = self.conn.query_row(
+                "SELECT id, date, amount_ml FROM water_log WHERE idempotency_key = ?1",
+                params![opts.idempotency_key],
+                |row| {
+                    Ok(WaterEntry {
+                        id: Some(row.get(0)?),
+                        date: row.get(1)?,
+                        amount_ml: row.get(2)?,
+                    })
+                },
+            )?;
+            return Ok(entry);
+        }
 
         let id = self.conn.last_insert_rowid();
+        self.record_change(None, "insert", "water_log", id, None)?;
         Ok(WaterEntry {
             id: Some(id),
             date,
@@ -864,7 +3688,10 @@ impl Database {
     }
 
     pub fn get_today_water(&self) -> Result<WaterTotals> {
-        let date = Local::now().format("%Y-%m-%d").to_string();
+        self.get_water_for_date(&Local::now().format("%Y-%m-%d").to_string())
+    }
+
+    pub fn get_water_for_date(&self, date: &str) -> Result<WaterTotals> {
         let total_ml: f64 = self.conn.query_row(
             "SELECT COALESCE(SUM(amount_ml), 0) FROM water_log WHERE date = ?1",
             params![date],
@@ -912,11 +3739,17 @@ impl Database {
                 })
             },
         )?;
-        self.conn
-            .execute("DELETE FROM water_log WHERE id = ?1", params![id])?;
+        self.ensure_open(&entry.date)?;
+        let before = self.snapshot_row("water_log", id)?;
+        retry_busy(|| {
+            self.conn
+                .execute("DELETE FROM water_log WHERE id = ?1", params![id])
+        })?;
+        self.record_change(None, "delete", "water_log", id, before)?;
         Ok(entry)
     }
 
+    #[cfg(feature = "sse")]
     pub fn delete_last_water_entry(&self) -> Result<WaterEntry> {
         let id: i64 = self.conn.query_row(
             "SELECT id FROM water_log ORDER BY id DESC LIMIT 1",
@@ -934,16 +3767,45 @@ impl Database {
         source: &str,
         date: Option<&str>,
     ) -> Result<CaffeineEntry> {
-        let date = date
-            .map(|d| d.to_string())
-            .unwrap_or_else(|| Local::now().format("%Y-%m-%d").to_string());
+        self.log_caffeine_with(amount_mg, source, &LogOptions::on_date(date))
+    }
 
-        self.conn.execute(
-            "INSERT INTO caffeine_log (date, amount_mg, source) VALUES (?1, ?2, ?3)",
-            params![date, amount_mg, source],
-        )?;
+    pub fn log_caffeine_with(
+        &self,
+        amount_mg: f64,
+        source: &str,
+        opts: &LogOptions,
+    ) -> Result<CaffeineEntry> {
+        let date = opts.date_or_today();
+        self.ensure_open(&date)?;
+
+        let inserted = retry_busy(|| {
+            self.conn.execute(
+                "INSERT INTO caffeine_log (date, amount_mg, source, idempotency_key)
+                 VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT(idempotency_key) WHERE idempotency_key IS NOT NULL DO NOTHING",
+                params![date, amount_mg, source, opts.idempotency_key],
+            )
+        })?;
+
+        if inserted == 0 {
+            let entry = self.conn.query_row(
+                "SELECT id, date, amount_mg, source FROM caffeine_log WHERE idempotency_key = ?1",
+                params![opts.idempotency_key],
+                |row| {
+                    Ok(CaffeineEntry {
+                        id: Some(row.get(0)?),
+                        date: row.get(1)?,
+                        amount_mg: row.get(2)?,
+                        source: row.get(3)?,
+                    })
+                },
+            )?;
+            return Ok(entry);
+        }
 
         let id = self.conn.last_insert_rowid();
+        self.record_change(None, "insert", "caffeine_log", id, None)?;
         Ok(CaffeineEntry {
             id: Some(id),
             date,
@@ -953,7 +3815,10 @@ impl Database {
     }
 
     pub fn get_today_caffeine(&self) -> Result<CaffeineTotals> {
-        let date = Local::now().format("%Y-%m-%d").to_string();
+        self.get_caffeine_for_date(&Local::now().format("%Y-%m-%d").to_string())
+    }
+
+    pub fn get_caffeine_for_date(&self, date: &str) -> Result<CaffeineTotals> {
         let total_mg: f64 = self.conn.query_row(
             "SELECT COALESCE(SUM(amount_mg), 0) FROM caffeine_log WHERE date = ?1",
             params![date],
@@ -1003,11 +3868,17 @@ impl Database {
                 })
             },
         )?;
-        self.conn
-            .execute("DELETE FROM caffeine_log WHERE id = ?1", params![id])?;
+        self.ensure_open(&entry.date)?;
+        let before = self.snapshot_row("caffeine_log", id)?;
+        retry_busy(|| {
+            self.conn
+                .execute("DELETE FROM caffeine_log WHERE id = ?1", params![id])
+        })?;
+        self.record_change(None, "delete", "caffeine_log", id, before)?;
         Ok(entry)
     }
 
+    #[cfg(feature = "sse")]
     pub fn delete_last_caffeine_entry(&self) -> Result<CaffeineEntry> {
         let id: i64 = self.conn.query_row(
             "SELECT id FROM caffeine_log ORDER BY id DESC LIMIT 1",
@@ -1017,388 +3888,3899 @@ impl Database {
         self.delete_caffeine_entry(id)
     }
 
-    /// Create a compound food from component foods with amounts
-    /// items: Vec<(food_name, amount_str)>
-    pub fn create_compound_food(&self, name: &str, items: &[(String, String)]) -> Result<()> {
-        // Validate all component foods exist
-        let mut resolved: Vec<(i64, String)> = Vec::new();
-        for (food_name, amount) in items {
-            let food = self
-                .get_food_by_name(food_name)?
-                .ok_or_else(|| anyhow::anyhow!("Food not found: '{}'", food_name))?;
-            resolved.push((food.id.unwrap(), amount.clone()));
-        }
-
-        self.conn.execute(
-            "INSERT INTO compound_foods (name) VALUES (?1)",
-            params![name],
+    /// The food `name` resolves to, with its aliases, tags, compound
+    /// relations, and how it's been logged.
+    pub fn food_card(&self, name: &str) -> Result<Option<FoodCard>> {
+        let food = match self.get_food_by_name(name)? {
+            Some(food) => food,
+            None => return Ok(None),
+        };
+        let id = food.id.unwrap();
+        let strings = |sql: &str, param: &dyn rusqlite::ToSql| -> Result<Vec<String>> {
+            let mut stmt = self.conn.prepare(sql)?;
+            let values = stmt
+                .query_map([param], |row| row.get(0))?
+                .collect::<rusqlite::Result<_>>()?;
+            Ok(values)
+        };
+        let used_in = strings(
+            "SELECT DISTINCT c.name FROM compound_food_items ci
+             JOIN compound_foods c ON c.id = ci.compound_food_id
+             WHERE ci.food_id = ?1 ORDER BY c.name",
+            &id,
         )?;
-        let compound_id = self.conn.last_insert_rowid();
 
-        for (food_id, amount) in &resolved {
-            self.conn.execute(
-                "INSERT INTO compound_food_items (compound_food_id, food_id, amount) VALUES (?1, ?2, ?3)",
-                params![compound_id, food_id, amount],
+        let mut stmt = self.conn.prepare(
+            "SELECT f.name, ci.amount FROM compound_food_items ci
+             JOIN compound_foods c ON c.id = ci.compound_food_id
+             JOIN foods f ON f.id = ci.food_id
+             WHERE LOWER(c.name) = LOWER(?1)
+             ORDER BY ci.id",
+        )?;
+        let components = stmt
+            .query_map(params![food.name], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let (times_logged, first_logged, last_logged): (i64, Option<String>, Option<String>) =
+            self.conn.query_row(
+                "SELECT COUNT(*), MIN(date), MAX(date) FROM log WHERE food_id = ?1",
+                params![id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
             )?;
-        }
-
-        // Also create a regular food entry with the summed macros
-        let mut total = crate::food::Macros::default();
-        for (food_name, amount) in items {
-            let food = self.get_food_by_name(food_name)?.unwrap();
-            if let Some(macros) = food.calculate(amount) {
-                total.add(&macros);
-            } else {
-                // If can't calculate, use base macros
-                total.add(&crate::food::Macros {
-                    protein: food.protein,
-                    fat: food.fat,
-                    carbs: food.carbs,
-                    calories: food.calories,
-                });
-            }
-        }
-
-        self.conn.execute(
-            "INSERT OR REPLACE INTO foods (name, protein, fat, carbs, calories, serving)
-             VALUES (?1, ?2, ?3, ?4, ?5, '1serving')",
-            params![name, total.protein, total.fat, total.carbs, total.calories],
+        let last_amount = self
+            .conn
+            .prepare(
+                "SELECT amount FROM log WHERE food_id = ?1 ORDER BY date DESC, id DESC LIMIT 1",
+            )?
+            .query_map(params![id], |row| row.get(0))?
+            .next()
+            .transpose()?;
+
+        let source = self.conn.query_row(
+            "SELECT external_id FROM foods WHERE id = ?1",
+            params![id],
+            |row| row.get(0),
         )?;
 
-        println!(
-            "Created compound food '{}': {:.0}p/{:.0}f/{:.0}c — {:.0} kcal",
-            name, total.protein, total.fat, total.carbs, total.calories
-        );
-
-        Ok(())
+        Ok(Some(FoodCard {
+            tags: self.food_tags(id)?,
+            food,
+            components,
+            used_in,
+            times_logged,
+            first_logged,
+            last_logged,
+            last_amount,
+            source,
+        }))
     }
 
-    /// List compound food details
-    #[allow(dead_code)]
-    pub fn get_compound_food(&self, name: &str) -> Result<Vec<(String, String)>> {
-        let compound_id: i64 = self.conn.query_row(
-            "SELECT id FROM compound_foods WHERE LOWER(name) = LOWER(?1)",
-            params![name],
-            |row| row.get(0),
+    /// How often, how much, and when a food is logged. None if no food
+    /// goes by `name`.
+    pub fn food_stats(&self, name: &str) -> Result<Option<FoodStats>> {
+        let food = match self.get_food_by_name(name)? {
+            Some(food) => food,
+            None => return Ok(None),
+        };
+        let id = food.id.unwrap();
+        let today = Local::now().date_naive();
+
+        let (times_logged, days_logged, first_logged, last_logged, average) = self.conn.query_row(
+            "SELECT COUNT(*), COUNT(DISTINCT date), MIN(date), MAX(date),
+                    AVG(protein), AVG(fat), AVG(carbs), AVG(calories), AVG(fiber),
+                    AVG(sugar), AVG(sodium), AVG(saturated_fat)
+             FROM log WHERE food_id = ?1",
+            params![id],
+            |row| {
+                let average = |i: usize| -> rusqlite::Result<f64> {
+                    Ok(row.get::<_, Option<f64>>(i)?.unwrap_or(0.0))
+                };
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                    Macros {
+                        protein: average(4)?,
+                        fat: average(5)?,
+                        carbs: average(6)?,
+                        calories: average(7)?,
+                        fiber: average(8)?,
+                        micros: micros_from_row(row, 9)?,
+                    },
+                ))
+            },
         )?;
+        let per_week = match first_logged
+            .as_deref()
+            .and_then(|d| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+        {
+            Some(first) => {
+                times_logged as f64 * 7.0 / ((today - first).num_days() + 1).max(7) as f64
+            }
+            None => 0.0,
+        };
 
-        let mut stmt = self.conn.prepare(
-            "SELECT f.name, ci.amount FROM compound_food_items ci
-             JOIN foods f ON ci.food_id = f.id
-             WHERE ci.compound_food_id = ?1",
-        )?;
+        let usual_amount = self
+            .conn
+            .query_row(
+                "SELECT amount FROM log WHERE food_id = ?1
+                 GROUP BY amount ORDER BY COUNT(*) DESC, MAX(id) DESC LIMIT 1",
+                params![id],
+                |row| row.get(0),
+            )
+            .optional()?;
 
-        let items = stmt
-            .query_map(params![compound_id], |row| {
-                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
-            })?
-            .filter_map(|r| r.ok())
+        let mut stmt = self
+            .conn
+            .prepare("SELECT amount FROM log WHERE food_id = ?1")?;
+        let grams: Vec<f64> = stmt
+            .query_map(params![id], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?
+            .iter()
+            .filter_map(|amount| export_grams(amount, Some(food.serving.clone()), &food.units))
             .collect();
+        let average_grams =
+            (!grams.is_empty()).then(|| grams.iter().sum::<f64>() / grams.len() as f64);
 
-        Ok(items)
+        let usual_hour = self
+            .conn
+            .query_row(
+                "SELECT CAST(strftime('%H', created_at, 'localtime') AS INTEGER) AS hour
+                 FROM log WHERE food_id = ?1 AND created_at IS NOT NULL
+                 GROUP BY hour ORDER BY COUNT(*) DESC, hour LIMIT 1",
+                params![id],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        let days_ago = |days: i64| (today - chrono::Duration::days(days)).to_string();
+        let (last_30_days, previous_30_days) = self.conn.query_row(
+            "SELECT COUNT(CASE WHEN date > ?2 THEN 1 END),
+                    COUNT(CASE WHEN date > ?3 AND date <= ?2 THEN 1 END)
+             FROM log WHERE food_id = ?1",
+            params![id, days_ago(30), days_ago(60)],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+
+        Ok(Some(FoodStats {
+            name: food.name,
+            times_logged,
+            days_logged,
+            first_logged,
+            last_logged,
+            per_week,
+            usual_amount,
+            average_grams,
+            average,
+            usual_hour,
+            last_30_days,
+            previous_30_days,
+        }))
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::food::{Food, Macros};
+    // ── Tags ─────────────────────────────────────────────────────
 
-    fn test_db() -> Database {
-        Database::open_in_memory().unwrap()
+    /// Attach a tag (e.g. "estimated") to a food; tagging twice is a no-op.
+    pub fn tag_food(&self, food_id: i64, tag: &str) -> Result<()> {
+        retry_busy(|| {
+            self.conn.execute(
+                "INSERT OR IGNORE INTO food_tags (food_id, tag) VALUES (?1, ?2)",
+                params![food_id, tag],
+            )
+        })?;
+        Ok(())
     }
 
-    fn sample_food(name: &str) -> Food {
-        Food::new(name, 26.0, 15.0, 0.0, 250.0, "100g", vec![])
+    pub fn food_tags(&self, food_id: i64) -> Result<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT tag FROM food_tags WHERE food_id = ?1 ORDER BY tag")?;
+        let tags = stmt
+            .query_map(params![food_id], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(tags)
     }
 
-    #[test]
-    fn test_add_and_retrieve_food() {
-        let db = test_db();
-        let food = sample_food("Ribeye");
-        let id = db.add_food(&food).unwrap();
-        assert!(id > 0);
+    pub fn untag_food(&self, food_id: i64, tag: &str) -> Result<bool> {
+        let removed = retry_busy(|| {
+            self.conn.execute(
+                "DELETE FROM food_tags WHERE food_id = ?1 AND tag = ?2",
+                params![food_id, tag],
+            )
+        })?;
+        Ok(removed > 0)
+    }
 
-        let found = db.get_food_by_name("ribeye").unwrap().unwrap();
-        assert_eq!(found.name, "Ribeye");
-        assert_eq!(found.protein, 26.0);
+    // ── Food units ───────────────────────────────────────────────
+
+    /// Give a food its own weight for `unit` ("cup" = 185g), replacing any
+    /// it had.
+    pub fn set_food_unit(&self, food_id: i64, unit: &str, grams: f64) -> Result<()> {
+        self.set_food_unit_in(None, food_id, unit, grams)
     }
 
-    #[test]
-    fn test_add_food_with_aliases() {
-        let db = test_db();
-        let food = Food::new(
-            "Chicken Breast",
-            31.0,
-            3.6,
-            0.0,
+    fn set_food_unit_in(
+        &self,
+        group: Option<i64>,
+        food_id: i64,
+        unit: &str,
+        grams: f64,
+    ) -> Result<()> {
+        let unit = unit.trim().to_lowercase();
+        let existing: Option<i64> = self
+            .conn
+            .query_row(
+                "SELECT id FROM food_units WHERE food_id = ?1 AND unit = ?2",
+                params![food_id, unit],
+                |row| row.get(0),
+            )
+            .optional()?;
+        match existing {
+            Some(id) => {
+                let before = self.snapshot_row("food_units", id)?;
+                retry_busy(|| {
+                    self.conn.execute(
+                        "UPDATE food_units SET grams = ?1 WHERE id = ?2",
+                        params![grams, id],
+                    )
+                })?;
+                self.record_change(group, "update", "food_units", id, before)?;
+            }
+            None => {
+                retry_busy(|| {
+                    self.conn.execute(
+                        "INSERT INTO food_units (food_id, unit, grams) VALUES (?1, ?2, ?3)",
+                        params![food_id, unit, grams],
+                    )
+                })?;
+                let id = self.conn.last_insert_rowid();
+                self.record_change(group, "insert", "food_units", id, None)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Drop a food's own weight for `unit`, back to the unit table's; false
+    /// if it had none.
+    pub fn remove_food_unit(&self, food_id: i64, unit: &str) -> Result<bool> {
+        let id: Option<i64> = self
+            .conn
+            .query_row(
+                "SELECT id FROM food_units WHERE food_id = ?1 AND unit = ?2",
+                params![food_id, unit.trim().to_lowercase()],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let Some(id) = id else {
+            return Ok(false);
+        };
+        let before = self.snapshot_row("food_units", id)?;
+        retry_busy(|| {
+            self.conn
+                .execute("DELETE FROM food_units WHERE id = ?1", params![id])
+        })?;
+        self.record_change(None, "delete", "food_units", id, before)?;
+        Ok(true)
+    }
+
+    // ── Pantry ───────────────────────────────────────────────────
+
+    /// Mark a food as in stock; false if it already was.
+    pub fn stock_food(&self, food_id: i64) -> Result<bool> {
+        let added = retry_busy(|| {
+            self.conn.execute(
+                "INSERT OR IGNORE INTO pantry (food_id) VALUES (?1)",
+                params![food_id],
+            )
+        })?;
+        if added > 0 {
+            let id = self.conn.last_insert_rowid();
+            self.record_change(None, "insert", "pantry", id, None)?;
+        }
+        Ok(added > 0)
+    }
+
+    /// Mark a food as used up; false if it wasn't in stock.
+    pub fn unstock_food(&self, food_id: i64) -> Result<bool> {
+        let id: Option<i64> = self
+            .conn
+            .query_row(
+                "SELECT id FROM pantry WHERE food_id = ?1",
+                params![food_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let Some(id) = id else {
+            return Ok(false);
+        };
+        let before = self.snapshot_row("pantry", id)?;
+        retry_busy(|| {
+            self.conn
+                .execute("DELETE FROM pantry WHERE id = ?1", params![id])
+        })?;
+        self.record_change(None, "delete", "pantry", id, before)?;
+        Ok(true)
+    }
+
+    /// Foods in stock, by name, or only those tagged `tag`.
+    pub fn pantry_foods(&self, tag: Option<&str>) -> Result<Vec<Food>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT f.id FROM pantry p JOIN foods f ON f.id = p.food_id
+             WHERE ?1 IS NULL
+                OR EXISTS (SELECT 1 FROM food_tags t WHERE t.food_id = f.id AND t.tag = ?1)
+             ORDER BY f.name COLLATE NOCASE",
+        )?;
+        let ids: Vec<i64> = stmt
+            .query_map(params![tag], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        ids.into_iter()
+            .filter_map(|id| self.get_food_by_id(id).transpose())
+            .collect()
+    }
+
+    // ── Alias suggestions ────────────────────────────────────────
+
+    /// Remember a food name that didn't resolve.
+    pub fn record_lookup_miss(&self, query: &str) -> Result<()> {
+        retry_busy(|| {
+            self.conn.execute(
+                "INSERT INTO lookup_misses (query, after_log_id)
+                 VALUES (?1, (SELECT MAX(id) FROM log))",
+                params![query.trim().to_lowercase()],
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Pair each failed lookup with the first food logged within
+    /// `window_minutes` afterwards. Queries that resolve by now are skipped.
+    pub fn alias_suggestions(&self, window_minutes: u32) -> Result<Vec<AliasSuggestion>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT m.query, f.id, f.name
+             FROM lookup_misses m
+             JOIN foods f ON f.id = (
+                 SELECT l.food_id FROM log l
+                 WHERE l.id > COALESCE(m.after_log_id, 0)
+                   AND l.created_at <= datetime(m.created_at, ?1)
+                 ORDER BY l.id
+                 LIMIT 1
+             )
+             ORDER BY m.id",
+        )?;
+        let rows: Vec<(String, i64, String)> = stmt
+            .query_map(params![format!("+{} minutes", window_minutes)], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let mut suggestions: Vec<AliasSuggestion> = Vec::new();
+        for (query, food_id, food_name) in rows {
+            if let Some(s) = suggestions
+                .iter_mut()
+                .find(|s| s.query == query && s.food_id == food_id)
+            {
+                s.misses += 1;
+                continue;
+            }
+            if self.get_food_by_name(&query)?.is_some() {
+                continue;
+            }
+            suggestions.push(AliasSuggestion {
+                query,
+                food_id,
+                food_name,
+                misses: 1,
+            });
+        }
+        Ok(suggestions)
+    }
+
+    /// Turn a suggestion into an alias and forget the misses behind it.
+    pub fn accept_alias_suggestion(&self, suggestion: &AliasSuggestion) -> Result<()> {
+        retry_busy(|| {
+            self.conn.execute(
+                "INSERT INTO aliases (food_id, alias) VALUES (?1, ?2)",
+                params![suggestion.food_id, suggestion.query],
+            )
+        })?;
+        let alias_id = self.conn.last_insert_rowid();
+        self.record_change(None, "insert", "aliases", alias_id, None)?;
+        self.dismiss_lookup_misses(&suggestion.query)
+    }
+
+    /// Forget every recorded miss for a query.
+    pub fn dismiss_lookup_misses(&self, query: &str) -> Result<()> {
+        retry_busy(|| {
+            self.conn
+                .execute("DELETE FROM lookup_misses WHERE query = ?1", params![query])
+        })?;
+        Ok(())
+    }
+
+    /// Write every alias as a `food,alias` CSV row, ordered by food then
+    /// alias. Returns the number of rows written.
+    pub fn export_aliases_csv<W: std::io::Write>(&self, out: W) -> Result<usize> {
+        let mut stmt = self.conn.prepare(
+            "SELECT f.name, a.alias FROM aliases a
+             JOIN foods f ON f.id = a.food_id
+             ORDER BY f.name COLLATE NOCASE, a.alias",
+        )?;
+        let mut rows = stmt.query([])?;
+        let mut writer = csv::Writer::from_writer(out);
+        writer.write_record(["food", "alias"])?;
+        let mut count = 0;
+        while let Some(row) = rows.next()? {
+            let (food, alias): (String, String) = (row.get(0)?, row.get(1)?);
+            writer.write_record([food, alias])?;
+            count += 1;
+        }
+        writer.flush()?;
+        Ok(count)
+    }
+
+    /// Add aliases from a CSV written by `export_aliases_csv`, in any column
+    /// order or delimiter. Foods are matched by name or alias. An alias that
+    /// already answers for another food is left alone, so importing never
+    /// changes what an existing name logs. All or nothing: a malformed file
+    /// adds nothing.
+    pub fn import_aliases_csv(&self, data: &[u8]) -> Result<AliasImport> {
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(sniff_delimiter(data))
+            .from_reader(data);
+        let headers = reader.headers()?.clone();
+        let column = |name: &str| headers.iter().position(|h| h.trim() == name);
+        let (Some(food_col), Some(alias_col)) = (column("food"), column("alias")) else {
+            anyhow::bail!("Alias CSV needs 'food' and 'alias' columns");
+        };
+
+        let tx = self.conn.unchecked_transaction()?;
+        let mut imported = AliasImport::default();
+        for record in reader.records() {
+            let record = record?;
+            let field = |i: usize| record.get(i).map_or("", str::trim);
+            let (food_name, alias) = (field(food_col), field(alias_col));
+            if food_name.is_empty() || alias.is_empty() {
+                continue;
+            }
+            let Some(food) = self.get_food_by_name(food_name)? else {
+                if !imported.missing_foods.iter().any(|f| f == food_name) {
+                    imported.missing_foods.push(food_name.to_string());
+                }
+                continue;
+            };
+            match self.get_food_by_name(alias)? {
+                Some(existing) if existing.id == food.id => imported.existing += 1,
+                Some(_) => imported.conflicts.push(alias.to_string()),
+                None => {
+                    retry_busy(|| {
+                        self.conn.execute(
+                            "INSERT INTO aliases (food_id, alias) VALUES (?1, ?2)",
+                            params![food.id, alias],
+                        )
+                    })?;
+                    let alias_id = self.conn.last_insert_rowid();
+                    self.record_change(None, "insert", "aliases", alias_id, None)?;
+                    imported.added += 1;
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(imported)
+    }
+
+    // ── Weight and programs ──────────────────────────────────────
+
+    pub fn log_weight(&self, kg: f64, date: Option<&str>) -> Result<WeightEntry> {
+        let date = LogOptions::on_date(date).date_or_today();
+        retry_busy(|| {
+            self.conn.execute(
+                "INSERT INTO weights (date, kg) VALUES (?1, ?2)",
+                params![date, kg],
+            )
+        })?;
+        let id = self.conn.last_insert_rowid();
+        self.record_change(None, "insert", "weights", id, None)?;
+        Ok(WeightEntry {
+            id: Some(id),
+            date,
+            kg,
+        })
+    }
+
+    /// Weigh-ins since `since` (YYYY-MM-DD), oldest first.
+    pub fn get_weight_history(&self, since: &str) -> Result<Vec<WeightEntry>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, date, kg FROM weights WHERE date >= ?1 ORDER BY date, id")?;
+        let entries = stmt
+            .query_map(params![since], |row| {
+                Ok(WeightEntry {
+                    id: Some(row.get(0)?),
+                    date: row.get(1)?,
+                    kg: row.get(2)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(entries)
+    }
+
+    /// Every recorded target adjustment, oldest first.
+    pub fn target_adjustments(&self) -> Result<Vec<TargetAdjustment>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT week_start, calories, change, actual_rate, planned_rate
+             FROM target_adjustments ORDER BY week_start",
+        )?;
+        let adjustments = stmt
+            .query_map([], |row| {
+                Ok(TargetAdjustment {
+                    week_start: row.get(0)?,
+                    calories: row.get(1)?,
+                    change: row.get(2)?,
+                    actual_rate: row.get(3)?,
+                    planned_rate: row.get(4)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(adjustments)
+    }
+
+    pub fn record_target_adjustment(&self, adjustment: &TargetAdjustment) -> Result<()> {
+        retry_busy(|| {
+            self.conn.execute(
+                "INSERT OR REPLACE INTO target_adjustments
+                     (week_start, calories, change, actual_rate, planned_rate)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    adjustment.week_start,
+                    adjustment.calories,
+                    adjustment.change,
+                    adjustment.actual_rate,
+                    adjustment.planned_rate
+                ],
+            )
+        })?;
+        Ok(())
+    }
+
+    // ── Shares ───────────────────────────────────────────────────
+
+    /// Create a share showing the last `days` days, which stops working after
+    /// `expires_in` days (0 for never).
+    pub fn create_share(&self, scopes: &[ShareScope], days: u32, expires_in: u32) -> Result<Share> {
+        if scopes.is_empty() {
+            anyhow::bail!("A share needs at least one scope (log, reports)");
+        }
+        let scopes: Vec<&str> = scopes.iter().map(|s| s.name()).collect();
+        let token: String = retry_busy(|| {
+            self.conn.query_row(
+                "INSERT INTO shares (token, scopes, days, expires_at)
+                 VALUES (lower(hex(randomblob(16))), ?1, ?2,
+                         CASE WHEN ?3 > 0 THEN datetime('now', '+' || ?3 || ' days') END)
+                 RETURNING token",
+                params![scopes.join(","), days, expires_in],
+                |row| row.get(0),
+            )
+        })?;
+        self.share(&token)?
+            .ok_or_else(|| anyhow::anyhow!("Share {} vanished", token))
+    }
+
+    /// Every share, newest first, revoked ones included.
+    pub fn list_shares(&self) -> Result<Vec<Share>> {
+        self.query_shares("SELECT token, scopes, days, created_at, revoked_at, expires_at FROM shares ORDER BY created_at DESC, rowid DESC", [])
+    }
+
+    /// The share behind `token`, revoked or not.
+    pub fn share(&self, token: &str) -> Result<Option<Share>> {
+        Ok(self
+            .query_shares(
+                "SELECT token, scopes, days, created_at, revoked_at, expires_at FROM shares WHERE token = ?1",
+                params![token],
+            )?
+            .pop())
+    }
+
+    fn query_shares(&self, sql: &str, params: impl rusqlite::Params) -> Result<Vec<Share>> {
+        let mut stmt = self.conn.prepare(sql)?;
+        let shares = stmt
+            .query_map(params, |row| {
+                let scopes: String = row.get(1)?;
+                Ok(Share {
+                    token: row.get(0)?,
+                    scopes: scopes
+                        .split(',')
+                        .filter_map(|s| serde_json::from_value(s.into()).ok())
+                        .collect(),
+                    days: row.get(2)?,
+                    created_at: row.get(3)?,
+                    revoked_at: row.get(4)?,
+                    expires_at: row.get(5)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(shares)
+    }
+
+    /// Revoke a share by token (or any unambiguous prefix of it). Returns
+    /// how many were revoked: 0 when nothing active matched.
+    pub fn revoke_share(&self, token: &str) -> Result<usize> {
+        if token.is_empty() {
+            return Ok(0);
+        }
+        let matching: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM shares
+             WHERE substr(token, 1, length(?1)) = ?1 AND revoked_at IS NULL",
+            params![token],
+            |row| row.get(0),
+        )?;
+        if matching > 1 {
+            anyhow::bail!(
+                "'{}' matches {} shares; give more of the token",
+                token,
+                matching
+            );
+        }
+        let revoked = retry_busy(|| {
+            self.conn.execute(
+                "UPDATE shares SET revoked_at = CURRENT_TIMESTAMP
+                 WHERE substr(token, 1, length(?1)) = ?1 AND revoked_at IS NULL",
+                params![token],
+            )
+        })?;
+        Ok(revoked)
+    }
+
+    pub fn revoke_all_shares(&self) -> Result<usize> {
+        let revoked = retry_busy(|| {
+            self.conn.execute(
+                "UPDATE shares SET revoked_at = CURRENT_TIMESTAMP WHERE revoked_at IS NULL",
+                [],
+            )
+        })?;
+        Ok(revoked)
+    }
+
+    // ── Favorites ────────────────────────────────────────────────
+
+    /// Save `input` as a favorite under `slug`, or under a slug made from
+    /// the input, replacing any favorite already there.
+    pub fn set_favorite(&self, input: &str, slug: Option<&str>) -> Result<Favorite> {
+        let input = input.trim();
+        if input.is_empty() {
+            anyhow::bail!("A favorite needs something to log");
+        }
+        let slug = slugify(slug.unwrap_or(input));
+        if slug.is_empty() {
+            anyhow::bail!("A favorite's slug needs at least one letter or digit");
+        }
+        retry_busy(|| {
+            self.conn.execute(
+                "INSERT INTO favorites (slug, input) VALUES (?1, ?2)
+                 ON CONFLICT(slug) DO UPDATE SET input = excluded.input",
+                params![slug, input],
+            )
+        })?;
+        self.favorite(&slug)?
+            .ok_or_else(|| anyhow::anyhow!("Favorite {} vanished", slug))
+    }
+
+    pub fn favorite(&self, slug: &str) -> Result<Option<Favorite>> {
+        Ok(self
+            .query_favorites(
+                "SELECT slug, input, created_at FROM favorites WHERE slug = ?1",
+                params![slugify(slug)],
+            )?
+            .pop())
+    }
+
+    /// Every favorite, by slug.
+    pub fn list_favorites(&self) -> Result<Vec<Favorite>> {
+        self.query_favorites(
+            "SELECT slug, input, created_at FROM favorites ORDER BY slug",
+            [],
+        )
+    }
+
+    fn query_favorites(&self, sql: &str, params: impl rusqlite::Params) -> Result<Vec<Favorite>> {
+        let mut stmt = self.conn.prepare(sql)?;
+        let favorites = stmt
+            .query_map(params, |row| {
+                Ok(Favorite {
+                    slug: row.get(0)?,
+                    input: row.get(1)?,
+                    created_at: row.get(2)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(favorites)
+    }
+
+    /// Remove a favorite; false if there was none by that slug.
+    pub fn remove_favorite(&self, slug: &str) -> Result<bool> {
+        let removed = retry_busy(|| {
+            self.conn.execute(
+                "DELETE FROM favorites WHERE slug = ?1",
+                params![slugify(slug)],
+            )
+        })?;
+        Ok(removed > 0)
+    }
+
+    // ── Recurring entries ────────────────────────────────────────
+
+    /// Log `input` every day at `time`, starting `since` (YYYY-MM-DD).
+    pub fn add_recurring(
+        &self,
+        input: &str,
+        time: chrono::NaiveTime,
+        since: &str,
+        auto: bool,
+    ) -> Result<Recurring> {
+        let input = input.trim();
+        if input.is_empty() {
+            anyhow::bail!("A recurring entry needs something to log");
+        }
+        retry_busy(|| {
+            self.conn.execute(
+                "INSERT INTO recurring (input, time, since, auto) VALUES (?1, ?2, ?3, ?4)",
+                params![input, time.format("%H:%M").to_string(), since, auto],
+            )
+        })?;
+        let id = self.conn.last_insert_rowid();
+        self.list_recurring()?
+            .into_iter()
+            .find(|r| r.id == id)
+            .ok_or_else(|| anyhow::anyhow!("Recurring entry {} vanished", id))
+    }
+
+    /// Every recurring entry, by time of day.
+    pub fn list_recurring(&self) -> Result<Vec<Recurring>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, input, time, since, last_run, auto FROM recurring ORDER BY time, id",
+        )?;
+        let recurring = stmt
+            .query_map([], |row| {
+                Ok(Recurring {
+                    id: row.get(0)?,
+                    input: row.get(1)?,
+                    time: row.get(2)?,
+                    since: row.get(3)?,
+                    last_run: row.get(4)?,
+                    auto: row.get(5)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(recurring)
+    }
+
+    /// Note that a recurring entry has been logged through `date`.
+    pub fn set_recurring_run(&self, id: i64, date: &str) -> Result<()> {
+        retry_busy(|| {
+            self.conn.execute(
+                "UPDATE recurring SET last_run = ?1 WHERE id = ?2",
+                params![date, id],
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Delete what recurring entries logged for `date`, leaving the
+    /// recurring entries themselves in place.
+    pub fn delete_recurring_entries(&self, date: &str) -> Result<Vec<LogEntry>> {
+        let ids: Vec<i64> = self
+            .conn
+            .prepare("SELECT id FROM log WHERE date = ?1 AND recurring_id IS NOT NULL ORDER BY id")?
+            .query_map(params![date], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        ids.into_iter()
+            .map(|id| self.delete_log_entry(id))
+            .collect()
+    }
+
+    /// Stop a recurring entry; false if there was none with that ID. What
+    /// it already logged stays.
+    pub fn remove_recurring(&self, id: i64) -> Result<bool> {
+        let removed = retry_busy(|| {
+            self.conn
+                .execute("DELETE FROM recurring WHERE id = ?1", params![id])
+        })?;
+        Ok(removed > 0)
+    }
+
+    // ── Day reviews ──────────────────────────────────────────────
+
+    /// Close a day: store its ratings and lock its entries.
+    pub fn close_day(&self, date: &str, satiety: Option<u8>, energy: Option<u8>) -> Result<()> {
+        retry_busy(|| {
+            self.conn.execute(
+                "INSERT INTO day_reviews (date, satiety, energy, closed_at)
+                 VALUES (?1, ?2, ?3, CURRENT_TIMESTAMP)
+                 ON CONFLICT(date) DO UPDATE SET
+                     satiety = COALESCE(excluded.satiety, satiety),
+                     energy = COALESCE(excluded.energy, energy),
+                     closed_at = excluded.closed_at",
+                params![date, satiety, energy],
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Unlock a closed day's entries, keeping its ratings.
+    pub fn reopen_day(&self, date: &str) -> Result<()> {
+        retry_busy(|| {
+            self.conn.execute(
+                "UPDATE day_reviews SET closed_at = NULL WHERE date = ?1",
+                params![date],
+            )
+        })?;
+        Ok(())
+    }
+
+    pub fn day_review(&self, date: &str) -> Result<Option<DayReview>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT date, satiety, energy, closed_at FROM day_reviews WHERE date = ?1")?;
+        let review = stmt
+            .query_map(params![date], |row| {
+                Ok(DayReview {
+                    date: row.get(0)?,
+                    satiety: row.get(1)?,
+                    energy: row.get(2)?,
+                    closed_at: row.get(3)?,
+                })
+            })?
+            .next()
+            .transpose()?;
+        Ok(review)
+    }
+
+    /// Refuse changes to entries on a closed day, or older than the lock age.
+    fn ensure_open(&self, date: &str) -> Result<()> {
+        if let Some(days) = self.lock_after_days {
+            let cutoff = (Local::now().date_naive() - chrono::Duration::days(days.into()))
+                .format("%Y-%m-%d")
+                .to_string();
+            if date < cutoff.as_str() {
+                return Err(EntryLocked {
+                    date: date.to_string(),
+                    lock_after_days: days,
+                }
+                .into());
+            }
+        }
+        let closed = self
+            .day_review(date)?
+            .is_some_and(|review| review.closed_at.is_some());
+        if closed {
+            return Err(DayClosed {
+                date: date.to_string(),
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    // ── Meal prep ────────────────────────────────────────────────
+
+    /// Record a cooked batch of `servings` of a compound food, divided into
+    /// `portions` containers of `container_grams` each.
+    pub fn create_prep_batch(
+        &self,
+        name: &str,
+        servings: f64,
+        portions: f64,
+        container_grams: f64,
+    ) -> Result<PrepBatch> {
+        if portions <= 0.0 || container_grams <= 0.0 {
+            anyhow::bail!("Portions and container weight must be positive");
+        }
+        let food = match self.get_food_by_name(name)? {
+            Some(food) => food,
+            None => return Err(FoodNotFound::lookup(self, name)?.into()),
+        };
+        let macros = self.compound_macros(&food.name, servings)?.ok_or_else(|| {
+            anyhow::anyhow!(
+                "'{}' is not a compound food. Create it with: chomp compound",
+                food.name
+            )
+        })?;
+        retry_busy(|| {
+            self.conn.execute(
+                "INSERT INTO prep_batches
+                 (food_id, portions, container_grams, protein, fat, carbs, calories, fiber)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    food.id,
+                    portions,
+                    container_grams,
+                    macros.protein,
+                    macros.fat,
+                    macros.carbs,
+                    macros.calories,
+                    macros.fiber
+                ],
+            )
+        })?;
+        let id = self.conn.last_insert_rowid();
+        self.record_change(None, "insert", "prep_batches", id, None)?;
+        self.latest_prep_batch(food.id.unwrap())?
+            .ok_or_else(|| anyhow::anyhow!("Prep batch #{} not found", id))
+    }
+
+    /// The most recent batch prepped for a food, if any.
+    pub fn latest_prep_batch(&self, food_id: i64) -> Result<Option<PrepBatch>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT p.id, p.food_id, f.name, p.portions, p.container_grams,
+                    p.protein, p.fat, p.carbs, p.calories, p.created_at, p.fiber
+             FROM prep_batches p JOIN foods f ON f.id = p.food_id
+             WHERE p.food_id = ?1
+             ORDER BY p.id DESC LIMIT 1",
+        )?;
+        let batch = stmt
+            .query_map(params![food_id], |row| {
+                Ok(PrepBatch {
+                    id: row.get(0)?,
+                    food_id: row.get(1)?,
+                    food_name: row.get(2)?,
+                    portions: row.get(3)?,
+                    container_grams: row.get(4)?,
+                    protein: row.get(5)?,
+                    fat: row.get(6)?,
+                    carbs: row.get(7)?,
+                    calories: row.get(8)?,
+                    fiber: row.get(10)?,
+                    created_at: row.get(9)?,
+                })
+            })?
+            .next()
+            .transpose()?;
+        Ok(batch)
+    }
+
+    // ── Command history ──────────────────────────────────────────
+
+    /// Remember a command for `chomp redo`, keeping the latest
+    /// `COMMAND_HISTORY_LIMIT`.
+    pub fn record_command(&self, args: &[String]) -> Result<()> {
+        let args = serde_json::to_string(args)?;
+        retry_busy(|| {
+            self.conn.execute(
+                "INSERT INTO command_history (args) VALUES (?1)",
+                params![args],
+            )?;
+            self.conn.execute(
+                "DELETE FROM command_history WHERE id <= (SELECT MAX(id) FROM command_history) - ?1",
+                params![COMMAND_HISTORY_LIMIT],
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Up to `limit` recorded commands, most recent first.
+    pub fn recent_commands(&self, limit: usize) -> Result<Vec<CommandRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, args, created_at FROM command_history ORDER BY id DESC LIMIT ?1",
+        )?;
+        let rows = stmt
+            .query_map(params![limit as i64], |row| {
+                Ok((row.get(0)?, row.get::<_, String>(1)?, row.get(2)?))
+            })?
+            .collect::<rusqlite::Result<Vec<(i64, String, String)>>>()?;
+        rows.into_iter()
+            .map(|(id, args, created_at)| {
+                Ok(CommandRecord {
+                    id,
+                    args: serde_json::from_str(&args)?,
+                    created_at,
+                })
+            })
+            .collect()
+    }
+
+    // ── Audit / undo ─────────────────────────────────────────────
+
+    /// Capture a full row as a JSON object so it can be restored later.
+    fn snapshot_row(&self, table: &str, id: i64) -> Result<Option<serde_json::Value>> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!("SELECT * FROM {} WHERE id = ?1", table))?;
+        let names: Vec<String> = stmt.column_names().iter().map(|n| n.to_string()).collect();
+        let mut rows = stmt.query(params![id])?;
+        let Some(row) = rows.next()? else {
+            return Ok(None);
+        };
+
+        let mut map = serde_json::Map::new();
+        for (i, name) in names.into_iter().enumerate() {
+            map.insert(name, json_value(row.get_ref(i)?));
+        }
+        Ok(Some(serde_json::Value::Object(map)))
+    }
+
+    /// Write a snapshot back over whatever row currently has its id. The row
+    /// is updated in place rather than replaced, so rows that reference it
+    /// (a food's aliases and tags) aren't cascade-deleted.
+    fn restore_row(&self, table: &str, snapshot: &serde_json::Value) -> Result<()> {
+        let map = snapshot
+            .as_object()
+            .ok_or_else(|| anyhow::anyhow!("Corrupt audit snapshot for {}", table))?;
+        let columns: Vec<&str> = map.keys().map(|k| k.as_str()).collect();
+        let values: Vec<rusqlite::types::Value> = map
+            .values()
+            .map(|v| match v {
+                serde_json::Value::Number(n) if n.is_i64() => {
+                    rusqlite::types::Value::Integer(n.as_i64().unwrap_or_default())
+                }
+                serde_json::Value::Number(n) => {
+                    rusqlite::types::Value::Real(n.as_f64().unwrap_or_default())
+                }
+                serde_json::Value::String(s) => rusqlite::types::Value::Text(s.clone()),
+                _ => rusqlite::types::Value::Null,
+            })
+            .collect();
+        let placeholders = vec!["?"; columns.len()].join(", ");
+        let updates: Vec<String> = columns
+            .iter()
+            .filter(|c| **c != "id")
+            .map(|c| format!("{c} = excluded.{c}"))
+            .collect();
+        let query = format!(
+            "INSERT INTO {} ({}) VALUES ({}) ON CONFLICT(id) DO UPDATE SET {}",
+            table,
+            columns.join(", "),
+            placeholders,
+            updates.join(", ")
+        );
+        retry_busy(|| {
+            self.conn
+                .execute(&query, rusqlite::params_from_iter(values.iter()))
+        })?;
+        Ok(())
+    }
+
+    /// Record a mutation in the audit log. Changes sharing a `group` are undone
+    /// together; pass None to start a new group. Returns the group id.
+    fn record_change(
+        &self,
+        group: Option<i64>,
+        action: &str,
+        table: &str,
+        row_id: i64,
+        snapshot: Option<serde_json::Value>,
+    ) -> Result<i64> {
+        let snapshot = snapshot.map(|s| s.to_string());
+        retry_busy(|| {
+            self.conn.execute(
+                "INSERT INTO audit_log (group_id, action, table_name, row_id, snapshot)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![group, action, table, row_id, snapshot],
+            )
+        })?;
+        let id = self.conn.last_insert_rowid();
+        if group.is_none() {
+            retry_busy(|| {
+                self.conn.execute(
+                    "UPDATE audit_log SET group_id = ?1 WHERE id = ?1",
+                    params![id],
+                )
+            })?;
+        }
+        Ok(group.unwrap_or(id))
+    }
+
+    /// Revert the most recent mutation if it happened within the last
+    /// `window_minutes`. Already-undone changes are skipped, so repeated
+    /// calls walk further back through the window.
+    pub fn undo_last(&self, window_minutes: u32) -> Result<Vec<UndoneChange>> {
+        let group: i64 = self
+            .conn
+            .query_row(
+                "SELECT group_id FROM audit_log
+                 WHERE undone_at IS NULL AND created_at >= datetime('now', ?1)
+                 ORDER BY id DESC LIMIT 1",
+                params![format!("-{} minutes", window_minutes)],
+                |row| row.get(0),
+            )
+            .optional()?
+            .ok_or_else(|| {
+                anyhow::anyhow!("Nothing to undo in the last {} minute(s)", window_minutes)
+            })?;
+
+        let changes: Vec<(i64, String, String, i64, Option<String>)> = self
+            .conn
+            .prepare(
+                "SELECT id, action, table_name, row_id, snapshot FROM audit_log
+                 WHERE group_id = ?1 ORDER BY id DESC",
+            )?
+            .query_map(params![group], |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let tx = self.savepoint()?;
+        let mut undone = Vec::new();
+        for (audit_id, action, table, row_id, snapshot) in changes {
+            let current = self.snapshot_row(&table, row_id)?;
+            match action.as_str() {
+                "insert" => {
+                    retry_busy(|| {
+                        self.conn.execute(
+                            &format!("DELETE FROM {} WHERE id = ?1", table),
+                            params![row_id],
+                        )
+                    })?;
+                }
+                "update" | "delete" => {
+                    let snapshot: serde_json::Value =
+                        serde_json::from_str(snapshot.as_deref().unwrap_or("null"))?;
+                    self.restore_row(&table, &snapshot)?;
+                }
+                other => anyhow::bail!("Unknown audit action: {}", other),
+            }
+            retry_busy(|| {
+                self.conn.execute(
+                    "UPDATE audit_log SET undone_at = CURRENT_TIMESTAMP WHERE id = ?1",
+                    params![audit_id],
+                )
+            })?;
+            let row = current.or_else(|| snapshot.and_then(|s| serde_json::from_str(&s).ok()));
+            undone.push(UndoneChange {
+                description: describe_change(&action, &table, row_id, row.as_ref()),
+                action,
+                table,
+                row_id,
+            });
+        }
+        tx.commit()?;
+
+        Ok(undone)
+    }
+
+    /// Create a compound food from component foods with amounts
+    /// items: Vec<(food_name, amount_str)>
+    pub fn create_compound_food(&self, name: &str, items: &[(String, String)]) -> Result<Macros> {
+        // Validate all component foods exist
+        let mut resolved: Vec<(i64, String)> = Vec::new();
+        for (food_name, amount) in items {
+            let food = match self.get_food_by_name(food_name)? {
+                Some(food) => food,
+                None => return Err(FoodNotFound::lookup(self, food_name)?.into()),
+            };
+            resolved.push((food.id.unwrap(), amount.clone()));
+        }
+
+        retry_busy(|| {
+            self.conn.execute(
+                "INSERT INTO compound_foods (name) VALUES (?1)",
+                params![name],
+            )
+        })?;
+        let compound_id = self.conn.last_insert_rowid();
+
+        for (food_id, amount) in &resolved {
+            retry_busy(|| {
+                self.conn.execute(
+                "INSERT INTO compound_food_items (compound_food_id, food_id, amount) VALUES (?1, ?2, ?3)",
+                params![compound_id, food_id, amount],
+            )
+            })?;
+        }
+
+        // Also create a regular food entry with the summed macros
+        let mut total = crate::food::Macros::default();
+        for (food_name, amount) in items {
+            let food = self.get_food_by_name(food_name)?.unwrap();
+            if let Some(macros) = food.calculate(amount) {
+                total.add(&macros);
+            } else {
+                // If can't calculate, use base macros
+                total.add(&crate::food::Macros {
+                    protein: food.protein,
+                    fat: food.fat,
+                    carbs: food.carbs,
+                    calories: food.calories,
+                    fiber: food.fiber,
+                    micros: food.micros,
+                });
+            }
+        }
+
+        retry_busy(|| {
+            self.conn.execute(
+                "INSERT OR REPLACE INTO foods (name, protein, fat, carbs, calories, serving, fiber,
+                                           sugar, sodium, saturated_fat)
+             VALUES (?1, ?2, ?3, ?4, ?5, '1serving', ?6, ?7, ?8, ?9)",
+                params![
+                    name,
+                    total.protein,
+                    total.fat,
+                    total.carbs,
+                    total.calories,
+                    total.fiber,
+                    total.micros.sugar,
+                    total.micros.sodium,
+                    total.micros.saturated_fat
+                ],
+            )
+        })?;
+
+        Ok(total)
+    }
+
+    /// How `name` divides if it's a compound food or recipe: one serving
+    /// unless set with `set_recipe_yield`.
+    pub fn recipe_yield(&self, name: &str) -> Result<Option<RecipeYield>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT servings, yield_grams FROM compound_foods WHERE LOWER(name) = LOWER(?1)",
+                params![name],
+                |row| {
+                    Ok(RecipeYield {
+                        servings: row.get(0)?,
+                        grams: row.get(1)?,
+                    })
+                },
+            )
+            .optional()?)
+    }
+
+    /// Divide the compound food `name` into `recipe`'s servings, updating
+    /// its food entry to the macros of one serving, which it returns.
+    pub fn set_recipe_yield(&self, name: &str, recipe: RecipeYield) -> Result<Macros> {
+        recipe.check()?;
+        let updated = retry_busy(|| {
+            self.conn.execute(
+                "UPDATE compound_foods SET servings = ?2, yield_grams = ?3
+                 WHERE LOWER(name) = LOWER(?1)",
+                params![name, recipe.servings, recipe.grams],
+            )
+        })?;
+        if updated == 0 {
+            anyhow::bail!(
+                "'{}' is not a recipe. Create it with: chomp recipe add",
+                name
+            );
+        }
+        self.refresh_compound(name)?;
+        self.compound_macros(name, 1.0)?
+            .ok_or_else(|| anyhow::anyhow!("'{}' has no ingredients", name))
+    }
+
+    /// Set the food entry of compound food `name` to the macros of one
+    /// serving from its components' current definitions. Returns the
+    /// entry's id and what it held before, or None if `name` isn't a
+    /// compound food with one.
+    fn refresh_compound(&self, name: &str) -> Result<Option<(i64, Option<serde_json::Value>)>> {
+        let Some(serving) = self.compound_macros(name, 1.0)? else {
+            return Ok(None);
+        };
+        let id: Option<i64> = self
+            .conn
+            .query_row(
+                "SELECT id FROM foods WHERE LOWER(name) = LOWER(?1)",
+                params![name],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let Some(id) = id else {
+            return Ok(None);
+        };
+        let before = self.snapshot_row("foods", id)?;
+        retry_busy(|| {
+            self.conn.execute(
+                "UPDATE foods SET protein = ?2, fat = ?3, carbs = ?4, calories = ?5, fiber = ?6,
+                                  sugar = ?7, sodium = ?8, saturated_fat = ?9
+                 WHERE id = ?1",
+                params![
+                    id,
+                    serving.protein,
+                    serving.fat,
+                    serving.carbs,
+                    serving.calories,
+                    serving.fiber,
+                    serving.micros.sugar,
+                    serving.micros.sodium,
+                    serving.micros.saturated_fat
+                ],
+            )
+        })?;
+        Ok(Some((id, before)))
+    }
+
+    /// Macros for `servings` of a compound food, summed from its components'
+    /// current definitions rather than the snapshot taken at creation, and
+    /// divided by the servings a recipe makes. None if `name` isn't a
+    /// compound food.
+    pub fn compound_macros(&self, name: &str, servings: f64) -> Result<Option<Macros>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT ci.food_id, ci.amount FROM compound_food_items ci
+             JOIN compound_foods c ON ci.compound_food_id = c.id
+             WHERE LOWER(c.name) = LOWER(?1)",
+        )?;
+        let items: Vec<(i64, String)> = stmt
+            .query_map(params![name], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+        let Some(recipe) = self.recipe_yield(name)?.filter(|_| !items.is_empty()) else {
+            return Ok(None);
+        };
+        let servings = servings / recipe.servings;
+
+        let mut total = Macros::default();
+        for (food_id, amount) in items {
+            let food = self
+                .get_food_by_id(food_id)?
+                .ok_or_else(|| anyhow::anyhow!("Component food #{} not found", food_id))?;
+            let macros = food.calculate(&amount).ok_or_else(|| {
+                anyhow::anyhow!("Could not calculate macros for {} of {}", amount, food.name)
+            })?;
+            total.add(&macros.scale(servings));
+        }
+        Ok(Some(total))
+    }
+
+    /// List compound food details
+    #[allow(dead_code)]
+    pub fn get_compound_food(&self, name: &str) -> Result<Vec<(String, String)>> {
+        let compound_id: i64 = self.conn.query_row(
+            "SELECT id FROM compound_foods WHERE LOWER(name) = LOWER(?1)",
+            params![name],
+            |row| row.get(0),
+        )?;
+
+        let mut stmt = self.conn.prepare(
+            "SELECT f.name, ci.amount FROM compound_food_items ci
+             JOIN foods f ON ci.food_id = f.id
+             WHERE ci.compound_food_id = ?1",
+        )?;
+
+        let items = stmt
+            .query_map(params![compound_id], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(items)
+    }
+}
+
+/// Write the next column of a Parquet row group; `None` values become nulls.
+#[cfg(feature = "parquet")]
+fn write_parquet_column<T: parquet::data_type::DataType>(
+    row_group: &mut parquet::file::writer::SerializedRowGroupWriter<'_, std::fs::File>,
+    values: Vec<Option<T::T>>,
+) -> Result<()> {
+    let mut column = row_group
+        .next_column()?
+        .ok_or_else(|| anyhow::anyhow!("Parquet schema has fewer columns than the export"))?;
+    let writer = column.typed::<T>();
+    let def_levels: Vec<i16> = values.iter().map(|v| v.is_some() as i16).collect();
+    let nullable = writer.get_descriptor().max_def_level() > 0;
+    let present: Vec<T::T> = values.into_iter().flatten().collect();
+    writer.write_batch(&present, nullable.then_some(def_levels.as_slice()), None)?;
+    column.close()?;
+    Ok(())
+}
+
+/// Human-readable summary of a reverted change, e.g. "Removed log entry #42 (200g)".
+fn describe_change(
+    action: &str,
+    table: &str,
+    row_id: i64,
+    row: Option<&serde_json::Value>,
+) -> String {
+    let verb = match action {
+        "insert" => "Removed",
+        "update" => "Reverted edit to",
+        _ => "Restored",
+    };
+    let field = |name: &str| row.map(|r| r[name].clone()).unwrap_or_default();
+    let detail = match table {
+        "log" => field("amount").as_str().map(String::from),
+        "water_log" => field("amount_ml").as_f64().map(|ml| format!("{:.0}ml", ml)),
+        "caffeine_log" => field("amount_mg").as_f64().map(|mg| format!("{:.0}mg", mg)),
+        "weights" => field("kg").as_f64().map(|kg| format!("{:.1}kg", kg)),
+        "foods" => field("name").as_str().map(String::from),
+        "aliases" => field("alias").as_str().map(String::from),
+        _ => None,
+    };
+    let noun = match table {
+        "log" => "log entry",
+        "water_log" => "water entry",
+        "caffeine_log" => "caffeine entry",
+        "weights" => "weigh-in",
+        "foods" => "food",
+        "aliases" => "alias",
+        "prep_batches" => "prep batch",
+        "food_tags" => "tag",
+        "food_units" => "unit weight",
+        "pantry" => "pantry item",
+        "compound_foods" => "compound food",
+        "compound_food_items" => "compound ingredient",
+        "trash" => "trash entry",
+        other => other,
+    };
+    match detail {
+        Some(d) => format!("{} {} #{} ({})", verb, noun, row_id, d),
+        None => format!("{} {} #{}", verb, noun, row_id),
+    }
+}
+
+/// Replaces personal food names in exports. Names of the built-in starter
+/// foods are generic and kept; anything else becomes "food-" plus an HMAC of
+/// the lowercased name. The key is a random secret kept in the database, so
+/// a name hashes the same in every export from it (entries for one food
+/// still group together), but can't be found by hashing guesses.
+struct Anonymizer {
+    common: std::collections::HashSet<String>,
+    key: ring::hmac::Key,
+}
+
+impl Anonymizer {
+    fn new(db: &Database) -> Result<Self> {
+        let mut reader = csv::Reader::from_reader(STARTER_FOODS.as_bytes());
+        let common = reader
+            .records()
+            .filter_map(|r| r.ok())
+            .filter_map(|r| r.get(0).map(|name| name.trim().to_lowercase()))
+            .collect();
+        retry_busy(|| {
+            db.conn.execute(
+                "INSERT OR IGNORE INTO meta (key, value)
+                 VALUES ('anonymize_key', lower(hex(randomblob(32))))",
+                [],
+            )
+        })?;
+        let secret = db.meta("anonymize_key")?.unwrap_or_default();
+        let key = ring::hmac::Key::new(ring::hmac::HMAC_SHA256, secret.as_bytes());
+        Ok(Self { common, key })
+    }
+
+    fn name(&self, name: &str) -> String {
+        let lower = name.trim().to_lowercase();
+        if self.common.contains(&lower) {
+            return name.to_string();
+        }
+        let tag = ring::hmac::sign(&self.key, lower.as_bytes());
+        let hex: String = tag.as_ref()[..6]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        format!("food-{}", hex)
+    }
+}
+
+fn export_name(anon: Option<&Anonymizer>, name: String) -> String {
+    match anon {
+        Some(anon) => anon.name(&name),
+        None => name,
+    }
+}
+
+/// A column value as JSON; blobs become null.
+fn json_value(value: ValueRef) -> serde_json::Value {
+    match value {
+        ValueRef::Null | ValueRef::Blob(_) => serde_json::Value::Null,
+        ValueRef::Integer(n) => serde_json::json!(n),
+        ValueRef::Real(f) => serde_json::json!(f),
+        ValueRef::Text(t) => serde_json::json!(String::from_utf8_lossy(t)),
+    }
+}
+
+/// Canonical grams for an exported entry, when its food is still around and
+/// the amount has a known weight. `units` are the food's own unit weights.
+fn export_grams(
+    amount: &str,
+    serving: Option<String>,
+    units: &std::collections::BTreeMap<String, f64>,
+) -> Option<f64> {
+    crate::food::amount_grams(amount, &serving?, units).map(|g| (g * 10.0).round() / 10.0)
+}
+
+/// A food's aliases as a JSON array and its own units as a JSON object,
+/// for a query over `foods f`.
+const ALIASES_COLUMN: &str =
+    "(SELECT json_group_array(a.alias) FROM aliases a WHERE a.food_id = f.id),
+     (SELECT json_group_object(u.unit, u.grams) FROM food_units u WHERE u.food_id = f.id)";
+
+/// A food's own units as a JSON object, for exports joining `foods f`.
+const UNITS_COLUMN: &str =
+    "(SELECT json_group_object(u.unit, u.grams) FROM food_units u WHERE u.food_id = f.id)";
+
+/// The columns `food_from_row` reads, ahead of `ALIASES_COLUMN`.
+const ENTRY_COLUMNS: &str = "l.id, l.date, COALESCE(f.name, 'deleted'), l.food_id, l.amount,
+     l.protein, l.fat, l.carbs, l.calories, l.recurring_id, l.meal";
+
+/// A log entry from `ENTRY_COLUMNS`, with `foods f` joined to `log l`.
+fn entry_from_row(row: &rusqlite::Row) -> rusqlite::Result<LogEntry> {
+    Ok(LogEntry {
+        id: Some(row.get(0)?),
+        date: row.get(1)?,
+        food_name: row.get(2)?,
+        food_id: row.get(3)?,
+        amount: row.get(4)?,
+        protein: row.get(5)?,
+        fat: row.get(6)?,
+        carbs: row.get(7)?,
+        calories: row.get(8)?,
+        recurring: row.get(9)?,
+        meal: row.get(10)?,
+        warning: None,
+    })
+}
+
+const FOOD_COLUMNS: &str = "f.id, f.name, f.protein, f.fat, f.carbs, f.calories, f.serving,
+     f.default_amount, f.fiber, f.sugar, f.sodium, f.saturated_fat";
+
+fn food_from_row(row: &rusqlite::Row) -> rusqlite::Result<Food> {
+    Ok(Food {
+        id: Some(row.get(0)?),
+        name: row.get(1)?,
+        protein: row.get(2)?,
+        fat: row.get(3)?,
+        carbs: row.get(4)?,
+        calories: row.get(5)?,
+        serving: row.get(6)?,
+        default_amount: row.get(7)?,
+        fiber: row.get(8)?,
+        micros: micros_from_row(row, 9)?,
+        aliases: parse_aliases(row, 12)?,
+        units: parse_units(row, 13)?,
+    })
+}
+
+/// Sugar, sodium, and saturated fat from three columns starting at `idx`.
+fn micros_from_row(row: &rusqlite::Row, idx: usize) -> rusqlite::Result<Micros> {
+    Ok(Micros {
+        sugar: row.get(idx)?,
+        sodium: row.get(idx + 1)?,
+        saturated_fat: row.get(idx + 2)?,
+    })
+}
+
+/// Aliases from an `ALIASES_COLUMN` value, sorted.
+fn parse_aliases(row: &rusqlite::Row, idx: usize) -> rusqlite::Result<Vec<String>> {
+    let json: String = row.get(idx)?;
+    let mut aliases: Vec<String> = serde_json::from_str(&json).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(idx, rusqlite::types::Type::Text, Box::new(e))
+    })?;
+    aliases.sort();
+    Ok(aliases)
+}
+
+/// A food's own units from the JSON object `ALIASES_COLUMN` ends with.
+fn parse_units(
+    row: &rusqlite::Row,
+    idx: usize,
+) -> rusqlite::Result<std::collections::BTreeMap<String, f64>> {
+    let json: String = row.get(idx)?;
+    serde_json::from_str(&json).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(idx, rusqlite::types::Type::Text, Box::new(e))
+    })
+}
+
+/// Commands kept for `chomp redo`.
+const COMMAND_HISTORY_LIMIT: i64 = 1000;
+
+/// A URL-safe slug: lowercase letters and digits, runs of anything else
+/// turned into single dashes. "Morning Shake!" becomes "morning-shake".
+fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.trim().chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+/// Lowest name similarity (0..1) at which `most_likely_food` will guess.
+const MIN_GUESS_SIMILARITY: f64 = 0.7;
+
+/// Lowest name similarity at which a failed lookup suggests a food.
+const MIN_SUGGEST_SIMILARITY: f64 = 0.5;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::food::{Food, Macros};
+
+    fn test_db() -> Database {
+        Database::open_in_memory().unwrap()
+    }
+
+    fn sample_food(name: &str) -> Food {
+        Food::new(name, 26.0, 15.0, 0.0, 250.0, "100g", vec![])
+    }
+
+    #[test]
+    fn test_add_and_retrieve_food() {
+        let db = test_db();
+        let food = sample_food("Ribeye");
+        let id = db.add_food(&food).unwrap();
+        assert!(id > 0);
+
+        let found = db.get_food_by_name("ribeye").unwrap().unwrap();
+        assert_eq!(found.name, "Ribeye");
+        assert_eq!(found.protein, 26.0);
+    }
+
+    #[test]
+    fn test_alias_suggestions_from_misses() {
+        let db = test_db();
+        let food_id = db.add_food(&sample_food("Chicken Breast")).unwrap();
+        db.record_lookup_miss("Chikn").unwrap();
+        db.record_lookup_miss("chikn").unwrap();
+        db.record_lookup_miss("ribeye").unwrap();
+
+        // Nothing logged yet, so nothing to suggest
+        assert!(db.alias_suggestions(10).unwrap().is_empty());
+
+        let macros = Macros::default();
+        db.log_food_with(food_id, "100g", &macros, &LogOptions::default())
+            .unwrap();
+        db.add_food(&sample_food("Ribeye")).unwrap();
+
+        // "ribeye" resolves now, so only the typo is suggested
+        let suggestions = db.alias_suggestions(10).unwrap();
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].query, "chikn");
+        assert_eq!(suggestions[0].food_name, "Chicken Breast");
+        assert_eq!(suggestions[0].misses, 2);
+
+        db.accept_alias_suggestion(&suggestions[0]).unwrap();
+        assert_eq!(
+            db.get_food_by_name("chikn").unwrap().unwrap().name,
+            "Chicken Breast"
+        );
+        assert!(db.alias_suggestions(10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_most_likely_food_weighs_history() {
+        let db = test_db();
+        let white = db.add_food(&sample_food("White Rice")).unwrap();
+        db.add_food(&sample_food("Brown Rice")).unwrap();
+        db.add_food(&sample_food("Ribeye")).unwrap();
+
+        // Same distance to both rices; the one I actually eat wins
+        let macros = Macros::default();
+        db.log_food_with(white, "100g", &macros, &LogOptions::default())
+            .unwrap();
+        assert_eq!(
+            db.most_likely_food("rice").unwrap().unwrap().name,
+            "White Rice"
+        );
+        assert_eq!(
+            db.most_likely_food("ribye").unwrap().unwrap().name,
+            "Ribeye"
+        );
+        assert!(db.most_likely_food("pizza").unwrap().is_none());
+        assert!(db.get_food_by_name("ribye").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_import_starter() {
+        let db = test_db();
+        db.add_food(&sample_food("Ribeye")).unwrap();
+
+        let added = db.import_starter().unwrap();
+        assert!(added >= 150);
+
+        // Existing foods are kept; bundled ones resolve by alias with sane servings
+        assert_eq!(
+            db.get_food_by_name("ribeye").unwrap().unwrap().protein,
+            26.0
+        );
+        let egg = db.get_food_by_name("eggs").unwrap().unwrap();
+        assert_eq!(egg.name, "egg");
+        let m = egg.calculate("2 eggs").unwrap();
+        assert!((m.calories - 144.0).abs() < 0.01);
+
+        assert_eq!(db.import_starter().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_add_food_with_aliases() {
+        let db = test_db();
+        let food = Food::new(
+            "Chicken Breast",
+            31.0,
+            3.6,
+            0.0,
+            165.0,
+            "100g",
+            vec!["chicken".to_string(), "chx".to_string()],
+        );
+        db.add_food(&food).unwrap();
+
+        let found = db.get_food_by_name("chicken").unwrap().unwrap();
+        assert_eq!(found.name, "Chicken Breast");
+
+        let found2 = db.get_food_by_name("chx").unwrap().unwrap();
+        assert_eq!(found2.name, "Chicken Breast");
+        assert_eq!(found2.aliases, ["chicken", "chx"]);
+
+        let by_name = db.get_food_by_name("chicken breast").unwrap().unwrap();
+        assert_eq!(by_name.aliases, ["chicken", "chx"]);
+        let searched = db.search_foods("chicken").unwrap();
+        assert_eq!(searched[0].aliases, ["chicken", "chx"]);
+    }
+
+    #[test]
+    fn test_alias_csv_round_trip() {
+        let db = test_db();
+        let mut chicken = sample_food("Chicken Breast");
+        chicken.aliases = vec!["chx".to_string(), "chicken".to_string()];
+        db.add_food(&chicken).unwrap();
+        db.add_food(&sample_food("Rice")).unwrap();
+        let mut out = Vec::new();
+        assert_eq!(db.export_aliases_csv(&mut out).unwrap(), 2);
+        assert_eq!(
+            String::from_utf8(out.clone()).unwrap(),
+            "food,alias\nChicken Breast,chicken\nChicken Breast,chx\n"
+        );
+
+        let other = test_db();
+        other.add_food(&sample_food("chicken breast")).unwrap();
+        other.add_food(&sample_food("Chx Nuggets")).unwrap();
+        let mut nuggets = sample_food("Nuggets");
+        nuggets.aliases = vec!["chx".to_string()];
+        other.add_food(&nuggets).unwrap();
+        let csv = [out.as_slice(), b"Chicken Breast,breast\nTofu,bean curd\n"].concat();
+        let imported = other.import_aliases_csv(&csv).unwrap();
+        assert_eq!(
+            imported,
+            AliasImport {
+                added: 2,
+                existing: 0,
+                conflicts: vec!["chx".to_string()],
+                missing_foods: vec!["Tofu".to_string()],
+            }
+        );
+        let found = other.get_food_by_name("chicken").unwrap().unwrap();
+        assert_eq!(found.aliases, ["breast", "chicken"]);
+        assert_eq!(other.import_aliases_csv(&csv).unwrap().existing, 2);
+    }
+
+    #[test]
+    fn test_search_foods_fuzzy() {
+        let db = test_db();
+        db.add_food(&sample_food("Ribeye Steak")).unwrap();
+        db.add_food(&sample_food("Rice")).unwrap();
+        db.add_food(&sample_food("Salmon")).unwrap();
+
+        let results = db.search_foods("rib").unwrap();
+        assert!(!results.is_empty());
+        assert_eq!(results[0].name, "Ribeye Steak");
+    }
+
+    #[test]
+    fn test_log_food_and_today_totals() {
+        let db = test_db();
+        let food = sample_food("Eggs");
+        let id = db.add_food(&food).unwrap();
+
+        let macros = Macros {
+            protein: 12.0,
+            fat: 10.0,
+            carbs: 1.0,
+            calories: 142.0,
+            fiber: 0.0,
+            ..Default::default()
+        };
+        let entry = db
+            .log_food_with(id, "2", &macros, &LogOptions::default())
+            .unwrap();
+        assert_eq!(entry.food_name, "Eggs");
+        assert_eq!(entry.protein, 12.0);
+
+        let totals = db.get_today_totals().unwrap();
+        assert_eq!(totals.protein, 12.0);
+        assert_eq!(totals.calories, 142.0);
+
+        // Log another
+        let macros2 = Macros {
+            protein: 26.0,
+            fat: 15.0,
+            carbs: 0.0,
+            calories: 250.0,
+            fiber: 0.0,
+            ..Default::default()
+        };
+        db.log_food_with(id, "100g", &macros2, &LogOptions::default())
+            .unwrap();
+
+        let totals = db.get_today_totals().unwrap();
+        assert_eq!(totals.protein, 38.0);
+    }
+
+    #[test]
+    fn test_get_history() {
+        let db = test_db();
+        let id = db.add_food(&sample_food("Bacon")).unwrap();
+        let macros = Macros {
+            protein: 12.0,
+            fat: 40.0,
+            carbs: 0.0,
+            calories: 400.0,
+            fiber: 0.0,
+            ..Default::default()
+        };
+        db.log_food_with(id, "100g", &macros, &LogOptions::default())
+            .unwrap();
+
+        let history = db.get_history(7).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].food_name, "Bacon");
+    }
+
+    #[test]
+    fn test_history_time_window() {
+        let db = test_db();
+        let id = db.add_food(&sample_food("Bacon")).unwrap();
+        for (amount, time) in [
+            ("1", "08:00"),
+            ("2", "12:30"),
+            ("3", "19:15"),
+            ("4", "23:30"),
+        ] {
+            let opts = LogOptions {
+                time: Some(parse_time_of_day(time).unwrap()),
+                ..Default::default()
+            };
+            db.log_food_with(id, amount, &Macros::default(), &opts)
+                .unwrap();
+        }
+        let amounts = |after: Option<&str>, before: Option<&str>| {
+            let window = TimeWindow::parse(after, before).unwrap();
+            db.get_history_page(0, window, None, 0)
+                .unwrap()
+                .entries
+                .into_iter()
+                .map(|e| e.amount)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(amounts(Some("18:00"), None), ["4", "3"]);
+        assert_eq!(amounts(None, Some("12:30")), ["1"]);
+        assert_eq!(amounts(Some("11am"), Some("8pm")), ["3", "2"]);
+        // After 10pm or before 9am
+        assert_eq!(amounts(Some("22:00"), Some("9")), ["4", "1"]);
+    }
+
+    #[test]
+    fn test_parse_time_of_day() {
+        let t = |s: &str| {
+            parse_time_of_day(s)
+                .ok()
+                .map(|t| t.format("%H:%M").to_string())
+        };
+        assert_eq!(t("18:00").as_deref(), Some("18:00"));
+        assert_eq!(t("6pm").as_deref(), Some("18:00"));
+        assert_eq!(t("6:30 PM").as_deref(), Some("18:30"));
+        assert_eq!(t("12am").as_deref(), Some("00:00"));
+        assert_eq!(t("12pm").as_deref(), Some("12:00"));
+        assert_eq!(t("7").as_deref(), Some("07:00"));
+        assert_eq!(t("13pm"), None);
+        assert_eq!(t("25:00"), None);
+        assert_eq!(t("dinner"), None);
+    }
+
+    #[test]
+    fn test_log_backdated_and_timed() {
+        let db = test_db();
+        let id = db.add_food(&sample_food("Bacon")).unwrap();
+        let opts = LogOptions {
+            date: Some("2024-05-01"),
+            time: Some(parse_time_of_day("8:30am").unwrap()),
+            ..Default::default()
+        };
+        let entry = db
+            .log_food_with(id, "1", &Macros::default(), &opts)
+            .unwrap();
+        assert_eq!(entry.date, "2024-05-01");
+        let logged_at: String = db
+            .conn
+            .query_row(
+                "SELECT created_at FROM log WHERE id = ?1",
+                params![entry.id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        let local = chrono::NaiveDateTime::parse_from_str("2024-05-01 08:30", "%Y-%m-%d %H:%M")
+            .unwrap()
+            .and_local_timezone(Local)
+            .unwrap();
+        assert_eq!(
+            logged_at,
+            local.naive_utc().format("%Y-%m-%d %H:%M:%S").to_string()
+        );
+
+        let bad = LogOptions {
+            date: Some("May 1"),
+            ..Default::default()
+        };
+        assert!(db.log_food_with(id, "1", &Macros::default(), &bad).is_err());
+    }
+
+    #[test]
+    fn test_get_history_pages() {
+        let db = test_db();
+        let id = db.add_food(&sample_food("Bacon")).unwrap();
+        for amount in ["1", "2", "3", "4", "5"] {
+            db.log_food_with(id, amount, &Macros::default(), &LogOptions::default())
+                .unwrap();
+        }
+
+        let page = db
+            .get_history_page(7, TimeWindow::default(), Some(2), 0)
+            .unwrap();
+        let amounts: Vec<&str> = page.entries.iter().map(|e| e.amount.as_str()).collect();
+        assert_eq!(amounts, ["5", "4"]);
+        assert_eq!(page.next_offset, Some(2));
+
+        let last = db
+            .get_history_page(7, TimeWindow::default(), Some(2), 4)
+            .unwrap();
+        assert_eq!(last.entries.len(), 1);
+        assert_eq!(last.next_offset, None);
+        assert_eq!(
+            db.get_history_page(7, TimeWindow::default(), Some(5), 0)
+                .unwrap()
+                .next_offset,
+            None
+        );
+        assert_eq!(
+            db.get_history_page(7, TimeWindow::default(), None, 3)
+                .unwrap()
+                .entries
+                .len(),
+            2
+        );
+
+        let empty = db
+            .get_history_page(7, TimeWindow::default(), Some(0), 0)
+            .unwrap();
+        assert_eq!(empty.entries.len(), 1);
+        assert_eq!(empty.next_offset, Some(1));
+    }
+
+    #[test]
+    fn test_edit_food() {
+        let db = test_db();
+        db.add_food(&sample_food("Salmon")).unwrap();
+
+        db.edit_food(
+            "Salmon",
+            Some(25.0),
+            None,
+            None,
+            None,
+            None,
+            None,
+            &Micros::default(),
+        )
+        .unwrap();
+        let food = db.get_food_by_name("Salmon").unwrap().unwrap();
+        assert_eq!(food.protein, 25.0);
+        // calories recalculated: 25*4 + 15*9 + 0*4 = 235
+        assert_eq!(food.calories, 235.0);
+
+        db.edit_food(
+            "Salmon",
+            None,
+            None,
+            Some(10.0),
+            Some(4.0),
+            None,
+            None,
+            &Micros::default(),
+        )
+        .unwrap();
+        let food = db.get_food_by_name("Salmon").unwrap().unwrap();
+        assert_eq!((food.carbs, food.fiber), (10.0, 4.0));
+    }
+
+    #[test]
+    fn test_delete_food() {
+        let db = test_db();
+        db.add_food(&sample_food("Temp Food")).unwrap();
+        assert!(db.get_food_by_name("Temp Food").unwrap().is_some());
+
+        db.delete_food("Temp Food", DeleteMode::Refuse).unwrap();
+        assert!(db.get_food_by_name("Temp Food").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_delete_log_entry() {
+        let db = test_db();
+        let id = db.add_food(&sample_food("Apple")).unwrap();
+        let macros = Macros {
+            protein: 0.3,
+            fat: 0.2,
+            carbs: 14.0,
+            calories: 52.0,
+            fiber: 0.0,
+            ..Default::default()
+        };
+        let entry = db
+            .log_food_with(id, "1", &macros, &LogOptions::default())
+            .unwrap();
+
+        let deleted = db.delete_log_entry(entry.id.unwrap()).unwrap();
+        assert_eq!(deleted.food_name, "Apple");
+
+        let totals = db.get_today_totals().unwrap();
+        assert_eq!(totals.calories, 0.0);
+    }
+
+    #[test]
+    fn test_delete_last_log_entry() {
+        let db = test_db();
+        let id = db.add_food(&sample_food("Banana")).unwrap();
+        let m = Macros {
+            protein: 1.0,
+            fat: 0.3,
+            carbs: 23.0,
+            calories: 89.0,
+            fiber: 0.0,
+            ..Default::default()
+        };
+        db.log_food_with(id, "1", &m, &LogOptions::default())
+            .unwrap();
+        db.log_food_with(id, "1", &m, &LogOptions::default())
+            .unwrap();
+
+        let deleted = db.delete_last_log_entry().unwrap();
+        assert_eq!(deleted.food_name, "Banana");
+
+        let totals = db.get_today_totals().unwrap();
+        assert_eq!(totals.calories, 89.0);
+    }
+
+    #[test]
+    fn test_edit_log_entry() {
+        let db = test_db();
+        let id = db.add_food(&sample_food("Steak")).unwrap();
+        let m = Macros {
+            protein: 26.0,
+            fat: 15.0,
+            carbs: 0.0,
+            calories: 250.0,
+            fiber: 0.0,
+            ..Default::default()
+        };
+        let entry = db
+            .log_food_with(id, "100g", &m, &LogOptions::default())
+            .unwrap();
+
+        let updated = db
+            .edit_log_entry(
+                entry.id.unwrap(),
+                Some("200g".to_string()),
+                Some(52.0),
+                None,
+                None,
+            )
+            .unwrap();
+        assert_eq!(updated.amount, "200g");
+        assert_eq!(updated.protein, 52.0);
+    }
+
+    #[test]
+    fn test_get_stats() {
+        let db = test_db();
+        let stats = db.get_stats().unwrap();
+        assert_eq!(stats.food_count, 0);
+        assert_eq!(stats.log_count, 0);
+
+        let id = db.add_food(&sample_food("Rice")).unwrap();
+        let m = Macros {
+            protein: 2.7,
+            fat: 0.3,
+            carbs: 28.0,
+            calories: 130.0,
+            fiber: 0.0,
+            ..Default::default()
+        };
+        db.log_food_with(id, "100g", &m, &LogOptions::default())
+            .unwrap();
+
+        let stats = db.get_stats().unwrap();
+        assert_eq!(stats.food_count, 1);
+        assert_eq!(stats.log_count, 1);
+    }
+
+    #[test]
+    fn test_retry_busy_backs_off_then_succeeds() {
+        let mut calls = 0;
+        let result = retry_busy(|| {
+            calls += 1;
+            if calls < 3 {
+                Err(rusqlite::Error::SqliteFailure(
+                    rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY),
+                    None,
+                ))
+            } else {
+                Ok(calls)
+            }
+        });
+        assert_eq!(result.unwrap(), 3);
+
+        // Other errors are returned immediately
+        let mut calls = 0;
+        let result: rusqlite::Result<()> = retry_busy(|| {
+            calls += 1;
+            Err(rusqlite::Error::QueryReturnedNoRows)
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn test_profile_path_validation() {
+        let path = Database::profile_path("alice").unwrap();
+        assert!(path.ends_with("profiles/alice.db"));
+        assert!(Database::profile_path("../etc").is_err());
+        assert!(Database::profile_path("").is_err());
+    }
+
+    #[test]
+    fn test_get_last_logged_at() {
+        let db = test_db();
+        assert!(db.get_last_logged_at().unwrap().is_none());
+
+        let id = db.add_food(&sample_food("Rice")).unwrap();
+        db.log_food_with(id, "100g", &Macros::default(), &LogOptions::default())
+            .unwrap();
+        let ts = db.get_last_logged_at().unwrap().unwrap();
+        assert!((chrono::Utc::now() - ts).num_seconds() < 60);
+    }
+
+    #[test]
+    fn test_idempotency_key_prevents_double_logging() {
+        let db = test_db();
+        let id = db.add_food(&sample_food("Oats")).unwrap();
+        let m = Macros {
+            protein: 5.0,
+            fat: 3.0,
+            carbs: 27.0,
+            calories: 150.0,
+            fiber: 0.0,
+            ..Default::default()
+        };
+        let opts = LogOptions {
+            idempotency_key: Some("req-1"),
+            ..Default::default()
+        };
+        let first = db.log_food_with(id, "40g", &m, &opts).unwrap();
+        let retry = db.log_food_with(id, "40g", &m, &opts).unwrap();
+        assert_eq!(first.id, retry.id);
+        assert_eq!(db.get_today_totals().unwrap().calories, 150.0);
+
+        // Without a key or a dedupe window, repeats are logged as-is
+        db.log_food_with(id, "40g", &m, &LogOptions::default())
+            .unwrap();
+        db.log_food_with(id, "40g", &m, &LogOptions::default())
+            .unwrap();
+        assert_eq!(db.get_today_totals().unwrap().calories, 450.0);
+
+        let w1 = db.log_water_with(500.0, &opts).unwrap();
+        let w2 = db.log_water_with(500.0, &opts).unwrap();
+        assert_eq!(w1.id, w2.id);
+        assert_eq!(db.get_today_water().unwrap().total_ml, 500.0);
+    }
+
+    #[test]
+    fn test_dedupe_window_rejects_recent_duplicates() {
+        let db = test_db();
+        let id = db.add_food(&sample_food("Oats")).unwrap();
+        let m = Macros {
+            protein: 5.0,
+            fat: 3.0,
+            carbs: 27.0,
+            calories: 150.0,
+            fiber: 0.0,
+            ..Default::default()
+        };
+        let opts = LogOptions {
+            dedupe_window: Some(5),
+            ..Default::default()
+        };
+        let first = db.log_food_with(id, "40g", &m, &opts).unwrap();
+        let err = db.log_food_with(id, "40g", &m, &opts).unwrap_err();
+        let dup = err.downcast_ref::<DuplicateLog>().unwrap();
+        assert_eq!(dup.existing.id, first.id);
+
+        // Or logs it with a warning, as the CLI does
+        let warned = LogOptions {
+            warn_duplicates: true,
+            ..opts
+        };
+        let again = db.log_food_with(id, "40g", &m, &warned).unwrap();
+        assert!(again.warning.unwrap().contains("already logged"));
+        db.delete_log_entry(again.id.unwrap()).unwrap();
+
+        // A different amount is not a duplicate
+        db.log_food_with(id, "80g", &m, &opts).unwrap();
+
+        // Outside the window the same entry is accepted again
+        db.conn
+            .execute(
+                "UPDATE log SET created_at = datetime('now', '-10 minutes')",
+                [],
+            )
+            .unwrap();
+        db.log_food_with(id, "40g", &m, &opts).unwrap();
+        assert_eq!(db.get_today_totals().unwrap().calories, 450.0);
+    }
+
+    #[test]
+    fn test_export_ndjson() {
+        let db = test_db();
+        let id = db
+            .add_food(&Food::new(
+                "Oats",
+                13.0,
+                7.0,
+                68.0,
+                389.0,
+                "100g",
+                vec!["oatmeal".to_string()],
+            ))
+            .unwrap();
+        let m = Macros {
+            protein: 6.5,
+            fat: 3.5,
+            carbs: 34.0,
+            calories: 194.5,
+            fiber: 0.0,
+            ..Default::default()
+        };
+        db.log_food_with(id, "50g", &m, &LogOptions::on_date(Some("2024-01-02")))
+            .unwrap();
+        db.log_food_with(id, "100g", &m, &LogOptions::on_date(Some("2024-01-01")))
+            .unwrap();
+
+        let mut buf = Vec::new();
+        db.export_ndjson(&mut buf, false).unwrap();
+        let lines: Vec<serde_json::Value> = String::from_utf8(buf)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["type"], "food");
+        assert_eq!(lines[0]["aliases"], serde_json::json!(["oatmeal"]));
+        assert_eq!(lines[1]["type"], "log");
+        assert_eq!(lines[1]["date"], "2024-01-01");
+        assert_eq!(lines[2]["amount"], "50g");
+        assert_eq!(lines[2]["grams"], 50.0);
+    }
+
+    #[test]
+    fn test_export_ndjson_anonymized() {
+        let db = test_db();
+        let private = db
+            .add_food(&Food::new(
+                "Grandma's Lasagna",
+                20.0,
+                15.0,
+                30.0,
+                340.0,
+                "1 slice",
+                vec!["nonna".to_string()],
+            ))
+            .unwrap();
+        let rice = db.add_food(&sample_food("White Rice")).unwrap();
+        let m = Macros::default();
+        db.log_food_with(private, "1", &m, &LogOptions::on_date(Some("2024-01-01")))
+            .unwrap();
+        db.log_food_with(private, "2", &m, &LogOptions::on_date(Some("2024-01-02")))
+            .unwrap();
+        db.log_food_with(rice, "100g", &m, &LogOptions::on_date(Some("2024-01-02")))
+            .unwrap();
+
+        let mut buf = Vec::new();
+        db.export_ndjson(&mut buf, true).unwrap();
+        let text = String::from_utf8(buf).unwrap();
+        assert!(!text.to_lowercase().contains("lasagna"));
+        assert!(!text.contains("nonna"));
+        let lines: Vec<serde_json::Value> = text
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        let hashed = lines[0]["name"].as_str().unwrap();
+        assert!(hashed.starts_with("food-"));
+        assert_eq!(lines[1]["name"], "White Rice");
+        assert_eq!(lines[2]["food_name"], hashed);
+        assert_eq!(lines[3]["food_name"], hashed);
+
+        // The same in a later export, but keyed per database
+        let anon = Anonymizer::new(&db).unwrap();
+        assert_eq!(anon.name("Grandma's Lasagna"), hashed);
+        let other = Anonymizer::new(&test_db()).unwrap();
+        assert_ne!(other.name("Grandma's Lasagna"), hashed);
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_export_parquet() {
+        let db = test_db();
+        let id = db.add_food(&sample_food("Oats")).unwrap();
+        let m = Macros {
+            protein: 5.0,
+            fat: 3.0,
+            carbs: 27.0,
+            calories: 150.0,
+            ..Default::default()
+        };
+        db.log_food_with(id, "40g", &m, &LogOptions::default())
+            .unwrap();
+        db.log_food_with(id, "80g", &m, &LogOptions::default())
+            .unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log.parquet");
+        assert_eq!(db.export_parquet(&path, false).unwrap(), 2);
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+        let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let meta = reader.metadata().file_metadata();
+        assert_eq!(meta.num_rows(), 2);
+        assert_eq!(meta.schema_descr().num_columns(), 17);
+
+        // Entries kept from a food purged from the trash have no food_id
+        let rye = db.add_food(&sample_food("Rye")).unwrap();
+        db.log_food_with(rye, "60g", &m, &LogOptions::default())
+            .unwrap();
+        db.delete_food("rye", DeleteMode::KeepHistory).unwrap();
+        db.conn
+            .execute(
+                "UPDATE trash SET deleted_at = datetime('now', '-31 days')",
+                [],
+            )
+            .unwrap();
+        db.list_trash().unwrap();
+        assert_eq!(db.export_parquet(&path, false).unwrap(), 3);
+        let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let food_ids: Vec<String> = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| row.unwrap().get_column_iter().nth(3).unwrap().1.to_string())
+            .collect();
+        assert_eq!(
+            food_ids,
+            [id.to_string(), id.to_string(), "null".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_today_totals_cache_sees_every_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("chomp.sqlite");
+        let server = Database::open_at(&path).unwrap().with_totals_cache(true);
+        server.init().unwrap();
+        let id = server.add_food(&sample_food("Ribeye")).unwrap();
+        let m = server
+            .get_food_by_name("ribeye")
+            .unwrap()
+            .unwrap()
+            .calculate("100g")
+            .unwrap();
+        assert_eq!(server.get_today_totals().unwrap().calories, 0.0);
+
+        // A write on the cached connection, then one from another process
+        server
+            .log_food_with(id, "100g", &m, &LogOptions::default())
+            .unwrap();
+        assert_eq!(server.get_today_totals().unwrap().calories, 250.0);
+        let cli = Database::open_at(&path).unwrap();
+        cli.log_food_with(id, "100g", &m, &LogOptions::default())
+            .unwrap();
+        assert_eq!(server.get_today_totals().unwrap().calories, 500.0);
+        assert_eq!(server.get_today_totals().unwrap().calories, 500.0);
+        cli.undo_last(1).unwrap();
+        assert_eq!(server.get_today_totals().unwrap().calories, 250.0);
+    }
+
+    #[test]
+    fn test_migrate_adds_missing_columns() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE log (id INTEGER PRIMARY KEY AUTOINCREMENT, date TEXT NOT NULL,
+                food_id INTEGER NOT NULL, amount TEXT NOT NULL, protein REAL NOT NULL,
+                fat REAL NOT NULL, carbs REAL NOT NULL, calories REAL NOT NULL,
+                created_at TEXT DEFAULT CURRENT_TIMESTAMP);",
+        )
+        .unwrap();
+        let db = Database {
+            conn,
+            lock_after_days: None,
+            calorie_factors: CalorieFactors::default(),
+            carb_mode: CarbMode::Total,
+            incomplete_days: IncompleteDays::default(),
+            cache_totals: false,
+        };
+        db.init().unwrap();
+        let id = db.add_food(&sample_food("Eggs")).unwrap();
+        let opts = LogOptions {
+            idempotency_key: Some("k"),
+            ..Default::default()
+        };
+        db.log_food_with(id, "1", &Macros::default(), &opts)
+            .unwrap();
+
+        // food_id lost its NOT NULL; the index and triggers came back
+        let not_null: bool = db
+            .conn
+            .query_row(
+                "SELECT \"notnull\" FROM pragma_table_info('log') WHERE name = 'food_id'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(!not_null);
+        assert!(db.log_food_with(id, "1", &Macros::default(), &opts).is_ok());
+        assert_eq!(db.get_history(1).unwrap().len(), 1);
+        assert_eq!(db.get_today_totals().unwrap().calories, 0.0);
+        assert_eq!(
+            db.conn
+                .query_row("SELECT entries FROM daily_totals", [], |row| row
+                    .get::<_, i64>(0))
+                .unwrap(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_undo_last_reverts_mutations() {
+        let db = test_db();
+        let id = db.add_food(&sample_food("Toast")).unwrap();
+        let m = Macros {
+            protein: 3.0,
+            fat: 1.0,
+            carbs: 15.0,
+            calories: 80.0,
+            fiber: 0.0,
+            ..Default::default()
+        };
+
+        // Undo an insert
+        db.log_food_with(id, "1", &m, &LogOptions::default())
+            .unwrap();
+        let undone = db.undo_last(10).unwrap();
+        assert_eq!(undone[0].action, "insert");
+        assert_eq!(undone[0].table, "log");
+        assert_eq!(db.get_today_totals().unwrap().calories, 0.0);
+
+        // Undo a delete restores the original row
+        let entry = db
+            .log_food_with(id, "2", &m, &LogOptions::default())
+            .unwrap();
+        db.delete_log_entry(entry.id.unwrap()).unwrap();
+        db.undo_last(10).unwrap();
+        let history = db.get_history(1).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].amount, "2");
+
+        // Undo an edit
+        db.edit_log_entry(entry.id.unwrap(), Some("5".into()), None, None, None)
+            .unwrap();
+        db.undo_last(10).unwrap();
+        assert_eq!(db.get_history(1).unwrap()[0].amount, "2");
+
+        // Undoing add_food removes the food and its aliases together
+        db.add_food(&Food::new(
+            "Jam",
+            0.0,
+            0.0,
+            13.0,
+            52.0,
+            "1tbsp",
+            vec!["jelly".to_string()],
+        ))
+        .unwrap();
+        let undone = db.undo_last(10).unwrap();
+        assert_eq!(undone.len(), 2);
+        assert!(db.get_food_by_name("jelly").unwrap().is_none());
+
+        // Undoing an edit or a delete of a food keeps its aliases and tags
+        let jam = db
+            .add_food(&Food::new(
+                "Jam",
+                0.0,
+                0.0,
+                13.0,
+                52.0,
+                "1tbsp",
+                vec!["jelly".to_string()],
+            ))
+            .unwrap();
+        db.tag_food(jam, "sweet").unwrap();
+        db.edit_food(
+            "jam",
+            None,
+            None,
+            Some(14.0),
+            None,
+            None,
+            None,
+            &Micros::default(),
+        )
+        .unwrap();
+        db.undo_last(10).unwrap();
+        assert_eq!(db.get_food_by_name("jelly").unwrap().unwrap().carbs, 13.0);
+        assert_eq!(db.food_tags(jam).unwrap(), ["sweet"]);
+        db.delete_food("jam", DeleteMode::Refuse).unwrap();
+        db.undo_last(10).unwrap();
+        assert!(db.get_food_by_name("jelly").unwrap().is_some());
+        assert_eq!(db.food_tags(jam).unwrap(), ["sweet"]);
+    }
+
+    #[test]
+    fn test_atomically() {
+        let db = test_db();
+        let id = db.add_food(&sample_food("Ribeye")).unwrap();
+        db.log_food_with(id, "100g", &Macros::default(), &LogOptions::default())
+            .unwrap();
+
+        // A failure part way through keeps nothing, including nested transactions
+        let failed = db.atomically(|db| {
+            db.log_food_with(id, "200g", &Macros::default(), &LogOptions::default())?;
+            db.add_food(&sample_food("Brisket"))?;
+            db.delete_food("Ribeye", DeleteMode::Cascade)?;
+            db.delete_log_entry(9999)
+        });
+        assert!(failed.is_err());
+        assert!(db.get_food_by_name("Brisket").unwrap().is_none());
+        assert!(db.get_food_by_name("Ribeye").unwrap().is_some());
+        assert_eq!(db.get_history(1).unwrap().len(), 1);
+
+        db.atomically(|db| {
+            db.log_food_with(id, "200g", &Macros::default(), &LogOptions::default())?;
+            db.add_food(&sample_food("Brisket"))
+        })
+        .unwrap();
+        assert_eq!(db.get_history(1).unwrap().len(), 2);
+
+        // The whole batch undoes as one change
+        assert_eq!(db.undo_last(10).unwrap().len(), 2);
+        assert!(db.get_food_by_name("Brisket").unwrap().is_none());
+        assert_eq!(db.get_history(1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_undo_last_respects_window() {
+        let db = test_db();
+        db.log_water(500.0, None).unwrap();
+        db.conn
+            .execute(
+                "UPDATE audit_log SET created_at = datetime('now', '-1 hour')",
+                [],
+            )
+            .unwrap();
+        assert!(db.undo_last(10).is_err());
+        assert!(db.undo_last(120).is_ok());
+        assert_eq!(db.get_today_water().unwrap().total_ml, 0.0);
+    }
+
+    #[test]
+    fn test_log_water() {
+        let db = test_db();
+        let entry = db.log_water(500.0, None).unwrap();
+        assert_eq!(entry.amount_ml, 500.0);
+
+        let totals = db.get_today_water().unwrap();
+        assert_eq!(totals.total_ml, 500.0);
+
+        db.log_water(250.0, None).unwrap();
+        let totals = db.get_today_water().unwrap();
+        assert_eq!(totals.total_ml, 750.0);
+    }
+
+    #[test]
+    fn test_delete_water_entry() {
+        let db = test_db();
+        let entry = db.log_water(500.0, None).unwrap();
+        db.delete_water_entry(entry.id.unwrap()).unwrap();
+        let totals = db.get_today_water().unwrap();
+        assert_eq!(totals.total_ml, 0.0);
+    }
+
+    #[test]
+    fn test_log_caffeine() {
+        let db = test_db();
+        let entry = db.log_caffeine(95.0, "coffee", None).unwrap();
+        assert_eq!(entry.amount_mg, 95.0);
+        assert_eq!(entry.source, "coffee");
+
+        let totals = db.get_today_caffeine().unwrap();
+        assert_eq!(totals.total_mg, 95.0);
+
+        db.log_caffeine(47.0, "tea", None).unwrap();
+        let totals = db.get_today_caffeine().unwrap();
+        assert_eq!(totals.total_mg, 142.0);
+    }
+
+    #[test]
+    fn test_delete_caffeine_entry() {
+        let db = test_db();
+        let entry = db.log_caffeine(95.0, "coffee", None).unwrap();
+        db.delete_caffeine_entry(entry.id.unwrap()).unwrap();
+        let totals = db.get_today_caffeine().unwrap();
+        assert_eq!(totals.total_mg, 0.0);
+    }
+
+    #[test]
+    fn test_duplicate_food_handling() {
+        let db = test_db();
+        db.add_food(&sample_food("Eggs")).unwrap();
+        let result = db.add_food(&sample_food("Eggs"));
+        assert!(result.is_err()); // UNIQUE constraint
+    }
+
+    #[test]
+    fn test_compound_food() {
+        let db = test_db();
+        db.add_food(&Food::new("Rice", 2.7, 0.3, 28.0, 130.0, "100g", vec![]))
+            .unwrap();
+        db.add_food(&Food::new(
+            "Chicken Breast",
+            31.0,
+            3.6,
+            0.0,
             165.0,
             "100g",
-            vec!["chicken".to_string(), "chx".to_string()],
-        );
-        db.add_food(&food).unwrap();
+            vec![],
+        ))
+        .unwrap();
 
-        let found = db.get_food_by_name("chicken").unwrap().unwrap();
-        assert_eq!(found.name, "Chicken Breast");
+        db.create_compound_food(
+            "Chicken Rice Bowl",
+            &[
+                ("Rice".to_string(), "200g".to_string()),
+                ("Chicken Breast".to_string(), "150g".to_string()),
+            ],
+        )
+        .unwrap();
 
-        let found2 = db.get_food_by_name("chx").unwrap().unwrap();
-        assert_eq!(found2.name, "Chicken Breast");
+        let found = db.get_food_by_name("Chicken Rice Bowl").unwrap().unwrap();
+        assert!(found.calories > 0.0);
+
+        let items = db.get_compound_food("Chicken Rice Bowl").unwrap();
+        assert_eq!(items.len(), 2);
     }
 
     #[test]
-    fn test_search_foods_fuzzy() {
+    fn test_compound_macros_scale_from_components() {
         let db = test_db();
-        db.add_food(&sample_food("Ribeye Steak")).unwrap();
-        db.add_food(&sample_food("Rice")).unwrap();
-        db.add_food(&sample_food("Salmon")).unwrap();
+        db.add_food(&Food::new("Beans", 8.0, 0.5, 24.0, 130.0, "100g", vec![]))
+            .unwrap();
+        db.add_food(&sample_food("Ground Beef")).unwrap();
+        db.create_compound_food(
+            "Chili",
+            &[
+                ("Beans".to_string(), "200g".to_string()),
+                ("Ground Beef".to_string(), "100g".to_string()),
+            ],
+        )
+        .unwrap();
 
-        let results = db.search_foods("rib").unwrap();
-        assert!(!results.is_empty());
-        assert_eq!(results[0].name, "Ribeye Steak");
+        let m = db.compound_macros("chili", 1.5).unwrap().unwrap();
+        assert!((m.protein - (16.0 + 26.0) * 1.5).abs() < 0.01);
+
+        // Later edits to a component flow through
+        db.edit_food(
+            "Beans",
+            Some(10.0),
+            None,
+            None,
+            None,
+            None,
+            None,
+            &Micros::default(),
+        )
+        .unwrap();
+        let m = db.compound_macros("Chili", 0.5).unwrap().unwrap();
+        assert!((m.protein - (20.0 + 26.0) * 0.5).abs() < 0.01);
+
+        assert!(db.compound_macros("Beans", 1.0).unwrap().is_none());
     }
 
     #[test]
-    fn test_log_food_and_today_totals() {
+    fn test_pantry_by_tag() {
         let db = test_db();
-        let food = sample_food("Eggs");
-        let id = db.add_food(&food).unwrap();
+        let almonds = db.add_food(&sample_food("Almonds")).unwrap();
+        let chips = db.add_food(&sample_food("Chips")).unwrap();
+        db.add_food(&sample_food("Bread")).unwrap();
+        assert!(db.stock_food(chips).unwrap());
+        assert!(db.stock_food(almonds).unwrap());
+        assert!(!db.stock_food(almonds).unwrap());
+        db.tag_food(almonds, "snack").unwrap();
+
+        let names = |tag| -> Vec<String> {
+            db.pantry_foods(tag)
+                .unwrap()
+                .into_iter()
+                .map(|f| f.name)
+                .collect()
+        };
+        assert_eq!(names(None), ["Almonds", "Chips"]);
+        assert_eq!(names(Some("snack")), ["Almonds"]);
+
+        assert!(db.untag_food(almonds, "snack").unwrap());
+        assert!(names(Some("snack")).is_empty());
+        assert!(db.unstock_food(chips).unwrap());
+        assert!(!db.unstock_food(chips).unwrap());
+        assert_eq!(names(None), ["Almonds"]);
+    }
 
-        let macros = Macros {
-            protein: 12.0,
-            fat: 10.0,
-            carbs: 1.0,
-            calories: 142.0,
+    #[test]
+    fn test_food_units() {
+        let db = test_db();
+        let mut rice = sample_food("Rice");
+        rice.units.insert("cup".to_string(), 185.0);
+        let id = db.add_food(&rice).unwrap();
+        let units = |name: &str| db.get_food_by_name(name).unwrap().unwrap().units;
+        assert_eq!(units("rice").get("cup"), Some(&185.0));
+
+        db.set_food_unit(id, "Cup", 195.0).unwrap();
+        db.set_food_unit(id, "scoop", 60.0).unwrap();
+        assert_eq!(units("rice").len(), 2);
+        assert_eq!(units("rice").get("cup"), Some(&195.0));
+        assert_eq!(db.search_foods("rice").unwrap()[0].units.len(), 2);
+
+        // Undo puts the earlier weight back, and deleting the food takes
+        // its units along
+        db.undo_last(1).unwrap();
+        db.undo_last(1).unwrap();
+        assert_eq!(units("rice").get("cup"), Some(&185.0));
+        assert!(db.remove_food_unit(id, "cup").unwrap());
+        assert!(!db.remove_food_unit(id, "cup").unwrap());
+        assert!(units("rice").is_empty());
+        db.set_food_unit(id, "cup", 185.0).unwrap();
+        db.delete_food("Rice", DeleteMode::Refuse).unwrap();
+        db.undo_last(1).unwrap();
+        assert_eq!(units("rice").get("cup"), Some(&185.0));
+        // Exports weigh amounts in the food's own units
+        db.log_food_with(
+            id,
+            "2 cups",
+            &Macros::default(),
+            &LogOptions::on_date(Some("2024-01-02")),
+        )
+        .unwrap();
+        let mut out = Vec::new();
+        let export = CsvExport {
+            columns: vec!["amount".into(), "grams".into()],
+            ..Default::default()
         };
-        let entry = db.log_food(id, "2", &macros, None).unwrap();
-        assert_eq!(entry.food_name, "Eggs");
-        assert_eq!(entry.protein, 12.0);
+        db.export_csv(&mut out, &export).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "amount,grams\n2 cups,370\n"
+        );
+    }
 
-        let totals = db.get_today_totals().unwrap();
-        assert_eq!(totals.protein, 12.0);
-        assert_eq!(totals.calories, 142.0);
+    #[test]
+    fn test_recipe_divides_by_servings_and_weight() {
+        let db = test_db();
+        db.add_food(&sample_food("Ground Beef")).unwrap();
+        db.create_compound_food(
+            "Lasagna",
+            &[("Ground Beef".to_string(), "800g".to_string())],
+        )
+        .unwrap();
+        let whole = db.compound_macros("lasagna", 1.0).unwrap().unwrap();
+        let recipe = RecipeYield {
+            servings: 8.0,
+            grams: None,
+        };
+        let serving = db.set_recipe_yield("lasagna", recipe).unwrap();
+        assert!((serving.protein - whole.protein / 8.0).abs() < 0.01);
+        let food = db.get_food_by_name("Lasagna").unwrap().unwrap();
+        assert!((food.protein - serving.protein).abs() < 0.01);
+
+        // By the serving; by weight only once the dish has been weighed
+        let entry =
+            crate::logging::parse_and_log_with(&db, "lasagna 2 servings", &LogOptions::default())
+                .unwrap();
+        assert!((entry.protein - serving.protein * 2.0).abs() < 0.01);
+        let err = crate::logging::parse_and_log_with(&db, "lasagna 250g", &LogOptions::default())
+            .unwrap_err();
+        assert!(err.to_string().contains("recipe yield"), "{}", err);
+        let recipe = RecipeYield {
+            servings: 8.0,
+            grams: Some(2000.0),
+        };
+        db.set_recipe_yield("Lasagna", recipe).unwrap();
+        assert_eq!(db.recipe_yield("lasagna").unwrap(), Some(recipe));
+        let entry = crate::logging::parse_and_log_with(&db, "lasagna 500g", &LogOptions::default())
+            .unwrap();
+        assert!((entry.protein - whole.protein / 4.0).abs() < 0.01);
 
-        // Log another
-        let macros2 = Macros {
+        assert!(db.set_recipe_yield("Ground Beef", recipe).is_err());
+        assert!(db.recipe_yield("Ground Beef").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_editing_a_component_refreshes_compounds() {
+        let db = test_db();
+        db.add_food(&Food::new("Rice", 2.7, 0.3, 28.0, 130.0, "100g", vec![]))
+            .unwrap();
+        db.add_food(&sample_food("Ground Beef")).unwrap();
+        db.create_compound_food(
+            "Bowl",
+            &[
+                ("Rice".to_string(), "200g".to_string()),
+                ("Ground Beef".to_string(), "100g".to_string()),
+            ],
+        )
+        .unwrap();
+        db.create_compound_food("Double Bowl", &[("Bowl".to_string(), "2".to_string())])
+            .unwrap();
+        let protein = |name| db.get_food_by_name(name).unwrap().unwrap().protein;
+        assert!((protein("Double Bowl") - (5.4 + 26.0) * 2.0).abs() < 0.01);
+
+        let refreshed = db
+            .edit_food(
+                "Rice",
+                Some(3.7),
+                None,
+                None,
+                None,
+                None,
+                None,
+                &Micros::default(),
+            )
+            .unwrap();
+        assert_eq!(refreshed, ["Bowl", "Double Bowl"]);
+        assert!((protein("Bowl") - (7.4 + 26.0)).abs() < 0.01);
+        assert!((protein("Double Bowl") - (7.4 + 26.0) * 2.0).abs() < 0.01);
+
+        // Undoing the edit puts the compounds back too
+        db.undo_last(5).unwrap();
+        assert!((protein("Rice") - 2.7).abs() < 0.01);
+        assert!((protein("Double Bowl") - (5.4 + 26.0) * 2.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_edit_that_breaks_a_compound_is_rejected() {
+        let db = test_db();
+        db.add_food(&Food::new(
+            "Granola",
+            4.0,
+            6.0,
+            26.0,
+            180.0,
+            "1 packet",
+            vec![],
+        ))
+        .unwrap();
+        db.create_compound_food(
+            "Parfait",
+            &[("Granola".to_string(), "1 packet".to_string())],
+        )
+        .unwrap();
+
+        let err = db
+            .edit_food(
+                "granola",
+                None,
+                None,
+                None,
+                None,
+                Some("40g"),
+                None,
+                &Micros::default(),
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Parfait couldn't be recalculated (Could not calculate macros for 1 packet of Granola); \
+             nothing was changed"
+        );
+        assert_eq!(
+            db.get_food_by_name("granola").unwrap().unwrap().serving,
+            "1 packet"
+        );
+    }
+
+    #[test]
+    fn test_prep_batch_logs_by_cooked_weight() {
+        let db = test_db();
+        db.add_food(&sample_food("Ground Beef")).unwrap();
+        db.create_compound_food("Chili", &[("Ground Beef".to_string(), "600g".to_string())])
+            .unwrap();
+        assert!(db
+            .create_prep_batch("Ground Beef", 1.0, 4.0, 300.0)
+            .is_err());
+
+        // 600g raw beef cooked down to 4 containers of 300g
+        let batch = db.create_prep_batch("chili", 1.0, 4.0, 300.0).unwrap();
+        assert_eq!(batch.total_grams(), 1200.0);
+        assert!((batch.protein - 156.0).abs() < 0.01);
+
+        let entry =
+            crate::logging::parse_and_log_with(&db, "chili 150g", &LogOptions::default()).unwrap();
+        assert!((entry.protein - 19.5).abs() < 0.01);
+        let entry =
+            crate::logging::parse_and_log_with(&db, "chili 2 containers", &LogOptions::default())
+                .unwrap();
+        assert!((entry.protein - 78.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_closed_day_locks_entries() {
+        let db = test_db();
+        let id = db.add_food(&sample_food("Ribeye")).unwrap();
+        let macros = Macros::default();
+        let entry = db
+            .log_food_with(
+                id,
+                "100g",
+                &macros,
+                &LogOptions::on_date(Some("2024-01-15")),
+            )
+            .unwrap();
+
+        db.close_day("2024-01-15", Some(4), None).unwrap();
+        let err = db
+            .log_food_with(
+                id,
+                "100g",
+                &macros,
+                &LogOptions::on_date(Some("2024-01-15")),
+            )
+            .unwrap_err();
+        assert!(err.is::<DayClosed>());
+        assert!(db.delete_log_entry(entry.id.unwrap()).is_err());
+        assert!(db.log_water(250.0, Some("2024-01-15")).is_err());
+        assert!(db
+            .log_food_with(
+                id,
+                "100g",
+                &macros,
+                &LogOptions::on_date(Some("2024-01-16"))
+            )
+            .is_ok());
+
+        db.reopen_day("2024-01-15").unwrap();
+        assert!(db.delete_log_entry(entry.id.unwrap()).is_ok());
+        let review = db.day_review("2024-01-15").unwrap().unwrap();
+        assert_eq!(review.satiety, Some(4));
+        assert!(review.closed_at.is_none());
+    }
+
+    #[test]
+    fn test_old_entries_lock() {
+        let db = test_db().with_lock_after_days(Some(7));
+        let id = db.add_food(&sample_food("Ribeye")).unwrap();
+        let macros = Macros::default();
+        let old = (Local::now().date_naive() - chrono::Duration::days(8))
+            .format("%Y-%m-%d")
+            .to_string();
+        let recent = (Local::now().date_naive() - chrono::Duration::days(7))
+            .format("%Y-%m-%d")
+            .to_string();
+
+        let err = db
+            .log_food_with(id, "100g", &macros, &LogOptions::on_date(Some(&old)))
+            .unwrap_err();
+        assert!(err.is::<EntryLocked>());
+        assert!(db
+            .log_food_with(id, "100g", &macros, &LogOptions::on_date(Some(&recent)))
+            .is_ok());
+
+        // Amending means opening without the lock
+        let db = db.with_lock_after_days(None);
+        let entry = db
+            .log_food_with(id, "100g", &macros, &LogOptions::on_date(Some(&old)))
+            .unwrap();
+        let db = db.with_lock_after_days(Some(7));
+        assert!(db.delete_log_entry(entry.id.unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_daily_totals_follow_log_writes() {
+        let db = test_db();
+        let id = db.add_food(&sample_food("Ribeye")).unwrap();
+        let m = Macros {
             protein: 26.0,
             fat: 15.0,
             carbs: 0.0,
             calories: 250.0,
+            fiber: 0.0,
+            ..Default::default()
         };
-        db.log_food(id, "100g", &macros2, None).unwrap();
+        let a = db
+            .log_food_with(id, "100g", &m, &LogOptions::on_date(Some("2024-01-01")))
+            .unwrap();
+        db.log_food_with(id, "100g", &m, &LogOptions::on_date(Some("2024-01-01")))
+            .unwrap();
+        assert_eq!(
+            db.get_totals_for_date("2024-01-01").unwrap().calories,
+            500.0
+        );
 
-        let totals = db.get_today_totals().unwrap();
-        assert_eq!(totals.protein, 38.0);
+        // Edits move the totals, including to another day
+        db.edit_log_entry(a.id.unwrap(), None, Some(30.0), None, None)
+            .unwrap();
+        assert_eq!(db.get_totals_for_date("2024-01-01").unwrap().protein, 56.0);
+        db.conn
+            .execute(
+                "UPDATE log SET date = '2024-01-02' WHERE id = ?1",
+                params![a.id],
+            )
+            .unwrap();
+        assert_eq!(db.get_totals_for_date("2024-01-01").unwrap().protein, 26.0);
+        assert_eq!(db.get_totals_for_date("2024-01-02").unwrap().protein, 30.0);
+
+        db.delete_log_entry(a.id.unwrap()).unwrap();
+        let days: i64 = db
+            .conn
+            .query_row("SELECT COUNT(*) FROM daily_totals", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(days, 1);
+
+        db.conn.execute("DELETE FROM daily_totals", []).unwrap();
+        assert_eq!(db.rebuild_daily_totals().unwrap(), 1);
+        assert_eq!(
+            db.get_totals_for_date("2024-01-01").unwrap().calories,
+            250.0
+        );
     }
 
     #[test]
-    fn test_get_history() {
+    fn test_micros_total_when_present() {
         let db = test_db();
-        let id = db.add_food(&sample_food("Bacon")).unwrap();
-        let macros = Macros {
-            protein: 12.0,
-            fat: 40.0,
-            carbs: 0.0,
-            calories: 400.0,
+        let mut bacon = sample_food("Bacon");
+        bacon.micros = Micros {
+            sodium: Some(190.0),
+            saturated_fat: Some(1.1),
+            ..Default::default()
+        };
+        let bacon_id = db.add_food(&bacon).unwrap();
+        let eggs_id = db.add_food(&sample_food("Eggs")).unwrap();
+        assert_eq!(
+            db.get_food_by_name("bacon").unwrap().unwrap().micros,
+            bacon.micros
+        );
+
+        let bacon_macros = bacon.calculate("200g").unwrap();
+        db.log_food_with(
+            eggs_id,
+            "1",
+            &Macros::default(),
+            &LogOptions::on_date(Some("2024-01-01")),
+        )
+        .unwrap();
+        assert!(db
+            .get_totals_for_date("2024-01-01")
+            .unwrap()
+            .micros
+            .is_empty());
+
+        db.log_food_with(
+            bacon_id,
+            "200g",
+            &bacon_macros,
+            &LogOptions::on_date(Some("2024-01-01")),
+        )
+        .unwrap();
+        let totals = db.get_totals_for_date("2024-01-01").unwrap();
+        assert_eq!(totals.micros.sodium, Some(380.0));
+        assert_eq!(totals.micros.sugar, None);
+
+        db.conn.execute("DELETE FROM daily_totals", []).unwrap();
+        db.rebuild_daily_totals().unwrap();
+        let rollups = db.rollups("2024-01-01", "day").unwrap();
+        assert_eq!(rollups[0].micros.sodium, Some(380.0));
+    }
+
+    #[test]
+    fn test_rollups() {
+        let db = test_db();
+        let id = db.add_food(&sample_food("Ribeye")).unwrap();
+        let m = |calories: f64| Macros {
+            calories,
+            ..Default::default()
+        };
+        // Sunday 2024-03-03 closes one week; Monday 2024-03-04 opens the next
+        db.log_food_with(
+            id,
+            "1",
+            &m(1000.0),
+            &LogOptions::on_date(Some("2024-02-28")),
+        )
+        .unwrap();
+        db.log_food_with(
+            id,
+            "1",
+            &m(2000.0),
+            &LogOptions::on_date(Some("2024-03-03")),
+        )
+        .unwrap();
+        db.log_food_with(id, "1", &m(500.0), &LogOptions::on_date(Some("2024-03-04")))
+            .unwrap();
+        db.log_food_with(
+            id,
+            "1",
+            &m(1000.0),
+            &LogOptions::on_date(Some("2024-03-04")),
+        )
+        .unwrap();
+
+        let months = db.rollups("2024-01-01", "month").unwrap();
+        assert_eq!(months.len(), 2);
+        assert_eq!(
+            (months[0].period.as_str(), months[0].calories),
+            ("2024-02", 1000.0)
+        );
+        assert_eq!((months[1].days, months[1].calories), (2, 1750.0));
+
+        let weeks = db.rollups("2024-01-01", "week").unwrap();
+        let periods: Vec<&str> = weeks.iter().map(|w| w.period.as_str()).collect();
+        assert_eq!(periods, ["2024-02-26", "2024-03-04"]);
+        assert_eq!(weeks[0].calories, 1500.0);
+
+        assert_eq!(db.rollups("2024-03-04", "day").unwrap().len(), 1);
+        assert!(db.rollups("2024-01-01", "year").is_err());
+    }
+
+    #[test]
+    fn test_rollups_incomplete_days() {
+        let db = test_db();
+        let id = db.add_food(&sample_food("Ribeye")).unwrap();
+        let m = |calories: f64| Macros {
+            calories,
+            ..Default::default()
         };
-        db.log_food(id, "100g", &macros, None).unwrap();
+        db.log_food_with(
+            id,
+            "1",
+            &m(2000.0),
+            &LogOptions::on_date(Some("2024-03-01")),
+        )
+        .unwrap();
+        db.log_food_with(id, "1", &m(300.0), &LogOptions::on_date(Some("2024-03-02")))
+            .unwrap();
+        db.log_food_with(id, "1", &m(200.0), &LogOptions::on_date(Some("2024-04-01")))
+            .unwrap();
+
+        let months = db.rollups("2024-01-01", "month").unwrap();
+        assert_eq!(
+            (months[0].days, months[0].incomplete, months[0].calories),
+            (2, 1, 1150.0)
+        );
+        assert_eq!(months[1].incomplete, 1);
+
+        let db = db.with_incomplete_days(IncompleteDays {
+            below: 500.0,
+            exclude: true,
+        });
+        let months = db.rollups("2024-01-01", "month").unwrap();
+        assert_eq!(months.len(), 1);
+        assert_eq!(
+            (months[0].days, months[0].incomplete, months[0].calories),
+            (1, 1, 2000.0)
+        );
+        assert_eq!(db.rollups("2024-01-01", "day").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_query_read_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("foods.db");
+        let db = Database::open_at(&path).unwrap();
+        db.init().unwrap();
+        let id = db.add_food(&sample_food("Ribeye")).unwrap();
+        db.log_food_with(
+            id,
+            "200g",
+            &Macros::default(),
+            &LogOptions::on_date(Some("2024-03-01")),
+        )
+        .unwrap();
+
+        let result = Database::query_read_only(
+            &path,
+            "SELECT f.name, COUNT(*) AS times, serving_grams(f.serving) AS grams
+             FROM log l JOIN foods f ON f.id = l.food_id GROUP BY f.name",
+        )
+        .unwrap();
+        assert_eq!(result.columns, ["name", "times", "grams"]);
+        assert_eq!(
+            result.rows,
+            [vec![
+                serde_json::json!("Ribeye"),
+                serde_json::json!(1),
+                serde_json::json!(100.0)
+            ]]
+        );
 
-        let history = db.get_history(7).unwrap();
-        assert_eq!(history.len(), 1);
-        assert_eq!(history[0].food_name, "Bacon");
+        let err = Database::query_read_only(&path, "DELETE FROM log").unwrap_err();
+        assert!(err.to_string().contains("readonly"), "{}", err);
+        assert!(Database::query_read_only(&path, "SELECT 1; DELETE FROM log").is_err());
+        assert_eq!(db.get_history(10000).unwrap().len(), 1);
     }
 
     #[test]
-    fn test_edit_food() {
+    fn test_snapshot_day() {
         let db = test_db();
-        db.add_food(&sample_food("Salmon")).unwrap();
+        let id = db.add_food(&sample_food("Ribeye")).unwrap();
+        let m = Macros {
+            protein: 40.0,
+            calories: 600.0,
+            ..Default::default()
+        };
+        db.log_food_with(id, "1", &m, &LogOptions::on_date(Some("2024-03-01")))
+            .unwrap();
+        db.log_food_with(id, "1", &m, &LogOptions::on_date(Some("2024-03-01")))
+            .unwrap();
 
-        db.edit_food("Salmon", Some(25.0), None, None, None, None)
+        // A stale cache row is rewritten from the log
+        db.conn
+            .execute(
+                "UPDATE daily_totals SET calories = 5 WHERE date = '2024-03-01'",
+                [],
+            )
             .unwrap();
-        let food = db.get_food_by_name("Salmon").unwrap().unwrap();
-        assert_eq!(food.protein, 25.0);
-        // calories recalculated: 25*4 + 15*9 + 0*4 = 235
-        assert_eq!(food.calories, 235.0);
+        let snapshot = db.snapshot_day("2024-03-01").unwrap();
+        assert_eq!(snapshot.entries, 2);
+        assert_eq!(snapshot.totals.calories, 1200.0);
+        assert_eq!(db.get_totals_for_date("2024-03-01").unwrap().protein, 80.0);
+
+        let empty = db.snapshot_day("2024-03-02").unwrap();
+        assert_eq!((empty.entries, empty.totals.calories), (0, 0.0));
     }
 
     #[test]
-    fn test_delete_food() {
+    fn test_missing_days() {
         let db = test_db();
-        db.add_food(&sample_food("Temp Food")).unwrap();
-        assert!(db.get_food_by_name("Temp Food").unwrap().is_some());
+        let id = db.add_food(&sample_food("Ribeye")).unwrap();
+        let m = |calories: f64| Macros {
+            calories,
+            ..Default::default()
+        };
+        let today = Local::now().date_naive();
+        let day = |n: i64| (today - chrono::Duration::days(n)).to_string();
+        assert!(db.missing_days(30).unwrap().is_empty());
 
-        db.delete_food("Temp Food").unwrap();
-        assert!(db.get_food_by_name("Temp Food").unwrap().is_none());
+        db.log_food_with(id, "1", &m(2000.0), &LogOptions::on_date(Some(&day(5))))
+            .unwrap();
+        db.log_food_with(id, "1", &m(300.0), &LogOptions::on_date(Some(&day(3))))
+            .unwrap();
+        db.log_food_with(id, "1", &m(1900.0), &LogOptions::on_date(Some(&day(1))))
+            .unwrap();
+        db.log_food_with(id, "1", &m(100.0), &LogOptions::on_date(Some(&day(0))))
+            .unwrap();
+
+        let missing = db.missing_days(30).unwrap();
+        let dates: Vec<&str> = missing.iter().map(|d| d.date.as_str()).collect();
+        assert_eq!(dates, [day(2), day(3), day(4)]);
+        assert_eq!((missing[1].entries, missing[1].calories), (1, 300.0));
+        assert_eq!(missing[0].entries, 0);
+        assert_eq!(db.missing_days(2).unwrap().len(), 1);
     }
 
     #[test]
-    fn test_delete_log_entry() {
+    fn test_trash_and_restore() {
         let db = test_db();
-        let id = db.add_food(&sample_food("Apple")).unwrap();
-        let macros = Macros {
-            protein: 0.3,
-            fat: 0.2,
-            carbs: 14.0,
-            calories: 52.0,
+        let mut food = sample_food("Ribeye");
+        food.aliases = vec!["rib".to_string()];
+        let id = db.add_food(&food).unwrap();
+        db.tag_food(id, "estimated").unwrap();
+        let m = Macros {
+            calories: 500.0,
+            ..Default::default()
         };
-        let entry = db.log_food(id, "1", &macros, None).unwrap();
+        db.log_food_with(id, "200g", &m, &LogOptions::default())
+            .unwrap();
 
-        let deleted = db.delete_log_entry(entry.id.unwrap()).unwrap();
-        assert_eq!(deleted.food_name, "Apple");
+        db.delete_food("ribeye", DeleteMode::KeepHistory).unwrap();
+        assert!(db.get_food_by_name("rib").unwrap().is_none());
+        assert_eq!(db.get_today_totals().unwrap().calories, 500.0);
+        let trash = db.list_trash().unwrap();
+        assert_eq!(
+            (trash[0].name.as_str(), trash[0].log_entries),
+            ("Ribeye", 1)
+        );
 
-        let totals = db.get_today_totals().unwrap();
-        assert_eq!(totals.calories, 0.0);
+        // A new food has taken the name in the meantime
+        let newer = db.add_food(&sample_food("Ribeye")).unwrap();
+        assert!(db.restore_food("ribeye").is_err());
+        db.conn
+            .execute(
+                "UPDATE foods SET name = 'Sirloin' WHERE id = ?1",
+                params![newer],
+            )
+            .unwrap();
+
+        let restored = db.restore_food("ribeye").unwrap();
+        assert_eq!(restored.log_entries, 1);
+        let back = db.get_food_by_name("rib").unwrap().unwrap();
+        assert_eq!(back.id, Some(id));
+        assert_eq!(db.food_tags(id).unwrap(), ["estimated"]);
+        assert_eq!(db.food_card("ribeye").unwrap().unwrap().times_logged, 1);
+        assert!(db.list_trash().unwrap().is_empty());
+
+        db.delete_food("sirloin", DeleteMode::Refuse).unwrap();
+        db.delete_food("ribeye", DeleteMode::KeepHistory).unwrap();
+        db.conn
+            .execute(
+                "UPDATE trash SET deleted_at = datetime('now', '-31 days')",
+                [],
+            )
+            .unwrap();
+        assert!(db.list_trash().unwrap().is_empty());
+
+        // Once purged, the kept entries no longer point at the food
+        let history = db.get_history(1).unwrap();
+        assert_eq!(
+            (history[0].food_name.as_str(), history[0].food_id),
+            ("deleted", None)
+        );
+        let dangling: i64 = db
+            .conn
+            .query_row(
+                "SELECT COUNT(*) FROM pragma_foreign_key_check('log')",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(dangling, 0);
     }
 
     #[test]
-    fn test_delete_last_log_entry() {
+    fn test_name_clashes() {
         let db = test_db();
-        let id = db.add_food(&sample_food("Banana")).unwrap();
-        let m = Macros {
-            protein: 1.0,
-            fat: 0.3,
-            carbs: 23.0,
-            calories: 89.0,
-        };
-        db.log_food(id, "1", &m, None).unwrap();
-        db.log_food(id, "1", &m, None).unwrap();
+        let mut thigh = sample_food("Chicken thigh");
+        thigh.aliases = vec!["chicken".to_string()];
+        db.add_food(&thigh).unwrap();
+
+        // A food named like an existing alias takes the lookup over
+        let clashes = db.name_clashes("Chicken", &[], None).unwrap();
+        assert_eq!(clashes.len(), 1);
+        assert!(clashes[0].new_wins());
+        assert_eq!(
+            clashes[0].to_string(),
+            "'Chicken' is already an alias of Chicken thigh; lookups will find the new food instead"
+        );
+        db.add_food(&sample_food("Chicken")).unwrap();
 
-        let deleted = db.delete_last_log_entry().unwrap();
-        assert_eq!(deleted.food_name, "Banana");
+        // An alias named like an existing food is never reached
+        let clashes = db
+            .name_clashes("Chicken breast", &["CHICKEN".to_string()], None)
+            .unwrap();
+        let found: Vec<(&str, bool)> = clashes
+            .iter()
+            .map(|c| (c.existing.as_str(), c.existing_is_alias))
+            .collect();
+        assert_eq!(found, [("Chicken", false), ("Chicken thigh", true)]);
+        assert!(clashes.iter().all(|c| c.is_alias && !c.new_wins()));
 
-        let totals = db.get_today_totals().unwrap();
-        assert_eq!(totals.calories, 89.0);
+        // A food doesn't clash with itself
+        let id = db.get_food_by_name("chicken thigh").unwrap().unwrap().id;
+        assert!(db
+            .name_clashes("Chicken thigh", &[], id)
+            .unwrap()
+            .is_empty());
     }
 
     #[test]
-    fn test_edit_log_entry() {
+    fn test_delete_guard() {
         let db = test_db();
-        let id = db.add_food(&sample_food("Steak")).unwrap();
+        let rice = db.add_food(&sample_food("Rice")).unwrap();
+        db.add_food(&sample_food("Beans")).unwrap();
+        db.create_compound_food(
+            "Rice and beans",
+            &[
+                ("rice".to_string(), "100g".to_string()),
+                ("beans".to_string(), "100g".to_string()),
+            ],
+        )
+        .unwrap();
         let m = Macros {
-            protein: 26.0,
-            fat: 15.0,
-            carbs: 0.0,
-            calories: 250.0,
+            calories: 130.0,
+            ..Default::default()
         };
-        let entry = db.log_food(id, "100g", &m, None).unwrap();
+        db.log_food_with(rice, "100g", &m, &LogOptions::default())
+            .unwrap();
+        db.log_food_with(rice, "200g", &m, &LogOptions::default())
+            .unwrap();
 
-        let updated = db
-            .edit_log_entry(
-                entry.id.unwrap(),
-                Some("200g".to_string()),
-                Some(52.0),
-                None,
-                None,
+        let err = db.delete_food("rice", DeleteMode::Refuse).unwrap_err();
+        let in_use = err.downcast_ref::<FoodInUse>().unwrap();
+        assert_eq!(in_use.log_entries, 2);
+        assert_eq!(in_use.compounds, ["Rice and beans"]);
+        assert!(err
+            .to_string()
+            .starts_with("'Rice' is used by 2 log entries and 1 compound food (Rice and beans)"));
+        assert!(db.get_food_by_name("rice").unwrap().is_some());
+
+        // Cascading takes the log entries and the compound's item along
+        db.delete_food("rice", DeleteMode::Cascade).unwrap();
+        assert!(db.get_history(1).unwrap().is_empty());
+        assert_eq!(db.get_today_totals().unwrap().calories, 0.0);
+        assert_eq!(db.get_compound_food("rice and beans").unwrap().len(), 1);
+        assert_eq!(db.list_trash().unwrap()[0].log_entries, 2);
+
+        db.restore_food("rice").unwrap();
+        assert_eq!(db.get_history(1).unwrap().len(), 2);
+        assert_eq!(db.get_compound_food("rice and beans").unwrap().len(), 2);
+
+        // Kept history stays readable without the food
+        db.delete_food("rice", DeleteMode::KeepHistory).unwrap();
+        let history = db.get_history(1).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].food_name, "deleted");
+    }
+
+    #[test]
+    fn test_shares() {
+        let db = test_db();
+        assert!(db.create_share(&[], 30, 30).is_err());
+        let share = db.create_share(&[ShareScope::Log], 14, 30).unwrap();
+        assert_eq!(share.token.len(), 32);
+        assert!(share.is_active());
+        assert_eq!(
+            (share.scopes.as_slice(), share.days),
+            ([ShareScope::Log].as_slice(), 14)
+        );
+        let other = db
+            .create_share(&[ShareScope::Log, ShareScope::Reports], 30, 0)
+            .unwrap();
+        assert_eq!(other.expires_at, None);
+        assert_eq!(db.list_shares().unwrap().len(), 2);
+
+        // Links stop working once they expire
+        db.conn
+            .execute(
+                "UPDATE shares SET expires_at = datetime('now', '-1 minute') WHERE token = ?1",
+                params![share.token],
             )
             .unwrap();
-        assert_eq!(updated.amount, "200g");
-        assert_eq!(updated.protein, 52.0);
+        assert!(!db.share(&share.token).unwrap().unwrap().is_active());
+        assert!(db.share(&other.token).unwrap().unwrap().is_active());
+
+        assert_eq!(db.revoke_share(&share.token[..8]).unwrap(), 1);
+        assert!(db
+            .share(&share.token)
+            .unwrap()
+            .unwrap()
+            .revoked_at
+            .is_some());
+        assert_eq!(db.revoke_share(&share.token).unwrap(), 0);
+        assert_eq!(db.revoke_share("").unwrap(), 0);
+        assert_eq!(db.revoke_all_shares().unwrap(), 1);
+        assert!(db
+            .share(&other.token)
+            .unwrap()
+            .unwrap()
+            .revoked_at
+            .is_some());
     }
 
     #[test]
-    fn test_get_stats() {
+    fn test_favorites() {
         let db = test_db();
-        let stats = db.get_stats().unwrap();
-        assert_eq!(stats.food_count, 0);
-        assert_eq!(stats.log_count, 0);
+        assert!(db.set_favorite("  ", None).is_err());
+        assert!(db.set_favorite("shake", Some("!!")).is_err());
 
-        let id = db.add_food(&sample_food("Rice")).unwrap();
-        let m = Macros {
+        let shake = db
+            .set_favorite("protein shake 1 scoop", Some("Morning Shake!"))
+            .unwrap();
+        assert_eq!(shake.slug, "morning-shake");
+        let eggs = db.set_favorite("2 eggs and toast", None).unwrap();
+        assert_eq!(eggs.slug, "2-eggs-and-toast");
+
+        // Same slug replaces what it logs
+        db.set_favorite("protein shake 2 scoops", Some("morning-shake"))
+            .unwrap();
+        let slugs: Vec<String> = db
+            .list_favorites()
+            .unwrap()
+            .into_iter()
+            .map(|f| f.slug)
+            .collect();
+        assert_eq!(slugs, ["2-eggs-and-toast", "morning-shake"]);
+        assert_eq!(
+            db.favorite("Morning Shake").unwrap().unwrap().input,
+            "protein shake 2 scoops"
+        );
+
+        assert!(db.remove_favorite("morning-shake").unwrap());
+        assert!(!db.remove_favorite("morning-shake").unwrap());
+        assert!(db.favorite("morning-shake").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_export_csv_columns() {
+        let db = test_db();
+        let id = db.add_food(&sample_food("Rice, white")).unwrap();
+        let macros = Macros {
             protein: 2.7,
             fat: 0.3,
             carbs: 28.0,
             calories: 130.0,
+            fiber: 0.4,
+            ..Default::default()
         };
-        db.log_food(id, "100g", &m, None).unwrap();
+        db.log_food_with(
+            id,
+            "100g",
+            &macros,
+            &LogOptions::on_date(Some("2024-01-02")),
+        )
+        .unwrap();
 
-        let stats = db.get_stats().unwrap();
-        assert_eq!(stats.food_count, 1);
-        assert_eq!(stats.log_count, 1);
+        let mut out = Vec::new();
+        db.export_csv(&mut out, &CsvExport::default()).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(
+            text,
+            "date,food,amount,grams,protein,fat,carbs,calories\n\
+             2024-01-02,\"Rice, white\",100g,100,2.7,0.3,28,130\n"
+        );
+
+        let export = CsvExport {
+            columns: vec!["calories".into(), "food".into(), "fiber".into()],
+            delimiter: b';',
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        db.export_csv(&mut out, &export).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "calories;food;fiber\n130;Rice, white;0.4\n"
+        );
+
+        let export = CsvExport {
+            since: Some("2024-01-03".into()),
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        db.export_csv(&mut out, &export).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 1);
+
+        let export = CsvExport {
+            columns: vec!["sodium".into()],
+            ..Default::default()
+        };
+        assert!(db.export_csv(Vec::new(), &export).is_err());
     }
 
     #[test]
-    fn test_log_water() {
+    fn test_csv_round_trip() {
         let db = test_db();
-        let entry = db.log_water(500.0, None).unwrap();
-        assert_eq!(entry.amount_ml, 500.0);
+        let odd = db.add_food(&sample_food("Rice, \"sticky\"\nbowl")).unwrap();
+        let eggs = db.add_food(&sample_food("Eggs")).unwrap();
+        let macros = Macros {
+            protein: 12.345,
+            fat: 0.3,
+            carbs: 28.0,
+            calories: 130.125,
+            fiber: 0.4,
+            ..Default::default()
+        };
+        db.log_food_with(
+            odd,
+            "1,5 cups",
+            &macros,
+            &LogOptions::on_date(Some("2024-01-02")),
+        )
+        .unwrap();
+        db.log_food_with(eggs, "2", &macros, &LogOptions::on_date(Some("2024-01-03")))
+            .unwrap();
+        db.log_food_with(eggs, "2", &macros, &LogOptions::on_date(Some("2024-01-03")))
+            .unwrap();
 
-        let totals = db.get_today_water().unwrap();
-        assert_eq!(totals.total_ml, 500.0);
+        let export = |db: &Database, export: &CsvExport| {
+            let mut out = Vec::new();
+            db.export_csv(&mut out, export).unwrap();
+            out
+        };
+        let full = CsvExport {
+            columns: CSV_COLUMNS
+                .iter()
+                .chain(["fiber", "created_at"].iter())
+                .map(|c| c.to_string())
+                .collect(),
+            delimiter: b';',
+            ..Default::default()
+        };
 
-        db.log_water(250.0, None).unwrap();
-        let totals = db.get_today_water().unwrap();
-        assert_eq!(totals.total_ml, 750.0);
+        for options in [CsvExport::default(), full.clone()] {
+            let original = export(&db, &options);
+            let copy = test_db();
+            let imported = copy.import_log_csv(&original).unwrap();
+            assert_eq!(
+                imported,
+                LogImport {
+                    entries: 3,
+                    skipped: 0,
+                    foods_created: 2
+                }
+            );
+            assert_eq!(export(&copy, &options), original);
+            let logged: (f64, f64) = copy
+                .conn
+                .query_row("SELECT protein, calories FROM log LIMIT 1", [], |row| {
+                    Ok((row.get(0)?, row.get(1)?))
+                })
+                .unwrap();
+            assert_eq!(logged, (12.345, 130.125));
+        }
+
+        // Entries already logged (same created_at) are not duplicated
+        let original = export(&db, &full);
+        let imported = db.import_log_csv(&original).unwrap();
+        assert_eq!((imported.entries, imported.skipped), (0, 3));
+        assert_eq!(export(&db, &full), original);
+
+        // A bad row aborts the whole import
+        let copy = test_db();
+        let bad = b"date,food,calories\n2024-01-02,Toast,80\n2024-01-03,Toast,lots\n";
+        assert!(copy.import_log_csv(bad).is_err());
+        assert!(copy.get_food_by_name("Toast").unwrap().is_none());
+        assert!(copy.import_log_csv(b"food,calories\nToast,80\n").is_err());
     }
 
     #[test]
-    fn test_delete_water_entry() {
+    fn test_import_reconciles_external_ids() {
         let db = test_db();
-        let entry = db.log_water(500.0, None).unwrap();
-        db.delete_water_entry(entry.id.unwrap()).unwrap();
-        let totals = db.get_today_water().unwrap();
-        assert_eq!(totals.total_ml, 0.0);
+        let oats = Food::new("Oats", 13.0, 7.0, 68.0, 389.0, "100g", vec![]);
+        assert_eq!(
+            db.import_food(&oats, Some("fdc:1")).unwrap(),
+            ImportOutcome::Added
+        );
+        assert_eq!(
+            db.import_food(&oats, Some("fdc:1")).unwrap(),
+            ImportOutcome::Unchanged
+        );
+
+        // A nutrition revision updates the row, keeping the user's rename
+        db.conn
+            .execute(
+                "UPDATE foods SET name = 'Rolled oats' WHERE name = 'Oats'",
+                [],
+            )
+            .unwrap();
+        let revised = Food::new("Oats", 13.5, 6.5, 68.0, 379.0, "100g", vec![]);
+        assert_eq!(
+            db.import_food(&revised, Some("fdc:1")).unwrap(),
+            ImportOutcome::Updated
+        );
+        let food = db.get_food_by_name("Rolled oats").unwrap().unwrap();
+        assert_eq!((food.protein, food.calories), (13.5, 379.0));
+        assert!(db.get_food_by_name("Oats").unwrap().is_none());
+        assert_eq!(
+            db.food_card("Rolled oats")
+                .unwrap()
+                .unwrap()
+                .source
+                .as_deref(),
+            Some("fdc:1")
+        );
+
+        // Foods imported before ids were tracked are adopted when they match
+        let rice = Food::new("Rice", 2.7, 0.3, 28.0, 130.0, "100g", vec![]);
+        assert_eq!(db.import_food(&rice, None).unwrap(), ImportOutcome::Added);
+        assert_eq!(db.import_food(&rice, None).unwrap(), ImportOutcome::Skipped);
+        assert_eq!(
+            db.import_food(&rice, Some("fdc:2")).unwrap(),
+            ImportOutcome::Unchanged
+        );
+        assert_eq!(
+            db.food_card("Rice").unwrap().unwrap().source.as_deref(),
+            Some("fdc:2")
+        );
+
+        // ...but a different food that happens to share the name is left alone
+        db.add_food(&sample_food("Steak")).unwrap();
+        let steak = Food::new("Steak", 20.0, 5.0, 0.0, 125.0, "100g", vec![]);
+        assert_eq!(
+            db.import_food(&steak, Some("off:3")).unwrap(),
+            ImportOutcome::Skipped
+        );
+        assert_eq!(db.get_food_by_name("Steak").unwrap().unwrap().protein, 26.0);
     }
 
     #[test]
-    fn test_log_caffeine() {
+    fn test_food_card() {
         let db = test_db();
-        let entry = db.log_caffeine(95.0, "coffee", None).unwrap();
-        assert_eq!(entry.amount_mg, 95.0);
-        assert_eq!(entry.source, "coffee");
-
-        let totals = db.get_today_caffeine().unwrap();
-        assert_eq!(totals.total_mg, 95.0);
+        let rice = db
+            .add_food(&Food::new(
+                "Rice",
+                2.7,
+                0.3,
+                28.0,
+                130.0,
+                "100g",
+                vec!["white rice".to_string()],
+            ))
+            .unwrap();
+        db.add_food(&sample_food("Chicken")).unwrap();
+        db.create_compound_food(
+            "Bowl",
+            &[
+                ("Rice".to_string(), "200g".to_string()),
+                ("Chicken".to_string(), "150g".to_string()),
+            ],
+        )
+        .unwrap();
+        db.tag_food(rice, "staple").unwrap();
+        let m = Macros::default();
+        db.log_food_with(rice, "150g", &m, &LogOptions::on_date(Some("2026-01-02")))
+            .unwrap();
+        db.log_food_with(rice, "100g", &m, &LogOptions::on_date(Some("2026-01-05")))
+            .unwrap();
 
-        db.log_caffeine(47.0, "tea", None).unwrap();
-        let totals = db.get_today_caffeine().unwrap();
-        assert_eq!(totals.total_mg, 142.0);
+        let card = db.food_card("white rice").unwrap().unwrap();
+        assert_eq!(card.food.name, "Rice");
+        assert_eq!(card.food.aliases, ["white rice"]);
+        assert_eq!(card.tags, ["staple"]);
+        assert_eq!(card.used_in, ["Bowl"]);
+        assert_eq!(card.times_logged, 2);
+        assert_eq!(card.first_logged.as_deref(), Some("2026-01-02"));
+        assert_eq!(card.last_amount.as_deref(), Some("100g"));
+
+        let bowl = db.food_card("bowl").unwrap().unwrap();
+        assert_eq!(bowl.components[0], ("Rice".to_string(), "200g".to_string()));
+        assert_eq!(bowl.times_logged, 0);
+        assert!(db.food_card("nothing").unwrap().is_none());
     }
 
     #[test]
-    fn test_delete_caffeine_entry() {
+    fn test_food_stats() {
         let db = test_db();
-        let entry = db.log_caffeine(95.0, "coffee", None).unwrap();
-        db.delete_caffeine_entry(entry.id.unwrap()).unwrap();
-        let totals = db.get_today_caffeine().unwrap();
-        assert_eq!(totals.total_mg, 0.0);
+        let rice = db.add_food(&sample_food("Rice")).unwrap();
+        db.add_food(&sample_food("Chicken")).unwrap();
+        let today = Local::now().date_naive();
+        let ago = |days: i64| (today - chrono::Duration::days(days)).to_string();
+        let m = |calories: f64| Macros {
+            calories,
+            ..Default::default()
+        };
+        db.log_food_with(
+            rice,
+            "150g",
+            &m(200.0),
+            &LogOptions::on_date(Some(&ago(40))),
+        )
+        .unwrap();
+        db.log_food_with(
+            rice,
+            "100g",
+            &m(130.0),
+            &LogOptions::on_date(Some(&ago(20))),
+        )
+        .unwrap();
+        db.log_food_with(
+            rice,
+            "100g",
+            &m(130.0),
+            &LogOptions::on_date(Some(&ago(20))),
+        )
+        .unwrap();
+        db.log_food_with(
+            rice,
+            "1 cup",
+            &m(240.0),
+            &LogOptions::on_date(Some(&ago(1))),
+        )
+        .unwrap();
+
+        let stats = db.food_stats("rice").unwrap().unwrap();
+        assert_eq!((stats.times_logged, stats.days_logged), (4, 3));
+        assert_eq!(stats.first_logged, Some(ago(40)));
+        assert!((stats.per_week - 4.0 * 7.0 / 41.0).abs() < 1e-9);
+        assert_eq!(stats.usual_amount.as_deref(), Some("100g"));
+        assert_eq!(stats.average_grams, Some(147.5));
+        assert_eq!(stats.average.calories, 175.0);
+        assert!(stats.usual_hour.is_some());
+        assert_eq!((stats.last_30_days, stats.previous_30_days), (3, 1));
+
+        let chicken = db.food_stats("chicken").unwrap().unwrap();
+        assert_eq!(chicken.times_logged, 0);
+        assert_eq!(chicken.usual_amount, None);
+        assert!(db.food_stats("nothing").unwrap().is_none());
     }
 
     #[test]
-    fn test_duplicate_food_handling() {
+    fn test_find_foods() {
         let db = test_db();
-        db.add_food(&sample_food("Eggs")).unwrap();
-        let result = db.add_food(&sample_food("Eggs"));
-        assert!(result.is_err()); // UNIQUE constraint
+        db.add_food(&Food::new("Chicken", 31.0, 3.6, 0.0, 165.0, "100g", vec![]))
+            .unwrap();
+        db.add_food(&Food::new("Salmon", 25.0, 13.0, 0.0, 208.0, "100g", vec![]))
+            .unwrap();
+        db.add_food(&Food::new("Jerky", 13.0, 1.0, 3.0, 70.0, "1oz", vec![]))
+            .unwrap();
+        db.add_food(&Food::new("Bar", 20.0, 4.0, 22.0, 200.0, "1 bar", vec![]))
+            .unwrap();
+        let filter = |column, expr| MacroFilter::parse(column, expr).unwrap();
+        let names = |foods: Vec<Food>| foods.into_iter().map(|f| f.name).collect::<Vec<_>>();
+
+        let lean = [filter("protein", ">=20"), filter("fat", "<5")];
+        assert_eq!(
+            names(db.find_foods(&lean, None).unwrap()),
+            ["Bar", "Chicken"]
+        );
+        // Per 100g, jerky has 46g protein and the bar drops out
+        let per_100g = db.find_foods(&lean, Some("100g")).unwrap();
+        assert_eq!(names(per_100g.clone()), ["Chicken", "Jerky"]);
+        assert_eq!(per_100g[1].serving, "100g");
+        assert!((per_100g[1].protein - 45.86).abs() < 0.01);
+
+        assert_eq!(
+            names(db.find_foods(&[filter("carbs", "0")], None).unwrap()),
+            ["Chicken", "Salmon"]
+        );
+        assert!(db.find_foods(&[], Some("1 cup")).is_err());
     }
 
     #[test]
-    fn test_compound_food() {
+    fn test_net_carb_mode() {
         let db = test_db();
-        db.add_food(&Food::new("Rice", 2.7, 0.3, 28.0, 130.0, "100g", vec![]))
+        let mut oats = Food::new("Oats", 13.0, 7.0, 68.0, 389.0, "100g", vec![]);
+        oats.fiber = 10.0;
+        let id = db.add_food(&oats).unwrap();
+        let macros = db
+            .get_food_by_name("oats")
+            .unwrap()
+            .unwrap()
+            .calculate("50g")
+            .unwrap();
+        assert_eq!(macros.fiber, 5.0);
+        db.log_food_with(id, "50g", &macros, &LogOptions::default())
             .unwrap();
-        db.add_food(&Food::new(
-            "Chicken Breast",
-            31.0,
-            3.6,
-            0.0,
-            165.0,
-            "100g",
-            vec![],
-        ))
-        .unwrap();
-
-        db.create_compound_food(
-            "Chicken Rice Bowl",
-            &[
-                ("Rice".to_string(), "200g".to_string()),
-                ("Chicken Breast".to_string(), "150g".to_string()),
-            ],
-        )
-        .unwrap();
 
-        let found = db.get_food_by_name("Chicken Rice Bowl").unwrap().unwrap();
-        assert!(found.calories > 0.0);
+        assert_eq!(db.get_today_totals().unwrap().carbs, 34.0);
+        let db = db.with_carb_mode(CarbMode::Net);
+        let totals = db.get_today_totals().unwrap();
+        assert_eq!((totals.carbs, totals.fiber), (29.0, 5.0));
+        let today = Local::now().format("%Y-%m-%d").to_string();
+        assert_eq!(db.rollups(&today, "day").unwrap()[0].carbs, 29.0);
+
+        // Recalculated calories count the fiber at its own factor
+        let entry = db.edit_log_entry(1, None, None, None, Some(34.0)).unwrap();
+        assert_eq!(
+            entry.calories,
+            6.5 * 4.0 + 3.5 * 9.0 + 29.0 * 4.0 + 5.0 * 2.0
+        );
+    }
 
-        let items = db.get_compound_food("Chicken Rice Bowl").unwrap();
-        assert_eq!(items.len(), 2);
+    #[test]
+    fn test_schema_version_handshake() {
+        let db = test_db();
+        let info = db.info().unwrap();
+        assert_eq!(info.schema_version, SCHEMA_VERSION);
+        assert_eq!(info.created_by.as_deref(), Some(env!("CARGO_PKG_VERSION")));
+        assert_eq!(info.migrated_by, None);
+
+        // A newer chomp moved it on: this one refuses it, naming that version
+        db.conn
+            .execute_batch(&format!(
+                "PRAGMA user_version = {};
+                 INSERT INTO meta (key, value) VALUES ('migrated_by', '9.0.0');",
+                SCHEMA_VERSION + 1
+            ))
+            .unwrap();
+        let err = db.init().unwrap_err().to_string();
+        assert!(err.contains("upgraded by chomp 9.0.0"), "{}", err);
+        assert!(err.contains("Upgrade chomp"), "{}", err);
+        assert_eq!(db.info().unwrap().schema_version, SCHEMA_VERSION + 1);
     }
 }
@@ -0,0 +1,345 @@
+//! `chomp digest`: a stretch of days (a week, typically) written up as one
+//! document — intake, adherence, weight, top foods, and gaps in the log —
+//! for mailing or posting from cron.
+
+use anyhow::Result;
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use serde::Serialize;
+
+use crate::adherence;
+use crate::config::Config;
+use crate::db::{Database, FoodTally, MissingDay, Rollup, TargetAdjustment};
+
+/// How many foods the digest lists.
+const TOP_FOODS: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    Markdown,
+    Text,
+}
+
+/// The first day of a digest: a date, "today", "yesterday", or a weekday
+/// meaning its latest occurrence up to `today` ("monday" on a Monday is
+/// today).
+pub fn parse_since(input: &str, today: NaiveDate) -> Result<NaiveDate> {
+    let input = input.trim().to_lowercase();
+    let since = match input.as_str() {
+        "today" => today,
+        "yesterday" => today - Duration::days(1),
+        _ => match input.parse::<Weekday>() {
+            Ok(weekday) => {
+                let back = (7 + today.weekday().num_days_from_monday()
+                    - weekday.num_days_from_monday())
+                    % 7;
+                today - Duration::days(back.into())
+            }
+            Err(_) => NaiveDate::parse_from_str(&input, "%Y-%m-%d").map_err(|_| {
+                anyhow::anyhow!(
+                    "Unknown start '{}' (use a weekday, yesterday, or YYYY-MM-DD)",
+                    input
+                )
+            })?,
+        },
+    };
+    if since > today {
+        anyhow::bail!("The digest can't start after today ({})", today);
+    }
+    Ok(since)
+}
+
+/// Calories and macros, summed or averaged over logged days.
+#[derive(Debug, Default, Serialize)]
+pub struct Intake {
+    pub calories: f64,
+    pub protein: f64,
+    pub fat: f64,
+    pub carbs: f64,
+}
+
+/// Everything a digest reports on, from `from` through `to`.
+#[derive(Debug, Serialize)]
+pub struct Digest {
+    pub from: String,
+    pub to: String,
+    /// Days in the period, today included
+    pub days: i64,
+    pub days_logged: usize,
+    /// Summed over the logged days
+    pub totals: Intake,
+    /// Per logged day
+    pub average: Intake,
+    /// Logged days on target, each judged by its own goals
+    pub days_on_target: usize,
+    /// Moving-average weight on the first and last day with one
+    pub weight_from: Option<f64>,
+    pub weight_to: Option<f64>,
+    /// Most calories contributed first
+    pub top_foods: Vec<FoodTally>,
+    /// Days before today with nothing or too little logged, oldest first
+    pub missing_days: Vec<MissingDay>,
+}
+
+/// Gather the digest for `since` through `today`.
+pub fn build(
+    db: &Database,
+    config: &Config,
+    adjustments: &[TargetAdjustment],
+    since: NaiveDate,
+    today: NaiveDate,
+) -> Result<Digest> {
+    let (from, to) = (since.to_string(), today.to_string());
+    let logged: Vec<Rollup> = db
+        .rollups(&from, "day")?
+        .into_iter()
+        .filter(|day| day.period <= to)
+        .collect();
+    let sum = |value: fn(&Rollup) -> f64| logged.iter().map(value).sum::<f64>();
+    let totals = Intake {
+        calories: sum(|d| d.calories),
+        protein: sum(|d| d.protein),
+        fat: sum(|d| d.fat),
+        carbs: sum(|d| d.carbs),
+    };
+    let n = logged.len().max(1) as f64;
+    let average = Intake {
+        calories: totals.calories / n,
+        protein: totals.protein / n,
+        fat: totals.fat / n,
+        carbs: totals.carbs / n,
+    };
+    let trend = crate::weight::trend(db, since, today)?;
+    let days = (today - since).num_days() + 1;
+    let mut missing_days: Vec<MissingDay> = db
+        .missing_days(days as u32)?
+        .into_iter()
+        .filter(|d| d.date >= from)
+        .collect();
+    missing_days.reverse();
+
+    Ok(Digest {
+        days,
+        days_logged: logged.len(),
+        days_on_target: adherence::evaluate(config, adjustments, &logged)
+            .iter()
+            .filter(|d| d.on_target)
+            .count(),
+        totals,
+        average,
+        weight_from: trend.iter().find_map(|d| d.average),
+        weight_to: trend.iter().rev().find_map(|d| d.average),
+        top_foods: db.top_foods(&from, &to, TOP_FOODS)?,
+        missing_days,
+        from,
+        to,
+    })
+}
+
+/// "Mon Oct 12" for a YYYY-MM-DD date.
+fn day_name(date: &str) -> String {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map(|d| d.format("%a %b %-d").to_string())
+        .unwrap_or_else(|_| date.to_string())
+}
+
+/// The digest as one document, headed sections in prose and lists.
+pub fn render(digest: &Digest, format: Format) -> String {
+    let markdown = format == Format::Markdown;
+    let heading = |level: usize, title: &str| {
+        if markdown {
+            format!("{} {}", "#".repeat(level), title)
+        } else {
+            let rule = if level == 1 { "=" } else { "-" };
+            format!("{}\n{}", title, rule.repeat(title.chars().count()))
+        }
+    };
+    let bullet = if markdown { "-" } else { "•" };
+    let mut sections = vec![heading(
+        1,
+        &format!(
+            "chomp digest: {} – {}",
+            day_name(&digest.from),
+            day_name(&digest.to)
+        ),
+    )];
+
+    let (avg, total) = (&digest.average, &digest.totals);
+    sections.push(if digest.days_logged == 0 {
+        format!("Nothing was logged in these {} days.", digest.days)
+    } else {
+        format!(
+            "You logged {} of {} days, averaging {:.0} kcal with {:.0}g protein, {:.0}g fat, and {:.0}g carbs. {} of those days ({:.0}%) were on target.",
+            digest.days_logged,
+            digest.days,
+            avg.calories,
+            avg.protein,
+            avg.fat,
+            avg.carbs,
+            digest.days_on_target,
+            digest.days_on_target as f64 / digest.days_logged as f64 * 100.0
+        )
+    });
+
+    if digest.days_logged > 0 {
+        let rows = [
+            ("Calories", "", total.calories, avg.calories),
+            ("Protein", "g", total.protein, avg.protein),
+            ("Fat", "g", total.fat, avg.fat),
+            ("Carbs", "g", total.carbs, avg.carbs),
+        ];
+        sections.push(heading(2, "Totals"));
+        let mut table = vec![if markdown {
+            "| | Total | Daily average |\n|---|---:|---:|".to_string()
+        } else {
+            format!("{:<10}  {:>8}  {:>13}", "", "Total", "Daily average")
+        }];
+        for (label, unit, sum, mean) in rows {
+            let (sum, mean) = (
+                format!("{:.0}{}", sum, unit),
+                format!("{:.0}{}", mean, unit),
+            );
+            table.push(if markdown {
+                format!("| {} | {} | {} |", label, sum, mean)
+            } else {
+                format!("{:<10}  {:>8}  {:>13}", label, sum, mean)
+            });
+        }
+        sections.push(table.join("\n"));
+    }
+
+    sections.push(heading(2, "Weight"));
+    sections.push(match (digest.weight_from, digest.weight_to) {
+        (Some(from), Some(to)) if (to - from).abs() >= 0.05 => format!(
+            "Your {}-day average went from {:.1}kg to {:.1}kg ({:+.1}kg).",
+            crate::weight::WINDOW,
+            from,
+            to,
+            to - from
+        ),
+        (Some(_), Some(to)) => format!(
+            "Your {}-day average held at {:.1}kg.",
+            crate::weight::WINDOW,
+            to
+        ),
+        _ => "No weigh-ins in this period.".to_string(),
+    });
+
+    if !digest.top_foods.is_empty() {
+        sections.push(heading(2, "Top foods"));
+        let foods: Vec<String> = digest
+            .top_foods
+            .iter()
+            .enumerate()
+            .map(|(i, food)| {
+                format!(
+                    "{}. {} — {:.0} kcal over {} {}",
+                    i + 1,
+                    food.name,
+                    food.calories,
+                    food.times,
+                    if food.times == 1 { "entry" } else { "entries" }
+                )
+            })
+            .collect();
+        sections.push(foods.join("\n"));
+    }
+
+    sections.push(heading(2, "Missing days"));
+    sections.push(if digest.missing_days.is_empty() {
+        "None.".to_string()
+    } else {
+        digest
+            .missing_days
+            .iter()
+            .map(|day| {
+                let what = if day.entries == 0 {
+                    "nothing logged".to_string()
+                } else {
+                    format!("only {:.0} kcal", day.calories)
+                };
+                format!("{} {} ({})", bullet, day_name(&day.date), what)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    });
+
+    sections.join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_parse_since() {
+        // 2026-10-16 is a Friday
+        let today = date("2026-10-16");
+        assert_eq!(parse_since("monday", today).unwrap(), date("2026-10-12"));
+        assert_eq!(parse_since("Fri", today).unwrap(), today);
+        assert_eq!(parse_since("saturday", today).unwrap(), date("2026-10-10"));
+        assert_eq!(parse_since("yesterday", today).unwrap(), date("2026-10-15"));
+        assert_eq!(
+            parse_since("2026-10-01", today).unwrap(),
+            date("2026-10-01")
+        );
+        assert!(parse_since("2026-10-17", today).is_err());
+        assert!(parse_since("someday", today).is_err());
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let digest = Digest {
+            from: "2026-10-12".to_string(),
+            to: "2026-10-16".to_string(),
+            days: 5,
+            days_logged: 3,
+            totals: Intake {
+                calories: 6150.0,
+                protein: 450.0,
+                fat: 210.0,
+                carbs: 630.0,
+            },
+            average: Intake {
+                calories: 2050.0,
+                protein: 150.0,
+                fat: 70.0,
+                carbs: 210.0,
+            },
+            days_on_target: 2,
+            weight_from: Some(82.4),
+            weight_to: Some(81.9),
+            top_foods: vec![
+                FoodTally {
+                    name: "chicken breast".to_string(),
+                    times: 4,
+                    calories: 1320.0,
+                },
+                FoodTally {
+                    name: "oats".to_string(),
+                    times: 1,
+                    calories: 380.0,
+                },
+            ],
+            missing_days: vec![
+                MissingDay {
+                    date: "2026-10-13".to_string(),
+                    entries: 0,
+                    calories: 0.0,
+                },
+                MissingDay {
+                    date: "2026-10-15".to_string(),
+                    entries: 1,
+                    calories: 310.0,
+                },
+            ],
+        };
+        insta::assert_snapshot!(render(&digest, Format::Markdown));
+        let text = render(&digest, Format::Text);
+        assert!(text.starts_with("chomp digest: Mon Oct 12 – Fri Oct 16\n====="));
+        assert!(text.contains("• Tue Oct 13 (nothing logged)"));
+    }
+}
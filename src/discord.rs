@@ -0,0 +1,268 @@
+//! `chomp bot discord`: `/log`, `/today`, and `/undo` slash commands, served
+//! as the interactions endpoint of a Discord application.
+
+use anyhow::Result;
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::chat;
+use crate::db::Database;
+
+const API: &str = "https://discord.com/api/v10";
+
+/// Interaction types Discord sends, and the response type that answers one
+/// with a message.
+const PING: u64 = 1;
+const APPLICATION_COMMAND: u64 = 2;
+const CHANNEL_MESSAGE: u64 = 4;
+
+/// Message flag that shows a reply only to the user who ran the command.
+const EPHEMERAL: u64 = 1 << 6;
+
+pub struct DiscordOptions {
+    /// The application's Ed25519 public key, which signs every interaction
+    pub public_key: Vec<u8>,
+    /// Discord user ID -> profile. When empty everyone shares the default
+    /// database; otherwise only listed users are answered.
+    pub users: HashMap<String, String>,
+}
+
+/// Decode the hex public key shown on the application's General Information page.
+pub fn parse_public_key(hex: &str) -> Result<Vec<u8>> {
+    let hex = hex.trim();
+    let bytes = decode_hex(hex)
+        .filter(|b| b.len() == 32)
+        .ok_or_else(|| anyhow::anyhow!("Invalid Discord public key: expected 64 hex digits"))?;
+    Ok(bytes)
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Whether Discord signed `timestamp` followed by `body` with `public_key`.
+fn verify(public_key: &[u8], signature: &str, timestamp: &str, body: &[u8]) -> bool {
+    let Some(signature) = decode_hex(signature) else {
+        return false;
+    };
+    let message = [timestamp.as_bytes(), body].concat();
+    ring::signature::UnparsedPublicKey::new(&ring::signature::ED25519, public_key)
+        .verify(&message, &signature)
+        .is_ok()
+}
+
+/// The slash commands, as registered with Discord.
+pub fn commands() -> Value {
+    json!([
+        {
+            "name": "log",
+            "description": "Log food, e.g. 2 eggs and toast",
+            "options": [{
+                "type": 3,
+                "name": "food",
+                "description": "What you ate",
+                "required": true
+            }]
+        },
+        {"name": "today", "description": "Today's totals"},
+        {"name": "undo", "description": "Undo the last change"}
+    ])
+}
+
+/// Register (or replace) the slash commands for an application.
+pub fn register(application_id: &str, bot_token: &str) -> Result<()> {
+    let response = reqwest::blocking::Client::new()
+        .put(format!("{}/applications/{}/commands", API, application_id))
+        .header("Authorization", format!("Bot {}", bot_token))
+        .json(&commands())
+        .send()?;
+    if !response.status().is_success() {
+        anyhow::bail!(
+            "Discord refused the commands ({}): {}",
+            response.status(),
+            response.text().unwrap_or_default()
+        );
+    }
+    Ok(())
+}
+
+/// Serve the interactions endpoint at `POST /interactions`.
+pub async fn serve_discord(port: u16, host: &str, options: DiscordOptions) -> Result<()> {
+    let users = options.users.len();
+    let app = Router::new()
+        .route("/interactions", post(interactions_handler))
+        .with_state(Arc::new(options));
+
+    let addr: std::net::SocketAddr = format!("{}:{}", host, port).parse()?;
+    eprintln!("chomp Discord bot listening on http://{}", addr);
+    eprintln!("  Interactions endpoint: http://{}/interactions", addr);
+    if users == 0 {
+        eprintln!("  Users:                 everyone, on the default database");
+    } else {
+        eprintln!("  Users:                 {} mapped to profiles", users);
+    }
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+async fn interactions_handler(
+    State(options): State<Arc<DiscordOptions>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+    };
+    if !verify(
+        &options.public_key,
+        header("x-signature-ed25519"),
+        header("x-signature-timestamp"),
+        &body,
+    ) {
+        return (StatusCode::UNAUTHORIZED, "Bad request signature").into_response();
+    }
+    let Ok(interaction) = serde_json::from_slice::<Value>(&body) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    Json(respond(&options.users, &interaction)).into_response()
+}
+
+/// The response to an interaction: a pong, or a reply to a slash command
+/// run against the caller's profile.
+fn respond(users: &HashMap<String, String>, interaction: &Value) -> Value {
+    if interaction["type"].as_u64() != Some(APPLICATION_COMMAND) {
+        return json!({"type": PING});
+    }
+    // In a server the caller is the member's user; in a DM, the user
+    let user = interaction["member"]["user"]["id"]
+        .as_str()
+        .or_else(|| interaction["user"]["id"].as_str())
+        .unwrap_or_default();
+    let content = match user_profile(users, user) {
+        Some(profile) => match crate::config::open_database(profile) {
+            Ok(db) => run_command(&db, &interaction["data"]),
+            Err(e) => format!("Database error: {}", e),
+        },
+        None => format!(
+            "Your Discord account isn't linked to chomp; add {} to --users",
+            user
+        ),
+    };
+    json!({
+        "type": CHANNEL_MESSAGE,
+        "data": {"content": content, "flags": EPHEMERAL},
+    })
+}
+
+/// The profile a Discord user logs to: `Some(None)` for the default
+/// database, `None` when they aren't allowed.
+fn user_profile<'a>(users: &'a HashMap<String, String>, user: &str) -> Option<Option<&'a str>> {
+    if users.is_empty() {
+        return Some(None);
+    }
+    users.get(user).map(|profile| Some(profile.as_str()))
+}
+
+/// Run a slash command and describe what happened.
+fn run_command(db: &Database, data: &Value) -> String {
+    let option = |name: &str| {
+        data["options"]
+            .as_array()
+            .and_then(|options| options.iter().find(|o| o["name"] == name))
+            .and_then(|o| o["value"].as_str())
+            .unwrap_or_default()
+    };
+    match data["name"].as_str().unwrap_or_default() {
+        "log" => chat::log_text(db, option("food")).text,
+        "today" => chat::today(db),
+        "undo" => chat::undo(db),
+        other => format!("Unknown command: /{}", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    #[test]
+    fn test_verify_signature() {
+        let rng = ring::rand::SystemRandom::new();
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&rng).unwrap();
+        let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let public_key = pair.public_key().as_ref();
+        let body = br#"{"type":1}"#;
+        let signature: String = pair
+            .sign(&[b"1760600000".as_slice(), body].concat())
+            .as_ref()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+
+        assert!(verify(public_key, &signature, "1760600000", body));
+        assert!(!verify(public_key, &signature, "1760600001", body));
+        assert!(!verify(public_key, "zz", "1760600000", body));
+
+        let hex: String = public_key.iter().map(|b| format!("{:02x}", b)).collect();
+        assert_eq!(parse_public_key(&hex).unwrap(), public_key);
+        assert!(parse_public_key("abcd").is_err());
+    }
+
+    #[test]
+    fn test_user_profiles() {
+        let ping = respond(&HashMap::new(), &json!({"type": PING}));
+        assert_eq!(ping, json!({"type": PING}));
+
+        assert_eq!(user_profile(&HashMap::new(), "42"), Some(None));
+        let users = HashMap::from([("42".to_string(), "alice".to_string())]);
+        assert_eq!(user_profile(&users, "42"), Some(Some("alice")));
+        assert_eq!(user_profile(&users, "7"), None);
+
+        let stranger = respond(
+            &users,
+            &json!({"type": APPLICATION_COMMAND, "user": {"id": "7"}, "data": {"name": "today"}}),
+        );
+        assert!(stranger["data"]["content"]
+            .as_str()
+            .unwrap()
+            .contains("isn't linked"));
+    }
+
+    #[test]
+    fn test_run_command() {
+        let db = Database::open_in_memory().unwrap();
+        let eggs = crate::food::Food::new("Eggs", 6.0, 5.0, 0.5, 70.0, "1", vec![]);
+        db.add_food(&eggs).unwrap();
+        let log = json!({"name": "log", "options": [{"name": "food", "value": "2 eggs"}]});
+        assert!(run_command(&db, &log).starts_with("Logged 2 Eggs: 140 kcal"));
+        assert!(run_command(&db, &json!({"name": "today"})).ends_with("(1 entries)"));
+        assert_eq!(
+            run_command(&db, &json!({"name": "undo"})),
+            "Removed log entry #1 (2)"
+        );
+        assert_eq!(
+            run_command(&db, &json!({"name": "nope"})),
+            "Unknown command: /nope"
+        );
+    }
+}
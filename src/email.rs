@@ -0,0 +1,414 @@
+//! `chomp bot email`: poll a mailbox over IMAP, log each new email's subject
+//! as a meal, and reply over SMTP with what was logged. Both connections use
+//! implicit TLS (IMAPS on 993, SMTPS on 465).
+
+use anyhow::{anyhow, Result};
+use base64::Engine;
+use native_tls::{TlsConnector, TlsStream};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+
+use crate::chat;
+
+/// How long a server may take to answer before the poll is abandoned.
+const IO_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+
+pub struct EmailOptions {
+    pub imap_host: String,
+    pub imap_port: u16,
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub username: String,
+    pub password: String,
+    /// Address replies come from
+    pub from: String,
+    /// Senders whose emails are logged; everything else is marked read and
+    /// ignored. Only as trustworthy as the mail server's checks against
+    /// forged From addresses (SPF, DKIM, DMARC).
+    pub allow: Vec<String>,
+    pub interval: std::time::Duration,
+    /// Profile database to log to
+    pub profile: Option<String>,
+}
+
+/// The headers of an incoming email that matter here.
+#[derive(Debug, Default, PartialEq)]
+struct Headers {
+    from: String,
+    subject: String,
+    message_id: Option<String>,
+}
+
+/// Poll until stopped, logging and answering new mail every `interval`.
+pub fn run(options: &EmailOptions) -> Result<()> {
+    eprintln!(
+        "chomp email bot polling {}@{}:{} every {}s",
+        options.username,
+        options.imap_host,
+        options.imap_port,
+        options.interval.as_secs()
+    );
+    loop {
+        match poll(options) {
+            Ok(0) => {}
+            Ok(n) => eprintln!("Answered {} email(s)", n),
+            Err(e) => eprintln!("Email poll failed, retrying: {}", e),
+        }
+        std::thread::sleep(options.interval);
+    }
+}
+
+/// Handle every unread email once, returning how many were answered.
+fn poll(options: &EmailOptions) -> Result<usize> {
+    let mut imap = Imap::connect(&options.imap_host, options.imap_port)?;
+    imap.command(&format!(
+        "LOGIN {} {}",
+        quote(&options.username),
+        quote(&options.password)
+    ))?;
+    imap.command("SELECT INBOX")?;
+    let uids = search_uids(&imap.command("UID SEARCH UNSEEN")?);
+
+    let mut answered = 0;
+    for uid in uids {
+        let header = imap.command(&format!(
+            "UID FETCH {} (BODY.PEEK[HEADER.FIELDS (FROM SUBJECT MESSAGE-ID)])",
+            uid
+        ))?;
+        // Read before anything is logged, so a failed reply doesn't log it
+        // again on the next poll
+        imap.command(&format!("UID STORE {} +FLAGS (\\Seen)", uid))?;
+        let headers = parse_headers(&header);
+        let sender = address(&headers.from);
+        if options
+            .allow
+            .iter()
+            .any(|a| a.eq_ignore_ascii_case(&sender))
+        {
+            let reply = match crate::config::open_database(options.profile.as_deref()) {
+                Ok(db) => chat::log_text(&db, &headers.subject).text,
+                Err(e) => format!("Database error: {}", e),
+            };
+            let message = reply_message(&options.from, &sender, &headers, &reply);
+            send_mail(options, &sender, &message)?;
+            answered += 1;
+        }
+    }
+    imap.command("LOGOUT")?;
+    Ok(answered)
+}
+
+fn tls_connect(host: &str, port: u16) -> Result<BufReader<TlsStream<TcpStream>>> {
+    let tcp = TcpStream::connect((host, port))
+        .map_err(|e| anyhow!("Failed to connect to {}:{}: {}", host, port, e))?;
+    tcp.set_read_timeout(Some(IO_TIMEOUT))?;
+    tcp.set_write_timeout(Some(IO_TIMEOUT))?;
+    let tls = TlsConnector::new()?
+        .connect(host, tcp)
+        .map_err(|e| anyhow!("TLS handshake with {} failed: {}", host, e))?;
+    Ok(BufReader::new(tls))
+}
+
+/// Just enough IMAP to search, fetch headers, and set flags.
+struct Imap {
+    stream: BufReader<TlsStream<TcpStream>>,
+    next_tag: u32,
+}
+
+impl Imap {
+    fn connect(host: &str, port: u16) -> Result<Self> {
+        let mut stream = tls_connect(host, port)?;
+        let mut greeting = String::new();
+        stream.read_line(&mut greeting)?;
+        if !greeting.starts_with("* OK") {
+            anyhow::bail!("Unexpected IMAP greeting: {}", greeting.trim_end());
+        }
+        Ok(Self {
+            stream,
+            next_tag: 1,
+        })
+    }
+
+    /// Send a command and return its untagged responses, literals inline,
+    /// failing unless it completes OK.
+    fn command(&mut self, command: &str) -> Result<String> {
+        let tag = format!("c{}", self.next_tag);
+        self.next_tag += 1;
+        let stream = self.stream.get_mut();
+        stream.write_all(format!("{} {}\r\n", tag, command).as_bytes())?;
+        stream.flush()?;
+
+        let mut data = Vec::new();
+        loop {
+            let mut line = Vec::new();
+            if self.stream.read_until(b'\n', &mut line)? == 0 {
+                anyhow::bail!("IMAP server closed the connection");
+            }
+            let text = String::from_utf8_lossy(&line).into_owned();
+            if let Some(status) = text.strip_prefix(&format!("{} ", tag)) {
+                if status.starts_with("OK") {
+                    return Ok(String::from_utf8_lossy(&data).into_owned());
+                }
+                // Don't echo the password back in the error
+                let verb = command.split_whitespace().next().unwrap_or_default();
+                anyhow::bail!("IMAP {} failed: {}", verb, status.trim_end());
+            }
+            data.extend_from_slice(&line);
+            if let Some(len) = literal_length(&text) {
+                let mut literal = vec![0; len];
+                self.stream.read_exact(&mut literal)?;
+                data.extend_from_slice(&literal);
+            }
+        }
+    }
+}
+
+/// The size of a literal announced at the end of a line: `{123}`.
+fn literal_length(line: &str) -> Option<usize> {
+    let line = line.trim_end();
+    line.strip_suffix('}')?.rsplit_once('{')?.1.parse().ok()
+}
+
+/// An IMAP quoted string.
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// UIDs from a `* SEARCH 1 2 3` response.
+fn search_uids(response: &str) -> Vec<u32> {
+    response
+        .lines()
+        .filter_map(|line| line.strip_prefix("* SEARCH"))
+        .flat_map(|ids| ids.split_whitespace().filter_map(|id| id.parse().ok()))
+        .collect()
+}
+
+/// From, Subject, and Message-ID out of a header block, unfolding
+/// continuation lines and decoding encoded words.
+fn parse_headers(block: &str) -> Headers {
+    let mut unfolded: Vec<String> = Vec::new();
+    for line in block.lines() {
+        match unfolded.last_mut() {
+            Some(last) if line.starts_with([' ', '\t']) => {
+                last.push(' ');
+                last.push_str(line.trim());
+            }
+            _ => unfolded.push(line.to_string()),
+        }
+    }
+    let mut headers = Headers::default();
+    for line in unfolded {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        // An encoded word can hide a line break, which would let a subject
+        // add headers to the reply
+        let value = decode_words(value.trim()).replace(['\r', '\n'], " ");
+        match name.to_ascii_lowercase().as_str() {
+            "from" => headers.from = value,
+            "subject" => headers.subject = value,
+            "message-id" => headers.message_id = Some(value),
+            _ => {}
+        }
+    }
+    headers
+}
+
+/// Decode RFC 2047 encoded words (`=?utf-8?B?...?=`, `=?utf-8?Q?...?=`),
+/// as mail clients write non-ASCII subjects. Other text passes through.
+fn decode_words(value: &str) -> String {
+    let mut out = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("=?") {
+        let decoded = rest[start + 2..]
+            .split_once("?=")
+            .and_then(|(word, after)| {
+                let mut parts = word.splitn(3, '?');
+                let (_charset, encoding, text) = (parts.next()?, parts.next()?, parts.next()?);
+                let bytes = match encoding.to_ascii_uppercase().as_str() {
+                    "B" => base64::engine::general_purpose::STANDARD
+                        .decode(text)
+                        .ok()?,
+                    "Q" => decode_q(text)?,
+                    _ => return None,
+                };
+                Some((String::from_utf8_lossy(&bytes).into_owned(), after))
+            });
+        match decoded {
+            Some((text, after)) => {
+                // Whitespace between adjacent encoded words is dropped
+                let before = &rest[..start];
+                if !before.trim().is_empty() || out.is_empty() {
+                    out.push_str(before);
+                }
+                out.push_str(&text);
+                rest = after;
+            }
+            None => {
+                out.push_str(&rest[..start + 2]);
+                rest = &rest[start + 2..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn decode_q(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut chars = text.bytes();
+    while let Some(b) = chars.next() {
+        match b {
+            b'_' => bytes.push(b' '),
+            b'=' => {
+                let hex = [chars.next()?, chars.next()?];
+                bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+            }
+            b => bytes.push(b),
+        }
+    }
+    Some(bytes)
+}
+
+/// The bare address in a From header: "Mei <mei@example.org>" -> "mei@example.org".
+fn address(from: &str) -> String {
+    match from.rsplit_once('<') {
+        Some((_, rest)) => rest.trim_end_matches('>').trim().to_string(),
+        None => from.trim().to_string(),
+    }
+}
+
+/// The reply to `incoming`, threaded under it.
+fn reply_message(from: &str, to: &str, incoming: &Headers, body: &str) -> String {
+    let mut lines = vec![
+        format!("From: {}", from),
+        format!("To: {}", to),
+        format!("Subject: Re: {}", incoming.subject),
+        format!("Date: {}", chrono::Local::now().to_rfc2822()),
+        "MIME-Version: 1.0".to_string(),
+        "Content-Type: text/plain; charset=utf-8".to_string(),
+        "Content-Transfer-Encoding: 8bit".to_string(),
+    ];
+    if let Some(id) = &incoming.message_id {
+        lines.push(format!("In-Reply-To: {}", id));
+        lines.push(format!("References: {}", id));
+    }
+    lines.push(String::new());
+    // A line that's only a dot would end the message early
+    lines.extend(body.lines().map(|line| match line.starts_with('.') {
+        true => format!(".{}", line),
+        false => line.to_string(),
+    }));
+    lines.join("\r\n")
+}
+
+/// Send `message` to `to` over SMTPS with AUTH PLAIN.
+fn send_mail(options: &EmailOptions, to: &str, message: &str) -> Result<()> {
+    let mut smtp = tls_connect(&options.smtp_host, options.smtp_port)?;
+    smtp_reply(&mut smtp, 220)?;
+    let credentials = base64::engine::general_purpose::STANDARD
+        .encode(format!("\0{}\0{}", options.username, options.password));
+    for (line, expect) in [
+        ("EHLO chomp".to_string(), 250),
+        (format!("AUTH PLAIN {}", credentials), 235),
+        (format!("MAIL FROM:<{}>", address(&options.from)), 250),
+        (format!("RCPT TO:<{}>", to), 250),
+        ("DATA".to_string(), 354),
+        (format!("{}\r\n.", message), 250),
+        ("QUIT".to_string(), 221),
+    ] {
+        let stream = smtp.get_mut();
+        stream.write_all(format!("{}\r\n", line).as_bytes())?;
+        stream.flush()?;
+        smtp_reply(&mut smtp, expect).map_err(|e| {
+            let verb = line
+                .split_whitespace()
+                .next()
+                .unwrap_or_default()
+                .to_string();
+            anyhow!("SMTP {} failed: {}", verb, e)
+        })?;
+    }
+    Ok(())
+}
+
+/// Read a (possibly multi-line) SMTP reply, failing unless its code is `expect`.
+fn smtp_reply(stream: &mut impl BufRead, expect: u16) -> Result<()> {
+    loop {
+        let mut line = String::new();
+        if stream.read_line(&mut line)? == 0 {
+            anyhow::bail!("SMTP server closed the connection");
+        }
+        // "250-..." continues, "250 ..." ends
+        if line.as_bytes().get(3) == Some(&b'-') {
+            continue;
+        }
+        return match line.get(..3).and_then(|code| code.parse::<u16>().ok()) {
+            Some(code) if code == expect => Ok(()),
+            _ => Err(anyhow!("{}", line.trim_end())),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_headers() {
+        let block = "* 3 FETCH (UID 7 BODY[HEADER.FIELDS (FROM SUBJECT MESSAGE-ID)] {120}\r\n\
+                     From: Mei <Mei@example.org>\r\n\
+                     Subject: =?utf-8?Q?cr=C3=A8me_br=C3=BBl=C3=A9e?=\r\n \
+                     and 2 eggs\r\n\
+                     Message-ID: <abc@mail.example.org>\r\n\r\n)\r\n";
+        let headers = parse_headers(block);
+        assert_eq!(headers.subject, "crème brûlée and 2 eggs");
+        assert_eq!(address(&headers.from), "Mei@example.org");
+        assert_eq!(
+            headers.message_id.as_deref(),
+            Some("<abc@mail.example.org>")
+        );
+
+        assert_eq!(
+            decode_words("=?UTF-8?B?dG9hc3Q=?= =?UTF-8?B?IGp1aWNl?="),
+            "toast juice"
+        );
+        assert_eq!(decode_words("no =? here"), "no =? here");
+        assert_eq!(address("plain@example.org"), "plain@example.org");
+
+        let headers = parse_headers("Subject: =?utf-8?Q?eggs=0D=0ABcc:_x@example.org?=\r\n");
+        assert_eq!(headers.subject, "eggs  Bcc: x@example.org");
+    }
+
+    #[test]
+    fn test_imap_helpers() {
+        assert_eq!(search_uids("* SEARCH 4 9 12\r\n"), [4, 9, 12]);
+        assert!(search_uids("* SEARCH\r\n").is_empty());
+        assert_eq!(literal_length("* 1 FETCH (BODY[] {42}\r\n"), Some(42));
+        assert_eq!(literal_length("* OK done\r\n"), None);
+        assert_eq!(quote(r#"pa"ss\"#), r#""pa\"ss\\""#);
+    }
+
+    #[test]
+    fn test_reply_message() {
+        let incoming = Headers {
+            from: "me@example.org".to_string(),
+            subject: "2 eggs".to_string(),
+            message_id: Some("<abc@example.org>".to_string()),
+        };
+        let message = reply_message(
+            "chomp@example.org",
+            "me@example.org",
+            &incoming,
+            "Logged 2 eggs\n.\nToday: 140 kcal",
+        );
+        assert!(message.contains("Subject: Re: 2 eggs\r\n"));
+        assert!(message.contains("In-Reply-To: <abc@example.org>\r\n"));
+        assert!(message.ends_with("\r\n\r\nLogged 2 eggs\r\n..\r\nToday: 140 kcal"));
+
+        let mut reply = "250-mail.example.org\r\n250-AUTH PLAIN\r\n250 OK\r\n".as_bytes();
+        assert!(smtp_reply(&mut reply, 250).is_ok());
+        let mut refused = "535 5.7.8 bad credentials\r\n".as_bytes();
+        assert!(smtp_reply(&mut refused, 235).is_err());
+    }
+}
@@ -0,0 +1,102 @@
+use serde::Serialize;
+
+use crate::config::Metric;
+use crate::food::{parse_grams, CarbMode, Food, Macros};
+
+/// The most of a food that fits what's left of one macro's budget.
+#[derive(Debug, Serialize)]
+pub struct Fit {
+    /// Amount to log, e.g. "32g", or a serving count for non-weight servings
+    pub amount: String,
+    pub servings: f64,
+    pub macros: Macros,
+}
+
+impl Fit {
+    /// The amount as a person would read it, e.g. "32g" or "1.5 × 1 bar".
+    pub fn describe(&self, food: &Food) -> String {
+        if parse_grams(&food.serving).is_some() {
+            self.amount.clone()
+        } else {
+            format!("{} × {}", self.amount, food.serving)
+        }
+    }
+}
+
+/// Solve for the largest amount of `food` whose `metric` stays within
+/// `remaining`, rounded down to whole grams (or tenths of a serving when the
+/// serving isn't a weight). Carbs count as `mode` counts them in totals.
+/// None when the food has none of that metric, so any amount fits.
+pub fn solve(food: &Food, metric: Metric, remaining: f64, mode: CarbMode) -> Option<Fit> {
+    let serving = Macros {
+        protein: food.protein,
+        fat: food.fat,
+        carbs: food.carbs,
+        calories: food.calories,
+        fiber: food.fiber,
+        micros: food.micros,
+    };
+    let per_serving = metric.counted(&serving, mode);
+    if per_serving <= 0.0 {
+        return None;
+    }
+    let servings = (remaining / per_serving).max(0.0);
+    let (amount, servings) = match parse_grams(&food.serving) {
+        Some(grams) => {
+            let total = (servings * grams).floor();
+            (format!("{}g", total), total / grams)
+        }
+        None => {
+            let count = (servings * 10.0).floor() / 10.0;
+            (format!("{}", count), count)
+        }
+    };
+    let macros = food.calculate(&amount)?;
+    Some(Fit {
+        amount,
+        servings,
+        macros,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solve() {
+        let pb = Food::new("peanut butter", 25.0, 50.0, 20.0, 588.0, "100g", vec![]);
+        let fit = solve(&pb, Metric::Fat, 16.4, CarbMode::Total).unwrap();
+        assert_eq!(fit.amount, "32g");
+        assert_eq!(fit.macros.fat, 16.0);
+        assert_eq!(
+            solve(&pb, Metric::Fat, -3.0, CarbMode::Total)
+                .unwrap()
+                .amount,
+            "0g"
+        );
+
+        let bar = Food::new("protein bar", 20.0, 8.0, 24.0, 240.0, "1 bar", vec![]);
+        let fit = solve(&bar, Metric::Calories, 400.0, CarbMode::Total).unwrap();
+        assert_eq!(fit.describe(&bar), "1.6 × 1 bar");
+        assert_eq!(fit.macros.calories, 384.0);
+
+        let chicken = Food::new("chicken", 31.0, 3.6, 0.0, 165.0, "100g", vec![]);
+        assert!(solve(&chicken, Metric::Carbs, 50.0, CarbMode::Total).is_none());
+
+        let mut oats = Food::new("oats", 13.0, 7.0, 68.0, 389.0, "100g", vec![]);
+        oats.fiber = 10.0;
+        assert_eq!(
+            solve(&oats, Metric::Carbs, 29.0, CarbMode::Total)
+                .unwrap()
+                .amount,
+            "42g"
+        );
+        assert_eq!(
+            solve(&oats, Metric::Carbs, 29.0, CarbMode::Net)
+                .unwrap()
+                .amount,
+            "50g"
+        );
+    }
+}
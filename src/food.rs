@@ -1,4 +1,6 @@
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::OnceLock;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Food {
@@ -12,6 +14,15 @@ pub struct Food {
     pub aliases: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub default_amount: Option<String>,
+    /// Grams of fiber per serving, part of `carbs`
+    #[serde(default)]
+    pub fiber: f64,
+    #[serde(flatten)]
+    pub micros: Micros,
+    /// Grams in one of the food's own units ("cup" = 185), which count
+    /// ahead of the unit table
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub units: BTreeMap<String, f64>,
 }
 
 impl Food {
@@ -34,19 +45,53 @@ impl Food {
             serving: serving.to_string(),
             aliases,
             default_amount: None,
+            fiber: 0.0,
+            micros: Micros::default(),
+            units: BTreeMap::new(),
         }
     }
 
     /// Calculate macros for a given amount
     pub fn calculate(&self, amount: &str) -> Option<Macros> {
-        let multiplier = parse_amount_multiplier(amount, &self.serving)?;
+        let multiplier = parse_amount_multiplier(amount, &self.serving, &self.units)?;
         Some(Macros {
             protein: self.protein * multiplier,
             fat: self.fat * multiplier,
             carbs: self.carbs * multiplier,
             calories: self.calories * multiplier,
+            fiber: self.fiber * multiplier,
+            micros: self.micros.scale(multiplier),
         })
     }
+
+    /// For a food created without serving info: switch to its category's
+    /// serving and default amount from the unit table, if it has one.
+    /// Returns the category.
+    pub fn assume_serving(&mut self) -> Option<String> {
+        let default = Units::current().serving_default(&self.name)?;
+        default.apply(self);
+        Some(default.category.clone())
+    }
+}
+
+/// A variant's full name, the way variants are told apart: "Oats" and
+/// "dry" make "Oats (dry)".
+pub fn variant_name(base: &str, variant: &str) -> String {
+    format!("{} ({})", base.trim(), variant.trim())
+}
+
+/// The name a food shares with its variants: "Oats" for "Oats (dry)" or
+/// "Oats (Quaker, cooked)". A name without a parenthesized suffix is its
+/// own base.
+pub fn base_name(name: &str) -> &str {
+    let name = name.trim();
+    match name
+        .strip_suffix(')')
+        .and_then(|rest| rest.rsplit_once(" ("))
+    {
+        Some((base, variant)) if !base.is_empty() && !variant.is_empty() => base.trim_end(),
+        _ => name,
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -55,6 +100,11 @@ pub struct Macros {
     pub fat: f64,
     pub carbs: f64,
     pub calories: f64,
+    /// Grams of fiber, already included in `carbs`
+    #[serde(default)]
+    pub fiber: f64,
+    #[serde(flatten)]
+    pub micros: Micros,
 }
 
 impl Default for Macros {
@@ -64,22 +114,180 @@ impl Default for Macros {
             fat: 0.0,
             carbs: 0.0,
             calories: 0.0,
+            fiber: 0.0,
+            micros: Micros::default(),
+        }
+    }
+}
+
+/// Nutrients tracked only where a label or import gives them. None means
+/// unknown rather than zero, so a total counts just the foods that have it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Micros {
+    /// Grams of sugar, part of `carbs`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sugar: Option<f64>,
+    /// Milligrams of sodium
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sodium: Option<f64>,
+    /// Grams of saturated fat, part of `fat`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub saturated_fat: Option<f64>,
+}
+
+impl Micros {
+    pub fn scale(&self, factor: f64) -> Self {
+        Self {
+            sugar: self.sugar.map(|v| v * factor),
+            sodium: self.sodium.map(|v| v * factor),
+            saturated_fat: self.saturated_fat.map(|v| v * factor),
+        }
+    }
+
+    /// Sum with `other`, leaving a nutrient unknown only if both are.
+    pub fn add(&mut self, other: &Micros) {
+        let sum = |a: Option<f64>, b: Option<f64>| match (a, b) {
+            (None, None) => None,
+            (a, b) => Some(a.unwrap_or(0.0) + b.unwrap_or(0.0)),
+        };
+        self.sugar = sum(self.sugar, other.sugar);
+        self.sodium = sum(self.sodium, other.sodium);
+        self.saturated_fat = sum(self.saturated_fat, other.saturated_fat);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Which carbs count toward totals and goals: all of them, or net of fiber.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CarbMode {
+    #[default]
+    Total,
+    Net,
+}
+
+impl CarbMode {
+    pub fn is_total(&self) -> bool {
+        *self == CarbMode::Total
+    }
+}
+
+/// Days logged under `below` calories look like forgotten logging rather
+/// than real intake: reports flag them, and with `exclude` leave them out of
+/// averages and adherence.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IncompleteDays {
+    pub below: f64,
+    pub exclude: bool,
+}
+
+impl Default for IncompleteDays {
+    fn default() -> Self {
+        Self {
+            below: 800.0,
+            exclude: false,
         }
     }
 }
 
+impl IncompleteDays {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Calories per gram of each macro, used wherever calories are derived from
+/// macros. Defaults are the Atwater 4/9/4 factors, with fiber at 2 when carbs
+/// are counted net of it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CalorieFactors {
+    pub protein: f64,
+    pub fat: f64,
+    pub carbs: f64,
+    pub fiber: f64,
+}
+
+impl Default for CalorieFactors {
+    fn default() -> Self {
+        Self {
+            protein: 4.0,
+            fat: 9.0,
+            carbs: 4.0,
+            fiber: 2.0,
+        }
+    }
+}
+
+impl CalorieFactors {
+    /// `carbs` include `fiber`; under net carbs the fiber is counted at its
+    /// own factor instead of the carb one.
+    pub fn calories_from_macros(
+        &self,
+        protein: f64,
+        fat: f64,
+        carbs: f64,
+        fiber: f64,
+        mode: CarbMode,
+    ) -> f64 {
+        let carbs = match mode {
+            CarbMode::Total => carbs * self.carbs,
+            CarbMode::Net => {
+                let fiber = fiber.min(carbs);
+                (carbs - fiber) * self.carbs + fiber * self.fiber
+            }
+        };
+        protein * self.protein + fat * self.fat + carbs
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
 impl Macros {
     pub fn add(&mut self, other: &Macros) {
         self.protein += other.protein;
         self.fat += other.fat;
         self.carbs += other.carbs;
         self.calories += other.calories;
+        self.fiber += other.fiber;
+        self.micros.add(&other.micros);
+    }
+
+    /// These macros multiplied by `factor`, e.g. a share of a whole recipe.
+    pub fn scale(&self, factor: f64) -> Self {
+        Macros {
+            protein: self.protein * factor,
+            fat: self.fat * factor,
+            carbs: self.carbs * factor,
+            calories: self.calories * factor,
+            fiber: self.fiber * factor,
+            micros: self.micros.scale(factor),
+        }
+    }
+
+    /// Carbs as they count toward totals and goals under `mode`.
+    pub fn counted_carbs(&self, mode: CarbMode) -> f64 {
+        match mode {
+            CarbMode::Total => self.carbs,
+            CarbMode::Net => (self.carbs - self.fiber).max(0.0),
+        }
     }
 }
 
 /// Parse amount string and return multiplier relative to serving size
-/// e.g., "8oz" with serving "100g" -> calculate ratio
-fn parse_amount_multiplier(amount: &str, serving: &str) -> Option<f64> {
+/// e.g., "8oz" with serving "100g" -> calculate ratio. Units the food
+/// weighs itself (`own`) go before the unit table.
+fn parse_amount_multiplier(
+    amount: &str,
+    serving: &str,
+    own: &BTreeMap<String, f64>,
+) -> Option<f64> {
     let (amount_val, amount_unit) = parse_quantity(amount)?;
     let (serving_val, serving_unit) = parse_quantity(serving)?;
 
@@ -87,66 +295,742 @@ fn parse_amount_multiplier(amount: &str, serving: &str) -> Option<f64> {
     // treat it as a serving count unless the serving itself is in grams.
     // e.g., "0.5" with serving "4oz" means half a serving, not 0.5g.
     if amount_unit == "g"
-        && amount.trim().parse::<f64>().is_ok()
+        && parse_count(amount).is_some()
         && !matches!(serving_unit.as_str(), "g" | "gram" | "grams")
     {
         return Some(amount_val);
     }
 
+    // "2 eggs" of a food served per "1 egg" needs no unit table
+    if same_unit(&amount_unit, &serving_unit) {
+        return Some(amount_val / serving_val);
+    }
+    // An amount in a unit we don't know can't be converted
+    if own_grams(own, &amount_unit).is_none() {
+        Units::current().grams_per(&amount_unit)?;
+    }
+
     // Convert both to grams for comparison
-    let amount_grams = to_grams(amount_val, &amount_unit)?;
-    let serving_grams = to_grams(serving_val, &serving_unit)?;
+    let grams = |value: f64, unit: &str| match own_grams(own, unit) {
+        Some(grams) => Some(value * grams),
+        None => to_grams(value, unit),
+    };
+    let amount_grams = grams(amount_val, &amount_unit)?;
+    let serving_grams = grams(serving_val, &serving_unit)?;
 
     Some(amount_grams / serving_grams)
 }
 
+/// Grams per `unit` in a food's own unit weights, allowing for plurals.
+fn own_grams(own: &BTreeMap<String, f64>, unit: &str) -> Option<f64> {
+    singulars(unit)
+        .into_iter()
+        .flatten()
+        .find_map(|name| own.get(&name).copied())
+}
+
+/// An amount's count and unit: "4 oz", "4oz", "1/2 cup", "1 1/2 oz",
+/// "half a cup", or "quarter lb". A count on its own is taken as grams.
 fn parse_quantity(s: &str) -> Option<(f64, String)> {
     let s = s.trim().to_lowercase();
 
-    // Split by whitespace first to handle "4 oz", "1 bar", etc.
-    let parts: Vec<&str> = s.split_whitespace().collect();
+    if let Some((num, rest)) = split_count(&s) {
+        let parts: Vec<&str> = rest.split_whitespace().collect();
+        return match parts[..] {
+            // Just a count, assume grams
+            [] => Some((num, "g".to_string())),
+            // "4 oz", "half a cup"
+            [unit] => Some((num, unit.to_string())),
+            _ => None,
+        };
+    }
 
-    if parts.len() == 2 {
-        // "4 oz" pattern
-        let num: f64 = parts[0].parse().ok()?;
-        let unit = parts[1].to_string();
-        Some((num, unit))
-    } else if parts.len() == 1 {
-        // Could be "4oz" or just "4"
-        let part = parts[0];
-        if let Some(num_end) = part.find(|c: char| !c.is_numeric() && c != '.') {
-            let num_str = &part[..num_end];
-            let unit = part[num_end..].to_string();
-            let num: f64 = num_str.parse().ok()?;
-            Some((num, unit))
-        } else {
-            // Just a number, assume grams
-            let num: f64 = part.parse().ok()?;
-            Some((num, "g".to_string()))
+    // "4oz" or "1/2cup"
+    let num_end = s.find(|c: char| !c.is_numeric() && c != '.' && c != '/')?;
+    let (num, unit) = s.split_at(num_end);
+    if unit.contains(char::is_whitespace) {
+        return None;
+    }
+    Some((parse_number(num)?, unit.to_string()))
+}
+
+/// A number written as digits ("1.5") or a fraction ("1/2").
+pub fn parse_number(s: &str) -> Option<f64> {
+    match s.split_once('/') {
+        Some((numerator, denominator)) => {
+            let denominator: f64 = denominator.parse().ok()?;
+            (denominator != 0.0).then_some(numerator.parse::<f64>().ok()? / denominator)
+        }
+        None => s.parse().ok(),
+    }
+}
+
+/// A count with nothing after it: "2", "1/2", "1 1/2", or "half".
+pub fn parse_count(s: &str) -> Option<f64> {
+    match split_count(&s.trim().to_lowercase())? {
+        (count, "") => Some(count),
+        _ => None,
+    }
+}
+
+/// The count `s` (lowercased) starts with, and what follows it. Counts are
+/// numbers, fractions, mixed numbers ("1 1/2"), and serving words, which
+/// may take an article or "of" around them ("a quarter of a", "half an").
+pub fn split_count(s: &str) -> Option<(f64, &str)> {
+    let (word, end) = first_word(s);
+    if let Some(count) = parse_number(word) {
+        // "1 1/2": a whole number and a proper fraction
+        let (next, next_end) = first_word(&s[end..]);
+        let fraction = parse_number(next).filter(|f| next.contains('/') && *f < 1.0);
+        return Some(match fraction {
+            Some(fraction) if !word.contains(['.', '/']) => {
+                (count + fraction, s[end + next_end..].trim_start())
+            }
+            _ => (count, s[end..].trim_start()),
+        });
+    }
+
+    let article = |word: &str| matches!(word, "a" | "an");
+    let (count, mut end) = if article(word) {
+        let (next, next_end) = first_word(&s[end..]);
+        match serving_word(next).filter(|_| !article(next)) {
+            // "a quarter"
+            Some(count) => (count, end + next_end),
+            // "a cup"
+            None => return Some((1.0, s[end..].trim_start())),
         }
     } else {
-        None
+        (serving_word(word)?, end)
+    };
+    // "half a cup", "quarter of an apple"
+    loop {
+        let (next, next_end) = first_word(&s[end..]);
+        if next != "of" && !article(next) {
+            break;
+        }
+        end += next_end;
     }
+    Some((count, s[end..].trim_start()))
 }
 
-fn to_grams(value: f64, unit: &str) -> Option<f64> {
+/// The first word of `s` and the byte offset just past it.
+fn first_word(s: &str) -> (&str, usize) {
+    let start = s.len() - s.trim_start().len();
+    let word = s[start..].split_whitespace().next().unwrap_or("");
+    (word, start + word.len())
+}
+
+/// Whether two units name the same thing, allowing for plurals.
+fn same_unit(a: &str, b: &str) -> bool {
+    let b = singulars(b);
+    singulars(a)
+        .iter()
+        .flatten()
+        .any(|a| b.iter().flatten().any(|b| a == b))
+}
+
+/// A lowercased unit and the singulars it might be the plural of.
+fn singulars(unit: &str) -> [Option<String>; 4] {
     let unit = unit.to_lowercase();
-    match unit.as_str() {
-        "g" | "gram" | "grams" => Some(value),
-        "oz" | "ounce" | "ounces" => Some(value * 28.3495),
-        "lb" | "lbs" | "pound" | "pounds" => Some(value * 453.592),
-        "kg" | "kilogram" | "kilograms" => Some(value * 1000.0),
-        "ml" | "milliliter" | "milliliters" => Some(value), // Assume 1:1 for liquids
-        "cup" | "cups" => Some(value * 240.0),              // Approximate
-        "tbsp" | "tablespoon" | "tablespoons" => Some(value * 15.0),
-        "tsp" | "teaspoon" | "teaspoons" => Some(value * 5.0),
-        // For discrete items (bar, piece, etc.), treat as 1:1 multiplier
-        "bar" | "bars" | "piece" | "pieces" | "serving" | "servings" | "scoop" | "scoops"
-        | "slice" | "slices" | "patty" | "patties" | "pack" | "packs" => Some(value * 100.0),
-        _ => Some(value), // Unknown unit, assume grams
+    [
+        Some(unit.clone()),
+        unit.strip_suffix("ies").map(|stem| format!("{}y", stem)),
+        unit.strip_suffix("es").map(str::to_string),
+        unit.strip_suffix('s').map(str::to_string),
+    ]
+}
+
+/// An amount in a unit that isn't in the unit table, for a food served in
+/// something else, so there's no telling how much it is. In strict units
+/// mode, also a unit (of the amount or the serving) whose weight would only
+/// be assumed: one not in the table, or a piece.
+#[derive(Debug)]
+pub struct UnknownUnit {
+    pub unit: String,
+    /// The food's serving, whose unit would also do
+    pub serving: String,
+    /// Turned down by strict units mode rather than missing from the table
+    pub strict: bool,
+}
+
+impl UnknownUnit {
+    /// The problem with logging `amount` of a food served per `serving`,
+    /// with its own unit weights `own`, if it's the unit.
+    pub fn check(amount: &str, serving: &str, own: &BTreeMap<String, f64>) -> Option<Self> {
+        let (_, unit) = parse_quantity(amount)?;
+        let (_, serving_unit) = parse_quantity(serving)?;
+        if same_unit(&unit, &serving_unit) {
+            return None;
+        }
+        let units = Units::current();
+        let problem = |unit: String, strict: bool| UnknownUnit {
+            unit,
+            serving: serving.to_string(),
+            strict,
+        };
+        if units.grams_per(&unit).is_none() && own_grams(own, &unit).is_none() {
+            return Some(problem(unit, false));
+        }
+        if !units.strict {
+            return None;
+        }
+        // Bare numbers are servings unless the serving is in grams
+        let bare = parse_count(amount).is_some();
+        [(!bare).then_some(unit), Some(serving_unit)]
+            .into_iter()
+            .flatten()
+            .find(|u| !matches!(units.grams_per(u), Some((_, kind)) if kind != UnitKind::Count))
+            .filter(|_| parse_amount_multiplier(amount, serving, own).is_none())
+            .map(|u| problem(u, true))
+    }
+}
+
+impl std::fmt::Display for UnknownUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.strict {
+            return write!(
+                f,
+                "No known weight for '{}' to convert between the amount and the food's serving \
+                 ({}), and strict units mode won't assume one. Log it in the serving's own unit, \
+                 or give '{}' a [weight] in units.toml",
+                self.unit, self.serving, self.unit
+            );
+        }
+        let units = Units::current().names();
+        write!(f, "Unknown unit '{}'.", self.unit)?;
+        let scored = units
+            .iter()
+            .map(|u| (crate::suggest::similarity(&self.unit, u), *u));
+        let close = crate::suggest::top(scored, 0.5, 1);
+        if let Some(unit) = close.first() {
+            write!(f, " Did you mean: {}?", unit)?;
+        }
+        write!(
+            f,
+            " Supported units: {}, or the food's serving ({}). Add your own in units.toml",
+            units.join(", "),
+            self.serving
+        )
+    }
+}
+
+impl std::error::Error for UnknownUnit {}
+
+fn to_grams(value: f64, unit: &str) -> Option<f64> {
+    let units = Units::current();
+    match units.grams_per(unit) {
+        // A piece only weighs what the unit table assumes
+        Some((_, UnitKind::Count)) if units.strict => None,
+        Some((grams, _)) => Some(value * grams),
+        None if units.strict => None,
+        // Unknown unit, assume grams
+        None => Some(value),
+    }
+}
+
+/// Unit table bundled with the binary.
+const DEFAULT_UNITS: &str = include_str!("../units.toml");
+
+static UNITS: OnceLock<Units> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnitKind {
+    Weight,
+    Volume,
+    Count,
+}
+
+/// Grams per unit, from the bundled `units.toml` with `~/.chomp/units.toml`
+/// (or CHOMP_UNITS_PATH) merged over it.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Units {
+    #[serde(default)]
+    weight: HashMap<String, f64>,
+    /// Millilitres, counted as grams
+    #[serde(default)]
+    volume: HashMap<String, f64>,
+    #[serde(default)]
+    count: HashMap<String, f64>,
+    /// Checked in order; the first whose keywords match wins
+    #[serde(default, rename = "serving")]
+    servings: Vec<ServingDefault>,
+    /// How much foods weigh cooked against dry or raw, checked in order
+    #[serde(default)]
+    cooking: Vec<CookingYield>,
+    /// Refuse to assume weights for unknown units and pieces
+    #[serde(skip)]
+    strict: bool,
+}
+
+/// Serving assumed for a category of foods created without one.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServingDefault {
+    pub category: String,
+    /// Matched against the food's main word: the last word before any comma
+    pub keywords: Vec<String>,
+    /// Serving to store nutrition per, rescaled from the food's own
+    #[serde(default)]
+    pub serving: Option<String>,
+    /// Grams per ml, for rescaling to a volume serving
+    #[serde(default = "water_density")]
+    pub density: f64,
+    #[serde(default)]
+    pub default_amount: Option<String>,
+}
+
+fn water_density() -> f64 {
+    1.0
+}
+
+/// Grams a category of foods weighs cooked per gram dry or raw, for logging
+/// "rice 150g cooked" against rice stored dry.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CookingYield {
+    pub category: String,
+    /// Matched against the food's main word, as for serving defaults
+    pub keywords: Vec<String>,
+    pub ratio: f64,
+}
+
+impl CookingYield {
+    fn check(&self) -> anyhow::Result<()> {
+        if !self.ratio.is_finite() || self.ratio <= 0.0 {
+            anyhow::bail!("Cooking ratio for {} must be positive", self.category);
+        }
+        Ok(())
+    }
+}
+
+/// Whether one of `keywords` is `name`'s main word: the last word before
+/// any comma, so "almond milk" and "Milk, whole" are milk.
+fn main_word_matches(keywords: &[String], name: &str) -> bool {
+    let Some(word) = name
+        .split(',')
+        .next()
+        .and_then(|head| head.split_whitespace().last())
+        .map(str::to_lowercase)
+    else {
+        return false;
+    };
+    keywords
+        .iter()
+        .any(|k| *k == word || word.strip_suffix('s') == Some(k.as_str()))
+}
+
+impl ServingDefault {
+    fn matches(&self, name: &str) -> bool {
+        main_word_matches(&self.keywords, name)
+    }
+
+    fn apply(&self, food: &mut Food) {
+        if let Some(serving) = &self.serving {
+            let volume = parse_quantity(serving).is_some_and(|(_, unit)| {
+                matches!(
+                    Units::current().grams_per(&unit),
+                    Some((_, UnitKind::Volume))
+                )
+            });
+            if let Some(mut factor) = parse_amount_multiplier(serving, &food.serving, &food.units) {
+                if volume {
+                    factor *= self.density;
+                }
+                food.protein *= factor;
+                food.fat *= factor;
+                food.carbs *= factor;
+                food.calories *= factor;
+                food.fiber *= factor;
+                food.micros = food.micros.scale(factor);
+                food.serving = serving.clone();
+            }
+        }
+        if food.default_amount.is_none() {
+            food.default_amount = self.default_amount.clone();
+        }
+    }
+
+    fn check(&self) -> anyhow::Result<()> {
+        let amounts = [&self.serving, &self.default_amount];
+        if let Some(bad) = amounts
+            .into_iter()
+            .flatten()
+            .find(|a| parse_quantity(a).is_none())
+        {
+            anyhow::bail!("Serving '{}' for {} isn't an amount", bad, self.category);
+        }
+        if !self.density.is_finite() || self.density <= 0.0 {
+            anyhow::bail!("Density for {} must be positive", self.category);
+        }
+        if self
+            .keywords
+            .iter()
+            .any(|k| k.contains(char::is_whitespace))
+        {
+            anyhow::bail!("Keywords for {} must be single words", self.category);
+        }
+        Ok(())
+    }
+}
+
+impl Units {
+    pub fn builtin() -> Self {
+        Self::parse(DEFAULT_UNITS).expect("bundled units.toml is valid")
+    }
+
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let units: Self = toml::from_str(text)?;
+        let mut lowered = Self::default();
+        for (kind, table) in units.tables() {
+            for (name, grams) in table {
+                if name.is_empty()
+                    || name
+                        .chars()
+                        .any(|c| c.is_whitespace() || c.is_ascii_digit() || c == '.')
+                {
+                    anyhow::bail!("Unit '{}' can't contain spaces, digits or dots", name);
+                }
+                if !grams.is_finite() || *grams <= 0.0 {
+                    anyhow::bail!("Unit '{}' needs a positive number of grams", name);
+                }
+                lowered.table_mut(kind).insert(name.to_lowercase(), *grams);
+            }
+        }
+        for serving in units.servings {
+            serving.check()?;
+            lowered.servings.push(ServingDefault {
+                keywords: serving.keywords.iter().map(|k| k.to_lowercase()).collect(),
+                ..serving
+            });
+        }
+        for cooking in units.cooking {
+            cooking.check()?;
+            lowered.cooking.push(CookingYield {
+                keywords: cooking.keywords.iter().map(|k| k.to_lowercase()).collect(),
+                ..cooking
+            });
+        }
+        Ok(lowered)
+    }
+
+    pub fn path() -> anyhow::Result<std::path::PathBuf> {
+        if let Ok(path) = std::env::var("CHOMP_UNITS_PATH") {
+            return Ok(std::path::PathBuf::from(path));
+        }
+        let home =
+            dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?;
+        Ok(home.join(".chomp").join("units.toml"))
+    }
+
+    /// The bundled table with the user's overrides applied.
+    pub fn load() -> anyhow::Result<Self> {
+        let mut units = Self::builtin();
+        let path = Self::path()?;
+        match std::fs::read_to_string(&path) {
+            Ok(text) => units.merge(
+                Self::parse(&text)
+                    .map_err(|e| anyhow::anyhow!("Invalid units {}: {}", path.display(), e))?,
+            ),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        Ok(units)
+    }
+
+    /// Units in `other` replace ours, moving between kinds if need be. Its
+    /// serving defaults and cooking yields go first, replacing ours of the
+    /// same category.
+    pub fn merge(&mut self, other: Self) {
+        self.servings
+            .retain(|s| !other.servings.iter().any(|o| o.category == s.category));
+        self.servings.splice(0..0, other.servings.iter().cloned());
+        self.cooking
+            .retain(|c| !other.cooking.iter().any(|o| o.category == c.category));
+        self.cooking.splice(0..0, other.cooking.iter().cloned());
+        for (kind, table) in other.tables() {
+            for (name, grams) in table {
+                for (_, existing) in self.tables_mut() {
+                    existing.remove(name);
+                }
+                self.table_mut(kind).insert(name.clone(), *grams);
+            }
+        }
+    }
+
+    /// Make amounts that need an assumed weight an error, not a guess.
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Use this table for every amount parsed from now on. Until called,
+    /// the bundled table is used.
+    pub fn install(self) {
+        let _ = UNITS.set(self);
+    }
+
+    pub fn current() -> &'static Units {
+        UNITS.get_or_init(Units::builtin)
+    }
+
+    /// Grams in one `unit` and what kind of unit it is. Plurals ("slices",
+    /// "patties", "glasses") fall back to their singular.
+    pub fn grams_per(&self, unit: &str) -> Option<(f64, UnitKind)> {
+        singulars(unit).into_iter().flatten().find_map(|name| {
+            self.tables()
+                .into_iter()
+                .find_map(|(kind, table)| table.get(&name).map(|grams| (*grams, kind)))
+        })
+    }
+
+    /// Every unit name, sorted.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .tables()
+            .into_iter()
+            .flat_map(|(_, table)| table.keys().map(String::as_str))
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    pub fn serving_default(&self, name: &str) -> Option<&ServingDefault> {
+        self.servings.iter().find(|s| s.matches(name))
+    }
+
+    /// Grams `name` weighs cooked per gram dry or raw, if its category has
+    /// a known yield.
+    pub fn cooked_ratio(&self, name: &str) -> Option<f64> {
+        self.cooking
+            .iter()
+            .find(|c| main_word_matches(&c.keywords, name))
+            .map(|c| c.ratio)
+    }
+
+    fn tables(&self) -> [(UnitKind, &HashMap<String, f64>); 3] {
+        [
+            (UnitKind::Weight, &self.weight),
+            (UnitKind::Volume, &self.volume),
+            (UnitKind::Count, &self.count),
+        ]
+    }
+
+    fn tables_mut(&mut self) -> [(UnitKind, &mut HashMap<String, f64>); 3] {
+        [
+            (UnitKind::Weight, &mut self.weight),
+            (UnitKind::Volume, &mut self.volume),
+            (UnitKind::Count, &mut self.count),
+        ]
+    }
+
+    fn table_mut(&mut self, kind: UnitKind) -> &mut HashMap<String, f64> {
+        match kind {
+            UnitKind::Weight => &mut self.weight,
+            UnitKind::Volume => &mut self.volume,
+            UnitKind::Count => &mut self.count,
+        }
+    }
+}
+
+/// Parse a serving count: "1.5", "2 servings", "1serving", or a word like
+/// "half" or "double". Used for compound foods, which are logged in servings.
+pub fn parse_servings(amount: &str) -> Option<f64> {
+    let amount = amount.trim().to_lowercase();
+    if let Some(n) = serving_word(&amount) {
+        return Some(n);
+    }
+    let number = amount
+        .trim_end_matches("servings")
+        .trim_end_matches("serving")
+        .trim();
+    parse_count(number).filter(|n| *n > 0.0)
+}
+
+/// How a recipe's ingredients divide: into `servings`, and, once the cooked
+/// dish has been weighed, by its total weight.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RecipeYield {
+    pub servings: f64,
+    /// Cooked weight of the whole recipe
+    pub grams: Option<f64>,
+}
+
+impl RecipeYield {
+    pub fn check(&self) -> anyhow::Result<()> {
+        if self.servings <= 0.0 || self.grams.is_some_and(|g| g <= 0.0) {
+            anyhow::bail!("Servings and yield must be positive");
+        }
+        Ok(())
+    }
+
+    /// Servings in `amount`, a count ("1 serving", "half") or a cooked
+    /// weight ("250g"). A weight needs the recipe's yield to be known.
+    pub fn servings_in(&self, amount: &str) -> Option<f64> {
+        match parse_grams(amount) {
+            Some(grams) => Some(grams / self.grams? * self.servings),
+            None => parse_servings(amount),
+        }
+    }
+
+    /// Cooked weight of one serving.
+    pub fn serving_grams(&self) -> Option<f64> {
+        Some(self.grams? / self.servings)
+    }
+}
+
+/// Grams in an amount with an explicit weight unit ("420g", "8 oz"). Bare
+/// numbers and volumes give None.
+pub fn parse_grams(amount: &str) -> Option<f64> {
+    let (value, unit) = parse_quantity(amount)?;
+    if parse_count(amount).is_some() {
+        return None;
+    }
+    match Units::current().grams_per(&unit)? {
+        (grams, UnitKind::Weight) => Some(value * grams),
+        _ => None,
+    }
+}
+
+/// A unit of a food's own from a declaration like "1 cup = 185g" or
+/// "large = 50g": the unit and the grams in one of it.
+pub fn parse_unit_weight(declaration: &str) -> anyhow::Result<(String, f64)> {
+    let invalid = || {
+        anyhow::anyhow!(
+            "Expected a unit and its weight, like \"1 cup = 185g\", not '{}'",
+            declaration
+        )
+    };
+    let (unit, weight) = declaration.split_once('=').ok_or_else(invalid)?;
+    let unit = unit.trim().to_lowercase();
+    let (count, unit) = match parse_quantity(&unit) {
+        Some(_) if parse_count(&unit).is_some() => return Err(invalid()),
+        Some(quantity) => quantity,
+        None if unit.chars().all(char::is_alphabetic) && !unit.is_empty() => (1.0, unit),
+        None => return Err(invalid()),
+    };
+    let grams = parse_grams(weight)
+        .or_else(|| weight.trim().parse().ok())
+        .ok_or_else(invalid)?;
+    if count <= 0.0 || grams <= 0.0 {
+        anyhow::bail!("A unit's amount and weight must be positive");
+    }
+    if let Some((_, UnitKind::Weight)) = Units::current().grams_per(&unit) {
+        anyhow::bail!("'{}' is already a weight and can't be redefined", unit);
+    }
+    Ok((unit, grams / count))
+}
+
+/// Grams in `amount` of a food whose nutrition is given per `serving`, with
+/// its own unit weights `own`, for exports. Volumes count at water density,
+/// as they do for macros; a serving measured in pieces ("1 egg") has no
+/// known weight unless the food gives it one.
+pub fn amount_grams(amount: &str, serving: &str, own: &BTreeMap<String, f64>) -> Option<f64> {
+    if let Some(grams) = parse_grams(amount) {
+        return Some(grams);
+    }
+    let (value, unit) = parse_quantity(serving)?;
+    let per_serving = match own_grams(own, &unit) {
+        Some(grams) => value * grams,
+        None => match Units::current().grams_per(&unit)? {
+            (grams, UnitKind::Weight | UnitKind::Volume) => value * grams,
+            (_, UnitKind::Count) => return None,
+        },
+    };
+    Some(parse_amount_multiplier(amount, serving, own)? * per_serving)
+}
+
+/// Serving counts spelled out as words.
+pub fn serving_word(word: &str) -> Option<f64> {
+    match word {
+        "quarter" => Some(0.25),
+        "third" => Some(1.0 / 3.0),
+        "half" => Some(0.5),
+        "a" | "one" | "whole" => Some(1.0),
+        "double" => Some(2.0),
+        "triple" => Some(3.0),
+        _ => None,
+    }
+}
+
+/// A condition on one nutrient, from expressions like ">20", "<=5",
+/// "10..20", or a bare number.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MacroFilter {
+    /// Column the condition applies to: protein, fat, carbs, calories, fiber,
+    /// sugar, sodium, or saturated_fat
+    pub column: &'static str,
+    pub min: Option<(f64, bool)>,
+    pub max: Option<(f64, bool)>,
+}
+
+impl MacroFilter {
+    pub fn parse(column: &'static str, expr: &str) -> anyhow::Result<Self> {
+        let expr = expr.trim();
+        let num = |s: &str| {
+            s.trim()
+                .parse::<f64>()
+                .map_err(|_| anyhow::anyhow!("Invalid {} filter '{}'", column, expr))
+        };
+        let (min, max) = if let Some(v) = expr.strip_prefix(">=") {
+            (Some((num(v)?, true)), None)
+        } else if let Some(v) = expr.strip_prefix("<=") {
+            (None, Some((num(v)?, true)))
+        } else if let Some(v) = expr.strip_prefix('>') {
+            (Some((num(v)?, false)), None)
+        } else if let Some(v) = expr.strip_prefix('<') {
+            (None, Some((num(v)?, false)))
+        } else if let Some((lo, hi)) = expr.split_once("..") {
+            (Some((num(lo)?, true)), Some((num(hi)?, true)))
+        } else {
+            // Labels round to the nearest gram, so "0" means under half a gram
+            let v = num(expr.strip_prefix('=').unwrap_or(expr))?;
+            (Some((v - 0.5, true)), Some((v + 0.5, false)))
+        };
+        Ok(Self { column, min, max })
+    }
+
+    /// SQL conditions on `value`, with the bound values to pass as parameters
+    /// in order.
+    pub fn to_sql(&self, value: &str) -> (Vec<String>, Vec<f64>) {
+        let mut conditions = Vec::new();
+        let mut params = Vec::new();
+        if let Some((v, inclusive)) = self.min {
+            conditions.push(format!(
+                "{} {} ?",
+                value,
+                if inclusive { ">=" } else { ">" }
+            ));
+            params.push(v);
+        }
+        if let Some((v, inclusive)) = self.max {
+            conditions.push(format!(
+                "{} {} ?",
+                value,
+                if inclusive { "<=" } else { "<" }
+            ));
+            params.push(v);
+        }
+        (conditions, params)
     }
 }
 
+/// Parse a body weight like "82.4", "82.4kg", or "181 lb" into kg. A bare
+/// number is kg.
+pub fn parse_weight_kg(input: &str) -> Option<f64> {
+    let input = input.trim().to_lowercase();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(input.len());
+    let value: f64 = input[..split].trim().parse().ok()?;
+    let kg = match input[split..].trim() {
+        "" | "kg" | "kgs" => value,
+        "lb" | "lbs" | "pound" | "pounds" => value * 0.453_592,
+        _ => return None,
+    };
+    Some(kg)
+}
+
 /// Parse a water amount string and return the value in ml.
 /// Supports: ml (default), oz, cups, liters, gallons.
 /// e.g., "500" -> 500ml, "16oz" -> ~473ml, "2cups" -> 480ml
@@ -186,6 +1070,22 @@ pub fn parse_water_ml(input: &str) -> Option<f64> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_macro_filter() {
+        let f = MacroFilter::parse("protein", ">20").unwrap();
+        assert_eq!(
+            f.to_sql("protein"),
+            (vec!["protein > ?".to_string()], vec![20.0])
+        );
+        let f = MacroFilter::parse("fat", "<= 5").unwrap();
+        assert_eq!(f.max, Some((5.0, true)));
+        let f = MacroFilter::parse("carbs", "10..20").unwrap();
+        assert_eq!((f.min, f.max), (Some((10.0, true)), Some((20.0, true))));
+        let f = MacroFilter::parse("carbs", "0").unwrap();
+        assert_eq!((f.min, f.max), (Some((-0.5, true)), Some((0.5, false))));
+        assert!(MacroFilter::parse("fat", "lots").is_err());
+    }
+
     #[test]
     fn test_parse_quantity() {
         assert_eq!(parse_quantity("100g"), Some((100.0, "g".to_string())));
@@ -200,6 +1100,48 @@ mod tests {
         assert_eq!(parse_quantity("2 packs"), Some((2.0, "packs".to_string())));
     }
 
+    #[test]
+    fn test_parse_servings() {
+        assert_eq!(parse_servings("1.5 servings"), Some(1.5));
+        assert_eq!(parse_servings("1serving"), Some(1.0));
+        assert_eq!(parse_servings("2"), Some(2.0));
+        assert_eq!(parse_servings("Half"), Some(0.5));
+        assert_eq!(parse_servings("200g"), None);
+    }
+
+    #[test]
+    fn test_recipe_yield_servings() {
+        let recipe = RecipeYield {
+            servings: 8.0,
+            grams: Some(2400.0),
+        };
+        assert_eq!(recipe.servings_in("1 serving"), Some(1.0));
+        assert_eq!(recipe.servings_in("half"), Some(0.5));
+        assert_eq!(recipe.servings_in("600g"), Some(2.0));
+        assert_eq!(recipe.serving_grams(), Some(300.0));
+        let unweighed = RecipeYield {
+            grams: None,
+            ..recipe
+        };
+        assert_eq!(unweighed.servings_in("600g"), None);
+        assert!(RecipeYield {
+            servings: 0.0,
+            grams: None
+        }
+        .check()
+        .is_err());
+    }
+
+    #[test]
+    fn test_variant_names() {
+        assert_eq!(variant_name("Oats ", "dry"), "Oats (dry)");
+        assert_eq!(base_name("Oats (dry)"), "Oats");
+        assert_eq!(base_name("Oats (Quaker, cooked)"), "Oats");
+        assert_eq!(base_name("Oats"), "Oats");
+        assert_eq!(base_name("(dry)"), "(dry)");
+        assert_eq!(base_name("Oats ()"), "Oats ()");
+    }
+
     #[test]
     fn test_to_grams() {
         assert_eq!(to_grams(100.0, "g"), Some(100.0));
@@ -210,6 +1152,87 @@ mod tests {
         assert_eq!(to_grams(1.0, "tbsp"), Some(15.0));
         assert_eq!(to_grams(1.0, "tsp"), Some(5.0));
         assert_eq!(to_grams(1.0, "bar"), Some(100.0));
+        assert_eq!(to_grams(2.0, "Patties"), Some(200.0));
+        assert_eq!(to_grams(1.0, "l"), Some(1000.0));
+        assert_eq!(to_grams(3.0, "handful"), Some(3.0));
+    }
+
+    #[test]
+    fn test_serving_defaults() {
+        let mut milk = Food::new("Milk, Whole", 3.2, 3.3, 4.6, 61.0, "100g", vec![]);
+        assert_eq!(milk.assume_serving().as_deref(), Some("milk"));
+        assert_eq!(milk.serving, "100ml");
+        assert!((milk.calories - 62.83).abs() < 0.01);
+        assert_eq!(milk.default_amount.as_deref(), Some("1 cup"));
+        assert!((milk.calculate("1 cup").unwrap().calories - 150.8).abs() < 0.1);
+
+        let mut oil = Food::new("olive oil", 0.0, 100.0, 0.0, 884.0, "100g", vec![]);
+        oil.assume_serving();
+        assert!((oil.calculate("1 tbsp").unwrap().fat - 13.8).abs() < 0.01);
+
+        // Greens keep grams, so gram amounts still log correctly
+        let mut spinach = Food::new("Baby spinach", 2.9, 0.4, 3.6, 23.0, "100g", vec![]);
+        assert_eq!(spinach.assume_serving().as_deref(), Some("leafy greens"));
+        assert_eq!(spinach.serving, "100g");
+        assert_eq!(spinach.default_amount.as_deref(), Some("30g"));
+
+        let mut chocolate = Food::new("Milk chocolate", 7.6, 30.0, 59.0, 535.0, "100g", vec![]);
+        assert_eq!(chocolate.assume_serving(), None);
+        assert_eq!(chocolate.serving, "100g");
+
+        let mut units = Units::builtin();
+        units.merge(
+            Units::parse(
+                "[[serving]]\ncategory = \"chocolate\"\nkeywords = [\"chocolate\"]\ndefault_amount = \"20g\"\n",
+            )
+            .unwrap(),
+        );
+        assert_eq!(
+            units
+                .serving_default("Milk chocolate")
+                .map(|s| s.category.as_str()),
+            Some("chocolate")
+        );
+        assert!(Units::parse(
+            "[[serving]]\ncategory = \"x\"\nkeywords = []\nserving = \"a heaping cup\"\n"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_cooking_ratios() {
+        let mut units = Units::builtin();
+        assert_eq!(units.cooked_ratio("Basmati rice"), Some(2.8));
+        assert_eq!(units.cooked_ratio("Chicken breast"), Some(0.75));
+        assert_eq!(units.cooked_ratio("eggs"), None);
+        units.merge(
+            Units::parse("[[cooking]]\ncategory = \"grains\"\nkeywords = [\"rice\"]\nratio = 3\n")
+                .unwrap(),
+        );
+        assert_eq!(units.cooked_ratio("rice"), Some(3.0));
+        assert_eq!(units.cooked_ratio("quinoa"), None);
+        assert!(Units::parse("[[cooking]]\ncategory = \"x\"\nkeywords = []\nratio = 0\n").is_err());
+    }
+
+    #[test]
+    fn test_unit_overrides() {
+        let mut units = Units::builtin();
+        units.merge(Units::parse("[count]\nknob = 10\n[volume]\ncup = 250\ntbsp = 20\n").unwrap());
+        assert_eq!(units.grams_per("knobs"), Some((10.0, UnitKind::Count)));
+        assert_eq!(units.grams_per("cups"), Some((250.0, UnitKind::Volume)));
+        assert_eq!(
+            units.grams_per("Tablespoon"),
+            Some((15.0, UnitKind::Volume))
+        );
+        assert_eq!(units.grams_per("handful"), None);
+
+        // Redefining a unit under another kind moves it
+        units.merge(Units::parse("[weight]\nscoop = 30\n").unwrap());
+        assert_eq!(units.grams_per("scoop"), Some((30.0, UnitKind::Weight)));
+
+        assert!(Units::parse("[count]\nknob = 0\n").is_err());
+        assert!(Units::parse("[count]\n\"big knob\" = 20\n").is_err());
+        assert!(Units::parse("[sizes]\nknob = 20\n").is_err());
     }
 
     #[test]
@@ -236,6 +1259,93 @@ mod tests {
         assert!((m.calories - 210.0).abs() < 0.01);
     }
 
+    #[test]
+    fn test_calculate_unknown_unit() {
+        // A unit the table doesn't know works against the food's own serving
+        let oatmeal = Food::new("Oatmeal", 4.0, 2.5, 27.0, 150.0, "1 packet", vec![]);
+        assert!((oatmeal.calculate("2 packets").unwrap().calories - 300.0).abs() < 0.01);
+        assert!(UnknownUnit::check("2 packets", "1 packet", &BTreeMap::new()).is_none());
+
+        // ...but isn't taken for grams of anything else
+        let oats = Food::new("Oats", 13.0, 7.0, 68.0, 389.0, "100g", vec![]);
+        assert!(oats.calculate("1 packet").is_none());
+        let err = UnknownUnit::check("1 packet", "100g", &BTreeMap::new())
+            .unwrap()
+            .to_string();
+        assert!(
+            err.starts_with("Unknown unit 'packet'. Did you mean: pack?"),
+            "{}",
+            err
+        );
+        assert!(err.contains("oz, pack, patty"), "{}", err);
+        assert!(UnknownUnit::check("1 cup", "100g", &BTreeMap::new()).is_none());
+        let own = BTreeMap::from([("packet".to_string(), 40.0)]);
+        assert!(UnknownUnit::check("1 packet", "100g", &own).is_none());
+    }
+
+    #[test]
+    fn test_calculate_own_units() {
+        // Rice weighs less than water, so a cup is its own
+        let mut rice = Food::new("Rice", 2.7, 0.3, 28.0, 130.0, "100g", vec![]);
+        let generic = rice.calculate("1 cup").unwrap().calories;
+        rice.units.insert("cup".to_string(), 185.0);
+        assert!((rice.calculate("2 cups").unwrap().calories - 481.0).abs() < 0.01);
+        assert!(rice.calculate("1 cup").unwrap().calories < generic);
+
+        // A unit the table doesn't know, and a serving in one of the food's units
+        let mut eggs = Food::new("Eggs", 6.0, 5.0, 0.5, 72.0, "1 egg", vec![]);
+        assert!(eggs.calculate("1 large").is_none());
+        eggs.units.insert("large".to_string(), 50.0);
+        eggs.units.insert("egg".to_string(), 50.0);
+        assert!((eggs.calculate("1 large").unwrap().calories - 72.0).abs() < 0.01);
+        assert!((eggs.calculate("100g").unwrap().calories - 144.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_parse_unit_weight() {
+        assert_eq!(
+            parse_unit_weight("1 cup = 185g").unwrap(),
+            ("cup".to_string(), 185.0)
+        );
+        assert_eq!(
+            parse_unit_weight("Large=50").unwrap(),
+            ("large".to_string(), 50.0)
+        );
+        assert_eq!(
+            parse_unit_weight("2 slices = 1.5oz").unwrap().1,
+            1.5 * 28.3495 / 2.0
+        );
+        assert!(parse_unit_weight("1 cup").is_err());
+        assert!(parse_unit_weight("2 = 50g").is_err());
+        assert!(parse_unit_weight("1 cup = a lot").is_err());
+        assert!(parse_unit_weight("1 oz = 30g").is_err());
+    }
+
+    #[test]
+    fn test_parse_quantity_fractions_and_words() {
+        let quantity = |s: &str| parse_quantity(s).unwrap();
+        assert_eq!(quantity("1/2 cup"), (0.5, "cup".to_string()));
+        assert_eq!(quantity("1/2cup"), (0.5, "cup".to_string()));
+        assert_eq!(quantity("1 1/2 oz"), (1.5, "oz".to_string()));
+        assert_eq!(quantity("half a cup"), (0.5, "cup".to_string()));
+        assert_eq!(quantity("a quarter of a lb"), (0.25, "lb".to_string()));
+        assert_eq!(quantity("Quarter lb"), (0.25, "lb".to_string()));
+        assert_eq!(quantity("3/4"), (0.75, "g".to_string()));
+        assert!(parse_quantity("1/0 cup").is_none());
+        assert!(parse_quantity("1 1/2 large eggs").is_none());
+
+        assert_eq!(parse_count("1 1/2"), Some(1.5));
+        assert_eq!(parse_count("half"), Some(0.5));
+        assert_eq!(parse_count("half cup"), None);
+
+        // Fractions count servings like bare numbers, and convert like any unit
+        let food = Food::new("Pork Loin", 26.0, 6.5, 0.0, 163.0, "4oz", vec![]);
+        assert!((food.calculate("1/2").unwrap().protein - 13.0).abs() < 0.01);
+        assert!((food.calculate("1 1/2 oz").unwrap().protein - 9.75).abs() < 0.01);
+        assert!((food.calculate("quarter lb").unwrap().protein - 26.0).abs() < 0.01);
+        assert_eq!(parse_servings("1/2 serving"), Some(0.5));
+    }
+
     #[test]
     fn test_calculate_bare_number_with_oz_serving() {
         // "0.5" with serving "4oz" = half a serving
@@ -273,6 +1383,23 @@ mod tests {
         assert!((m.calories - 260.0).abs() < 0.01);
     }
 
+    #[test]
+    fn test_amount_grams() {
+        let grams = |amount: &str, serving: &str| amount_grams(amount, serving, &BTreeMap::new());
+        assert_eq!(grams("8 oz", "1 stick"), Some(226.796));
+        assert_eq!(grams("200", "100g"), Some(200.0));
+        assert_eq!(grams("0.5", "4oz"), Some(0.5 * 4.0 * 28.3495));
+        assert_eq!(grams("2", "1tbsp"), Some(30.0));
+        assert_eq!(grams("1 cup", "100g"), Some(240.0));
+        // Pieces have no known weight
+        assert_eq!(grams("1", "1 stick"), None);
+        assert_eq!(grams("2 slices", "1 slice"), None);
+        // ...unless the food gives them one
+        let own = BTreeMap::from([("slice".to_string(), 25.0)]);
+        assert_eq!(amount_grams("2 slices", "1 slice", &own), Some(50.0));
+        assert_eq!(amount_grams("1 slice", "100g", &own), Some(25.0));
+    }
+
     #[test]
     fn test_parse_water_ml() {
         assert!((parse_water_ml("500").unwrap() - 500.0).abs() < 0.01);
@@ -291,12 +1418,16 @@ mod tests {
             fat: 5.0,
             carbs: 20.0,
             calories: 165.0,
+            fiber: 0.0,
+            ..Default::default()
         };
         let b = Macros {
             protein: 5.0,
             fat: 3.0,
             carbs: 10.0,
             calories: 87.0,
+            fiber: 0.0,
+            ..Default::default()
         };
         a.add(&b);
         assert_eq!(a.protein, 15.0);
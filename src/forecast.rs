@@ -0,0 +1,228 @@
+//! `chomp forecast`: where the weight trend is heading, fitted to recent
+//! weigh-ins and read against recent intake.
+
+use anyhow::Result;
+use chrono::{Duration, NaiveDate};
+use serde::Serialize;
+
+use crate::db::{Database, WeightEntry};
+use crate::program::KCAL_PER_KG;
+
+/// Weeks ahead to project.
+const HORIZONS: [u32; 3] = [4, 8, 12];
+
+/// Fewest weigh-ins, and fewest days between the first and last, that a
+/// trend is fitted to.
+const MIN_WEIGH_INS: usize = 3;
+const MIN_SPAN_DAYS: i64 = 7;
+
+/// Half-width of the band in standard errors, for roughly 95% confidence.
+const BAND: f64 = 2.0;
+
+/// Projected trend weight some weeks out.
+#[derive(Debug, Serialize)]
+pub struct Projection {
+    pub weeks: u32,
+    pub date: String,
+    pub kg: f64,
+    pub low: f64,
+    pub high: f64,
+}
+
+/// The weight trend, what it implies about intake, and where it leads.
+#[derive(Debug, Serialize)]
+pub struct Forecast {
+    /// First and last day of the weigh-ins the trend is fitted to
+    pub from: String,
+    pub to: String,
+    pub weigh_ins: usize,
+    /// Trend weight today
+    pub kg: f64,
+    pub kg_per_week: f64,
+    /// Daily calories under (-) or over (+) maintenance that the trend implies
+    pub daily_balance: f64,
+    /// Average calories per logged day over the same stretch
+    pub average_intake: Option<f64>,
+    /// Intake that would hold weight steady: average intake less the balance
+    pub maintenance: Option<f64>,
+    pub projections: Vec<Projection>,
+}
+
+/// Fit a line to the weigh-ins since `since` and project it `HORIZONS`
+/// weeks past `today`, reading the slope against average logged intake.
+pub fn forecast(db: &Database, since: NaiveDate, today: NaiveDate) -> Result<Forecast> {
+    let weights = db.get_weight_history(&since.to_string())?;
+    let days = db.rollups(&since.to_string(), "day")?;
+    let intake = (!days.is_empty())
+        .then(|| days.iter().map(|d| d.calories).sum::<f64>() / days.len() as f64);
+    project(&weights, intake, today)
+}
+
+/// The forecast for `weights`; see `forecast`.
+pub fn project(weights: &[WeightEntry], intake: Option<f64>, today: NaiveDate) -> Result<Forecast> {
+    let points: Vec<(NaiveDate, f64)> = weights
+        .iter()
+        .filter_map(|w| Some((NaiveDate::parse_from_str(&w.date, "%Y-%m-%d").ok()?, w.kg)))
+        .collect();
+    let (Some(first), Some(last)) = (
+        points.iter().map(|p| p.0).min(),
+        points.iter().map(|p| p.0).max(),
+    ) else {
+        anyhow::bail!("No weigh-ins to forecast from; record some with: chomp weight 80kg");
+    };
+    if points.len() < MIN_WEIGH_INS || (last - first).num_days() < MIN_SPAN_DAYS {
+        anyhow::bail!(
+            "Need at least {} weigh-ins spanning a week to forecast (have {} over {} days)",
+            MIN_WEIGH_INS,
+            points.len(),
+            (last - first).num_days()
+        );
+    }
+
+    // Least squares of kg on days since the first weigh-in
+    let n = points.len() as f64;
+    let x = |date: NaiveDate| (date - first).num_days() as f64;
+    let mean_x = points.iter().map(|p| x(p.0)).sum::<f64>() / n;
+    let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+    let sxx: f64 = points.iter().map(|p| (x(p.0) - mean_x).powi(2)).sum();
+    let sxy: f64 = points
+        .iter()
+        .map(|p| (x(p.0) - mean_x) * (p.1 - mean_y))
+        .sum();
+    let slope = sxy / sxx;
+    let intercept = mean_y - slope * mean_x;
+    let residual = (points
+        .iter()
+        .map(|p| (p.1 - intercept - slope * x(p.0)).powi(2))
+        .sum::<f64>()
+        / (n - 2.0))
+        .sqrt();
+    let at = |date: NaiveDate| {
+        let x0 = x(date);
+        let kg = intercept + slope * x0;
+        let error = residual * (1.0 / n + (x0 - mean_x).powi(2) / sxx).sqrt();
+        (kg, BAND * error)
+    };
+
+    let daily_balance = slope * KCAL_PER_KG;
+    let projections = HORIZONS
+        .iter()
+        .map(|&weeks| {
+            let date = today + Duration::weeks(i64::from(weeks));
+            let (kg, spread) = at(date);
+            Projection {
+                weeks,
+                date: date.to_string(),
+                kg,
+                low: kg - spread,
+                high: kg + spread,
+            }
+        })
+        .collect();
+    Ok(Forecast {
+        from: first.to_string(),
+        to: last.to_string(),
+        weigh_ins: points.len(),
+        kg: at(today).0,
+        kg_per_week: slope * 7.0,
+        daily_balance,
+        average_intake: intake,
+        maintenance: intake.map(|i| i - daily_balance),
+        projections,
+    })
+}
+
+pub fn render(forecast: &Forecast) -> String {
+    let direction = if forecast.daily_balance < 0.0 {
+        "deficit"
+    } else {
+        "surplus"
+    };
+    let mut lines = vec![
+        format!(
+            "Trend from {} weigh-ins, {} to {}: {:.1}kg now, {:+.2}kg/week",
+            forecast.weigh_ins, forecast.from, forecast.to, forecast.kg, forecast.kg_per_week
+        ),
+        match (forecast.average_intake, forecast.maintenance) {
+            (Some(intake), Some(maintenance)) => format!(
+                "Eating {:.0} kcal/day, a {} of about {:.0} kcal/day (maintenance ≈ {:.0})",
+                intake,
+                direction,
+                forecast.daily_balance.abs(),
+                maintenance
+            ),
+            _ => format!(
+                "A {} of about {:.0} kcal/day",
+                direction,
+                forecast.daily_balance.abs()
+            ),
+        },
+        String::new(),
+    ];
+    for p in &forecast.projections {
+        lines.push(format!(
+            "{:>2} weeks  {}  {:.1}kg  ({:.1}–{:.1})",
+            p.weeks, p.date, p.kg, p.low, p.high
+        ));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn weigh_in(date: &str, kg: f64) -> WeightEntry {
+        WeightEntry {
+            id: None,
+            date: date.to_string(),
+            kg,
+        }
+    }
+
+    #[test]
+    fn test_project_trend() {
+        // Losing 0.5kg a week, with a little noise
+        let weights = [
+            weigh_in("2026-09-18", 80.1),
+            weigh_in("2026-09-25", 79.4),
+            weigh_in("2026-10-02", 79.1),
+            weigh_in("2026-10-09", 78.4),
+            weigh_in("2026-10-16", 78.1),
+        ];
+        let forecast = project(&weights, Some(2000.0), date("2026-10-16")).unwrap();
+        assert!((forecast.kg_per_week + 0.5).abs() < 0.01);
+        assert!((forecast.daily_balance + 550.0).abs() < 10.0);
+        assert!((forecast.maintenance.unwrap() - 2550.0).abs() < 10.0);
+
+        let [four, _, twelve] = &forecast.projections[..] else {
+            panic!("three horizons");
+        };
+        assert_eq!(four.date, "2026-11-13");
+        assert!((four.kg - 76.0).abs() < 0.05);
+        assert!(four.low < four.kg && four.kg < four.high);
+        // Further out, less certain
+        assert!(twelve.high - twelve.low > four.high - four.low);
+
+        let text = render(&forecast);
+        assert!(text.contains("a deficit of about 550 kcal/day (maintenance ≈ 2550)"));
+    }
+
+    #[test]
+    fn test_needs_enough_weigh_ins() {
+        let today = date("2026-10-16");
+        assert!(project(&[], None, today).is_err());
+        let two = [weigh_in("2026-10-01", 80.0), weigh_in("2026-10-15", 79.0)];
+        assert!(project(&two, None, today).is_err());
+        let short = [
+            weigh_in("2026-10-13", 80.0),
+            weigh_in("2026-10-14", 79.8),
+            weigh_in("2026-10-15", 79.9),
+        ];
+        assert!(project(&short, None, today).is_err());
+    }
+}
@@ -0,0 +1,89 @@
+//! `chomp goal`: daily macro goals, and how far today's totals are toward them.
+//!
+//! Goals live under `[goals]` in config.toml rather than in the database:
+//! `chomp init` already writes them there, the program, schedule, and
+//! thresholds all build on them, and `chomp settings export` carries them.
+
+use serde::Serialize;
+
+use crate::config::{Config, Goals, Metric};
+use crate::food::Macros;
+use crate::status::Targets;
+
+pub const METRICS: [Metric; 4] = [
+    Metric::Calories,
+    Metric::Protein,
+    Metric::Fat,
+    Metric::Carbs,
+];
+
+/// Today's goals: CHOMP_CALORIE_TARGET and CHOMP_PROTEIN_TARGET win, then
+/// the config's, which already carry any program or schedule for today.
+pub fn resolve(config: &Config) -> Goals {
+    let targets = Targets::resolve(config);
+    Goals {
+        calories: targets.calories,
+        protein: targets.protein,
+        ..config.goals
+    }
+}
+
+/// One metric's total against its goal.
+#[derive(Debug, Serialize)]
+pub struct Progress {
+    pub metric: Metric,
+    pub value: f64,
+    pub goal: f64,
+    pub percent: f64,
+    /// Negative once over the goal
+    pub remaining: f64,
+}
+
+/// Progress for each metric that has a goal, calories first.
+pub fn progress(totals: &Macros, goals: &Goals) -> Vec<Progress> {
+    METRICS
+        .iter()
+        .filter_map(|&metric| {
+            let goal = goals.get(metric).filter(|g| *g > 0.0)?;
+            let value = metric.value(totals);
+            Some(Progress {
+                metric,
+                value,
+                goal,
+                percent: value / goal * 100.0,
+                remaining: goal - value,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress() {
+        let totals = Macros {
+            protein: 120.0,
+            fat: 80.0,
+            carbs: 150.0,
+            calories: 1800.0,
+            fiber: 0.0,
+            ..Default::default()
+        };
+        let goals = Goals {
+            calories: Some(2400.0),
+            protein: Some(160.0),
+            fat: Some(70.0),
+            carbs: None,
+            water: None,
+        };
+        let progress = progress(&totals, &goals);
+        let metrics: Vec<Metric> = progress.iter().map(|p| p.metric).collect();
+        assert_eq!(metrics, [Metric::Calories, Metric::Protein, Metric::Fat]);
+        assert_eq!(progress[0].percent, 75.0);
+        assert_eq!(progress[0].remaining, 600.0);
+        assert_eq!(progress[1].remaining, 40.0);
+        assert_eq!(progress[2].remaining, -10.0);
+    }
+}
@@ -0,0 +1,225 @@
+//! `chomp heatmap`: recent weeks as a calendar, one cell per day, shaded by
+//! calories or by whether the day was on target.
+
+use chrono::{Datelike, Duration, NaiveDate};
+use serde::Serialize;
+
+use crate::adherence::{self, Day};
+use crate::render::{Renderer, Role};
+
+/// Lightest to darkest; level 0 is a day with nothing logged.
+const SHADES: [&str; 5] = ["·", "░", "▒", "▓", "█"];
+
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// One logged day of the heatmap.
+#[derive(Debug, Clone, Serialize)]
+pub struct Cell {
+    pub date: String,
+    pub calories: f64,
+    /// 1 (lightest) to 4 (darkest)
+    pub level: usize,
+    /// Over the calorie goal, or off target when shading by adherence
+    pub over: bool,
+}
+
+/// Shade each day by calories: against the day's own calorie goal when it
+/// has one (under half, under 90%, on target, over), otherwise in quarters
+/// of the busiest day.
+pub fn calorie_cells(days: &[Day]) -> Vec<Cell> {
+    let most = days.iter().map(|d| d.calories).fold(0.0, f64::max);
+    days.iter()
+        .map(|day| {
+            let (level, over) = match day.goals.calories {
+                Some(goal) if goal > 0.0 => {
+                    let ratio = day.calories / goal;
+                    let level = if ratio < 0.5 {
+                        1
+                    } else if ratio < 1.0 - adherence::TOLERANCE {
+                        2
+                    } else if ratio <= 1.0 + adherence::TOLERANCE {
+                        3
+                    } else {
+                        4
+                    };
+                    (level, level == 4)
+                }
+                _ if most > 0.0 => (
+                    ((day.calories / most) * 4.0).ceil().clamp(1.0, 4.0) as usize,
+                    false,
+                ),
+                _ => (1, false),
+            };
+            Cell {
+                date: day.date.clone(),
+                calories: day.calories,
+                level,
+                over,
+            }
+        })
+        .collect()
+}
+
+/// Shade each day by adherence: dark when on target, light when not.
+pub fn adherence_cells(days: &[Day]) -> Vec<Cell> {
+    days.iter()
+        .map(|day| Cell {
+            date: day.date.clone(),
+            calories: day.calories,
+            level: if day.on_target { 4 } else { 1 },
+            over: !day.on_target,
+        })
+        .collect()
+}
+
+/// The first day (a Monday) of a heatmap of `weeks` weeks ending with the
+/// week that holds `today`.
+pub fn start(today: NaiveDate, weeks: u32) -> NaiveDate {
+    let monday = today - Duration::days(i64::from(today.weekday().num_days_from_monday()));
+    monday - Duration::weeks(i64::from(weeks.max(1)) - 1)
+}
+
+/// Weekday rows by week columns from `start(today, weeks)`, month names over
+/// the weeks they begin in, and a legend. Days after today are left blank.
+pub fn render(
+    out: &Renderer,
+    cells: &[Cell],
+    today: NaiveDate,
+    weeks: u32,
+    legend: &str,
+) -> String {
+    let first = start(today, weeks);
+    let weeks = i64::from(weeks.max(1));
+    let by_date: std::collections::HashMap<&str, &Cell> =
+        cells.iter().map(|c| (c.date.as_str(), c)).collect();
+
+    // A week is labeled with the month its Sunday falls in, when that month
+    // starts in the week (or it's the first week)
+    let mut months = String::new();
+    for week in 0..weeks {
+        let sunday = first + Duration::weeks(week) + Duration::days(6);
+        let column = 5 + 2 * week as usize;
+        if (week == 0 || sunday.day() <= 7) && months.chars().count() < column {
+            months.push_str(&" ".repeat(column - months.chars().count()));
+            months.push_str(&sunday.format("%b").to_string());
+        }
+    }
+    let mut lines = vec![months.trim_end().to_string()];
+
+    for (row, name) in WEEKDAYS.iter().enumerate() {
+        let mut line = format!("{} ", name);
+        for week in 0..weeks {
+            let date = first + Duration::weeks(week) + Duration::days(row as i64);
+            if date > today {
+                break;
+            }
+            let cell = match by_date.get(date.to_string().as_str()) {
+                Some(cell) => {
+                    let role = if cell.over {
+                        Role::Over
+                    } else {
+                        Role::Progress
+                    };
+                    out.paint(role, SHADES[cell.level])
+                }
+                None => out.paint(Role::Muted, SHADES[0]),
+            };
+            line.push(' ');
+            line.push_str(&cell);
+        }
+        lines.push(line);
+    }
+    lines.push(String::new());
+    lines.push(legend.to_string());
+    lines.join("\n")
+}
+
+/// What the shades mean for `calorie_cells`.
+pub fn calorie_legend(has_goal: bool) -> String {
+    if has_goal {
+        format!(
+            "{} nothing  {} <50%  {} <90%  {} on target  {} over",
+            SHADES[0], SHADES[1], SHADES[2], SHADES[3], SHADES[4]
+        )
+    } else {
+        format!("{} nothing  less {} more", SHADES[0], SHADES[1..].join(" "))
+    }
+}
+
+/// What the shades mean for `adherence_cells`.
+pub fn adherence_legend() -> String {
+    format!(
+        "{} nothing  {} off target  {} on target",
+        SHADES[0], SHADES[1], SHADES[4]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Goals;
+    use crate::i18n::Locale;
+    use crate::render::Theme;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn day(date: &str, calories: f64, goal: Option<f64>) -> Day {
+        let goals = Goals {
+            calories: goal,
+            ..Default::default()
+        };
+        Day {
+            date: date.to_string(),
+            calories,
+            protein: 0.0,
+            on_target: adherence::judge(calories, 0.0, &goals),
+            goals,
+            scheduled: None,
+            incomplete: false,
+        }
+    }
+
+    #[test]
+    fn test_calorie_levels() {
+        let goal = Some(2000.0);
+        let levels: Vec<(usize, bool)> = calorie_cells(&[
+            day("2026-10-12", 900.0, goal),
+            day("2026-10-13", 1700.0, goal),
+            day("2026-10-14", 2100.0, goal),
+            day("2026-10-15", 2500.0, goal),
+            day("2026-10-16", 1000.0, None),
+        ])
+        .iter()
+        .map(|c| (c.level, c.over))
+        .collect();
+        assert_eq!(
+            levels,
+            [(1, false), (2, false), (3, false), (4, true), (2, false)]
+        );
+    }
+
+    #[test]
+    fn test_render_grid() {
+        let out = Renderer::new(false, Theme::Default, Locale::En);
+        let goal = Some(2000.0);
+        let cells = calorie_cells(&[
+            day("2026-09-28", 2000.0, goal),
+            day("2026-10-03", 2600.0, goal),
+            day("2026-10-14", 900.0, goal),
+        ]);
+        // Thursday: the last week stops after today
+        let today = date("2026-10-15");
+        assert_eq!(start(today, 5), date("2026-09-14"));
+        let text = render(&out, &cells, today, 5, &calorie_legend(true));
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "     Sep Oct");
+        assert_eq!(lines[1], "Mon  · · ▓ · ·");
+        assert_eq!(lines[3], "Wed  · · · · ░");
+        assert_eq!(lines[4], "Thu  · · · · ·");
+        assert_eq!(lines[5], "Fri  · · · ·");
+        assert_eq!(lines[6], "Sat  · · █ ·");
+        assert_eq!(lines[9], calorie_legend(true));
+    }
+}
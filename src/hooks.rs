@@ -0,0 +1,212 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::io::Write;
+use std::thread::JoinHandle;
+
+use crate::config::{Config, Goals, Hooks, Metric, Threshold};
+use crate::db::Database;
+use crate::food::Macros;
+use crate::status::Targets;
+
+/// Where the day stands against one configured threshold.
+#[derive(Debug, Clone, Serialize)]
+pub struct Flag {
+    pub name: String,
+    pub metric: &'static str,
+    pub percent: f64,
+    pub target: f64,
+    pub value: f64,
+    pub crossed: bool,
+}
+
+/// The configured thresholds with their targets resolved.
+pub struct Thresholds {
+    thresholds: Vec<Threshold>,
+    targets: Targets,
+    goals: Goals,
+    hooks: Hooks,
+}
+
+impl Thresholds {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            thresholds: config.thresholds.clone(),
+            targets: Targets::resolve(config),
+            goals: config.goals,
+            hooks: config.hooks.clone(),
+        }
+    }
+
+    /// Thresholds from the config on disk, with the program's current
+    /// calorie target; a broken config means none.
+    pub fn load(db: &Database) -> Self {
+        let mut config = Config::load().unwrap_or_default();
+        if crate::program::apply(&mut config, db).is_err() {
+            config.program = None;
+        }
+        Self::new(&config)
+    }
+
+    /// One flag per threshold whose target is known.
+    pub fn flags(&self, totals: &Macros) -> Vec<Flag> {
+        self.thresholds
+            .iter()
+            .filter_map(|t| {
+                let target = t.target.or(match t.metric {
+                    Metric::Calories => self.targets.calories,
+                    Metric::Protein => self.targets.protein,
+                    Metric::Fat => self.goals.fat,
+                    Metric::Carbs => self.goals.carbs,
+                })?;
+                let value = t.metric.value(totals);
+                Some(Flag {
+                    name: t.name(),
+                    metric: t.metric.name(),
+                    percent: t.percent,
+                    target,
+                    value,
+                    crossed: value >= target * t.percent / 100.0,
+                })
+            })
+            .collect()
+    }
+
+    /// Flags crossed by going from `before` to `after`.
+    pub fn newly_crossed(&self, before: &Macros, after: &Macros) -> Vec<Flag> {
+        let was: Vec<bool> = self.flags(before).iter().map(|f| f.crossed).collect();
+        self.flags(after)
+            .into_iter()
+            .zip(was)
+            .filter(|(flag, was)| flag.crossed && !was)
+            .map(|(flag, _)| flag)
+            .collect()
+    }
+
+    /// Send each flag to the configured hooks on a background thread. Hook
+    /// failures are reported on stderr and never fail the log itself.
+    pub fn fire(&self, flags: Vec<Flag>, totals: &Macros) -> Option<JoinHandle<()>> {
+        if flags.is_empty() || (self.hooks.command.is_none() && self.hooks.webhook.is_none()) {
+            return None;
+        }
+        let hooks = self.hooks.clone();
+        let date = chrono::Local::now().format("%Y-%m-%d").to_string();
+        let events: Vec<serde_json::Value> = flags
+            .into_iter()
+            .map(|flag| {
+                serde_json::json!({
+                    "event": "threshold",
+                    "date": date,
+                    "threshold": flag,
+                    "totals": totals,
+                })
+            })
+            .collect();
+        Some(std::thread::spawn(move || {
+            for event in &events {
+                if let Err(e) = send(&hooks, event) {
+                    eprintln!("Warning: threshold hook failed: {}", e);
+                }
+            }
+        }))
+    }
+}
+
+/// Send an event to the configured hooks and wait for them. Returns
+/// whether any hook is configured.
+pub fn send_now(config: &Config, event: &serde_json::Value) -> Result<bool> {
+    let hooks = &config.hooks;
+    if hooks.command.is_none() && hooks.webhook.is_none() {
+        return Ok(false);
+    }
+    send(hooks, event)?;
+    Ok(true)
+}
+
+fn send(hooks: &Hooks, event: &serde_json::Value) -> Result<()> {
+    if let Some(command) = &hooks.command {
+        let mut cmd = std::process::Command::new("sh");
+        cmd.arg("-c")
+            .arg(command)
+            .env("CHOMP_EVENT", event["event"].as_str().unwrap_or_default());
+        if let Some(name) = event["threshold"]["name"].as_str() {
+            cmd.env("CHOMP_THRESHOLD", name);
+        }
+        let mut child = cmd.stdin(std::process::Stdio::piped()).spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(event.to_string().as_bytes())?;
+        }
+        let status = child.wait()?;
+        if !status.success() {
+            anyhow::bail!("'{}' exited with {}", command, status);
+        }
+    }
+    if let Some(url) = &hooks.webhook {
+        reqwest::blocking::Client::builder()
+            .timeout(std::time::Duration::from_secs(10))
+            .build()?
+            .post(url)
+            .json(event)
+            .send()?
+            .error_for_status()?;
+    }
+    Ok(())
+}
+
+/// Today's totals before a log, to compare against afterwards.
+pub fn before_log(db: &Database) -> Macros {
+    db.get_today_totals().unwrap_or_default()
+}
+
+/// Fire hooks for thresholds the log just crossed.
+pub fn after_log(db: &Database, before: &Macros) -> Option<JoinHandle<()>> {
+    let thresholds = Thresholds::load(db);
+    let after = db.get_today_totals().ok()?;
+    thresholds.fire(thresholds.newly_crossed(before, &after), &after)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn macros(protein: f64, calories: f64) -> Macros {
+        Macros {
+            protein,
+            calories,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_flags_and_crossings() {
+        let config = Config::parse(
+            "[goals]\ncalories = 2000.0\nprotein = 150.0\n\n\
+             [[thresholds]]\nmetric = \"calories\"\npercent = 90\n\n\
+             [[thresholds]]\nmetric = \"protein\"\n\n\
+             [[thresholds]]\nmetric = \"fat\"\n",
+        )
+        .unwrap();
+        let thresholds = Thresholds {
+            thresholds: config.thresholds.clone(),
+            targets: Targets {
+                calories: Some(2000.0),
+                protein: Some(150.0),
+                water: None,
+            },
+            goals: config.goals,
+            hooks: config.hooks.clone(),
+        };
+
+        // Fat has no goal and no target, so it never flags
+        let flags = thresholds.flags(&macros(100.0, 1850.0));
+        assert_eq!(flags.len(), 2);
+        assert!(flags[0].crossed);
+        assert!(!flags[1].crossed);
+
+        let crossed = thresholds.newly_crossed(&macros(100.0, 1700.0), &macros(150.0, 1850.0));
+        let names: Vec<&str> = crossed.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["calories-90", "protein-100"]);
+        assert!(thresholds
+            .newly_crossed(&macros(150.0, 1850.0), &macros(160.0, 1900.0))
+            .is_empty());
+    }
+}
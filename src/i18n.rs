@@ -0,0 +1,296 @@
+use crate::config::Config;
+
+/// Languages with a message catalog. Anything missing from a catalog falls
+/// back to English.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Locale {
+    En,
+    Es,
+    De,
+    Fr,
+}
+
+impl Locale {
+    /// Parse a tag like "de", "es-MX", or "fr_FR.UTF-8".
+    pub fn parse(tag: &str) -> Option<Self> {
+        let lang = tag
+            .split(['_', '-', '.', '@'])
+            .next()
+            .unwrap_or("")
+            .to_lowercase();
+        match lang.as_str() {
+            "en" | "c" | "posix" => Some(Locale::En),
+            "es" => Some(Locale::Es),
+            "de" => Some(Locale::De),
+            "fr" => Some(Locale::Fr),
+            _ => None,
+        }
+    }
+
+    /// CHOMP_LOCALE, then `locale` in the config, then the usual POSIX
+    /// variables. Unsupported languages get English.
+    pub fn detect(config: &Config) -> Self {
+        let from_env = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        from_env("CHOMP_LOCALE")
+            .or_else(|| config.locale.clone())
+            .or_else(|| from_env("LC_ALL"))
+            .or_else(|| from_env("LC_MESSAGES"))
+            .or_else(|| from_env("LANG"))
+            .and_then(|tag| Self::parse(&tag))
+            .unwrap_or(Locale::En)
+    }
+
+    fn decimal_separator(self) -> char {
+        match self {
+            Locale::En => '.',
+            Locale::Es | Locale::De | Locale::Fr => ',',
+        }
+    }
+
+    /// Format a number with the locale's decimal separator.
+    pub fn number(self, value: f64, decimals: usize) -> String {
+        let text = format!("{:.*}", decimals, value);
+        match self.decimal_separator() {
+            '.' => text,
+            sep => text.replace('.', &sep.to_string()),
+        }
+    }
+
+    /// Look up a message template by key.
+    pub fn message(self, key: &str) -> &'static str {
+        let localized = match self {
+            Locale::En => None,
+            Locale::Es => es(key),
+            Locale::De => de(key),
+            Locale::Fr => fr(key),
+        };
+        localized.or_else(|| en(key)).unwrap_or("")
+    }
+
+    /// Fill a message template's `{name}` placeholders in a single pass, so
+    /// braces inside substituted values (e.g. food names) are left alone.
+    pub fn format(self, key: &str, args: &[(&str, &str)]) -> String {
+        let mut out = String::new();
+        let mut rest = self.message(key);
+        while let Some(start) = rest.find('{') {
+            out.push_str(&rest[..start]);
+            let after = &rest[start + 1..];
+            match after.find('}') {
+                Some(end) => {
+                    let name = &after[..end];
+                    match args.iter().find(|(n, _)| *n == name) {
+                        Some((_, value)) => out.push_str(value),
+                        None => out.push_str(&rest[start..start + end + 2]),
+                    }
+                    rest = &after[end + 1..];
+                }
+                None => {
+                    out.push_str(&rest[start..]);
+                    rest = "";
+                }
+            }
+        }
+        out.push_str(rest);
+        out
+    }
+}
+
+fn en(key: &str) -> Option<&'static str> {
+    Some(match key {
+        "macros" => "{protein}p/{fat}f/{carbs}c",
+        "logged" => "Logged: {amount} {food} — {macros}",
+        "auto-logged" => "Auto-logged: {amount} {food} — {macros}",
+        "auto-logged-tag" => "(auto-logged)",
+        "deleted-log-entry" => "Deleted log entry: {amount} {food} — {macros}",
+        "deleted-last-log-entry" => "Deleted last log entry: {amount} {food} — {macros}",
+        "updated-log-entry" => "Updated log entry: {amount} {food} — {macros}",
+        "food-added" => "Added: {food} ({macros} per {serving})",
+        "food-updated" => "Updated: {food} ({macros} per {serving})",
+        "food-deleted" => "Deleted: {food}",
+        "food-line" => "{food}: {macros} per {serving}",
+        "today" => "Today: {protein}p / {fat}f / {carbs}c — {kcal}",
+        "today-extras" => "{water}ml water / {caffeine}mg caffeine",
+        "water-today" => "Water today: {ml}ml ({oz} oz)",
+        "water-logged" => "Logged: {ml}ml water ({oz} oz)",
+        "caffeine-logged" => "Logged: {mg}mg caffeine{source}",
+        "goal-calories" => "Calories",
+        "goal-protein" => "Protein",
+        "goal-fat" => "Fat",
+        "goal-carbs" => "Carbs",
+        "goal-left" => "{amount} left",
+        "goal-over" => "{amount} over",
+        "micro-sugar" => "{amount}g sugar",
+        "micro-saturated-fat" => "{amount}g saturated fat",
+        "micro-sodium" => "{amount}mg sodium",
+        "meal-other" => "Other",
+        "stats" => "Foods: {foods}\nLog entries: {logs}\nFirst entry: {first}\nLast entry: {last}",
+        _ => return None,
+    })
+}
+
+fn es(key: &str) -> Option<&'static str> {
+    Some(match key {
+        "macros" => "{protein}P/{fat}G/{carbs}C",
+        "logged" => "Registrado: {amount} {food} — {macros}",
+        "auto-logged" => "Registrado automáticamente: {amount} {food} — {macros}",
+        "auto-logged-tag" => "(registrado automáticamente)",
+        "deleted-log-entry" => "Entrada eliminada: {amount} {food} — {macros}",
+        "deleted-last-log-entry" => "Última entrada eliminada: {amount} {food} — {macros}",
+        "updated-log-entry" => "Entrada actualizada: {amount} {food} — {macros}",
+        "food-added" => "Añadido: {food} ({macros} por {serving})",
+        "food-updated" => "Actualizado: {food} ({macros} por {serving})",
+        "food-deleted" => "Eliminado: {food}",
+        "food-line" => "{food}: {macros} por {serving}",
+        "today" => "Hoy: {protein}P / {fat}G / {carbs}C — {kcal}",
+        "today-extras" => "{water}ml de agua / {caffeine}mg de cafeína",
+        "water-today" => "Agua hoy: {ml}ml ({oz} oz)",
+        "water-logged" => "Registrado: {ml}ml de agua ({oz} oz)",
+        "caffeine-logged" => "Registrado: {mg}mg de cafeína{source}",
+        "goal-calories" => "Calorías",
+        "goal-protein" => "Proteínas",
+        "goal-fat" => "Grasas",
+        "goal-carbs" => "Carbohidratos",
+        "goal-left" => "quedan {amount}",
+        "goal-over" => "{amount} de más",
+        "micro-sugar" => "{amount}g de azúcar",
+        "micro-saturated-fat" => "{amount}g de grasas saturadas",
+        "micro-sodium" => "{amount}mg de sodio",
+        "meal-other" => "Otros",
+        "stats" => {
+            "Alimentos: {foods}\nEntradas: {logs}\nPrimera entrada: {first}\nÚltima entrada: {last}"
+        }
+        _ => return None,
+    })
+}
+
+fn de(key: &str) -> Option<&'static str> {
+    Some(match key {
+        "macros" => "{protein}E/{fat}F/{carbs}K",
+        "logged" => "Erfasst: {amount} {food} — {macros}",
+        "auto-logged" => "Automatisch erfasst: {amount} {food} — {macros}",
+        "auto-logged-tag" => "(automatisch erfasst)",
+        "deleted-log-entry" => "Eintrag gelöscht: {amount} {food} — {macros}",
+        "deleted-last-log-entry" => "Letzten Eintrag gelöscht: {amount} {food} — {macros}",
+        "updated-log-entry" => "Eintrag geändert: {amount} {food} — {macros}",
+        "food-added" => "Hinzugefügt: {food} ({macros} pro {serving})",
+        "food-updated" => "Geändert: {food} ({macros} pro {serving})",
+        "food-deleted" => "Gelöscht: {food}",
+        "food-line" => "{food}: {macros} pro {serving}",
+        "today" => "Heute: {protein}E / {fat}F / {carbs}K — {kcal}",
+        "today-extras" => "{water}ml Wasser / {caffeine}mg Koffein",
+        "water-today" => "Wasser heute: {ml}ml ({oz} oz)",
+        "water-logged" => "Erfasst: {ml}ml Wasser ({oz} oz)",
+        "caffeine-logged" => "Erfasst: {mg}mg Koffein{source}",
+        "goal-calories" => "Kalorien",
+        "goal-protein" => "Eiweiß",
+        "goal-fat" => "Fett",
+        "goal-carbs" => "Kohlenhydrate",
+        "goal-left" => "noch {amount}",
+        "goal-over" => "{amount} drüber",
+        "micro-sugar" => "{amount}g Zucker",
+        "micro-saturated-fat" => "{amount}g gesättigte Fettsäuren",
+        "micro-sodium" => "{amount}mg Natrium",
+        "meal-other" => "Sonstiges",
+        "stats" => "Lebensmittel: {foods}\nEinträge: {logs}\nErster Eintrag: {first}\nLetzter Eintrag: {last}",
+        _ => return None,
+    })
+}
+
+fn fr(key: &str) -> Option<&'static str> {
+    Some(match key {
+        "macros" => "{protein}P/{fat}L/{carbs}G",
+        "logged" => "Enregistré : {amount} {food} — {macros}",
+        "auto-logged" => "Enregistré automatiquement : {amount} {food} — {macros}",
+        "auto-logged-tag" => "(enregistré automatiquement)",
+        "deleted-log-entry" => "Entrée supprimée : {amount} {food} — {macros}",
+        "deleted-last-log-entry" => "Dernière entrée supprimée : {amount} {food} — {macros}",
+        "updated-log-entry" => "Entrée modifiée : {amount} {food} — {macros}",
+        "food-added" => "Ajouté : {food} ({macros} pour {serving})",
+        "food-updated" => "Modifié : {food} ({macros} pour {serving})",
+        "food-deleted" => "Supprimé : {food}",
+        "food-line" => "{food} : {macros} pour {serving}",
+        "today" => "Aujourd'hui : {protein}P / {fat}L / {carbs}G — {kcal}",
+        "today-extras" => "{water}ml d'eau / {caffeine}mg de caféine",
+        "water-today" => "Eau aujourd'hui : {ml}ml ({oz} oz)",
+        "water-logged" => "Enregistré : {ml}ml d'eau ({oz} oz)",
+        "caffeine-logged" => "Enregistré : {mg}mg de caféine{source}",
+        "goal-calories" => "Calories",
+        "goal-protein" => "Protéines",
+        "goal-fat" => "Lipides",
+        "goal-carbs" => "Glucides",
+        "goal-left" => "reste {amount}",
+        "goal-over" => "{amount} de trop",
+        "micro-sugar" => "{amount}g de sucres",
+        "micro-saturated-fat" => "{amount}g d'acides gras saturés",
+        "micro-sodium" => "{amount}mg de sodium",
+        "meal-other" => "Autres",
+        "stats" => "Aliments : {foods}\nEntrées : {logs}\nPremière entrée : {first}\nDernière entrée : {last}",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_locale_tags() {
+        assert_eq!(Locale::parse("de_DE.UTF-8"), Some(Locale::De));
+        assert_eq!(Locale::parse("es-MX"), Some(Locale::Es));
+        assert_eq!(Locale::parse("C"), Some(Locale::En));
+        assert_eq!(Locale::parse("ja_JP"), None);
+    }
+
+    #[test]
+    fn test_format_and_numbers() {
+        assert_eq!(
+            Locale::De.format("food-deleted", &[("food", "Haferflocken")]),
+            "Gelöscht: Haferflocken"
+        );
+        assert_eq!(
+            Locale::En.format("food-deleted", &[("food", "{macros} bar")]),
+            "Deleted: {macros} bar"
+        );
+        assert_eq!(Locale::Fr.number(16.9, 1), "16,9");
+        assert_eq!(Locale::En.number(16.9, 1), "16.9");
+    }
+
+    #[test]
+    fn test_catalogs_cover_every_english_key() {
+        let keys = [
+            "macros",
+            "logged",
+            "auto-logged",
+            "auto-logged-tag",
+            "deleted-log-entry",
+            "deleted-last-log-entry",
+            "updated-log-entry",
+            "food-added",
+            "food-updated",
+            "food-deleted",
+            "food-line",
+            "today",
+            "today-extras",
+            "water-today",
+            "water-logged",
+            "caffeine-logged",
+            "goal-calories",
+            "goal-protein",
+            "goal-fat",
+            "goal-carbs",
+            "goal-left",
+            "goal-over",
+            "micro-sugar",
+            "micro-saturated-fat",
+            "micro-sodium",
+            "meal-other",
+            "stats",
+        ];
+        for key in keys {
+            assert!(en(key).is_some(), "en missing {}", key);
+            assert!(es(key).is_some(), "es missing {}", key);
+            assert!(de(key).is_some(), "de missing {}", key);
+            assert!(fr(key).is_some(), "fr missing {}", key);
+        }
+    }
+}
@@ -0,0 +1,266 @@
+use anyhow::Result;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+use crate::config::Config;
+use crate::db::Database;
+
+/// Activity multipliers for TDEE, from sedentary to very active.
+const ACTIVITY_LEVELS: [(&str, f64); 5] = [
+    ("sedentary (desk job, little exercise)", 1.2),
+    ("light (exercise 1-3 days/week)", 1.375),
+    ("moderate (exercise 3-5 days/week)", 1.55),
+    ("active (exercise 6-7 days/week)", 1.725),
+    ("very active (physical job or twice-daily training)", 1.9),
+];
+
+/// Body stats used to estimate daily energy expenditure.
+pub struct BodyStats {
+    pub male: bool,
+    pub age: f64,
+    pub weight_kg: f64,
+    pub height_cm: f64,
+    pub activity: f64,
+}
+
+/// Total daily energy expenditure via Mifflin-St Jeor BMR × activity.
+pub fn tdee(stats: &BodyStats) -> f64 {
+    let sex = if stats.male { 5.0 } else { -161.0 };
+    let bmr = 10.0 * stats.weight_kg + 6.25 * stats.height_cm - 5.0 * stats.age + sex;
+    bmr * stats.activity
+}
+
+/// Line-based prompts; generic over input/output so the wizard is testable.
+struct Prompter<R, W> {
+    input: R,
+    output: W,
+    assume_defaults: bool,
+}
+
+impl<R: BufRead, W: Write> Prompter<R, W> {
+    /// Ask a question; an empty answer (or `--yes`) returns None.
+    fn ask(&mut self, question: &str) -> Result<Option<String>> {
+        if self.assume_defaults {
+            return Ok(None);
+        }
+        write!(self.output, "{} ", question)?;
+        self.output.flush()?;
+        let mut line = String::new();
+        self.input.read_line(&mut line)?;
+        let answer = line.trim();
+        Ok((!answer.is_empty()).then(|| answer.to_string()))
+    }
+
+    fn ask_number(&mut self, question: &str) -> Result<Option<f64>> {
+        loop {
+            match self.ask(question)? {
+                None => return Ok(None),
+                Some(answer) => match answer.parse::<f64>() {
+                    Ok(n) if n > 0.0 => return Ok(Some(n)),
+                    _ => writeln!(self.output, "  Please enter a positive number.")?,
+                },
+            }
+        }
+    }
+
+    fn confirm(&mut self, question: &str, default: bool) -> Result<bool> {
+        let hint = if default { "[Y/n]" } else { "[y/N]" };
+        Ok(match self.ask(&format!("{} {}", question, hint))? {
+            None => default,
+            Some(answer) => answer.to_lowercase().starts_with('y'),
+        })
+    }
+
+    fn say(&mut self, text: &str) -> Result<()> {
+        writeln!(self.output, "{}", text)?;
+        Ok(())
+    }
+}
+
+/// Interactive first-run setup: config, goals, foods, and completions.
+pub fn run(db: &Database, assume_defaults: bool) -> Result<()> {
+    let stdin = std::io::stdin();
+    let mut p = Prompter {
+        input: stdin.lock(),
+        output: std::io::stdout(),
+        assume_defaults,
+    };
+
+    let mut config = Config::load()?;
+    p.say("Welcome to chomp! Press Enter to skip any question.\n")?;
+
+    ask_goals(&mut p, &mut config)?;
+    let path = config.save()?;
+    p.say(&format!("Saved config to {}\n", path.display()))?;
+
+    let food_count = db.get_stats()?.food_count;
+    if food_count > 0 {
+        p.say(&format!("Your database already has {} foods.", food_count))?;
+    } else if p.confirm(
+        "Load the starter pack of ~200 common foods so you can log right away?",
+        true,
+    )? {
+        let count = db.import_starter()?;
+        p.say(&format!("Imported {} starter foods.", count))?;
+    } else if p.confirm(
+        "Import the USDA SR Legacy database (~7,800 foods, needs a download)?",
+        false,
+    )? {
+        db.import_usda()?;
+    } else {
+        p.say("Add foods with: chomp add \"oats\" --protein 13 --fat 7 --carbs 68")?;
+    }
+
+    if let Some(shell) = clap_complete::Shell::from_env() {
+        if p.confirm(&format!("\nInstall {} completions?", shell), true)? {
+            match install_completions(shell) {
+                Ok((path, hint)) => {
+                    p.say(&format!("Wrote completions to {}", path.display()))?;
+                    if let Some(hint) = hint {
+                        p.say(hint)?;
+                    }
+                }
+                Err(e) => p.say(&format!("Could not install completions: {}", e))?,
+            }
+        }
+    }
+
+    p.say("\nAll set. Log your first food with: chomp <food> <amount>")?;
+    Ok(())
+}
+
+fn ask_goals<R: BufRead, W: Write>(p: &mut Prompter<R, W>, config: &mut Config) -> Result<()> {
+    p.say("Daily goals")?;
+    let mut calories = p.ask_number("  Calorie target (blank to estimate from your TDEE):")?;
+    let mut weight_kg = None;
+
+    if calories.is_none() && !p.assume_defaults {
+        let male = p
+            .ask("  Sex for the BMR formula (m/f):")?
+            .map(|s| s.to_lowercase().starts_with('m'));
+        let age = p.ask_number("  Age:")?;
+        weight_kg = p.ask_number("  Weight (kg):")?;
+        let height_cm = p.ask_number("  Height (cm):")?;
+        for (i, (label, _)) in ACTIVITY_LEVELS.iter().enumerate() {
+            p.say(&format!("    {}. {}", i + 1, label))?;
+        }
+        let activity = p
+            .ask_number("  Activity level (1-5):")?
+            .and_then(|n| ACTIVITY_LEVELS.get((n as usize).saturating_sub(1)))
+            .map(|(_, factor)| *factor);
+
+        if let (Some(male), Some(age), Some(weight_kg), Some(height_cm), Some(activity)) =
+            (male, age, weight_kg, height_cm, activity)
+        {
+            let maintenance = tdee(&BodyStats {
+                male,
+                age,
+                weight_kg,
+                height_cm,
+                activity,
+            });
+            p.say(&format!(
+                "  Estimated maintenance: {:.0} kcal/day",
+                maintenance
+            ))?;
+            let adjust = match p.ask("  Goal: (l)ose, (m)aintain, or (g)ain? [m]")? {
+                Some(a) if a.starts_with('l') => -500.0,
+                Some(a) if a.starts_with('g') => 300.0,
+                _ => 0.0,
+            };
+            calories = Some((maintenance + adjust).round());
+        }
+    }
+
+    // Default protein to 1.6 g/kg when we know body weight
+    let suggested = weight_kg.map(|w| (w * 1.6).round());
+    let question = match suggested {
+        Some(s) => format!("  Protein target in grams [{:.0}]:", s),
+        None => "  Protein target in grams:".to_string(),
+    };
+    let protein = p.ask_number(&question)?.or(suggested);
+
+    if calories.is_some() {
+        config.goals.calories = calories;
+    }
+    if protein.is_some() {
+        config.goals.protein = protein;
+    }
+    if let (Some(c), Some(pr)) = (config.goals.calories, config.goals.protein) {
+        p.say(&format!("  Goals: {:.0} kcal, {:.0}g protein", c, pr))?;
+    }
+    Ok(())
+}
+
+/// Write the completion script where the shell picks it up automatically.
+/// Returns the path and, when needed, a line to add to the shell rc file.
+fn install_completions(shell: clap_complete::Shell) -> Result<(PathBuf, Option<&'static str>)> {
+    use clap_complete::Shell;
+
+    let home = dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?;
+    let data = dirs::data_dir().unwrap_or_else(|| home.join(".local/share"));
+    let (path, hint) = match shell {
+        Shell::Bash => (data.join("bash-completion/completions/chomp"), None),
+        Shell::Fish => (home.join(".config/fish/completions/chomp.fish"), None),
+        Shell::Zsh => (
+            home.join(".zfunc/_chomp"),
+            Some("Add to ~/.zshrc: fpath=(~/.zfunc $fpath); autoload -U compinit && compinit"),
+        ),
+        other => anyhow::bail!("run `chomp completions {}` and install it manually", other),
+    };
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut file = std::fs::File::create(&path)?;
+    crate::write_completions(shell, &mut file);
+    Ok((path, hint))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tdee() {
+        let stats = BodyStats {
+            male: true,
+            age: 30.0,
+            weight_kg: 80.0,
+            height_cm: 180.0,
+            activity: 1.55,
+        };
+        // BMR = 800 + 1125 - 150 + 5 = 1780
+        assert!((tdee(&stats) - 1780.0 * 1.55).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_goals_from_body_stats() {
+        let answers = "\nm\n30\n80\n180\n3\nl\n\n";
+        let mut p = Prompter {
+            input: answers.as_bytes(),
+            output: Vec::new(),
+            assume_defaults: false,
+        };
+        let mut config = Config::default();
+        ask_goals(&mut p, &mut config).unwrap();
+        assert_eq!(
+            config.goals.calories,
+            Some((1780.0_f64 * 1.55 - 500.0).round())
+        );
+        assert_eq!(config.goals.protein, Some(128.0));
+    }
+
+    #[test]
+    fn test_defaults_leave_goals_unset() {
+        let mut p = Prompter {
+            input: "".as_bytes(),
+            output: Vec::new(),
+            assume_defaults: true,
+        };
+        let mut config = Config::default();
+        ask_goals(&mut p, &mut config).unwrap();
+        assert!(config.goals.calories.is_none());
+        assert!(!p.confirm("?", false).unwrap());
+    }
+}
@@ -0,0 +1,232 @@
+use anyhow::Result;
+
+use crate::food::{CalorieFactors, CarbMode};
+
+/// Values read from a pasted Nutrition Facts panel, per serving.
+#[derive(Debug, Default, PartialEq)]
+pub struct Label {
+    /// Serving size as printed, e.g. "2/3 cup (55g)"
+    pub serving_size: Option<String>,
+    /// Serving weight in grams (or ml), when the label gives one
+    pub serving_grams: Option<f64>,
+    pub calories: Option<f64>,
+    pub fat: f64,
+    pub carbs: f64,
+    pub protein: f64,
+}
+
+impl Label {
+    /// Parse a label pasted as text. Lines may be run together, as they are
+    /// when copied from a web page.
+    pub fn parse(text: &str) -> Result<Self> {
+        let text = text.to_lowercase();
+
+        let fat = value_after(&text, &["total fat", "fat"]);
+        let carbs = value_after(
+            &text,
+            &["total carbohydrate", "total carbs", "carbohydrate", "carbs"],
+        );
+        let protein = value_after(&text, &["protein"]);
+        let missing: Vec<&str> = [("fat", fat), ("carbs", carbs), ("protein", protein)]
+            .iter()
+            .filter(|(_, v)| v.is_none())
+            .map(|(name, _)| *name)
+            .collect();
+        if !missing.is_empty() {
+            anyhow::bail!("Could not find {} in the label", missing.join(", "));
+        }
+
+        // The serving size runs to the end of its line, or to the next
+        // field when the label was pasted as one line
+        let serving_size = text.find("serving size").map(|i| {
+            let line = text[i + "serving size".len()..]
+                .lines()
+                .next()
+                .unwrap_or("");
+            let end = [
+                "amount per",
+                "servings per",
+                "calories",
+                "energy",
+                "total",
+                "fat",
+            ]
+            .iter()
+            .filter_map(|field| line.find(field))
+            .min()
+            .unwrap_or(line.len());
+            line[..end]
+                .trim_start_matches([':', ' '])
+                .trim()
+                .to_string()
+        });
+        // European labels give values per 100g instead of a serving
+        let per_100g = text.contains("per 100 g") || text.contains("per 100g");
+        let serving_grams = serving_size
+            .as_deref()
+            .and_then(grams_in)
+            .or(per_100g.then_some(100.0));
+
+        Ok(Self {
+            serving_grams,
+            serving_size: serving_size.filter(|s| !s.is_empty()),
+            calories: kcal(&text).or_else(|| value_after(&text, &["calories"])),
+            fat: fat.unwrap_or_default(),
+            carbs: carbs.unwrap_or_default(),
+            protein: protein.unwrap_or_default(),
+        })
+    }
+
+    /// Serving to store with the food: the weight when known ("55g") so gram
+    /// amounts convert, otherwise the printed size.
+    pub fn serving(&self) -> String {
+        match (self.serving_grams, &self.serving_size) {
+            (Some(g), _) => format!("{}g", g),
+            (None, Some(size)) => size.clone(),
+            (None, None) => "1 serving".to_string(),
+        }
+    }
+
+    pub fn calories(&self) -> f64 {
+        self.calories.unwrap_or_else(|| {
+            CalorieFactors::default().calories_from_macros(
+                self.protein,
+                self.fat,
+                self.carbs,
+                0.0,
+                CarbMode::Total,
+            )
+        })
+    }
+
+    /// (protein, fat, carbs, calories) per 100g, when the serving weight is known.
+    pub fn per_100g(&self) -> Option<(f64, f64, f64, f64)> {
+        let factor = 100.0 / self.serving_grams.filter(|g| *g > 0.0)?;
+        Some((
+            self.protein * factor,
+            self.fat * factor,
+            self.carbs * factor,
+            self.calories() * factor,
+        ))
+    }
+}
+
+/// The first number after any of `keys`, skipping sub-lines such as
+/// "Saturated Fat" and "Calories from Fat".
+fn value_after(text: &str, keys: &[&str]) -> Option<f64> {
+    for key in keys {
+        for (i, _) in text.match_indices(key) {
+            let before = text[..i].split_whitespace().last().unwrap_or("");
+            if matches!(
+                before,
+                "saturated" | "trans" | "from" | "polyunsaturated" | "monounsaturated"
+            ) {
+                continue;
+            }
+            if let Some(value) = number_at(&text[i + key.len()..]) {
+                return Some(value);
+            }
+        }
+    }
+    None
+}
+
+/// Energy in kcal from European-style labels ("1046 kJ / 250 kcal").
+fn kcal(text: &str) -> Option<f64> {
+    let i = text.find("kcal")?;
+    let number: String = text[..i]
+        .trim_end()
+        .chars()
+        .rev()
+        .take_while(|c| c.is_ascii_digit() || *c == '.' || *c == ',')
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+        .collect();
+    number.replace(',', ".").parse().ok()
+}
+
+/// A number at the start of `s` after separators. "<1g" reads as 0.5, the
+/// middle of what the label allows.
+fn number_at(s: &str) -> Option<f64> {
+    let s = s.trim_start_matches([' ', ':', '\t']);
+    let (less_than, s) = match s.strip_prefix('<') {
+        Some(rest) => (true, rest.trim_start()),
+        None => (false, s),
+    };
+    let end = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == ','))
+        .unwrap_or(s.len());
+    let value: f64 = s[..end].replace(',', ".").parse().ok()?;
+    Some(if less_than { value / 2.0 } else { value })
+}
+
+/// A weight like "55g" or "(240 ml)" inside a serving size.
+fn grams_in(serving: &str) -> Option<f64> {
+    let mut rest = serving;
+    while let Some(start) = rest.find(|c: char| c.is_ascii_digit()) {
+        let tail = &rest[start..];
+        let end = tail
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(tail.len());
+        let unit = tail[end..].trim_start();
+        let is_unit = |u: &str| {
+            unit.strip_prefix(u)
+                .is_some_and(|after| !after.starts_with(|c: char| c.is_alphabetic()))
+        };
+        if is_unit("g") || is_unit("ml") {
+            return tail[..end].parse().ok();
+        }
+        rest = &tail[end..];
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_us_label() {
+        let label = Label::parse(
+            "Nutrition Facts\n8 servings per container\nServing size 2/3 cup (55g)\n\
+             Amount per serving\nCalories 230\nTotal Fat 8g 10%\nSaturated Fat 1g 5%\n\
+             Trans Fat 0g\nCholesterol 0mg\nSodium 160mg 7%\nTotal Carbohydrate 37g 13%\n\
+             Dietary Fiber 4g\nTotal Sugars 12g\nProtein 3g",
+        )
+        .unwrap();
+        assert_eq!(label.serving_size.as_deref(), Some("2/3 cup (55g)"));
+        assert_eq!(label.serving(), "55g");
+        assert_eq!(label.calories, Some(230.0));
+        assert_eq!((label.fat, label.carbs, label.protein), (8.0, 37.0, 3.0));
+        let (p, _, _, kcal) = label.per_100g().unwrap();
+        assert!((p - 3.0 / 0.55).abs() < 0.01);
+        assert!((kcal - 230.0 / 0.55).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_parse_run_together_and_european() {
+        let label = Label::parse(
+            "Serving Size: 1 bar Calories from Fat 70 Calories 190 Fat 7g Carbs 22g Protein <1g",
+        )
+        .unwrap();
+        assert_eq!(label.calories, Some(190.0));
+        assert_eq!(label.protein, 0.5);
+        assert_eq!(label.serving(), "1 bar");
+
+        let label = Label::parse(
+            "Per 100 g\nEnergy 1046 kJ / 250 kcal\nFat 3,5 g\nCarbohydrate 40 g\nProtein 12 g",
+        )
+        .unwrap();
+        assert_eq!(label.calories, Some(250.0));
+        assert_eq!(label.fat, 3.5);
+        assert!(label.serving_size.is_none());
+        assert_eq!(label.serving(), "100g");
+    }
+
+    #[test]
+    fn test_missing_macros_is_an_error() {
+        let err = Label::parse("Calories 100\nProtein 5g").unwrap_err();
+        assert!(err.to_string().contains("fat, carbs"));
+    }
+}
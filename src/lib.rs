@@ -1,3 +1,4 @@
 pub mod db;
 pub mod food;
 pub mod logging;
+pub mod suggest;
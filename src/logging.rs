@@ -1,20 +1,104 @@
 use anyhow::{anyhow, Result};
 
-use crate::db::{Database, LogEntry};
-
-/// Parse input like "ribeye 8oz" or "bare bar" and log it.
-/// Optional date parameter allows backdating entries (format: YYYY-MM-DD).
-pub fn parse_and_log(db: &Database, input: &str, date: Option<&str>) -> Result<LogEntry> {
-    let (food_name, amount) = parse_input(input);
-
-    // Look up the food
-    let food = db.get_food_by_name(&food_name)?.ok_or_else(|| {
-        anyhow!(
-            "Food not found: '{}'. Add it with: chomp add \"{}\" --protein X --fat Y --carbs Z",
-            food_name,
-            food_name
+use crate::db::{Database, LogEntry, LogOptions};
+use crate::food::{
+    parse_count, parse_grams, parse_number, serving_word, split_count, variant_name, Food, Units,
+    UnknownUnit,
+};
+
+/// Minutes within which an identical food+amount is treated as a double
+/// submission, from CHOMP_DEDUPE_WINDOW (default 5, 0 disables).
+pub fn default_dedupe_window() -> u32 {
+    std::env::var("CHOMP_DEDUPE_WINDOW")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(5)
+}
+
+/// How many close names a failed lookup offers.
+const SUGGESTIONS: usize = 3;
+
+/// Returned (via anyhow) when no food matches the name being logged.
+#[derive(Debug)]
+pub struct FoodNotFound {
+    pub name: String,
+    /// Foods with similar names, closest first
+    pub suggestions: Vec<String>,
+}
+
+impl FoodNotFound {
+    /// The error for `name`, with the closest foods in `db` as suggestions.
+    pub fn lookup(db: &Database, name: &str) -> Result<Self> {
+        Ok(FoodNotFound {
+            name: name.to_string(),
+            suggestions: db.food_suggestions(name, SUGGESTIONS)?,
+        })
+    }
+}
+
+impl std::fmt::Display for FoodNotFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.suggestions.is_empty() {
+            write!(
+                f,
+                "Food not found: '{}'. Add it with: chomp add \"{}\" --protein X --fat Y --carbs Z",
+                self.name, self.name
+            )
+        } else {
+            write!(
+                f,
+                "Food not found: '{}'. Did you mean: {}?",
+                self.name,
+                self.suggestions.join(", ")
+            )
+        }
+    }
+}
+
+impl std::error::Error for FoodNotFound {}
+
+/// Returned (via anyhow) when a name matches no food itself but several of
+/// its variants, e.g. "oats" with both "Oats (dry)" and "Oats (cooked)".
+#[derive(Debug)]
+pub struct AmbiguousFood {
+    pub name: String,
+    /// Full names of the variants, by name
+    pub variants: Vec<String>,
+    /// Amount that was given with the name, to log the chosen variant with
+    pub amount: Option<String>,
+}
+
+impl AmbiguousFood {
+    /// What to log once `variant` is chosen: its name with the amount.
+    pub fn input(&self, variant: &str) -> String {
+        match &self.amount {
+            Some(amount) => format!("{} {}", variant, amount),
+            None => variant.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for AmbiguousFood {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "'{}' could be {}. Log one by its full name, e.g. chomp \"{}\"{}",
+            self.name,
+            self.variants.join(" or "),
+            self.variants[0],
+            self.amount
+                .as_ref()
+                .map(|a| format!(" {}", a))
+                .unwrap_or_default()
         )
-    })?;
+    }
+}
+
+impl std::error::Error for AmbiguousFood {}
+
+/// Parse input like "ribeye 8oz" or "bare bar" and log it with `opts`.
+pub fn parse_and_log_with(db: &Database, input: &str, opts: &LogOptions) -> Result<LogEntry> {
+    let (food, amount) = resolve(db, input, opts.strict)?;
 
     // Use provided amount, default amount, or serving size
     let actual_amount = if let Some(amt) = amount {
@@ -25,28 +109,262 @@ pub fn parse_and_log(db: &Database, input: &str, date: Option<&str>) -> Result<L
         food.serving.clone()
     };
 
-    // Calculate macros
-    let macros = food.calculate(&actual_amount).ok_or_else(|| {
-        anyhow!(
-            "Could not calculate macros for {} of {}",
-            actual_amount,
-            food.name
-        )
-    })?;
+    // A prepped batch is logged by cooked weight or by the container
+    let prepped = match food.id {
+        Some(id) => db.latest_prep_batch(id)?.and_then(|batch| {
+            let grams = parse_grams(&actual_amount)
+                .or_else(|| parse_containers(&actual_amount).map(|n| n * batch.container_grams))?;
+            Some(batch.macros_for(grams))
+        }),
+        None => None,
+    };
+
+    // Compound foods scale their components, by servings or, for a weighed
+    // recipe, cooked weight; everything else its own row
+    let compound = match (&prepped, db.recipe_yield(&food.name)?) {
+        (None, Some(recipe)) => match recipe.servings_in(&actual_amount) {
+            Some(servings) => db.compound_macros(&food.name, servings)?,
+            None if parse_grams(&actual_amount).is_some() => anyhow::bail!(
+                "{} hasn't been weighed. Weigh the whole cooked dish, then: chomp recipe yield \"{}\" <weight>",
+                food.name,
+                food.name
+            ),
+            None => None,
+        },
+        _ => None,
+    };
+    let macros = match prepped.or(compound) {
+        Some(macros) => macros,
+        None => food.calculate(&actual_amount).ok_or_else(|| {
+            match UnknownUnit::check(&actual_amount, &food.serving, &food.units) {
+                Some(unknown) => unknown.into(),
+                None => anyhow!(
+                    "Could not calculate macros for {} of {}",
+                    actual_amount,
+                    food.name
+                ),
+            }
+        })?,
+    };
 
     // Log it
-    let entry = db.log_food(food.id.unwrap(), &actual_amount, &macros, date)?;
+    let mut entry = db.log_food_with(food.id.unwrap(), &actual_amount, &macros, opts)?;
+    if let Some(warning) = unconverted_preparation(db, input, &food)? {
+        entry.warning = Some(match entry.warning {
+            Some(duplicate) => format!("{}; {}", duplicate, warning),
+            None => warning,
+        });
+    }
 
     Ok(entry)
 }
 
+/// A warning for input weighed in a preparation ("rice 150g cooked") that
+/// had no variant to go by, so the amount was logged against `food` as is.
+fn unconverted_preparation(db: &Database, input: &str, food: &Food) -> Result<Option<String>> {
+    let (rest, Some(preparation)) = split_preparation(input) else {
+        return Ok(None);
+    };
+    if db.get_food_by_name(input.trim())?.is_some()
+        || resolve_prepared(db, rest, preparation)?.is_some()
+    {
+        return Ok(None);
+    }
+    let base = crate::food::base_name(&food.name);
+    Ok(Some(format!(
+        "{} isn't stored cooked or uncooked, so \"{}\" was logged as is. Add \"{}\" to log it by {} weight",
+        food.name,
+        input.trim(),
+        variant_name(base, preparation),
+        preparation
+    )))
+}
+
+/// The food `input` names and the amount it gives, if any. Misses are
+/// remembered for `chomp aliases suggest`, and fall back to the most likely
+/// match unless `strict`.
+pub fn resolve(db: &Database, input: &str, strict: bool) -> Result<(Food, Option<String>)> {
+    // A food whose name looks like it ends in an amount ("half and half")
+    // still matches as a whole
+    let whole = db.get_food_by_name(input.trim())?;
+    if whole.is_none() {
+        if let (rest, Some(preparation)) = split_preparation(input) {
+            return match resolve_prepared(db, rest, preparation)? {
+                Some(found) => Ok(found),
+                // Nothing stored in either state: the food by its plain name
+                None => resolve(db, rest, strict),
+            };
+        }
+    }
+    let (food_name, amount) = match whole {
+        Some(_) => (input.trim().to_string(), None),
+        None => parse_input(input),
+    };
+
+    let mut found = db.get_food_by_name(&food_name)?;
+    if found.is_none() {
+        // "oats" for "Oats (dry)" when it's the only variant (unless
+        // strict); with several, the caller has to pick
+        match db.food_variants(&food_name)?.as_slice() {
+            [] => {}
+            [only] if !strict => found = db.get_food_by_name(only)?,
+            [_] => {}
+            variants => {
+                return Err(AmbiguousFood {
+                    name: food_name,
+                    variants: variants.to_vec(),
+                    amount,
+                }
+                .into())
+            }
+        }
+    }
+    if found.is_none() && !food_name.is_empty() {
+        db.record_lookup_miss(&food_name)?;
+        if !strict {
+            found = db.most_likely_food(&food_name)?;
+        }
+    }
+    match found {
+        Some(food) => Ok((food, amount)),
+        None => Err(FoodNotFound::lookup(db, &food_name)?.into()),
+    }
+}
+
+/// Words that can follow an amount to say what state the food was weighed
+/// in, as in "rice 150g cooked". All but "cooked" mean before cooking.
+const PREPARATIONS: [&str; 4] = ["cooked", "dry", "raw", "uncooked"];
+
+/// `input` without a trailing preparation word, and the word: "rice 150g
+/// cooked" gives ("rice 150g", Some("cooked")).
+pub fn split_preparation(input: &str) -> (&str, Option<&'static str>) {
+    let input = input.trim();
+    let Some((rest, last)) = input.rsplit_once(char::is_whitespace) else {
+        return (input, None);
+    };
+    match PREPARATIONS.iter().find(|p| p.eq_ignore_ascii_case(last)) {
+        Some(preparation) => (rest.trim_end(), Some(preparation)),
+        None => (input, None),
+    }
+}
+
+/// The food and amount for `input` weighed in `preparation`: the variant
+/// stored in that state ("Rice (cooked)"), or else one stored in the other
+/// state, with the amount converted by the category's cooking ratio from
+/// units.toml ("rice 150g cooked" as 53.6g of "Rice (dry)"). None when
+/// neither variant exists.
+fn resolve_prepared(
+    db: &Database,
+    input: &str,
+    preparation: &str,
+) -> Result<Option<(Food, Option<String>)>> {
+    let (name, amount) = parse_input(input);
+    let cooked = preparation == PREPARATIONS[0];
+    let (same, other) = PREPARATIONS.split_at(1);
+    let (same, other) = if cooked { (same, other) } else { (other, same) };
+    let same = std::iter::once(preparation).chain(same.iter().copied());
+    for state in same {
+        if let Some(food) = db.get_food_by_name(&variant_name(&name, state))? {
+            return Ok(Some((food, amount)));
+        }
+    }
+    for state in other {
+        let Some(food) = db.get_food_by_name(&variant_name(&name, state))? else {
+            continue;
+        };
+        let ratio = Units::current().cooked_ratio(&name).ok_or_else(|| {
+            anyhow!(
+                "No cooking ratio for {}: log {} by its own weight, or add one under [[cooking]] in units.toml",
+                name,
+                food.name
+            )
+        })?;
+        let grams = amount.as_deref().and_then(parse_grams).ok_or_else(|| {
+            anyhow!(
+                "Give {} {} as a weight to convert it to {}, e.g. {} 150g {}",
+                preparation,
+                name,
+                food.name,
+                name,
+                preparation
+            )
+        })?;
+        let grams = if cooked { grams / ratio } else { grams * ratio };
+        let amount = format!("{}g", (grams * 10.0).round() / 10.0);
+        return Ok(Some((food, Some(amount))));
+    }
+    Ok(None)
+}
+
+/// Split free text like "2 eggs and toast, coffee" into one input per food,
+/// on newlines, commas, "+", "&", and "and". A "+", "&", or "and" inside a
+/// food's name ("half and half 30ml", "mac & cheese") is left alone.
+pub fn split_items(db: &Database, text: &str) -> Result<Vec<String>> {
+    split_items_with(text, |name| Ok(db.get_food_by_name(name)?.is_some()))
+}
+
+/// `split_items`, asking `is_food` whether a run of words is a food's name.
+pub fn split_items_with(
+    text: &str,
+    mut is_food: impl FnMut(&str) -> Result<bool>,
+) -> Result<Vec<String>> {
+    let mut items = Vec::new();
+    for piece in text.split(['\n', ',']) {
+        let piece = piece.replace('+', " + ").replace('&', " & ");
+        let words: Vec<&str> = piece.split_whitespace().collect();
+        let mut start = 0;
+        for (i, word) in words.iter().enumerate() {
+            if !(*word == "+" || *word == "&" || word.eq_ignore_ascii_case("and")) {
+                continue;
+            }
+            if i == start {
+                start = i + 1;
+            } else if !names_food_across(&mut is_food, &words[start..], i - start)? {
+                items.push(words[start..i].join(" "));
+                start = i + 1;
+            }
+        }
+        if start < words.len() {
+            items.push(words[start..].join(" "));
+        }
+    }
+    Ok(items)
+}
+
+/// Whether some run of `words` spanning the word at `at` is a food's name.
+fn names_food_across(
+    is_food: &mut impl FnMut(&str) -> Result<bool>,
+    words: &[&str],
+    at: usize,
+) -> Result<bool> {
+    for first in 0..at {
+        for last in at + 1..words.len() {
+            if is_food(&words[first..=last].join(" "))? {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// A container count: "2 containers", "1container", or "half a container".
+/// Anything without the unit is left to the food's own serving.
+fn parse_containers(amount: &str) -> Option<f64> {
+    let amount = amount.trim().to_lowercase();
+    let count = amount
+        .strip_suffix("containers")
+        .or_else(|| amount.strip_suffix("container"))?;
+    parse_count(count)
+}
+
 /// Parse input into food name and optional amount
 /// Examples:
 ///   "ribeye 8oz" -> ("ribeye", Some("8oz"))
 ///   "bare bar" -> ("bare bar", None)
 ///   "salmon 4 oz" -> ("salmon", Some("4 oz"))
 ///   "heavy cream 50ml" -> ("heavy cream", Some("50ml"))
-fn parse_input(input: &str) -> (String, Option<String>) {
+///   "half a cup rice" -> ("rice", Some("half a cup"))
+pub fn parse_input(input: &str) -> (String, Option<String>) {
     let input = input.trim();
 
     // Try to find an amount at the end
@@ -70,21 +388,61 @@ fn parse_input(input: &str) -> (String, Option<String>) {
         None
     };
 
-    // Pattern: "salmon 4 oz" (number then unit)
+    // Where a count just before the last word starts: "1 1/2" takes two
+    let count_at = match words.len() {
+        n if n >= 4 && is_mixed_number(words[n - 3], words[n - 2]) => n - 3,
+        n => n - 2,
+    };
+
+    // Pattern: "salmon 4 oz" (number then unit), "beef 1 1/2 lb", "beef quarter lb"
+    if let Some(sl) = second_last {
+        if (is_number(sl) || serving_word(&sl.to_lowercase()).is_some()) && is_unit(last) {
+            let amount = words[count_at..].join(" ");
+            let food_name = words[..count_at].join(" ");
+            return (food_name, Some(amount));
+        }
+    }
+
+    // Pattern: "oatmeal 1 packet" (number then a unit we may not know; the
+    // food's serving or the unit table decides whether it's usable)
     if let Some(sl) = second_last {
-        if is_number(sl) && is_unit(last) {
-            let amount = format!("{} {}", sl, last);
-            let food_name = words[..words.len() - 2].join(" ");
+        if words.len() >= 3 && is_number(sl) && !is_number(last) {
+            let amount = words[count_at..].join(" ");
+            let food_name = words[..count_at].join(" ");
             return (food_name, Some(amount));
         }
     }
 
+    // Pattern: "chili half" (serving count spelled out)
+    if let Some(n) = serving_word(&last.to_lowercase()) {
+        let food_name = words[..words.len() - 1].join(" ");
+        return (food_name, Some(n.to_string()));
+    }
+
     // Pattern: "salmon 4oz" (number+unit combined)
     if is_amount(last) {
         let food_name = words[..words.len() - 1].join(" ");
         return (food_name, Some(last.to_string()));
     }
 
+    // Pattern: "1/2 cup rice", "1 1/2 oz chicken", "quarter lb beef",
+    // "half a banana" (a count that isn't a plain number, then maybe a unit)
+    if let Some((count, rest)) = split_count(&input.to_lowercase()) {
+        let count_words = words.len() - rest.split_whitespace().count();
+        let rest = &words[count_words..];
+        if !is_number(words[0]) || count_words > 1 || words[0].contains('/') {
+            match rest {
+                [unit, food @ ..] if !food.is_empty() && is_unit(unit) => {
+                    return (food.join(" "), Some(words[..=count_words].join(" ")));
+                }
+                [_, ..] if count_words > 1 || words[0].contains('/') => {
+                    return (rest.join(" "), Some(count.to_string()));
+                }
+                _ => {}
+            }
+        }
+    }
+
     // Pattern: "3 oz milk" (number then unit then food)
     if words.len() >= 3 && is_number(words[0]) && is_unit(words[1]) {
         let amount = format!("{}{}", words[0], words[1]);
@@ -92,6 +450,11 @@ fn parse_input(input: &str) -> (String, Option<String>) {
         return (food_name, Some(amount));
     }
 
+    // Pattern: "250g lasagna" (number+unit combined, then food)
+    if is_amount(words[0]) {
+        return (words[1..].join(" "), Some(words[0].to_string()));
+    }
+
     // Pattern: "2 eggs" (number at start, but second word is not a recognized unit)
     if is_number(words[0]) && words.len() >= 2 {
         let amount = words[0].to_string();
@@ -106,59 +469,31 @@ fn parse_input(input: &str) -> (String, Option<String>) {
         return (food_name, Some(amount));
     }
 
+    // Pattern: "half chili" (last, so "whole milk 200ml" keeps its amount)
+    if let Some(n) = serving_word(&words[0].to_lowercase()) {
+        return (words[1..].join(" "), Some(n.to_string()));
+    }
+
     // No amount found, entire input is food name
     (input.to_string(), None)
 }
 
 fn is_number(s: &str) -> bool {
-    s.parse::<f64>().is_ok()
+    parse_number(s).is_some()
+}
+
+/// Whether two words make a mixed number, like "1 1/2".
+fn is_mixed_number(whole: &str, fraction: &str) -> bool {
+    whole.parse::<u32>().is_ok() && fraction.contains('/') && is_number(fraction)
 }
 
 fn is_unit(s: &str) -> bool {
-    let units = [
-        "g",
-        "gram",
-        "grams",
-        "oz",
-        "ounce",
-        "ounces",
-        "lb",
-        "lbs",
-        "pound",
-        "pounds",
-        "kg",
-        "kilogram",
-        "kilograms",
-        "ml",
-        "milliliter",
-        "milliliters",
-        "l",
-        "liter",
-        "liters",
-        "cup",
-        "cups",
-        "tbsp",
-        "tablespoon",
-        "tablespoons",
-        "tsp",
-        "teaspoon",
-        "teaspoons",
-        "bar",
-        "bars",
-        "piece",
-        "pieces",
-        "serving",
-        "servings",
-        "scoop",
-        "scoops",
-        "slice",
-        "slices",
-        "patty",
-        "patties",
-        "pack",
-        "packs",
-    ];
-    units.contains(&s.to_lowercase().as_str())
+    let s = s.to_lowercase();
+    crate::food::Units::current().grams_per(&s).is_some()
+        || matches!(
+            s.as_str(),
+            "container" | "containers" | "serving" | "servings"
+        )
 }
 
 fn is_amount(s: &str) -> bool {
@@ -200,6 +535,94 @@ mod tests {
             parse_input("2 eggs"),
             ("eggs".to_string(), Some("2".to_string()))
         );
+        assert_eq!(
+            parse_input("250g lasagna"),
+            ("lasagna".to_string(), Some("250g".to_string()))
+        );
+        assert_eq!(
+            parse_input("1 serving lasagna"),
+            ("lasagna".to_string(), Some("1serving".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_input_unknown_unit() {
+        assert_eq!(
+            parse_input("oatmeal 1 packet"),
+            ("oatmeal".to_string(), Some("1 packet".to_string()))
+        );
+        assert_eq!(
+            parse_input("2 packet"),
+            ("packet".to_string(), Some("2".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_input_serving_words() {
+        assert_eq!(
+            parse_input("chili half"),
+            ("chili".to_string(), Some("0.5".to_string()))
+        );
+        assert_eq!(
+            parse_input("double chili"),
+            ("chili".to_string(), Some("2".to_string()))
+        );
+        assert_eq!(
+            parse_input("chili 1.5 servings"),
+            ("chili".to_string(), Some("1.5 servings".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_input_fractions_and_words() {
+        let parsed = |input: &str| {
+            let (name, amount) = parse_input(input);
+            (name, amount.unwrap_or_default())
+        };
+        let expect = |name: &str, amount: &str| (name.to_string(), amount.to_string());
+        assert_eq!(parsed("1/2 cup rice"), expect("rice", "1/2 cup"));
+        assert_eq!(parsed("1 1/2 oz chicken"), expect("chicken", "1 1/2 oz"));
+        assert_eq!(
+            parsed("chicken breast 1 1/2 oz"),
+            expect("chicken breast", "1 1/2 oz")
+        );
+        assert_eq!(parsed("half a banana"), expect("banana", "0.5"));
+        assert_eq!(parsed("half a cup oats"), expect("oats", "half a cup"));
+        assert_eq!(
+            parsed("quarter lb ground beef"),
+            expect("ground beef", "quarter lb")
+        );
+        assert_eq!(
+            parsed("ground beef quarter lb"),
+            expect("ground beef", "quarter lb")
+        );
+        assert_eq!(parsed("1/2 avocado"), expect("avocado", "0.5"));
+        assert_eq!(parsed("avocado 1/2"), expect("avocado", "1/2"));
+    }
+
+    #[test]
+    fn test_parse_input_serving_word_in_name() {
+        let parsed = |input: &str| {
+            let (name, amount) = parse_input(input);
+            (name, amount.unwrap_or_default())
+        };
+        let expect = |name: &str, amount: &str| (name.to_string(), amount.to_string());
+        assert_eq!(parsed("whole milk 200ml"), expect("whole milk", "200ml"));
+        assert_eq!(
+            parsed("half and half 30ml"),
+            expect("half and half", "30ml")
+        );
+        assert_eq!(parsed("quarter pounder 2"), expect("quarter pounder", "2"));
+        assert_eq!(parsed("half chili"), expect("chili", "0.5"));
+    }
+
+    #[test]
+    fn test_parse_containers() {
+        assert_eq!(parse_containers("2 containers"), Some(2.0));
+        assert_eq!(parse_containers("1container"), Some(1.0));
+        assert_eq!(parse_containers("half a container"), Some(0.5));
+        assert_eq!(parse_containers("2"), None);
+        assert_eq!(parse_containers("1 serving"), None);
     }
 
     #[test]
@@ -256,4 +679,102 @@ mod tests {
         assert_eq!(name, "bare bar");
         assert_eq!(amount, Some("0.5".to_string()));
     }
+
+    #[test]
+    fn test_split_items() {
+        let db = Database::open_in_memory().unwrap();
+        let half = crate::food::Food::new("Half and Half", 3.0, 12.0, 4.0, 130.0, "100ml", vec![]);
+        db.add_food(&half).unwrap();
+        let mac = crate::food::Food::new("Mac & Cheese", 5.0, 4.0, 20.0, 140.0, "100g", vec![]);
+        db.add_food(&mac).unwrap();
+        assert_eq!(
+            split_items(&db, "2 eggs and toast, coffee + half and half 30ml").unwrap(),
+            ["2 eggs", "toast", "coffee", "half and half 30ml"]
+        );
+        assert_eq!(
+            split_items(&db, "mac & cheese 200g & peas+corn").unwrap(),
+            ["mac & cheese 200g", "peas", "corn"]
+        );
+        assert_eq!(split_items(&db, " \n ,").unwrap(), Vec::<String>::new());
+        assert_eq!(
+            split_items_with("toast & jam, salt and pepper and eggs", |name| Ok(
+                name == "salt and pepper"
+            ))
+            .unwrap(),
+            ["toast", "jam", "salt and pepper", "eggs"]
+        );
+    }
+
+    #[test]
+    fn test_resolve_variants() {
+        let db = Database::open_in_memory().unwrap();
+        let dry = Food::new("Oats (dry)", 13.0, 7.0, 68.0, 389.0, "100g", vec![]);
+        db.add_food(&dry).unwrap();
+        let (food, amount) = resolve(&db, "oats 40g", false).unwrap();
+        assert_eq!(
+            (food.name.as_str(), amount.as_deref()),
+            ("Oats (dry)", Some("40g"))
+        );
+        assert!(resolve(&db, "oats 40g", true).is_err());
+
+        let cooked = Food::new("Oats (cooked)", 2.5, 1.5, 12.0, 71.0, "100g", vec![]);
+        db.add_food(&cooked).unwrap();
+        let err = resolve(&db, "oats 40g", false).unwrap_err();
+        let ambiguous = err.downcast_ref::<AmbiguousFood>().unwrap();
+        assert_eq!(ambiguous.variants, ["Oats (cooked)", "Oats (dry)"]);
+        assert_eq!(ambiguous.input("Oats (dry)"), "Oats (dry) 40g");
+        assert_eq!(
+            resolve(&db, &ambiguous.input("Oats (dry)"), true)
+                .unwrap()
+                .0
+                .name,
+            "Oats (dry)"
+        );
+    }
+
+    #[test]
+    fn test_resolve_preparation() {
+        assert_eq!(
+            split_preparation("rice 150g Cooked"),
+            ("rice 150g", Some("cooked"))
+        );
+        assert_eq!(split_preparation("raw"), ("raw", None));
+        assert_eq!(split_preparation("rice 150g"), ("rice 150g", None));
+
+        let db = Database::open_in_memory().unwrap();
+        let dry = Food::new("Rice (dry)", 7.0, 0.6, 80.0, 360.0, "100g", vec![]);
+        db.add_food(&dry).unwrap();
+        let (food, amount) = resolve(&db, "rice 60g dry", true).unwrap();
+        assert_eq!(
+            (food.name.as_str(), amount.as_deref()),
+            ("Rice (dry)", Some("60g"))
+        );
+        // 2.8g cooked per gram dry, from units.toml
+        let (food, amount) = resolve(&db, "rice 140g cooked", true).unwrap();
+        assert_eq!(
+            (food.name.as_str(), amount.as_deref()),
+            ("Rice (dry)", Some("50g"))
+        );
+        assert!(resolve(&db, "rice 1 cup cooked", true).is_err());
+
+        let cooked = Food::new("Rice (cooked)", 2.7, 0.3, 28.0, 130.0, "100g", vec![]);
+        db.add_food(&cooked).unwrap();
+        let (food, amount) = resolve(&db, "rice 140g cooked", true).unwrap();
+        assert_eq!(
+            (food.name.as_str(), amount.as_deref()),
+            ("Rice (cooked)", Some("140g"))
+        );
+
+        // A food that's stored in neither state logs by its plain name
+        let eggs = Food::new("eggs", 6.0, 5.0, 0.5, 70.0, "1", vec![]);
+        db.add_food(&eggs).unwrap();
+        assert_eq!(resolve(&db, "eggs 2 raw", true).unwrap().0.name, "eggs");
+
+        // ...with a warning that the amount went in unconverted
+        let opts = LogOptions::default();
+        let entry = parse_and_log_with(&db, "eggs 2 raw", &opts).unwrap();
+        assert!(entry.warning.unwrap().contains("\"eggs (raw)\""));
+        let entry = parse_and_log_with(&db, "rice 140g cooked", &opts).unwrap();
+        assert_eq!(entry.warning, None);
+    }
 }
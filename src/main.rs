@@ -1,13 +1,47 @@
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand};
+use std::io::{BufRead, IsTerminal, Read, Write};
 
+mod adherence;
+mod batch;
+mod card;
+mod chat;
 mod client;
+mod config;
 mod db;
+mod digest;
+#[cfg(feature = "sse")]
+mod discord;
+mod email;
+mod fit;
 mod food;
+mod forecast;
+mod goal;
+mod heatmap;
+mod hooks;
+mod i18n;
+mod init;
+mod label;
 mod logging;
+mod matrix;
 mod mcp;
+mod meal;
+#[cfg(feature = "sse")]
+mod metrics;
+mod program;
+mod recipe;
+mod recurring;
+mod render;
+mod report;
+mod seed;
+mod settings;
+mod snack;
 #[cfg(feature = "sse")]
 mod sse;
+mod status;
+mod suggest;
+mod transcript;
+mod weight;
 
 #[derive(Parser)]
 #[command(name = "chomp")]
@@ -25,62 +59,515 @@ struct Cli {
     #[arg(long)]
     date: Option<String>,
 
+    /// Time it was eaten, e.g. 08:30 or 8:30am (defaults to now)
+    #[arg(long, value_parser = db::parse_time_of_day)]
+    time: Option<chrono::NaiveTime>,
+
+    /// Meal to log under: breakfast, lunch, dinner, snack, or one from `meals` in the config
+    #[arg(long)]
+    meal: Option<String>,
+
+    /// Skip the duplicate warning when the same food and amount was just logged
+    #[arg(long)]
+    force: bool,
+
+    /// Log each line on the clipboard as a food, e.g. meals jotted in a notes app
+    #[arg(long, conflicts_with_all = ["food", "from_file"])]
+    from_clipboard: bool,
+
+    /// Log each line of a file as a food ("-" reads stdin)
+    #[arg(long, value_name = "PATH", conflicts_with = "food")]
+    from_file: Option<std::path::PathBuf>,
+
+    /// Minutes within which an identical entry is treated as a duplicate (0 disables)
+    #[arg(long, default_value_t = 5, env = "CHOMP_DEDUPE_WINDOW")]
+    dedupe_window: u32,
+
+    /// Allow changes to entries older than the configured lock_after_days
+    #[arg(long, global = true)]
+    amend: bool,
+
+    /// Only log exact food names or aliases instead of guessing the closest match
+    #[arg(long, env = "CHOMP_STRICT")]
+    strict: bool,
+
+    /// Refuse amounts whose weight would be assumed (unknown units, pieces of a food served by weight)
+    #[arg(long, global = true, env = "CHOMP_STRICT_UNITS")]
+    strict_units: bool,
+
     /// Output as JSON
     #[arg(long, global = true)]
     json: bool,
+
+    /// Disable colored output (also honors NO_COLOR)
+    #[arg(long, global = true)]
+    no_color: bool,
+}
+
+#[derive(Subcommand)]
+enum AliasesAction {
+    /// Offer names that failed to look up as aliases for the food logged right after
+    Suggest {
+        /// Accept every suggestion without prompting
+        #[arg(long)]
+        yes: bool,
+        /// Minutes after a failed lookup in which the next logged food counts
+        #[arg(long, default_value_t = ALIAS_SUGGEST_WINDOW)]
+        window: u32,
+    },
+    /// Write every alias as a food,alias CSV
+    Export {
+        /// Output file (default: stdout)
+        #[arg(long)]
+        output: Option<std::path::PathBuf>,
+    },
+    /// Add aliases from a food,alias CSV, skipping any that name another food
+    Import {
+        /// CSV file, as written by `chomp aliases export`
+        path: std::path::PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum FoodAction {
+    /// How often and how much you log a food, when, and whether that's changing
+    Stats {
+        /// Food name or alias
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum TrashAction {
+    /// List deleted foods that can still be restored
+    List,
+    /// Bring a deleted food back with its aliases, tags, and log history
+    Restore {
+        /// Name of the deleted food
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ShareAction {
+    /// Create a read-only link to recent data, served by `chomp serve --transport sse`
+    Create {
+        /// How many days back the link shows
+        #[arg(long, default_value_t = 30)]
+        days: u32,
+        /// What the link shows
+        #[arg(long, value_enum, value_delimiter = ',', default_value = "log,reports")]
+        scope: Vec<db::ShareScope>,
+        /// Days until the link stops working (0 never expires)
+        #[arg(long, default_value_t = 30)]
+        expires_in: u32,
+        /// Public address of the server, to print a full link
+        #[arg(long, env = "CHOMP_SHARE_BASE_URL")]
+        base_url: Option<String>,
+    },
+    /// List share links
+    List,
+    /// Revoke a share link by its token (or the start of it)
+    Revoke {
+        /// Token of the link to revoke
+        #[arg(required_unless_present = "all")]
+        token: Option<String>,
+        /// Revoke every active link
+        #[arg(long, conflicts_with = "token")]
+        all: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum FavAction {
+    /// Save what to log under a short slug, e.g. "protein shake 1 scoop" --slug morning-shake
+    Add {
+        /// What to log, as for `chomp log`; several foods can be joined with "and"
+        #[arg(required = true)]
+        input: Vec<String>,
+        /// Slug for the URL (default: made from the input)
+        #[arg(long)]
+        slug: Option<String>,
+    },
+    /// List favorites and their slugs
+    List,
+    /// Remove a favorite
+    Remove {
+        /// Slug of the favorite
+        slug: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum RecurringAction {
+    /// Log something every day, e.g. "creatine 5g" --daily 08:00
+    Add {
+        /// What to log, as for `chomp log`; several foods can be joined with "and"
+        #[arg(required = true)]
+        input: Vec<String>,
+        /// Time of day it's logged at, e.g. 08:00 or 8am
+        #[arg(long)]
+        daily: String,
+        /// Log it the first time chomp runs each day, without waiting for `chomp recurring run`
+        #[arg(long)]
+        auto: bool,
+    },
+    /// List recurring entries
+    List,
+    /// Stop a recurring entry (what it logged stays)
+    Remove {
+        /// ID from `chomp recurring list`
+        id: i64,
+    },
+    /// Log whatever is due, catching up on days missed since the last run (e.g. from cron)
+    Run,
+}
+
+#[derive(Subcommand)]
+enum SettingsAction {
+    /// Write the config, custom units, recurring entries, and profile names as JSON
+    Export {
+        /// Output file (default: stdout)
+        #[arg(long)]
+        output: Option<std::path::PathBuf>,
+    },
+    /// Set this machine up from `chomp settings export` output or a `chomp snapshot --backup` database
+    Import {
+        /// Settings JSON, or a backup database
+        path: std::path::PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum PantryAction {
+    /// Mark foods as in stock
+    Add {
+        #[arg(required = true)]
+        foods: Vec<String>,
+    },
+    /// Mark foods as used up
+    Remove {
+        #[arg(required = true)]
+        foods: Vec<String>,
+    },
+    /// List what's in stock
+    List,
+}
+
+/// Optional nutrients for `add` and `edit`, per serving.
+#[derive(clap::Args)]
+struct MicroArgs {
+    /// Sugar in grams, included in carbs
+    #[arg(long)]
+    sugar: Option<f64>,
+    /// Sodium in milligrams
+    #[arg(long)]
+    sodium: Option<f64>,
+    /// Saturated fat in grams, included in fat
+    #[arg(long)]
+    saturated_fat: Option<f64>,
+}
+
+impl MicroArgs {
+    fn micros(&self) -> food::Micros {
+        food::Micros {
+            sugar: self.sugar,
+            sodium: self.sodium,
+            saturated_fat: self.saturated_fat,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum GoalAction {
+    /// Set daily goals, e.g. --calories 2200 --protein 160
+    #[command(group = clap::ArgGroup::new("goals").required(true).multiple(true))]
+    Set {
+        #[arg(long, group = "goals")]
+        calories: Option<f64>,
+        /// Grams
+        #[arg(long, group = "goals")]
+        protein: Option<f64>,
+        /// Grams
+        #[arg(long, group = "goals")]
+        fat: Option<f64>,
+        /// Grams
+        #[arg(long, group = "goals")]
+        carbs: Option<f64>,
+    },
+    /// Today's goals and progress toward them
+    Show,
+    /// Remove goals (all of them unless some are named)
+    Clear {
+        #[arg(value_enum)]
+        metrics: Vec<config::Metric>,
+    },
+}
+
+#[derive(Subcommand)]
+enum DevAction {
+    /// Fill an empty database with generated foods and history, for demos and benchmarks
+    Seed {
+        /// Days of history, ending today
+        #[arg(long, default_value_t = 90)]
+        days: u32,
+        /// Number of foods: the starter foods, then variants of them
+        #[arg(long, default_value_t = 200)]
+        foods: usize,
+        /// The same seed always generates the same history
+        #[arg(long, default_value_t = 42)]
+        seed: u64,
+    },
+}
+
+#[derive(Subcommand)]
+enum DbAction {
+    /// Show where the database is, which chomp created it, and its schema version
+    Info,
+}
+
+#[derive(Subcommand)]
+enum ReportAction {
+    /// Months side by side: average intake, weight change, adherence, and top foods
+    Compare {
+        /// Months to compare, e.g. june,july or 2025-06,2026-06
+        #[arg(long, value_delimiter = ',', required = true)]
+        months: Vec<String>,
+    },
+    /// Which foods your calories, protein, fat, and carbs came from (default: the last 7 days)
+    Sources {
+        /// The last 7 days, today included
+        #[arg(long)]
+        week: bool,
+        /// The last 30 days, today included
+        #[arg(long, conflicts_with = "week")]
+        month: bool,
+        /// The last N days, today included
+        #[arg(long, conflicts_with_all = ["week", "month"])]
+        days: Option<u32>,
+        /// Macro to rank foods by
+        #[arg(long, value_enum, default_value_t = config::Metric::Calories)]
+        by: config::Metric,
+    },
+    /// Weekday against weekend averages, and each day of the week
+    Weekdays {
+        /// Number of weeks to look back, this one included
+        #[arg(long, default_value_t = 8)]
+        weeks: u32,
+    },
+}
+
+#[derive(Subcommand)]
+enum RecipeAction {
+    /// Create a recipe from the whole batch's ingredients, logged by the serving or cooked weight
+    Add {
+        /// Name for the recipe
+        name: String,
+        /// Ingredients as logged (e.g., "ground beef 500g, lasagna noodles 250g")
+        #[arg(long, short = 'i')]
+        items: String,
+        /// Servings the batch makes
+        #[arg(long, default_value_t = 1.0)]
+        servings: f64,
+        /// Cooked weight of the whole batch (e.g., "2.4kg"), to log it by weight
+        #[arg(long = "yield")]
+        yield_weight: Option<String>,
+    },
+    /// Record what a cooked recipe weighed, and optionally how many servings it made
+    Yield {
+        /// Recipe that was cooked
+        name: String,
+        /// Cooked weight of the whole batch (e.g., "1200g")
+        weight: String,
+        /// Servings the batch made (default: unchanged)
+        #[arg(long)]
+        servings: Option<f64>,
+    },
+    /// Import a recipe page (schema.org/Recipe), divided into the servings it yields
+    Import {
+        /// Recipe page URL
+        url: String,
+        /// Name for the recipe (default: the recipe's name)
+        #[arg(long)]
+        name: Option<String>,
+        /// Accept the best guess for every ingredient without prompting
+        #[arg(long, short)]
+        yes: bool,
+    },
 }
 
+/// Commands shown by `chomp redo --list`.
+const REDO_LIST_LIMIT: usize = 20;
+
+/// Default pairing window for `chomp aliases suggest`.
+const ALIAS_SUGGEST_WINDOW: u32 = 10;
+
 #[derive(Subcommand)]
 enum Commands {
+    /// First-run setup: config, daily goals, foods, and shell completions
+    Init {
+        /// Accept all defaults without prompting
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Print a shell completion script
+    Completions {
+        /// Shell to generate completions for
+        shell: clap_complete::Shell,
+    },
     /// Add a new food to the database
     Add {
         /// Food name
         name: String,
+        /// Brand or preparation telling this food apart from others of the
+        /// same name: `chomp add oats --variant dry` adds "oats (dry)"
+        #[arg(long)]
+        variant: Option<String>,
         /// Protein in grams
-        #[arg(long, short)]
-        protein: f64,
+        #[arg(long, short, required_unless_present = "from_label")]
+        protein: Option<f64>,
         /// Fat in grams
-        #[arg(long, short)]
-        fat: f64,
+        #[arg(long, short, required_unless_present = "from_label")]
+        fat: Option<f64>,
         /// Carbs in grams
-        #[arg(long, short)]
-        carbs: f64,
-        /// Serving size (e.g., "100g", "1 bar", "3oz")
-        #[arg(long, default_value = "100g")]
-        per: String,
+        #[arg(long, short, required_unless_present = "from_label")]
+        carbs: Option<f64>,
+        /// Serving size (e.g., "100g", "1 bar", "3oz"); defaults to 100g, or
+        /// the serving for the food's category in units.toml (milk → 100ml)
+        #[arg(long)]
+        per: Option<String>,
         /// Calories (calculated if not provided)
         #[arg(long)]
         calories: Option<f64>,
+        /// Fiber in grams, included in carbs
+        #[arg(long, default_value_t = 0.0)]
+        fiber: f64,
+        #[command(flatten)]
+        micros: MicroArgs,
         /// Aliases for this food
         #[arg(long, short)]
         alias: Vec<String>,
+        /// Read a pasted Nutrition Facts label from stdin instead of --protein/--fat/--carbs
+        #[arg(long, conflicts_with_all = ["protein", "fat", "carbs", "per", "calories"])]
+        from_label: bool,
+        /// With --from-label, store values per 100g instead of per serving
+        #[arg(long, requires = "from_label")]
+        per_100g: bool,
+        /// With --from-label, save without asking for confirmation
+        #[arg(long, short, requires = "from_label")]
+        yes: bool,
     },
     /// Search foods in database
     Search {
         /// Search query
         query: String,
     },
+    /// Show a food's full nutrition card: serving variants, aliases, tags, and usage
+    Show {
+        /// Food name or alias
+        food: String,
+    },
+    /// Per-food views of the log
+    Food {
+        #[command(subcommand)]
+        action: FoodAction,
+    },
+    /// Find foods by macro profile, e.g. --protein ">20" --fat "<5" --per 100g
+    Find {
+        /// Protein filter: ">20", "<=5", "10..20", or an exact amount
+        #[arg(long, allow_hyphen_values = true)]
+        protein: Option<String>,
+        /// Fat filter
+        #[arg(long, allow_hyphen_values = true)]
+        fat: Option<String>,
+        /// Carbs filter
+        #[arg(long, allow_hyphen_values = true)]
+        carbs: Option<String>,
+        /// Calories filter
+        #[arg(long, allow_hyphen_values = true)]
+        calories: Option<String>,
+        /// Fiber filter
+        #[arg(long, allow_hyphen_values = true)]
+        fiber: Option<String>,
+        /// Sugar filter (foods without sugar data never match)
+        #[arg(long, allow_hyphen_values = true)]
+        sugar: Option<String>,
+        /// Sodium filter, in mg
+        #[arg(long, allow_hyphen_values = true)]
+        sodium: Option<String>,
+        /// Saturated fat filter
+        #[arg(long, allow_hyphen_values = true)]
+        saturated_fat: Option<String>,
+        /// Compare per this weight (e.g., "100g") instead of per serving
+        #[arg(long)]
+        per: Option<String>,
+    },
     /// Show today's totals
     Today,
+    /// One-line summary of today's totals for status bars
+    Status {
+        /// Output format: plain, waybar, polybar, or tmux
+        #[arg(long, default_value = "plain")]
+        format: String,
+        /// Daily calorie target (env: CHOMP_CALORIE_TARGET)
+        #[arg(long, env = "CHOMP_CALORIE_TARGET")]
+        calories: Option<f64>,
+        /// Daily protein target in grams (env: CHOMP_PROTEIN_TARGET)
+        #[arg(long, env = "CHOMP_PROTEIN_TARGET")]
+        protein: Option<f64>,
+    },
     /// Show recent log entries
     History {
         /// Number of days to show
         #[arg(short, long, default_value = "7")]
         days: u32,
+        /// Show at most this many entries
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+        limit: Option<u32>,
+        /// Skip this many of the newest entries (for paging with --limit)
+        #[arg(long, default_value_t = 0)]
+        offset: u32,
+        /// Only today's entries (same as --days 0)
+        #[arg(long, conflicts_with = "days")]
+        today: bool,
+        /// Only entries logged at or after this time of day (e.g. 18:00 or 6pm)
+        #[arg(long)]
+        after: Option<String>,
+        /// Only entries logged before this time of day
+        #[arg(long)]
+        before: Option<String>,
     },
     /// Export data
     Export {
-        /// Export format
+        /// Export format: csv, json, ndjson, or parquet
         #[arg(long, default_value = "csv")]
         format: String,
+        /// Output file (required for parquet)
+        #[arg(long)]
+        output: Option<std::path::PathBuf>,
+        /// Replace personal food names with stable hashes, for sharing
+        #[arg(long)]
+        anonymize: bool,
+        /// CSV columns, in order (date, food, amount, grams, protein, fat, carbs, calories, id, fiber, created_at)
+        #[arg(long, value_delimiter = ',')]
+        columns: Vec<String>,
+        /// CSV field delimiter: one character, or "tab"
+        #[arg(long, default_value = ",", value_parser = parse_delimiter)]
+        delimiter: u8,
     },
     /// Import from USDA or other sources
     Import {
-        /// Source (usda, csv)
+        /// Source (usda, csv, starter, transcript)
         source: String,
+        /// File for csv or transcript import
+        #[arg(conflicts_with = "path")]
+        file: Option<String>,
         /// Path for csv import
         #[arg(long)]
         path: Option<String>,
+        /// Day a transcript starts on (YYYY-MM-DD format, defaults to today)
+        #[arg(long)]
+        date: Option<String>,
     },
     /// Edit a food entry
     Edit {
@@ -95,25 +582,81 @@ enum Commands {
         /// Carbs in grams
         #[arg(long, short)]
         carbs: Option<f64>,
+        /// Fiber in grams, included in carbs
+        #[arg(long)]
+        fiber: Option<f64>,
         /// Serving size (e.g., "100g", "1 bar", "3oz")
         #[arg(long)]
         per: Option<String>,
         /// Calories (calculated if not provided)
         #[arg(long)]
         calories: Option<f64>,
+        #[command(flatten)]
+        micros: MicroArgs,
     },
-    /// Delete a food entry
+    /// Delete a food entry (it stays in the trash for 30 days)
     Delete {
         /// Food name to delete
         name: String,
+        /// Also move the food's log entries to the trash
+        #[arg(long, conflicts_with = "keep_history")]
+        cascade: bool,
+        /// Keep the food's log entries (history shows them as 'deleted')
+        #[arg(long)]
+        keep_history: bool,
+    },
+    /// Deleted foods: list or restore them
+    Trash {
+        #[command(subcommand)]
+        action: TrashAction,
+    },
+    /// Read-only links to your log and reports, e.g. for a coach
+    Share {
+        #[command(subcommand)]
+        action: ShareAction,
+    },
+    /// Favorites, logged with no typing by POST /quicklog/fav/<slug>
+    Fav {
+        #[command(subcommand)]
+        action: FavAction,
+    },
+    /// Entries logged every day, like supplements
+    Recurring {
+        #[command(subcommand)]
+        action: RecurringAction,
+    },
+    /// Daily calorie and macro goals
+    Goal {
+        #[command(subcommand)]
+        action: GoalAction,
     },
     /// Delete a log entry by ID
     Unlog {
         /// Log entry ID to delete
-        id: i64,
+        #[arg(required_unless_present = "auto")]
+        id: Option<i64>,
+        /// Delete what recurring entries logged today instead
+        #[arg(long, conflicts_with = "id")]
+        auto: bool,
     },
     /// Delete the most recent log entry
     UnlogLast,
+    /// Run the last command again, optionally with a new amount (also `chomp '!!'`)
+    #[command(alias = "!!")]
+    Redo {
+        /// New amount when the last command logged a food, e.g. 6oz
+        #[arg(trailing_var_arg = true)]
+        amount: Vec<String>,
+        /// List recent commands instead
+        #[arg(long, conflicts_with = "amount")]
+        list: bool,
+    },
+    /// Revert the most recent change (log, edit, delete, add) within the undo window
+    Undo {
+        /// How far back to look, in minutes
+        #[arg(long, default_value_t = 10, env = "CHOMP_UNDO_WINDOW")]
+        window: u32,
+    },
     /// Edit a log entry
     EditLog {
         /// Log entry ID to edit
@@ -139,6 +682,93 @@ enum Commands {
         #[arg(long, short = 'i')]
         items: String,
     },
+    /// Manage food aliases
+    Aliases {
+        #[command(subcommand)]
+        action: AliasesAction,
+    },
+    /// Recipes: whole batches logged by the serving or cooked weight
+    Recipe {
+        #[command(subcommand)]
+        action: RecipeAction,
+    },
+    /// Record a meal-prep batch of a compound food, then log it by container weight
+    Prep {
+        /// Compound food that was cooked
+        name: String,
+        /// Number of containers the batch was split into
+        #[arg(long)]
+        portions: f64,
+        /// Cooked weight of one container (e.g., "420g", "15oz")
+        #[arg(long)]
+        container_weight: String,
+        /// Servings of the compound food that went into the batch
+        #[arg(long, default_value_t = 1.0)]
+        servings: f64,
+    },
+    /// How much of a food fits what's left of today's budget for one macro
+    Fit {
+        /// Food to fit
+        food: String,
+        /// Macro whose remaining budget caps the amount
+        #[arg(long, value_enum, default_value = "calories")]
+        limit: config::Metric,
+        /// Daily budget for that macro (defaults to the configured goal)
+        #[arg(long)]
+        budget: Option<f64>,
+    },
+    /// In-stock snacks that fit what's left of today's goals, most protein and fiber per calorie first
+    Snack {
+        /// Only foods with this tag (e.g., "snack")
+        #[arg(long)]
+        tag: Option<String>,
+        /// Number of suggestions
+        #[arg(long, default_value_t = 5)]
+        limit: usize,
+    },
+    /// Foods you have on hand, for `chomp snack`
+    Pantry {
+        #[command(subcommand)]
+        action: PantryAction,
+    },
+    /// Tag a food (e.g., "snack"), or untag it with --remove
+    Tag {
+        /// Food to tag
+        food: String,
+        #[arg(required = true)]
+        tags: Vec<String>,
+        /// Remove the tags instead
+        #[arg(long)]
+        remove: bool,
+    },
+    /// Weigh a food's own units ("1 cup = 185g"), ahead of the unit table's guesses
+    Unit {
+        /// Food the units belong to
+        food: String,
+        /// Units and their weights, like "1 cup = 185g" or "large = 50g" (unit names
+        /// with --remove); lists the food's units when omitted
+        weights: Vec<String>,
+        /// Remove the units instead
+        #[arg(long)]
+        remove: bool,
+    },
+    /// Close a day: review it against your goals, rate it, and lock its entries
+    Close {
+        /// Day to close (YYYY-MM-DD format, defaults to today)
+        #[arg(long)]
+        date: Option<String>,
+        /// How full the day left you, 1-5 (asked when omitted)
+        #[arg(long, value_parser = clap::value_parser!(u8).range(1..=5))]
+        satiety: Option<u8>,
+        /// Energy through the day, 1-5 (asked when omitted)
+        #[arg(long, value_parser = clap::value_parser!(u8).range(1..=5))]
+        energy: Option<u8>,
+        /// Unlock a closed day so its entries can be changed again
+        #[arg(long)]
+        reopen: bool,
+    },
+    /// Rebuild the per-day totals cache from the raw log
+    Recache,
     /// Log water intake (default: ml, supports oz/cups/l)
     Water {
         /// Amount (e.g., "500", "16oz", "2 cups")
@@ -159,8 +789,116 @@ enum Commands {
         #[arg(long)]
         date: Option<String>,
     },
+    /// Record a weigh-in (kg unless given in lb), or list recent ones
+    Weight {
+        /// Weight, e.g. "82.4", "82.4kg", or "181 lb"
+        #[arg(trailing_var_arg = true)]
+        amount: Vec<String>,
+        /// Date to log for (YYYY-MM-DD format, defaults to today)
+        #[arg(long)]
+        date: Option<String>,
+        /// Show the 7-day moving average day by day, beside calories logged
+        #[arg(long, conflicts_with = "amount")]
+        trend: bool,
+        /// Days to list
+        #[arg(long, default_value_t = 30)]
+        days: u32,
+    },
+    /// Project weight 4, 8, and 12 weeks out from the recent weigh-in trend and intake
+    Forecast {
+        /// Days of weigh-ins and intake to fit the trend to
+        #[arg(long, default_value_t = 28)]
+        days: u32,
+    },
+    /// Show the cut/maintain/bulk program: weigh-ins and weekly target adjustments
+    Program,
+    /// Which recent days were on target, judging refeeds and diet breaks by their own goals
+    Adherence {
+        /// Number of days to look back
+        #[arg(long, default_value_t = 28)]
+        days: u32,
+    },
+    /// Calendar of recent weeks, each day shaded by calories against its goal
+    Heatmap {
+        /// Number of weeks to show, ending with this one
+        #[arg(long, default_value_t = 12)]
+        weeks: u32,
+        /// Shade by whether each day was on target instead
+        #[arg(long)]
+        adherence: bool,
+    },
+    /// Past days with nothing logged or suspiciously few calories
+    Missing {
+        /// Number of days to look back
+        #[arg(long, default_value_t = 30)]
+        days: u32,
+    },
+    /// Nightly cron job: refresh the day's totals, back up, notify hooks,
+    /// and exit with status 2 if the day looks unlogged
+    Snapshot {
+        /// Day to snapshot (YYYY-MM-DD format, defaults to today)
+        #[arg(long)]
+        date: Option<String>,
+        /// Directory to write a dated copy of the database into
+        #[arg(long)]
+        backup: Option<std::path::PathBuf>,
+    },
+    /// Apply a script of changes (log, unlog, edit-log, delete, water,
+    /// weight) in one transaction: all of them or none
+    Batch {
+        /// File with one command per line or a JSON array of operations; "-" reads stdin
+        input: String,
+    },
+    /// Run a read-only SQL query against the database
+    Query {
+        /// One SQL statement, e.g. "SELECT date, calories FROM daily_totals"
+        sql: String,
+    },
+    /// Reports over longer stretches of the log
+    Report {
+        #[command(subcommand)]
+        action: ReportAction,
+    },
+    /// A week (or any stretch) written up as one document: intake, adherence, weight, top foods, and missing days
+    Digest {
+        /// First day: a weekday (its latest occurrence), yesterday, or YYYY-MM-DD (default: 6 days ago)
+        #[arg(long)]
+        since: Option<String>,
+        #[arg(long, value_enum, default_value = "markdown")]
+        format: digest::Format,
+    },
+    /// Export or import your setup: goals, schedules, meals, units, recurring entries, and profiles
+    Settings {
+        #[command(subcommand)]
+        action: SettingsAction,
+    },
     /// Show database stats
     Stats,
+    /// The database file itself: versions and schema
+    Db {
+        #[command(subcommand)]
+        action: DbAction,
+    },
+    /// Average daily intake per week or month, over months or years of history
+    Trend {
+        /// How many years back to go
+        #[arg(long, default_value_t = 1)]
+        years: u32,
+        /// Period to average over: day, week, or month
+        #[arg(long, default_value = "month", value_parser = ["day", "week", "month"])]
+        resolution: String,
+        /// Moving average over this many periods (e.g. 7 with --resolution day)
+        #[arg(long)]
+        smooth: Option<u32>,
+        /// Center the moving average on each period instead of trailing it
+        #[arg(long, requires = "smooth")]
+        centered: bool,
+    },
+    /// Developer tools: generate demo data
+    Dev {
+        #[command(subcommand)]
+        action: DevAction,
+    },
     /// Start MCP server (for AI assistants like Claude Desktop)
     Serve {
         /// Transport mode: stdio, sse, or both
@@ -175,6 +913,120 @@ enum Commands {
         /// Auth key required for SSE connections (env: CHOMP_AUTH_KEY)
         #[arg(long, env = "CHOMP_AUTH_KEY")]
         auth_key: Option<String>,
+        /// Route each MCP session to the profile named in this request header (env: CHOMP_PROFILE_HEADER)
+        #[arg(long, env = "CHOMP_PROFILE_HEADER")]
+        profile_header: Option<String>,
+        /// Per-profile auth keys as name=key,name2=key2; each key is pinned to its profile (env: CHOMP_PROFILE_KEYS)
+        #[arg(long, env = "CHOMP_PROFILE_KEYS")]
+        profile_keys: Option<String>,
+        /// Also expose Prometheus metrics on this port (env: CHOMP_METRICS_PORT)
+        #[arg(long, env = "CHOMP_METRICS_PORT")]
+        metrics_port: Option<u16>,
+        /// Daily calorie target for goal metrics (env: CHOMP_CALORIE_TARGET)
+        #[arg(long, env = "CHOMP_CALORIE_TARGET")]
+        calorie_target: Option<f64>,
+        /// Daily protein target for goal metrics (env: CHOMP_PROTEIN_TARGET)
+        #[arg(long, env = "CHOMP_PROTEIN_TARGET")]
+        protein_target: Option<f64>,
+        /// Seconds between SSE keep-alive comments (env: CHOMP_KEEP_ALIVE; default 15)
+        #[arg(long, value_name = "SECS", env = "CHOMP_KEEP_ALIVE")]
+        keep_alive: Option<u64>,
+        /// Events queued per SSE session before sends wait on a slow client (env: CHOMP_CHANNEL_CAPACITY; default 96)
+        #[arg(long, env = "CHOMP_CHANNEL_CAPACITY")]
+        channel_capacity: Option<usize>,
+        /// Refuse new SSE sessions beyond this many (env: CHOMP_MAX_SESSIONS)
+        #[arg(long, env = "CHOMP_MAX_SESSIONS")]
+        max_sessions: Option<usize>,
+        /// Seconds an MCP request may run before it is cancelled with a timeout error (env: CHOMP_REQUEST_TIMEOUT; default 300)
+        #[arg(long, value_name = "SECS", env = "CHOMP_REQUEST_TIMEOUT")]
+        request_timeout: Option<u64>,
+    },
+    /// Log from chat apps
+    Bot {
+        #[command(subcommand)]
+        action: BotAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum BotAction {
+    /// Serve /log, /today, and /undo slash commands as a Discord application's interactions endpoint
+    Discord {
+        /// Port for the interactions endpoint (env: CHOMP_DISCORD_PORT)
+        #[arg(long, default_value_t = 3001, env = "CHOMP_DISCORD_PORT")]
+        port: u16,
+        /// Host to listen on (env: CHOMP_HOST)
+        #[arg(long, default_value = "127.0.0.1", env = "CHOMP_HOST")]
+        host: String,
+        /// The application's public key, from its General Information page (env: DISCORD_PUBLIC_KEY)
+        #[arg(long, env = "DISCORD_PUBLIC_KEY")]
+        public_key: String,
+        /// Discord users and their profiles as profile=user_id,profile2=user_id2; only they are answered (env: CHOMP_DISCORD_USERS)
+        #[arg(long, env = "CHOMP_DISCORD_USERS")]
+        users: Option<String>,
+        /// Register the slash commands with Discord before serving
+        #[arg(long, requires_all = ["application_id", "bot_token"])]
+        register: bool,
+        /// Application ID, for --register (env: DISCORD_APPLICATION_ID)
+        #[arg(long, env = "DISCORD_APPLICATION_ID")]
+        application_id: Option<String>,
+        /// Bot token, for --register (env: DISCORD_BOT_TOKEN)
+        #[arg(long, env = "DISCORD_BOT_TOKEN")]
+        bot_token: Option<String>,
+    },
+    /// Log meals sent as messages in a Matrix room, replying with the macros
+    Matrix {
+        /// Homeserver URL, e.g. https://matrix.example.org (env: MATRIX_HOMESERVER)
+        #[arg(long, env = "MATRIX_HOMESERVER")]
+        homeserver: String,
+        /// Access token of the bot's account (env: MATRIX_ACCESS_TOKEN)
+        #[arg(long, env = "MATRIX_ACCESS_TOKEN")]
+        access_token: String,
+        /// Room ID or alias to listen in, usually a DM with the bot (env: MATRIX_ROOM)
+        #[arg(long, env = "MATRIX_ROOM")]
+        room: String,
+        /// Only answer this user, e.g. @me:example.org (env: MATRIX_SENDER)
+        #[arg(long, env = "MATRIX_SENDER")]
+        sender: Option<String>,
+        /// Profile database to log to (default: the main one)
+        #[arg(long)]
+        profile: Option<String>,
+    },
+    /// Log the subject of each email sent to a mailbox, replying with the macros
+    Email {
+        /// IMAP server, reached over TLS (env: CHOMP_IMAP_HOST)
+        #[arg(long, env = "CHOMP_IMAP_HOST")]
+        imap_host: String,
+        #[arg(long, default_value_t = 993, env = "CHOMP_IMAP_PORT")]
+        imap_port: u16,
+        /// SMTP server for replies, reached over TLS (env: CHOMP_SMTP_HOST; default: the IMAP host)
+        #[arg(long, env = "CHOMP_SMTP_HOST")]
+        smtp_host: Option<String>,
+        #[arg(long, default_value_t = 465, env = "CHOMP_SMTP_PORT")]
+        smtp_port: u16,
+        /// Mailbox login, for both servers (env: CHOMP_EMAIL_USER)
+        #[arg(long, env = "CHOMP_EMAIL_USER")]
+        username: String,
+        /// Mailbox password (env: CHOMP_EMAIL_PASSWORD)
+        #[arg(long, env = "CHOMP_EMAIL_PASSWORD")]
+        password: String,
+        /// Address replies are sent from (env: CHOMP_EMAIL_FROM; default: the login)
+        #[arg(long, env = "CHOMP_EMAIL_FROM")]
+        from: Option<String>,
+        /// Senders whose emails are logged, comma-separated; others are ignored (env: CHOMP_EMAIL_ALLOW)
+        #[arg(
+            long,
+            required = true,
+            value_delimiter = ',',
+            env = "CHOMP_EMAIL_ALLOW"
+        )]
+        allow: Vec<String>,
+        /// Seconds between checks for new mail
+        #[arg(long, default_value_t = 60)]
+        interval: u64,
+        /// Profile database to log to (default: the main one)
+        #[arg(long)]
+        profile: Option<String>,
     },
 }
 
@@ -185,7 +1037,40 @@ enum Backend {
 }
 
 fn main() -> Result<()> {
-    let cli = Cli::parse();
+    let args: Vec<String> = std::env::args_os()
+        .skip(1)
+        .map(|a| a.to_string_lossy().into_owned())
+        .collect();
+    let cli = Cli::parse_from(
+        std::iter::once("chomp").chain(log_flags(&args).iter().map(String::as_str)),
+    );
+    let (cli, args) = match &cli.command {
+        Some(Commands::Redo { amount, list }) => match redo(amount, *list, cli.json)? {
+            Some(redone) => redone,
+            None => return Ok(()),
+        },
+        _ => (cli, args),
+    };
+
+    let record = records_history(&cli);
+    run(cli)?;
+    if record {
+        // Best effort: the command itself already succeeded
+        if let Ok(db) = db::Database::open() {
+            let _ = db.record_command(&args);
+        }
+    }
+    Ok(())
+}
+
+fn run(mut cli: Cli) -> Result<()> {
+    let mut config = config::Config::load()?;
+    if let Some(meal) = &cli.meal {
+        cli.meal = Some(config.meal(meal)?);
+    }
+    food::Units::load()?
+        .with_strict(cli.strict_units || config.strict_units)
+        .install();
 
     // Commands that always use local mode
     match &cli.command {
@@ -194,61 +1079,249 @@ fn main() -> Result<()> {
             port,
             host,
             auth_key,
+            profile_header,
+            profile_keys,
+            metrics_port,
+            calorie_target,
+            protein_target,
+            keep_alive,
+            channel_capacity,
+            max_sessions,
+            request_timeout,
         }) => {
-            return run_serve(transport, *port, host, auth_key.as_deref());
+            if let Some(metrics_port) = metrics_port {
+                let targets = status::Targets {
+                    calories: *calorie_target,
+                    protein: *protein_target,
+                    water: None,
+                }
+                .or_config(&config);
+                run_metrics(*metrics_port, host, targets)?;
+            }
+            return run_serve(
+                transport,
+                *port,
+                host,
+                auth_key.as_deref(),
+                profile_header.as_deref(),
+                profile_keys.as_deref(),
+                config::Server {
+                    keep_alive_secs: *keep_alive,
+                    channel_capacity: *channel_capacity,
+                    max_sessions: *max_sessions,
+                    request_timeout_secs: *request_timeout,
+                }
+                .or_config(&config),
+            );
         }
-        Some(Commands::Import { source, path }) => {
+        Some(Commands::Import {
+            source,
+            file,
+            path,
+            date,
+        }) => {
             let db = db::Database::open()?;
             db.init()?;
-            return run_import(&db, source, path.as_deref());
+            let path = file.as_deref().or(path.as_deref());
+            return run_import(&db, source, path, date.as_deref(), cli.json);
         }
-        _ => {}
-    }
-
-    // Determine backend
-    let backend = if let Ok(server_url) = std::env::var("CHOMP_SERVER_URL") {
-        let auth_key = std::env::var("CHOMP_AUTH_KEY").unwrap_or_default();
-        Backend::Remote(client::RemoteClient::new(&server_url, &auth_key))
-    } else {
-        let db = db::Database::open()?;
-        db.init()?;
-        Backend::Local(db)
-    };
-
-    match cli.command {
-        Some(Commands::Add {
-            name,
-            protein,
-            fat,
-            carbs,
+        Some(Commands::Init { yes }) => {
+            let db = db::Database::open()?;
+            db.init()?;
+            return init::run(&db, *yes);
+        }
+        Some(Commands::Dev {
+            action: DevAction::Seed { days, foods, seed },
+        }) => {
+            let db = db::Database::open()?;
+            db.init()?;
+            let seeded = seed::seed(&db, *days, *foods, *seed)?;
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&seeded)?);
+            } else {
+                println!(
+                    "Seeded {} foods, {} log entries, {} water logs, and {} weigh-ins from {} to {} (seed {})",
+                    seeded.foods,
+                    seeded.entries,
+                    seeded.water,
+                    seeded.weigh_ins,
+                    seeded.from,
+                    seeded.to,
+                    seeded.seed
+                );
+            }
+            return Ok(());
+        }
+        Some(Commands::Completions { shell }) => {
+            write_completions(*shell, &mut std::io::stdout());
+            return Ok(());
+        }
+        Some(Commands::Db {
+            action: DbAction::Info,
+        }) => {
+            // Not init: a database too new to open can still be described
+            let path = db::Database::db_path()?;
+            if !path.exists() {
+                anyhow::bail!(
+                    "No database at {} yet; chomp creates it the first time it runs",
+                    path.display()
+                );
+            }
+            let info = db::Database::open_at(&path)?.info()?;
+            if cli.json {
+                let mut json = serde_json::to_value(&info)?;
+                json["path"] = serde_json::json!(path);
+                json["chomp_version"] = serde_json::json!(env!("CARGO_PKG_VERSION"));
+                println!("{}", serde_json::to_string_pretty(&json)?);
+            } else {
+                println!("Database:  {}", path.display());
+                println!(
+                    "Schema:    {} (this chomp {} reads up to {})",
+                    info.schema_version,
+                    env!("CARGO_PKG_VERSION"),
+                    info.supported_schema_version
+                );
+                println!(
+                    "Created:   {}",
+                    match (&info.created_by, &info.created_at) {
+                        (Some(by), Some(at)) => format!("by chomp {} at {} UTC", by, at),
+                        (Some(by), None) => format!("by chomp {}", by),
+                        _ => "before chomp recorded versions".to_string(),
+                    }
+                );
+                if let Some(by) = &info.migrated_by {
+                    println!("Migrated:  by chomp {}", by);
+                }
+                println!("SQLite:    {}", info.sqlite_version);
+            }
+            return Ok(());
+        }
+        Some(Commands::Bot { action }) => return run_bot(action),
+        _ => {}
+    }
+
+    // Determine backend
+    let out = render::Renderer::detect(cli.no_color, &config)?;
+
+    let backend = if let Ok(server_url) = std::env::var("CHOMP_SERVER_URL") {
+        let auth_key = std::env::var("CHOMP_AUTH_KEY").unwrap_or_default();
+        Backend::Remote(client::RemoteClient::new(&server_url, &auth_key))
+    } else {
+        let lock_after_days = (!cli.amend).then(|| config.lock_after_days()).flatten();
+        let db = db::Database::open()?
+            .with_lock_after_days(lock_after_days)
+            .with_calorie_factors(config.calorie_factors)
+            .with_carb_mode(config.carb_mode)
+            .with_incomplete_days(config.incomplete_days);
+        db.init()?;
+        if shows_goals(&cli) {
+            // A broken [program] shouldn't stop the command, as with hooks
+            if let Err(e) = program::apply(&mut config, &db) {
+                eprintln!("Warning: program targets not applied: {}", e);
+            }
+        }
+        if rolls_over(&cli) {
+            roll_over(&db, &out);
+        }
+        Backend::Local(db)
+    };
+
+    match cli.command {
+        Some(Commands::Add {
+            name,
+            variant,
+            protein,
+            fat,
+            carbs,
             per,
             calories,
+            fiber,
+            micros,
             alias,
+            from_label,
+            per_100g,
+            yes,
         }) => {
-            let cals = calories.unwrap_or(protein * 4.0 + fat * 9.0 + carbs * 4.0);
+            let name = match &variant {
+                Some(variant) => food::variant_name(&name, variant),
+                None => name,
+            };
+            let (protein, fat, carbs, per, calories) = if from_label {
+                match read_label(&name, per_100g, yes, &config.calorie_factors)? {
+                    Some((protein, fat, carbs, per, calories)) => {
+                        (protein, fat, carbs, Some(per), calories)
+                    }
+                    None => return Ok(()),
+                }
+            } else {
+                // clap requires all three unless --from-label
+                (
+                    protein.unwrap(),
+                    fat.unwrap(),
+                    carbs.unwrap(),
+                    per,
+                    calories,
+                )
+            };
+            let cals = calories.unwrap_or(config.calorie_factors.calories_from_macros(
+                protein,
+                fat,
+                carbs,
+                fiber,
+                config.carb_mode,
+            ));
             match &backend {
                 Backend::Local(db) => {
-                    let food = food::Food::new(&name, protein, fat, carbs, cals, &per, alias);
+                    let mut food = food::Food::new(
+                        &name,
+                        protein,
+                        fat,
+                        carbs,
+                        cals,
+                        per.as_deref().unwrap_or("100g"),
+                        alias,
+                    );
+                    food.fiber = fiber;
+                    food.micros = micros.micros();
+                    let assumed = per.is_none().then(|| food.assume_serving()).flatten();
+                    let interactive =
+                        !from_label && !yes && !cli.json && std::io::stdin().is_terminal();
+                    if !resolve_clashes(db, &mut food, interactive)? {
+                        println!("Not saved.");
+                        return Ok(());
+                    }
                     db.add_food(&food)?;
                     if cli.json {
                         println!("{}", serde_json::to_string_pretty(&food)?);
                     } else {
-                        println!(
-                            "Added: {} ({:.0}p/{:.0}f/{:.0}c per {})",
-                            name, protein, fat, carbs, per
-                        );
+                        println!("{}", out.food_saved("food-added", &food));
+                        if let Some(category) = assumed {
+                            println!(
+                                "Assumed a {} serving of {}; pass --per to set your own.",
+                                category, food.serving
+                            );
+                        }
                     }
                 }
                 Backend::Remote(client) => {
-                    let food =
-                        client.add_food(&name, protein, fat, carbs, &per, calories, alias)?;
+                    let (food, clashes) = client.add_food(
+                        &name,
+                        protein,
+                        fat,
+                        carbs,
+                        per.as_deref(),
+                        calories,
+                        fiber,
+                        &micros.micros(),
+                        alias,
+                    )?;
+                    for clash in &clashes {
+                        eprintln!("Warning: {}", clash);
+                    }
                     if cli.json {
                         println!("{}", serde_json::to_string_pretty(&food)?);
                     } else {
-                        println!(
-                            "Added: {} ({:.0}p/{:.0}f/{:.0}c per {})",
-                            food.name, food.protein, food.fat, food.carbs, food.serving
-                        );
+                        println!("{}", out.food_saved("food-added", &food));
                     }
                 }
             }
@@ -262,10 +1335,78 @@ fn main() -> Result<()> {
                 println!("{}", serde_json::to_string_pretty(&results)?);
             } else {
                 for food in results {
-                    println!(
-                        "{}: {:.0}p/{:.0}f/{:.0}c per {}",
-                        food.name, food.protein, food.fat, food.carbs, food.serving
-                    );
+                    println!("{}", out.food(&food));
+                }
+            }
+        }
+        Some(Commands::Show { food: name }) => {
+            let db = match &backend {
+                Backend::Local(db) => db,
+                Backend::Remote(_) => anyhow::bail!("show is only available in local mode"),
+            };
+            let card = match db.food_card(&name)? {
+                Some(card) => card,
+                None => return Err(logging::FoodNotFound::lookup(db, &name)?.into()),
+            };
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&card)?);
+            } else {
+                println!("{}", card::render(&card));
+            }
+        }
+        Some(Commands::Food {
+            action: FoodAction::Stats { name },
+        }) => {
+            let db = match &backend {
+                Backend::Local(db) => db,
+                Backend::Remote(_) => anyhow::bail!("Food stats are only available in local mode"),
+            };
+            let stats = match db.food_stats(&name)? {
+                Some(stats) => stats,
+                None => return Err(logging::FoodNotFound::lookup(db, &name)?.into()),
+            };
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&stats)?);
+            } else {
+                println!("{}", card::render_stats(&stats));
+            }
+        }
+        Some(Commands::Find {
+            protein,
+            fat,
+            carbs,
+            calories,
+            fiber,
+            sugar,
+            sodium,
+            saturated_fat,
+            per,
+        }) => {
+            let db = match &backend {
+                Backend::Local(db) => db,
+                Backend::Remote(_) => anyhow::bail!("find is only available in local mode"),
+            };
+            let filters = [
+                ("protein", protein),
+                ("fat", fat),
+                ("carbs", carbs),
+                ("calories", calories),
+                ("fiber", fiber),
+                ("sugar", sugar),
+                ("sodium", sodium),
+                ("saturated_fat", saturated_fat),
+            ]
+            .into_iter()
+            .filter_map(|(column, expr)| expr.map(|e| food::MacroFilter::parse(column, &e)))
+            .collect::<Result<Vec<_>>>()?;
+            let results = db.find_foods(&filters, per.as_deref())?;
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&results)?);
+            } else if results.is_empty() {
+                println!("No foods match");
+            } else {
+                for food in results {
+                    println!("{}", out.food(&food));
                 }
             }
         }
@@ -282,6 +1423,7 @@ fn main() -> Result<()> {
                 Backend::Local(db) => db.get_today_caffeine()?,
                 Backend::Remote(client) => client.get_today_caffeine()?,
             };
+            let meals = today_meals(&backend, &config)?;
             if cli.json {
                 println!(
                     "{}",
@@ -289,44 +1431,123 @@ fn main() -> Result<()> {
                         "macros": totals,
                         "water": water,
                         "caffeine": caffeine,
+                        "meals": meals,
                     }))?
                 );
             } else {
-                println!(
-                    "Today: {:.0}p / {:.0}f / {:.0}c — {:.0} kcal",
-                    totals.protein, totals.fat, totals.carbs, totals.calories
-                );
-                println!(
-                    "       {:.0}ml water / {:.0}mg caffeine",
-                    water.total_ml, caffeine.total_mg
-                );
+                let targets = status::Targets::resolve(&config);
+                println!("{}", out.today(&totals, &water, &caffeine, &targets));
+                let progress = goal::progress(&totals, &goal::resolve(&config));
+                if !progress.is_empty() {
+                    println!("{}", out.goal_progress(&progress));
+                }
+                if !meals.is_empty() {
+                    println!("{}", out.meal_subtotals(&meals));
+                }
             }
         }
-        Some(Commands::History { days }) => {
-            let entries = match &backend {
-                Backend::Local(db) => db.get_history(days)?,
-                Backend::Remote(client) => client.get_history(days)?,
+        Some(Commands::Status {
+            format,
+            calories,
+            protein,
+        }) => {
+            let totals = match &backend {
+                Backend::Local(db) => db.get_today_totals()?,
+                Backend::Remote(client) => client.get_today_totals()?,
+            };
+            let targets = status::Targets {
+                calories,
+                protein,
+                water: None,
+            }
+            .or_config(&config);
+            println!("{}", status::render(&format, &totals, &targets)?);
+        }
+        Some(Commands::History {
+            days,
+            limit,
+            offset,
+            today,
+            after,
+            before,
+        }) => {
+            let days = if today { 0 } else { days };
+            let window = db::TimeWindow::parse(after.as_deref(), before.as_deref())?;
+            let page = match &backend {
+                Backend::Local(db) => db.get_history_page(days, window, limit, offset)?,
+                Backend::Remote(client) => client.get_history_page(days, window, limit, offset)?,
             };
             if cli.json {
-                println!("{}", serde_json::to_string_pretty(&entries)?);
+                println!("{}", serde_json::to_string_pretty(&page.entries)?);
+            } else if meal::any(&page.entries) {
+                for group in meal::group(page.entries, &config.meals()) {
+                    println!("{}", out.meal_heading(&group));
+                    for entry in &group.entries {
+                        println!("  {}", out.history_entry(entry));
+                    }
+                }
             } else {
-                for entry in entries {
-                    println!(
-                        "{} | {} {} | {:.0}p/{:.0}f/{:.0}c",
-                        entry.date,
-                        entry.amount,
-                        entry.food_name,
-                        entry.protein,
-                        entry.fat,
-                        entry.carbs
-                    );
+                for entry in &page.entries {
+                    println!("{}", out.history_entry(entry));
+                }
+            }
+            if let (Some(next), Some(limit)) = (page.next_offset, limit) {
+                let mut window_args = String::new();
+                if let Some(after) = window.after {
+                    window_args.push_str(&format!(" --after {}", after.format("%H:%M")));
+                }
+                if let Some(before) = window.before {
+                    window_args.push_str(&format!(" --before {}", before.format("%H:%M")));
                 }
+                eprintln!(
+                    "More entries: chomp history --days {}{} --limit {} --offset {}",
+                    days, window_args, limit, next
+                );
             }
         }
-        Some(Commands::Export { format }) => match &backend {
+        Some(Commands::Export {
+            format,
+            output,
+            anonymize,
+            columns,
+            delimiter,
+        }) => match &backend {
             Backend::Local(db) => match format.as_str() {
-                "csv" => db.export_csv()?,
-                "json" => db.export_json()?,
+                "csv" => {
+                    let mut export = db::CsvExport {
+                        delimiter,
+                        anonymize,
+                        ..Default::default()
+                    };
+                    if !columns.is_empty() {
+                        export.columns = columns;
+                    }
+                    match db.export_csv(std::io::stdout().lock(), &export) {
+                        Err(e) if is_broken_pipe(&e) => {}
+                        result => result?,
+                    }
+                }
+                "json" => db.export_json(anonymize)?,
+                "ndjson" => {
+                    let out = std::io::BufWriter::new(std::io::stdout().lock());
+                    match db.export_ndjson(out, anonymize) {
+                        Err(e) if is_broken_pipe(&e) => {}
+                        result => result?,
+                    }
+                }
+                #[cfg(feature = "parquet")]
+                "parquet" => {
+                    let path = output.ok_or_else(|| {
+                        anyhow::anyhow!("Parquet export needs a file: --output log.parquet")
+                    })?;
+                    let rows = db.export_parquet(&path, anonymize)?;
+                    eprintln!("Wrote {} log entries to {}", rows, path.display());
+                }
+                #[cfg(not(feature = "parquet"))]
+                "parquet" => {
+                    let _ = (output, anonymize);
+                    anyhow::bail!("Parquet export requires building with --features parquet");
+                }
                 _ => anyhow::bail!("Unknown format: {}", format),
             },
             Backend::Remote(_) => {
@@ -338,162 +1559,1520 @@ fn main() -> Result<()> {
             protein,
             fat,
             carbs,
+            fiber,
             per,
             calories,
+            micros,
         }) => match &backend {
             Backend::Local(db) => {
-                db.edit_food(&name, protein, fat, carbs, per.as_deref(), calories)?;
+                let refreshed = db.edit_food(
+                    &name,
+                    protein,
+                    fat,
+                    carbs,
+                    fiber,
+                    per.as_deref(),
+                    calories,
+                    &micros.micros(),
+                )?;
                 let food = db.search_food(&name)?;
                 if let Some(f) = food {
-                    println!(
-                        "Updated: {} ({}p/{}f/{}c per {})",
-                        f.name, f.protein, f.fat, f.carbs, f.serving
-                    );
+                    println!("{}", out.food_saved("food-updated", &f));
+                }
+                if !refreshed.is_empty() {
+                    println!("Recalculated {}", refreshed.join(", "));
                 }
             }
             Backend::Remote(client) => {
-                let food =
-                    client.edit_food(&name, protein, fat, carbs, per.as_deref(), calories)?;
+                let food = client.edit_food(
+                    &name,
+                    protein,
+                    fat,
+                    carbs,
+                    fiber,
+                    per.as_deref(),
+                    calories,
+                    &micros.micros(),
+                )?;
                 if let Some(f) = food {
-                    println!(
-                        "Updated: {} ({}p/{}f/{}c per {})",
-                        f.name, f.protein, f.fat, f.carbs, f.serving
-                    );
+                    println!("{}", out.food_saved("food-updated", &f));
                 }
             }
         },
-        Some(Commands::Delete { name }) => {
+        Some(Commands::Delete {
+            name,
+            cascade,
+            keep_history,
+        }) => {
+            let mode = if cascade {
+                db::DeleteMode::Cascade
+            } else if keep_history {
+                db::DeleteMode::KeepHistory
+            } else {
+                db::DeleteMode::Refuse
+            };
             match &backend {
-                Backend::Local(db) => db.delete_food(&name)?,
-                Backend::Remote(client) => client.delete_food(&name)?,
+                Backend::Local(db) => db.delete_food(&name, mode)?,
+                Backend::Remote(client) => client.delete_food(&name, mode)?,
             }
-            println!("Deleted: {}", name);
-        }
-        Some(Commands::Unlog { id }) => {
-            let entry = match &backend {
-                Backend::Local(db) => db.delete_log_entry(id)?,
-                Backend::Remote(client) => client.delete_log_entry(id)?,
-            };
-            if cli.json {
-                println!("{}", serde_json::to_string_pretty(&entry)?);
-            } else {
-                println!(
-                    "Deleted log entry: {} {} — {:.0}p/{:.0}f/{:.0}c",
-                    entry.amount, entry.food_name, entry.protein, entry.fat, entry.carbs
+            println!("{}", out.food_deleted(&name));
+            if matches!(backend, Backend::Local(_)) {
+                eprintln!(
+                    "Kept in the trash for {} days. Undo with: chomp trash restore \"{}\"",
+                    db::TRASH_DAYS,
+                    name
                 );
             }
         }
-        Some(Commands::UnlogLast) => {
-            let entry = match &backend {
-                Backend::Local(db) => db.delete_last_log_entry()?,
-                Backend::Remote(client) => client.delete_last_log_entry()?,
+        Some(Commands::Trash { action }) => {
+            let db = match &backend {
+                Backend::Local(db) => db,
+                Backend::Remote(_) => anyhow::bail!("The trash is only available in local mode"),
             };
-            if cli.json {
-                println!("{}", serde_json::to_string_pretty(&entry)?);
-            } else {
-                println!(
-                    "Deleted last log entry: {} {} — {:.0}p/{:.0}f/{:.0}c",
-                    entry.amount, entry.food_name, entry.protein, entry.fat, entry.carbs
-                );
+            match action {
+                TrashAction::List => {
+                    let trash = db.list_trash()?;
+                    if cli.json {
+                        println!("{}", serde_json::to_string_pretty(&trash)?);
+                    } else if trash.is_empty() {
+                        println!("The trash is empty");
+                    } else {
+                        for food in trash {
+                            println!(
+                                "{}  deleted {}  ({} log entries)",
+                                food.name, food.deleted_at, food.log_entries
+                            );
+                        }
+                    }
+                }
+                TrashAction::Restore { name } => {
+                    let food = db.restore_food(&name)?;
+                    if cli.json {
+                        println!("{}", serde_json::to_string_pretty(&food)?);
+                    } else {
+                        println!(
+                            "Restored {} with {} log entries",
+                            food.name, food.log_entries
+                        );
+                    }
+                }
             }
         }
-        Some(Commands::EditLog {
-            id,
-            amount,
-            protein,
-            fat,
-            carbs,
-        }) => {
-            let entry = match &backend {
-                Backend::Local(db) => db.edit_log_entry(id, amount, protein, fat, carbs)?,
-                Backend::Remote(client) => {
-                    client.edit_log_entry(id, amount, protein, fat, carbs)?
+        Some(Commands::Fav { action }) => {
+            let db = match &backend {
+                Backend::Local(db) => db,
+                Backend::Remote(_) => anyhow::bail!("Favorites are only available in local mode"),
+            };
+            match action {
+                FavAction::Add { input, slug } => {
+                    let input = input.join(" ");
+                    for item in logging::split_items(db, &input)? {
+                        logging::resolve(db, &item, false)?;
+                    }
+                    let favorite = db.set_favorite(&input, slug.as_deref())?;
+                    if cli.json {
+                        println!("{}", serde_json::to_string_pretty(&favorite)?);
+                    } else {
+                        println!("Favorite {}: {}", favorite.slug, favorite.input);
+                        println!("Log it with: POST /quicklog/fav/{}", favorite.slug);
+                    }
+                }
+                FavAction::List => {
+                    let favorites = db.list_favorites()?;
+                    if cli.json {
+                        println!("{}", serde_json::to_string_pretty(&favorites)?);
+                    } else if favorites.is_empty() {
+                        println!("No favorites; add one with: chomp fav add \"protein shake 1 scoop\" --slug morning-shake");
+                    } else {
+                        let width = favorites.iter().map(|f| f.slug.len()).max().unwrap_or(0);
+                        for favorite in favorites {
+                            println!("{:<width$}  {}", favorite.slug, favorite.input);
+                        }
+                    }
+                }
+                FavAction::Remove { slug } => {
+                    if !db.remove_favorite(&slug)? {
+                        anyhow::bail!("No favorite '{}'", slug);
+                    }
+                    println!("Removed favorite {}", slug);
                 }
+            }
+        }
+        Some(Commands::Snack { tag, limit }) => {
+            let db = match &backend {
+                Backend::Local(db) => db,
+                Backend::Remote(_) => anyhow::bail!("snack is only available in local mode"),
             };
+            let foods = db.pantry_foods(tag.as_deref())?;
+            let left = snack::remaining(&goal::resolve(&config), &db.get_today_totals()?);
+            let snacks: Vec<snack::Snack> = snack::suggest(&foods, &left, config.carb_mode)
+                .into_iter()
+                .take(limit)
+                .collect();
             if cli.json {
-                println!("{}", serde_json::to_string_pretty(&entry)?);
+                println!("{}", serde_json::to_string_pretty(&snacks)?);
+            } else if foods.is_empty() {
+                match &tag {
+                    Some(tag) => println!("Nothing tagged '{}' is in the pantry", tag),
+                    None => println!("The pantry is empty. Stock it with: chomp pantry add <food>"),
+                }
+            } else if snacks.is_empty() {
+                println!("Nothing in the pantry fits what's left today");
             } else {
-                println!(
-                    "Updated log entry: {} {} — {:.0}p/{:.0}f/{:.0}c",
-                    entry.amount, entry.food_name, entry.protein, entry.fat, entry.carbs
-                );
+                for s in &snacks {
+                    println!(
+                        "{} {} — {:.0}p/{:.0}f/{:.0}c, {:.0} kcal",
+                        s.food,
+                        s.amount,
+                        s.macros.protein,
+                        s.macros.fat,
+                        s.macros.carbs,
+                        s.macros.calories
+                    );
+                }
             }
         }
-        Some(Commands::Compound { name, items }) => match &backend {
-            Backend::Local(db) => {
-                let parts: Vec<(String, String)> = items
-                    .split('+')
-                    .map(|part| {
-                        let part = part.trim();
-                        let words: Vec<&str> = part.split_whitespace().collect();
-                        if words.len() >= 2 {
-                            let amount = words[0].to_string();
-                            let food = words[1..].join(" ");
-                            (food, format!("{}{}", amount, "serving"))
+        Some(Commands::Pantry { action }) => {
+            let db = match &backend {
+                Backend::Local(db) => db,
+                Backend::Remote(_) => anyhow::bail!("The pantry is only available in local mode"),
+            };
+            match action {
+                PantryAction::Add { foods } => {
+                    for name in foods {
+                        let food = find_food(db, &name)?;
+                        if db.stock_food(food.id.unwrap())? {
+                            println!("Stocked {}", food.name);
                         } else {
-                            (part.to_string(), "1serving".to_string())
+                            println!("{} is already in the pantry", food.name);
                         }
-                    })
-                    .collect();
-                db.create_compound_food(&name, &parts)?;
-            }
-            Backend::Remote(_) => {
-                anyhow::bail!("Compound food creation is only available in local mode");
+                    }
+                }
+                PantryAction::Remove { foods } => {
+                    for name in foods {
+                        let food = find_food(db, &name)?;
+                        if db.unstock_food(food.id.unwrap())? {
+                            println!("Used up {}", food.name);
+                        } else {
+                            println!("{} isn't in the pantry", food.name);
+                        }
+                    }
+                }
+                PantryAction::List => {
+                    let foods = db.pantry_foods(None)?;
+                    if cli.json {
+                        let names: Vec<&str> = foods.iter().map(|f| f.name.as_str()).collect();
+                        println!("{}", serde_json::to_string_pretty(&names)?);
+                    } else if foods.is_empty() {
+                        println!("The pantry is empty. Stock it with: chomp pantry add <food>");
+                    } else {
+                        for food in foods {
+                            println!("{}", food.name);
+                        }
+                    }
+                }
             }
-        },
-        Some(Commands::Water { amount, date }) => {
-            let input = amount.join(" ");
-            if input.is_empty() {
-                // Show today's water total
-                let totals = match &backend {
-                    Backend::Local(db) => db.get_today_water()?,
-                    Backend::Remote(client) => client.get_today_water()?,
-                };
-                if cli.json {
-                    println!("{}", serde_json::to_string_pretty(&totals)?);
+        }
+        Some(Commands::Tag { food, tags, remove }) => {
+            let db = match &backend {
+                Backend::Local(db) => db,
+                Backend::Remote(_) => anyhow::bail!("Tags are only available in local mode"),
+            };
+            let food = find_food(db, &food)?;
+            let id = food.id.unwrap();
+            for tag in &tags {
+                let tag = tag.trim().to_lowercase();
+                if remove {
+                    db.untag_food(id, &tag)?;
                 } else {
-                    println!(
-                        "Water today: {:.0}ml ({:.1} oz)",
-                        totals.total_ml,
-                        totals.total_ml / 29.5735
-                    );
+                    db.tag_food(id, &tag)?;
                 }
+            }
+            let tags = db.food_tags(id)?;
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&tags)?);
+            } else if tags.is_empty() {
+                println!("{} has no tags", food.name);
             } else {
-                let ml = food::parse_water_ml(&input)
-                    .ok_or_else(|| anyhow::anyhow!("Could not parse water amount: '{}'", input))?;
-                let entry = match &backend {
-                    Backend::Local(db) => db.log_water(ml, date.as_deref())?,
-                    Backend::Remote(client) => client.log_water(ml, date.as_deref())?,
-                };
-                if cli.json {
-                    println!("{}", serde_json::to_string_pretty(&entry)?);
-                } else {
-                    println!(
-                        "Logged: {:.0}ml water ({:.1} oz)",
-                        entry.amount_ml,
-                        entry.amount_ml / 29.5735
-                    );
-                }
+                println!("{}: {}", food.name, tags.join(", "));
             }
         }
-        Some(Commands::Caffeine {
-            amount,
-            source,
-            date,
+        Some(Commands::Unit {
+            food,
+            weights,
+            remove,
+        }) => {
+            let db = match &backend {
+                Backend::Local(db) => db,
+                Backend::Remote(_) => {
+                    anyhow::bail!("Food units are only available in local mode")
+                }
+            };
+            let food = find_food(db, &food)?;
+            let id = food.id.unwrap();
+            for weight in &weights {
+                if remove {
+                    if !db.remove_food_unit(id, weight)? {
+                        println!("{} has no unit '{}'", food.name, weight.trim());
+                    }
+                } else {
+                    let (unit, grams) = food::parse_unit_weight(weight)?;
+                    db.set_food_unit(id, &unit, grams)?;
+                }
+            }
+            let units = find_food(db, &food.name)?.units;
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&units)?);
+            } else if units.is_empty() {
+                println!("{} has no units of its own", food.name);
+            } else {
+                for (unit, grams) in units {
+                    let grams = (grams * 10.0).round() / 10.0;
+                    println!("{}: 1 {} = {}g", food.name, unit, grams);
+                }
+            }
+        }
+        Some(Commands::Recurring { action }) => {
+            let db = match &backend {
+                Backend::Local(db) => db,
+                Backend::Remote(_) => {
+                    anyhow::bail!("Recurring entries are only available in local mode")
+                }
+            };
+            match action {
+                RecurringAction::Add { input, daily, auto } => {
+                    let input = input.join(" ");
+                    for item in logging::split_items(db, &input)? {
+                        logging::resolve(db, &item, false)?;
+                    }
+                    let time = db::parse_time_of_day(&daily)?;
+                    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+                    let recurring = db.add_recurring(&input, time, &today, auto)?;
+                    if cli.json {
+                        println!("{}", serde_json::to_string_pretty(&recurring)?);
+                    } else {
+                        println!(
+                            "Recurring {}: {} daily at {}",
+                            recurring.id, recurring.input, recurring.time
+                        );
+                        if recurring.auto {
+                            println!("Logged the first time chomp runs each day; remove a day's with: chomp unlog --auto");
+                        } else {
+                            println!("Log what's due with: chomp recurring run");
+                        }
+                    }
+                }
+                RecurringAction::List => {
+                    let recurring = db.list_recurring()?;
+                    if cli.json {
+                        println!("{}", serde_json::to_string_pretty(&recurring)?);
+                    } else if recurring.is_empty() {
+                        println!("No recurring entries; add one with: chomp recurring add \"creatine 5g\" --daily 08:00");
+                    } else {
+                        for r in recurring {
+                            let last = r
+                                .last_run
+                                .map(|d| format!("  (last logged for {})", d))
+                                .unwrap_or_default();
+                            let auto = if r.auto { "  auto" } else { "" };
+                            println!("{:>3}  {}  {}{}{}", r.id, r.time, r.input, auto, last);
+                        }
+                    }
+                }
+                RecurringAction::Remove { id } => {
+                    if !db.remove_recurring(id)? {
+                        anyhow::bail!("No recurring entry {}", id);
+                    }
+                    println!("Removed recurring entry {}", id);
+                }
+                RecurringAction::Run => {
+                    let ran = recurring::run(db, chrono::Local::now().naive_local())?;
+                    let mut entries = Vec::new();
+                    let mut failed = 0;
+                    for item in ran {
+                        match item.entry {
+                            Ok(entry) => {
+                                if !cli.json {
+                                    println!(
+                                        "{} {} {}: {:.0}p/{:.0}f/{:.0}c — {:.0} kcal",
+                                        item.at.format("%Y-%m-%d %H:%M"),
+                                        entry.amount,
+                                        entry.food_name,
+                                        entry.protein,
+                                        entry.fat,
+                                        entry.carbs,
+                                        entry.calories
+                                    );
+                                }
+                                entries.push(entry);
+                            }
+                            Err(e) => {
+                                eprintln!(
+                                    "Recurring {}: couldn't log {}: {}",
+                                    item.recurring, item.item, e
+                                );
+                                failed += 1;
+                            }
+                        }
+                    }
+                    if cli.json {
+                        println!("{}", serde_json::to_string_pretty(&entries)?);
+                    } else if entries.is_empty() && failed == 0 {
+                        println!("Nothing due");
+                    }
+                    if failed > 0 {
+                        anyhow::bail!(
+                            "{} of {} items couldn't be logged",
+                            failed,
+                            entries.len() + failed
+                        );
+                    }
+                }
+            }
+        }
+        Some(Commands::Goal { action }) => {
+            match action {
+                GoalAction::Set {
+                    calories,
+                    protein,
+                    fat,
+                    carbs,
+                } => {
+                    // Reload: `config` holds today's goals, with any program applied
+                    let mut saved = config::Config::load()?;
+                    for (metric, value) in [
+                        (config::Metric::Calories, calories),
+                        (config::Metric::Protein, protein),
+                        (config::Metric::Fat, fat),
+                        (config::Metric::Carbs, carbs),
+                    ] {
+                        if let Some(value) = value {
+                            if value <= 0.0 {
+                                anyhow::bail!("The {} goal must be more than 0", metric.name());
+                            }
+                            saved.goals.set(metric, Some(value));
+                        }
+                    }
+                    let path = saved.save()?;
+                    if cli.json {
+                        println!("{}", serde_json::to_string_pretty(&saved.goals)?);
+                    } else {
+                        println!("Goals: {}", describe_goals(&saved.goals));
+                        println!("Saved to {}", path.display());
+                    }
+                }
+                GoalAction::Show => {
+                    let goals = goal::resolve(&config);
+                    let totals = match &backend {
+                        Backend::Local(db) => db.get_today_totals()?,
+                        Backend::Remote(client) => client.get_today_totals()?,
+                    };
+                    let progress = goal::progress(&totals, &goals);
+                    if cli.json {
+                        println!(
+                            "{}",
+                            serde_json::to_string_pretty(&serde_json::json!({
+                                "goals": goals,
+                                "progress": progress,
+                            }))?
+                        );
+                    } else if progress.is_empty() {
+                        println!("No goals set; set them with: chomp goal set --calories 2200 --protein 160");
+                    } else {
+                        println!("Goals: {}", describe_goals(&goals));
+                        println!("{}", out.goal_progress(&progress));
+                    }
+                }
+                GoalAction::Clear { metrics } => {
+                    let mut saved = config::Config::load()?;
+                    let metrics = if metrics.is_empty() {
+                        goal::METRICS.to_vec()
+                    } else {
+                        metrics
+                    };
+                    for metric in metrics {
+                        saved.goals.set(metric, None);
+                    }
+                    saved.save()?;
+                    if cli.json {
+                        println!("{}", serde_json::to_string_pretty(&saved.goals)?);
+                    } else if goal::METRICS.iter().all(|m| saved.goals.get(*m).is_none()) {
+                        println!("Cleared all goals");
+                    } else {
+                        println!("Goals: {}", describe_goals(&saved.goals));
+                    }
+                }
+            }
+        }
+        Some(Commands::Share { action }) => {
+            let db = match &backend {
+                Backend::Local(db) => db,
+                Backend::Remote(_) => anyhow::bail!("Sharing is only available in local mode"),
+            };
+            match action {
+                ShareAction::Create {
+                    days,
+                    scope,
+                    expires_in,
+                    base_url,
+                } => {
+                    let share = db.create_share(&scope, days, expires_in)?;
+                    let link = format!(
+                        "{}/share/{}",
+                        base_url.as_deref().unwrap_or("").trim_end_matches('/'),
+                        share.token
+                    );
+                    if cli.json {
+                        let mut value = serde_json::to_value(&share)?;
+                        value["link"] = serde_json::json!(link);
+                        println!("{}", serde_json::to_string_pretty(&value)?);
+                    } else {
+                        println!("Share link: {}", link);
+                        println!(
+                            "Read-only, last {} days{}; revoke with: chomp share revoke {}",
+                            share.days,
+                            share
+                                .expires_at
+                                .as_ref()
+                                .map(|at| format!(", expires {}", at))
+                                .unwrap_or_default(),
+                            share.token
+                        );
+                    }
+                }
+                ShareAction::List => {
+                    let shares = db.list_shares()?;
+                    if cli.json {
+                        println!("{}", serde_json::to_string_pretty(&shares)?);
+                    } else if shares.is_empty() {
+                        println!("No share links");
+                    } else {
+                        for share in shares {
+                            let scopes: Vec<&str> = share.scopes.iter().map(|s| s.name()).collect();
+                            let status = match (&share.revoked_at, &share.expires_at) {
+                                (Some(at), _) => format!("  revoked {}", at),
+                                (None, Some(at)) if !share.is_active() => {
+                                    format!("  expired {}", at)
+                                }
+                                (None, Some(at)) => format!("  expires {}", at),
+                                (None, None) => String::new(),
+                            };
+                            println!(
+                                "{}  {}  last {} days  created {}{}",
+                                share.token,
+                                scopes.join(","),
+                                share.days,
+                                share.created_at,
+                                status
+                            );
+                        }
+                    }
+                }
+                ShareAction::Revoke { token, all } => {
+                    let revoked = match token {
+                        Some(token) if !all => db.revoke_share(&token)?,
+                        _ => db.revoke_all_shares()?,
+                    };
+                    if revoked == 0 {
+                        anyhow::bail!("No active share link matched");
+                    }
+                    println!(
+                        "Revoked {} share link{}",
+                        revoked,
+                        if revoked == 1 { "" } else { "s" }
+                    );
+                }
+            }
+        }
+        Some(Commands::Unlog { id: None, .. }) => {
+            let db = match &backend {
+                Backend::Local(db) => db,
+                Backend::Remote(_) => {
+                    anyhow::bail!("Recurring entries are only available in local mode")
+                }
+            };
+            let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+            let entries = db.delete_recurring_entries(&today)?;
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&entries)?);
+            } else if entries.is_empty() {
+                println!("Nothing was auto-logged today");
+            }
+            for entry in entries.iter().filter(|_| !cli.json) {
+                println!("{}", out.log_entry("deleted-log-entry", entry));
+            }
+        }
+        Some(Commands::Unlog { id: Some(id), .. }) => {
+            let entry = match &backend {
+                Backend::Local(db) => db.delete_log_entry(id)?,
+                Backend::Remote(client) => client.delete_log_entry(id)?,
+            };
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&entry)?);
+            } else {
+                println!("{}", out.log_entry("deleted-log-entry", &entry));
+            }
+        }
+        Some(Commands::UnlogLast) => {
+            let entry = match &backend {
+                Backend::Local(db) => db.delete_last_log_entry()?,
+                Backend::Remote(client) => client.delete_last_log_entry()?,
+            };
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&entry)?);
+            } else {
+                println!("{}", out.log_entry("deleted-last-log-entry", &entry));
+            }
+        }
+        Some(Commands::Undo { window }) => {
+            let undone = match &backend {
+                Backend::Local(db) => db.undo_last(window)?,
+                Backend::Remote(client) => client.undo_last(window)?,
+            };
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&undone)?);
+            } else {
+                for change in &undone {
+                    println!("{}", change.description);
+                }
+            }
+        }
+        Some(Commands::EditLog {
+            id,
+            amount,
+            protein,
+            fat,
+            carbs,
+        }) => {
+            let entry = match &backend {
+                Backend::Local(db) => db.edit_log_entry(id, amount, protein, fat, carbs)?,
+                Backend::Remote(client) => {
+                    client.edit_log_entry(id, amount, protein, fat, carbs)?
+                }
+            };
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&entry)?);
+            } else {
+                println!("{}", out.log_entry("updated-log-entry", &entry));
+            }
+        }
+        Some(Commands::Compound { name, items }) => match &backend {
+            Backend::Local(db) => {
+                let parts: Vec<(String, String)> = items
+                    .split('+')
+                    .map(|part| {
+                        let part = part.trim();
+                        let words: Vec<&str> = part.split_whitespace().collect();
+                        if words.len() >= 2 {
+                            let amount = words[0].to_string();
+                            let food = words[1..].join(" ");
+                            (food, format!("{}{}", amount, "serving"))
+                        } else {
+                            (part.to_string(), "1serving".to_string())
+                        }
+                    })
+                    .collect();
+                let total = db.create_compound_food(&name, &parts)?;
+                println!(
+                    "Created compound food '{}': {:.0}p/{:.0}f/{:.0}c — {:.0} kcal",
+                    name, total.protein, total.fat, total.carbs, total.calories
+                );
+            }
+            Backend::Remote(_) => {
+                anyhow::bail!("Compound food creation is only available in local mode");
+            }
+        },
+        Some(Commands::Aliases { action }) => match action {
+            AliasesAction::Suggest { yes, window } => match &backend {
+                Backend::Local(db) => suggest_aliases(db, window, yes, cli.json)?,
+                Backend::Remote(_) => {
+                    anyhow::bail!("Alias suggestions are only available in local mode");
+                }
+            },
+            AliasesAction::Export { output } => match &backend {
+                Backend::Local(db) => match output {
+                    Some(path) => {
+                        let file = std::fs::File::create(&path).map_err(|e| {
+                            anyhow::anyhow!("Failed to create {}: {}", path.display(), e)
+                        })?;
+                        let count = db.export_aliases_csv(file)?;
+                        eprintln!("Wrote {} aliases to {}", count, path.display());
+                    }
+                    None => match db.export_aliases_csv(std::io::stdout().lock()) {
+                        Err(e) if is_broken_pipe(&e) => {}
+                        result => {
+                            result?;
+                        }
+                    },
+                },
+                Backend::Remote(_) => {
+                    anyhow::bail!("Alias export is only available in local mode");
+                }
+            },
+            AliasesAction::Import { path } => match &backend {
+                Backend::Local(db) => {
+                    let data = std::fs::read(&path)
+                        .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?;
+                    let imported = db.import_aliases_csv(&data)?;
+                    if cli.json {
+                        println!("{}", serde_json::to_string_pretty(&imported)?);
+                    } else {
+                        println!(
+                            "Imported {} aliases ({} already present)",
+                            imported.added, imported.existing
+                        );
+                        if !imported.conflicts.is_empty() {
+                            eprintln!(
+                                "Skipped aliases that already name another food: {}",
+                                imported.conflicts.join(", ")
+                            );
+                        }
+                        if !imported.missing_foods.is_empty() {
+                            eprintln!(
+                                "Skipped aliases for unknown foods: {}",
+                                imported.missing_foods.join(", ")
+                            );
+                        }
+                    }
+                }
+                Backend::Remote(_) => {
+                    anyhow::bail!("Alias import is only available in local mode");
+                }
+            },
+        },
+        Some(Commands::Recipe { action }) => match action {
+            RecipeAction::Add {
+                name,
+                items,
+                servings,
+                yield_weight,
+            } => match &backend {
+                Backend::Local(db) => {
+                    let recipe = food::RecipeYield {
+                        servings,
+                        grams: yield_weight.as_deref().map(parse_weight).transpose()?,
+                    };
+                    let serving = recipe::add(db, &name, &items, recipe)?;
+                    print_recipe(&name, &recipe, &serving, "Created recipe", cli.json)?;
+                }
+                Backend::Remote(_) => {
+                    anyhow::bail!("Recipes are only available in local mode");
+                }
+            },
+            RecipeAction::Yield {
+                name,
+                weight,
+                servings,
+            } => match &backend {
+                Backend::Local(db) => {
+                    let current = db.recipe_yield(&name)?.ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "'{}' is not a recipe. Create it with: chomp recipe add",
+                            name
+                        )
+                    })?;
+                    let recipe = food::RecipeYield {
+                        servings: servings.unwrap_or(current.servings),
+                        grams: Some(parse_weight(&weight)?),
+                    };
+                    let serving = db.set_recipe_yield(&name, recipe)?;
+                    print_recipe(&name, &recipe, &serving, "Updated", cli.json)?;
+                }
+                Backend::Remote(_) => {
+                    anyhow::bail!("Recipes are only available in local mode");
+                }
+            },
+            RecipeAction::Import { url, name, yes } => match &backend {
+                Backend::Local(db) => recipe::import(db, &url, name.as_deref(), yes)?,
+                Backend::Remote(_) => {
+                    anyhow::bail!("Recipe import is only available in local mode");
+                }
+            },
+        },
+        Some(Commands::Prep {
+            name,
+            portions,
+            container_weight,
+            servings,
+        }) => {
+            let container_grams = parse_weight(&container_weight)?;
+            let batch = match &backend {
+                Backend::Local(db) => {
+                    db.create_prep_batch(&name, servings, portions, container_grams)?
+                }
+                Backend::Remote(_) => {
+                    anyhow::bail!("Meal prep is only available in local mode");
+                }
+            };
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&batch)?);
+            } else {
+                let per = batch.macros_for(batch.container_grams);
+                println!(
+                    "Prepped {}: {} containers × {:.0}g ({:.0}g cooked)",
+                    batch.food_name,
+                    batch.portions,
+                    batch.container_grams,
+                    batch.total_grams()
+                );
+                println!(
+                    "Per container: {:.0}p/{:.0}f/{:.0}c — {:.0} kcal. Log with: chomp {} 1 container, or by weight",
+                    per.protein, per.fat, per.carbs, per.calories, batch.food_name
+                );
+            }
+        }
+        Some(Commands::Fit {
+            food: name,
+            limit,
+            budget,
+        }) => {
+            let db = match &backend {
+                Backend::Local(db) => db,
+                Backend::Remote(_) => anyhow::bail!("fit is only available in local mode"),
+            };
+            let food = match db.get_food_by_name(&name)? {
+                Some(food) => food,
+                None => return Err(logging::FoodNotFound::lookup(db, &name)?.into()),
+            };
+            let goal = budget
+                .or(goal::resolve(&config).get(limit))
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "No {} goal set. Add `{} = ...` under [goals] in the config, or pass --budget",
+                        limit.name(),
+                        limit.name()
+                    )
+                })?;
+            let totals = db.get_today_totals()?;
+            let remaining = goal - limit.value(&totals);
+            let fit = fit::solve(&food, limit, remaining, config.carb_mode);
+            if cli.json {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&serde_json::json!({
+                        "food": food.name,
+                        "limit": limit,
+                        "remaining": remaining,
+                        "fit": fit,
+                    }))?
+                );
+            } else {
+                match fit {
+                    None => println!(
+                        "{} has no {}, so any amount fits your remaining {} budget",
+                        food.name,
+                        limit.name(),
+                        limit.name()
+                    ),
+                    Some(_) if remaining <= 0.0 => println!(
+                        "No {} left today: {} of {}",
+                        limit.name(),
+                        metric_amount(limit, limit.value(&totals)),
+                        metric_amount(limit, goal)
+                    ),
+                    Some(fit) => {
+                        println!(
+                            "Up to {} of {} ({} left of {})",
+                            fit.describe(&food),
+                            food.name,
+                            metric_amount(limit, remaining),
+                            metric_amount(limit, goal)
+                        );
+                        println!(
+                            "  {:.0}p/{:.0}f/{:.0}c — {:.0} kcal. Log with: chomp {} {}",
+                            fit.macros.protein,
+                            fit.macros.fat,
+                            fit.macros.carbs,
+                            fit.macros.calories,
+                            food.name,
+                            fit.amount
+                        );
+                    }
+                }
+            }
+        }
+        Some(Commands::Close {
+            date,
+            satiety,
+            energy,
+            reopen,
+        }) => match &backend {
+            Backend::Local(db) => {
+                let date =
+                    date.unwrap_or_else(|| chrono::Local::now().format("%Y-%m-%d").to_string());
+                if reopen {
+                    db.reopen_day(&date)?;
+                    println!("Reopened {}", date);
+                } else {
+                    let totals = db.get_totals_for_date(&date)?;
+                    let water = db.get_water_for_date(&date)?;
+                    let caffeine = db.get_caffeine_for_date(&date)?;
+                    // The goals as written, in effect on the day being closed
+                    let mut written = config::Config::load()?;
+                    let day = chrono::NaiveDate::parse_from_str(&date, "%Y-%m-%d")?;
+                    let (goals, scheduled) = program::goals_on(db, &written, day)?;
+                    written.goals = goals;
+                    let targets = status::Targets::resolve(&written);
+                    if !cli.json {
+                        match &scheduled {
+                            Some(name) => println!("{} ({})", date, name),
+                            None => println!("{}", date),
+                        }
+                        println!("{}", out.today(&totals, &water, &caffeine, &targets));
+                    }
+                    let satiety = match satiety {
+                        Some(n) => Some(n),
+                        None if !cli.json => ask_rating("Satiety (1-5, Enter to skip)?")?,
+                        None => None,
+                    };
+                    let energy = match energy {
+                        Some(n) => Some(n),
+                        None if !cli.json => ask_rating("Energy (1-5, Enter to skip)?")?,
+                        None => None,
+                    };
+                    db.close_day(&date, satiety, energy)?;
+                    if cli.json {
+                        println!(
+                            "{}",
+                            serde_json::to_string_pretty(&serde_json::json!({
+                                "review": db.day_review(&date)?,
+                                "macros": totals,
+                                "water": water,
+                                "caffeine": caffeine,
+                            }))?
+                        );
+                    } else {
+                        println!(
+                            "Closed {}; reopen with: chomp close --reopen --date {}",
+                            date, date
+                        );
+                    }
+                }
+            }
+            Backend::Remote(_) => {
+                anyhow::bail!("Closing days is only available in local mode");
+            }
+        },
+        Some(Commands::Recache) => match &backend {
+            Backend::Local(db) => {
+                let days = db.rebuild_daily_totals()?;
+                println!("Rebuilt daily totals for {} days", days);
+            }
+            Backend::Remote(_) => {
+                anyhow::bail!("Recache is only available in local mode");
+            }
+        },
+        Some(Commands::Water { amount, date }) => {
+            let input = amount.join(" ");
+            if input.is_empty() {
+                // Show today's water total
+                let totals = match &backend {
+                    Backend::Local(db) => db.get_today_water()?,
+                    Backend::Remote(client) => client.get_today_water()?,
+                };
+                if cli.json {
+                    println!("{}", serde_json::to_string_pretty(&totals)?);
+                } else {
+                    println!(
+                        "{}",
+                        out.water_today(&totals, status::Targets::resolve(&config).water)
+                    );
+                }
+            } else {
+                let ml = food::parse_water_ml(&input)
+                    .ok_or_else(|| anyhow::anyhow!("Could not parse water amount: '{}'", input))?;
+                let entry = match &backend {
+                    Backend::Local(db) => db.log_water(ml, date.as_deref())?,
+                    Backend::Remote(client) => client.log_water(ml, date.as_deref())?,
+                };
+                if cli.json {
+                    println!("{}", serde_json::to_string_pretty(&entry)?);
+                } else {
+                    println!("{}", out.water_logged(&entry));
+                }
+            }
+        }
+        Some(Commands::Caffeine {
+            amount,
+            source,
+            date,
         }) => {
             let entry = match &backend {
                 Backend::Local(db) => db.log_caffeine(amount, &source, date.as_deref())?,
                 Backend::Remote(client) => client.log_caffeine(amount, &source, date.as_deref())?,
             };
             if cli.json {
-                println!("{}", serde_json::to_string_pretty(&entry)?);
+                println!("{}", serde_json::to_string_pretty(&entry)?);
+            } else {
+                println!("{}", out.caffeine_logged(&entry));
+            }
+        }
+        Some(Commands::Trend {
+            years,
+            resolution,
+            smooth,
+            centered,
+        }) => match &backend {
+            Backend::Local(db) => {
+                let since = chrono::Local::now()
+                    .date_naive()
+                    .checked_sub_months(chrono::Months::new(years.saturating_mul(12)))
+                    .unwrap_or(chrono::NaiveDate::MIN)
+                    .format("%Y-%m-%d")
+                    .to_string();
+                let mut rollups = db.rollups(&since, &resolution)?;
+                if let Some(window) = smooth {
+                    rollups = report::smooth(&rollups, &resolution, window, centered);
+                }
+                let adjustments = db.target_adjustments()?;
+                if cli.json {
+                    println!("{}", serde_json::to_string_pretty(&rollups)?);
+                } else if rollups.is_empty() {
+                    println!("Nothing logged since {}", since);
+                } else {
+                    for r in &rollups {
+                        // The program target in effect when the period began
+                        let first_day = if r.period.len() == 7 {
+                            format!("{}-01", r.period)
+                        } else {
+                            r.period.clone()
+                        };
+                        let target = adjustments
+                            .iter()
+                            .rev()
+                            .find(|a| a.week_start <= first_day)
+                            .map(|a| format!("  target {:.0}", a.calories))
+                            .unwrap_or_default();
+                        let incomplete = match r.incomplete {
+                            0 => String::new(),
+                            n if config.incomplete_days.exclude => {
+                                format!(", {} incomplete left out", n)
+                            }
+                            n => format!(", {} incomplete", n),
+                        };
+                        println!(
+                            "{:<10}  {:>5.0} kcal  P {:>3.0}  F {:>3.0}  C {:>3.0}  ({} day{}{}){}",
+                            r.period,
+                            r.calories,
+                            r.protein,
+                            r.fat,
+                            r.carbs,
+                            r.days,
+                            if r.days == 1 { "" } else { "s" },
+                            incomplete,
+                            target
+                        );
+                    }
+                }
+            }
+            Backend::Remote(_) => {
+                anyhow::bail!("Trends are only available in local mode");
+            }
+        },
+        Some(Commands::Weight {
+            amount,
+            date,
+            trend,
+            days,
+        }) => {
+            let db = match &backend {
+                Backend::Local(db) => db,
+                Backend::Remote(_) => anyhow::bail!("Weight is only available in local mode"),
+            };
+            let input = amount.join(" ");
+            if trend {
+                let today = chrono::Local::now().date_naive();
+                let since = days_before_today(days.max(1) - 1)?;
+                let trend = weight::trend(db, since, today)?;
+                if cli.json {
+                    println!("{}", serde_json::to_string_pretty(&trend)?);
+                } else if trend.is_empty() {
+                    println!("No weigh-ins or meals in the last {} days", days);
+                } else {
+                    println!("{}", weight::render(&trend));
+                }
+            } else if input.is_empty() {
+                let since = days_before_today(days)?.format("%Y-%m-%d").to_string();
+                let weights = db.get_weight_history(&since)?;
+                if cli.json {
+                    println!("{}", serde_json::to_string_pretty(&weights)?);
+                } else if weights.is_empty() {
+                    println!("No weigh-ins in the last {} days", days);
+                } else {
+                    for w in &weights {
+                        println!("{}  {:.1}kg", w.date, w.kg);
+                    }
+                }
+            } else {
+                let kg = food::parse_weight_kg(&input)
+                    .ok_or_else(|| anyhow::anyhow!("Could not parse weight: '{}'", input))?;
+                let entry = db.log_weight(kg, date.as_deref())?;
+                if cli.json {
+                    println!("{}", serde_json::to_string_pretty(&entry)?);
+                } else {
+                    println!("Weighed in: {:.1}kg on {}", entry.kg, entry.date);
+                }
+            }
+        }
+        Some(Commands::Forecast { days }) => {
+            let db = match &backend {
+                Backend::Local(db) => db,
+                Backend::Remote(_) => anyhow::bail!("Forecasts are only available in local mode"),
+            };
+            let today = chrono::Local::now().date_naive();
+            let since = days_before_today(days)?;
+            let forecast = forecast::forecast(db, since, today)?;
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&forecast)?);
+            } else {
+                println!("{}", forecast::render(&forecast));
+            }
+        }
+        Some(Commands::Program) => {
+            let db = match &backend {
+                Backend::Local(db) => db,
+                Backend::Remote(_) => anyhow::bail!("Programs are only available in local mode"),
+            };
+            let Some(plan) = &config.program else {
+                anyhow::bail!(
+                    "No program set up; add a [program] section (phase, start_date, start_weight) to the config"
+                );
+            };
+            if config.goals.calories.is_none() {
+                anyhow::bail!("The program starts from goals.calories; set it in the config first");
+            }
+            let adjustments = program::adjustments(db, &config)?;
+            let today = chrono::Local::now().date_naive();
+            let target = program::goals_with(&config, &adjustments, today).0.calories;
+            let latest = db.get_weight_history(&plan.start_date)?.pop();
+            if cli.json {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&serde_json::json!({
+                        "program": plan,
+                        "calories": target,
+                        "latest_weight": latest,
+                        "adjustments": adjustments,
+                    }))?
+                );
+            } else {
+                println!(
+                    "{:?} since {} from {:.1}kg, planning {:+.2}kg/week",
+                    plan.phase,
+                    plan.start_date,
+                    plan.start_weight,
+                    plan.planned_rate()
+                );
+                if let Some(w) = &latest {
+                    println!(
+                        "Latest weigh-in: {:.1}kg on {} ({:+.1}kg)",
+                        w.kg,
+                        w.date,
+                        w.kg - plan.start_weight
+                    );
+                }
+                println!("Calorie target: {:.0}", target.unwrap_or_default());
+                for a in &adjustments {
+                    let actual = a
+                        .actual_rate
+                        .map(|r| format!("{:+.2}kg/week", r))
+                        .unwrap_or_else(|| "no weigh-ins".to_string());
+                    println!(
+                        "  {}  {:>5.0} kcal ({:+.0})  {}",
+                        a.week_start, a.calories, a.change, actual
+                    );
+                }
+            }
+        }
+        Some(Commands::Heatmap { weeks, adherence }) => {
+            let db = match &backend {
+                Backend::Local(db) => db,
+                Backend::Remote(_) => anyhow::bail!("Heatmap is only available in local mode"),
+            };
+            // Judge each day by the goals as written, not today's
+            let config = config::Config::load()?;
+            let today = chrono::Local::now().date_naive();
+            let since = heatmap::start(today, weeks).to_string();
+            let days = adherence::evaluate(
+                &config,
+                &program::adjustments(db, &config)?,
+                &db.rollups(&since, "day")?,
+            );
+            let (cells, legend) = if adherence {
+                (heatmap::adherence_cells(&days), heatmap::adherence_legend())
+            } else {
+                let has_goal = days.iter().any(|d| d.goals.calories.is_some());
+                (
+                    heatmap::calorie_cells(&days),
+                    heatmap::calorie_legend(has_goal),
+                )
+            };
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&cells)?);
+            } else {
+                println!("{}", heatmap::render(&out, &cells, today, weeks, &legend));
+            }
+        }
+        Some(Commands::Adherence { days }) => {
+            let db = match &backend {
+                Backend::Local(db) => db,
+                Backend::Remote(_) => anyhow::bail!("Adherence is only available in local mode"),
+            };
+            // Judge each day by the goals as written, not today's
+            let config = config::Config::load()?;
+            let since = days_before_today(days)?.format("%Y-%m-%d").to_string();
+            let judged = adherence::evaluate(
+                &config,
+                &program::adjustments(db, &config)?,
+                &db.rollups(&since, "day")?,
+            );
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&judged)?);
+            } else if judged.is_empty() {
+                println!("Nothing logged since {}", since);
+            } else {
+                for day in &judged {
+                    let target = |value: f64, goal: Option<f64>| match goal {
+                        Some(goal) => format!("{:.0}/{:.0}", value, goal),
+                        None => format!("{:.0}", value),
+                    };
+                    println!(
+                        "{}  {} kcal  P {}  {}{}{}",
+                        day.date,
+                        target(day.calories, day.goals.calories),
+                        target(day.protein, day.goals.protein),
+                        if day.on_target { "on target" } else { "off" },
+                        day.scheduled
+                            .as_deref()
+                            .map(|name| format!(" ({})", name))
+                            .unwrap_or_default(),
+                        if day.incomplete { "  incomplete?" } else { "" }
+                    );
+                }
+                let hits = judged.iter().filter(|d| d.on_target).count();
+                println!(
+                    "{} of {} logged days on target (within {:.0}%)",
+                    hits,
+                    judged.len(),
+                    adherence::TOLERANCE * 100.0
+                );
+                let incomplete = judged.iter().filter(|d| d.incomplete).count();
+                if incomplete > 0 {
+                    println!(
+                        "{} under {:.0} kcal {} incompletely logged; set \
+                         incomplete_days.exclude to leave them out",
+                        if incomplete == 1 {
+                            "1 day".to_string()
+                        } else {
+                            format!("{} days", incomplete)
+                        },
+                        config.incomplete_days.below,
+                        if incomplete == 1 { "looks" } else { "look" }
+                    );
+                }
+            }
+        }
+        Some(Commands::Missing { days }) => {
+            let db = match &backend {
+                Backend::Local(db) => db,
+                Backend::Remote(_) => {
+                    anyhow::bail!("Missing days are only available in local mode")
+                }
+            };
+            let missing = db.missing_days(days)?;
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&missing)?);
+            } else if missing.is_empty() {
+                println!("Nothing missing in the last {} days", days);
+            } else {
+                for day in &missing {
+                    let weekday = chrono::NaiveDate::parse_from_str(&day.date, "%Y-%m-%d")
+                        .map(|d| d.format("%a").to_string())
+                        .unwrap_or_default();
+                    if day.entries == 0 {
+                        println!("{} {}  nothing logged", day.date, weekday);
+                    } else {
+                        println!(
+                            "{} {}  only {:.0} kcal ({} {})",
+                            day.date,
+                            weekday,
+                            day.calories,
+                            day.entries,
+                            if day.entries == 1 { "entry" } else { "entries" }
+                        );
+                    }
+                }
+                let empty = missing.iter().filter(|d| d.entries == 0).count();
+                println!(
+                    "{} of the last {} days: {} with nothing logged, {} under {:.0} kcal",
+                    missing.len(),
+                    days,
+                    empty,
+                    missing.len() - empty,
+                    config.incomplete_days.below
+                );
+            }
+        }
+        Some(Commands::Snapshot { date, backup }) => {
+            let db = match &backend {
+                Backend::Local(db) => db,
+                Backend::Remote(_) => anyhow::bail!("Snapshots are only available in local mode"),
+            };
+            let date = date.unwrap_or_else(|| chrono::Local::now().format("%Y-%m-%d").to_string());
+            chrono::NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+                .map_err(|_| anyhow::anyhow!("Invalid date '{}', expected YYYY-MM-DD", date))?;
+            let snapshot = db.snapshot_day(&date)?;
+            let unlogged = snapshot.totals.calories < config.incomplete_days.below;
+
+            let backup = match backup {
+                Some(dir) => {
+                    std::fs::create_dir_all(&dir)?;
+                    let path = dir.join(format!("chomp-{}.db", date));
+                    if path.exists() {
+                        std::fs::remove_file(&path)?;
+                    }
+                    db.backup_to(&path)?;
+                    let settings = serde_json::to_string(&settings::gather(db)?)?;
+                    db::Database::open_at(&path)?.store_settings(&settings)?;
+                    Some(path)
+                }
+                None => None,
+            };
+
+            let event = serde_json::json!({
+                "event": "snapshot",
+                "date": date,
+                "entries": snapshot.entries,
+                "totals": snapshot.totals,
+                "unlogged": unlogged,
+                "backup": backup,
+            });
+            let notified = hooks::send_now(&config, &event)
+                .map_err(|e| anyhow::anyhow!("Snapshot hook failed: {}", e))?;
+
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&event)?);
+            } else {
+                println!(
+                    "{}: {} {}, {:.0} kcal, P {:.0}g  F {:.0}g  C {:.0}g",
+                    date,
+                    snapshot.entries,
+                    if snapshot.entries == 1 {
+                        "entry"
+                    } else {
+                        "entries"
+                    },
+                    snapshot.totals.calories,
+                    snapshot.totals.protein,
+                    snapshot.totals.fat,
+                    snapshot.totals.carbs
+                );
+                if let Some(path) = &backup {
+                    println!("Backed up to {}", path.display());
+                }
+                if notified {
+                    println!("Hooks notified");
+                }
+            }
+            if unlogged {
+                eprintln!(
+                    "{} looks unlogged (under {:.0} kcal)",
+                    date, config.incomplete_days.below
+                );
+                std::process::exit(2);
+            }
+        }
+        Some(Commands::Batch { input }) => {
+            let db = match &backend {
+                Backend::Local(db) => db,
+                Backend::Remote(_) => anyhow::bail!("Batches are only available in local mode"),
+            };
+            let script = if input == "-" {
+                let mut script = String::new();
+                std::io::stdin().read_to_string(&mut script)?;
+                script
+            } else {
+                std::fs::read_to_string(&input)
+                    .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", input, e))?
+            };
+            let steps = batch::parse(&script)?;
+            let before = hooks::before_log(db);
+            let applied = batch::run(db, steps, cli.strict)?;
+            if let Some(hook) = hooks::after_log(db, &before) {
+                let _ = hook.join();
+            }
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&applied)?);
+            } else if applied.is_empty() {
+                println!("Nothing to do");
+            } else {
+                for step in &applied {
+                    println!("{}: {}", step.step, step.summary);
+                }
+                println!(
+                    "Applied {} {} in one transaction; `chomp undo` reverts them together",
+                    applied.len(),
+                    if applied.len() == 1 {
+                        "change"
+                    } else {
+                        "changes"
+                    }
+                );
+            }
+        }
+        Some(Commands::Query { sql }) => {
+            if let Backend::Remote(_) = &backend {
+                anyhow::bail!("Queries are only available in local mode");
+            }
+            let result = db::Database::query_read_only(&db::Database::db_path()?, &sql)?;
+            if cli.json {
+                let rows: Vec<serde_json::Map<String, serde_json::Value>> = result
+                    .rows
+                    .into_iter()
+                    .map(|row| result.columns.iter().cloned().zip(row).collect())
+                    .collect();
+                println!("{}", serde_json::to_string_pretty(&rows)?);
+            } else {
+                print_query_result(&result);
+            }
+        }
+        Some(Commands::Settings { action }) => {
+            let db = match &backend {
+                Backend::Local(db) => db,
+                Backend::Remote(_) => anyhow::bail!("Settings are only available in local mode"),
+            };
+            match action {
+                SettingsAction::Export { output } => {
+                    let json = serde_json::to_string_pretty(&settings::gather(db)?)?;
+                    match output {
+                        Some(path) => {
+                            std::fs::write(&path, json + "\n").map_err(|e| {
+                                anyhow::anyhow!("Failed to write {}: {}", path.display(), e)
+                            })?;
+                            eprintln!("Wrote settings to {}", path.display());
+                        }
+                        None => println!("{}", json),
+                    }
+                }
+                SettingsAction::Import { path } => {
+                    let imported = settings::read(&path)?;
+                    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+                    let applied = settings::apply(db, &imported, &today)?;
+                    if cli.json {
+                        println!("{}", serde_json::to_string_pretty(&applied)?);
+                    } else {
+                        println!(
+                            "Imported settings from chomp {} into {}",
+                            imported.chomp_version,
+                            config::Config::path()?.display()
+                        );
+                        if let Some(backup) = &applied.config_backup {
+                            println!("  previous config moved to {}", backup.display());
+                        }
+                        if applied.units {
+                            println!("  units written to {}", food::Units::path()?.display());
+                        }
+                        if applied.recurring_added > 0 {
+                            println!(
+                                "  {} recurring {} added",
+                                applied.recurring_added,
+                                if applied.recurring_added == 1 {
+                                    "entry"
+                                } else {
+                                    "entries"
+                                }
+                            );
+                        }
+                        if !applied.profiles_created.is_empty() {
+                            println!(
+                                "  profiles created: {}",
+                                applied.profiles_created.join(", ")
+                            );
+                        }
+                    }
+                }
+            }
+        }
+        Some(Commands::Digest { since, format }) => {
+            let db = match &backend {
+                Backend::Local(db) => db,
+                Backend::Remote(_) => anyhow::bail!("The digest is only available in local mode"),
+            };
+            // Judge each day by the goals as written, not today's
+            let config = config::Config::load()?;
+            let adjustments = program::adjustments(db, &config)?;
+            let today = chrono::Local::now().date_naive();
+            let since = match since {
+                Some(since) => digest::parse_since(&since, today)?,
+                None => today - chrono::Duration::days(6),
+            };
+            let digest = digest::build(db, &config, &adjustments, since, today)?;
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&digest)?);
             } else {
-                let src = if entry.source.is_empty() {
-                    String::new()
-                } else {
-                    format!(" ({})", entry.source)
-                };
-                println!("Logged: {:.0}mg caffeine{}", entry.amount_mg, src);
+                println!("{}", digest::render(&digest, format));
+            }
+        }
+        Some(Commands::Report { action }) => {
+            let db = match &backend {
+                Backend::Local(db) => db,
+                Backend::Remote(_) => anyhow::bail!("Reports are only available in local mode"),
+            };
+            // Judge each day by the goals as written, not today's
+            let config = config::Config::load()?;
+            let adjustments = program::adjustments(db, &config)?;
+            match action {
+                ReportAction::Compare { months } => {
+                    if months.len() < 2 {
+                        anyhow::bail!(
+                            "Give at least two months to compare, e.g. --months june,july"
+                        );
+                    }
+                    let today = chrono::Local::now().date_naive();
+                    let summaries = months
+                        .iter()
+                        .map(|m| {
+                            report::summarize(
+                                db,
+                                &config,
+                                &adjustments,
+                                report::parse_month(m, today)?,
+                            )
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+                    if cli.json {
+                        println!("{}", serde_json::to_string_pretty(&summaries)?);
+                    } else {
+                        println!("{}", report::render(&summaries));
+                    }
+                }
+                ReportAction::Sources {
+                    week: _,
+                    month,
+                    days,
+                    by,
+                } => {
+                    let days = days.unwrap_or(if month { 30 } else { 7 }).max(1);
+                    let to = chrono::Local::now().date_naive();
+                    let from = to - chrono::Duration::days(i64::from(days) - 1);
+                    let sources = report::sources(db, from, to, by)?;
+                    if cli.json {
+                        println!("{}", serde_json::to_string_pretty(&sources)?);
+                    } else {
+                        println!("{}", report::render_sources(&sources, by));
+                    }
+                }
+                ReportAction::Weekdays { weeks } => {
+                    let to = chrono::Local::now().date_naive();
+                    let from = to - chrono::Duration::weeks(i64::from(weeks.max(1)))
+                        + chrono::Duration::days(1);
+                    let split = report::weekday_split(db, from, to)?;
+                    if cli.json {
+                        println!("{}", serde_json::to_string_pretty(&split)?);
+                    } else {
+                        println!("{}", report::render_weekdays(&split));
+                    }
+                }
             }
         }
         Some(Commands::Stats) => {
@@ -501,13 +3080,30 @@ fn main() -> Result<()> {
                 Backend::Local(db) => db.get_stats()?,
                 Backend::Remote(client) => client.get_stats()?,
             };
-            println!("Foods: {}", stats.food_count);
-            println!("Log entries: {}", stats.log_count);
-            println!("First entry: {}", stats.first_entry.unwrap_or_default());
-            println!("Last entry: {}", stats.last_entry.unwrap_or_default());
+            println!("{}", out.stats(&stats));
+        }
+        // Serve, Bot, Import, Init, Dev, Db, and Completions handled above; Redo in main
+        Some(Commands::Serve { .. })
+        | Some(Commands::Bot { .. })
+        | Some(Commands::Import { .. })
+        | Some(Commands::Init { .. })
+        | Some(Commands::Dev { .. })
+        | Some(Commands::Db { .. })
+        | Some(Commands::Redo { .. })
+        | Some(Commands::Completions { .. }) => unreachable!(),
+        None if cli.from_clipboard || cli.from_file.is_some() => {
+            let text = match &cli.from_file {
+                Some(path) if path.as_os_str() == "-" => {
+                    let mut text = String::new();
+                    std::io::stdin().read_to_string(&mut text)?;
+                    text
+                }
+                Some(path) => std::fs::read_to_string(path)
+                    .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?,
+                None => read_clipboard()?,
+            };
+            log_lines(&backend, &cli, &out, &text)?;
         }
-        // Serve and Import handled above
-        Some(Commands::Serve { .. }) | Some(Commands::Import { .. }) => unreachable!(),
         None => {
             // Default action: log food
             if cli.food.is_empty() {
@@ -523,6 +3119,7 @@ fn main() -> Result<()> {
                     Backend::Local(db) => db.get_today_caffeine()?,
                     Backend::Remote(client) => client.get_today_caffeine()?,
                 };
+                let meals = today_meals(&backend, &config)?;
                 if cli.json {
                     println!(
                         "{}",
@@ -530,54 +3127,860 @@ fn main() -> Result<()> {
                             "macros": totals,
                             "water": water,
                             "caffeine": caffeine,
+                            "meals": meals,
                         }))?
                     );
                 } else {
-                    println!(
-                        "Today: {:.0}p / {:.0}f / {:.0}c — {:.0} kcal",
-                        totals.protein, totals.fat, totals.carbs, totals.calories
-                    );
-                    println!(
-                        "       {:.0}ml water / {:.0}mg caffeine",
-                        water.total_ml, caffeine.total_mg
-                    );
+                    let targets = status::Targets::resolve(&config);
+                    println!("{}", out.today(&totals, &water, &caffeine, &targets));
+                    let progress = goal::progress(&totals, &goal::resolve(&config));
+                    if !progress.is_empty() {
+                        println!("{}", out.goal_progress(&progress));
+                    }
+                    if !meals.is_empty() {
+                        println!("{}", out.meal_subtotals(&meals));
+                    }
                 }
             } else {
-                let input = cli.food.join(" ");
+                let words = food_words(&backend, &cli.food);
+                let input = words.join(" ");
+                // A mistyped command ends up here as a food that isn't found
+                let unknown_command = |e: anyhow::Error| match suggest_command(&words[0]) {
+                    _ if !e.is::<logging::FoodNotFound>() => e,
+                    Some(command) => e.context(format!(
+                        "'{}' isn't a command. Did you mean: chomp {}?",
+                        words[0], command
+                    )),
+                    None => e,
+                };
                 let entry = match &backend {
-                    Backend::Local(db) => logging::parse_and_log(db, &input, cli.date.as_deref())?,
-                    Backend::Remote(client) => client.log_food(&input, cli.date.as_deref())?,
+                    Backend::Local(db) => {
+                        let opts = db::LogOptions {
+                            date: cli.date.as_deref(),
+                            time: cli.time,
+                            meal: cli.meal.as_deref(),
+                            dedupe_window: (!cli.force).then_some(cli.dedupe_window),
+                            warn_duplicates: true,
+                            strict: cli.strict,
+                            ..Default::default()
+                        };
+                        let before = hooks::before_log(db);
+                        let entry = match logging::parse_and_log_with(db, &input, &opts) {
+                            Err(e) => match e.downcast_ref::<logging::AmbiguousFood>() {
+                                Some(ambiguous) if !cli.json => match choose_variant(ambiguous)? {
+                                    Some(variant) => logging::parse_and_log_with(
+                                        db,
+                                        &ambiguous.input(&variant),
+                                        &opts,
+                                    ),
+                                    None => Err(e),
+                                },
+                                _ => Err(e),
+                            },
+                            logged => logged,
+                        }
+                        .map_err(unknown_command)?;
+                        if let Some(hook) = hooks::after_log(db, &before) {
+                            let _ = hook.join();
+                        }
+                        entry
+                    }
+                    Backend::Remote(client) => client.log_food(
+                        &input,
+                        cli.date.as_deref(),
+                        cli.time,
+                        cli.meal.as_deref(),
+                        cli.force,
+                        cli.strict,
+                    )?,
                 };
                 if cli.json {
                     println!("{}", serde_json::to_string_pretty(&entry)?);
                 } else {
-                    println!(
-                        "Logged: {} {} — {:.0}p/{:.0}f/{:.0}c",
-                        entry.amount, entry.food_name, entry.protein, entry.fat, entry.carbs
-                    );
+                    println!("{}", out.log_entry("logged", &entry));
+                    if let Some(warning) = &entry.warning {
+                        eprintln!("Warning: {}", warning);
+                    }
+                    if let Backend::Local(db) = &backend {
+                        let suggestions = db.alias_suggestions(ALIAS_SUGGEST_WINDOW)?;
+                        if let Some(s) = suggestions
+                            .iter()
+                            .rfind(|s| Some(s.food_id) == entry.food_id)
+                        {
+                            eprintln!(
+                                "Tip: run `chomp aliases suggest` to make \"{}\" an alias for {}",
+                                s.query, s.food_name
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Log each meal line of `text` (see `meal_lines`), carrying on past
+/// failures and reporting them at the end.
+fn log_lines(backend: &Backend, cli: &Cli, out: &render::Renderer, text: &str) -> Result<()> {
+    let text = meal_lines(text);
+    let items = match backend {
+        Backend::Local(db) => logging::split_items(db, &text)?,
+        Backend::Remote(client) => logging::split_items_with(&text, |name| {
+            Ok(client.search_foods(name)?.iter().any(|food| {
+                food.name.eq_ignore_ascii_case(name)
+                    || food.aliases.iter().any(|a| a.eq_ignore_ascii_case(name))
+            }))
+        })?,
+    };
+    if items.is_empty() {
+        anyhow::bail!("Nothing to log");
+    }
+    let before = match backend {
+        Backend::Local(db) => Some(hooks::before_log(db)),
+        Backend::Remote(_) => None,
+    };
+    let mut entries = Vec::new();
+    let mut failed = 0;
+    for item in &items {
+        let logged = match backend {
+            Backend::Local(db) => {
+                let opts = db::LogOptions {
+                    date: cli.date.as_deref(),
+                    time: cli.time,
+                    meal: cli.meal.as_deref(),
+                    dedupe_window: (!cli.force).then_some(cli.dedupe_window),
+                    warn_duplicates: true,
+                    strict: cli.strict,
+                    ..Default::default()
+                };
+                logging::parse_and_log_with(db, item, &opts)
+            }
+            Backend::Remote(client) => client.log_food(
+                item,
+                cli.date.as_deref(),
+                cli.time,
+                cli.meal.as_deref(),
+                cli.force,
+                cli.strict,
+            ),
+        };
+        match logged {
+            Ok(entry) => {
+                if !cli.json {
+                    println!("{}", out.log_entry("logged", &entry));
+                    if let Some(warning) = &entry.warning {
+                        eprintln!("Warning: {}", warning);
+                    }
+                }
+                entries.push(entry);
+            }
+            Err(e) => {
+                eprintln!("Couldn't log {}: {}", item, e);
+                failed += 1;
+            }
+        }
+    }
+    if let (Backend::Local(db), Some(before)) = (backend, before) {
+        if let Some(hook) = hooks::after_log(db, &before) {
+            let _ = hook.join();
+        }
+    }
+    if cli.json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+    }
+    if failed > 0 {
+        anyhow::bail!("{} of {} items couldn't be logged", failed, items.len());
+    }
+    Ok(())
+}
+
+/// One meal per line, as jotted in a notes app: list bullets, numbering, and
+/// checkboxes are dropped, as are blank lines and `#` comments or headings.
+fn meal_lines(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            let mut line = line.trim();
+            for bullet in ["- [x]", "- [ ]", "-", "*", "•", "·"] {
+                if let Some(rest) = line.strip_prefix(bullet) {
+                    line = rest.trim_start();
+                    break;
+                }
+            }
+            // "1. " or "2) " numbering, but not "1.5 cups rice"
+            let number = line.trim_start_matches(|c: char| c.is_ascii_digit());
+            if number.len() < line.len() {
+                if let Some(rest) = number
+                    .strip_prefix(['.', ')'])
+                    .filter(|rest| rest.starts_with(char::is_whitespace))
+                {
+                    line = rest.trim_start();
+                }
+            }
+            line
+        })
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Text on the system clipboard, via whichever paste tool the platform has.
+fn read_clipboard() -> Result<String> {
+    let tools: &[(&str, &[&str])] = &[
+        ("pbpaste", &[]),
+        ("wl-paste", &["--no-newline"]),
+        ("xclip", &["-selection", "clipboard", "-o"]),
+        ("xsel", &["--clipboard", "--output"]),
+        ("powershell", &["-NoProfile", "-Command", "Get-Clipboard"]),
+    ];
+    for (tool, args) in tools {
+        if let Ok(output) = std::process::Command::new(tool).args(*args).output() {
+            if output.status.success() {
+                return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+            }
+        }
+    }
+    anyhow::bail!(
+        "Couldn't read the clipboard: install pbpaste, wl-paste, xclip, or xsel, or use --from-file"
+    )
+}
+
+/// The words of a default-action log, without a leading "log": `chomp log
+/// 2 eggs` reads as `chomp 2 eggs`. The "log" stays when the whole input
+/// names a food, like `chomp log cabin syrup`.
+fn food_words<'a>(backend: &Backend, food: &'a [String]) -> &'a [String] {
+    let names_food = || match backend {
+        Backend::Local(db) => {
+            let (name, _) = logging::parse_input(&food.join(" "));
+            matches!(db.get_food_by_name(&name), Ok(Some(_)))
+        }
+        Backend::Remote(_) => false,
+    };
+    match food.split_first() {
+        Some((first, rest)) if first == "log" && !rest.is_empty() && !names_food() => rest,
+        _ => food,
+    }
+}
+
+/// Whether a command logs food or shows today, and so should auto-log the
+/// day's standing recurring entries first. Undoing one mustn't log it again.
+fn rolls_over(cli: &Cli) -> bool {
+    matches!(
+        cli.command,
+        None | Some(Commands::Today | Commands::Status { .. })
+    )
+}
+
+/// Whether a command shows today's goals, which a `[program]` adjusts.
+/// Only these catch the program up, recording its weekly adjustments.
+fn shows_goals(cli: &Cli) -> bool {
+    match &cli.command {
+        None => cli.food.is_empty(),
+        Some(
+            Commands::Today
+            | Commands::Status { .. }
+            | Commands::Snack { .. }
+            | Commands::Goal { .. }
+            | Commands::Fit { .. }
+            | Commands::Water { .. },
+        ) => true,
+        Some(_) => false,
+    }
+}
+
+/// Whether a command is worth offering to `chomp redo`: not the bare status
+/// view, long-running servers, or anything against a remote server.
+fn records_history(cli: &Cli) -> bool {
+    if std::env::var("CHOMP_SERVER_URL").is_ok() {
+        return false;
+    }
+    match &cli.command {
+        None => !cli.food.is_empty(),
+        Some(Commands::Serve { .. })
+        | Some(Commands::Bot { .. })
+        | Some(Commands::Completions { .. })
+        | Some(Commands::Redo { .. }) => false,
+        Some(_) => true,
+    }
+}
+
+/// The last recorded command, parsed and ready to run again, with `amount`
+/// in place of a logged food's amount. A repeated log skips the duplicate
+/// check. None after `--list` prints the recent commands instead.
+fn redo(amount: &[String], list: bool, json: bool) -> Result<Option<(Cli, Vec<String>)>> {
+    if std::env::var("CHOMP_SERVER_URL").is_ok() {
+        anyhow::bail!("Redo is only available in local mode");
+    }
+    let db = db::Database::open()?;
+    db.init()?;
+    if list {
+        let commands = db.recent_commands(REDO_LIST_LIMIT)?;
+        if json {
+            println!("{}", serde_json::to_string_pretty(&commands)?);
+        } else if commands.is_empty() {
+            println!("No commands recorded yet.");
+        } else {
+            for command in commands.iter().rev() {
+                println!(
+                    "{}  chomp {}",
+                    command.created_at,
+                    shell_join(&command.args)
+                );
+            }
+        }
+        return Ok(None);
+    }
+
+    let last = db
+        .recent_commands(1)?
+        .pop()
+        .ok_or_else(|| anyhow::anyhow!("No command to redo yet"))?;
+    let args = if amount.is_empty() {
+        last.args
+    } else {
+        with_amount(&last.args, &amount.join(" "))?
+    };
+    eprintln!("chomp {}", shell_join(&args));
+    let mut cli = parse_args(&args)?;
+    // Repeating a log is deliberate, not a double submit
+    cli.force = true;
+    Ok(Some((cli, args)))
+}
+
+/// A logged food's arguments with its amount replaced: `ribeye 8oz` with
+/// "6oz" becomes `ribeye 6oz`. Flags such as --date are kept.
+fn with_amount(args: &[String], amount: &str) -> Result<Vec<String>> {
+    let cli = parse_args(args)?;
+    if cli.command.is_some() || cli.food.is_empty() {
+        anyhow::bail!(
+            "Only a logged food can be redone with a new amount; the last command was: chomp {}",
+            shell_join(args)
+        );
+    }
+    // A leading "log" is kept and dropped again when the command runs
+    let (name, _) = logging::parse_input(&cli.food.join(" "));
+    let start = args
+        .windows(cli.food.len())
+        .position(|w| w == cli.food)
+        .unwrap_or(args.len() - cli.food.len());
+    let mut redone = args[..start].to_vec();
+    redone.extend(name.split_whitespace().map(String::from));
+    redone.push(amount.to_string());
+    redone.extend_from_slice(&args[start + cli.food.len()..]);
+    Ok(redone)
+}
+
+/// Parse recorded arguments, which leave out the program name.
+fn parse_args(args: &[String]) -> Result<Cli> {
+    Ok(Cli::try_parse_from(
+        std::iter::once("chomp").chain(log_flags(args).iter().map(String::as_str)),
+    )?)
+}
+
+/// `chomp log --from-file meals.txt` reads as `chomp --from-file meals.txt`:
+/// otherwise flags after "log" are taken as part of the food.
+fn log_flags(args: &[String]) -> &[String] {
+    match args {
+        [first, next, ..] if first == "log" && next.starts_with("--") => &args[1..],
+        _ => args,
+    }
+}
+
+/// Arguments as they'd be typed, quoting any a shell would split.
+fn shell_join(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| {
+            if !arg.is_empty()
+                && !arg.contains(|c: char| c.is_whitespace() || "'\"!$`\\".contains(c))
+            {
+                arg.clone()
+            } else {
+                format!("'{}'", arg.replace('\'', "'\\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The command a word taken as a food was probably meant to be, e.g.
+/// "histroy" for history. "log" counts, as the name of the default action.
+fn suggest_command(word: &str) -> Option<String> {
+    let word = word.to_lowercase();
+    let cli = Cli::command();
+    let scored = cli
+        .get_subcommands()
+        .filter(|c| !c.is_hide_set())
+        .map(|c| c.get_name().to_string())
+        .chain(["log".to_string()])
+        .map(|name| (suggest::similarity(&word, &name), name));
+    suggest::top(scored, 0.6, 1).pop()
+}
+
+/// Today's entries by meal, or nothing when none were logged under one.
+fn today_meals(backend: &Backend, config: &config::Config) -> Result<Vec<meal::MealGroup>> {
+    let entries = match backend {
+        Backend::Local(db) => db.get_today_entries()?,
+        Backend::Remote(client) => {
+            client
+                .get_history_page(0, db::TimeWindow::default(), None, 0)?
+                .entries
+        }
+    };
+    if !meal::any(&entries) {
+        return Ok(Vec::new());
+    }
+    Ok(meal::group(entries, &config.meals()))
+}
+
+/// Log standing recurring entries on the first run of a new day, noting
+/// each on stderr so `--json` output stays clean. Best effort: a food that
+/// can't be logged shouldn't stop the command that was asked for.
+fn roll_over(db: &db::Database, out: &render::Renderer) {
+    let Ok(ran) = recurring::roll_over(db, chrono::Local::now().naive_local()) else {
+        return;
+    };
+    for item in ran {
+        match item.entry {
+            Ok(entry) => eprintln!("{}", out.log_entry("auto-logged", &entry)),
+            Err(e) => eprintln!(
+                "Recurring {}: couldn't log {}: {}",
+                item.recurring, item.item, e
+            ),
+        }
+    }
+}
+
+/// Set goals in one line, e.g. "2200 kcal, 160g protein".
+fn describe_goals(goals: &config::Goals) -> String {
+    let set: Vec<String> = goal::METRICS
+        .iter()
+        .filter_map(|&metric| goals.get(metric).map(|goal| metric_amount(metric, goal)))
+        .collect();
+    if set.is_empty() {
+        "none".to_string()
+    } else {
+        set.join(", ")
+    }
+}
+
+/// "420 kcal" or "18g fat".
+fn metric_amount(metric: config::Metric, value: f64) -> String {
+    match metric {
+        config::Metric::Calories => format!("{:.0} kcal", value),
+        _ => format!("{:.0}g {}", value, metric.name()),
+    }
+}
+
+/// Read a Nutrition Facts label from stdin, show what was parsed, and
+/// confirm on the terminal (stdin holds the label). Returns protein, fat,
+/// carbs, serving, and calories, or None if the user declined.
+#[allow(clippy::type_complexity)]
+fn read_label(
+    name: &str,
+    per_100g: bool,
+    yes: bool,
+    factors: &food::CalorieFactors,
+) -> Result<Option<(f64, f64, f64, String, Option<f64>)>> {
+    if std::io::stdin().is_terminal() {
+        eprintln!("Paste the nutrition label, then press Ctrl-D:");
+    }
+    let mut text = String::new();
+    std::io::stdin().read_to_string(&mut text)?;
+    let mut label = label::Label::parse(&text)?;
+    if label.calories.is_none() {
+        // Labels read here carry no fiber, so the carb mode makes no difference
+        label.calories = Some(factors.calories_from_macros(
+            label.protein,
+            label.fat,
+            label.carbs,
+            0.0,
+            food::CarbMode::Total,
+        ));
+    }
+
+    let per_serving = (
+        label.protein,
+        label.fat,
+        label.carbs,
+        label.serving(),
+        Some(label.calories()),
+    );
+    let normalized = label
+        .per_100g()
+        .map(|(p, f, c, kcal)| (p, f, c, "100g".to_string(), Some(kcal)));
+
+    println!(
+        "{}: {:.1}p/{:.1}f/{:.1}c, {:.0} kcal per {}{}",
+        name,
+        label.protein,
+        label.fat,
+        label.carbs,
+        label.calories(),
+        label.serving(),
+        label
+            .serving_size
+            .as_deref()
+            .filter(|size| *size != label.serving())
+            .map(|size| format!(" ({})", size))
+            .unwrap_or_default()
+    );
+    if let Some((p, f, c, _, kcal)) = &normalized {
+        println!(
+            "{}  {:.1}p/{:.1}f/{:.1}c, {:.0} kcal per 100g",
+            " ".repeat(name.chars().count()),
+            p,
+            f,
+            c,
+            kcal.unwrap_or_default()
+        );
+    }
+
+    let values = if per_100g {
+        normalized.ok_or_else(|| {
+            anyhow::anyhow!(
+                "The label has no serving weight in grams, so it can't be stored per 100g"
+            )
+        })?
+    } else {
+        per_serving
+    };
+
+    if !yes {
+        let mut tty = std::fs::File::options()
+            .read(true)
+            .write(true)
+            .open("/dev/tty")
+            .map_err(|_| anyhow::anyhow!("No terminal to confirm on; pass --yes to save"))?;
+        write!(tty, "Save {} per {}? [Y/n] ", name, values.3)?;
+        let mut answer = String::new();
+        std::io::BufReader::new(&tty).read_line(&mut answer)?;
+        if answer.trim().to_lowercase().starts_with('n') {
+            println!("Not saved.");
+            return Ok(None);
+        }
+    }
+    Ok(Some(values))
+}
+
+/// Warn about each name or alias of `food` that another food already answers
+/// to. When `interactive`, ask what to do about it: keep it anyway (Enter),
+/// drop the alias or rename the food, or abort. Returns false on abort.
+fn resolve_clashes(db: &db::Database, food: &mut food::Food, interactive: bool) -> Result<bool> {
+    let mut kept: Vec<(String, String)> = Vec::new();
+    loop {
+        let clash = db
+            .name_clashes(&food.name, &food.aliases, None)?
+            .into_iter()
+            .find(|c| !kept.contains(&(c.name.clone(), c.existing.clone())));
+        let Some(clash) = clash else {
+            return Ok(true);
+        };
+        eprintln!("Warning: {}", clash);
+        let mut answer = String::new();
+        if interactive {
+            if clash.is_alias {
+                eprint!("  [k]eep it, [d]rop the alias, or [a]bort? [K/d/a] ");
+            } else {
+                eprint!("  [k]eep it, [r]ename the food, or [a]bort? [K/r/a] ");
+            }
+            std::io::stdin().lock().read_line(&mut answer)?;
+        }
+        match answer.trim().to_lowercase().as_str() {
+            "d" | "drop" if clash.is_alias => food.aliases.retain(|a| *a != clash.name),
+            "r" | "rename" if !clash.is_alias => {
+                eprint!("  New name: ");
+                let mut name = String::new();
+                std::io::stdin().lock().read_line(&mut name)?;
+                if !name.trim().is_empty() {
+                    food.name = name.trim().to_string();
+                }
+            }
+            "a" | "abort" => return Ok(false),
+            _ => kept.push((clash.name, clash.existing)),
+        }
+    }
+}
+
+/// The food `name` names, or a "not found" error with its suggestions.
+fn find_food(db: &db::Database, name: &str) -> Result<food::Food> {
+    match db.get_food_by_name(name)? {
+        Some(food) => Ok(food),
+        None => Err(logging::FoodNotFound::lookup(db, name)?.into()),
+    }
+}
+
+/// Grams in a weight given on the command line ("420g", "15oz"); a bare
+/// number is grams.
+fn parse_weight(weight: &str) -> Result<f64> {
+    food::parse_grams(weight)
+        .or_else(|| weight.trim().parse().ok())
+        .ok_or_else(|| anyhow::anyhow!("Could not parse weight: '{}'", weight))
+}
+
+/// Print a recipe just created or reweighed, with one serving's macros.
+fn print_recipe(
+    name: &str,
+    recipe: &food::RecipeYield,
+    serving: &food::Macros,
+    verb: &str,
+    json: bool,
+) -> Result<()> {
+    if json {
+        let value = serde_json::json!({
+            "name": name,
+            "servings": recipe.servings,
+            "yield_grams": recipe.grams,
+            "serving_grams": recipe.serving_grams(),
+            "per_serving": serving,
+        });
+        println!("{}", serde_json::to_string_pretty(&value)?);
+    } else {
+        println!("{} {}", verb, recipe::describe(name, recipe, serving));
+    }
+    Ok(())
+}
+
+/// Ask which variant of an ambiguous food was meant, by number; Enter,
+/// EOF, or no terminal gives None.
+fn choose_variant(ambiguous: &logging::AmbiguousFood) -> Result<Option<String>> {
+    if !std::io::stdin().is_terminal() {
+        return Ok(None);
+    }
+    println!("Which '{}'?", ambiguous.name);
+    for (i, variant) in ambiguous.variants.iter().enumerate() {
+        println!("  {}. {}", i + 1, variant);
+    }
+    loop {
+        print!("Number (Enter to cancel): ");
+        std::io::stdout().flush()?;
+        let mut line = String::new();
+        if std::io::stdin().lock().read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        match line.trim() {
+            "" => return Ok(None),
+            answer => match answer.parse::<usize>() {
+                Ok(n) if (1..=ambiguous.variants.len()).contains(&n) => {
+                    return Ok(Some(ambiguous.variants[n - 1].clone()))
+                }
+                _ => println!(
+                    "  Please enter a number from 1 to {}.",
+                    ambiguous.variants.len()
+                ),
+            },
+        }
+    }
+}
+
+/// Ask for a 1-5 rating on the terminal; Enter, EOF, or no terminal skips it.
+fn ask_rating(question: &str) -> Result<Option<u8>> {
+    if !std::io::stdin().is_terminal() {
+        return Ok(None);
+    }
+    loop {
+        print!("{} ", question);
+        std::io::stdout().flush()?;
+        let mut line = String::new();
+        if std::io::stdin().lock().read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        match line.trim() {
+            "" => return Ok(None),
+            answer => match answer.parse::<u8>() {
+                Ok(n @ 1..=5) => return Ok(Some(n)),
+                _ => println!("  Please enter a number from 1 to 5."),
+            },
+        }
+    }
+}
+
+/// The date `days` days before today, for a `--days` option.
+fn days_before_today(days: u32) -> Result<chrono::NaiveDate> {
+    chrono::Local::now()
+        .date_naive()
+        .checked_sub_signed(chrono::Duration::days(days.into()))
+        .ok_or_else(|| anyhow::anyhow!("--days {} reaches back further than dates go", days))
+}
+
+/// Downstream closed early (e.g. `| head`); not an error for streamed output.
+fn is_broken_pipe(e: &anyhow::Error) -> bool {
+    let io = e.downcast_ref::<std::io::Error>().or_else(|| {
+        e.downcast_ref::<csv::Error>().and_then(|e| match e.kind() {
+            csv::ErrorKind::Io(io) => Some(io),
+            _ => None,
+        })
+    });
+    io.map(|e| e.kind()) == Some(std::io::ErrorKind::BrokenPipe)
+}
+
+/// Rows as aligned plain-text columns, numbers right-aligned.
+fn print_query_result(result: &db::QueryResult) {
+    let cell = |value: &serde_json::Value| match value {
+        serde_json::Value::Null => String::new(),
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    let rows: Vec<Vec<String>> = result
+        .rows
+        .iter()
+        .map(|row| row.iter().map(cell).collect())
+        .collect();
+    let widths: Vec<usize> = result
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .chain([name.chars().count()])
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+    let numeric: Vec<bool> = (0..result.columns.len())
+        .map(|i| {
+            result
+                .rows
+                .iter()
+                .all(|row| row[i].is_number() || row[i].is_null())
+        })
+        .collect();
+    let line = |cells: &[String]| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .zip(&numeric)
+            .map(|((cell, &width), &numeric)| {
+                if numeric {
+                    format!("{:>width$}", cell)
+                } else {
+                    format!("{:<width$}", cell)
                 }
+            })
+            .collect();
+        padded.join("  ").trim_end().to_string()
+    };
+    println!("{}", line(&result.columns));
+    for row in &rows {
+        println!("{}", line(row));
+    }
+    println!(
+        "({} {})",
+        rows.len(),
+        if rows.len() == 1 { "row" } else { "rows" }
+    );
+}
+
+/// A CSV delimiter: a single ASCII character, or "tab".
+fn parse_delimiter(s: &str) -> std::result::Result<u8, String> {
+    match s {
+        "tab" | "\\t" => Ok(b'\t'),
+        _ if s.len() == 1 && s.is_ascii() => Ok(s.as_bytes()[0]),
+        _ => Err(format!("'{}' is not a single character (or \"tab\")", s)),
+    }
+}
+
+/// Walk through alias suggestions: Enter/y adds the alias, n forgets the
+/// misses behind it, s leaves it for next time.
+fn suggest_aliases(db: &db::Database, window: u32, yes: bool, json: bool) -> Result<()> {
+    let suggestions = db.alias_suggestions(window)?;
+    if json {
+        if yes {
+            for s in &suggestions {
+                db.accept_alias_suggestion(s)?;
             }
         }
+        println!("{}", serde_json::to_string_pretty(&suggestions)?);
+        return Ok(());
+    }
+    if suggestions.is_empty() {
+        println!("No alias suggestions.");
+        return Ok(());
     }
 
+    let stdin = std::io::stdin();
+    for s in &suggestions {
+        let answer = if yes {
+            String::new()
+        } else {
+            print!(
+                "Add \"{}\" as an alias for {}? (missed {}x) [Y/n/s] ",
+                s.query, s.food_name, s.misses
+            );
+            std::io::stdout().flush()?;
+            let mut line = String::new();
+            if stdin.lock().read_line(&mut line)? == 0 {
+                println!();
+                break;
+            }
+            line.trim().to_lowercase()
+        };
+        match answer.as_str() {
+            "" | "y" | "yes" => {
+                db.accept_alias_suggestion(s)?;
+                println!("Added alias: {} → {}", s.query, s.food_name);
+            }
+            "n" | "no" => db.dismiss_lookup_misses(&s.query)?,
+            _ => {}
+        }
+    }
     Ok(())
 }
 
-fn run_serve(transport: &str, port: u16, host: &str, auth_key: Option<&str>) -> Result<()> {
+fn run_serve(
+    transport: &str,
+    port: u16,
+    host: &str,
+    auth_key: Option<&str>,
+    profile_header: Option<&str>,
+    profile_keys: Option<&str>,
+    server: config::Server,
+) -> Result<()> {
+    #[cfg(feature = "sse")]
+    let sse_options = || -> Result<sse::SseOptions> {
+        let defaults = sse::SseOptions::default();
+        Ok(sse::SseOptions {
+            profile_header: profile_header.map(String::from),
+            profile_keys: profile_keys
+                .map(sse::parse_profile_keys)
+                .transpose()?
+                .unwrap_or_default(),
+            keep_alive: server
+                .keep_alive_secs
+                .map(std::time::Duration::from_secs)
+                .unwrap_or(defaults.keep_alive),
+            channel_capacity: server.channel_capacity.unwrap_or(defaults.channel_capacity),
+            max_sessions: server.max_sessions,
+            request_timeout: server
+                .request_timeout_secs
+                .map(std::time::Duration::from_secs)
+                .unwrap_or(defaults.request_timeout),
+        })
+    };
+    #[cfg(not(feature = "sse"))]
+    let _ = (port, host, auth_key, profile_header, profile_keys, server);
+
     match transport {
         "stdio" => mcp::serve_stdio()?,
         #[cfg(feature = "sse")]
         "sse" => {
             let rt = tokio::runtime::Runtime::new()?;
-            rt.block_on(sse::serve_sse(port, host, auth_key))?;
+            rt.block_on(sse::serve_sse(port, host, auth_key, sse_options()?))?;
         }
         #[cfg(feature = "sse")]
         "both" => {
             let host_clone = host.to_string();
             let auth_key_clone = auth_key.map(String::from);
+            let options = sse_options()?;
             let sse_handle = std::thread::spawn(move || {
                 let rt = tokio::runtime::Runtime::new().expect("tokio runtime");
-                rt.block_on(sse::serve_sse(port, &host_clone, auth_key_clone.as_deref()))
+                rt.block_on(sse::serve_sse(
+                    port,
+                    &host_clone,
+                    auth_key_clone.as_deref(),
+                    options,
+                ))
             });
             std::thread::sleep(std::time::Duration::from_millis(100));
             if sse_handle.is_finished() {
@@ -600,13 +4003,188 @@ fn run_serve(transport: &str, port: u16, host: &str, auth_key: Option<&str>) ->
     Ok(())
 }
 
-fn run_import(db: &db::Database, source: &str, path: Option<&str>) -> Result<()> {
+/// Run a chat bot until it's stopped.
+fn run_bot(action: &BotAction) -> Result<()> {
+    match action {
+        #[cfg(feature = "sse")]
+        BotAction::Discord {
+            port,
+            host,
+            public_key,
+            users,
+            register,
+            application_id,
+            bot_token,
+        } => {
+            // Discord user ID -> profile, checked like --profile-keys
+            let users = match users {
+                Some(spec) => sse::parse_profile_keys(spec)?,
+                None => Default::default(),
+            };
+            let options = discord::DiscordOptions {
+                public_key: discord::parse_public_key(public_key)?,
+                users,
+            };
+            if *register {
+                if let (Some(id), Some(token)) = (application_id, bot_token) {
+                    discord::register(id, token)?;
+                    eprintln!("Registered /log, /today, and /undo");
+                }
+            }
+            let rt = tokio::runtime::Runtime::new()?;
+            rt.block_on(discord::serve_discord(*port, host, options))
+        }
+        #[cfg(not(feature = "sse"))]
+        BotAction::Discord { .. } => anyhow::bail!(
+            "The Discord bot requires the 'sse' feature. Rebuild with: cargo build --features sse"
+        ),
+        BotAction::Matrix {
+            homeserver,
+            access_token,
+            room,
+            sender,
+            profile,
+        } => {
+            if let Some(name) = profile {
+                db::Database::profile_path(name)?;
+            }
+            matrix::run(&matrix::MatrixOptions {
+                homeserver: homeserver.clone(),
+                access_token: access_token.clone(),
+                room: room.clone(),
+                sender: sender.clone(),
+                profile: profile.clone(),
+            })
+        }
+        BotAction::Email {
+            imap_host,
+            imap_port,
+            smtp_host,
+            smtp_port,
+            username,
+            password,
+            from,
+            allow,
+            interval,
+            profile,
+        } => {
+            if let Some(name) = profile {
+                db::Database::profile_path(name)?;
+            }
+            email::run(&email::EmailOptions {
+                imap_host: imap_host.clone(),
+                imap_port: *imap_port,
+                smtp_host: smtp_host.clone().unwrap_or_else(|| imap_host.clone()),
+                smtp_port: *smtp_port,
+                username: username.clone(),
+                password: password.clone(),
+                from: from.clone().unwrap_or_else(|| username.clone()),
+                allow: allow.iter().map(|a| a.trim().to_string()).collect(),
+                interval: std::time::Duration::from_secs((*interval).max(1)),
+                profile: profile.clone(),
+            })
+        }
+    }
+}
+
+/// Start the Prometheus exporter on a background thread.
+#[cfg(feature = "sse")]
+fn run_metrics(port: u16, host: &str, targets: status::Targets) -> Result<()> {
+    let host = host.to_string();
+    let handle = std::thread::spawn(move || {
+        let rt = tokio::runtime::Runtime::new().expect("tokio runtime");
+        rt.block_on(metrics::serve_metrics(port, &host, targets))
+    });
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    if handle.is_finished() {
+        match handle.join() {
+            Ok(Err(e)) => anyhow::bail!("Metrics server failed to start: {}", e),
+            Err(_) => anyhow::bail!("Metrics server thread panicked"),
+            Ok(Ok(())) => anyhow::bail!("Metrics server exited unexpectedly"),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "sse"))]
+fn run_metrics(_port: u16, _host: &str, _targets: status::Targets) -> Result<()> {
+    anyhow::bail!("Metrics require the 'sse' feature. Rebuild with: cargo build --features sse");
+}
+
+/// Generate the completion script for `shell` from the CLI definition.
+fn write_completions(shell: clap_complete::Shell, out: &mut dyn std::io::Write) {
+    clap_complete::generate(shell, &mut Cli::command(), "chomp", out);
+}
+
+fn run_import(
+    db: &db::Database,
+    source: &str,
+    path: Option<&str>,
+    date: Option<&str>,
+    json: bool,
+) -> Result<()> {
     match source {
         "usda" => db.import_usda()?,
+        "starter" => {
+            let count = db.import_starter()?;
+            println!(
+                "Imported {} starter foods (ones you already had were kept)",
+                count
+            );
+        }
         "csv" => {
             let p = path.ok_or_else(|| anyhow::anyhow!("--path required for csv import"))?;
             db.import_csv(p)?;
         }
+        "transcript" => {
+            let p = path.ok_or_else(|| {
+                anyhow::anyhow!("A file is required: chomp import transcript notes.txt")
+            })?;
+            let text = std::fs::read_to_string(p)
+                .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", p, e))?;
+            let start = match date {
+                Some(date) => chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                    .map_err(|_| anyhow::anyhow!("Invalid date: {} (expected YYYY-MM-DD)", date))?,
+                None => chrono::Local::now().date_naive(),
+            };
+            let notes = transcript::parse(&text, start)?;
+            let imported = transcript::import(db, &notes)?;
+            let mut entries = Vec::new();
+            let mut failed = 0;
+            for item in imported {
+                match item.entry {
+                    Ok(entry) => {
+                        if !json {
+                            println!(
+                                "{} {} {}: {:.0}p/{:.0}f/{:.0}c — {:.0} kcal",
+                                item.at.format("%Y-%m-%d %H:%M"),
+                                entry.amount,
+                                entry.food_name,
+                                entry.protein,
+                                entry.fat,
+                                entry.carbs,
+                                entry.calories
+                            );
+                        }
+                        entries.push(entry);
+                    }
+                    Err(e) => {
+                        eprintln!("Line {}: couldn't log {}: {}", item.note, item.item, e);
+                        failed += 1;
+                    }
+                }
+            }
+            if json {
+                println!("{}", serde_json::to_string_pretty(&entries)?);
+            }
+            if failed > 0 {
+                anyhow::bail!(
+                    "{} of {} items couldn't be logged",
+                    failed,
+                    entries.len() + failed
+                );
+            }
+        }
         _ => anyhow::bail!("Unknown source: {}", source),
     }
     Ok(())
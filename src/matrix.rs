@@ -0,0 +1,266 @@
+//! `chomp bot matrix`: log meals sent as messages in a Matrix room (a DM
+//! with the bot's account) and reply with what was logged, talking to the
+//! homeserver's client-server API directly. It has no end-to-end
+//! encryption support, so it can't read messages in encrypted rooms.
+
+use anyhow::Result;
+use reqwest::blocking::Client;
+use serde_json::{json, Value};
+
+use crate::chat;
+use crate::db::Database;
+
+/// How long each `/sync` waits on the homeserver for new events.
+const SYNC_TIMEOUT_MS: u64 = 30_000;
+
+/// Pause after a failed sync before trying again.
+const RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(5);
+
+pub struct MatrixOptions {
+    /// Homeserver base URL, e.g. https://matrix.example.org
+    pub homeserver: String,
+    pub access_token: String,
+    /// Room ID or alias to listen in; joined on start, accepting an invite
+    pub room: String,
+    /// Only answer this user (e.g. @me:example.org); otherwise anyone in the room
+    pub sender: Option<String>,
+    /// Profile database to log to
+    pub profile: Option<String>,
+}
+
+struct Homeserver {
+    base: String,
+    token: String,
+    client: Client,
+}
+
+impl Homeserver {
+    fn new(options: &MatrixOptions) -> Result<Self> {
+        Ok(Self {
+            base: format!(
+                "{}/_matrix/client/v3",
+                options.homeserver.trim_end_matches('/')
+            ),
+            token: options.access_token.clone(),
+            client: Client::builder()
+                .timeout(std::time::Duration::from_millis(SYNC_TIMEOUT_MS + 30_000))
+                .build()?,
+        })
+    }
+
+    fn send(&self, request: reqwest::blocking::RequestBuilder) -> Result<Value> {
+        let response = request.bearer_auth(&self.token).send()?;
+        let status = response.status();
+        let body: Value = response.json().unwrap_or_default();
+        if !status.is_success() {
+            anyhow::bail!(
+                "Matrix homeserver returned {}: {}",
+                status,
+                body["error"].as_str().unwrap_or("no details")
+            );
+        }
+        Ok(body)
+    }
+
+    fn get(&self, path: &str) -> Result<Value> {
+        self.send(self.client.get(format!("{}{}", self.base, path)))
+    }
+
+    fn whoami(&self) -> Result<String> {
+        let body = self.get("/account/whoami")?;
+        body["user_id"]
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| anyhow::anyhow!("Matrix homeserver didn't say who the token belongs to"))
+    }
+
+    /// Join a room by ID or alias, returning its ID.
+    fn join(&self, room: &str) -> Result<String> {
+        let url = format!("{}/join/{}", self.base, urlencoding::encode(room));
+        let body = self.send(self.client.post(url).json(&json!({})))?;
+        body["room_id"]
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| anyhow::anyhow!("Joining {} returned no room ID", room))
+    }
+
+    /// Whether the room has end-to-end encryption turned on.
+    fn encrypted(&self, room_id: &str) -> Result<bool> {
+        let url = format!(
+            "{}/rooms/{}/state/m.room.encryption/",
+            self.base,
+            urlencoding::encode(room_id)
+        );
+        let response = self.client.get(url).bearer_auth(&self.token).send()?;
+        Ok(response.status().is_success())
+    }
+
+    /// New events since `since` (or none, just a position, without it).
+    fn sync(&self, since: Option<&str>) -> Result<Value> {
+        let path = match since {
+            Some(since) => format!(
+                "/sync?since={}&timeout={}",
+                urlencoding::encode(since),
+                SYNC_TIMEOUT_MS
+            ),
+            None => format!(
+                "/sync?filter={}",
+                urlencoding::encode(r#"{"room":{"timeline":{"limit":0}}}"#)
+            ),
+        };
+        self.get(&path)
+    }
+
+    /// Post `text` to the room as a notice, which other bots leave alone.
+    fn notice(&self, room_id: &str, txn: &str, text: &str) -> Result<()> {
+        let url = format!(
+            "{}/rooms/{}/send/m.room.message/{}",
+            self.base,
+            urlencoding::encode(room_id),
+            urlencoding::encode(txn)
+        );
+        self.send(
+            self.client
+                .put(url)
+                .json(&json!({"msgtype": "m.notice", "body": text})),
+        )?;
+        Ok(())
+    }
+}
+
+/// Listen in the room until stopped, answering each message.
+pub fn run(options: &MatrixOptions) -> Result<()> {
+    let server = Homeserver::new(options)?;
+    let me = server.whoami()?;
+    let room_id = server.join(&options.room)?;
+    // Start from now: messages sent while the bot was away aren't replayed
+    let mut since = server.sync(None)?["next_batch"]
+        .as_str()
+        .map(String::from)
+        .ok_or_else(|| anyhow::anyhow!("Matrix sync returned no position"))?;
+    if server.encrypted(&room_id)? {
+        eprintln!(
+            "Warning: {} is end-to-end encrypted, and the bot can't read encrypted messages; \
+             use an unencrypted room",
+            room_id
+        );
+    }
+    eprintln!("chomp Matrix bot listening as {} in {}", me, room_id);
+
+    let mut sent = 0u64;
+    loop {
+        let batch = match server.sync(Some(&since)) {
+            Ok(batch) => batch,
+            Err(e) => {
+                eprintln!("Matrix sync failed, retrying: {}", e);
+                std::thread::sleep(RETRY_DELAY);
+                continue;
+            }
+        };
+        let unreadable = encrypted_events(&batch, &room_id);
+        if unreadable > 0 {
+            eprintln!(
+                "Skipped {} encrypted message(s) in {}; the bot can't decrypt them",
+                unreadable, room_id
+            );
+        }
+        for message in messages(&batch, &room_id, &me, options.sender.as_deref()) {
+            let reply = match crate::config::open_database(options.profile.as_deref()) {
+                Ok(db) => respond(&db, &message),
+                Err(e) => format!("Database error: {}", e),
+            };
+            sent += 1;
+            let txn = format!("chomp-{}-{}", chrono::Utc::now().timestamp_millis(), sent);
+            if let Err(e) = server.notice(&room_id, &txn, &reply) {
+                eprintln!("Couldn't reply in {}: {}", room_id, e);
+            }
+        }
+        if let Some(next) = batch["next_batch"].as_str() {
+            since = next.to_string();
+        }
+    }
+}
+
+/// Text messages in `room_id` from a sync response, oldest first, skipping
+/// the bot's own and, with `sender`, anyone else's.
+fn messages(batch: &Value, room_id: &str, me: &str, sender: Option<&str>) -> Vec<String> {
+    let Some(events) = batch["rooms"]["join"][room_id]["timeline"]["events"].as_array() else {
+        return Vec::new();
+    };
+    events
+        .iter()
+        .filter(|e| e["type"] == "m.room.message" && e["content"]["msgtype"] == "m.text")
+        .filter(|e| {
+            let from = e["sender"].as_str().unwrap_or_default();
+            from != me && sender.is_none_or(|s| s == from)
+        })
+        .filter_map(|e| e["content"]["body"].as_str().map(String::from))
+        .collect()
+}
+
+/// Encrypted events in `room_id` from a sync response, which `messages`
+/// can't read.
+fn encrypted_events(batch: &Value, room_id: &str) -> usize {
+    batch["rooms"]["join"][room_id]["timeline"]["events"]
+        .as_array()
+        .map_or(0, |events| {
+            events
+                .iter()
+                .filter(|e| e["type"] == "m.room.encrypted")
+                .count()
+        })
+}
+
+/// "today" and "undo" do what they say; anything else is logged.
+fn respond(db: &Database, message: &str) -> String {
+    match message.trim().to_lowercase().as_str() {
+        "today" => chat::today(db),
+        "undo" => chat::undo(db),
+        _ => chat::log_text(db, message).text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages_from_sync() {
+        let batch = json!({
+            "next_batch": "s2",
+            "rooms": {"join": {"!dm:example.org": {"timeline": {"events": [
+                {"type": "m.room.message", "sender": "@me:example.org",
+                 "content": {"msgtype": "m.text", "body": "2 eggs"}},
+                {"type": "m.room.message", "sender": "@chomp:example.org",
+                 "content": {"msgtype": "m.notice", "body": "Logged 2 eggs"}},
+                {"type": "m.room.member", "sender": "@other:example.org",
+                 "content": {"membership": "join"}},
+                {"type": "m.room.message", "sender": "@other:example.org",
+                 "content": {"msgtype": "m.text", "body": "pizza"}},
+                {"type": "m.room.encrypted", "sender": "@me:example.org",
+                 "content": {"algorithm": "m.megolm.v1.aes-sha2"}},
+            ]}}}}
+        });
+        let me = "@chomp:example.org";
+        assert_eq!(
+            messages(&batch, "!dm:example.org", me, None),
+            ["2 eggs", "pizza"]
+        );
+        assert_eq!(
+            messages(&batch, "!dm:example.org", me, Some("@me:example.org")),
+            ["2 eggs"]
+        );
+        assert!(messages(&batch, "!other:example.org", me, None).is_empty());
+        assert_eq!(encrypted_events(&batch, "!dm:example.org"), 1);
+    }
+
+    #[test]
+    fn test_respond() {
+        let db = Database::open_in_memory().unwrap();
+        let eggs = crate::food::Food::new("Eggs", 6.0, 5.0, 0.5, 70.0, "1", vec![]);
+        db.add_food(&eggs).unwrap();
+        assert!(respond(&db, "2 eggs").starts_with("Logged 2 Eggs: 140 kcal"));
+        assert!(respond(&db, " Today ").starts_with("Today: 140 kcal"));
+        assert_eq!(respond(&db, "undo"), "Removed log entry #1 (2)");
+    }
+}
@@ -1,19 +1,106 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use std::io::{BufRead, Write};
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::io::Write;
 
-use crate::db::Database;
-use crate::food::Food;
-use crate::logging::parse_and_log;
+use crate::db::{
+    Database, DeleteMode, DuplicateLog, EntryLocked, FoodInUse, LogEntry, LogOptions, TimeWindow,
+};
+use crate::food::{Food, Macros, Micros};
+use crate::hooks;
+use crate::logging::{default_dedupe_window, parse_and_log_with, AmbiguousFood, FoodNotFound};
 
 const SERVER_NAME: &str = "chomp";
 const SERVER_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// MCP protocol versions we speak, newest first.
+const PROTOCOL_VERSIONS: [&str; 3] = ["2025-06-18", "2025-03-26", "2024-11-05"];
+
+// JSON-RPC 2.0 error codes
+const PARSE_ERROR: i32 = -32700;
+const INVALID_REQUEST: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+const INTERNAL_ERROR: i32 = -32603;
+/// Server-defined: the request ran past the server's time limit
+#[cfg(feature = "sse")]
+const REQUEST_TIMEOUT: i32 = -32001;
+
+/// Default page size for the get_history tool.
+const HISTORY_PAGE_SIZE: u64 = 100;
+
+/// Default cap on search_food results.
+const SEARCH_RESULTS: u64 = 25;
+
+/// A markdown table as an extra text content block, for clients that render
+/// markdown. The JSON block stays first so programmatic callers are unaffected.
+fn markdown_block(headers: &[&str], rows: &[Vec<String>]) -> Value {
+    let mut table = format!("| {} |\n|", headers.join(" | "));
+    for header in headers {
+        table.push_str(if matches!(*header, "Date" | "Food" | "Amount") {
+            " --- |"
+        } else {
+            " ---: |"
+        });
+    }
+    for row in rows {
+        let cells: Vec<String> = row.iter().map(|c| c.replace('|', "\\|")).collect();
+        table.push_str(&format!("\n| {} |", cells.join(" | ")));
+    }
+    json!({"type": "text", "text": table})
+}
+
+fn macro_row(label: &str, amount: &str, totals: &Macros) -> Vec<String> {
+    vec![
+        label.to_string(),
+        amount.to_string(),
+        format!("{:.0}", totals.calories),
+        format!("{:.1}g", totals.protein),
+        format!("{:.1}g", totals.fat),
+        format!("{:.1}g", totals.carbs),
+    ]
+}
+
+fn entry_row(entry: &LogEntry, with_date: bool) -> Vec<String> {
+    let totals = Macros {
+        protein: entry.protein,
+        fat: entry.fat,
+        carbs: entry.carbs,
+        calories: entry.calories,
+        ..Default::default()
+    };
+    let mut row = macro_row(&entry.food_name, &entry.amount, &totals);
+    if with_date {
+        row.insert(0, entry.date.clone());
+    }
+    row
+}
+
+/// Keep only the named fields of each object, so large lists fit a model's
+/// context. Anything but a non-empty array of names keeps every field.
+fn select_fields(items: Vec<Value>, fields: &Value) -> Vec<Value> {
+    let names: Vec<&str> = match fields.as_array() {
+        Some(names) if !names.is_empty() => names.iter().filter_map(|n| n.as_str()).collect(),
+        _ => return items,
+    };
+    items
+        .into_iter()
+        .map(|item| {
+            let kept: serde_json::Map<String, Value> = names
+                .iter()
+                .filter_map(|name| item.get(*name).map(|v| (name.to_string(), v.clone())))
+                .collect();
+            Value::Object(kept)
+        })
+        .collect()
+}
+
+/// A request or notification. The `jsonrpc` member isn't checked, so clients
+/// that leave it out still work.
 #[derive(Debug, Deserialize)]
 pub struct JsonRpcRequest {
-    #[allow(dead_code)]
-    pub jsonrpc: String,
     pub id: Option<Value>,
     pub method: String,
     #[serde(default)]
@@ -27,18 +114,162 @@ pub struct JsonRpcResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub result: Option<Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub error: Option<JsonRpcError>,
+    pub error: Option<Box<JsonRpcError>>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct JsonRpcError {
     pub code: i32,
     pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+/// Sends a server-initiated `sampling/createMessage` request to the client,
+/// letting tools ask the client's model for help, and waits for the result.
+pub trait Sampler {
+    fn create_message(&self, params: Value) -> Result<Value>;
+}
+
+/// Whether this message (or a batch containing it) is an `initialize` from a
+/// client that accepts sampling requests.
+pub fn client_supports_sampling(message: &Value) -> bool {
+    match message {
+        Value::Array(items) => items.iter().any(client_supports_sampling),
+        message => {
+            message["method"] == "initialize"
+                && message["params"]["capabilities"]["sampling"].is_object()
+        }
+    }
+}
+
+/// Whether this is a client's response to one of our requests, rather than a
+/// request of its own.
+pub fn is_response(message: &Value) -> bool {
+    message.is_object()
+        && message.get("method").is_none()
+        && (message.get("result").is_some() || message.get("error").is_some())
+}
+
+/// Turn a JSON-RPC response to one of our sampling requests into its result.
+pub fn sampling_result(response: Value) -> Result<Value> {
+    if let Some(error) = response.get("error") {
+        anyhow::bail!(
+            "Client declined sampling request: {}",
+            error["message"].as_str().unwrap_or("unknown error")
+        );
+    }
+    Ok(response["result"].clone())
+}
+
+fn error_response(id: Value, code: i32, message: impl Into<String>) -> JsonRpcResponse {
+    JsonRpcResponse {
+        jsonrpc: "2.0".to_string(),
+        id,
+        result: None,
+        error: Some(Box::new(JsonRpcError {
+            code,
+            message: message.into(),
+            data: None,
+        })),
+    }
+}
+
+/// Handle one incoming message: a request, a notification, or a batch (array)
+/// of them, and return what to send back. Notifications, stray responses,
+/// and batches made only of those get nothing.
+/// `sampler` is set once the client has said it supports sampling.
+pub fn handle_message(
+    db: &Database,
+    message: Value,
+    sampler: Option<&dyn Sampler>,
+) -> Option<Value> {
+    match message {
+        Value::Array(items) if items.is_empty() => Some(json!(error_response(
+            Value::Null,
+            INVALID_REQUEST,
+            "Invalid request: empty batch"
+        ))),
+        Value::Array(items) => {
+            let responses: Vec<Value> = items
+                .into_iter()
+                .filter_map(|item| handle_single(db, item, sampler))
+                .collect();
+            (!responses.is_empty()).then_some(Value::Array(responses))
+        }
+        message => handle_single(db, message, sampler),
+    }
+}
+
+/// What to send back for a message whose handling was abandoned: a timeout
+/// error for each request in it.
+#[cfg(feature = "sse")]
+pub fn timeout_response(message: &Value, timeout: std::time::Duration) -> Option<Value> {
+    let error = |item: &Value| {
+        let id = item
+            .get("id")
+            .filter(|id| !id.is_null() && !is_response(item))?;
+        Some(json!(error_response(
+            id.clone(),
+            REQUEST_TIMEOUT,
+            format!("Request timed out after {}s", timeout.as_secs())
+        )))
+    };
+    match message {
+        Value::Array(items) => {
+            let responses: Vec<Value> = items.iter().filter_map(error).collect();
+            (!responses.is_empty()).then_some(Value::Array(responses))
+        }
+        item => error(item),
+    }
+}
+
+fn handle_single(db: &Database, message: Value, sampler: Option<&dyn Sampler>) -> Option<Value> {
+    if is_response(&message) {
+        return None;
+    }
+    let id = match message.get("id") {
+        None | Some(Value::Null | Value::String(_) | Value::Number(_)) => {
+            message.get("id").cloned()
+        }
+        Some(_) => {
+            return Some(json!(error_response(
+                Value::Null,
+                INVALID_REQUEST,
+                "Invalid request: id must be a string or number"
+            )))
+        }
+    };
+    let request: JsonRpcRequest = match serde_json::from_value(message) {
+        Ok(request) => request,
+        Err(e) => {
+            return Some(json!(error_response(
+                id.unwrap_or(Value::Null),
+                INVALID_REQUEST,
+                format!("Invalid request: {}", e)
+            )))
+        }
+    };
+    // A panicking tool fails its request, not the server
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        handle_request(db, &request, sampler)
+    }))
+    .unwrap_or_else(|_| {
+        request
+            .id
+            .clone()
+            .map(|id| error_response(id, INTERNAL_ERROR, "Internal error"))
+    })
+    .map(|response| json!(response))
 }
 
 /// Handle a JSON-RPC request and return a response.
 /// Returns None for notifications (no id) that don't need a response.
-pub fn handle_request(db: &Database, request: &JsonRpcRequest) -> Option<JsonRpcResponse> {
+pub fn handle_request(
+    db: &Database,
+    request: &JsonRpcRequest,
+    sampler: Option<&dyn Sampler>,
+) -> Option<JsonRpcResponse> {
     // Per JSON-RPC 2.0 spec, requests without an id are notifications
     // and MUST NOT receive a response.
     let id = match &request.id {
@@ -47,10 +278,24 @@ pub fn handle_request(db: &Database, request: &JsonRpcRequest) -> Option<JsonRpc
     };
 
     let result = match request.method.as_str() {
-        "initialize" => handle_initialize(),
+        "initialize" => Ok(handle_initialize(&request.params)),
+        "ping" => Ok(json!({})),
         "tools/list" => handle_tools_list(),
-        "tools/call" => handle_tools_call(db, &request.params),
-        _ => Err(anyhow::anyhow!("Method not found: {}", request.method)),
+        "tools/call" if !request.params["name"].is_string() => {
+            return Some(error_response(
+                id,
+                INVALID_PARAMS,
+                "Invalid params: tools/call needs a tool name",
+            ))
+        }
+        "tools/call" => handle_tools_call(db, &request.params, sampler),
+        method => {
+            return Some(error_response(
+                id,
+                METHOD_NOT_FOUND,
+                format!("Method not found: {}", method),
+            ))
+        }
     };
 
     Some(match result {
@@ -60,395 +305,784 @@ pub fn handle_request(db: &Database, request: &JsonRpcRequest) -> Option<JsonRpc
             result: Some(value),
             error: None,
         },
-        Err(e) => JsonRpcResponse {
-            jsonrpc: "2.0".to_string(),
-            id,
-            result: None,
-            error: Some(JsonRpcError {
-                code: -32603,
-                message: e.to_string(),
-            }),
-        },
+        Err(e) => {
+            let mut response = error_response(id, INTERNAL_ERROR, error_text(&e));
+            // Let clients offer the close matches, or the variants to choose
+            // from, without parsing the message
+            if let Some(error) = response.error.as_mut() {
+                if let Some(missing) = e.downcast_ref::<FoodNotFound>() {
+                    error.data = Some(json!({
+                        "name": missing.name,
+                        "suggestions": missing.suggestions,
+                    }));
+                } else if let Some(ambiguous) = e.downcast_ref::<AmbiguousFood>() {
+                    error.data = Some(json!({
+                        "name": ambiguous.name,
+                        "variants": ambiguous.variants,
+                    }));
+                }
+            }
+            response
+        }
     })
 }
 
-/// Parse a JSON line into a request, returning an error response on failure.
-pub fn parse_request(line: &str) -> std::result::Result<JsonRpcRequest, JsonRpcResponse> {
-    serde_json::from_str(line).map_err(|e| JsonRpcResponse {
-        jsonrpc: "2.0".to_string(),
-        id: Value::Null,
-        result: None,
-        error: Some(JsonRpcError {
-            code: -32700,
-            message: format!("Parse error: {}", e),
-        }),
-    })
+/// An error's message, with the CLI's flag hints reworded as tool arguments.
+fn error_text(e: &anyhow::Error) -> String {
+    if let Some(in_use) = e.downcast_ref::<FoodInUse>() {
+        format!(
+            "{}; call delete_food with mode: \"keep-history\" to keep the log entries, or mode: \"cascade\" to trash them along with the food",
+            in_use.summary()
+        )
+    } else if let Some(locked) = e.downcast_ref::<EntryLocked>() {
+        format!(
+            "{}; they can only be changed with chomp --amend on the command line",
+            locked.summary()
+        )
+    } else {
+        e.to_string()
+    }
+}
+
+/// Parse a JSON line into a message, returning an error response on failure.
+pub fn parse_message(line: &str) -> std::result::Result<Value, JsonRpcResponse> {
+    serde_json::from_str(line)
+        .map_err(|e| error_response(Value::Null, PARSE_ERROR, format!("Parse error: {}", e)))
+}
+
+/// The client on the other end of stdio. Messages that arrive while we wait
+/// for a sampling response are queued and handled afterwards.
+struct StdioPeer {
+    lines: RefCell<std::io::Lines<std::io::StdinLock<'static>>>,
+    deferred: RefCell<VecDeque<String>>,
+    next_id: Cell<u64>,
+}
+
+impl StdioPeer {
+    fn next_line(&self) -> Result<Option<String>> {
+        if let Some(line) = self.deferred.borrow_mut().pop_front() {
+            return Ok(Some(line));
+        }
+        Ok(self.lines.borrow_mut().next().transpose()?)
+    }
+
+    fn send<T: Serialize>(&self, message: &T) -> Result<()> {
+        let mut stdout = std::io::stdout().lock();
+        writeln!(stdout, "{}", serde_json::to_string(message)?)?;
+        stdout.flush()?;
+        Ok(())
+    }
+}
+
+impl Sampler for StdioPeer {
+    fn create_message(&self, params: Value) -> Result<Value> {
+        let id = format!("chomp-sampling-{}", self.next_id.get());
+        self.next_id.set(self.next_id.get() + 1);
+        self.send(&json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "sampling/createMessage",
+            "params": params
+        }))?;
+
+        loop {
+            let line = self
+                .lines
+                .borrow_mut()
+                .next()
+                .transpose()?
+                .ok_or_else(|| anyhow::anyhow!("Client closed stdin during sampling"))?;
+            match serde_json::from_str::<Value>(&line) {
+                Ok(message) if message["id"] == id && message.get("method").is_none() => {
+                    return sampling_result(message);
+                }
+                _ => self.deferred.borrow_mut().push_back(line),
+            }
+        }
+    }
 }
 
 /// Run the MCP server over stdio transport.
 pub fn serve_stdio() -> Result<()> {
-    let db = Database::open()?;
-    db.init()?;
+    let db = crate::config::open_database(None)?;
 
-    let stdin = std::io::stdin();
-    let mut stdout = std::io::stdout();
+    let peer = StdioPeer {
+        lines: RefCell::new(std::io::stdin().lines()),
+        deferred: RefCell::new(VecDeque::new()),
+        next_id: Cell::new(1),
+    };
+    let mut sampling = false;
 
-    for line in stdin.lock().lines() {
-        let line = line?;
+    while let Some(line) = peer.next_line()? {
         if line.trim().is_empty() {
             continue;
         }
 
-        match parse_request(&line) {
-            Ok(request) => {
-                if let Some(response) = handle_request(&db, &request) {
-                    writeln!(stdout, "{}", serde_json::to_string(&response)?)?;
-                    stdout.flush()?;
+        match parse_message(&line) {
+            Ok(message) => {
+                sampling |= client_supports_sampling(&message);
+                let sampler = sampling.then_some(&peer as &dyn Sampler);
+                if let Some(response) = handle_message(&db, message, sampler) {
+                    peer.send(&response)?;
                 }
             }
-            Err(error_response) => {
-                writeln!(stdout, "{}", serde_json::to_string(&error_response)?)?;
-                stdout.flush()?;
-            }
+            Err(error_response) => peer.send(&error_response)?,
         }
     }
 
     Ok(())
 }
 
-fn handle_initialize() -> Result<Value> {
-    Ok(json!({
-        "protocolVersion": "2024-11-05",
+/// Answer the handshake with the client's protocol version when we speak it,
+/// and otherwise our newest, which the client may accept or disconnect over.
+fn handle_initialize(params: &Value) -> Value {
+    let version = params["protocolVersion"]
+        .as_str()
+        .filter(|v| PROTOCOL_VERSIONS.contains(v))
+        .unwrap_or(PROTOCOL_VERSIONS[0]);
+    json!({
+        "protocolVersion": version,
         "capabilities": {
-            "tools": {}
+            "tools": {
+                "listChanged": false
+            }
         },
         "serverInfo": {
             "name": SERVER_NAME,
             "version": SERVER_VERSION
         }
-    }))
+    })
+}
+
+/// Undo window in minutes, from CHOMP_UNDO_WINDOW (default 10).
+fn default_undo_window() -> u32 {
+    std::env::var("CHOMP_UNDO_WINDOW")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(10)
 }
 
 fn handle_tools_list() -> Result<Value> {
-    Ok(json!({
-        "tools": [
-            {
-                "name": "log_food",
-                "description": "Log food consumption. Returns calculated macros. When logging beverages (coffee, tea, milk, juice, etc.), also call log_water with the liquid volume and log_caffeine if caffeinated.",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "food": {
-                            "type": "string",
-                            "description": "Food name and optional amount, e.g. 'salmon 4oz' or 'bare bar'"
-                        },
-                        "date": {
-                            "type": "string",
-                            "description": "Date to log for in YYYY-MM-DD format (defaults to today if omitted)"
-                        }
+    Ok(json!({ "tools": tools() }))
+}
+
+/// Every tool the server offers, with its name, description, and JSON
+/// schema for its arguments, as `tools/list` returns them. Clients match on
+/// these names and arguments, so renaming either is a breaking change; the
+/// `tool_schemas` snapshot test catches it.
+pub fn tools() -> Value {
+    json!([
+        {
+            "name": "log_food",
+            "description": "Log food consumption. Returns calculated macros. When logging beverages (coffee, tea, milk, juice, etc.), also call log_water with the liquid volume and log_caffeine if caffeinated.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "food": {
+                        "type": "string",
+                        "description": "Food name and optional amount, e.g. 'salmon 4oz' or 'bare bar'"
                     },
-                    "required": ["food"]
-                }
-            },
-            {
-                "name": "search_food",
-                "description": "Search for foods in the database. Returns matching foods with nutrition info.",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "query": {
-                            "type": "string",
-                            "description": "Search query (fuzzy matching supported)"
-                        }
+                    "date": {
+                        "type": "string",
+                        "description": "Date to log for in YYYY-MM-DD format (defaults to today if omitted)"
                     },
-                    "required": ["query"]
-                }
-            },
-            {
-                "name": "add_food",
-                "description": "Add a new food to the database.",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "name": {
-                            "type": "string",
-                            "description": "Food name"
-                        },
-                        "protein": {
-                            "type": "number",
-                            "description": "Protein in grams per serving"
-                        },
-                        "fat": {
-                            "type": "number",
-                            "description": "Fat in grams per serving"
-                        },
-                        "carbs": {
-                            "type": "number",
-                            "description": "Carbs in grams per serving"
-                        },
-                        "serving": {
-                            "type": "string",
-                            "description": "Serving size, e.g. '100g', '1 bar', '4oz'"
-                        },
-                        "calories": {
-                            "type": "number",
-                            "description": "Calories per serving (calculated if not provided)"
-                        },
-                        "aliases": {
-                            "type": "array",
-                            "items": { "type": "string" },
-                            "description": "Alternative names for this food"
-                        }
+                    "time": {
+                        "type": "string",
+                        "description": "Time it was eaten, e.g. '08:30' or '8:30am' (defaults to now); use with date to record a forgotten meal"
                     },
-                    "required": ["name", "protein", "fat", "carbs", "serving"]
-                }
-            },
-            {
-                "name": "get_today",
-                "description": "Get today's nutrition totals.",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {}
-                }
-            },
-            {
-                "name": "get_history",
-                "description": "Get recent food log entries.",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "days": {
-                            "type": "integer",
-                            "description": "Number of days to look back (default: 7)"
-                        }
+                    "meal": {
+                        "type": "string",
+                        "description": "Meal to log under: breakfast, lunch, dinner, snack, or another meal from the user's config"
+                    },
+                    "idempotency_key": {
+                        "type": "string",
+                        "description": "Optional unique key for this call; retrying with the same key returns the original entry instead of logging twice"
+                    },
+                    "force": {
+                        "type": "boolean",
+                        "description": "Log even if the same food and amount was just logged (otherwise the call is flagged as a duplicate and skipped)"
+                    },
+                    "strict": {
+                        "type": "boolean",
+                        "description": "Only accept an exact food name or alias; by default a misspelled name logs the closest food, weighted by logging history"
+                    },
+                    "estimate": {
+                        "type": "boolean",
+                        "description": "When the food is unknown and the client supports sampling, ask the client's model to estimate its macros and save it as a food tagged 'estimated' (default true)"
                     }
-                }
-            },
-            {
-                "name": "unlog",
-                "description": "Delete a log entry by its ID (rowid from the log table).",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "id": {
-                            "type": "integer",
-                            "description": "Log entry ID to delete"
-                        }
+                },
+                "required": ["food"]
+            }
+        },
+        {
+            "name": "search_food",
+            "description": "Search for foods in the database. Returns matching foods with nutrition info.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "Search query (fuzzy matching supported)"
                     },
-                    "required": ["id"]
-                }
-            },
-            {
-                "name": "unlog_last",
-                "description": "Delete the most recent log entry.",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {}
-                }
-            },
-            {
-                "name": "delete_food",
-                "description": "Delete a food from the database by name.",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "name": {
-                            "type": "string",
-                            "description": "Food name to delete"
-                        }
+                    "max_results": {
+                        "type": "integer",
+                        "description": "Maximum foods to return (default: 25)"
                     },
-                    "required": ["name"]
-                }
-            },
-            {
-                "name": "edit_food",
-                "description": "Edit a food entry. Only provided fields are updated.",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "name": {
-                            "type": "string",
-                            "description": "Food name to edit"
-                        },
-                        "protein": {
-                            "type": "number",
-                            "description": "New protein in grams per serving"
-                        },
-                        "fat": {
-                            "type": "number",
-                            "description": "New fat in grams per serving"
-                        },
-                        "carbs": {
-                            "type": "number",
-                            "description": "New carbs in grams per serving"
-                        },
-                        "serving": {
-                            "type": "string",
-                            "description": "New serving size"
-                        },
-                        "calories": {
-                            "type": "number",
-                            "description": "New calories (recalculated from macros if not provided)"
-                        }
+                    "fields": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Only include these fields of each food, e.g. [\"name\", \"calories\"]"
                     },
-                    "required": ["name"]
-                }
-            },
-            {
-                "name": "edit_log",
-                "description": "Edit a log entry. Only provided fields are updated.",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "id": {
-                            "type": "integer",
-                            "description": "Log entry ID to edit"
-                        },
-                        "amount": {
-                            "type": "string",
-                            "description": "New amount"
-                        },
-                        "protein": {
-                            "type": "number",
-                            "description": "New protein in grams"
-                        },
-                        "fat": {
-                            "type": "number",
-                            "description": "New fat in grams"
-                        },
-                        "carbs": {
-                            "type": "number",
-                            "description": "New carbs in grams"
-                        }
+                    "summary": {
+                        "type": "boolean",
+                        "description": "One compact line per food instead of JSON (default: false)"
+                    }
+                },
+                "required": ["query"]
+            }
+        },
+        {
+            "name": "add_food",
+            "description": "Add a new food to the database.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "name": {
+                        "type": "string",
+                        "description": "Food name"
                     },
-                    "required": ["id"]
-                }
-            },
-            {
-                "name": "log_water",
-                "description": "Log water/hydration intake. Tracks ALL liquid intake including plain water, coffee, tea, milk, juice, etc. Supports ml (default), oz, cups, liters.",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "amount": {
-                            "type": "string",
-                            "description": "Water amount, e.g. '500', '500ml', '16oz', '2 cups', '1l'"
-                        },
-                        "date": {
-                            "type": "string",
-                            "description": "Date in YYYY-MM-DD format (defaults to today)"
-                        }
+                    "variant": {
+                        "type": "string",
+                        "description": "Brand or preparation telling this food apart from others of the same name, e.g. 'dry' to add 'Oats (dry)' beside 'Oats (cooked)'"
                     },
-                    "required": ["amount"]
-                }
-            },
-            {
-                "name": "get_water_today",
-                "description": "Get today's total hydration (all liquids) in ml.",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {}
-                }
-            },
-            {
-                "name": "get_water_history",
-                "description": "Get water intake history.",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "days": {
-                            "type": "integer",
-                            "description": "Number of days to look back (default: 7)"
-                        }
+                    "protein": {
+                        "type": "number",
+                        "description": "Protein in grams per serving"
+                    },
+                    "fat": {
+                        "type": "number",
+                        "description": "Fat in grams per serving"
+                    },
+                    "carbs": {
+                        "type": "number",
+                        "description": "Carbs in grams per serving"
+                    },
+                    "serving": {
+                        "type": "string",
+                        "description": "Serving size, e.g. '100g', '1 bar', '4oz'"
+                    },
+                    "calories": {
+                        "type": "number",
+                        "description": "Calories per serving (calculated if not provided)"
+                    },
+                    "fiber": {
+                        "type": "number",
+                        "description": "Grams of fiber per serving, included in carbs (default: 0)"
+                    },
+                    "sugar": {
+                        "type": "number",
+                        "description": "Grams of sugar per serving, included in carbs (omit if unknown)"
+                    },
+                    "sodium": {
+                        "type": "number",
+                        "description": "Milligrams of sodium per serving (omit if unknown)"
+                    },
+                    "saturated_fat": {
+                        "type": "number",
+                        "description": "Grams of saturated fat per serving, included in fat (omit if unknown)"
+                    },
+                    "aliases": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Alternative names for this food"
+                    }
+                },
+                "required": ["name", "protein", "fat", "carbs", "serving"]
+            }
+        },
+        {
+            "name": "get_today",
+            "description": "Get today's nutrition totals.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "markdown": {
+                        "type": "boolean",
+                        "description": "Also return a markdown table as a second content block (default: false)"
                     }
                 }
-            },
-            {
-                "name": "unlog_water",
-                "description": "Delete a water log entry by ID.",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "id": {
-                            "type": "integer",
-                            "description": "Water log entry ID to delete"
-                        }
+            }
+        },
+        {
+            "name": "get_history",
+            "description": "Get recent food log entries, newest first, a page at a time.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "days": {
+                        "type": "integer",
+                        "description": "Number of days to look back (default: 7)"
                     },
-                    "required": ["id"]
-                }
-            },
-            {
-                "name": "log_caffeine",
-                "description": "Log caffeine intake in mg with optional source. Common amounts: coffee ~95mg/8oz, espresso ~63mg/shot, black tea ~47mg/8oz, green tea ~28mg/8oz, cola ~34mg/12oz.",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "amount_mg": {
-                            "type": "number",
-                            "description": "Caffeine amount in milligrams"
-                        },
-                        "source": {
-                            "type": "string",
-                            "description": "Source of caffeine, e.g. 'coffee', 'tea', 'energy drink'"
-                        },
-                        "date": {
-                            "type": "string",
-                            "description": "Date in YYYY-MM-DD format (defaults to today)"
-                        }
+                    "limit": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "description": "Maximum entries to return (default: 100); max_results is accepted too"
+                    },
+                    "fields": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Only include these fields of each entry, e.g. [\"date\", \"food_name\", \"calories\"]"
+                    },
+                    "summary": {
+                        "type": "boolean",
+                        "description": "Return one line of totals per day instead of individual entries (default: false)"
                     },
-                    "required": ["amount_mg"]
+                    "markdown": {
+                        "type": "boolean",
+                        "description": "Also return a markdown table as a second content block (default: false)"
+                    },
+                    "offset": {
+                        "type": "integer",
+                        "description": "Entries to skip; pass the previous page's next_offset (default: 0)"
+                    },
+                    "after": {
+                        "type": "string",
+                        "description": "Only entries logged at or after this local time of day, e.g. \"18:00\" or \"6pm\""
+                    },
+                    "before": {
+                        "type": "string",
+                        "description": "Only entries logged before this local time of day; with after, a later after than before wraps past midnight"
+                    }
                 }
-            },
-            {
-                "name": "get_caffeine_today",
-                "description": "Get today's total caffeine intake in mg.",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {}
+            }
+        },
+        {
+            "name": "unlog",
+            "description": "Delete a log entry by its ID (rowid from the log table).",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "integer",
+                        "description": "Log entry ID to delete"
+                    }
+                },
+                "required": ["id"]
+            }
+        },
+        {
+            "name": "unlog_last",
+            "description": "Delete the most recent log entry.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {}
+            }
+        },
+        {
+            "name": "undo_last",
+            "description": "Revert the most recent change (log, edit, delete, or added food) if it happened within the undo window. Call again to step further back.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "window_minutes": {
+                        "type": "number",
+                        "description": "How far back to look in minutes (default: CHOMP_UNDO_WINDOW or 10)"
+                    }
                 }
-            },
-            {
-                "name": "get_caffeine_history",
-                "description": "Get caffeine intake history.",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "days": {
-                            "type": "integer",
-                            "description": "Number of days to look back (default: 7)"
-                        }
+            }
+        },
+        {
+            "name": "delete_food",
+            "description": "Delete a food from the database by name. Refuses while log entries or compound foods use it unless a mode says what to do with them.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "name": {
+                        "type": "string",
+                        "description": "Food name to delete"
+                    },
+                    "mode": {
+                        "type": "string",
+                        "enum": ["keep-history", "cascade"],
+                        "description": "keep-history keeps the food's log entries; cascade moves them to the trash with it"
+                    }
+                },
+                "required": ["name"]
+            }
+        },
+        {
+            "name": "edit_food",
+            "description": "Edit a food entry. Only provided fields are updated.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "name": {
+                        "type": "string",
+                        "description": "Food name to edit"
+                    },
+                    "protein": {
+                        "type": "number",
+                        "description": "New protein in grams per serving"
+                    },
+                    "fat": {
+                        "type": "number",
+                        "description": "New fat in grams per serving"
+                    },
+                    "carbs": {
+                        "type": "number",
+                        "description": "New carbs in grams per serving"
+                    },
+                    "fiber": {
+                        "type": "number",
+                        "description": "New fiber in grams per serving, included in carbs"
+                    },
+                    "serving": {
+                        "type": "string",
+                        "description": "New serving size"
+                    },
+                    "calories": {
+                        "type": "number",
+                        "description": "New calories (recalculated from macros if not provided)"
+                    },
+                    "sugar": {
+                        "type": "number",
+                        "description": "New sugar in grams per serving"
+                    },
+                    "sodium": {
+                        "type": "number",
+                        "description": "New sodium in milligrams per serving"
+                    },
+                    "saturated_fat": {
+                        "type": "number",
+                        "description": "New saturated fat in grams per serving"
+                    }
+                },
+                "required": ["name"]
+            }
+        },
+        {
+            "name": "edit_log",
+            "description": "Edit a log entry. Only provided fields are updated.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "integer",
+                        "description": "Log entry ID to edit"
+                    },
+                    "amount": {
+                        "type": "string",
+                        "description": "New amount"
+                    },
+                    "protein": {
+                        "type": "number",
+                        "description": "New protein in grams"
+                    },
+                    "fat": {
+                        "type": "number",
+                        "description": "New fat in grams"
+                    },
+                    "carbs": {
+                        "type": "number",
+                        "description": "New carbs in grams"
+                    }
+                },
+                "required": ["id"]
+            }
+        },
+        {
+            "name": "log_water",
+            "description": "Log water/hydration intake. Tracks ALL liquid intake including plain water, coffee, tea, milk, juice, etc. Supports ml (default), oz, cups, liters.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "amount": {
+                        "type": "string",
+                        "description": "Water amount, e.g. '500', '500ml', '16oz', '2 cups', '1l'"
+                    },
+                    "date": {
+                        "type": "string",
+                        "description": "Date in YYYY-MM-DD format (defaults to today)"
+                    },
+                    "idempotency_key": {
+                        "type": "string",
+                        "description": "Optional unique key for this call; retrying with the same key returns the original entry instead of logging twice"
+                    }
+                },
+                "required": ["amount"]
+            }
+        },
+        {
+            "name": "get_water_today",
+            "description": "Get today's total hydration (all liquids) in ml.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {}
+            }
+        },
+        {
+            "name": "get_water_history",
+            "description": "Get water intake history.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "days": {
+                        "type": "integer",
+                        "description": "Number of days to look back (default: 7)"
                     }
                 }
-            },
-            {
-                "name": "unlog_caffeine",
-                "description": "Delete a caffeine log entry by ID.",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "id": {
-                            "type": "integer",
-                            "description": "Caffeine log entry ID to delete"
-                        }
+            }
+        },
+        {
+            "name": "unlog_water",
+            "description": "Delete a water log entry by ID.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "integer",
+                        "description": "Water log entry ID to delete"
+                    }
+                },
+                "required": ["id"]
+            }
+        },
+        {
+            "name": "log_caffeine",
+            "description": "Log caffeine intake in mg with optional source. Common amounts: coffee ~95mg/8oz, espresso ~63mg/shot, black tea ~47mg/8oz, green tea ~28mg/8oz, cola ~34mg/12oz.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "amount_mg": {
+                        "type": "number",
+                        "description": "Caffeine amount in milligrams"
+                    },
+                    "source": {
+                        "type": "string",
+                        "description": "Source of caffeine, e.g. 'coffee', 'tea', 'energy drink'"
                     },
-                    "required": ["id"]
+                    "date": {
+                        "type": "string",
+                        "description": "Date in YYYY-MM-DD format (defaults to today)"
+                    },
+                    "idempotency_key": {
+                        "type": "string",
+                        "description": "Optional unique key for this call; retrying with the same key returns the original entry instead of logging twice"
+                    }
+                },
+                "required": ["amount_mg"]
+            }
+        },
+        {
+            "name": "get_caffeine_today",
+            "description": "Get today's total caffeine intake in mg.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {}
+            }
+        },
+        {
+            "name": "get_caffeine_history",
+            "description": "Get caffeine intake history.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "days": {
+                        "type": "integer",
+                        "description": "Number of days to look back (default: 7)"
+                    }
                 }
             }
-        ]
+        },
+        {
+            "name": "unlog_caffeine",
+            "description": "Delete a caffeine log entry by ID.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "integer",
+                        "description": "Caffeine log entry ID to delete"
+                    }
+                },
+                "required": ["id"]
+            }
+        },
+        {
+            "name": "get_missing_days",
+            "description": "List recent past days with nothing logged or suspiciously few calories, so you can ask whether the user ate nothing or forgot to log. Today is not included.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "days": {
+                        "type": "integer",
+                        "description": "Number of days to look back (default: 30)"
+                    }
+                }
+            }
+        }
+    ])
+}
+
+/// Common options accepted by the log-writing tools.
+fn log_options(arguments: &Value) -> LogOptions<'_> {
+    LogOptions {
+        date: arguments["date"].as_str(),
+        idempotency_key: arguments["idempotency_key"].as_str(),
+        dedupe_window: if arguments["force"].as_bool().unwrap_or(false) {
+            None
+        } else {
+            Some(default_dedupe_window())
+        },
+        strict: arguments["strict"].as_bool().unwrap_or(false),
+        ..Default::default()
+    }
+}
+
+/// Sugar, sodium, and saturated fat arguments; any left out stay unknown.
+fn micros_argument(arguments: &Value) -> Micros {
+    Micros {
+        sugar: arguments["sugar"].as_f64(),
+        sodium: arguments["sodium"].as_f64(),
+        saturated_fat: arguments["saturated_fat"].as_f64(),
+    }
+}
+
+/// Prompt for `estimate_food`; the reply is parsed as JSON.
+const ESTIMATE_PROMPT: &str = "Estimate the nutrition of this food as typically eaten: \"{food}\". \
+Reply with only a JSON object, no prose: {\"serving\": a typical serving such as \"1 cup\", \"1 slice\" or \"100g\", \
+\"protein\": grams, \"fat\": grams, \"carbs\": grams, \"calories\": kcal} for that serving.";
+
+#[derive(Debug, Deserialize)]
+struct Estimate {
+    serving: String,
+    protein: f64,
+    fat: f64,
+    carbs: f64,
+    calories: Option<f64>,
+}
+
+/// Ask the client's model for the macros of an unknown food, then save it
+/// under the name the user typed, tagged "estimated" so it can be reviewed.
+fn estimate_food(db: &Database, sampler: &dyn Sampler, name: &str) -> Result<Food> {
+    let result = sampler.create_message(json!({
+        "messages": [{
+            "role": "user",
+            "content": {"type": "text", "text": ESTIMATE_PROMPT.replace("{food}", name)}
+        }],
+        "systemPrompt": "You are a nutrition database. Answer with JSON only.",
+        "maxTokens": 200
+    }))?;
+    let text = result["content"]["text"]
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("Sampling result had no text content"))?;
+    let json = match (text.find('{'), text.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &text[start..=end],
+        _ => anyhow::bail!("Could not read an estimate from: {}", text),
+    };
+    let estimate: Estimate = serde_json::from_str(json)
+        .map_err(|e| anyhow::anyhow!("Could not read an estimate from {}: {}", json, e))?;
+
+    let calories = estimate.calories.unwrap_or_else(|| {
+        db.calories_from_macros(estimate.protein, estimate.fat, estimate.carbs, 0.0)
+    });
+    let mut food = Food::new(
+        name,
+        estimate.protein,
+        estimate.fat,
+        estimate.carbs,
+        calories,
+        &estimate.serving,
+        vec![],
+    );
+    let id = db.add_food(&food)?;
+    db.tag_food(id, "estimated")?;
+    food.id = Some(id);
+    Ok(food)
+}
+
+/// Estimate an unknown food via sampling, then log the original input.
+fn log_estimated(
+    db: &Database,
+    sampler: &dyn Sampler,
+    name: &str,
+    input: &str,
+    opts: &LogOptions,
+) -> Result<Value> {
+    let food = estimate_food(db, sampler, name)?;
+    let before = hooks::before_log(db);
+    let entry = parse_and_log_with(db, input, opts)?;
+    hooks::after_log(db, &before);
+    Ok(json!({
+        "content": [{
+            "type": "text",
+            "text": format!(
+                "'{}' wasn't in the database, so it was added from your model's estimate ({:.0}p/{:.0}f/{:.0}c per {}, tagged 'estimated'; fix it with edit_food).\n{}",
+                food.name,
+                food.protein,
+                food.fat,
+                food.carbs,
+                food.serving,
+                serde_json::to_string_pretty(&entry)?
+            )
+        }],
+        "estimated": true
     }))
 }
 
-fn handle_tools_call(db: &Database, params: &Value) -> Result<Value> {
+fn handle_tools_call(
+    db: &Database,
+    params: &Value,
+    sampler: Option<&dyn Sampler>,
+) -> Result<Value> {
     let tool_name = params["name"].as_str().unwrap_or("");
     let arguments = &params["arguments"];
 
     match tool_name {
         "log_food" => {
+            crate::recurring::roll_over_now(db);
             let food = arguments["food"]
                 .as_str()
                 .ok_or_else(|| anyhow::anyhow!("Missing 'food' argument"))?;
-            let date = arguments["date"].as_str();
-            let entry = parse_and_log(db, food, date)?;
+            let meal = match arguments["meal"].as_str() {
+                Some(meal) => Some(crate::config::Config::load()?.meal(meal)?),
+                None => None,
+            };
+            let opts = LogOptions {
+                time: arguments["time"]
+                    .as_str()
+                    .map(crate::db::parse_time_of_day)
+                    .transpose()?,
+                meal: meal.as_deref(),
+                ..log_options(arguments)
+            };
+            let estimate = arguments["estimate"].as_bool().unwrap_or(true);
+            let before = hooks::before_log(db);
+            let entry = match parse_and_log_with(db, food, &opts) {
+                Ok(entry) => entry,
+                Err(e) => match e.downcast_ref::<DuplicateLog>() {
+                    Some(dup) => {
+                        return Ok(json!({
+                            "content": [{
+                                "type": "text",
+                                "text": format!(
+                                    "Possible duplicate, not logged: {}. Call log_food again with force: true if this is intentional.",
+                                    dup.summary()
+                                )
+                            }],
+                            "duplicate": true,
+                            "existing": dup.existing
+                        }))
+                    }
+                    None => match (e.downcast_ref::<FoodNotFound>(), sampler) {
+                        (Some(missing), Some(sampler)) if estimate => {
+                            return log_estimated(db, sampler, &missing.name, food, &opts);
+                        }
+                        _ => return Err(e),
+                    },
+                },
+            };
+            hooks::after_log(db, &before);
             Ok(json!({
                 "content": [{
                     "type": "text",
@@ -460,12 +1094,38 @@ fn handle_tools_call(db: &Database, params: &Value) -> Result<Value> {
             let query = arguments["query"]
                 .as_str()
                 .ok_or_else(|| anyhow::anyhow!("Missing 'query' argument"))?;
-            let results = db.search_foods(query)?;
-            Ok(json!({
-                "content": [{
+            let mut results = db.search_foods(query)?;
+            let max = arguments["max_results"].as_u64().unwrap_or(SEARCH_RESULTS) as usize;
+            let more = results.len().saturating_sub(max);
+            results.truncate(max);
+            let text = if arguments["summary"].as_bool().unwrap_or(false) {
+                results
+                    .iter()
+                    .map(|f| {
+                        format!(
+                            "{}: {:.0}p/{:.0}f/{:.0}c, {:.0} kcal per {}",
+                            f.name, f.protein, f.fat, f.carbs, f.calories, f.serving
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            } else {
+                let items = results
+                    .iter()
+                    .map(serde_json::to_value)
+                    .collect::<serde_json::Result<Vec<_>>>()?;
+                serde_json::to_string_pretty(&select_fields(items, &arguments["fields"]))?
+            };
+            let mut content = vec![json!({"type": "text", "text": text})];
+            if more > 0 {
+                content.push(json!({
                     "type": "text",
-                    "text": serde_json::to_string_pretty(&results)?
-                }]
+                    "text": format!("{} more match(es); narrow the query or raise max_results", more)
+                }));
+            }
+            Ok(json!({
+                "content": content,
+                "more": more
             }))
         }
         "add_food" => {
@@ -484,9 +1144,10 @@ fn handle_tools_call(db: &Database, params: &Value) -> Result<Value> {
             let serving = arguments["serving"]
                 .as_str()
                 .ok_or_else(|| anyhow::anyhow!("Missing 'serving' argument"))?;
+            let fiber = arguments["fiber"].as_f64().unwrap_or(0.0);
             let calories = arguments["calories"]
                 .as_f64()
-                .unwrap_or(protein * 4.0 + fat * 9.0 + carbs * 4.0);
+                .unwrap_or_else(|| db.calories_from_macros(protein, fat, carbs, fiber));
             let aliases: Vec<String> = arguments["aliases"]
                 .as_array()
                 .map(|arr| {
@@ -496,34 +1157,141 @@ fn handle_tools_call(db: &Database, params: &Value) -> Result<Value> {
                 })
                 .unwrap_or_default();
 
-            let food = Food::new(name, protein, fat, carbs, calories, serving, aliases);
+            let name = match arguments["variant"].as_str() {
+                Some(variant) => crate::food::variant_name(name, variant),
+                None => name.to_string(),
+            };
+            let mut food = Food::new(&name, protein, fat, carbs, calories, serving, aliases);
+            food.fiber = fiber;
+            food.micros = micros_argument(arguments);
+            let clashes = db.name_clashes(&food.name, &food.aliases, None)?;
             db.add_food(&food)?;
 
+            let mut text = format!(
+                "Added: {} ({:.0}p/{:.0}f/{:.0}c per {})",
+                name, protein, fat, carbs, serving
+            );
+            for clash in &clashes {
+                text.push_str(&format!("\nWarning: {}", clash));
+            }
             Ok(json!({
                 "content": [{
                     "type": "text",
-                    "text": format!("Added: {} ({:.0}p/{:.0}f/{:.0}c per {})",
-                        name, protein, fat, carbs, serving)
-                }]
+                    "text": text
+                }],
+                "clashes": clashes
             }))
         }
         "get_today" => {
+            crate::recurring::roll_over_now(db);
             let totals = db.get_today_totals()?;
+            let flags = hooks::Thresholds::load(db).flags(&totals);
+            let mut text = serde_json::to_string_pretty(&totals)?;
+            for flag in flags.iter().filter(|f| f.crossed) {
+                text.push_str(&format!(
+                    "\nThreshold {} crossed: {} {:.0} of {:.0} ({}%)",
+                    flag.name, flag.metric, flag.value, flag.target, flag.percent
+                ));
+            }
+            let mut content = vec![json!({"type": "text", "text": text})];
+            if arguments["markdown"].as_bool().unwrap_or(false) {
+                let mut rows: Vec<Vec<String>> = db
+                    .get_today_entries()?
+                    .iter()
+                    .map(|e| entry_row(e, false))
+                    .collect();
+                rows.push(macro_row("**Total**", "", &totals));
+                content.push(markdown_block(
+                    &["Food", "Amount", "kcal", "Protein", "Fat", "Carbs"],
+                    &rows,
+                ));
+            }
             Ok(json!({
-                "content": [{
-                    "type": "text",
-                    "text": serde_json::to_string_pretty(&totals)?
-                }]
+                "content": content,
+                "flags": flags
             }))
         }
         "get_history" => {
             let days = arguments["days"].as_u64().unwrap_or(7) as u32;
-            let entries = db.get_history(days)?;
-            Ok(json!({
-                "content": [{
+            if arguments["summary"].as_bool().unwrap_or(false) {
+                let since = chrono::Local::now()
+                    .checked_sub_signed(chrono::Duration::days(days.into()))
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("days {} reaches back further than dates go", days)
+                    })?
+                    .format("%Y-%m-%d")
+                    .to_string();
+                let days = db.rollups(&since, "day")?;
+                let lines: Vec<String> = days
+                    .iter()
+                    .rev()
+                    .map(|d| {
+                        format!(
+                            "{}: {:.0} kcal, {:.0}p/{:.0}f/{:.0}c",
+                            d.period, d.calories, d.protein, d.fat, d.carbs
+                        )
+                    })
+                    .collect();
+                let text = if lines.is_empty() {
+                    "No entries".to_string()
+                } else {
+                    lines.join("\n")
+                };
+                let mut content = vec![json!({"type": "text", "text": text})];
+                if arguments["markdown"].as_bool().unwrap_or(false) {
+                    let rows: Vec<Vec<String>> = days
+                        .iter()
+                        .rev()
+                        .map(|d| {
+                            vec![
+                                d.period.clone(),
+                                format!("{:.0}", d.calories),
+                                format!("{:.1}g", d.protein),
+                                format!("{:.1}g", d.fat),
+                                format!("{:.1}g", d.carbs),
+                            ]
+                        })
+                        .collect();
+                    content.push(markdown_block(
+                        &["Date", "kcal", "Protein", "Fat", "Carbs"],
+                        &rows,
+                    ));
+                }
+                return Ok(json!({ "content": content }));
+            }
+            let limit = arguments["limit"]
+                .as_u64()
+                .or(arguments["max_results"].as_u64())
+                .unwrap_or(HISTORY_PAGE_SIZE)
+                .min(u32::MAX as u64) as u32;
+            let offset = arguments["offset"].as_u64().unwrap_or(0) as u32;
+            let window =
+                TimeWindow::parse(arguments["after"].as_str(), arguments["before"].as_str())?;
+            let page = db.get_history_page(days, window, Some(limit), offset)?;
+            let items = page
+                .entries
+                .iter()
+                .map(serde_json::to_value)
+                .collect::<serde_json::Result<Vec<_>>>()?;
+            let text = serde_json::to_string_pretty(&select_fields(items, &arguments["fields"]))?;
+            let mut content = vec![json!({"type": "text", "text": text})];
+            if arguments["markdown"].as_bool().unwrap_or(false) {
+                let rows: Vec<Vec<String>> =
+                    page.entries.iter().map(|e| entry_row(e, true)).collect();
+                content.push(markdown_block(
+                    &["Date", "Food", "Amount", "kcal", "Protein", "Fat", "Carbs"],
+                    &rows,
+                ));
+            }
+            if let Some(next) = page.next_offset {
+                content.push(json!({
                     "type": "text",
-                    "text": serde_json::to_string_pretty(&entries)?
-                }]
+                    "text": format!("More entries: call get_history again with offset {}", next)
+                }));
+            }
+            Ok(json!({
+                "content": content,
+                "next_offset": page.next_offset
             }))
         }
         "unlog" => {
@@ -547,11 +1315,33 @@ fn handle_tools_call(db: &Database, params: &Value) -> Result<Value> {
                 }]
             }))
         }
+        "undo_last" => {
+            let window = arguments["window_minutes"]
+                .as_u64()
+                .map(|w| w as u32)
+                .unwrap_or_else(default_undo_window);
+            let undone = db.undo_last(window)?;
+            let text = undone
+                .iter()
+                .map(|c| c.description.as_str())
+                .collect::<Vec<_>>()
+                .join("\n");
+            Ok(json!({
+                "content": [{
+                    "type": "text",
+                    "text": text
+                }]
+            }))
+        }
         "delete_food" => {
             let name = arguments["name"]
                 .as_str()
                 .ok_or_else(|| anyhow::anyhow!("Missing 'name' argument"))?;
-            db.delete_food(name)?;
+            let mode = match arguments.get("mode") {
+                Some(mode) => serde_json::from_value(mode.clone())?,
+                None => DeleteMode::Refuse,
+            };
+            db.delete_food(name, mode)?;
             Ok(json!({
                 "content": [{
                     "type": "text",
@@ -566,13 +1356,20 @@ fn handle_tools_call(db: &Database, params: &Value) -> Result<Value> {
             let protein = arguments["protein"].as_f64();
             let fat = arguments["fat"].as_f64();
             let carbs = arguments["carbs"].as_f64();
+            let fiber = arguments["fiber"].as_f64();
             let serving = arguments["serving"].as_str();
             let calories = arguments["calories"].as_f64();
-            db.edit_food(name, protein, fat, carbs, serving, calories)?;
+            let micros = micros_argument(arguments);
+            let refreshed =
+                db.edit_food(name, protein, fat, carbs, fiber, serving, calories, &micros)?;
+            let mut text = format!("Updated food: {}", name);
+            if !refreshed.is_empty() {
+                text.push_str(&format!(" (recalculated {})", refreshed.join(", ")));
+            }
             Ok(json!({
                 "content": [{
                     "type": "text",
-                    "text": format!("Updated food: {}", name)
+                    "text": text
                 }]
             }))
         }
@@ -598,8 +1395,7 @@ fn handle_tools_call(db: &Database, params: &Value) -> Result<Value> {
                 .ok_or_else(|| anyhow::anyhow!("Missing 'amount' argument"))?;
             let ml = crate::food::parse_water_ml(amount)
                 .ok_or_else(|| anyhow::anyhow!("Could not parse water amount: '{}'", amount))?;
-            let date = arguments["date"].as_str();
-            let entry = db.log_water(ml, date)?;
+            let entry = db.log_water_with(ml, &log_options(arguments))?;
             Ok(json!({
                 "content": [{
                     "type": "text",
@@ -626,6 +1422,16 @@ fn handle_tools_call(db: &Database, params: &Value) -> Result<Value> {
                 }]
             }))
         }
+        "get_missing_days" => {
+            let days = arguments["days"].as_u64().unwrap_or(30) as u32;
+            let missing = db.missing_days(days)?;
+            Ok(json!({
+                "content": [{
+                    "type": "text",
+                    "text": serde_json::to_string_pretty(&missing)?
+                }]
+            }))
+        }
         "unlog_water" => {
             let id = arguments["id"]
                 .as_i64()
@@ -643,8 +1449,7 @@ fn handle_tools_call(db: &Database, params: &Value) -> Result<Value> {
                 .as_f64()
                 .ok_or_else(|| anyhow::anyhow!("Missing 'amount_mg' argument"))?;
             let source = arguments["source"].as_str().unwrap_or("");
-            let date = arguments["date"].as_str();
-            let entry = db.log_caffeine(amount_mg, source, date)?;
+            let entry = db.log_caffeine_with(amount_mg, source, &log_options(arguments))?;
             let src = if entry.source.is_empty() {
                 String::new()
             } else {
@@ -696,3 +1501,329 @@ fn handle_tools_call(db: &Database, params: &Value) -> Result<Value> {
         _ => Err(anyhow::anyhow!("Unknown tool: {}", tool_name)),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answers every sampling request with a canned model reply.
+    struct FakeSampler(&'static str);
+
+    impl Sampler for FakeSampler {
+        fn create_message(&self, params: Value) -> Result<Value> {
+            assert_eq!(params["messages"][0]["role"], "user");
+            Ok(json!({
+                "role": "assistant",
+                "content": {"type": "text", "text": self.0},
+                "model": "fake"
+            }))
+        }
+    }
+
+    fn log_food(db: &Database, food: &str, sampler: Option<&dyn Sampler>) -> Result<Value> {
+        handle_tools_call(
+            db,
+            &json!({"name": "log_food", "arguments": {"food": food}}),
+            sampler,
+        )
+    }
+
+    #[test]
+    fn test_handle_message() {
+        let db = Database::open_in_memory().unwrap();
+        let handle = |message: Value| handle_message(&db, message, None);
+
+        let init = |version: &str| {
+            handle(json!({"jsonrpc": "2.0", "id": 1, "method": "initialize",
+                          "params": {"protocolVersion": version, "capabilities": {}}}))
+            .unwrap()["result"]["protocolVersion"]
+                .clone()
+        };
+        assert_eq!(init("2024-11-05"), "2024-11-05");
+        assert_eq!(init("2025-03-26"), "2025-03-26");
+        assert_eq!(init("2099-01-01"), PROTOCOL_VERSIONS[0]);
+
+        // Notifications, even unknown ones, and stray responses get no reply
+        assert!(handle(json!({"jsonrpc": "2.0", "method": "notifications/initialized"})).is_none());
+        assert!(handle(json!({"jsonrpc": "2.0", "method": "no/such/thing"})).is_none());
+        assert!(handle(json!({"jsonrpc": "2.0", "id": "x", "result": {}})).is_none());
+
+        let code = |message: Value| handle(message).unwrap()["error"]["code"].clone();
+        assert_eq!(
+            code(json!({"jsonrpc": "2.0", "id": 2, "method": "no/such/thing"})),
+            -32601
+        );
+        assert_eq!(
+            code(json!({"jsonrpc": "2.0", "id": 3, "method": "tools/call"})),
+            -32602
+        );
+        assert_eq!(code(json!({"jsonrpc": "2.0", "id": 4})), -32600);
+        assert_eq!(
+            code(json!({"jsonrpc": "2.0", "id": [5], "method": "ping"})),
+            -32600
+        );
+        assert_eq!(code(json!([])), -32600);
+        assert_eq!(code(json!(42)), -32600);
+
+        // The jsonrpc member is optional
+        assert_eq!(
+            handle(json!({"id": 6, "method": "ping"})).unwrap()["result"],
+            json!({})
+        );
+
+        let responses = handle(json!([
+            {"jsonrpc": "2.0", "id": 7, "method": "ping"},
+            {"jsonrpc": "2.0", "method": "notifications/initialized"},
+            {"jsonrpc": "2.0", "id": 8, "method": "tools/list"}
+        ]))
+        .unwrap();
+        let ids: Vec<&Value> = responses
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| &r["id"])
+            .collect();
+        assert_eq!(ids, [&json!(7), &json!(8)]);
+        assert!(
+            handle(json!([{"jsonrpc": "2.0", "method": "notifications/initialized"}])).is_none()
+        );
+
+        assert_eq!(
+            parse_message("{not json").unwrap_err().error.unwrap().code,
+            -32700
+        );
+    }
+
+    #[cfg(feature = "sse")]
+    #[test]
+    fn test_timeout_response() {
+        let timeout = std::time::Duration::from_secs(30);
+        let response = timeout_response(&json!({"id": 1, "method": "tools/call"}), timeout);
+        assert_eq!(response.as_ref().unwrap()["error"]["code"], -32001);
+        assert_eq!(
+            response.unwrap()["error"]["message"],
+            "Request timed out after 30s"
+        );
+
+        let responses = timeout_response(
+            &json!([
+                {"id": "a", "method": "ping"},
+                {"method": "notifications/initialized"},
+                {"id": "b", "result": {}}
+            ]),
+            timeout,
+        )
+        .unwrap();
+        assert_eq!(responses.as_array().unwrap().len(), 1);
+        assert_eq!(responses[0]["id"], "a");
+        assert!(
+            timeout_response(&json!({"method": "notifications/initialized"}), timeout).is_none()
+        );
+    }
+
+    #[test]
+    fn test_log_food_estimates_unknown_food_via_sampling() {
+        let db = Database::open_in_memory().unwrap();
+        assert!(log_food(&db, "grandma lasagna 2 slices", None).is_err());
+
+        let sampler = FakeSampler(
+            "Sure! {\"serving\": \"1 slice\", \"protein\": 20, \"fat\": 15, \"carbs\": 30}",
+        );
+        let result = log_food(&db, "grandma lasagna 2 slices", Some(&sampler)).unwrap();
+        assert_eq!(result["estimated"], true);
+
+        let food = db.get_food_by_name("grandma lasagna").unwrap().unwrap();
+        assert_eq!(food.serving, "1 slice");
+        assert_eq!(food.calories, 335.0);
+        assert_eq!(db.food_tags(food.id.unwrap()).unwrap(), vec!["estimated"]);
+        let entry = &db.get_history(1).unwrap()[0];
+        assert_eq!(entry.protein, 40.0);
+    }
+
+    #[test]
+    fn test_search_and_history_size_controls() {
+        let db = Database::open_in_memory().unwrap();
+        for name in ["oat milk", "oat bran", "oats"] {
+            db.add_food(&Food::new(name, 5.0, 3.0, 20.0, 127.0, "100g", vec![]))
+                .unwrap();
+        }
+        db.log_food_with(1, "100g", &Macros::default(), &LogOptions::default())
+            .unwrap();
+        db.log_food_with(1, "100g", &Macros::default(), &LogOptions::default())
+            .unwrap();
+        let call_with = |name: &str, arguments: Value| {
+            handle_tools_call(&db, &json!({"name": name, "arguments": arguments}), None).unwrap()
+        };
+        let call = |name: &str, arguments: Value| {
+            call_with(name, arguments)["content"][0]["text"]
+                .as_str()
+                .unwrap()
+                .to_string()
+        };
+
+        let result = call_with(
+            "search_food",
+            json!({"query": "oat", "max_results": 2, "fields": ["name"]}),
+        );
+        assert_eq!(result["more"], 1);
+        let items: Value =
+            serde_json::from_str(result["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(items.as_array().unwrap().len(), 2);
+        assert_eq!(items[0].as_object().unwrap().len(), 1);
+        assert!(result["content"][1]["text"]
+            .as_str()
+            .unwrap()
+            .starts_with("1 more match"));
+
+        let result = call_with("get_history", json!({"limit": 1}));
+        assert_eq!(result["next_offset"], 1);
+        let items: Value =
+            serde_json::from_str(result["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(items.as_array().unwrap().len(), 1);
+        assert!(result["content"][1]["text"]
+            .as_str()
+            .unwrap()
+            .contains("offset 1"));
+
+        let text = call("search_food", json!({"query": "oats", "summary": true}));
+        assert!(text.starts_with("oats: 5p/3f/20c, 127 kcal per 100g"));
+
+        let text = call("get_history", json!({"fields": ["food_name", "amount"]}));
+        let items: Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(items[0], json!({"food_name": "oat milk", "amount": "100g"}));
+
+        let text = call("get_history", json!({"summary": true}));
+        assert!(text.ends_with(": 0 kcal, 0p/0f/0c"));
+    }
+
+    #[test]
+    fn test_markdown_tables() {
+        let db = Database::open_in_memory().unwrap();
+        db.add_food(&Food::new("a|b", 5.0, 3.0, 20.0, 127.0, "100g", vec![]))
+            .unwrap();
+        db.log_food_with(1, "100g", &Macros::default(), &LogOptions::default())
+            .unwrap();
+
+        let plain = handle_tools_call(&db, &json!({"name": "get_today"}), None).unwrap();
+        assert_eq!(plain["content"].as_array().unwrap().len(), 1);
+
+        let result = handle_tools_call(
+            &db,
+            &json!({"name": "get_today", "arguments": {"markdown": true}}),
+            None,
+        )
+        .unwrap();
+        let table = result["content"][1]["text"].as_str().unwrap();
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[0], "| Food | Amount | kcal | Protein | Fat | Carbs |");
+        assert!(lines[2].starts_with("| a\\|b | 100g |"));
+        assert!(lines[3].starts_with("| **Total** |"));
+
+        let result = handle_tools_call(
+            &db,
+            &json!({"name": "get_history", "arguments": {"summary": true, "markdown": true}}),
+            None,
+        )
+        .unwrap();
+        let table = result["content"][1]["text"].as_str().unwrap();
+        assert!(table.starts_with("| Date | kcal | Protein | Fat | Carbs |"));
+    }
+
+    #[test]
+    fn test_unreadable_estimate_is_an_error() {
+        let db = Database::open_in_memory().unwrap();
+        let sampler = FakeSampler("I can't estimate that.");
+        assert!(log_food(&db, "mystery stew", Some(&sampler)).is_err());
+        assert!(db.get_food_by_name("mystery stew").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_food_not_found_suggests_names() {
+        let db = Database::open_in_memory().unwrap();
+        for name in ["Chicken Breast", "Chicken Thigh", "Ribeye"] {
+            db.add_food(&Food::new(name, 25.0, 5.0, 0.0, 145.0, "100g", vec![]))
+                .unwrap();
+        }
+        let response = handle_message(
+            &db,
+            json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": {
+                "name": "log_food",
+                "arguments": {"food": "chiken", "strict": true, "estimate": false}
+            }}),
+            None,
+        )
+        .unwrap();
+        let error = &response["error"];
+        assert_eq!(
+            error["message"],
+            "Food not found: 'chiken'. Did you mean: Chicken Breast, Chicken Thigh?"
+        );
+        assert_eq!(error["data"]["name"], "chiken");
+        assert_eq!(
+            error["data"]["suggestions"],
+            json!(["Chicken Breast", "Chicken Thigh"])
+        );
+    }
+
+    #[test]
+    fn test_delete_food_in_use_names_the_mode_argument() {
+        let db = Database::open_in_memory().unwrap();
+        db.add_food(&Food::new("Ribeye", 25.0, 20.0, 0.0, 290.0, "100g", vec![]))
+            .unwrap();
+        log_food(&db, "ribeye 200g", None).unwrap();
+        let response = handle_message(
+            &db,
+            json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": {
+                "name": "delete_food",
+                "arguments": {"name": "ribeye"}
+            }}),
+            None,
+        )
+        .unwrap();
+        let message = response["error"]["message"].as_str().unwrap();
+        assert!(message.contains("mode: \"keep-history\""), "{}", message);
+        assert!(!message.contains("--"), "{}", message);
+    }
+
+    #[test]
+    fn test_tools_are_well_formed() {
+        let db = Database::open_in_memory().unwrap();
+        let tools = tools();
+        let mut names = Vec::new();
+        for tool in tools.as_array().unwrap() {
+            let name = tool["name"].as_str().unwrap();
+            assert!(!names.contains(&name), "{} listed twice", name);
+            names.push(name);
+            assert!(
+                tool["description"].is_string(),
+                "{} has no description",
+                name
+            );
+            let schema = &tool["inputSchema"];
+            assert_eq!(schema["type"], "object", "{}", name);
+            let properties = schema["properties"].as_object().unwrap();
+            for required in schema["required"].as_array().into_iter().flatten() {
+                assert!(
+                    properties.contains_key(required.as_str().unwrap()),
+                    "{} requires {} but doesn't describe it",
+                    name,
+                    required
+                );
+            }
+            if let Err(e) = handle_tools_call(&db, &json!({"name": name, "arguments": {}}), None) {
+                assert!(
+                    !e.to_string().starts_with("Unknown tool"),
+                    "{} isn't handled",
+                    name
+                );
+            }
+        }
+    }
+
+    /// The tool list clients integrate against. A change here breaks them
+    /// unless it only adds: review the snapshot diff before accepting it.
+    #[test]
+    fn test_tool_schemas() {
+        insta::assert_snapshot!(serde_json::to_string_pretty(&tools()).unwrap());
+    }
+}
@@ -0,0 +1,120 @@
+//! Meals entries are logged under (`--meal breakfast`), and the per-meal
+//! subtotals `chomp today` and `chomp history` group entries by.
+
+use serde::Serialize;
+
+use crate::db::LogEntry;
+use crate::food::Macros;
+
+/// One day's entries for one meal; `meal` is None for entries logged
+/// without one.
+#[derive(Debug, Serialize)]
+pub struct MealGroup {
+    pub date: String,
+    pub meal: Option<String>,
+    pub totals: Macros,
+    pub entries: Vec<LogEntry>,
+}
+
+/// Whether any entry was logged under a meal, i.e. whether grouping is
+/// worth showing.
+pub fn any(entries: &[LogEntry]) -> bool {
+    entries.iter().any(|e| e.meal.is_some())
+}
+
+/// Group `entries` by day, keeping the days in the order they come, then by
+/// meal in the order of `meals`. Meals no longer in `meals` follow those,
+/// and entries without a meal come last.
+pub fn group(entries: Vec<LogEntry>, meals: &[String]) -> Vec<MealGroup> {
+    let rank = |meal: &Option<String>| match meal {
+        Some(meal) => meals.iter().position(|m| m == meal).unwrap_or(meals.len()),
+        None => meals.len() + 1,
+    };
+    let mut days: Vec<String> = Vec::new();
+    let mut groups: Vec<MealGroup> = Vec::new();
+    for entry in entries {
+        if !days.contains(&entry.date) {
+            days.push(entry.date.clone());
+        }
+        let i = match groups
+            .iter()
+            .position(|g| g.date == entry.date && g.meal == entry.meal)
+        {
+            Some(i) => i,
+            None => {
+                groups.push(MealGroup {
+                    date: entry.date.clone(),
+                    meal: entry.meal.clone(),
+                    totals: Macros::default(),
+                    entries: Vec::new(),
+                });
+                groups.len() - 1
+            }
+        };
+        let totals = &mut groups[i].totals;
+        totals.protein += entry.protein;
+        totals.fat += entry.fat;
+        totals.carbs += entry.carbs;
+        totals.calories += entry.calories;
+        groups[i].entries.push(entry);
+    }
+    groups.sort_by_key(|g| {
+        let day = days.iter().position(|d| *d == g.date);
+        (day, rank(&g.meal), g.meal.clone())
+    });
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(date: &str, meal: Option<&str>, calories: f64) -> LogEntry {
+        LogEntry {
+            id: None,
+            date: date.to_string(),
+            food_name: "eggs".to_string(),
+            food_id: Some(1),
+            amount: "2".to_string(),
+            protein: 12.0,
+            fat: 10.0,
+            carbs: 1.0,
+            calories,
+            recurring: None,
+            meal: meal.map(str::to_string),
+            warning: None,
+        }
+    }
+
+    #[test]
+    fn test_group_by_day_then_meal() {
+        let meals: Vec<String> = ["breakfast", "lunch", "dinner", "snack"]
+            .map(String::from)
+            .to_vec();
+        let entries = vec![
+            entry("2026-10-16", Some("dinner"), 600.0),
+            entry("2026-10-16", None, 100.0),
+            entry("2026-10-16", Some("breakfast"), 300.0),
+            entry("2026-10-16", Some("breakfast"), 150.0),
+            entry("2026-10-15", Some("elevenses"), 200.0),
+            entry("2026-10-15", Some("lunch"), 500.0),
+        ];
+        assert!(any(&entries));
+        let groups = group(entries, &meals);
+        let keys: Vec<(&str, Option<&str>, f64)> = groups
+            .iter()
+            .map(|g| (g.date.as_str(), g.meal.as_deref(), g.totals.calories))
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                ("2026-10-16", Some("breakfast"), 450.0),
+                ("2026-10-16", Some("dinner"), 600.0),
+                ("2026-10-16", None, 100.0),
+                ("2026-10-15", Some("lunch"), 500.0),
+                ("2026-10-15", Some("elevenses"), 200.0),
+            ]
+        );
+        assert_eq!(groups[0].entries.len(), 2);
+    }
+}
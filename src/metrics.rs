@@ -0,0 +1,173 @@
+use anyhow::Result;
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::IntoResponse,
+    routing::get,
+    Router,
+};
+use std::fmt::Write;
+
+use crate::db::Database;
+use crate::food::Macros;
+use crate::status::Targets;
+
+/// Point-in-time values exported as Prometheus gauges.
+pub struct Snapshot {
+    pub totals: Macros,
+    pub targets: Targets,
+    pub food_count: i64,
+    pub last_log_age_secs: Option<i64>,
+}
+
+impl Snapshot {
+    pub fn collect(db: &Database, targets: Targets) -> Result<Self> {
+        let totals = db.get_today_totals()?;
+        let stats = db.get_stats()?;
+        let last_log_age_secs = db
+            .get_last_logged_at()?
+            .map(|ts| (chrono::Utc::now() - ts).num_seconds().max(0));
+        Ok(Self {
+            totals,
+            targets,
+            food_count: stats.food_count,
+            last_log_age_secs,
+        })
+    }
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: f64) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} gauge", name);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+/// Render a snapshot in the Prometheus text exposition format.
+pub fn render(snapshot: &Snapshot) -> String {
+    let mut out = String::new();
+    let t = &snapshot.totals;
+    gauge(
+        &mut out,
+        "chomp_today_calories",
+        "Calories logged today.",
+        t.calories,
+    );
+    gauge(
+        &mut out,
+        "chomp_today_protein_grams",
+        "Protein logged today in grams.",
+        t.protein,
+    );
+    gauge(
+        &mut out,
+        "chomp_today_fat_grams",
+        "Fat logged today in grams.",
+        t.fat,
+    );
+    gauge(
+        &mut out,
+        "chomp_today_carbs_grams",
+        "Carbs logged today in grams.",
+        t.carbs,
+    );
+    if let Some(target) = snapshot.targets.calories {
+        gauge(
+            &mut out,
+            "chomp_goal_remaining_calories",
+            "Calories remaining before today's target.",
+            target - t.calories,
+        );
+    }
+    if let Some(target) = snapshot.targets.protein {
+        gauge(
+            &mut out,
+            "chomp_goal_remaining_protein_grams",
+            "Protein remaining before today's target in grams.",
+            target - t.protein,
+        );
+    }
+    gauge(
+        &mut out,
+        "chomp_foods",
+        "Number of foods in the database.",
+        snapshot.food_count as f64,
+    );
+    if let Some(age) = snapshot.last_log_age_secs {
+        gauge(
+            &mut out,
+            "chomp_last_log_age_seconds",
+            "Seconds since the most recent log entry was created.",
+            age as f64,
+        );
+    }
+    out
+}
+
+/// Serve GET /metrics on the given port/host until the process exits.
+pub async fn serve_metrics(port: u16, host: &str, targets: Targets) -> Result<()> {
+    let app = Router::new()
+        .route("/metrics", get(metrics_handler))
+        .with_state(targets);
+
+    let addr: std::net::SocketAddr = format!("{}:{}", host, port).parse()?;
+    eprintln!("chomp metrics listening on http://{}/metrics", addr);
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+/// GET /metrics — Prometheus scrape endpoint.
+async fn metrics_handler(State(targets): State<Targets>) -> impl IntoResponse {
+    let body = Database::open()
+        .and_then(|db| {
+            db.init()?;
+            Snapshot::collect(&db, targets)
+        })
+        .map(|snapshot| render(&snapshot));
+
+    match body {
+        Ok(text) => (
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+            text,
+        ),
+        Err(e) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+            format!("# database error: {}\n", e),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_metrics() {
+        let snapshot = Snapshot {
+            totals: Macros {
+                protein: 92.0,
+                fat: 40.0,
+                carbs: 120.0,
+                calories: 1430.0,
+                fiber: 0.0,
+                ..Default::default()
+            },
+            targets: Targets {
+                calories: Some(2200.0),
+                protein: None,
+                water: None,
+            },
+            food_count: 12,
+            last_log_age_secs: None,
+        };
+        let text = render(&snapshot);
+        assert!(text.contains("# TYPE chomp_today_calories gauge\nchomp_today_calories 1430\n"));
+        assert!(text.contains("chomp_goal_remaining_calories 770\n"));
+        assert!(!text.contains("chomp_goal_remaining_protein_grams"));
+        assert!(text.contains("chomp_foods 12\n"));
+        assert!(!text.contains("chomp_last_log_age_seconds"));
+    }
+}
@@ -0,0 +1,219 @@
+use anyhow::Result;
+use chrono::{Datelike, Duration, NaiveDate};
+
+use crate::config::{Config, Goals, Program};
+use crate::db::{Database, TargetAdjustment, WeightEntry};
+
+/// Energy in a kilogram of body weight, to turn a weight trend into calories.
+pub const KCAL_PER_KG: f64 = 7700.0;
+/// Most the target moves in one week, so a noisy week can't swing it far.
+const MAX_WEEKLY_CHANGE: f64 = 250.0;
+
+/// The first Monday at least a full week after `start`: the first week
+/// with a week of weigh-ins behind it.
+pub fn first_week(start: NaiveDate) -> NaiveDate {
+    let day = start + Duration::days(7);
+    day + Duration::days((7 - i64::from(day.weekday().num_days_from_monday())) % 7)
+}
+
+/// Average weight from `from` up to (not including) `to`.
+fn average(weights: &[WeightEntry], from: NaiveDate, to: NaiveDate) -> Option<f64> {
+    let (from, to) = (from.to_string(), to.to_string());
+    let kgs: Vec<f64> = weights
+        .iter()
+        .filter(|w| w.date >= from && w.date < to)
+        .map(|w| w.kg)
+        .collect();
+    (!kgs.is_empty()).then(|| kgs.iter().sum::<f64>() / kgs.len() as f64)
+}
+
+/// The target for the week starting `week_start`: the week before is
+/// compared with the one before that (or the start weight, early on), and
+/// the gap between the planned and actual weekly change is made up in
+/// calories. Without weigh-ins the target holds.
+pub fn adjust(
+    program: &Program,
+    start: NaiveDate,
+    weights: &[WeightEntry],
+    week_start: NaiveDate,
+    current: f64,
+) -> TargetAdjustment {
+    let week = Duration::days(7);
+    let last = average(weights, week_start - week, week_start);
+    // Each average stands for the middle of its week
+    let baseline = if week_start - week - week < start {
+        let days = (week_start - start).num_days() as f64 - 3.5;
+        Some((program.start_weight, days / 7.0))
+    } else {
+        average(weights, week_start - week - week, week_start - week).map(|kg| (kg, 1.0))
+    };
+    let actual_rate = match (last, baseline) {
+        (Some(last), Some((before, weeks))) if weeks > 0.0 => Some((last - before) / weeks),
+        _ => None,
+    };
+    let planned_rate = program.planned_rate();
+    let change = actual_rate.map_or(0.0, |actual| {
+        let change = (planned_rate - actual) * KCAL_PER_KG / 7.0;
+        (change.clamp(-MAX_WEEKLY_CHANGE, MAX_WEEKLY_CHANGE) / 10.0).round() * 10.0
+    });
+    TargetAdjustment {
+        week_start: week_start.to_string(),
+        calories: current + change,
+        change,
+        actual_rate,
+        planned_rate,
+    }
+}
+
+/// Record an adjustment for every program week that has begun since the
+/// last measured one, and return the calorie target now in effect. None without a
+/// program or a starting `goals.calories`.
+pub fn catch_up(db: &Database, config: &Config) -> Result<Option<f64>> {
+    let (Some(program), Some(base)) = (&config.program, config.goals.calories) else {
+        return Ok(None);
+    };
+    let start = NaiveDate::parse_from_str(&program.start_date, "%Y-%m-%d").map_err(|_| {
+        anyhow::anyhow!(
+            "Invalid program start_date '{}' (use YYYY-MM-DD)",
+            program.start_date
+        )
+    })?;
+    // Weeks held for lack of weigh-ins are revisited, in case they've
+    // been filled in since
+    let previous = db
+        .target_adjustments()?
+        .into_iter()
+        .rfind(|a| a.week_start >= program.start_date && a.actual_rate.is_some());
+    let (mut current, mut week) = match previous {
+        Some(a) => (
+            a.calories,
+            NaiveDate::parse_from_str(&a.week_start, "%Y-%m-%d")? + Duration::days(7),
+        ),
+        None => (base, first_week(start)),
+    };
+
+    let today = chrono::Local::now().date_naive();
+    if week <= today {
+        let weights = db.get_weight_history(&(week - Duration::days(14)).to_string())?;
+        while week <= today {
+            let adjustment = adjust(program, start, &weights, week, current);
+            db.record_target_adjustment(&adjustment)?;
+            current = adjustment.calories;
+            week += Duration::days(7);
+        }
+    }
+    Ok(Some(current))
+}
+
+/// The calorie target the program had set on `date`, from its recorded
+/// adjustments; before the first one, the goal it started from.
+fn target_on(adjustments: &[TargetAdjustment], date: &str) -> Option<f64> {
+    match adjustments
+        .iter()
+        .rev()
+        .find(|a| a.week_start.as_str() <= date)
+    {
+        Some(a) => Some(a.calories),
+        None => adjustments.first().map(|a| a.calories - a.change),
+    }
+}
+
+/// The goals in effect on `date`, given the program's adjustments: a
+/// scheduled refeed or diet break first, then the program's calorie target,
+/// then `goals`. Also names the schedule entry, if one applies.
+pub fn goals_with(
+    config: &Config,
+    adjustments: &[TargetAdjustment],
+    date: NaiveDate,
+) -> (Goals, Option<String>) {
+    let mut goals = config.goals;
+    if config.program.is_some() {
+        if let Some(calories) = target_on(adjustments, &date.to_string()) {
+            goals.calories = Some(calories);
+        }
+    }
+    match config.scheduled_on(date) {
+        Some(scheduled) => (scheduled.apply(goals), Some(scheduled.name.clone())),
+        None => (goals, None),
+    }
+}
+
+/// The program's adjustments so far, catching up on any weeks due.
+pub fn adjustments(db: &Database, config: &Config) -> Result<Vec<TargetAdjustment>> {
+    let Some(program) = &config.program else {
+        return Ok(Vec::new());
+    };
+    catch_up(db, config)?;
+    Ok(db
+        .target_adjustments()?
+        .into_iter()
+        .filter(|a| a.week_start >= program.start_date)
+        .collect())
+}
+
+/// The goals in effect on `date`; see `goals_with`.
+pub fn goals_on(
+    db: &Database,
+    config: &Config,
+    date: NaiveDate,
+) -> Result<(Goals, Option<String>)> {
+    Ok(goals_with(config, &adjustments(db, config)?, date))
+}
+
+/// Put today's goals, with the program's target and any scheduled refeed
+/// or diet break, in place of `goals`.
+pub fn apply(config: &mut Config, db: &Database) -> Result<()> {
+    let (goals, _) = goals_on(db, config, chrono::Local::now().date_naive())?;
+    config.goals = goals;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Phase;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn weigh_in(date: &str, kg: f64) -> WeightEntry {
+        WeightEntry {
+            id: None,
+            date: date.to_string(),
+            kg,
+        }
+    }
+
+    #[test]
+    fn test_adjust() {
+        let program = Program {
+            phase: Phase::Cut,
+            start_date: "2026-09-02".to_string(),
+            start_weight: 90.0,
+            rate: 0.5,
+        };
+        let start = date("2026-09-02");
+        assert_eq!(first_week(start), date("2026-09-14"));
+
+        // Losing 0.2kg/week on a 0.5kg/week cut: eat less, at most 250 less
+        let weights = [
+            weigh_in("2026-09-07", 89.8),
+            weigh_in("2026-09-08", 89.6),
+            weigh_in("2026-09-14", 89.5),
+            weigh_in("2026-09-16", 89.3),
+        ];
+        let a = adjust(&program, start, &weights, date("2026-09-21"), 2200.0);
+        assert!((a.actual_rate.unwrap() + 0.3).abs() < 1e-9);
+        assert_eq!((a.change, a.calories), (-220.0, 1980.0));
+
+        // Early weeks measure from the start weight
+        let a = adjust(&program, start, &weights, date("2026-09-14"), 2200.0);
+        assert_eq!(a.change, -250.0);
+        assert!((a.actual_rate.unwrap() + 0.3 / (8.5 / 7.0)).abs() < 1e-9);
+
+        // No weigh-ins: hold the target
+        let a = adjust(&program, start, &weights, date("2026-10-05"), 1980.0);
+        assert_eq!((a.actual_rate, a.calories), (None, 1980.0));
+    }
+}
@@ -0,0 +1,396 @@
+use anyhow::Result;
+use serde_json::Value;
+use std::io::{BufRead, Write};
+
+use crate::db::Database;
+use crate::food::{Food, Macros, RecipeYield};
+
+/// The parts of a schema.org/Recipe that chomp uses.
+#[derive(Debug, PartialEq)]
+pub struct Recipe {
+    pub name: String,
+    pub servings: f64,
+    pub ingredients: Vec<String>,
+}
+
+/// An ingredient line split into amount and food, e.g.
+/// "1 1/2 cups rolled oats" -> 1.5, "cup", "rolled oats".
+#[derive(Debug, PartialEq)]
+pub struct Ingredient {
+    pub quantity: Option<f64>,
+    pub unit: Option<String>,
+    pub name: String,
+}
+
+impl Ingredient {
+    /// Amount string chomp can calculate with, times `scale`. Weights and
+    /// volumes keep their unit; counts ("2 large eggs", "1 can") are that
+    /// many of the food's usual portion.
+    pub fn amount(&self, food: &Food, scale: f64) -> Option<String> {
+        let quantity = self.quantity? * scale;
+        Some(match self.unit.as_deref() {
+            Some("l") => format!("{}ml", rounded(quantity * 1000.0)),
+            Some(unit @ ("g" | "kg" | "oz" | "lb" | "ml" | "cup" | "tbsp" | "tsp")) => {
+                format!("{}{}", rounded(quantity), unit)
+            }
+            _ => {
+                let portion = food.default_amount.as_deref().unwrap_or(&food.serving);
+                let split = portion
+                    .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                    .unwrap_or(portion.len());
+                let per: f64 = portion[..split].parse().unwrap_or(1.0);
+                format!("{}{}", rounded(per * quantity), &portion[split..])
+            }
+        })
+    }
+}
+
+fn rounded(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+/// Fetch a recipe page and read its JSON-LD.
+pub fn fetch(url: &str) -> Result<Recipe> {
+    let html = reqwest::blocking::Client::builder()
+        .user_agent(concat!("chomp/", env!("CARGO_PKG_VERSION")))
+        .build()?
+        .get(url)
+        .send()
+        .map_err(|e| anyhow::anyhow!("Failed to fetch {}: {}", url, e))?
+        .error_for_status()?
+        .text()?;
+    from_html(&html)
+}
+
+/// Find the schema.org/Recipe among a page's `application/ld+json` blocks.
+pub fn from_html(html: &str) -> Result<Recipe> {
+    let mut rest = html;
+    while let Some(i) = rest.find("application/ld+json") {
+        rest = &rest[i..];
+        let Some(start) = rest.find('>') else { break };
+        let Some(end) = rest.find("</script>") else {
+            break;
+        };
+        if start < end {
+            if let Ok(json) = serde_json::from_str::<Value>(&rest[start + 1..end]) {
+                if let Some(recipe) = find_recipe(&json) {
+                    return Ok(recipe);
+                }
+            }
+        }
+        rest = &rest[end..];
+    }
+    anyhow::bail!("No schema.org Recipe found on the page")
+}
+
+fn is_recipe(value: &Value) -> bool {
+    match &value["@type"] {
+        Value::String(t) => t == "Recipe",
+        Value::Array(types) => types.iter().any(|t| t == "Recipe"),
+        _ => false,
+    }
+}
+
+fn find_recipe(value: &Value) -> Option<Recipe> {
+    match value {
+        Value::Array(items) => items.iter().find_map(find_recipe),
+        Value::Object(_) if is_recipe(value) => Some(Recipe {
+            name: value["name"]
+                .as_str()
+                .map(decode_entities)
+                .unwrap_or_else(|| "recipe".to_string()),
+            servings: servings(&value["recipeYield"]).unwrap_or(1.0),
+            ingredients: value["recipeIngredient"]
+                .as_array()
+                .map(|lines| {
+                    lines
+                        .iter()
+                        .filter_map(|l| l.as_str().map(decode_entities))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }),
+        Value::Object(_) => find_recipe(&value["@graph"]),
+        _ => None,
+    }
+}
+
+/// recipeYield may be 4, "4", "Serves 4", or ["4", "4 servings"].
+fn servings(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s
+            .split(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .find_map(|part| part.parse().ok()),
+        Value::Array(items) => items.iter().find_map(servings),
+        _ => None,
+    }
+    .filter(|n| *n > 0.0)
+}
+
+fn decode_entities(s: &str) -> String {
+    s.replace("&amp;", "&")
+        .replace("&#39;", "'")
+        .replace("&#039;", "'")
+        .replace("&quot;", "\"")
+        .replace("&nbsp;", " ")
+        .trim()
+        .to_string()
+}
+
+/// Units recognized in ingredient lines, mapped to chomp's spelling.
+fn unit(word: &str) -> Option<&'static str> {
+    Some(match word.trim_end_matches('.') {
+        "g" | "gram" | "grams" => "g",
+        "kg" | "kilogram" | "kilograms" => "kg",
+        "oz" | "ounce" | "ounces" => "oz",
+        "lb" | "lbs" | "pound" | "pounds" => "lb",
+        "ml" | "milliliter" | "milliliters" => "ml",
+        "l" | "liter" | "liters" | "litre" | "litres" => "l",
+        "cup" | "cups" => "cup",
+        "tbsp" | "tablespoon" | "tablespoons" => "tbsp",
+        "tsp" | "teaspoon" | "teaspoons" => "tsp",
+        "can" | "cans" => "can",
+        "clove" | "cloves" => "clove",
+        "slice" | "slices" => "slice",
+        "piece" | "pieces" => "piece",
+        "large" | "medium" | "small" => "whole",
+        _ => return None,
+    })
+}
+
+/// "1", "1/2", "1.5", or "1-2" (the low end of a range).
+fn quantity(word: &str) -> Option<f64> {
+    let word = word.split(['-', '–']).next()?;
+    match word.split_once('/') {
+        Some((n, d)) => {
+            let (n, d): (f64, f64) = (n.parse().ok()?, d.parse().ok()?);
+            (d != 0.0).then(|| n / d)
+        }
+        None => word.parse().ok(),
+    }
+}
+
+/// Split an ingredient line into quantity, unit, and food name.
+pub fn parse_ingredient(line: &str) -> Ingredient {
+    let mut text = line.to_lowercase();
+    for (glyph, ascii) in [
+        ("½", " 1/2"),
+        ("⅓", " 1/3"),
+        ("⅔", " 2/3"),
+        ("¼", " 1/4"),
+        ("¾", " 3/4"),
+        ("⅛", " 1/8"),
+    ] {
+        text = text.replace(glyph, ascii);
+    }
+    // Drop notes: "(15 oz)", and anything after a comma ("drained")
+    while let (Some(open), Some(close)) = (text.find('('), text.find(')')) {
+        if close < open {
+            break;
+        }
+        text.replace_range(open..=close, " ");
+    }
+    let text = text.split(',').next().unwrap_or("");
+
+    let mut words = text.split_whitespace().peekable();
+    let mut total: Option<f64> = None;
+    while let Some(q) = words.peek().and_then(|w| quantity(w)) {
+        total = Some(total.unwrap_or(0.0) + q);
+        words.next();
+    }
+    let unit = words.peek().and_then(|w| unit(w)).map(String::from);
+    if unit.is_some() {
+        words.next();
+    }
+    let name: Vec<&str> = words.skip_while(|w| *w == "of").collect();
+
+    Ingredient {
+        quantity: total,
+        unit,
+        name: name.join(" "),
+    }
+}
+
+/// Ask which food an ingredient is: Enter accepts the guess, a name picks
+/// another food, "s" skips.
+fn resolve<R: BufRead, W: Write>(
+    db: &Database,
+    line: &str,
+    guess: Option<Food>,
+    input: &mut R,
+    output: &mut W,
+) -> Result<Option<Food>> {
+    loop {
+        match &guess {
+            Some(food) => write!(
+                output,
+                "  {} → {}? [Enter to accept, a food name, or s to skip] ",
+                line, food.name
+            )?,
+            None => write!(output, "  {} → ? [a food name, or Enter to skip] ", line)?,
+        }
+        output.flush()?;
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            return Ok(None);
+        }
+        let answer = answer.trim();
+        match answer {
+            "" => return Ok(guess),
+            "s" => return Ok(None),
+            name => match db.get_food_by_name(name)? {
+                Some(food) => return Ok(Some(food)),
+                None => writeln!(output, "  No food named '{}'.", name)?,
+            },
+        }
+    }
+}
+
+/// Import a recipe page as a recipe divided into the servings it yields.
+pub fn import(db: &Database, url: &str, name: Option<&str>, assume_yes: bool) -> Result<()> {
+    let recipe = fetch(url)?;
+    let name = name.unwrap_or(&recipe.name);
+    println!(
+        "{}: {} ingredients, serves {}",
+        name,
+        recipe.ingredients.len(),
+        recipe.servings
+    );
+
+    let stdin = std::io::stdin();
+    let mut input = stdin.lock();
+    let mut output = std::io::stdout();
+    let mut items = Vec::new();
+    for line in &recipe.ingredients {
+        let ingredient = parse_ingredient(line);
+        if ingredient.quantity.is_none() {
+            println!("  {} → skipped (no amount)", line);
+            continue;
+        }
+        let guess = match db.get_food_by_name(&ingredient.name)? {
+            Some(food) => Some(food),
+            None => db.most_likely_food(&ingredient.name)?,
+        };
+        let food = if assume_yes {
+            guess
+        } else {
+            resolve(db, line, guess, &mut input, &mut output)?
+        };
+        match food {
+            Some(food) => {
+                let amount = ingredient.amount(&food, 1.0).unwrap_or_default();
+                if assume_yes {
+                    println!("  {} → {} {}", line, amount, food.name);
+                }
+                items.push((food.name, amount));
+            }
+            None => println!("  {} → skipped", line),
+        }
+    }
+
+    if items.is_empty() {
+        anyhow::bail!("None of the ingredients matched a known food");
+    }
+    let recipe = RecipeYield {
+        servings: recipe.servings,
+        grams: None,
+    };
+    let serving = db.atomically(|db| {
+        db.create_compound_food(name, &items)?;
+        db.set_recipe_yield(name, recipe)
+    })?;
+    println!("Created recipe {}", describe(name, &recipe, &serving));
+    Ok(())
+}
+
+/// Create the recipe `name` from `items`, the whole batch as it's logged
+/// (e.g. "ground beef 500g, lasagna noodles 250g"), divided as `recipe`
+/// says. Returns the macros of one serving.
+pub fn add(db: &Database, name: &str, items: &str, recipe: RecipeYield) -> Result<Macros> {
+    recipe.check()?;
+    let mut parts = Vec::new();
+    for item in crate::logging::split_items(db, items)? {
+        let (food, amount) = crate::logging::resolve(db, &item, true)?;
+        let amount = amount
+            .or_else(|| food.default_amount.clone())
+            .unwrap_or_else(|| food.serving.clone());
+        parts.push((food.name, amount));
+    }
+    if parts.is_empty() {
+        anyhow::bail!("A recipe needs at least one ingredient");
+    }
+    db.atomically(|db| {
+        db.create_compound_food(name, &parts)?;
+        db.set_recipe_yield(name, recipe)
+    })
+}
+
+/// A recipe's servings and what one holds, e.g.
+/// "'lasagna': 8 servings of 300g, each 31p/22f/28c — 450 kcal".
+pub fn describe(name: &str, recipe: &RecipeYield, serving: &Macros) -> String {
+    let weight = recipe
+        .serving_grams()
+        .map(|g| format!(" of {:.0}g", g))
+        .unwrap_or_default();
+    format!(
+        "'{}': {} serving{}{}, each {:.0}p/{:.0}f/{:.0}c — {:.0} kcal",
+        name,
+        recipe.servings,
+        if recipe.servings == 1.0 { "" } else { "s" },
+        weight,
+        serving.protein,
+        serving.fat,
+        serving.carbs,
+        serving.calories
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recipe_from_json_ld_graph() {
+        let html = r#"<html><head>
+            <script type="application/ld+json">{"@type": "WebSite", "name": "Blog"}</script>
+            <script type="application/ld+json">{"@graph": [
+                {"@type": "WebPage"},
+                {"@type": ["Recipe"], "name": "Weeknight Chili &amp; Rice",
+                 "recipeYield": ["6", "6 servings"],
+                 "recipeIngredient": ["1 lb ground beef", "2 cans kidney beans"]}
+            ]}</script></head></html>"#;
+        let recipe = from_html(html).unwrap();
+        assert_eq!(recipe.name, "Weeknight Chili & Rice");
+        assert_eq!(recipe.servings, 6.0);
+        assert_eq!(recipe.ingredients.len(), 2);
+        assert!(from_html("<html></html>").is_err());
+    }
+
+    #[test]
+    fn test_parse_ingredient() {
+        assert_eq!(
+            parse_ingredient("1 1/2 cups rolled oats"),
+            Ingredient {
+                quantity: Some(1.5),
+                unit: Some("cup".to_string()),
+                name: "rolled oats".to_string()
+            }
+        );
+        let mut beans_food = Food::new("black beans", 8.9, 0.5, 23.7, 132.0, "100g", vec![]);
+        beans_food.default_amount = Some("130g".to_string());
+        let beans = parse_ingredient("1 (15 oz) can black beans, drained and rinsed");
+        assert_eq!(beans.name, "black beans");
+        assert_eq!(beans.amount(&beans_food, 0.5).as_deref(), Some("65g"));
+
+        let egg = Food::new("egg", 6.3, 5.3, 0.4, 72.0, "1 egg", vec![]);
+        let eggs = parse_ingredient("3 large eggs");
+        assert_eq!(eggs.amount(&egg, 1.0).as_deref(), Some("3 egg"));
+
+        let butter = parse_ingredient("½ tbsp. of butter");
+        assert_eq!(butter.name, "butter");
+        assert_eq!(butter.amount(&egg, 1.0).as_deref(), Some("0.5tbsp"));
+
+        assert_eq!(parse_ingredient("salt to taste").quantity, None);
+    }
+}
@@ -0,0 +1,207 @@
+//! `chomp recurring`: daily constants like supplements, logged by
+//! `chomp recurring run` (e.g. from cron) instead of by hand, or, for
+//! standing items added with `--auto`, the first time chomp runs each day.
+
+use anyhow::Result;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+
+use crate::db::{Database, LogEntry, LogOptions, Recurring};
+
+/// Days `recurring` is due on and hasn't been logged for, up to `now`:
+/// every day since the last run (or since it was added), and today once
+/// its time has passed.
+pub fn due(recurring: &Recurring, now: NaiveDateTime) -> Result<Vec<NaiveDate>> {
+    let time = NaiveTime::parse_from_str(&recurring.time, "%H:%M")?;
+    let first = match &recurring.last_run {
+        Some(last) => NaiveDate::parse_from_str(last, "%Y-%m-%d")?
+            .succ_opt()
+            .unwrap_or(NaiveDate::MAX),
+        None => NaiveDate::parse_from_str(&recurring.since, "%Y-%m-%d")?,
+    };
+    Ok(first
+        .iter_days()
+        .take_while(|day| day.and_time(time) <= now)
+        .collect())
+}
+
+/// What a run logged for one recurring entry on one day.
+pub struct Ran {
+    pub recurring: i64,
+    pub at: NaiveDateTime,
+    pub item: String,
+    pub entry: Result<LogEntry>,
+}
+
+/// Log every recurring entry that's due, catching up on days missed since
+/// the last run.
+pub fn run(db: &Database, now: NaiveDateTime) -> Result<Vec<Ran>> {
+    log_due(db, &db.list_recurring()?, NaiveDate::MIN, now, now)
+}
+
+/// Log the `auto` recurring entries the first time chomp runs on a new day:
+/// today's go in whatever the time, timed no later than `now`. Days chomp
+/// didn't run on are skipped, not filled in.
+pub fn roll_over(db: &Database, now: NaiveDateTime) -> Result<Vec<Ran>> {
+    let standing: Vec<Recurring> = db
+        .list_recurring()?
+        .into_iter()
+        .filter(|r| r.auto)
+        .collect();
+    let end_of_day = now.date().and_hms_opt(23, 59, 59).unwrap_or(now);
+    log_due(db, &standing, now.date(), end_of_day, now)
+}
+
+/// `roll_over` as of now, for the server, which reports to stderr.
+pub fn roll_over_now(db: &Database) {
+    let ran = match roll_over(db, chrono::Local::now().naive_local()) {
+        Ok(ran) => ran,
+        Err(e) => return eprintln!("Couldn't auto-log recurring entries: {}", e),
+    };
+    for item in ran {
+        match item.entry {
+            Ok(entry) => eprintln!("Auto-logged {} {}", entry.amount, entry.food_name),
+            Err(e) => eprintln!(
+                "Recurring {}: couldn't log {}: {}",
+                item.recurring, item.item, e
+            ),
+        }
+    }
+}
+
+/// Log what each of `recurring` is due for from `from` through `until`.
+/// Entries are keyed by recurring ID, day, and item, so runs that overlap
+/// don't log anything twice.
+fn log_due(
+    db: &Database,
+    recurring: &[Recurring],
+    from: NaiveDate,
+    until: NaiveDateTime,
+    now: NaiveDateTime,
+) -> Result<Vec<Ran>> {
+    let mut ran = Vec::new();
+    for recurring in recurring {
+        let time = NaiveTime::parse_from_str(&recurring.time, "%H:%M")?;
+        for day in due(recurring, until)?
+            .into_iter()
+            .filter(|day| *day >= from)
+        {
+            let date = day.format("%Y-%m-%d").to_string();
+            let at = day.and_time(time).min(now);
+            let mut logged = true;
+            for (i, item) in crate::logging::split_items(db, &recurring.input)?
+                .into_iter()
+                .enumerate()
+            {
+                let key = format!("recurring:{}:{}:{}:{}", recurring.id, date, i, item);
+                let opts = LogOptions {
+                    date: Some(&date),
+                    idempotency_key: Some(&key),
+                    recurring: Some(recurring.id),
+                    time: Some(at.time()),
+                    ..Default::default()
+                };
+                let entry = crate::logging::parse_and_log_with(db, &item, &opts);
+                logged &= entry.is_ok();
+                ran.push(Ran {
+                    recurring: recurring.id,
+                    at,
+                    item,
+                    entry,
+                });
+            }
+            // A day with an item that failed stays due, to be tried again
+            // on the next run; the items that did log aren't logged twice
+            if !logged {
+                break;
+            }
+            db.set_recurring_run(recurring.id, &date)?;
+        }
+    }
+    Ok(ran)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_run_catches_up_once() {
+        let db = Database::open_in_memory().unwrap();
+        let creatine = crate::food::Food::new("creatine", 0.0, 0.0, 0.0, 0.0, "5g", vec![]);
+        db.add_food(&creatine).unwrap();
+        let time = NaiveTime::from_hms_opt(8, 0, 0).unwrap();
+        let recurring = db
+            .add_recurring("creatine 5g", time, "2026-10-14", false)
+            .unwrap();
+
+        assert!(due(&recurring, at("2026-10-14 07:59")).unwrap().is_empty());
+        let ran = run(&db, at("2026-10-16 07:00")).unwrap();
+        let dates: Vec<&str> = ran
+            .iter()
+            .map(|r| r.entry.as_ref().unwrap().date.as_str())
+            .collect();
+        assert_eq!(dates, ["2026-10-14", "2026-10-15"]);
+
+        assert!(run(&db, at("2026-10-16 07:30")).unwrap().is_empty());
+        let ran = run(&db, at("2026-10-16 08:00")).unwrap();
+        assert_eq!(ran.len(), 1);
+        assert_eq!(ran[0].at, at("2026-10-16 08:00"));
+        assert_eq!(
+            db.list_recurring().unwrap()[0].last_run.as_deref(),
+            Some("2026-10-16")
+        );
+    }
+
+    #[test]
+    fn test_run_retries_a_failed_day() {
+        let db = Database::open_in_memory().unwrap();
+        let time = NaiveTime::from_hms_opt(8, 0, 0).unwrap();
+        db.add_recurring("creatine 5g", time, "2026-10-16", false)
+            .unwrap();
+
+        let ran = run(&db, at("2026-10-16 09:00")).unwrap();
+        assert!(ran[0].entry.is_err());
+        assert_eq!(db.list_recurring().unwrap()[0].last_run, None);
+
+        let creatine = crate::food::Food::new("creatine", 0.0, 0.0, 0.0, 0.0, "5g", vec![]);
+        db.add_food(&creatine).unwrap();
+        let ran = run(&db, at("2026-10-16 09:30")).unwrap();
+        assert!(ran[0].entry.is_ok());
+        assert!(run(&db, at("2026-10-16 10:00")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_roll_over_logs_standing_items_once_a_day() {
+        let db = Database::open_in_memory().unwrap();
+        let coffee = crate::food::Food::new("coffee", 0.3, 0.0, 0.0, 2.0, "1 cup", vec![]);
+        db.add_food(&coffee).unwrap();
+        let time = NaiveTime::from_hms_opt(8, 0, 0).unwrap();
+        db.add_recurring("coffee", time, "2026-10-16", true)
+            .unwrap();
+        db.add_recurring("coffee 2 cups", time, "2026-10-16", false)
+            .unwrap();
+
+        let ran = roll_over(&db, at("2026-10-16 06:30")).unwrap();
+        assert_eq!(ran.len(), 1);
+        assert_eq!(ran[0].at, at("2026-10-16 06:30"));
+        let entry = ran[0].entry.as_ref().unwrap();
+        assert_eq!(entry.amount, "1 cup");
+        assert!(entry.recurring.is_some());
+        assert!(roll_over(&db, at("2026-10-16 12:00")).unwrap().is_empty());
+
+        // Removing today's doesn't bring it back
+        let removed = db.delete_recurring_entries("2026-10-16").unwrap();
+        assert_eq!(removed.len(), 1);
+        assert!(roll_over(&db, at("2026-10-16 13:00")).unwrap().is_empty());
+        assert_eq!(roll_over(&db, at("2026-10-17 07:00")).unwrap().len(), 1);
+
+        // Days chomp didn't run on aren't filled in
+        let ran = roll_over(&db, at("2026-10-20 09:00")).unwrap();
+        assert_eq!(ran.len(), 1);
+        assert_eq!(ran[0].entry.as_ref().unwrap().date, "2026-10-20");
+    }
+}
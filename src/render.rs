@@ -0,0 +1,528 @@
+use anyhow::Result;
+use std::io::IsTerminal;
+
+use crate::config::{Config, Metric};
+use crate::db::{CaffeineEntry, CaffeineTotals, LogEntry, Stats, WaterEntry, WaterTotals};
+use crate::food::{Food, Macros, Micros};
+use crate::goal::Progress;
+use crate::i18n::Locale;
+use crate::meal::MealGroup;
+use crate::status::Targets;
+
+/// Width of a goal progress bar, in characters.
+const GOAL_BAR: usize = 20;
+
+/// What a piece of output means; the theme decides how it looks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Role {
+    /// Over a daily target
+    Over,
+    /// Progress toward (or meeting) a target
+    Progress,
+    /// Names and headings
+    Emphasis,
+    /// Secondary details such as dates and ids
+    Muted,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Theme {
+    Default,
+    HighContrast,
+    Mono,
+}
+
+impl Theme {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "default" => Ok(Theme::Default),
+            "high-contrast" => Ok(Theme::HighContrast),
+            "mono" => Ok(Theme::Mono),
+            _ => anyhow::bail!(
+                "Unknown theme: {}. Use default, high-contrast, or mono.",
+                name
+            ),
+        }
+    }
+
+    fn ansi(self, role: Role) -> &'static str {
+        match (self, role) {
+            (Theme::Default, Role::Over) => "31",
+            (Theme::Default, Role::Progress) => "32",
+            (Theme::Default, Role::Emphasis) => "1",
+            (Theme::Default, Role::Muted) => "2",
+            (Theme::HighContrast, Role::Over) => "1;91",
+            (Theme::HighContrast, Role::Progress) => "1;92",
+            (Theme::HighContrast, Role::Emphasis) => "1;97",
+            (Theme::HighContrast, Role::Muted) => "37",
+            (Theme::Mono, Role::Over) => "1;7",
+            (Theme::Mono, Role::Progress) => "1",
+            (Theme::Mono, Role::Emphasis) => "1",
+            (Theme::Mono, Role::Muted) => "2",
+        }
+    }
+}
+
+/// All human-readable CLI output goes through here so color, theme, and
+/// language handling live in one place.
+pub struct Renderer {
+    color: bool,
+    theme: Theme,
+    locale: Locale,
+}
+
+impl Renderer {
+    pub fn new(color: bool, theme: Theme, locale: Locale) -> Self {
+        Self {
+            color,
+            theme,
+            locale,
+        }
+    }
+
+    /// Color is on only for a terminal, and only if none of `--no-color`,
+    /// NO_COLOR, or `color = false` in the config turned it off.
+    pub fn detect(no_color: bool, config: &Config) -> Result<Self> {
+        let theme = Theme::parse(config.theme.as_deref().unwrap_or("default"))?;
+        let no_color_env = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        let color = !no_color
+            && !no_color_env
+            && config.color.unwrap_or(true)
+            && std::io::stdout().is_terminal();
+        Ok(Self::new(color, theme, Locale::detect(config)))
+    }
+
+    pub fn paint(&self, role: Role, text: &str) -> String {
+        if self.color {
+            format!("\x1b[{}m{}\x1b[0m", self.theme.ansi(role), text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn num(&self, value: f64) -> String {
+        self.locale.number(value, 0)
+    }
+
+    fn macros(&self, protein: f64, fat: f64, carbs: f64) -> String {
+        self.locale.format(
+            "macros",
+            &[
+                ("protein", &self.num(protein)),
+                ("fat", &self.num(fat)),
+                ("carbs", &self.num(carbs)),
+            ],
+        )
+    }
+
+    /// A log entry line, e.g. "Logged: 50g oats — 6p/4f/34c" for `logged`.
+    pub fn log_entry(&self, key: &str, entry: &LogEntry) -> String {
+        self.locale.format(
+            key,
+            &[
+                ("amount", &entry.amount),
+                ("food", &self.paint(Role::Emphasis, &entry.food_name)),
+                (
+                    "macros",
+                    &self.macros(entry.protein, entry.fat, entry.carbs),
+                ),
+            ],
+        )
+    }
+
+    /// History line; entries a recurring entry logged end in "(auto-logged)".
+    pub fn history_entry(&self, entry: &LogEntry) -> String {
+        let auto = if entry.recurring.is_some() {
+            let tag = self.locale.format("auto-logged-tag", &[]);
+            format!(" {}", self.paint(Role::Muted, &tag))
+        } else {
+            String::new()
+        };
+        format!(
+            "{} | {} {} | {}{}",
+            self.paint(Role::Muted, &entry.date),
+            entry.amount,
+            self.paint(Role::Emphasis, &entry.food_name),
+            self.macros(entry.protein, entry.fat, entry.carbs),
+            auto
+        )
+    }
+
+    fn food_args(&self, key: &str, food: &Food) -> String {
+        self.locale.format(
+            key,
+            &[
+                ("food", &self.paint(Role::Emphasis, &food.name)),
+                ("macros", &self.macros(food.protein, food.fat, food.carbs)),
+                ("serving", &food.serving),
+            ],
+        )
+    }
+
+    /// Search result line, e.g. "oats: 13p/7f/68c per 100g".
+    pub fn food(&self, food: &Food) -> String {
+        self.food_args("food-line", food)
+    }
+
+    /// "Added: oats (13p/7f/68c per 100g)" for `food-added`, likewise `food-updated`.
+    pub fn food_saved(&self, key: &str, food: &Food) -> String {
+        self.food_args(key, food)
+    }
+
+    pub fn food_deleted(&self, name: &str) -> String {
+        self.locale.format("food-deleted", &[("food", name)])
+    }
+
+    /// Two-line daily summary. Calories turn red once over target; protein
+    /// is shown as progress toward its target and turns green when met.
+    pub fn today(
+        &self,
+        totals: &Macros,
+        water: &WaterTotals,
+        caffeine: &CaffeineTotals,
+        targets: &Targets,
+    ) -> String {
+        let protein = match targets.protein {
+            Some(t) => {
+                let text = format!("{}/{}", self.num(totals.protein), self.num(t));
+                let role = if totals.protein >= t {
+                    Role::Progress
+                } else {
+                    Role::Emphasis
+                };
+                self.paint(role, &text)
+            }
+            None => self.num(totals.protein),
+        };
+        let kcal = match targets.calories {
+            Some(t) => {
+                let text = format!("{}/{} kcal", self.num(totals.calories), self.num(t));
+                let role = if totals.calories > t {
+                    Role::Over
+                } else {
+                    Role::Progress
+                };
+                self.paint(role, &text)
+            }
+            None => format!("{} kcal", self.num(totals.calories)),
+        };
+        let extras = self.locale.format(
+            "today-extras",
+            &[
+                ("water", &self.of_target(water.total_ml, targets.water)),
+                ("caffeine", &self.num(caffeine.total_mg)),
+            ],
+        );
+        let mut text = format!(
+            "{}\n       {}",
+            self.locale.format(
+                "today",
+                &[
+                    ("protein", &protein),
+                    ("fat", &self.num(totals.fat)),
+                    ("carbs", &self.num(totals.carbs)),
+                    ("kcal", &kcal),
+                ],
+            ),
+            self.paint(Role::Muted, &extras)
+        );
+        if !totals.micros.is_empty() {
+            text.push_str("\n       ");
+            text.push_str(&self.paint(Role::Muted, &self.micros(&totals.micros)));
+        }
+        text
+    }
+
+    /// Whichever of sugar, saturated fat, and sodium are known, e.g.
+    /// "32g sugar / 1840mg sodium".
+    fn micros(&self, micros: &Micros) -> String {
+        [
+            ("micro-sugar", micros.sugar),
+            ("micro-saturated-fat", micros.saturated_fat),
+            ("micro-sodium", micros.sodium),
+        ]
+        .iter()
+        .filter_map(|(key, value)| {
+            let value = (*value)?;
+            Some(self.locale.format(key, &[("amount", &self.num(value))]))
+        })
+        .collect::<Vec<_>>()
+        .join(" / ")
+    }
+
+    /// A meal as a heading: its name capitalized, or "Other" for entries
+    /// logged without one.
+    fn meal_name(&self, meal: Option<&str>) -> String {
+        match meal {
+            Some(meal) => {
+                let mut chars = meal.chars();
+                chars
+                    .next()
+                    .map(|first| first.to_uppercase().chain(chars).collect())
+                    .unwrap_or_default()
+            }
+            None => self.locale.message("meal-other").to_string(),
+        }
+    }
+
+    fn meal_totals(&self, totals: &Macros) -> String {
+        format!(
+            "{} — {} kcal",
+            self.macros(totals.protein, totals.fat, totals.carbs),
+            self.num(totals.calories)
+        )
+    }
+
+    /// A line per meal under today's totals, e.g. "Breakfast  30p/18f/40c — 470 kcal".
+    pub fn meal_subtotals(&self, groups: &[MealGroup]) -> String {
+        let width = groups
+            .iter()
+            .map(|g| self.meal_name(g.meal.as_deref()).chars().count())
+            .max()
+            .unwrap_or(0);
+        groups
+            .iter()
+            .map(|g| {
+                let name = format!("{:<width$}", self.meal_name(g.meal.as_deref()));
+                format!(
+                    "{}  {}",
+                    self.paint(Role::Emphasis, &name),
+                    self.meal_totals(&g.totals)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// A heading over one day's entries for a meal in history, e.g.
+    /// "2026-03-21 Breakfast: 30p/18f/40c — 470 kcal".
+    pub fn meal_heading(&self, group: &MealGroup) -> String {
+        format!(
+            "{} {}: {}",
+            self.paint(Role::Muted, &group.date),
+            self.paint(Role::Emphasis, &self.meal_name(group.meal.as_deref())),
+            self.meal_totals(&group.totals)
+        )
+    }
+
+    /// A bar per goal, e.g. "Calories  █████████████░░░░░░░   65%  770 kcal left".
+    /// Bars turn green once a goal is met, and red once calories go over.
+    pub fn goal_progress(&self, progress: &[Progress]) -> String {
+        let label = |p: &Progress| self.locale.message(&format!("goal-{}", p.metric.name()));
+        let width = progress
+            .iter()
+            .map(|p| label(p).chars().count())
+            .max()
+            .unwrap_or(0);
+        progress
+            .iter()
+            .map(|p| {
+                let filled =
+                    ((p.percent / 100.0).clamp(0.0, 1.0) * GOAL_BAR as f64).round() as usize;
+                let bar = format!("{}{}", "█".repeat(filled), "░".repeat(GOAL_BAR - filled));
+                let role = match p.metric {
+                    Metric::Calories if p.remaining < 0.0 => Role::Over,
+                    _ if p.remaining <= 0.0 => Role::Progress,
+                    _ => Role::Emphasis,
+                };
+                let unit = |value: f64| match p.metric {
+                    Metric::Calories => format!("{} kcal", self.num(value)),
+                    _ => format!("{}g", self.num(value)),
+                };
+                let left = if p.remaining < 0.0 {
+                    self.locale
+                        .format("goal-over", &[("amount", &unit(-p.remaining))])
+                } else {
+                    self.locale
+                        .format("goal-left", &[("amount", &unit(p.remaining))])
+                };
+                format!(
+                    "{:<width$}  {}  {:>4}%  {}",
+                    label(p),
+                    self.paint(role, &bar),
+                    self.num(p.percent),
+                    self.paint(Role::Muted, &left)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn water_args(&self, key: &str, ml: f64, goal: Option<f64>) -> String {
+        self.locale.format(
+            key,
+            &[
+                ("ml", &self.of_target(ml, goal)),
+                ("oz", &self.locale.number(ml / 29.5735, 1)),
+            ],
+        )
+    }
+
+    /// `value`, or "value/target" when there's a target.
+    fn of_target(&self, value: f64, target: Option<f64>) -> String {
+        match target {
+            Some(target) => format!("{}/{}", self.num(value), self.num(target)),
+            None => self.num(value),
+        }
+    }
+
+    /// Today's water, against the daily goal if one is set.
+    pub fn water_today(&self, totals: &WaterTotals, goal: Option<f64>) -> String {
+        self.water_args("water-today", totals.total_ml, goal)
+    }
+
+    pub fn water_logged(&self, entry: &WaterEntry) -> String {
+        self.water_args("water-logged", entry.amount_ml, None)
+    }
+
+    pub fn caffeine_logged(&self, entry: &CaffeineEntry) -> String {
+        let source = if entry.source.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", entry.source)
+        };
+        self.locale.format(
+            "caffeine-logged",
+            &[("mg", &self.num(entry.amount_mg)), ("source", &source)],
+        )
+    }
+
+    pub fn stats(&self, stats: &Stats) -> String {
+        self.locale.format(
+            "stats",
+            &[
+                ("foods", &stats.food_count.to_string()),
+                ("logs", &stats.log_count.to_string()),
+                ("first", stats.first_entry.as_deref().unwrap_or_default()),
+                ("last", stats.last_entry.as_deref().unwrap_or_default()),
+            ],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn totals(calories: f64, protein: f64) -> Macros {
+        Macros {
+            protein,
+            fat: 40.0,
+            carbs: 120.0,
+            calories,
+            fiber: 0.0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_plain_output_has_no_escapes() {
+        let r = Renderer::new(false, Theme::Default, Locale::En);
+        let text = r.today(
+            &totals(1430.0, 92.0),
+            &WaterTotals { total_ml: 500.0 },
+            &CaffeineTotals { total_mg: 0.0 },
+            &Targets::default(),
+        );
+        assert_eq!(
+            text,
+            "Today: 92p / 40f / 120c — 1430 kcal\n       500ml water / 0mg caffeine"
+        );
+    }
+
+    #[test]
+    fn test_water_goal() {
+        let r = Renderer::new(false, Theme::Default, Locale::En);
+        let water = WaterTotals { total_ml: 750.0 };
+        let targets = Targets {
+            water: Some(2500.0),
+            ..Default::default()
+        };
+        let text = r.today(
+            &totals(1430.0, 92.0),
+            &water,
+            &CaffeineTotals { total_mg: 0.0 },
+            &targets,
+        );
+        assert!(
+            text.ends_with("750/2500ml water / 0mg caffeine"),
+            "{}",
+            text
+        );
+        assert_eq!(
+            r.water_today(&water, Some(2500.0)),
+            "Water today: 750/2500ml (25.4 oz)"
+        );
+    }
+
+    #[test]
+    fn test_overage_and_progress_colors() {
+        let r = Renderer::new(true, Theme::Default, Locale::En);
+        let targets = Targets {
+            calories: Some(2000.0),
+            protein: Some(150.0),
+            water: None,
+        };
+        let water = WaterTotals { total_ml: 0.0 };
+        let caffeine = CaffeineTotals { total_mg: 0.0 };
+
+        let over = r.today(&totals(2500.0, 160.0), &water, &caffeine, &targets);
+        assert!(over.contains("\x1b[31m2500/2000 kcal\x1b[0m"));
+        assert!(over.contains("\x1b[32m160/150\x1b[0mp"));
+
+        let mono = Renderer::new(true, Theme::Mono, Locale::En);
+        assert!(!mono
+            .today(&totals(2500.0, 160.0), &water, &caffeine, &targets)
+            .contains("\x1b[31m"));
+
+        assert!(Theme::parse("neon").is_err());
+    }
+
+    #[test]
+    fn test_goal_progress_bars() {
+        let r = Renderer::new(false, Theme::Default, Locale::En);
+        let goals = crate::config::Goals {
+            calories: Some(2000.0),
+            protein: Some(150.0),
+            ..Default::default()
+        };
+        let progress = crate::goal::progress(&totals(2200.0, 60.0), &goals);
+        assert_eq!(
+            r.goal_progress(&progress),
+            "Calories  ████████████████████   110%  200 kcal over\n\
+             Protein   ████████░░░░░░░░░░░░    40%  90g left"
+        );
+    }
+
+    #[test]
+    fn test_meal_subtotals() {
+        let r = Renderer::new(false, Theme::Default, Locale::En);
+        let group = |meal: Option<&str>, calories: f64| MealGroup {
+            date: "2026-10-16".to_string(),
+            meal: meal.map(str::to_string),
+            totals: totals(calories, 30.0),
+            entries: Vec::new(),
+        };
+        let groups = [group(Some("breakfast"), 470.0), group(None, 120.0)];
+        assert_eq!(
+            r.meal_subtotals(&groups),
+            "Breakfast  30p/40f/120c — 470 kcal\n\
+             Other      30p/40f/120c — 120 kcal"
+        );
+        assert_eq!(
+            r.meal_heading(&groups[0]),
+            "2026-10-16 Breakfast: 30p/40f/120c — 470 kcal"
+        );
+    }
+
+    #[test]
+    fn test_localized_output() {
+        let r = Renderer::new(false, Theme::Default, Locale::De);
+        let entry = WaterEntry {
+            id: Some(1),
+            date: "2024-01-01".to_string(),
+            amount_ml: 500.0,
+        };
+        assert_eq!(r.water_logged(&entry), "Erfasst: 500ml Wasser (16,9 oz)");
+    }
+}
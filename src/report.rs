@@ -0,0 +1,833 @@
+use anyhow::Result;
+use chrono::{Datelike, Duration, Months, NaiveDate};
+use serde::Serialize;
+
+use crate::adherence;
+use crate::config::{Config, Metric};
+use crate::db::{Database, FoodTally, MacroSource, Rollup, TargetAdjustment};
+use crate::food::Macros;
+
+const MONTH_NAMES: [&str; 12] = [
+    "january",
+    "february",
+    "march",
+    "april",
+    "may",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+];
+
+/// How many foods each month lists.
+const TOP_FOODS: usize = 5;
+
+/// How many foods `chomp report sources` lists before lumping the rest.
+const TOP_SOURCES: usize = 10;
+
+/// A calendar month, first to last day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Month {
+    pub first: NaiveDate,
+    pub last: NaiveDate,
+}
+
+/// "2026-06", or a month name ("june", "jun") meaning its latest occurrence
+/// up to `today`.
+pub fn parse_month(input: &str, today: NaiveDate) -> Result<Month> {
+    let input = input.trim().to_lowercase();
+    let first = match NaiveDate::parse_from_str(&format!("{}-01", input), "%Y-%m-%d") {
+        Ok(first) => first,
+        Err(_) => {
+            let month = MONTH_NAMES
+                .iter()
+                .position(|name| input.len() >= 3 && name.starts_with(&input))
+                .ok_or_else(|| anyhow::anyhow!("Unknown month '{}' (use june or 2026-06)", input))?
+                as u32
+                + 1;
+            let year = if month > today.month() {
+                today.year() - 1
+            } else {
+                today.year()
+            };
+            NaiveDate::from_ymd_opt(year, month, 1).expect("valid month")
+        }
+    };
+    let last = first
+        .checked_add_months(Months::new(1))
+        .map_or(NaiveDate::MAX, |next| next - Duration::days(1));
+    Ok(Month { first, last })
+}
+
+/// One month of the log, boiled down for comparison with others.
+#[derive(Debug, Serialize)]
+pub struct MonthSummary {
+    /// "2026-06"
+    pub month: String,
+    pub days_logged: usize,
+    /// Days logged under the incomplete-day threshold
+    pub incomplete_days: i64,
+    /// Averages per logged day
+    pub calories: f64,
+    pub protein: f64,
+    pub fat: f64,
+    pub carbs: f64,
+    /// Last weigh-in of the month minus the first, in kg
+    pub weight_change: Option<f64>,
+    /// Logged days on target, each judged by its own goals
+    pub days_on_target: usize,
+    /// Most calories contributed first
+    pub top_foods: Vec<FoodTally>,
+}
+
+impl MonthSummary {
+    fn on_target_share(&self) -> Option<f64> {
+        (self.days_logged > 0).then(|| self.days_on_target as f64 / self.days_logged as f64)
+    }
+}
+
+pub fn summarize(
+    db: &Database,
+    config: &Config,
+    adjustments: &[TargetAdjustment],
+    month: Month,
+) -> Result<MonthSummary> {
+    let (first, last) = (month.first.to_string(), month.last.to_string());
+    let days: Vec<Rollup> = db
+        .rollups(&first, "day")?
+        .into_iter()
+        .filter(|day| day.period <= last)
+        .collect();
+    let mean = |value: fn(&Rollup) -> f64| {
+        if days.is_empty() {
+            0.0
+        } else {
+            days.iter().map(value).sum::<f64>() / days.len() as f64
+        }
+    };
+    let weights: Vec<f64> = db
+        .get_weight_history(&first)?
+        .into_iter()
+        .filter(|w| w.date <= last)
+        .map(|w| w.kg)
+        .collect();
+
+    let label = month.first.format("%Y-%m").to_string();
+    // Days left out of the daily series still count toward the month's
+    let incomplete_days = db
+        .rollups(&first, "month")?
+        .iter()
+        .find(|m| m.period == label)
+        .map_or(0, |m| m.incomplete);
+
+    Ok(MonthSummary {
+        month: label,
+        days_logged: days.len(),
+        incomplete_days,
+        calories: mean(|d| d.calories),
+        protein: mean(|d| d.protein),
+        fat: mean(|d| d.fat),
+        carbs: mean(|d| d.carbs),
+        weight_change: match weights.as_slice() {
+            [first, .., last] => Some(last - first),
+            _ => None,
+        },
+        days_on_target: adherence::evaluate(config, adjustments, &days)
+            .iter()
+            .filter(|d| d.on_target)
+            .count(),
+        top_foods: db.top_foods(&first, &last, TOP_FOODS)?,
+    })
+}
+
+/// Months side by side, with the change from the first to the second when
+/// there are exactly two.
+pub fn render(months: &[MonthSummary]) -> String {
+    let change = months.len() == 2;
+    let width = months
+        .iter()
+        .flat_map(|m| m.top_foods.iter().map(|f| f.name.chars().count()))
+        .max()
+        .unwrap_or(0)
+        .clamp(10, 24);
+    let row = |label: &str, cells: Vec<String>| {
+        let mut line = format!("{:<12}", label);
+        for cell in cells {
+            line.push_str(&format!("  {:>width$}", cell, width = width));
+        }
+        line.trim_end().to_string()
+    };
+    let logged = |m: &MonthSummary, text: String| {
+        if m.days_logged == 0 {
+            "—".to_string()
+        } else {
+            text
+        }
+    };
+    let macro_row = |label: &str, unit: &str, value: fn(&MonthSummary) -> f64| {
+        let mut cells: Vec<String> = months
+            .iter()
+            .map(|m| logged(m, format!("{:.0}{}", value(m), unit)))
+            .collect();
+        if change {
+            cells.push(match (months[0].days_logged, months[1].days_logged) {
+                (0, _) | (_, 0) => "—".to_string(),
+                _ => format!("{:+.0}{}", value(&months[1]) - value(&months[0]), unit),
+            });
+        }
+        row(label, cells)
+    };
+
+    let mut header: Vec<String> = months
+        .iter()
+        .map(|m| {
+            NaiveDate::parse_from_str(&format!("{}-01", m.month), "%Y-%m-%d")
+                .map(|d| d.format("%b %Y").to_string())
+                .unwrap_or_else(|_| m.month.clone())
+        })
+        .collect();
+    let mut days: Vec<String> = months.iter().map(|m| m.days_logged.to_string()).collect();
+    let mut incomplete: Vec<String> = months
+        .iter()
+        .map(|m| m.incomplete_days.to_string())
+        .collect();
+    let mut weight: Vec<String> = months
+        .iter()
+        .map(|m| {
+            m.weight_change
+                .map_or("—".to_string(), |kg| format!("{:+.1}kg", kg))
+        })
+        .collect();
+    let mut on_target: Vec<String> = months
+        .iter()
+        .map(|m| match m.on_target_share() {
+            Some(share) => format!("{} ({:.0}%)", m.days_on_target, share * 100.0),
+            None => "—".to_string(),
+        })
+        .collect();
+    if change {
+        let (a, b) = (&months[0], &months[1]);
+        header.push("Change".to_string());
+        days.push(format!("{:+}", b.days_logged as i64 - a.days_logged as i64));
+        incomplete.push(format!("{:+}", b.incomplete_days - a.incomplete_days));
+        weight.push(match (a.weight_change, b.weight_change) {
+            (Some(a), Some(b)) => format!("{:+.1}kg", b - a),
+            _ => "—".to_string(),
+        });
+        on_target.push(match (a.on_target_share(), b.on_target_share()) {
+            (Some(a), Some(b)) => format!("{:+.0} pts", (b - a) * 100.0),
+            _ => "—".to_string(),
+        });
+    }
+
+    let mut lines = vec![row("", header), row("Days logged", days)];
+    if months.iter().any(|m| m.incomplete_days > 0) {
+        lines.push(row("Incomplete", incomplete));
+    }
+    lines.extend([
+        macro_row("Calories", "", |m| m.calories),
+        macro_row("Protein", "g", |m| m.protein),
+        macro_row("Fat", "g", |m| m.fat),
+        macro_row("Carbs", "g", |m| m.carbs),
+        row("Weight", weight),
+        row("On target", on_target),
+    ]);
+    for rank in 0..TOP_FOODS {
+        let cells: Vec<String> = months
+            .iter()
+            .map(|m| {
+                m.top_foods
+                    .get(rank)
+                    .map_or(String::new(), |food| truncate(&food.name, width))
+            })
+            .collect();
+        if cells.iter().all(|c| c.is_empty()) {
+            break;
+        }
+        let label = if rank == 0 { "Top foods" } else { "" };
+        lines.push(row(label, cells));
+    }
+    lines.join("\n")
+}
+
+/// Where a stretch of days' macros came from, food by food.
+#[derive(Debug, Serialize)]
+pub struct SourcesReport {
+    pub from: String,
+    pub to: String,
+    pub totals: Macros,
+    /// Biggest contributors to the chosen macro first
+    pub foods: Vec<Source>,
+}
+
+/// One food's part of a `SourcesReport`.
+#[derive(Debug, Serialize)]
+pub struct Source {
+    pub name: String,
+    pub times: i64,
+    pub totals: Macros,
+    /// Percent of the period's total, per macro
+    pub share: Macros,
+}
+
+/// Each food's contribution to every macro from `from` to `to`, ordered by
+/// its share of `by`.
+pub fn sources(db: &Database, from: NaiveDate, to: NaiveDate, by: Metric) -> Result<SourcesReport> {
+    let logged: Vec<MacroSource> = db.macro_sources(&from.to_string(), &to.to_string())?;
+    let mut totals = Macros::default();
+    for food in &logged {
+        totals.protein += food.totals.protein;
+        totals.fat += food.totals.fat;
+        totals.carbs += food.totals.carbs;
+        totals.calories += food.totals.calories;
+        totals.fiber += food.totals.fiber;
+        totals.micros.add(&food.totals.micros);
+    }
+    let percent = |part: f64, whole: f64| {
+        if whole > 0.0 {
+            part * 100.0 / whole
+        } else {
+            0.0
+        }
+    };
+    let mut foods: Vec<Source> = logged
+        .into_iter()
+        .map(|food| Source {
+            share: Macros {
+                protein: percent(food.totals.protein, totals.protein),
+                fat: percent(food.totals.fat, totals.fat),
+                carbs: percent(food.totals.carbs, totals.carbs),
+                calories: percent(food.totals.calories, totals.calories),
+                fiber: percent(food.totals.fiber, totals.fiber),
+                ..Default::default()
+            },
+            name: food.name,
+            times: food.times,
+            totals: food.totals,
+        })
+        .collect();
+    // Stable, so ties keep the calorie order
+    foods.sort_by(|a, b| by.value(&b.totals).total_cmp(&by.value(&a.totals)));
+    Ok(SourcesReport {
+        from: from.to_string(),
+        to: to.to_string(),
+        totals,
+        foods,
+    })
+}
+
+/// A table of each food's percentage of every macro, the top foods by
+/// `by` and then everything else on one line.
+pub fn render_sources(report: &SourcesReport, by: Metric) -> String {
+    let t = &report.totals;
+    let mut lines = vec![format!(
+        "{} to {}: {:.0} kcal, {:.0}g protein, {:.0}g fat, {:.0}g carbs",
+        report.from, report.to, t.calories, t.protein, t.fat, t.carbs
+    )];
+    if report.foods.is_empty() {
+        lines.push("Nothing logged".to_string());
+        return lines.join("\n");
+    }
+
+    let rest = report.foods.get(TOP_SOURCES..).unwrap_or_default();
+    let other = (!rest.is_empty()).then(|| {
+        let sum = |value: fn(&Macros) -> f64| rest.iter().map(|f| value(&f.share)).sum::<f64>();
+        (
+            format!("Other ({} foods)", rest.len()),
+            Macros {
+                protein: sum(|m| m.protein),
+                fat: sum(|m| m.fat),
+                carbs: sum(|m| m.carbs),
+                calories: sum(|m| m.calories),
+                fiber: sum(|m| m.fiber),
+                ..Default::default()
+            },
+        )
+    });
+    let rows: Vec<(String, &Macros)> = report
+        .foods
+        .iter()
+        .take(TOP_SOURCES)
+        .map(|f| (f.name.clone(), &f.share))
+        .chain(other.as_ref().map(|(name, share)| (name.clone(), share)))
+        .collect();
+    let width = rows
+        .iter()
+        .map(|(name, _)| name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .clamp(10, 24);
+
+    let columns = [
+        Metric::Calories,
+        Metric::Protein,
+        Metric::Fat,
+        Metric::Carbs,
+    ];
+    let mut header = format!("{:<width$}", "", width = width);
+    for metric in columns {
+        let mut label = metric.name().to_string();
+        label[..1].make_ascii_uppercase();
+        if metric == by {
+            label.push('*');
+        }
+        header.push_str(&format!("  {:>9}", label));
+    }
+    lines.push(header);
+    for (name, share) in rows {
+        let mut line = format!("{:<width$}", truncate(&name, width), width = width);
+        for metric in columns {
+            line.push_str(&format!("  {:>8.0}%", metric.value(share)));
+        }
+        lines.push(line);
+    }
+    lines.join("\n")
+}
+
+/// Average intake over some set of days, e.g. weekends.
+#[derive(Debug, Serialize)]
+pub struct Average {
+    pub label: String,
+    pub days: usize,
+    pub calories: f64,
+    pub protein: f64,
+    pub fat: f64,
+    pub carbs: f64,
+}
+
+impl Average {
+    fn of(label: &str, days: &[&Rollup]) -> Self {
+        let mean = |value: fn(&Rollup) -> f64| {
+            if days.is_empty() {
+                0.0
+            } else {
+                days.iter().map(|d| value(d)).sum::<f64>() / days.len() as f64
+            }
+        };
+        Average {
+            label: label.to_string(),
+            days: days.len(),
+            calories: mean(|d| d.calories),
+            protein: mean(|d| d.protein),
+            fat: mean(|d| d.fat),
+            carbs: mean(|d| d.carbs),
+        }
+    }
+}
+
+/// Weekdays against weekends, and each day of the week, over a stretch of
+/// the log.
+#[derive(Debug, Serialize)]
+pub struct WeekdaySplit {
+    pub from: String,
+    pub to: String,
+    /// Monday to Friday
+    pub weekdays: Average,
+    /// Saturday and Sunday
+    pub weekend: Average,
+    /// Monday first
+    pub by_day: Vec<Average>,
+}
+
+impl WeekdaySplit {
+    /// Calories a week the weekend adds over eating at the weekday pace all
+    /// week. None unless both have logged days.
+    pub fn weekend_surplus(&self) -> Option<f64> {
+        (self.weekdays.days > 0 && self.weekend.days > 0)
+            .then_some(2.0 * (self.weekend.calories - self.weekdays.calories))
+    }
+}
+
+/// Average intake by day of the week from `from` to `to`, from the daily
+/// totals.
+pub fn weekday_split(db: &Database, from: NaiveDate, to: NaiveDate) -> Result<WeekdaySplit> {
+    let last = to.to_string();
+    let days: Vec<(chrono::Weekday, Rollup)> = db
+        .rollups(&from.to_string(), "day")?
+        .into_iter()
+        .filter(|day| day.period <= last)
+        .filter_map(|day| {
+            let date = NaiveDate::parse_from_str(&day.period, "%Y-%m-%d").ok()?;
+            Some((date.weekday(), day))
+        })
+        .collect();
+    let matching = |keep: &dyn Fn(chrono::Weekday) -> bool| -> Vec<&Rollup> {
+        days.iter()
+            .filter(|(weekday, _)| keep(*weekday))
+            .map(|(_, day)| day)
+            .collect()
+    };
+    let is_weekend = |d: chrono::Weekday| matches!(d, chrono::Weekday::Sat | chrono::Weekday::Sun);
+    let by_day = (0..7)
+        .map(|n| {
+            let weekday = chrono::Weekday::try_from(n as u8).expect("0..7 is a weekday");
+            Average::of(&weekday.to_string(), &matching(&|d| d == weekday))
+        })
+        .collect();
+    Ok(WeekdaySplit {
+        from: from.to_string(),
+        to: last,
+        weekdays: Average::of("Weekdays", &matching(&|d| !is_weekend(d))),
+        weekend: Average::of("Weekend", &matching(&is_weekend)),
+        by_day,
+    })
+}
+
+/// Weekdays, weekend, and their difference, then each day of the week.
+pub fn render_weekdays(split: &WeekdaySplit) -> String {
+    let row = |label: &str, cells: [String; 5]| {
+        let mut line = format!("{:<10}", label);
+        for cell in cells {
+            line.push_str(&format!("  {:>8}", cell));
+        }
+        line.trim_end().to_string()
+    };
+    let average = |a: &Average| {
+        if a.days == 0 {
+            return row(
+                &a.label,
+                [
+                    "0".to_string(),
+                    "—".into(),
+                    "—".into(),
+                    "—".into(),
+                    "—".into(),
+                ],
+            );
+        }
+        row(
+            &a.label,
+            [
+                a.days.to_string(),
+                format!("{:.0}", a.calories),
+                format!("{:.0}g", a.protein),
+                format!("{:.0}g", a.fat),
+                format!("{:.0}g", a.carbs),
+            ],
+        )
+    };
+
+    let mut lines = vec![
+        format!("{} to {}", split.from, split.to),
+        row(
+            "",
+            ["Days", "Calories", "Protein", "Fat", "Carbs"].map(String::from),
+        ),
+        average(&split.weekdays),
+        average(&split.weekend),
+    ];
+    let surplus = split.weekend_surplus();
+    if surplus.is_some() {
+        let (w, e) = (&split.weekdays, &split.weekend);
+        lines.push(row(
+            "Difference",
+            [
+                String::new(),
+                format!("{:+.0}", e.calories - w.calories),
+                format!("{:+.0}g", e.protein - w.protein),
+                format!("{:+.0}g", e.fat - w.fat),
+                format!("{:+.0}g", e.carbs - w.carbs),
+            ],
+        ));
+    }
+    lines.push(String::new());
+    lines.extend(split.by_day.iter().map(average));
+    if let Some(surplus) = surplus {
+        lines.push(String::new());
+        lines.push(format!(
+            "Weekends {} {:.0} kcal a week against your weekday pace",
+            if surplus >= 0.0 { "add" } else { "save" },
+            surplus.abs()
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Moving average of `rollups` over `window` periods of `resolution` (day,
+/// week, or month): each period with itself and the ones before it, or
+/// with it in the middle when `centered`. Windows span calendar time, so
+/// unlogged days narrow them rather than reaching further back, and periods
+/// count by their logged days. `days` is left as logged.
+pub fn smooth(rollups: &[Rollup], resolution: &str, window: u32, centered: bool) -> Vec<Rollup> {
+    let step = if resolution == "week" { 7 } else { 1 };
+    let index = |period: &str| -> Option<i64> {
+        if let Ok(date) = NaiveDate::parse_from_str(period, "%Y-%m-%d") {
+            return Some(i64::from(date.num_days_from_ce()));
+        }
+        let month = NaiveDate::parse_from_str(&format!("{}-01", period), "%Y-%m-%d").ok()?;
+        Some(i64::from(month.year()) * 12 + i64::from(month.month0()))
+    };
+    let window = i64::from(window.max(1));
+    let (before, after) = if centered {
+        ((window - 1) / 2, window / 2)
+    } else {
+        (window - 1, 0)
+    };
+    let indexed: Vec<(Option<i64>, &Rollup)> =
+        rollups.iter().map(|r| (index(&r.period), r)).collect();
+
+    indexed
+        .iter()
+        .map(|(at, rollup)| {
+            let Some(at) = at else {
+                return (*rollup).clone();
+            };
+            let (from, to) = (at - before * step, at + after * step);
+            let members: Vec<&Rollup> = indexed
+                .iter()
+                .filter(|(i, _)| i.is_some_and(|i| i >= from && i <= to))
+                .map(|(_, r)| *r)
+                .collect();
+            let days: i64 = members.iter().map(|r| r.days.max(1)).sum();
+            let mean = |value: fn(&Rollup) -> f64| {
+                members
+                    .iter()
+                    .map(|r| value(r) * r.days.max(1) as f64)
+                    .sum::<f64>()
+                    / days as f64
+            };
+            Rollup {
+                period: rollup.period.clone(),
+                days: rollup.days,
+                incomplete: rollup.incomplete,
+                protein: mean(|r| r.protein),
+                fat: mean(|r| r.fat),
+                carbs: mean(|r| r.carbs),
+                calories: mean(|r| r.calories),
+                micros: rollup.micros,
+            }
+        })
+        .collect()
+}
+
+fn truncate(name: &str, width: usize) -> String {
+    if name.chars().count() <= width {
+        name.to_string()
+    } else {
+        let mut short: String = name.chars().take(width - 1).collect();
+        short.push('…');
+        short
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::LogOptions;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn add_oats(db: &Database) -> i64 {
+        db.add_food(&crate::food::Food::new(
+            "Oats",
+            13.0,
+            7.0,
+            68.0,
+            389.0,
+            "100g",
+            vec![],
+        ))
+        .unwrap()
+    }
+
+    /// One day's log of Oats, with fixed macros and the given calories.
+    fn log_day(db: &Database, oats: i64, date: &str, calories: f64) {
+        let macros = Macros {
+            protein: 100.0,
+            fat: 50.0,
+            carbs: 200.0,
+            calories,
+            fiber: 0.0,
+            ..Default::default()
+        };
+        db.log_food_with(oats, "100g", &macros, &LogOptions::on_date(Some(date)))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_smooth() {
+        let day = |period: &str, calories: f64| Rollup {
+            period: period.to_string(),
+            days: 1,
+            incomplete: 0,
+            protein: 0.0,
+            fat: 0.0,
+            carbs: 0.0,
+            calories,
+            micros: Default::default(),
+        };
+        let days = [
+            day("2026-10-01", 2000.0),
+            day("2026-10-02", 2600.0),
+            day("2026-10-03", 1400.0),
+            day("2026-10-06", 2200.0),
+        ];
+        let calories =
+            |smoothed: Vec<Rollup>| -> Vec<f64> { smoothed.iter().map(|r| r.calories).collect() };
+        assert_eq!(
+            calories(smooth(&days, "day", 3, false)),
+            [2000.0, 2300.0, 2000.0, 2200.0]
+        );
+        assert_eq!(
+            calories(smooth(&days, "day", 3, true)),
+            [2300.0, 2000.0, 2000.0, 2200.0]
+        );
+        assert_eq!(
+            calories(smooth(&days, "day", 1, false)),
+            calories(days.to_vec())
+        );
+
+        // Periods count by their logged days
+        let mut months = vec![day("2026-09", 2000.0), day("2026-10", 2300.0)];
+        months[0].days = 20;
+        months[1].days = 10;
+        assert_eq!(calories(smooth(&months, "month", 2, false))[1], 2100.0);
+        let weeks = [day("2026-09-28", 2000.0), day("2026-10-12", 2400.0)];
+        assert_eq!(calories(smooth(&weeks, "week", 2, false))[1], 2400.0);
+    }
+
+    #[test]
+    fn test_parse_month() {
+        let today = date("2026-10-16");
+        let june = parse_month("June", today).unwrap();
+        assert_eq!(
+            (june.first, june.last),
+            (date("2026-06-01"), date("2026-06-30"))
+        );
+        assert_eq!(parse_month("nov", today).unwrap().first, date("2025-11-01"));
+        assert_eq!(parse_month("oct", today).unwrap().first, date("2026-10-01"));
+        assert_eq!(
+            parse_month("2024-02", today).unwrap().last,
+            date("2024-02-29")
+        );
+        assert!(parse_month("ju", today).is_err());
+        assert!(parse_month("2024-13", today).is_err());
+    }
+
+    #[test]
+    fn test_sources() {
+        let db = Database::open_in_memory().unwrap();
+        let add = |name: &str, protein: f64, fat: f64, carbs: f64| {
+            db.add_food(&crate::food::Food::new(
+                name,
+                protein,
+                fat,
+                carbs,
+                protein * 4.0 + fat * 9.0 + carbs * 4.0,
+                "100g",
+                vec![],
+            ))
+            .unwrap()
+        };
+        let rice = add("Rice", 3.0, 0.0, 28.0);
+        let chicken = add("Chicken", 31.0, 4.0, 0.0);
+        let log = |food: i64, protein: f64, fat: f64, carbs: f64, date: &str| {
+            let macros = Macros {
+                protein,
+                fat,
+                carbs,
+                calories: protein * 4.0 + fat * 9.0 + carbs * 4.0,
+                fiber: 0.0,
+                ..Default::default()
+            };
+            db.log_food_with(food, "100g", &macros, &LogOptions::on_date(Some(date)))
+                .unwrap();
+        };
+        log(rice, 10.0, 0.0, 150.0, "2026-10-10");
+        log(rice, 0.0, 0.0, 50.0, "2026-10-12");
+        log(chicken, 90.0, 20.0, 0.0, "2026-10-12");
+        log(chicken, 90.0, 20.0, 0.0, "2026-10-01");
+
+        let report = sources(&db, date("2026-10-10"), date("2026-10-16"), Metric::Protein).unwrap();
+        assert_eq!(report.totals.carbs, 200.0);
+        assert_eq!(report.foods[0].name, "Chicken");
+        assert_eq!(report.foods[0].share.protein, 90.0);
+        assert_eq!(report.foods[1].share.carbs, 100.0);
+        assert_eq!(report.foods[1].times, 2);
+
+        let text = render_sources(&report, Metric::Protein);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            lines[0],
+            "2026-10-10 to 2026-10-16: 1380 kcal, 100g protein, 20g fat, 200g carbs"
+        );
+        assert_eq!(
+            lines[1],
+            "             Calories   Protein*        Fat      Carbs"
+        );
+        assert_eq!(
+            lines[2],
+            "Chicken           39%        90%       100%         0%"
+        );
+    }
+
+    #[test]
+    fn test_weekday_split() {
+        let db = Database::open_in_memory().unwrap();
+        let oats = add_oats(&db);
+        // Mon, Tue, then Sat and Sun
+        log_day(&db, oats, "2026-10-05", 1800.0);
+        log_day(&db, oats, "2026-10-06", 2000.0);
+        log_day(&db, oats, "2026-10-10", 2600.0);
+        log_day(&db, oats, "2026-10-11", 2800.0);
+        log_day(&db, oats, "2026-10-12", 9000.0);
+
+        let split = weekday_split(&db, date("2026-10-05"), date("2026-10-11")).unwrap();
+        assert_eq!((split.weekdays.days, split.weekdays.calories), (2, 1900.0));
+        assert_eq!((split.weekend.days, split.weekend.calories), (2, 2700.0));
+        assert_eq!(split.by_day[0].label, "Mon");
+        assert_eq!(split.by_day[2].days, 0);
+        assert_eq!(split.weekend_surplus(), Some(1600.0));
+
+        let text = render_weekdays(&split);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            lines[1],
+            "                Days  Calories   Protein       Fat     Carbs"
+        );
+        assert_eq!(
+            lines[4],
+            "Difference                +800       +0g       +0g       +0g"
+        );
+        assert_eq!(
+            lines[8],
+            "Wed                0         —         —         —         —"
+        );
+        assert!(text.ends_with("Weekends add 1600 kcal a week against your weekday pace"));
+    }
+
+    #[test]
+    fn test_compare_months() {
+        let db = Database::open_in_memory().unwrap();
+        let oats = add_oats(&db);
+        log_day(&db, oats, "2026-06-01", 2400.0);
+        log_day(&db, oats, "2026-06-02", 2000.0);
+        log_day(&db, oats, "2026-07-01", 2000.0);
+        db.log_weight(80.0, Some("2026-06-01")).unwrap();
+        db.log_weight(79.5, Some("2026-06-30")).unwrap();
+
+        let config = Config::parse("[goals]\ncalories = 2000.0\n").unwrap();
+        let today = date("2026-10-16");
+        let months: Vec<MonthSummary> = ["june", "july"]
+            .iter()
+            .map(|m| summarize(&db, &config, &[], parse_month(m, today).unwrap()).unwrap())
+            .collect();
+        assert_eq!((months[0].days_logged, months[0].calories), (2, 2200.0));
+        assert_eq!(months[0].days_on_target, 1);
+        assert_eq!(months[0].weight_change, Some(-0.5));
+        assert_eq!(months[1].weight_change, None);
+        assert_eq!(months[1].top_foods[0].times, 1);
+
+        let text = render(&months);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "                Jun 2026    Jul 2026      Change");
+        assert_eq!(lines[2], "Calories            2200        2000        -200");
+        assert_eq!(lines[7], "On target        1 (50%)    1 (100%)     +50 pts");
+        assert_eq!(lines[8], "Top foods           Oats        Oats");
+    }
+}
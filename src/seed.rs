@@ -0,0 +1,349 @@
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+
+use chrono::{Datelike, Duration, Local, NaiveTime, TimeZone};
+use serde::Serialize;
+
+use crate::db::{Database, LogOptions, STARTER_FOODS};
+use crate::food::Food;
+
+/// Prefixes that turn a starter food into a plausible extra one once the
+/// starter list runs out.
+const VARIANTS: [&str; 6] = [
+    "homemade",
+    "organic",
+    "store brand",
+    "restaurant",
+    "frozen",
+    "deli",
+];
+
+/// Meals as (earliest, latest) local start times and how many sides go with
+/// the main food. Snacks have no main.
+const MEALS: [(&str, (u32, u32), usize); 4] = [
+    ("breakfast", (7 * 60, 9 * 60 + 30), 1),
+    ("lunch", (12 * 60, 14 * 60), 2),
+    ("snack", (15 * 60, 16 * 60 + 30), 1),
+    ("dinner", (18 * 60, 20 * 60 + 30), 2),
+];
+
+/// What `chomp dev seed` generated.
+#[derive(Debug, Serialize)]
+pub struct Seeded {
+    pub seed: u64,
+    pub foods: usize,
+    pub entries: usize,
+    pub water: usize,
+    pub weigh_ins: usize,
+    pub from: String,
+    pub to: String,
+}
+
+/// SplitMix64: small, fast, and the same sequence on every platform, which
+/// is all a demo history needs.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1).
+    fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn range(&mut self, low: f64, high: f64) -> f64 {
+        low + self.unit() * (high - low)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn chance(&mut self, p: f64) -> bool {
+        self.unit() < p
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
+        }
+    }
+}
+
+/// The starter foods, then variants of them until there are `count`.
+fn generate_foods(rng: &mut Rng, count: usize) -> Result<Vec<Food>> {
+    let mut reader = csv::Reader::from_reader(STARTER_FOODS.as_bytes());
+    let mut starters = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        let field = |i: usize| record.get(i).unwrap_or("").trim();
+        let number = |i: usize| field(i).parse::<f64>().unwrap_or(0.0);
+        let aliases = field(7)
+            .split(';')
+            .filter(|a| !a.is_empty())
+            .map(String::from)
+            .collect();
+        let mut food = Food::new(
+            field(0),
+            number(1),
+            number(2),
+            number(3),
+            number(4),
+            field(5),
+            aliases,
+        );
+        food.default_amount = Some(field(6)).filter(|d| !d.is_empty()).map(String::from);
+        starters.push(food);
+    }
+    rng.shuffle(&mut starters);
+
+    let mut foods: Vec<Food> = starters.iter().take(count).cloned().collect();
+    let mut taken: HashSet<String> = starters
+        .iter()
+        .flat_map(|f| std::iter::once(&f.name).chain(&f.aliases))
+        .map(|name| name.to_lowercase())
+        .collect();
+    let mut round = 0;
+    while foods.len() < count {
+        let prefix = VARIANTS[round % VARIANTS.len()];
+        for base in &starters {
+            if foods.len() == count {
+                break;
+            }
+            let name = match round / VARIANTS.len() {
+                0 => format!("{} {}", prefix, base.name),
+                n => format!("{} {} {}", prefix, base.name, n + 1),
+            };
+            if !taken.insert(name.to_lowercase()) {
+                continue;
+            }
+            let scale = rng.range(0.85, 1.15);
+            let mut food = Food::new(
+                &name,
+                round1(base.protein * scale),
+                round1(base.fat * rng.range(0.8, 1.2)),
+                round1(base.carbs * scale),
+                0.0,
+                &base.serving,
+                vec![],
+            );
+            food.calories = (food.protein * 4.0 + food.fat * 9.0 + food.carbs * 4.0).round();
+            food.default_amount = base.default_amount.clone();
+            foods.push(food);
+        }
+        round += 1;
+    }
+    Ok(foods)
+}
+
+fn round1(x: f64) -> f64 {
+    (x * 10.0).round() / 10.0
+}
+
+/// A serving near the food's usual one: weights and volumes vary by up to
+/// a third, plural counts by one.
+fn portion(rng: &mut Rng, food: &Food, appetite: f64) -> String {
+    let usual = food.default_amount.as_deref().unwrap_or(&food.serving);
+    let split = usual
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(usual.len());
+    let (number, unit) = usual.split_at(split);
+    let Ok(number) = number.parse::<f64>() else {
+        return usual.to_string();
+    };
+    let unit = unit.trim_start();
+    match unit {
+        "g" | "ml" => {
+            let step = if number >= 50.0 { 10.0 } else { 1.0 };
+            let amount = (number * appetite * rng.range(0.7, 1.3) / step).round() * step;
+            format!("{}{}", amount.max(step), unit)
+        }
+        // "3 slices" can become "4 slices"; "1 egg" stays as written
+        _ if number > 1.0 && rng.chance(0.25) => {
+            let spaced = if usual[split..].starts_with(' ') {
+                " "
+            } else {
+                ""
+            };
+            format!("{}{}{}", number + 1.0, spaced, unit)
+        }
+        _ => usual.to_string(),
+    }
+}
+
+/// Fill an empty database with `foods` foods and `days` days of meals ending
+/// today, plus water and weigh-ins. The same seed gives the same history
+/// (relative to today) every time.
+pub fn seed(db: &Database, days: u32, foods: usize, seed: u64) -> Result<Seeded> {
+    let existing = db.get_stats()?;
+    if existing.log_count > 0 || existing.food_count > 0 {
+        anyhow::bail!(
+            "The database already has {} foods and {} log entries; seed a fresh one, e.g. \
+             CHOMP_DB_PATH=/tmp/demo.db chomp dev seed",
+            existing.food_count,
+            existing.log_count
+        );
+    }
+    if days == 0 || foods == 0 {
+        anyhow::bail!("Give at least one day and one food");
+    }
+
+    let mut rng = Rng(seed);
+    let foods = generate_foods(&mut rng, foods)?;
+    // Protein-dense foods anchor meals; everything else goes alongside
+    let (mains, sides): (Vec<&Food>, Vec<&Food>) = foods
+        .iter()
+        .partition(|f| f.calories > 0.0 && f.protein * 4.0 / f.calories >= 0.3);
+    // Most meals come from a handful of favourites
+    let favourites = |list: &[&Food]| list.len().div_ceil(5).max(1);
+    let mains = if mains.is_empty() {
+        sides.clone()
+    } else {
+        mains
+    };
+    let sides = if sides.is_empty() {
+        mains.clone()
+    } else {
+        sides
+    };
+
+    let today = Local::now().date_naive();
+    let from = today - Duration::days(i64::from(days) - 1);
+    let mut weight = rng.range(65.0, 95.0);
+    let weekly_change = rng.range(-0.6, 0.2);
+
+    db.atomically(|db| {
+        let mut ids = HashMap::new();
+        for food in &foods {
+            ids.insert(&food.name, db.add_food(food)?);
+        }
+        let (mut entries, mut water, mut weigh_ins) = (0, 0, 0);
+        for offset in 0..days {
+            let date = from + Duration::days(i64::from(offset));
+            let day = date.format("%Y-%m-%d").to_string();
+            let opts = LogOptions::on_date(Some(&day));
+
+            // Some days go unlogged or half-logged, the way real ones do
+            let logged_meals = match rng.unit() {
+                p if p < 0.04 => 0,
+                p if p < 0.10 => 1,
+                _ => MEALS.len(),
+            };
+            let weekend = date.weekday().number_from_monday() >= 6;
+            let appetite = if weekend { 1.15 } else { 1.0 };
+            for (meal, window, side_count) in MEALS.iter().take(logged_meals) {
+                if *meal == "snack" && rng.chance(0.4) {
+                    continue;
+                }
+                let mut picks = Vec::new();
+                if *meal != "snack" {
+                    picks.push(pick(&mut rng, &mains, favourites(&mains)));
+                }
+                for _ in 0..1 + rng.below(*side_count) {
+                    picks.push(pick(&mut rng, &sides, favourites(&sides)));
+                }
+                let minute = window.0 + rng.below((window.1 - window.0) as usize) as u32;
+                for (i, food) in picks.into_iter().enumerate() {
+                    let amount = portion(&mut rng, food, appetite);
+                    let macros = food.calculate(&amount).ok_or_else(|| {
+                        anyhow::anyhow!("Bad amount {} for {}", amount, food.name)
+                    })?;
+                    let entry = db.log_food_with(ids[&food.name], &amount, &macros, &opts)?;
+                    let at =
+                        NaiveTime::from_num_seconds_from_midnight_opt(minute * 60 + i as u32, 0)
+                            .unwrap_or_default();
+                    if let Some(local) = Local.from_local_datetime(&date.and_time(at)).earliest() {
+                        db.set_logged_at(entry.id.unwrap_or_default(), local.naive_utc())?;
+                    }
+                    entries += 1;
+                }
+            }
+
+            if logged_meals > 0 {
+                for _ in 0..2 + rng.below(4) {
+                    db.log_water(250.0 * (1 + rng.below(3)) as f64, Some(&day))?;
+                    water += 1;
+                }
+            }
+
+            weight += weekly_change / 7.0 + rng.range(-0.25, 0.25);
+            if rng.chance(0.5) {
+                db.log_weight(round1(weight), Some(&day))?;
+                weigh_ins += 1;
+            }
+        }
+        Ok(Seeded {
+            seed,
+            foods: foods.len(),
+            entries,
+            water,
+            weigh_ins,
+            from: from.format("%Y-%m-%d").to_string(),
+            to: today.format("%Y-%m-%d").to_string(),
+        })
+    })
+}
+
+/// A favourite most of the time, anything else now and then.
+fn pick<'a>(rng: &mut Rng, list: &[&'a Food], favourites: usize) -> &'a Food {
+    if rng.chance(0.75) {
+        list[rng.below(favourites)]
+    } else {
+        list[rng.below(list.len())]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::CsvExport;
+
+    fn export(db: &Database) -> String {
+        let mut out = Vec::new();
+        db.export_csv(&mut out, &CsvExport::default()).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_seed_is_deterministic() {
+        let a = Database::open_in_memory().unwrap();
+        let b = Database::open_in_memory().unwrap();
+        let seeded = seed(&a, 30, 250, 42).unwrap();
+        seed(&b, 30, 250, 42).unwrap();
+        assert_eq!(seeded.foods, 250);
+        assert!(seeded.entries > 30 * 3, "{:?}", seeded);
+        assert_eq!(export(&a), export(&b));
+        assert_eq!(a.get_stats().unwrap().food_count, 250);
+
+        let c = Database::open_in_memory().unwrap();
+        seed(&c, 30, 250, 7).unwrap();
+        assert_ne!(export(&a), export(&c));
+
+        let err = seed(&a, 30, 250, 42).unwrap_err().to_string();
+        assert!(err.contains("already has 250 foods"), "{}", err);
+    }
+
+    #[test]
+    fn test_portion() {
+        let mut rng = Rng(1);
+        let mut food = Food::new("Rice", 2.7, 0.3, 28.0, 130.0, "100g", vec![]);
+        food.default_amount = Some("150g".to_string());
+        for _ in 0..20 {
+            let grams: f64 = portion(&mut rng, &food, 1.0)
+                .trim_end_matches('g')
+                .parse()
+                .unwrap();
+            assert!((100.0..=200.0).contains(&grams) && grams % 10.0 == 0.0);
+        }
+        let egg = Food::new("Egg", 6.3, 5.3, 0.4, 72.0, "1 egg", vec![]);
+        for _ in 0..20 {
+            assert_eq!(portion(&mut rng, &egg, 1.0), "1 egg");
+        }
+    }
+}
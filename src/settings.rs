@@ -0,0 +1,189 @@
+//! `chomp settings export/import`: the setup around the data — the config
+//! (goals, schedules, meals, program, hooks), custom units, recurring
+//! entries, and profile names — as one JSON document. Backups taken with
+//! `chomp snapshot --backup` carry a copy, so either sets up a new machine.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::db::Database;
+
+/// A recurring entry as it's set up, without its run history.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecurringSetting {
+    pub input: String,
+    /// HH:MM
+    pub time: String,
+    #[serde(default)]
+    pub auto: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Settings {
+    /// Version of chomp that exported them
+    pub chomp_version: String,
+    pub config: Config,
+    /// The user's units.toml, verbatim
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub units: Option<String>,
+    #[serde(default)]
+    pub recurring: Vec<RecurringSetting>,
+    /// Profile databases next to the main one
+    #[serde(default)]
+    pub profiles: Vec<String>,
+}
+
+/// The settings in effect: the config and units files as written, and the
+/// recurring entries in `db`.
+pub fn gather(db: &Database) -> Result<Settings> {
+    let units = match std::fs::read_to_string(crate::food::Units::path()?) {
+        Ok(text) => Some(text),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    Ok(Settings {
+        chomp_version: env!("CARGO_PKG_VERSION").to_string(),
+        config: Config::load()?,
+        units,
+        recurring: db
+            .list_recurring()?
+            .into_iter()
+            .map(|r| RecurringSetting {
+                input: r.input,
+                time: r.time,
+                auto: r.auto,
+            })
+            .collect(),
+        profiles: Database::profile_names()?,
+    })
+}
+
+/// Settings from `path`: a `chomp settings export` file, or a database
+/// backup holding a copy.
+pub fn read(path: &std::path::Path) -> Result<Settings> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?;
+    let text = if bytes.starts_with(b"SQLite format 3\0") {
+        Database::open_at(path)?.stored_settings()?.ok_or_else(|| {
+            anyhow::anyhow!(
+                "{} is a database without settings; back up with chomp snapshot --backup",
+                path.display()
+            )
+        })?
+    } else {
+        String::from_utf8(bytes)?
+    };
+    serde_json::from_str(&text)
+        .map_err(|e| anyhow::anyhow!("Invalid settings {}: {}", path.display(), e))
+}
+
+/// What `apply` changed.
+#[derive(Debug, Default, Serialize)]
+pub struct Applied {
+    /// Where the config that was replaced was moved to
+    pub config_backup: Option<std::path::PathBuf>,
+    pub units: bool,
+    pub recurring_added: usize,
+    pub profiles_created: Vec<String>,
+}
+
+/// Write `settings` to this machine: the config and units files (moving
+/// different ones aside to `.bak`), recurring entries `db` doesn't have
+/// yet, and empty databases for missing profiles.
+pub fn apply(db: &Database, settings: &Settings, today: &str) -> Result<Applied> {
+    let mut applied = Applied::default();
+    let config_path = Config::path()?;
+    let config = toml::to_string(&settings.config)?;
+    applied.config_backup = replace(&config_path, &config)?;
+
+    if let Some(units) = &settings.units {
+        crate::food::Units::parse(units).map_err(|e| anyhow::anyhow!("Invalid units: {}", e))?;
+        replace(&crate::food::Units::path()?, units)?;
+        applied.units = true;
+    }
+
+    let existing: Vec<(String, String)> = db
+        .list_recurring()?
+        .into_iter()
+        .map(|r| (r.input, r.time))
+        .collect();
+    for recurring in &settings.recurring {
+        if existing.contains(&(recurring.input.clone(), recurring.time.clone())) {
+            continue;
+        }
+        let time = crate::db::parse_time_of_day(&recurring.time)?;
+        db.add_recurring(&recurring.input, time, today, recurring.auto)?;
+        applied.recurring_added += 1;
+    }
+
+    for name in &settings.profiles {
+        let path = Database::profile_path(name)?;
+        if !path.exists() {
+            Database::open_at(&path)?.init()?;
+            applied.profiles_created.push(name.clone());
+        }
+    }
+    Ok(applied)
+}
+
+/// Write `text` to `path`, first moving a different file already there to
+/// `<path>.bak`, which is returned.
+fn replace(path: &std::path::Path, text: &str) -> Result<Option<std::path::PathBuf>> {
+    let backup = match std::fs::read_to_string(path) {
+        Ok(current) if current == text => return Ok(None),
+        Ok(_) => {
+            let mut backup = path.as_os_str().to_owned();
+            backup.push(".bak");
+            let backup = std::path::PathBuf::from(backup);
+            std::fs::rename(path, &backup)?;
+            Some(backup)
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, text)?;
+    Ok(backup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_round_trip() {
+        let config = Config::parse(
+            "meals = [\"breakfast\", \"dinner\"]\n\n\
+             [goals]\ncalories = 2000.0\nprotein = 140.0\n\n\
+             [[schedule]]\nname = \"refeed\"\nweekdays = [\"sat\"]\ncalories = 2800.0\n",
+        )
+        .unwrap();
+        let settings = Settings {
+            chomp_version: "0.1.0".to_string(),
+            config,
+            units: Some("[weight]\nscoop = 30\n".to_string()),
+            recurring: vec![RecurringSetting {
+                input: "creatine 5g".to_string(),
+                time: "08:00".to_string(),
+                auto: true,
+            }],
+            profiles: vec!["alice".to_string()],
+        };
+        let json = serde_json::to_string(&settings).unwrap();
+        let back: Settings = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.config.goals.calories, Some(2000.0));
+        assert_eq!(back.config.schedule.len(), 1);
+        assert_eq!(back.config.meals(), ["breakfast", "dinner"]);
+        assert_eq!(back.recurring, settings.recurring);
+        assert_eq!(back.units, settings.units);
+
+        // Stored in a database, as backups carry them
+        let db = Database::open_in_memory().unwrap();
+        assert!(db.stored_settings().unwrap().is_none());
+        db.store_settings(&json).unwrap();
+        assert_eq!(db.stored_settings().unwrap(), Some(json));
+    }
+}
@@ -0,0 +1,129 @@
+//! `chomp snack`: what's in the pantry that fits the rest of today's
+//! budget, most nutrient-dense first.
+
+use serde::Serialize;
+
+use crate::config::{Goals, Metric};
+use crate::food::{CarbMode, Food, Macros};
+
+/// Servings below which a cut-down snack isn't worth suggesting.
+const MIN_SHARE: f64 = 0.25;
+
+/// One suggestion: how much of a food to eat and what it holds.
+#[derive(Debug, Serialize)]
+pub struct Snack {
+    pub food: String,
+    /// Amount to log, e.g. "30g" or "1 bar"
+    pub amount: String,
+    pub macros: Macros,
+    /// Grams of protein and fiber per 100 kcal
+    pub density: f64,
+}
+
+/// Grams of protein and fiber per 100 kcal.
+pub fn density(macros: &Macros) -> f64 {
+    (macros.protein + macros.fiber) / macros.calories * 100.0
+}
+
+/// What's left of each capped goal after `totals`. Protein is a floor to
+/// reach rather than a cap, so it never rules a snack out.
+pub fn remaining(goals: &Goals, totals: &Macros) -> Vec<(Metric, f64)> {
+    [Metric::Calories, Metric::Fat, Metric::Carbs]
+        .into_iter()
+        .filter_map(|metric| Some((metric, goals.get(metric)? - metric.value(totals))))
+        .collect()
+}
+
+/// The usual amount of each of `foods` (its default amount, or a serving),
+/// cut down to fit `remaining`, best first. Foods without calories, or
+/// that don't fit even a quarter of that amount, are left out. Carbs count
+/// as `mode` counts them in totals.
+pub fn suggest(foods: &[Food], remaining: &[(Metric, f64)], mode: CarbMode) -> Vec<Snack> {
+    let mut snacks: Vec<Snack> = foods
+        .iter()
+        .filter_map(|food| {
+            let usual = food.default_amount.as_ref().unwrap_or(&food.serving);
+            let macros = food.calculate(usual)?;
+            if macros.calories <= 0.0 {
+                return None;
+            }
+            // The metric that caps the portion soonest, and how much fits
+            let (metric, share) = remaining
+                .iter()
+                .filter(|(metric, _)| metric.counted(&macros, mode) > 0.0)
+                .map(|(metric, left)| (*metric, left / metric.counted(&macros, mode)))
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .unwrap_or((Metric::Calories, 1.0));
+            if share < MIN_SHARE {
+                return None;
+            }
+            let (amount, macros) = if share >= 1.0 {
+                (usual.clone(), macros)
+            } else {
+                let left = remaining.iter().find(|(m, _)| *m == metric)?.1;
+                let fit = crate::fit::solve(food, metric, left, mode)?;
+                (fit.amount, fit.macros)
+            };
+            Some(Snack {
+                food: food.name.clone(),
+                amount,
+                density: density(&macros),
+                macros,
+            })
+        })
+        .collect();
+    snacks.sort_by(|a, b| b.density.total_cmp(&a.density).then(a.food.cmp(&b.food)));
+    snacks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suggest_fits_remaining() {
+        let goals = Goals {
+            calories: Some(2000.0),
+            fat: Some(70.0),
+            ..Default::default()
+        };
+        let totals = Macros {
+            calories: 1800.0,
+            fat: 62.0,
+            ..Default::default()
+        };
+        let left = remaining(&goals, &totals);
+        assert_eq!(left, [(Metric::Calories, 200.0), (Metric::Fat, 8.0)]);
+
+        let mut yogurt = Food::new("greek yogurt", 10.0, 0.4, 3.6, 59.0, "100g", vec![]);
+        yogurt.default_amount = Some("170g".to_string());
+        let mut almonds = Food::new("almonds", 21.0, 50.0, 22.0, 579.0, "100g", vec![]);
+        almonds.default_amount = Some("30g".to_string());
+        let bar = Food::new("protein bar", 20.0, 8.0, 24.0, 240.0, "1 bar", vec![]);
+        let chips = Food::new("chips", 7.0, 34.0, 53.0, 536.0, "100g", vec![]);
+        let coffee = Food::new("coffee", 0.3, 0.0, 0.0, 0.0, "1 cup", vec![]);
+        let snacks = suggest(
+            &[chips, almonds, coffee, bar, yogurt],
+            &left,
+            CarbMode::Total,
+        );
+        let picks: Vec<(&str, &str)> = snacks
+            .iter()
+            .map(|s| (s.food.as_str(), s.amount.as_str()))
+            .collect();
+        // Chips fit less than a quarter of a serving; the rest shrink to
+        // the fat or calories left
+        assert_eq!(
+            picks,
+            [
+                ("greek yogurt", "170g"),
+                ("protein bar", "0.8"),
+                ("almonds", "16g")
+            ]
+        );
+        assert!(snacks[0].density > snacks[1].density);
+
+        let apple = Food::new("apple", 0.3, 0.2, 14.0, 52.0, "100g", vec![]);
+        assert!(suggest(&[apple], &[(Metric::Calories, -50.0)], CarbMode::Total).is_empty());
+    }
+}
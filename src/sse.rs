@@ -1,33 +1,223 @@
 use anyhow::Result;
 use axum::{
     extract::{Path, Query, Request, State},
-    http::{header, Method, StatusCode},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
     middleware::{self, Next},
     response::{
         sse::{Event, KeepAlive},
         Html, IntoResponse, Redirect, Response, Sse,
     },
     routing::{delete, get, post, put},
-    Json, Router,
+    Form, Json, Router,
 };
-use serde::Deserialize;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
 use std::convert::Infallible;
 use std::sync::Arc;
 use tokio::sync::{mpsc, Mutex};
 use tokio_stream::wrappers::ReceiverStream;
 use tower_http::cors::{Any, CorsLayer};
 
-use crate::db::Database;
-use crate::mcp::{self, JsonRpcRequest};
+use crate::chat::Outcome;
+use crate::db::{
+    CsvExport, Database, DayClosed, DeleteMode, DuplicateLog, EntryLocked, FoodInUse, LogEntry,
+    LogOptions, Rollup, Share, ShareScope, TimeWindow,
+};
+use crate::mcp::{self, Sampler};
 
 /// Per-session sender for SSE events.
 type SessionTx = mpsc::Sender<std::result::Result<Event, Infallible>>;
 
+/// A connected MCP client and the profile database it is routed to.
+struct Session {
+    /// The stream the client is currently connected to
+    tx: SessionTx,
+    profile: Option<String>,
+    /// Client declared the sampling capability in `initialize`
+    sampling: bool,
+    events: EventLog,
+    /// When the client's stream was first seen closed
+    disconnected_at: Option<std::time::Instant>,
+    created_at: std::time::Instant,
+    /// Last connect, resume, or message from the client
+    last_active: std::time::Instant,
+    /// JSON-RPC messages received
+    messages: u64,
+}
+
+impl Session {
+    fn new(tx: SessionTx, profile: Option<String>) -> Self {
+        let now = std::time::Instant::now();
+        Session {
+            tx,
+            profile,
+            sampling: false,
+            events: EventLog::default(),
+            disconnected_at: None,
+            created_at: now,
+            last_active: now,
+            messages: 0,
+        }
+    }
+
+    /// Whether the session should be evicted: its client has been gone past
+    /// the resume window, or it has been idle past `IDLE_TIMEOUT`.
+    fn is_stale(&mut self, now: std::time::Instant) -> bool {
+        if now.duration_since(self.last_active) >= IDLE_TIMEOUT {
+            return true;
+        }
+        if !self.tx.is_closed() {
+            self.disconnected_at = None;
+            return false;
+        }
+        let since = *self.disconnected_at.get_or_insert(now);
+        now.duration_since(since) >= RESUME_WINDOW
+    }
+}
+
+/// Sessions with no activity for this long are evicted even if connected.
+const IDLE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(6 * 60 * 60);
+
+/// Events kept per session, for replay to a client that reconnects with
+/// `Last-Event-ID`.
+const REPLAY_EVENTS: usize = 64;
+
+/// How long a disconnected session can be resumed before it is dropped.
+const RESUME_WINDOW: std::time::Duration = std::time::Duration::from_secs(300);
+
+/// A session's recent events, numbered so a reconnecting client can say
+/// which one it saw last.
+#[derive(Default)]
+struct EventLog {
+    next: u64,
+    events: VecDeque<(u64, &'static str, String)>,
+}
+
+impl EventLog {
+    fn push(&mut self, name: &'static str, data: String) -> u64 {
+        self.next += 1;
+        self.events.push_back((self.next, name, data));
+        if self.events.len() > REPLAY_EVENTS {
+            self.events.pop_front();
+        }
+        self.next
+    }
+
+    /// Events after `last`, oldest first.
+    fn after(&self, last: u64) -> impl Iterator<Item = &(u64, &'static str, String)> {
+        self.events.iter().filter(move |(seq, _, _)| *seq > last)
+    }
+}
+
+/// An SSE event whose id names its session, so `Last-Event-ID` alone is
+/// enough to resume.
+fn session_event(session_id: &str, seq: u64, name: &str, data: &str) -> Event {
+    Event::default()
+        .id(format!("{}:{}", session_id, seq))
+        .event(name)
+        .data(data)
+}
+
+/// Number and buffer an event for a session, returning it with the stream to
+/// send it on. An event whose send fails waits in the buffer for the client
+/// to resume.
+fn queue_event(
+    sessions: &mut HashMap<String, Session>,
+    session_id: &str,
+    name: &'static str,
+    data: String,
+) -> Option<(SessionTx, Event)> {
+    let session = sessions.get_mut(session_id)?;
+    let event = session_event(
+        session_id,
+        session.events.push(name, data.clone()),
+        name,
+        &data,
+    );
+    Some((session.tx.clone(), event))
+}
+
+/// How long a tool waits for the client to answer a sampling request.
+const SAMPLING_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(120);
+
+/// Optional server behaviour beyond port/host/auth.
+pub struct SseOptions {
+    /// Request header whose value selects the profile database for a session.
+    pub profile_header: Option<String>,
+    /// Bearer tokens that authenticate as, and are pinned to, a profile (key -> profile).
+    pub profile_keys: HashMap<String, String>,
+    /// Interval between keep-alive comments on an idle stream.
+    pub keep_alive: std::time::Duration,
+    /// Events queued per session before sends wait on a slow client. A
+    /// resumed stream starts with a full replay, so it must hold one.
+    pub channel_capacity: usize,
+    /// Sessions allowed at once; further connections are refused.
+    pub max_sessions: Option<usize>,
+    /// How long an MCP message may run before it is cancelled and answered
+    /// with a timeout error.
+    pub request_timeout: std::time::Duration,
+}
+
+impl Default for SseOptions {
+    fn default() -> Self {
+        SseOptions {
+            profile_header: None,
+            profile_keys: HashMap::new(),
+            keep_alive: std::time::Duration::from_secs(15),
+            channel_capacity: REPLAY_EVENTS + 32,
+            max_sessions: None,
+            request_timeout: std::time::Duration::from_secs(300),
+        }
+    }
+}
+
+impl SseOptions {
+    fn check(&self) -> Result<()> {
+        if self.keep_alive.is_zero() {
+            anyhow::bail!("Keep-alive interval must be at least 1 second");
+        }
+        if self.channel_capacity <= REPLAY_EVENTS {
+            anyhow::bail!(
+                "Channel capacity must be at least {} to replay missed events",
+                REPLAY_EVENTS + 1
+            );
+        }
+        if self.max_sessions == Some(0) {
+            anyhow::bail!("Max sessions must be at least 1");
+        }
+        if self.request_timeout.is_zero() {
+            anyhow::bail!("Request timeout must be at least 1 second");
+        }
+        Ok(())
+    }
+}
+
+/// Parse `name=key,name2=key2` into a key -> profile map.
+pub fn parse_profile_keys(spec: &str) -> Result<HashMap<String, String>> {
+    let mut keys = HashMap::new();
+    for pair in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (profile, key) = pair
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("Invalid profile key '{}': expected name=key", pair))?;
+        Database::profile_path(profile.trim())?;
+        keys.insert(key.trim().to_string(), profile.trim().to_string());
+    }
+    Ok(keys)
+}
+
 /// Shared state across all handlers.
 struct AppState {
-    sessions: Mutex<HashMap<String, SessionTx>>,
+    sessions: Mutex<HashMap<String, Session>>,
     auth_key: Option<String>,
+    options: SseOptions,
+    /// Sampling requests awaiting the client's response, by JSON-RPC id
+    pending_samples: std::sync::Mutex<HashMap<String, std::sync::mpsc::Sender<serde_json::Value>>>,
+}
+
+impl AppState {
+    fn auth_enabled(&self) -> bool {
+        self.auth_key.is_some() || !self.options.profile_keys.is_empty()
+    }
 }
 
 #[derive(Deserialize)]
@@ -37,10 +227,18 @@ struct MessageQuery {
 }
 
 /// Start the SSE MCP server on the given port/host.
-pub async fn serve_sse(port: u16, host: &str, auth_key: Option<&str>) -> Result<()> {
+pub async fn serve_sse(
+    port: u16,
+    host: &str,
+    auth_key: Option<&str>,
+    options: SseOptions,
+) -> Result<()> {
+    options.check()?;
     let state = Arc::new(AppState {
         sessions: Mutex::new(HashMap::new()),
         auth_key: auth_key.map(String::from),
+        options,
+        pending_samples: std::sync::Mutex::new(HashMap::new()),
     });
 
     let cors = CorsLayer::new()
@@ -58,10 +256,13 @@ pub async fn serve_sse(port: u16, host: &str, auth_key: Option<&str>) -> Result<
     let app = Router::new()
         .route("/sse", get(sse_handler))
         .route("/message", post(message_handler))
+        .route("/sessions", get(sessions_handler))
         .route("/dashboard", get(dashboard_handler))
         .route("/api/export", get(export_handler))
         .route("/api/today", get(today_handler))
         .route("/api/log", post(log_handler))
+        .route("/quicklog", post(quicklog_handler))
+        .route("/quicklog/fav/:slug", post(quicklog_fav_handler))
         .route(
             "/api/foods",
             get(search_foods_handler).post(add_food_handler),
@@ -72,6 +273,7 @@ pub async fn serve_sse(port: u16, host: &str, auth_key: Option<&str>) -> Result<
         )
         .route("/api/history", get(history_handler))
         .route("/api/log/last", delete(delete_last_log_handler))
+        .route("/api/undo", post(undo_handler))
         .route(
             "/api/log/:id",
             delete(delete_log_handler).put(edit_log_handler),
@@ -100,19 +302,45 @@ pub async fn serve_sse(port: u16, host: &str, auth_key: Option<&str>) -> Result<
         .route("/health", get(health_handler))
         .route("/login", get(login_page_handler).post(login_handler))
         .route("/logout", post(logout_handler))
+        // Share links carry their own token
+        .route("/share/:token", get(share_handler))
         .layer(cors)
-        .with_state(state);
+        .with_state(state.clone());
 
     let addr: std::net::SocketAddr = format!("{}:{}", host, port).parse()?;
     eprintln!("chomp MCP server (SSE) listening on http://{}", addr);
-    if auth_key.is_some() {
+    if state.auth_enabled() {
         eprintln!("  Auth:          enabled (Bearer token required)");
     } else {
         eprintln!("  Auth:          disabled (use --auth-key to enable)");
     }
+    if let Some(h) = &state.options.profile_header {
+        eprintln!("  Profiles:      selected per session by the {} header", h);
+    }
+    if !state.options.profile_keys.is_empty() {
+        eprintln!(
+            "  Profiles:      {} profile key(s) configured",
+            state.options.profile_keys.len()
+        );
+    }
     eprintln!("  SSE endpoint:  http://{}/sse", addr);
     eprintln!("  POST endpoint: http://{}/message", addr);
     eprintln!("  Dashboard:     http://{}/dashboard", addr);
+    eprintln!("  Sessions:      http://{}/sessions", addr);
+    eprintln!(
+        "  Streams:       keep-alive every {}s, {} queued events per session{}",
+        state.options.keep_alive.as_secs(),
+        state.options.channel_capacity,
+        state
+            .options
+            .max_sessions
+            .map(|n| format!(", at most {} sessions", n))
+            .unwrap_or_default()
+    );
+    eprintln!(
+        "  Requests:      time out after {}s",
+        state.options.request_timeout.as_secs()
+    );
     eprintln!("  Health check:  http://{}/health", addr);
 
     let listener = tokio::net::TcpListener::bind(addr).await?;
@@ -135,6 +363,38 @@ fn get_session_cookie(request: &Request) -> Option<String> {
         })
 }
 
+/// Extract the token from an `Authorization: Bearer <token>` header.
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+}
+
+/// Pick the profile for a new session: a profile key pins the profile,
+/// otherwise the configured profile header may select one.
+fn session_profile(
+    state: &AppState,
+    headers: &HeaderMap,
+) -> std::result::Result<Option<String>, StatusCode> {
+    if let Some(profile) = bearer_token(headers).and_then(|t| state.options.profile_keys.get(t)) {
+        return Ok(Some(profile.clone()));
+    }
+    let requested = state
+        .options
+        .profile_header
+        .as_deref()
+        .and_then(|h| headers.get(h))
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|v| !v.is_empty());
+    match requested {
+        Some(name) if Database::profile_path(name).is_ok() => Ok(Some(name.to_string())),
+        Some(_) => Err(StatusCode::BAD_REQUEST),
+        None => Ok(None),
+    }
+}
+
 /// Returns true if the request looks like it came from a browser expecting HTML.
 fn is_browser_request(request: &Request) -> bool {
     request
@@ -145,25 +405,36 @@ fn is_browser_request(request: &Request) -> bool {
         .unwrap_or(false)
 }
 
+/// Routes a profile key may use; the rest serve the main database.
+const PROFILE_ROUTES: [&str; 3] = ["/sse", "/message", "/sessions"];
+
 /// Middleware that checks for a valid Bearer token or session cookie when auth is enabled.
 async fn auth_middleware(
     State(state): State<Arc<AppState>>,
     request: Request,
     next: Next,
 ) -> Response {
-    if let Some(expected_key) = &state.auth_key {
-        // Check Bearer token first
-        let bearer_ok = request
-            .headers()
-            .get("authorization")
-            .and_then(|v| v.to_str().ok())
-            .and_then(|h| h.strip_prefix("Bearer "))
-            .map(|token| token == expected_key.as_str())
-            .unwrap_or(false);
+    if state.auth_enabled() {
+        let expected_key = state.auth_key.as_deref();
+
+        // Check Bearer token first (the shared key or any profile key)
+        let token = bearer_token(request.headers());
+        let profile_key = token
+            .is_some_and(|t| Some(t) != expected_key && state.options.profile_keys.contains_key(t));
+        let bearer_ok = token.is_some_and(|t| Some(t) == expected_key) || profile_key;
+
+        // Only the MCP endpoints route to the key's profile; everything else
+        // reads and writes the main database
+        if profile_key && !PROFILE_ROUTES.contains(&request.uri().path()) {
+            return Response::builder()
+                .status(StatusCode::FORBIDDEN)
+                .body("A profile key only reaches /sse, /message, and /sessions".into())
+                .unwrap();
+        }
 
         // Then check session cookie
         let cookie_ok = get_session_cookie(&request)
-            .map(|token| token == *expected_key)
+            .map(|token| Some(token.as_str()) == expected_key)
             .unwrap_or(false);
 
         if !bearer_ok && !cookie_ok {
@@ -187,72 +458,248 @@ async fn auth_middleware(
     next.run(request).await
 }
 
-/// Helper to open DB, returning an error response on failure.
+/// An error's message, with the CLI's flag hints reworded for the API.
+fn api_error(e: &anyhow::Error) -> String {
+    if let Some(dup) = e.downcast_ref::<DuplicateLog>() {
+        format!("{}; send \"force\": true to log it again", dup.summary())
+    } else if let Some(in_use) = e.downcast_ref::<FoodInUse>() {
+        format!(
+            "{}; delete with ?mode=keep-history to keep the log entries, or ?mode=cascade to trash them along with the food",
+            in_use.summary()
+        )
+    } else if let Some(locked) = e.downcast_ref::<EntryLocked>() {
+        format!(
+            "{}; they can only be changed with chomp --amend on the command line",
+            locked.summary()
+        )
+    } else {
+        e.to_string()
+    }
+}
+
+/// Helper to open DB, returning an error response on failure. The config is
+/// re-read per request like the database itself.
 fn open_db() -> std::result::Result<Database, (StatusCode, Json<serde_json::Value>)> {
-    Database::open()
-        .and_then(|db| {
-            db.init()?;
-            Ok(db)
-        })
-        .map_err(|e| {
-            (
-                StatusCode::SERVICE_UNAVAILABLE,
-                Json(serde_json::json!({"error": format!("database error: {}", e)})),
-            )
-        })
+    crate::config::open_database(None).map_err(|e| {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({"error": format!("database error: {}", e)})),
+        )
+    })
 }
 
-/// GET /sse — client connects here, receives an SSE stream.
-async fn sse_handler(
-    State(state): State<Arc<AppState>>,
-) -> Sse<ReceiverStream<std::result::Result<Event, Infallible>>> {
-    let session_id = uuid::Uuid::new_v4().to_string();
-    let (tx, rx) = mpsc::channel(32);
+fn endpoint_event(session_id: &str) -> Event {
+    Event::default()
+        .event("endpoint")
+        .data(format!("/message?sessionId={}", session_id))
+}
 
-    let endpoint_url = format!("/message?sessionId={}", session_id);
-    let _ = tx
-        .send(Ok(Event::default().event("endpoint").data(endpoint_url)))
-        .await;
+/// GET /sse — client connects here, receives an SSE stream. A client that
+/// reconnects with `Last-Event-ID` gets its session back, with the events it
+/// missed replayed.
+async fn sse_handler(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Response {
+    let profile = match session_profile(&state, &headers) {
+        Ok(p) => p,
+        Err(status) => return (status, "Invalid profile name").into_response(),
+    };
+    let resume = headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.rsplit_once(':'))
+        .and_then(|(sid, seq)| Some((sid, seq.parse::<u64>().ok()?)));
+    if let Some((session_id, last)) = resume {
+        let mut sessions = state.sessions.lock().await;
+        if let Some(session) = sessions
+            .get_mut(session_id)
+            .filter(|s| s.profile == profile)
+        {
+            let (tx, rx) = mpsc::channel(state.options.channel_capacity);
+            // The new stream is empty and sized for a full replay, so these
+            // can't fail; holding the lock keeps live events behind them
+            let _ = tx.try_send(Ok(endpoint_event(session_id)));
+            for (seq, name, data) in session.events.after(last) {
+                let _ = tx.try_send(Ok(session_event(session_id, *seq, name, data)));
+            }
+            session.tx = tx;
+            session.disconnected_at = None;
+            session.last_active = std::time::Instant::now();
+            return event_stream(&state, rx);
+        }
+    }
 
-    let tx_clone = tx.clone();
-    state.sessions.lock().await.insert(session_id.clone(), tx);
+    let session_id = uuid::Uuid::new_v4().to_string();
+    let (tx, rx) = mpsc::channel(state.options.channel_capacity);
+    let _ = tx.send(Ok(endpoint_event(&session_id))).await;
+
+    let mut sessions = state.sessions.lock().await;
+    if let Some(max) = state
+        .options
+        .max_sessions
+        .filter(|&max| sessions.len() >= max)
+    {
+        eprintln!("Refusing SSE connection: already at {} sessions", max);
+        return (StatusCode::SERVICE_UNAVAILABLE, "Too many sessions").into_response();
+    }
+    sessions.insert(session_id.clone(), Session::new(tx, profile));
+    drop(sessions);
 
     let state_clone = state.clone();
-    let sid = session_id.clone();
     tokio::spawn(async move {
         loop {
             tokio::time::sleep(tokio::time::Duration::from_secs(30)).await;
-            if tx_clone.is_closed() {
-                state_clone.sessions.lock().await.remove(&sid);
+            let mut sessions = state_clone.sessions.lock().await;
+            let Some(session) = sessions.get_mut(&session_id) else {
+                break;
+            };
+            if session.is_stale(std::time::Instant::now()) {
+                sessions.remove(&session_id);
                 break;
             }
         }
     });
 
-    Sse::new(ReceiverStream::new(rx)).keep_alive(KeepAlive::default())
+    event_stream(&state, rx)
 }
 
-/// POST /message?sessionId=xxx — client sends JSON-RPC requests here.
+fn event_stream(
+    state: &AppState,
+    rx: mpsc::Receiver<std::result::Result<Event, Infallible>>,
+) -> Response {
+    Sse::new(ReceiverStream::new(rx))
+        .keep_alive(KeepAlive::new().interval(state.options.keep_alive))
+        .into_response()
+}
+
+/// Send an already-buffered event, saying so when the client is falling
+/// behind rather than failing quietly.
+async fn send_event(session_id: &str, tx: SessionTx, event: Event) {
+    let event = match tx.try_send(Ok(event)) {
+        Ok(()) => return,
+        Err(mpsc::error::TrySendError::Full(event)) => event,
+        Err(mpsc::error::TrySendError::Closed(_)) => {
+            eprintln!(
+                "SSE session {} is disconnected; event kept for when it resumes",
+                session_id
+            );
+            return;
+        }
+    };
+    eprintln!(
+        "SSE session {} has {} events queued; waiting for the client to catch up",
+        session_id,
+        tx.max_capacity()
+    );
+    let _ = tx.send(event).await;
+}
+
+/// GET /sessions — MCP sessions with their age, idle time, and message
+/// count. A profile key only sees its own profile's sessions.
+async fn sessions_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Json<serde_json::Value> {
+    let pinned = bearer_token(&headers).and_then(|t| state.options.profile_keys.get(t));
+    let now = std::time::Instant::now();
+    let sessions = state.sessions.lock().await;
+    let mut list: Vec<_> = sessions
+        .iter()
+        .filter(|(_, s)| pinned.is_none() || s.profile.as_ref() == pinned)
+        .collect();
+    list.sort_by_key(|(_, s)| s.created_at);
+    let list: Vec<_> = list
+        .into_iter()
+        .map(|(id, s)| {
+            serde_json::json!({
+                "id": id,
+                "profile": s.profile,
+                "connected": !s.tx.is_closed(),
+                "sampling": s.sampling,
+                "age_secs": now.duration_since(s.created_at).as_secs(),
+                "idle_secs": now.duration_since(s.last_active).as_secs(),
+                "messages": s.messages,
+            })
+        })
+        .collect();
+    Json(serde_json::json!({ "count": list.len(), "sessions": list }))
+}
+
+/// Sends sampling requests down a session's SSE stream; the client answers
+/// with a POST to /message, which `message_handler` routes back here.
+struct SseSampler {
+    session_id: String,
+    state: Arc<AppState>,
+}
+
+impl Sampler for SseSampler {
+    fn create_message(&self, params: serde_json::Value) -> Result<serde_json::Value> {
+        let id = format!("chomp-sampling-{}", uuid::Uuid::new_v4());
+        let (reply_tx, reply_rx) = std::sync::mpsc::channel();
+        self.state
+            .pending_samples
+            .lock()
+            .unwrap()
+            .insert(id.clone(), reply_tx);
+
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "sampling/createMessage",
+            "params": params
+        });
+        let queued = queue_event(
+            &mut self.state.sessions.blocking_lock(),
+            &self.session_id,
+            "message",
+            request.to_string(),
+        );
+        // A client that dropped off can still resume and answer in time
+        let reply = match queued {
+            Some((tx, event)) => {
+                tokio::runtime::Handle::current().block_on(send_event(&self.session_id, tx, event));
+                reply_rx.recv_timeout(SAMPLING_TIMEOUT).ok()
+            }
+            None => None,
+        };
+        self.state.pending_samples.lock().unwrap().remove(&id);
+        mcp::sampling_result(
+            reply.ok_or_else(|| anyhow::anyhow!("Client did not answer the sampling request"))?,
+        )
+    }
+}
+
+/// POST /message?sessionId=xxx — client sends JSON-RPC requests (singly or
+/// batched) here, and its responses to our sampling requests.
 async fn message_handler(
     State(state): State<Arc<AppState>>,
     Query(query): Query<MessageQuery>,
-    Json(request): Json<JsonRpcRequest>,
+    Json(message): Json<serde_json::Value>,
 ) -> StatusCode {
+    if mcp::is_response(&message) {
+        let id = message["id"].as_str().unwrap_or_default();
+        let reply = state.pending_samples.lock().unwrap().remove(id);
+        return match reply {
+            Some(reply) => {
+                let _ = reply.send(message);
+                StatusCode::ACCEPTED
+            }
+            None => StatusCode::NOT_FOUND,
+        };
+    }
+    // A session whose client is disconnected still takes requests; the
+    // responses wait for it to resume
     let mut sessions = state.sessions.lock().await;
-    let tx = match sessions.get(&query.session_id) {
-        Some(tx) if tx.is_closed() => {
-            sessions.remove(&query.session_id);
-            return StatusCode::NOT_FOUND;
+    let (profile, sampling) = match sessions.get_mut(&query.session_id) {
+        Some(s) => {
+            s.sampling |= mcp::client_supports_sampling(&message);
+            s.last_active = std::time::Instant::now();
+            s.messages += message.as_array().map_or(1, |batch| batch.len() as u64);
+            (s.profile.clone(), s.sampling)
         }
-        Some(tx) => tx.clone(),
         None => return StatusCode::NOT_FOUND,
     };
     drop(sessions);
 
-    let db = match Database::open().and_then(|db| {
-        db.init()?;
-        Ok(db)
-    }) {
+    let db = match crate::config::open_database(profile.as_deref()) {
         Ok(db) => db,
         Err(err) => {
             eprintln!("Database error in message_handler: {}", err);
@@ -260,7 +707,40 @@ async fn message_handler(
         }
     };
 
-    if let Some(response) = mcp::handle_request(&db, &request) {
+    // Tools may block waiting on the client (sampling), so run off the runtime
+    let sampler = sampling.then(|| SseSampler {
+        session_id: query.session_id.clone(),
+        state: state.clone(),
+    });
+    // Cleared once the handler returns, so a late timeout never interrupts
+    // anything but this request
+    let interrupt = Arc::new(std::sync::Mutex::new(Some(db.interrupt_handle())));
+    let running = interrupt.clone();
+    let timeout = state.options.request_timeout;
+    let pending = message.clone();
+    let task = tokio::task::spawn_blocking(move || {
+        let response =
+            mcp::handle_message(&db, message, sampler.as_ref().map(|s| s as &dyn Sampler));
+        running.lock().unwrap_or_else(|e| e.into_inner()).take();
+        response
+    });
+    let response = match tokio::time::timeout(timeout, task).await {
+        Ok(joined) => joined.unwrap_or_else(|e| {
+            eprintln!("MCP request handler panicked: {}", e);
+            None
+        }),
+        Err(_) => {
+            // Abort the statement it is stuck in; whatever it returns later
+            // is dropped
+            if let Some(handle) = interrupt.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+                handle.interrupt();
+            }
+            eprintln!("MCP request timed out after {}s", timeout.as_secs());
+            mcp::timeout_response(&pending, timeout)
+        }
+    };
+
+    if let Some(response) = response {
         let json = match serde_json::to_string(&response) {
             Ok(j) => j,
             Err(e) => {
@@ -269,10 +749,14 @@ async fn message_handler(
             }
         };
 
-        let event = Event::default().event("message").data(json);
-        if tx.send(Ok(event)).await.is_err() {
-            eprintln!("SSE client disconnected, could not deliver response");
-            return StatusCode::INTERNAL_SERVER_ERROR;
+        let queued = queue_event(
+            &mut *state.sessions.lock().await,
+            &query.session_id,
+            "message",
+            json,
+        );
+        if let Some((tx, event)) = queued {
+            send_event(&query.session_id, tx, event).await;
         }
     }
 
@@ -285,7 +769,7 @@ async fn dashboard_handler() -> impl IntoResponse {
     Html(html)
 }
 
-/// GET /api/export?days=N — returns CSV of log entries.
+/// GET /api/export?days=N&columns=date,food,calories — returns CSV of log entries.
 async fn export_handler(Query(params): Query<HashMap<String, String>>) -> impl IntoResponse {
     let db = match open_db() {
         Ok(db) => db,
@@ -304,35 +788,31 @@ async fn export_handler(Query(params): Query<HashMap<String, String>>) -> impl I
         .and_then(|d| d.parse().ok())
         .unwrap_or(90);
 
-    let entries = match db.get_history(days) {
-        Ok(e) => e,
-        Err(_) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                [(header::CONTENT_TYPE, "text/plain")],
-                "Export error".to_string(),
-            )
-                .into_response();
-        }
+    let Some(since) = chrono::Local::now().checked_sub_signed(chrono::Duration::days(days.into()))
+    else {
+        return (
+            StatusCode::BAD_REQUEST,
+            [(header::CONTENT_TYPE, "text/plain")],
+            format!("days={} reaches back further than dates go", days),
+        )
+            .into_response();
+    };
+    let mut export = CsvExport {
+        since: Some(since.format("%Y-%m-%d").to_string()),
+        ..Default::default()
     };
+    if let Some(columns) = params.get("columns") {
+        export.columns = columns.split(',').map(|c| c.trim().to_string()).collect();
+    }
 
-    let mut csv = String::from("date,food,amount,protein,fat,carbs,calories\n");
-    for e in &entries {
-        // Quote food_name and amount since they may contain commas
-        let food_quoted = if e.food_name.contains(',') {
-            format!("\"{}\"", e.food_name.replace('"', "\"\""))
-        } else {
-            e.food_name.clone()
-        };
-        let amount_quoted = if e.amount.contains(',') {
-            format!("\"{}\"", e.amount.replace('"', "\"\""))
-        } else {
-            e.amount.clone()
-        };
-        csv.push_str(&format!(
-            "{},{},{},{:.1},{:.1},{:.1},{:.0}\n",
-            e.date, food_quoted, amount_quoted, e.protein, e.fat, e.carbs, e.calories
-        ));
+    let mut csv = Vec::new();
+    if let Err(e) = db.export_csv(&mut csv, &export) {
+        return (
+            StatusCode::BAD_REQUEST,
+            [(header::CONTENT_TYPE, "text/plain")],
+            e.to_string(),
+        )
+            .into_response();
     }
 
     (StatusCode::OK, [(header::CONTENT_TYPE, "text/csv")], csv).into_response()
@@ -345,6 +825,7 @@ async fn today_handler() -> impl IntoResponse {
         Err(e) => return e.into_response(),
     };
 
+    crate::recurring::roll_over_now(&db);
     let totals = db.get_today_totals().unwrap_or_default();
     let entries = db.get_today_entries().unwrap_or_default();
     let water = db.get_today_water().unwrap_or_default();
@@ -365,6 +846,33 @@ async fn today_handler() -> impl IntoResponse {
 struct LogRequest {
     food: String,
     date: Option<String>,
+    /// Time eaten, e.g. "08:30"
+    time: Option<String>,
+    /// Meal to log under, e.g. "breakfast"
+    meal: Option<String>,
+    #[serde(default)]
+    force: bool,
+    /// Log a duplicate anyway, with a warning, instead of a 409
+    #[serde(default)]
+    warn_duplicates: bool,
+    #[serde(default)]
+    strict: bool,
+}
+
+impl LogRequest {
+    /// The time and meal given, checked against the config's meals.
+    fn time_and_meal(&self) -> anyhow::Result<(Option<chrono::NaiveTime>, Option<String>)> {
+        let time = self
+            .time
+            .as_deref()
+            .map(crate::db::parse_time_of_day)
+            .transpose()?;
+        let meal = match &self.meal {
+            Some(meal) => Some(crate::config::Config::load()?.meal(meal)?),
+            None => None,
+        };
+        Ok((time, meal))
+    }
 }
 
 /// POST /api/log — parse and log food.
@@ -374,8 +882,37 @@ async fn log_handler(Json(body): Json<LogRequest>) -> impl IntoResponse {
         Err(e) => return e.into_response(),
     };
 
-    match crate::logging::parse_and_log(&db, &body.food, body.date.as_deref()) {
-        Ok(entry) => (StatusCode::CREATED, Json(serde_json::json!(entry))).into_response(),
+    let (time, meal) = match body.time_and_meal() {
+        Ok(checked) => checked,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({"error": e.to_string()})),
+            )
+                .into_response()
+        }
+    };
+    let opts = LogOptions {
+        date: body.date.as_deref(),
+        time,
+        meal: meal.as_deref(),
+        dedupe_window: (!body.force).then(crate::logging::default_dedupe_window),
+        warn_duplicates: body.warn_duplicates,
+        strict: body.strict,
+        ..Default::default()
+    };
+    crate::recurring::roll_over_now(&db);
+    let before = crate::hooks::before_log(&db);
+    match crate::logging::parse_and_log_with(&db, &body.food, &opts) {
+        Ok(entry) => {
+            crate::hooks::after_log(&db, &before);
+            (StatusCode::CREATED, Json(serde_json::json!(entry))).into_response()
+        }
+        Err(e) if e.is::<DuplicateLog>() || e.is::<DayClosed>() || e.is::<EntryLocked>() => (
+            StatusCode::CONFLICT,
+            Json(serde_json::json!({"error": api_error(&e)})),
+        )
+            .into_response(),
         Err(e) => (
             StatusCode::BAD_REQUEST,
             Json(serde_json::json!({"error": e.to_string()})),
@@ -384,6 +921,59 @@ async fn log_handler(Json(body): Json<LogRequest>) -> impl IntoResponse {
     }
 }
 
+#[derive(Deserialize)]
+struct QuickLogForm {
+    text: String,
+}
+
+/// POST /quicklog — log free text like `text=2 eggs and toast` from a form
+/// post, answering in plain text for iOS Shortcuts or Tasker to show as is.
+async fn quicklog_handler(Form(form): Form<QuickLogForm>) -> Response {
+    let db = match open_db() {
+        Ok(db) => db,
+        Err(e) => return plain_error(e),
+    };
+    quicklog(&db, &form.text).into_response()
+}
+
+/// POST /quicklog/fav/:slug — log a favorite saved with `chomp fav add`, for
+/// an NFC tag or a Stream Deck button.
+async fn quicklog_fav_handler(Path(slug): Path<String>) -> Response {
+    let db = match open_db() {
+        Ok(db) => db,
+        Err(e) => return plain_error(e),
+    };
+    match db.favorite(&slug) {
+        Ok(Some(favorite)) => quicklog(&db, &favorite.input).into_response(),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            format!("No favorite '{}'; add one with: chomp fav add", slug),
+        )
+            .into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+/// A JSON error from `open_db` as plain text.
+fn plain_error((status, Json(body)): (StatusCode, Json<serde_json::Value>)) -> Response {
+    let message = body["error"].as_str().unwrap_or_default().to_string();
+    (status, message).into_response()
+}
+
+/// Log each food in `text`, one confirmation (or complaint) per line and
+/// today's totals at the end.
+pub(crate) fn quicklog(db: &Database, text: &str) -> (StatusCode, String) {
+    crate::recurring::roll_over_now(db);
+    let reply = crate::chat::log_text(db, text);
+    let status = match reply.outcome {
+        Outcome::Logged => StatusCode::CREATED,
+        Outcome::Conflict => StatusCode::CONFLICT,
+        Outcome::Failed => StatusCode::BAD_REQUEST,
+        Outcome::Error => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, reply.text)
+}
+
 #[derive(Deserialize)]
 struct SearchQuery {
     #[serde(rename = "q")]
@@ -413,18 +1003,19 @@ struct AddFoodRequest {
     protein: f64,
     fat: f64,
     carbs: f64,
-    #[serde(default = "default_serving")]
-    per: String,
+    /// Defaults to 100g, or the food's category serving
+    per: Option<String>,
     calories: Option<f64>,
     #[serde(default)]
+    fiber: f64,
+    #[serde(flatten)]
+    micros: crate::food::Micros,
+    #[serde(default)]
     aliases: Vec<String>,
 }
 
-fn default_serving() -> String {
-    "100g".to_string()
-}
-
-/// POST /api/foods — add a new food.
+/// POST /api/foods — add a new food. The response lists, under `clashes`,
+/// names and aliases another food already answers to.
 async fn add_food_handler(Json(body): Json<AddFoodRequest>) -> impl IntoResponse {
     let db = match open_db() {
         Ok(db) => db,
@@ -433,19 +1024,31 @@ async fn add_food_handler(Json(body): Json<AddFoodRequest>) -> impl IntoResponse
 
     let cals = body
         .calories
-        .unwrap_or(body.protein * 4.0 + body.fat * 9.0 + body.carbs * 4.0);
-    let food = crate::food::Food::new(
+        .unwrap_or_else(|| db.calories_from_macros(body.protein, body.fat, body.carbs, body.fiber));
+    let mut food = crate::food::Food::new(
         &body.name,
         body.protein,
         body.fat,
         body.carbs,
         cals,
-        &body.per,
+        body.per.as_deref().unwrap_or("100g"),
         body.aliases,
     );
+    food.fiber = body.fiber;
+    food.micros = body.micros;
+    if body.per.is_none() {
+        food.assume_serving();
+    }
 
-    match db.add_food(&food) {
-        Ok(_) => (StatusCode::CREATED, Json(serde_json::json!(food))).into_response(),
+    let added = db
+        .name_clashes(&food.name, &food.aliases, None)
+        .and_then(|clashes| db.add_food(&food).map(|_| clashes));
+    match added {
+        Ok(clashes) => {
+            let mut value = serde_json::json!(food);
+            value["clashes"] = serde_json::json!(clashes);
+            (StatusCode::CREATED, Json(value)).into_response()
+        }
         Err(e) => (
             StatusCode::BAD_REQUEST,
             Json(serde_json::json!({"error": e.to_string()})),
@@ -459,8 +1062,11 @@ struct EditFoodRequest {
     protein: Option<f64>,
     fat: Option<f64>,
     carbs: Option<f64>,
+    fiber: Option<f64>,
     per: Option<String>,
     calories: Option<f64>,
+    #[serde(flatten)]
+    micros: crate::food::Micros,
 }
 
 /// PUT /api/foods/:name — edit a food.
@@ -478,10 +1084,12 @@ async fn edit_food_handler(
         body.protein,
         body.fat,
         body.carbs,
+        body.fiber,
         body.per.as_deref(),
         body.calories,
+        &body.micros,
     ) {
-        Ok(()) => {
+        Ok(_) => {
             let food = db.search_food(&name).ok().flatten();
             Json(serde_json::json!(food)).into_response()
         }
@@ -493,15 +1101,29 @@ async fn edit_food_handler(
     }
 }
 
-/// DELETE /api/foods/:name — delete a food.
-async fn delete_food_handler(Path(name): Path<String>) -> impl IntoResponse {
+#[derive(Deserialize)]
+struct DeleteQuery {
+    mode: Option<DeleteMode>,
+}
+
+/// DELETE /api/foods/:name?mode=keep-history|cascade — delete a food. Without
+/// a mode, a food that's still logged or in a compound food is a conflict.
+async fn delete_food_handler(
+    Path(name): Path<String>,
+    Query(params): Query<DeleteQuery>,
+) -> impl IntoResponse {
     let db = match open_db() {
         Ok(db) => db,
         Err(e) => return e.into_response(),
     };
 
-    match db.delete_food(&name) {
+    match db.delete_food(&name, params.mode.unwrap_or_default()) {
         Ok(()) => Json(serde_json::json!({"deleted": name})).into_response(),
+        Err(e) if e.is::<FoodInUse>() => (
+            StatusCode::CONFLICT,
+            Json(serde_json::json!({"error": api_error(&e)})),
+        )
+            .into_response(),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(serde_json::json!({"error": e.to_string()})),
@@ -513,9 +1135,16 @@ async fn delete_food_handler(Path(name): Path<String>) -> impl IntoResponse {
 #[derive(Deserialize)]
 struct HistoryQuery {
     days: Option<u32>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+    after: Option<String>,
+    before: Option<String>,
 }
 
-/// GET /api/history?days=N — get history.
+/// GET /api/history?days=N&limit=L&offset=O&after=HH:MM&before=HH:MM — get
+/// history, newest first, optionally only entries logged within a time of
+/// day. When more entries follow, the X-Next-Offset header gives the next
+/// offset.
 async fn history_handler(Query(params): Query<HistoryQuery>) -> impl IntoResponse {
     let db = match open_db() {
         Ok(db) => db,
@@ -523,8 +1152,26 @@ async fn history_handler(Query(params): Query<HistoryQuery>) -> impl IntoRespons
     };
 
     let days = params.days.unwrap_or(7);
-    match db.get_history(days) {
-        Ok(entries) => Json(serde_json::json!(entries)).into_response(),
+    let window = match TimeWindow::parse(params.after.as_deref(), params.before.as_deref()) {
+        Ok(window) => window,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({"error": e.to_string()})),
+            )
+                .into_response()
+        }
+    };
+    match db.get_history_page(days, window, params.limit, params.offset.unwrap_or(0)) {
+        Ok(page) => {
+            let mut response = Json(serde_json::json!(page.entries)).into_response();
+            if let Some(next) = page.next_offset {
+                response
+                    .headers_mut()
+                    .insert("x-next-offset", HeaderValue::from(next));
+            }
+            response
+        }
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(serde_json::json!({"error": e.to_string()})),
@@ -542,6 +1189,11 @@ async fn delete_log_handler(Path(id): Path<i64>) -> impl IntoResponse {
 
     match db.delete_log_entry(id) {
         Ok(entry) => Json(serde_json::json!(entry)).into_response(),
+        Err(e) if e.is::<DayClosed>() || e.is::<EntryLocked>() => (
+            StatusCode::CONFLICT,
+            Json(serde_json::json!({"error": api_error(&e)})),
+        )
+            .into_response(),
         Err(e) => (
             StatusCode::NOT_FOUND,
             Json(serde_json::json!({"error": e.to_string()})),
@@ -567,6 +1219,28 @@ async fn delete_last_log_handler() -> impl IntoResponse {
     }
 }
 
+#[derive(Deserialize)]
+struct UndoQuery {
+    window: Option<u32>,
+}
+
+/// POST /api/undo?window=10 — revert the most recent change within the window.
+async fn undo_handler(Query(params): Query<UndoQuery>) -> impl IntoResponse {
+    let db = match open_db() {
+        Ok(db) => db,
+        Err(e) => return e.into_response(),
+    };
+
+    match db.undo_last(params.window.unwrap_or(10)) {
+        Ok(undone) => Json(serde_json::json!(undone)).into_response(),
+        Err(e) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": e.to_string()})),
+        )
+            .into_response(),
+    }
+}
+
 #[derive(Deserialize)]
 struct EditLogRequest {
     amount: Option<String>,
@@ -589,7 +1263,7 @@ async fn edit_log_handler(
         Ok(entry) => Json(serde_json::json!(entry)).into_response(),
         Err(e) => (
             StatusCode::BAD_REQUEST,
-            Json(serde_json::json!({"error": e.to_string()})),
+            Json(serde_json::json!({"error": api_error(&e)})),
         )
             .into_response(),
     }
@@ -813,6 +1487,161 @@ async fn stats_handler() -> impl IntoResponse {
     }
 }
 
+#[derive(Deserialize)]
+struct ShareQuery {
+    format: Option<String>,
+}
+
+/// GET /share/:token?format=json — the read-only view behind a share link,
+/// as a page or as JSON. Unknown, revoked, and expired tokens are a 404.
+async fn share_handler(Path(token): Path<String>, Query(params): Query<ShareQuery>) -> Response {
+    let db = match open_db() {
+        Ok(db) => db,
+        Err(e) => return e.into_response(),
+    };
+    let share =
+        match db.share(&token) {
+            Ok(Some(share)) if share.is_active() => share,
+            Ok(_) => return (
+                StatusCode::NOT_FOUND,
+                Json(serde_json::json!({"error": "This share link doesn't exist, was revoked, or has expired"})),
+            )
+                .into_response(),
+            Err(e) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(serde_json::json!({"error": e.to_string()})),
+                )
+                    .into_response()
+            }
+        };
+
+    let view = match ShareView::load(&db, &share) {
+        Ok(view) => view,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"error": e.to_string()})),
+            )
+                .into_response()
+        }
+    };
+
+    if params.format.as_deref() == Some("json") {
+        return Json(view).into_response();
+    }
+    Html(view.page()).into_response()
+}
+
+/// What a share link shows: the log entries and daily totals its scopes allow.
+#[derive(Serialize)]
+struct ShareView {
+    since: String,
+    days: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    log: Option<Vec<LogEntry>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reports: Option<Vec<Rollup>>,
+}
+
+impl ShareView {
+    fn load(db: &Database, share: &Share) -> Result<Self> {
+        let since = (chrono::Local::now() - chrono::Duration::days(share.days.into()))
+            .format("%Y-%m-%d")
+            .to_string();
+        let log = if share.scopes.contains(&ShareScope::Log) {
+            Some(db.get_history(share.days)?)
+        } else {
+            None
+        };
+        let reports = if share.scopes.contains(&ShareScope::Reports) {
+            Some(db.rollups(&since, "day")?)
+        } else {
+            None
+        };
+        Ok(Self {
+            since,
+            days: share.days,
+            log,
+            reports,
+        })
+    }
+
+    /// A plain page of tables.
+    fn page(&self) -> String {
+        let mut sections = Vec::new();
+        if let Some(reports) = &self.reports {
+            let rows = reports
+                .iter()
+                .rev()
+                .map(|r| {
+                    vec![
+                        r.period.clone(),
+                        format!("{:.0}", r.calories),
+                        format!("{:.0}", r.protein),
+                        format!("{:.0}", r.fat),
+                        format!("{:.0}", r.carbs),
+                    ]
+                })
+                .collect();
+            sections.push(format!(
+                "<h2>Daily totals</h2>{}",
+                html_table(&["Date", "kcal", "Protein", "Fat", "Carbs"], rows)
+            ));
+        }
+        if let Some(log) = &self.log {
+            let rows = log
+                .iter()
+                .map(|e| {
+                    vec![
+                        e.date.clone(),
+                        e.food_name.clone(),
+                        e.amount.clone(),
+                        format!("{:.0}", e.calories),
+                        format!("{:.0}", e.protein),
+                    ]
+                })
+                .collect();
+            sections.push(format!(
+                "<h2>Log</h2>{}",
+                html_table(&["Date", "Food", "Amount", "kcal", "Protein"], rows)
+            ));
+        }
+        format!(
+            "<!doctype html><html><head><meta charset=\"utf-8\"><title>chomp</title>\
+             <meta name=\"viewport\" content=\"width=device-width\">\
+             <style>body{{font-family:sans-serif;margin:2em}}table{{border-collapse:collapse}}\
+             td,th{{padding:.3em .8em;border-bottom:1px solid #ddd;text-align:left}}</style>\
+             </head><body><h1>chomp</h1><p>Read-only, since {} ({} days)</p>{}</body></html>",
+            self.since,
+            self.days,
+            sections.join("")
+        )
+    }
+}
+
+fn html_table(headers: &[&str], rows: Vec<Vec<String>>) -> String {
+    let head: String = headers.iter().map(|h| format!("<th>{}</th>", h)).collect();
+    let body: String = rows
+        .iter()
+        .map(|row| {
+            let cells: String = row
+                .iter()
+                .map(|c| format!("<td>{}</td>", escape_html(c)))
+                .collect();
+            format!("<tr>{}</tr>", cells)
+        })
+        .collect();
+    format!("<table><tr>{}</tr>{}</table>", head, body)
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
 /// GET /api/backup — download the SQLite database file.
 async fn backup_handler() -> impl IntoResponse {
     let db_path = match Database::db_path() {
@@ -912,3 +1741,70 @@ async fn health_handler() -> Json<serde_json::Value> {
         "version": env!("CARGO_PKG_VERSION")
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_log_replays_after_last_seen() {
+        let mut log = EventLog::default();
+        for i in 0..REPLAY_EVENTS + 3 {
+            log.push("message", i.to_string());
+        }
+        let missed: Vec<u64> = log.after(REPLAY_EVENTS as u64).map(|e| e.0).collect();
+        assert_eq!(missed, [65, 66, 67]);
+        // Only the newest REPLAY_EVENTS are kept
+        assert_eq!(log.after(0).count(), REPLAY_EVENTS);
+        assert_eq!(log.after(0).next().unwrap().0, 4);
+    }
+
+    #[test]
+    fn test_api_errors_drop_cli_flags() {
+        let in_use = anyhow::Error::new(FoodInUse {
+            name: "Ribeye".to_string(),
+            log_entries: 2,
+            compounds: vec![],
+        });
+        assert_eq!(
+            api_error(&in_use),
+            "'Ribeye' is used by 2 log entries; delete with ?mode=keep-history to keep the log entries, or ?mode=cascade to trash them along with the food"
+        );
+        let locked = anyhow::Error::new(EntryLocked {
+            date: "2024-01-01".to_string(),
+            lock_after_days: 7,
+        });
+        assert!(!api_error(&locked).contains("pass --amend"));
+    }
+
+    #[test]
+    fn test_options_check() {
+        assert!(SseOptions::default().check().is_ok());
+        let small = SseOptions {
+            channel_capacity: REPLAY_EVENTS,
+            ..Default::default()
+        };
+        assert!(small.check().is_err());
+        let no_keep_alive = SseOptions {
+            keep_alive: std::time::Duration::ZERO,
+            ..Default::default()
+        };
+        assert!(no_keep_alive.check().is_err());
+    }
+
+    #[test]
+    fn test_session_eviction() {
+        let minute = std::time::Duration::from_secs(60);
+        let (tx, rx) = mpsc::channel(1);
+        let mut session = Session::new(tx, None);
+        let start = session.last_active;
+        assert!(!session.is_stale(start + minute));
+        assert!(session.is_stale(start + IDLE_TIMEOUT));
+
+        // A dropped client can resume within the window
+        drop(rx);
+        assert!(!session.is_stale(start + minute));
+        assert!(!session.is_stale(start + minute + RESUME_WINDOW - minute));
+        assert!(session.is_stale(start + minute + RESUME_WINDOW));
+    }
+}
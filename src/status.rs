@@ -0,0 +1,146 @@
+use anyhow::Result;
+
+use crate::config::Config;
+use crate::food::Macros;
+
+/// Optional daily targets shown next to the running totals.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Targets {
+    pub calories: Option<f64>,
+    pub protein: Option<f64>,
+    /// Water in ml
+    pub water: Option<f64>,
+}
+
+impl Targets {
+    /// Targets from CHOMP_CALORIE_TARGET / CHOMP_PROTEIN_TARGET /
+    /// CHOMP_WATER_TARGET, falling back to the goals saved in the config.
+    pub fn resolve(config: &Config) -> Self {
+        let var = |name: &str| std::env::var(name).ok().and_then(|v| v.parse().ok());
+        Self {
+            calories: var("CHOMP_CALORIE_TARGET").or(config.goals.calories),
+            protein: var("CHOMP_PROTEIN_TARGET").or(config.goals.protein),
+            water: var("CHOMP_WATER_TARGET").or(config.goals.water),
+        }
+    }
+
+    /// Fill any target not given explicitly from the config goals.
+    pub fn or_config(self, config: &Config) -> Self {
+        Self {
+            calories: self.calories.or(config.goals.calories),
+            protein: self.protein.or(config.goals.protein),
+            water: self.water.or(config.goals.water),
+        }
+    }
+}
+
+/// Build the compact summary, e.g. "1430/2200 kcal · P 92/150".
+pub fn summary_line(totals: &Macros, targets: &Targets) -> String {
+    let kcal = match targets.calories {
+        Some(t) => format!("{:.0}/{:.0} kcal", totals.calories, t),
+        None => format!("{:.0} kcal", totals.calories),
+    };
+    let protein = match targets.protein {
+        Some(t) => format!("P {:.0}/{:.0}", totals.protein, t),
+        None => format!("P {:.0}", totals.protein),
+    };
+    format!("{} · {}", kcal, protein)
+}
+
+fn over_calories(totals: &Macros, targets: &Targets) -> bool {
+    targets.calories.is_some_and(|t| totals.calories > t)
+}
+
+/// Render the status line for the given status bar format.
+/// Supported: plain, waybar (JSON), polybar, tmux.
+pub fn render(format: &str, totals: &Macros, targets: &Targets) -> Result<String> {
+    let line = summary_line(totals, targets);
+    let over = over_calories(totals, targets);
+
+    match format {
+        "plain" => Ok(line),
+        "waybar" => {
+            let tooltip = format!(
+                "Protein {:.0}g · Fat {:.0}g · Carbs {:.0}g",
+                totals.protein, totals.fat, totals.carbs
+            );
+            let mut value = serde_json::json!({
+                "text": line,
+                "tooltip": tooltip,
+                "class": if over { "over" } else { "ok" },
+            });
+            if let Some(t) = targets.calories.filter(|t| *t > 0.0) {
+                value["percentage"] = serde_json::json!((totals.calories / t * 100.0).round());
+            }
+            Ok(value.to_string())
+        }
+        "polybar" => Ok(if over {
+            format!("%{{F#e06c75}}{}%{{F-}}", line)
+        } else {
+            line
+        }),
+        "tmux" => Ok(if over {
+            format!("#[fg=red]{}#[default]", line)
+        } else {
+            line
+        }),
+        _ => anyhow::bail!(
+            "Unknown status format: {}. Use plain, waybar, polybar, or tmux.",
+            format
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn totals(calories: f64, protein: f64) -> Macros {
+        Macros {
+            protein,
+            fat: 40.0,
+            carbs: 120.0,
+            calories,
+            fiber: 0.0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_summary_line_with_targets() {
+        let t = Targets {
+            calories: Some(2200.0),
+            protein: Some(150.0),
+            water: None,
+        };
+        assert_eq!(
+            summary_line(&totals(1430.0, 92.0), &t),
+            "1430/2200 kcal · P 92/150"
+        );
+        assert_eq!(
+            summary_line(&totals(1430.0, 92.0), &Targets::default()),
+            "1430 kcal · P 92"
+        );
+    }
+
+    #[test]
+    fn test_render_formats() {
+        let t = Targets {
+            calories: Some(2000.0),
+            protein: None,
+            water: None,
+        };
+        let waybar: serde_json::Value =
+            serde_json::from_str(&render("waybar", &totals(1000.0, 50.0), &t).unwrap()).unwrap();
+        assert_eq!(waybar["class"], "ok");
+        assert_eq!(waybar["percentage"], 50.0);
+
+        let tmux = render("tmux", &totals(2500.0, 50.0), &t).unwrap();
+        assert!(tmux.starts_with("#[fg=red]"));
+
+        let polybar = render("polybar", &totals(1000.0, 50.0), &t).unwrap();
+        assert_eq!(polybar, "1000/2000 kcal · P 50");
+
+        assert!(render("i3", &totals(0.0, 0.0), &t).is_err());
+    }
+}
@@ -0,0 +1,84 @@
+//! Fuzzy name matching, shared by food lookups and "did you mean" hints.
+
+/// Edit distance over chars, counting a swap of neighbours ("lgo" for
+/// "log") as one edit, like any other typo.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // d[i][j]: distance between the first i chars of a and the first j of b
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    d[0] = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        d[i][0] = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+/// Edit distance scaled to 0..1, where 1 is identical.
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let longest = a.chars().count().max(b.chars().count());
+    if longest == 0 {
+        return 1.0;
+    }
+    1.0 - edit_distance(a, b) as f64 / longest as f64
+}
+
+/// How close a query is to a candidate name: whole-string similarity, or
+/// (slightly discounted) how well each query word matches some word of the
+/// candidate, so "salmn" still finds "smoked salmon".
+pub fn name_similarity(query: &str, candidate: &str) -> f64 {
+    let words: Vec<&str> = candidate.split_whitespace().collect();
+    let query_words: Vec<&str> = query.split_whitespace().collect();
+    let per_word = query_words
+        .iter()
+        .map(|q| words.iter().map(|w| similarity(q, w)).fold(0.0, f64::max))
+        .sum::<f64>()
+        / query_words.len().max(1) as f64;
+    similarity(query, candidate).max(0.9 * per_word)
+}
+
+/// The `limit` best-scoring items at or above `min`, best first; ties keep
+/// their original order.
+pub fn top<T>(scored: impl IntoIterator<Item = (f64, T)>, min: f64, limit: usize) -> Vec<T> {
+    let mut scored: Vec<(f64, T)> = scored.into_iter().filter(|(s, _)| *s >= min).collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scored
+        .into_iter()
+        .take(limit)
+        .map(|(_, item)| item)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("ribeye", "ribeye"), 0);
+        assert_eq!(edit_distance("ribye", "ribeye"), 1);
+        assert_eq!(edit_distance("lgo", "log"), 1);
+        assert_eq!(edit_distance("histroy", "history"), 1);
+        assert_eq!(edit_distance("", "oats"), 4);
+    }
+
+    #[test]
+    fn test_top_ranks_and_cuts() {
+        let names = ["Chicken Breast", "Chicken Thigh", "Ribeye", "Chickpeas"];
+        let scored = names
+            .iter()
+            .map(|n| (name_similarity("chiken", &n.to_lowercase()), *n));
+        let best = top(scored, 0.6, 3);
+        assert_eq!(best[..2], ["Chicken Breast", "Chicken Thigh"]);
+        assert!(!best.contains(&"Ribeye"));
+    }
+}
@@ -0,0 +1,298 @@
+//! `chomp import transcript`: meals from timestamped voice notes, e.g.
+//! Whisper output with one "09:12 two eggs and coffee" line per note.
+
+use anyhow::Result;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+
+use crate::db::{Database, LogEntry, LogOptions};
+
+/// One voice note: when it was spoken and what was said.
+#[derive(Debug, PartialEq)]
+pub struct Note {
+    pub line: usize,
+    pub at: NaiveDateTime,
+    pub text: String,
+}
+
+/// Notes in a transcript, with days starting on `start`. A line holding
+/// only a date (or starting with one) moves to that day; otherwise a time
+/// earlier than the note before it is taken to be past midnight. Lines
+/// without a time continue the note above them.
+pub fn parse(text: &str, start: NaiveDate) -> Result<Vec<Note>> {
+    let mut notes: Vec<Note> = Vec::new();
+    let mut day = start;
+    let mut last: Option<NaiveTime> = None;
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (date, rest) = leading_date(line);
+        if let Some(date) = date {
+            day = date;
+            last = None;
+        }
+        let rest = rest.trim_start();
+        if rest.is_empty() {
+            continue;
+        }
+        match leading_time(rest) {
+            Some((time, text)) => {
+                if date.is_none() && last.is_some_and(|last| time < last) {
+                    day = day.succ_opt().unwrap_or(day);
+                }
+                last = Some(time);
+                notes.push(Note {
+                    line: i + 1,
+                    at: day.and_time(time),
+                    text: text.to_string(),
+                });
+            }
+            None => match notes.last_mut() {
+                Some(note) if date.is_none() => {
+                    note.text.push('\n');
+                    note.text.push_str(rest);
+                }
+                _ => anyhow::bail!(
+                    "Line {} has no time; expected e.g. \"09:12 two eggs and coffee\"",
+                    i + 1
+                ),
+            },
+        }
+    }
+    Ok(notes)
+}
+
+/// A YYYY-MM-DD at the start of `line`, and what follows it.
+fn leading_date(line: &str) -> (Option<NaiveDate>, &str) {
+    let Some(prefix) = line.get(..10) else {
+        return (None, line);
+    };
+    match NaiveDate::parse_from_str(prefix, "%Y-%m-%d") {
+        Ok(date) => {
+            let rest = &line[10..];
+            (Some(date), rest.strip_prefix('T').unwrap_or(rest))
+        }
+        Err(_) => (None, line),
+    }
+}
+
+/// A time like "09:12", "9:12:30", "[09:12]", or "9:12pm" at the start of
+/// `line`, and the words after it.
+fn leading_time(line: &str) -> Option<(NaiveTime, &str)> {
+    let line = line.strip_prefix('[').unwrap_or(line);
+    let end = line
+        .find(|c: char| !(c.is_ascii_digit() || c == ':'))
+        .unwrap_or(line.len());
+    let clock = &line[..end];
+    let mut rest = &line[end..];
+    let mut time = NaiveTime::parse_from_str(clock, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(clock, "%H:%M"))
+        .ok()?;
+    let lower = rest.trim_start().to_lowercase();
+    for (suffix, pm) in [("am", false), ("pm", true), ("a.m.", false), ("p.m.", true)] {
+        // Only a word of its own: "8:10 americano" is an americano at 08:10
+        let ends = lower.strip_prefix(suffix).map(|after| {
+            after
+                .chars()
+                .next()
+                .is_none_or(|c| c.is_whitespace() || matches!(c, ']' | '-' | '–' | ':'))
+        });
+        if ends == Some(true) {
+            time = time.with_hour(time.hour() % 12 + if pm { 12 } else { 0 })?;
+            rest = &rest.trim_start()[suffix.len()..];
+            break;
+        }
+    }
+    let rest = rest.strip_prefix(']').unwrap_or(rest);
+    let rest = rest
+        .trim_start()
+        .trim_start_matches(['-', '–', ':'])
+        .trim_start();
+    Some((time, rest))
+}
+
+/// Filler a voice note tends to open with, dropped before logging.
+const FILLERS: [&str; 6] = [
+    "i just had ",
+    "i just ate ",
+    "i had ",
+    "i ate ",
+    "had ",
+    "ate ",
+];
+
+/// Number words a leading amount may be spoken as.
+const NUMBERS: [&str; 12] = [
+    "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten", "eleven",
+    "twelve",
+];
+
+/// Turn what was said into what `chomp log` takes: opening filler ("I had")
+/// goes, and each sentence becomes its own line.
+pub fn loggable(text: &str) -> String {
+    let mut text = text.trim().to_string();
+    let lower = text.to_lowercase();
+    if let Some(filler) = FILLERS.iter().find(|f| lower.starts_with(*f)) {
+        text = text[filler.len()..].to_string();
+    }
+    text.replace(". ", "\n")
+        .replace("? ", "\n")
+        .replace("! ", "\n")
+        .lines()
+        .map(|line| line.trim().trim_end_matches(['.', '!', '?']))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A leading spoken amount as a number, e.g. "two eggs" → "2 eggs".
+fn spoken_amount(item: &str) -> String {
+    let lower = item.to_lowercase();
+    for (prefix, amount) in [("half a ", "0.5"), ("half an ", "0.5")] {
+        if lower.starts_with(prefix) {
+            return format!("{} {}", amount, &item[prefix.len()..]);
+        }
+    }
+    let Some((first, rest)) = item.split_once(' ') else {
+        return item.to_string();
+    };
+    let first = first.to_lowercase();
+    if first == "a" || first == "an" {
+        return rest.to_string();
+    }
+    match NUMBERS.iter().position(|n| *n == first) {
+        Some(i) => format!("{} {}", i + 1, rest),
+        None => item.to_string(),
+    }
+}
+
+/// What became of one food in a note.
+pub struct Imported {
+    /// Line of the note it came from
+    pub note: usize,
+    pub at: NaiveDateTime,
+    pub item: String,
+    pub entry: Result<LogEntry>,
+}
+
+/// Log every food in `notes`, each dated and timed as its note. Entries are
+/// keyed by note time and text, so importing the same transcript again
+/// doesn't log anything twice.
+pub fn import(db: &Database, notes: &[Note]) -> Result<Vec<Imported>> {
+    let mut imported = Vec::new();
+    for note in notes {
+        let date = note.at.format("%Y-%m-%d").to_string();
+        let items = crate::logging::split_items(db, &loggable(&note.text))?;
+        for (i, item) in items.iter().enumerate() {
+            let item = spoken_amount(item);
+            let key = format!("transcript:{}:{}:{}", note.at, i, item);
+            let opts = LogOptions {
+                date: Some(&date),
+                idempotency_key: Some(&key),
+                time: Some(note.at.time()),
+                ..Default::default()
+            };
+            let entry = crate::logging::parse_and_log_with(db, &item, &opts);
+            imported.push(Imported {
+                note: note.line,
+                at: note.at,
+                item,
+                entry,
+            });
+        }
+    }
+    Ok(imported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(date: &str, time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(&format!("{} {}", date, time), "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_parse_transcript() {
+        let start = NaiveDate::from_ymd_opt(2026, 10, 14).unwrap();
+        let text = "09:12 two eggs and coffee\n\
+                    [12:30] Chicken salad,\n  \
+                    and an apple.\n\
+                    7:45pm - salmon 6oz\n\
+                    00:20 a glass of milk\n\
+                    \n\
+                    2026-10-20\n\
+                    08:05:40 oatmeal\n\
+                    2026-10-21 13:00 soup";
+        let notes = parse(text, start).unwrap();
+        let times: Vec<NaiveDateTime> = notes.iter().map(|n| n.at).collect();
+        assert_eq!(
+            times,
+            [
+                at("2026-10-14", "09:12"),
+                at("2026-10-14", "12:30"),
+                at("2026-10-14", "19:45"),
+                at("2026-10-15", "00:20"),
+                at("2026-10-20", "08:05") + chrono::Duration::seconds(40),
+                at("2026-10-21", "13:00"),
+            ]
+        );
+        assert_eq!(notes[1].text, "Chicken salad,\nand an apple.");
+        assert_eq!(notes[1].line, 2);
+        assert_eq!(notes[2].text, "salmon 6oz");
+
+        assert!(parse("two eggs\n09:12 coffee", start).is_err());
+    }
+
+    #[test]
+    fn test_food_starting_with_am_is_not_a_time_suffix() {
+        let db = Database::open_in_memory().unwrap();
+        let americano = crate::food::Food::new("americano", 0.3, 0.0, 0.0, 5.0, "1", vec![]);
+        db.add_food(&americano).unwrap();
+        let start = NaiveDate::from_ymd_opt(2026, 10, 14).unwrap();
+        let notes = parse(
+            "20:10 americano\n7:30 amaranth porridge\n9:00am] oats",
+            start,
+        )
+        .unwrap();
+        assert_eq!(notes[0].at, at("2026-10-14", "20:10"));
+        assert_eq!(notes[0].text, "americano");
+        assert_eq!(notes[1].text, "amaranth porridge");
+        assert_eq!(notes[2].text, "oats");
+
+        let imported = import(&db, &notes[..1]).unwrap();
+        assert_eq!(imported[0].entry.as_ref().unwrap().food_name, "americano");
+    }
+
+    #[test]
+    fn test_spoken_text() {
+        assert_eq!(loggable("I had two eggs. Coffee."), "two eggs\nCoffee");
+        assert_eq!(loggable("oats 1.5 cups"), "oats 1.5 cups");
+        assert_eq!(spoken_amount("Two eggs"), "2 eggs");
+        assert_eq!(spoken_amount("half a bagel"), "0.5 bagel");
+        assert_eq!(spoken_amount("an apple"), "apple");
+        assert_eq!(spoken_amount("tenderloin"), "tenderloin");
+    }
+
+    #[test]
+    fn test_import_is_idempotent() {
+        let db = Database::open_in_memory().unwrap();
+        let eggs = crate::food::Food::new("eggs", 6.0, 5.0, 0.5, 70.0, "1", vec![]);
+        db.add_food(&eggs).unwrap();
+        let start = NaiveDate::from_ymd_opt(2026, 10, 14).unwrap();
+        let notes = parse("09:12 I had two eggs and toast", start).unwrap();
+
+        let first = import(&db, &notes).unwrap();
+        assert_eq!(first.len(), 2);
+        let entry = first[0].entry.as_ref().unwrap();
+        assert_eq!(
+            (entry.date.as_str(), entry.amount.as_str()),
+            ("2026-10-14", "2")
+        );
+        assert!(first[1].entry.is_err());
+
+        let again = import(&db, &notes).unwrap();
+        assert_eq!(again[0].entry.as_ref().unwrap().id, entry.id);
+    }
+}
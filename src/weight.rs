@@ -0,0 +1,161 @@
+//! `chomp weight --trend`: weigh-ins smoothed into a moving average, day by
+//! day beside what was eaten, so intake can be read against the scale.
+
+use anyhow::Result;
+use chrono::{Duration, NaiveDate};
+use serde::Serialize;
+
+use crate::db::{Database, WeightEntry};
+
+/// Days of weigh-ins each day's average spans, itself included.
+pub const WINDOW: i64 = 7;
+
+/// One day of the trend.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct TrendDay {
+    pub date: String,
+    /// That day's weigh-in (the last, if there were several)
+    pub kg: Option<f64>,
+    /// Mean of the weigh-ins over the `WINDOW` days ending that day; None
+    /// until the first weigh-in
+    pub average: Option<f64>,
+    /// Calories logged that day
+    pub calories: Option<f64>,
+}
+
+/// The trend from `since` through `today`, reading weigh-ins from a window
+/// before `since` so its first days are averaged like the rest.
+pub fn trend(db: &Database, since: NaiveDate, today: NaiveDate) -> Result<Vec<TrendDay>> {
+    let weights = db.get_weight_history(&(since - Duration::days(WINDOW - 1)).to_string())?;
+    let calories: Vec<(String, f64)> = db
+        .rollups(&since.to_string(), "day")?
+        .into_iter()
+        .map(|r| (r.period, r.calories))
+        .collect();
+    Ok(smooth(&weights, &calories, since, today))
+}
+
+/// Days from `since` through `today` with a weigh-in, a moving average, or
+/// calories logged; see `trend`.
+pub fn smooth(
+    weights: &[WeightEntry],
+    calories: &[(String, f64)],
+    since: NaiveDate,
+    today: NaiveDate,
+) -> Vec<TrendDay> {
+    let points: Vec<(NaiveDate, f64)> = weights
+        .iter()
+        .filter_map(|w| Some((NaiveDate::parse_from_str(&w.date, "%Y-%m-%d").ok()?, w.kg)))
+        .collect();
+    since
+        .iter_days()
+        .take_while(|day| *day <= today)
+        .filter_map(|day| {
+            let date = day.format("%Y-%m-%d").to_string();
+            let window: Vec<f64> = points
+                .iter()
+                .filter(|(d, _)| *d <= day && *d > day - Duration::days(WINDOW))
+                .map(|(_, kg)| *kg)
+                .collect();
+            let average =
+                (!window.is_empty()).then(|| window.iter().sum::<f64>() / window.len() as f64);
+            let kg = points
+                .iter()
+                .rev()
+                .find(|(d, _)| *d == day)
+                .map(|(_, kg)| *kg);
+            let calories = calories.iter().find(|(d, _)| *d == date).map(|(_, c)| *c);
+            (kg.is_some() || average.is_some() || calories.is_some()).then_some(TrendDay {
+                date,
+                kg,
+                average,
+                calories,
+            })
+        })
+        .collect()
+}
+
+/// One line per day: weigh-in, average, and calories, dashes where missing.
+pub fn render(days: &[TrendDay]) -> String {
+    let or_dash = |value: Option<String>| value.unwrap_or_else(|| "—".to_string());
+    let mut lines = vec![format!(
+        "{:<10}  {:>7}  {:>7}  {:>6}",
+        "Date",
+        "Weight",
+        format!("{}d avg", WINDOW),
+        "kcal"
+    )];
+    for day in days {
+        lines.push(format!(
+            "{:<10}  {:>7}  {:>7}  {:>6}",
+            day.date,
+            or_dash(day.kg.map(|kg| format!("{:.1}kg", kg))),
+            or_dash(day.average.map(|kg| format!("{:.1}kg", kg))),
+            or_dash(day.calories.map(|c| format!("{:.0}", c))),
+        ));
+    }
+    if let (Some(first), Some(last)) = (
+        days.iter().find_map(|d| d.average),
+        days.iter().rev().find_map(|d| d.average),
+    ) {
+        let logged: Vec<f64> = days.iter().filter_map(|d| d.calories).collect();
+        let mut summary = format!("Average {:+.1}kg over the period", last - first);
+        if !logged.is_empty() {
+            summary.push_str(&format!(
+                " on {:.0} kcal/day",
+                logged.iter().sum::<f64>() / logged.len() as f64
+            ));
+        }
+        lines.push(String::new());
+        lines.push(summary);
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn weigh_in(date: &str, kg: f64) -> WeightEntry {
+        WeightEntry {
+            id: None,
+            date: date.to_string(),
+            kg,
+        }
+    }
+
+    #[test]
+    fn test_moving_average() {
+        let weights = [
+            weigh_in("2026-10-01", 84.0),
+            weigh_in("2026-10-05", 83.0),
+            weigh_in("2026-10-08", 82.0),
+            weigh_in("2026-10-08", 81.6),
+        ];
+        let calories = [("2026-10-06".to_string(), 2100.0)];
+        let days = smooth(&weights, &calories, date("2026-10-05"), date("2026-10-09"));
+        let rows: Vec<_> = days
+            .iter()
+            .map(|d| {
+                let average = d.average.map(|kg| (kg * 10.0).round() / 10.0);
+                (d.date.as_str(), d.kg, average, d.calories)
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                ("2026-10-05", Some(83.0), Some(83.5), None),
+                ("2026-10-06", None, Some(83.5), Some(2100.0)),
+                ("2026-10-07", None, Some(83.5), None),
+                // 10-01 falls out of the window; both 10-08 weigh-ins count
+                ("2026-10-08", Some(81.6), Some(82.2), None),
+                ("2026-10-09", None, Some(82.2), None),
+            ]
+        );
+        assert!(smooth(&[], &[], date("2026-10-05"), date("2026-10-09")).is_empty());
+    }
+}
@@ -0,0 +1,243 @@
+//! Snapshot tests of the CLI, run as a subprocess against a throwaway
+//! database. When output changes on purpose, accept the new snapshots with
+//! `cargo insta review` (or `INSTA_UPDATE=always cargo test --test cli`).
+
+use assert_cmd::Command;
+use insta::assert_snapshot;
+
+/// A temp database and config that nothing else touches.
+struct Env {
+    dir: tempfile::TempDir,
+    today: String,
+}
+
+impl Env {
+    fn new() -> Env {
+        Env {
+            dir: tempfile::tempdir().unwrap(),
+            today: chrono::Local::now().format("%Y-%m-%d").to_string(),
+        }
+    }
+
+    /// Two foods and two entries logged today.
+    fn seeded() -> Env {
+        let env = Env::new();
+        env.run(&[
+            "add",
+            "Chicken Breast",
+            "-p",
+            "31",
+            "-f",
+            "3.6",
+            "-c",
+            "0",
+            "-a",
+            "chicken",
+        ]);
+        env.run(&[
+            "add",
+            "White Rice",
+            "-p",
+            "2.7",
+            "-f",
+            "0.3",
+            "-c",
+            "28",
+            "-a",
+            "rice",
+        ]);
+        env.run(&["chicken", "200g"]);
+        env.run(&["rice", "1.5", "cups"]);
+        env
+    }
+
+    fn chomp(&self, args: &[&str]) -> Command {
+        let mut cmd = Command::cargo_bin("chomp").unwrap();
+        cmd.args(args)
+            .env("CHOMP_DB_PATH", self.dir.path().join("foods.db"))
+            .env("CHOMP_CONFIG_PATH", self.dir.path().join("config.toml"))
+            .env("CHOMP_UNITS_PATH", self.dir.path().join("units.toml"))
+            .env("CHOMP_LOCALE", "en")
+            .env("NO_COLOR", "1")
+            .env("RUST_BACKTRACE", "0")
+            .env_remove("CHOMP_SERVER_URL")
+            .env_remove("CHOMP_CALORIE_TARGET")
+            .env_remove("CHOMP_PROTEIN_TARGET")
+            .env_remove("CHOMP_STRICT")
+            .env_remove("CHOMP_STRICT_UNITS")
+            .env_remove("CHOMP_DEDUPE_WINDOW")
+            .env_remove("CHOMP_LOCK_AFTER_DAYS");
+        cmd
+    }
+
+    /// Stdout of a command that must succeed, with today's date masked.
+    fn run(&self, args: &[&str]) -> String {
+        let out = self.chomp(args).assert().success().get_output().clone();
+        self.mask(&out.stdout)
+    }
+
+    /// Stderr of a command that must fail.
+    fn fail(&self, args: &[&str]) -> String {
+        let out = self.chomp(args).assert().failure().get_output().clone();
+        self.mask(&out.stderr)
+    }
+
+    fn mask(&self, output: &[u8]) -> String {
+        String::from_utf8_lossy(output).replace(&self.today, "[today]")
+    }
+}
+
+#[test]
+fn test_add_and_log() {
+    let env = Env::new();
+    let mut out = env.run(&[
+        "add",
+        "Greek Yogurt",
+        "-p",
+        "10",
+        "-f",
+        "0.4",
+        "-c",
+        "3.6",
+        "-a",
+        "yogurt",
+    ]);
+    out += &env.run(&["yogurt", "170g"]);
+    out += &env.run(&["--date", "2024-03-05", "greek", "yogurt", "1", "cup"]);
+    out += &env.run(&["--json", "--force", "yogurt", "50g"]);
+    assert_snapshot!(out);
+}
+
+#[test]
+fn test_duplicate_log_warns() {
+    let env = Env::new();
+    env.run(&["add", "Oats", "-p", "13", "-f", "7", "-c", "68"]);
+    env.run(&["oats", "50g"]);
+    let out = env.chomp(&["oats", "50g"]).assert().success();
+    let stderr = env.mask(&out.get_output().stderr);
+    assert!(
+        stderr.contains("Warning: 50g Oats was already logged"),
+        "{}",
+        stderr
+    );
+    assert_eq!(
+        env.run(&["history", "--json"]).matches("\"50g\"").count(),
+        2
+    );
+}
+
+#[test]
+fn test_today() {
+    let env = Env::seeded();
+    assert_snapshot!(env.run(&["today"]));
+    assert_snapshot!("today_json", env.run(&["today", "--json"]));
+}
+
+#[test]
+fn test_history() {
+    let env = Env::seeded();
+    assert_snapshot!(env.run(&["history"]));
+    assert_snapshot!("history_json", env.run(&["history", "--json"]));
+}
+
+#[test]
+fn test_search() {
+    let env = Env::seeded();
+    assert_snapshot!(env.run(&["search", "rice"]));
+    assert_snapshot!("search_json", env.run(&["search", "chick", "--json"]));
+}
+
+#[test]
+fn test_export() {
+    let env = Env::seeded();
+    assert_snapshot!("export_csv", env.run(&["export"]));
+    assert_snapshot!(
+        "export_csv_columns",
+        env.run(&[
+            "export",
+            "--columns",
+            "food,grams,calories",
+            "--delimiter",
+            "tab"
+        ])
+    );
+    assert_snapshot!("export_json", env.run(&["export", "--format", "json"]));
+}
+
+#[test]
+fn test_redo() {
+    let env = Env::seeded();
+    let mut out = env.run(&["redo"]);
+    out += &env.run(&["redo", "150g"]);
+    out += &env.run(&["!!"]);
+    assert_snapshot!(out);
+    let list = env.run(&["redo", "--list"]);
+    assert!(list.trim_end().ends_with("chomp rice 150g"), "{}", list);
+    env.run(&["today"]);
+    assert_snapshot!("redo_not_a_log", env.fail(&["redo", "100g"]));
+}
+
+#[test]
+fn test_log_prefix() {
+    let env = Env::seeded();
+    env.run(&[
+        "add",
+        "Log Cabin Syrup",
+        "-p",
+        "0",
+        "-f",
+        "0",
+        "-c",
+        "13",
+        "--per",
+        "15ml",
+    ]);
+    let out = env.run(&["log", "cabin", "syrup", "30ml"]);
+    assert!(out.contains("Log Cabin Syrup"), "{}", out);
+    let out = env.run(&["log", "chicken", "100g"]);
+    assert!(out.contains("Chicken Breast"), "{}", out);
+}
+
+#[test]
+fn test_log_from_file() {
+    let env = Env::seeded();
+    let meals = env.dir.path().join("meals.txt");
+    std::fs::write(
+        &meals,
+        "# Lunch\n- chicken 150g\n2) rice 1 cup, chicken 50g\n\n",
+    )
+    .unwrap();
+    let path = meals.to_str().unwrap();
+    assert_snapshot!(env.run(&["log", "--from-file", path]));
+
+    std::fs::write(&meals, "- [ ] frobnicate 100g\n- chicken 10g\n").unwrap();
+    assert_snapshot!(
+        "log_from_file_errors",
+        env.fail(&["log", "--from-file", path])
+    );
+}
+
+#[test]
+fn test_errors() {
+    let env = Env::seeded();
+    assert_snapshot!("unknown_food", env.fail(&["frobnicate", "100g"]));
+    assert_snapshot!(
+        "strict_misspelling",
+        env.fail(&["--strict", "chiken", "100g"])
+    );
+    assert_snapshot!("bad_argument", env.fail(&["history", "--days", "soon"]));
+    assert_snapshot!("unknown_command", env.fail(&["histroy"]));
+    assert_snapshot!("unknown_flag", env.fail(&["history", "--dyas", "3"]));
+    assert_snapshot!("unknown_unit", env.fail(&["chicken", "1", "packet"]));
+    assert_snapshot!(
+        "strict_units",
+        env.fail(&["--strict-units", "chicken", "1", "pack"])
+    );
+    assert_snapshot!(
+        "bad_export_format",
+        env.fail(&["export", "--format", "xml"])
+    );
+    assert!(env
+        .fail(&["adherence", "--days", "4000000000"])
+        .contains("reaches back further than dates go"));
+}
@@ -0,0 +1,130 @@
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+
+use chomp::db::{Database, LogOptions};
+use chomp::food::Food;
+use chomp::logging::parse_and_log_with;
+
+const WRITERS: usize = 4;
+const WRITES_PER_WRITER: usize = 25;
+
+fn seed(path: &Path) {
+    let db = Database::open_at(path).unwrap();
+    db.init().unwrap();
+    db.add_food(&Food::new("Rice", 2.7, 0.3, 28.0, 130.0, "100g", vec![]))
+        .unwrap();
+}
+
+fn log_count(path: &Path) -> i64 {
+    let db = Database::open_at(path).unwrap();
+    db.get_stats().unwrap().log_count
+}
+
+#[test]
+fn test_concurrent_connections_do_not_lose_writes() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = Arc::new(dir.path().join("foods.db"));
+    seed(&path);
+
+    let handles: Vec<_> = (0..WRITERS)
+        .map(|_| {
+            let path = path.clone();
+            thread::spawn(move || {
+                let db = Database::open_at(&path).unwrap();
+                for _ in 0..WRITES_PER_WRITER {
+                    parse_and_log_with(&db, "rice 100g", &LogOptions::default()).unwrap();
+                    db.log_water(250.0, None).unwrap();
+                }
+            })
+        })
+        .collect();
+    for h in handles {
+        h.join().unwrap();
+    }
+
+    assert_eq!(log_count(&path), (WRITERS * WRITES_PER_WRITER) as i64);
+}
+
+/// Run the server and several CLI processes against the same database file.
+#[cfg(feature = "sse")]
+#[test]
+fn test_server_and_cli_write_concurrently() {
+    use std::process::Command;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("foods.db");
+    seed(&path);
+
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let mut server = Command::new(env!("CARGO_BIN_EXE_chomp"))
+        .args(["serve", "--transport", "sse", "--port", &port.to_string()])
+        .env("CHOMP_DB_PATH", &path)
+        .env("CHOMP_DEDUPE_WINDOW", "0")
+        .env_remove("CHOMP_AUTH_KEY")
+        .env_remove("CHOMP_SERVER_URL")
+        .spawn()
+        .unwrap();
+
+    let base = format!("http://127.0.0.1:{}", port);
+    let client = reqwest::blocking::Client::new();
+    let mut ready = false;
+    for _ in 0..50 {
+        if client.get(format!("{}/health", base)).send().is_ok() {
+            ready = true;
+            break;
+        }
+        thread::sleep(std::time::Duration::from_millis(100));
+    }
+    assert!(ready, "server did not start");
+
+    let http_writer = {
+        let base = base.clone();
+        thread::spawn(move || {
+            let client = reqwest::blocking::Client::new();
+            for _ in 0..WRITES_PER_WRITER {
+                let resp = client
+                    .post(format!("{}/api/log", base))
+                    .json(&serde_json::json!({"food": "rice 100g"}))
+                    .send()
+                    .unwrap();
+                assert!(resp.status().is_success(), "{:?}", resp.text());
+            }
+        })
+    };
+
+    let cli_writers: Vec<_> = (0..2)
+        .map(|_| {
+            let path = path.clone();
+            thread::spawn(move || {
+                for _ in 0..WRITES_PER_WRITER {
+                    let out = Command::new(env!("CARGO_BIN_EXE_chomp"))
+                        .args(["rice", "100g"])
+                        .env("CHOMP_DB_PATH", &path)
+                        .env("CHOMP_DEDUPE_WINDOW", "0")
+                        .env_remove("CHOMP_SERVER_URL")
+                        .output()
+                        .unwrap();
+                    assert!(
+                        out.status.success(),
+                        "{}",
+                        String::from_utf8_lossy(&out.stderr)
+                    );
+                }
+            })
+        })
+        .collect();
+
+    http_writer.join().unwrap();
+    for h in cli_writers {
+        h.join().unwrap();
+    }
+    server.kill().unwrap();
+    let _ = server.wait();
+
+    assert_eq!(log_count(&path), (3 * WRITES_PER_WRITER) as i64);
+}
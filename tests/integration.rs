@@ -1,6 +1,6 @@
-use chomp::db::Database;
+use chomp::db::{Database, LogOptions};
 use chomp::food::Food;
-use chomp::logging::parse_and_log;
+use chomp::logging::parse_and_log_with;
 
 #[test]
 fn test_full_workflow() {
@@ -19,8 +19,8 @@ fn test_full_workflow() {
     let food_id = db.add_food(&food).unwrap();
     assert!(food_id > 0);
 
-    // Log it via parse_and_log
-    let entry = parse_and_log(&db, "ribeye 8oz", None).unwrap();
+    // Log it via parse_and_log_with
+    let entry = parse_and_log_with(&db, "ribeye 8oz", &LogOptions::default()).unwrap();
     assert_eq!(entry.food_name, "Ribeye");
     assert!(entry.calories > 0.0);
 
@@ -35,7 +35,7 @@ fn test_full_workflow() {
     assert_eq!(history[0].food_name, "Ribeye");
 
     // Log via alias
-    let entry2 = parse_and_log(&db, "steak 200g", None).unwrap();
+    let entry2 = parse_and_log_with(&db, "steak 200g", &LogOptions::default()).unwrap();
     assert_eq!(entry2.food_name, "Ribeye");
 
     // Totals should have both
@@ -46,6 +46,6 @@ fn test_full_workflow() {
 #[test]
 fn test_food_not_found() {
     let db = Database::open_in_memory().unwrap();
-    let result = parse_and_log(&db, "nonexistent 100g", None);
+    let result = parse_and_log_with(&db, "nonexistent 100g", &LogOptions::default());
     assert!(result.is_err());
 }
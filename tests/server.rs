@@ -0,0 +1,435 @@
+//! End to end tests of the SSE server: the binary is started on a free port
+//! and driven the way an MCP client would, over a live event stream.
+#![cfg(feature = "sse")]
+
+use std::collections::BTreeSet;
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+
+use chomp::db::Database;
+use serde_json::{json, Value};
+
+/// How long to wait for any one event before failing.
+const EVENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A running `chomp serve --transport sse`, killed on drop.
+struct Server {
+    child: Child,
+    base: String,
+    _dir: tempfile::TempDir,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+async fn start(args: &[&str]) -> Server {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("foods.db");
+    Database::open_at(&path).unwrap().init().unwrap();
+
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let child = Command::new(env!("CARGO_BIN_EXE_chomp"))
+        .args(["serve", "--transport", "sse", "--port", &port.to_string()])
+        .args(args)
+        .env("CHOMP_DB_PATH", &path)
+        .env("CHOMP_CONFIG_PATH", dir.path().join("config.toml"))
+        .env("CHOMP_DEDUPE_WINDOW", "0")
+        .env_remove("CHOMP_AUTH_KEY")
+        .env_remove("CHOMP_PROFILE_KEYS")
+        .env_remove("CHOMP_SERVER_URL")
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let server = Server {
+        child,
+        base: format!("http://127.0.0.1:{}", port),
+        _dir: dir,
+    };
+
+    for _ in 0..50 {
+        if reqwest::get(format!("{}/health", server.base))
+            .await
+            .is_ok()
+        {
+            return server;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("server did not start");
+}
+
+#[derive(Debug)]
+struct SseEvent {
+    id: Option<String>,
+    event: String,
+    data: String,
+}
+
+/// One MCP session: the open event stream and the URL its messages go to.
+struct Client {
+    http: reqwest::Client,
+    base: String,
+    stream: reqwest::Response,
+    buffer: String,
+    session_id: String,
+    next_id: u64,
+}
+
+impl Client {
+    async fn connect(server: &Server) -> Client {
+        Self::connect_with(server, None).await
+    }
+
+    async fn connect_with(server: &Server, last_event_id: Option<&str>) -> Client {
+        let http = reqwest::Client::new();
+        let mut request = http.get(format!("{}/sse", server.base));
+        if let Some(id) = last_event_id {
+            request = request.header("Last-Event-ID", id);
+        }
+        let stream = request.send().await.unwrap();
+        assert_eq!(stream.status(), 200);
+        let mut client = Client {
+            http,
+            base: server.base.clone(),
+            stream,
+            buffer: String::new(),
+            session_id: String::new(),
+            next_id: 0,
+        };
+        let endpoint = client.next_event().await;
+        assert_eq!(endpoint.event, "endpoint");
+        client.session_id = endpoint
+            .data
+            .strip_prefix("/message?sessionId=")
+            .unwrap()
+            .to_string();
+        client
+    }
+
+    /// The next event on the stream, skipping keep-alive comments.
+    async fn next_event(&mut self) -> SseEvent {
+        loop {
+            if let Some(end) = self.buffer.find("\n\n") {
+                let frame: String = self.buffer.drain(..end + 2).collect();
+                let mut event = SseEvent {
+                    id: None,
+                    event: "message".to_string(),
+                    data: String::new(),
+                };
+                for line in frame.lines() {
+                    if let Some(id) = line.strip_prefix("id: ") {
+                        event.id = Some(id.to_string());
+                    } else if let Some(name) = line.strip_prefix("event: ") {
+                        event.event = name.to_string();
+                    } else if let Some(data) = line.strip_prefix("data: ") {
+                        event.data.push_str(data);
+                    }
+                }
+                if !event.data.is_empty() {
+                    return event;
+                }
+                continue;
+            }
+            let chunk = tokio::time::timeout(EVENT_TIMEOUT, self.stream.chunk())
+                .await
+                .expect("timed out waiting for an event")
+                .unwrap()
+                .expect("stream closed");
+            self.buffer.push_str(&String::from_utf8_lossy(&chunk));
+        }
+    }
+
+    async fn post(&self, message: Value) -> reqwest::StatusCode {
+        self.http
+            .post(format!(
+                "{}/message?sessionId={}",
+                self.base, self.session_id
+            ))
+            .json(&message)
+            .send()
+            .await
+            .unwrap()
+            .status()
+    }
+
+    /// Send a request and wait for the response with its id.
+    async fn request(&mut self, method: &str, params: Value) -> Value {
+        self.next_id += 1;
+        let id = self.next_id;
+        let status = self
+            .post(json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params}))
+            .await;
+        assert_eq!(status, 202);
+        loop {
+            let event = self.next_event().await;
+            let message: Value = serde_json::from_str(&event.data).unwrap();
+            if message["id"] == id {
+                return message;
+            }
+        }
+    }
+
+    async fn initialize(&mut self) -> Value {
+        let response = self
+            .request(
+                "initialize",
+                json!({"protocolVersion": "2025-06-18", "capabilities": {}}),
+            )
+            .await;
+        let status = self
+            .post(json!({"jsonrpc": "2.0", "method": "notifications/initialized"}))
+            .await;
+        assert_eq!(status, 202);
+        response
+    }
+
+    /// Call a tool and parse the JSON in its first content block.
+    async fn call(&mut self, tool: &str, arguments: Value) -> Value {
+        let response = self
+            .request("tools/call", json!({"name": tool, "arguments": arguments}))
+            .await;
+        assert!(
+            response.get("error").is_none(),
+            "{} failed: {}",
+            tool,
+            response["error"]
+        );
+        let text = response["result"]["content"][0]["text"].as_str().unwrap();
+        serde_json::from_str(text).unwrap_or_else(|_| Value::String(text.to_string()))
+    }
+}
+
+fn tool_names(response: &Value) -> BTreeSet<String> {
+    response["result"]["tools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["name"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_handshake_and_every_tool() {
+    let server = start(&[]).await;
+    let mut client = Client::connect(&server).await;
+
+    let init = client.initialize().await;
+    assert_eq!(init["result"]["protocolVersion"], "2025-06-18");
+    assert_eq!(init["result"]["serverInfo"]["name"], "chomp");
+    let listed = tool_names(&client.request("tools/list", json!({})).await);
+
+    let mut called = BTreeSet::new();
+    macro_rules! call {
+        ($tool:literal, $args:tt) => {{
+            called.insert($tool.to_string());
+            client.call($tool, json!($args)).await
+        }};
+    }
+
+    call!("add_food", {"name": "Test Oats", "protein": 13.0, "fat": 7.0,
+                       "carbs": 68.0, "serving": "100g", "aliases": ["oats"]});
+    let entry = call!("log_food", {"food": "oats 50g"});
+    assert_eq!(entry["food_name"], "Test Oats");
+    let log_id = entry["id"].as_i64().unwrap();
+    let found = call!("search_food", {"query": "oats"});
+    assert!(found.to_string().contains("Test Oats"), "{}", found);
+    let today = call!("get_today", {});
+    assert_eq!(today["calories"], entry["calories"]);
+    let history = call!("get_history", {"days": 1});
+    assert!(history.to_string().contains("Test Oats"), "{}", history);
+
+    let edited = call!("edit_log", {"id": log_id, "amount": "100g"});
+    assert_eq!(edited["amount"], "100g");
+    call!("edit_food", {"name": "Test Oats", "protein": 14.0});
+
+    call!("log_water", {"amount": "500ml"});
+    assert_eq!(call!("get_water_today", {})["total_ml"], 500.0);
+    let water = call!("get_water_history", {"days": 7});
+    call!("unlog_water", {"id": water[0]["id"]});
+
+    call!("log_caffeine", {"amount_mg": 95.0, "source": "coffee"});
+    assert_eq!(call!("get_caffeine_today", {})["total_mg"], 95.0);
+    let caffeine = call!("get_caffeine_history", {"days": 7});
+    call!("unlog_caffeine", {"id": caffeine[0]["id"]});
+
+    call!("get_missing_days", {"days": 7});
+
+    call!("unlog", {"id": log_id});
+    call!("log_food", {"food": "oats 30g"});
+    call!("unlog_last", {});
+    call!("undo_last", {});
+    call!("delete_food", {"name": "Test Oats", "mode": "cascade"});
+
+    assert_eq!(
+        called, listed,
+        "every tool should be exercised over the server"
+    );
+}
+
+#[tokio::test]
+async fn test_errors_are_streamed_as_json_rpc_errors() {
+    let server = start(&[]).await;
+    let mut client = Client::connect(&server).await;
+    client.initialize().await;
+
+    let unknown = client.request("no/such/method", json!({})).await;
+    assert_eq!(unknown["error"]["code"], -32601);
+    let missing = client
+        .request(
+            "tools/call",
+            json!({"name": "unlog", "arguments": {"id": 999}}),
+        )
+        .await;
+    assert!(missing["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("not found"));
+
+    let status = client
+        .post(json!([
+            {"jsonrpc": "2.0", "id": "a", "method": "ping"},
+            {"jsonrpc": "2.0", "id": "b", "method": "ping"}
+        ]))
+        .await;
+    assert_eq!(status, 202);
+    let batch: Value = serde_json::from_str(&client.next_event().await.data).unwrap();
+    assert_eq!(batch.as_array().unwrap().len(), 2);
+
+    let http = reqwest::Client::new();
+    let stale = http
+        .post(format!("{}/message?sessionId=nope", server.base))
+        .json(&json!({"jsonrpc": "2.0", "id": 1, "method": "ping"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(stale.status(), 404);
+}
+
+#[tokio::test]
+async fn test_reconnect_replays_missed_responses() {
+    let server = start(&[]).await;
+    let mut client = Client::connect(&server).await;
+    client.initialize().await;
+    let seen = client.request("ping", json!({})).await;
+    assert_eq!(seen["result"], json!({}));
+
+    // Drop the stream, then send a request while disconnected
+    let session_id = client.session_id.clone();
+    let last_id = format!("{}:2", session_id);
+    drop(client.stream);
+    let http = reqwest::Client::new();
+    let status = http
+        .post(format!("{}/message?sessionId={}", server.base, session_id))
+        .json(&json!({"jsonrpc": "2.0", "id": 99, "method": "ping"}))
+        .send()
+        .await
+        .unwrap()
+        .status();
+    assert_eq!(status, 202);
+
+    let mut resumed = Client::connect_with(&server, Some(&last_id)).await;
+    assert_eq!(resumed.session_id, session_id);
+    let missed = resumed.next_event().await;
+    assert_eq!(missed.id.as_deref(), Some(&*format!("{}:3", session_id)));
+    let message: Value = serde_json::from_str(&missed.data).unwrap();
+    assert_eq!(message["id"], 99);
+
+    let sessions: Value = reqwest::get(format!("{}/sessions", server.base))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(sessions["count"], 1);
+    assert_eq!(sessions["sessions"][0]["messages"], 4);
+}
+
+#[tokio::test]
+async fn test_auth_and_session_limit() {
+    let server = start(&["--auth-key", "secret", "--max-sessions", "1"]).await;
+    let http = reqwest::Client::new();
+    let sse = |key: Option<&str>| {
+        let mut request = http.get(format!("{}/sse", server.base));
+        if let Some(key) = key {
+            request = request.bearer_auth(key);
+        }
+        request.send()
+    };
+
+    assert_eq!(sse(None).await.unwrap().status(), 401);
+    assert_eq!(sse(Some("wrong")).await.unwrap().status(), 401);
+    let first = sse(Some("secret")).await.unwrap();
+    assert_eq!(first.status(), 200);
+    assert_eq!(sse(Some("secret")).await.unwrap().status(), 503);
+}
+
+#[tokio::test]
+async fn test_profile_key_cannot_reach_main_database() {
+    let server = start(&["--auth-key", "secret", "--profile-keys", "alice=alicekey"]).await;
+    let http = reqwest::Client::new();
+    let get = |path: &str, key: &str| {
+        http.get(format!("{}{}", server.base, path))
+            .bearer_auth(key)
+            .send()
+    };
+    for path in ["/api/today", "/api/history", "/api/backup", "/dashboard"] {
+        assert_eq!(
+            get(path, "alicekey").await.unwrap().status(),
+            403,
+            "{}",
+            path
+        );
+        assert_eq!(get(path, "secret").await.unwrap().status(), 200, "{}", path);
+    }
+    let quicklog = http
+        .post(format!("{}/quicklog", server.base))
+        .bearer_auth("alicekey")
+        .body("eggs 2")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(quicklog.status(), 403);
+    assert_eq!(get("/sessions", "alicekey").await.unwrap().status(), 200);
+}
+
+#[tokio::test]
+async fn test_add_food_reports_name_clashes() {
+    let server = start(&[]).await;
+    let http = reqwest::Client::new();
+    let add = |food: Value| {
+        http.post(format!("{}/api/foods", server.base))
+            .json(&food)
+            .send()
+    };
+    let first = add(json!({"name": "Chicken", "protein": 31, "fat": 3.6, "carbs": 0}))
+        .await
+        .unwrap();
+    assert_eq!(first.status(), 201);
+    assert_eq!(first.json::<Value>().await.unwrap()["clashes"], json!([]));
+
+    let second = add(json!({
+        "name": "Chicken thigh", "protein": 26, "fat": 9, "carbs": 0, "aliases": ["chicken"]
+    }))
+    .await
+    .unwrap();
+    assert_eq!(second.status(), 201);
+    let clashes = second.json::<Value>().await.unwrap()["clashes"].clone();
+    assert_eq!(clashes[0]["name"], "chicken");
+    assert_eq!(clashes[0]["existing"], "Chicken");
+}
+
+#[tokio::test]
+async fn test_export_rejects_days_out_of_range() {
+    let server = start(&[]).await;
+    let export = |days: &str| reqwest::get(format!("{}/api/export?days={}", server.base, days));
+    assert_eq!(export("30").await.unwrap().status(), 200);
+    assert_eq!(export("4000000000").await.unwrap().status(), 400);
+}